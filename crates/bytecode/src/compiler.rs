@@ -0,0 +1,1136 @@
+// Compiles the MIR into bytecode for the stack VM. Each function gets a fixed number of local
+// slots that are allocated up front when it's called, temporaries live above them on the stack.
+// The compiler keeps track of the static type of locals where the MIR tells us what it is so
+// that field accesses can be turned into slot indexes instead of name lookups.
+
+use std::{collections::HashMap, fmt};
+
+use elp_ir::mir::{self, BinaryOp, Expr, Literal, Pattern, Stmt, StmtKind, UnaryOp};
+
+use crate::{
+    module::{Capture, Constant, FunctionProto, Module, SwitchTable, TypeInfo},
+    op::Op,
+};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct CompileError {
+    pub line: u32,
+    pub message: String,
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for CompileError {}
+
+type CompileResult<T> = Result<T, CompileError>;
+
+pub fn compile_module(ir: &mir::Module) -> CompileResult<Module> {
+    let mut compiler = ModuleCompiler::new(ir);
+    compiler.compile()?;
+
+    Ok(compiler.module)
+}
+
+#[derive(Debug)]
+struct Local {
+    name: String,
+    slot: u16,
+    // The object or enum this local holds, when we know it.
+    type_name: Option<String>,
+}
+
+#[derive(Debug)]
+struct Upvalue {
+    name: String,
+    capture: Capture,
+    type_name: Option<String>,
+}
+
+#[derive(Debug)]
+struct FunctionState {
+    proto: FunctionProto,
+    locals: Vec<Local>,
+    upvalues: Vec<Upvalue>,
+    next_slot: u16,
+    // The furthest forward jump so far, a trailing `Return` only ends the function if nothing
+    // jumps past it.
+    furthest_jump: u32,
+}
+
+impl FunctionState {
+    fn new(name: String, arity: u8) -> Self {
+        Self {
+            proto: FunctionProto {
+                name,
+                arity,
+                locals: 0,
+                captures: vec![],
+                code: vec![],
+                lines: vec![],
+                switches: vec![],
+            },
+            locals: vec![],
+            upvalues: vec![],
+            next_slot: 0,
+            furthest_jump: 0,
+        }
+    }
+
+    fn resolve_local(&self, name: &str) -> Option<&Local> {
+        self.locals.iter().rev().find(|local| local.name == name)
+    }
+}
+
+// What a name resolved to inside the function being compiled.
+enum Resolved {
+    Local(u16, Option<String>),
+    Upvalue(u16, Option<String>),
+}
+
+struct ModuleCompiler<'ir> {
+    ir: &'ir mir::Module,
+    module: Module,
+    // Functions can be overloaded by arity (`Graph.new()` and `Graph.new(nodes)`).
+    functions: HashMap<String, Vec<(u32, u8)>>,
+    frames: Vec<FunctionState>,
+    line: u32,
+}
+
+impl<'ir> ModuleCompiler<'ir> {
+    fn new(ir: &'ir mir::Module) -> Self {
+        Self {
+            ir,
+            module: Module {
+                name: ir.name.clone(),
+                ..Default::default()
+            },
+            functions: HashMap::new(),
+            frames: vec![],
+            line: 0,
+        }
+    }
+
+    fn error<T>(&self, message: impl Into<String>) -> CompileResult<T> {
+        Err(CompileError {
+            line: self.line,
+            message: message.into(),
+        })
+    }
+
+    fn compile(&mut self) -> CompileResult<()> {
+        for object in &self.ir.objects {
+            self.module.types.push(TypeInfo::Object {
+                name: object.name.clone(),
+                fields: object.fields.iter().map(|f| f.name.clone()).collect(),
+            });
+        }
+        for r#enum in &self.ir.enums {
+            self.module.types.push(TypeInfo::Enum {
+                name: r#enum.name.clone(),
+                variants: r#enum
+                    .variants
+                    .iter()
+                    .map(|variant| (variant.name.clone(), variant.payload.len() as u8))
+                    .collect(),
+            });
+        }
+
+        // Reserve every function first so that calls can refer to functions declared later on.
+        for function in &self.ir.functions {
+            let index = self.module.functions.len() as u32;
+            let arity = function.params.len() as u8;
+            let name = function.qualified_name();
+            self.module
+                .functions
+                .push(FunctionState::new(name.clone(), arity).proto);
+            self.functions.entry(name).or_default().push((index, arity));
+        }
+
+        if !self.ir.globals.is_empty() {
+            self.compile_init()?;
+        }
+
+        for (index, function) in self.ir.functions.iter().enumerate() {
+            let proto = self.compile_function(function)?;
+            self.module.functions[index] = proto;
+        }
+
+        Ok(())
+    }
+
+    fn compile_init(&mut self) -> CompileResult<()> {
+        self.frames.push(FunctionState::new("<init>".into(), 0));
+
+        for global in &self.ir.globals {
+            self.line = global.span.line;
+            self.compile_expr(&global.value)?;
+            let index = self.module.add_global(&global.name);
+            self.emit(Op::StoreGlobal(index));
+        }
+        self.emit(Op::Nil);
+        self.emit(Op::Return);
+
+        let proto = self.finish_function();
+        self.module.functions.push(proto);
+        self.module.init = Some((self.module.functions.len() - 1) as u32);
+
+        Ok(())
+    }
+
+    fn compile_function(&mut self, function: &mir::Function) -> CompileResult<FunctionProto> {
+        self.line = function.span.line;
+        self.frames.push(FunctionState::new(
+            function.qualified_name(),
+            function.params.len() as u8,
+        ));
+        for param in &function.params {
+            self.declare_local(&param.name, param.ty.named().map(String::from));
+        }
+
+        self.compile_body(&function.body)?;
+
+        Ok(self.finish_function())
+    }
+
+    fn compile_body(&mut self, body: &[Stmt]) -> CompileResult<()> {
+        for stmt in body {
+            self.compile_stmt(stmt)?;
+        }
+
+        let state = self.current();
+        let ends_in_return = state.proto.code.last() == Some(&Op::Return)
+            && (state.furthest_jump as usize) < state.proto.code.len();
+        if !ends_in_return {
+            self.emit(Op::Nil);
+            self.emit(Op::Return);
+        }
+
+        Ok(())
+    }
+
+    fn finish_function(&mut self) -> FunctionProto {
+        let state = self.frames.pop().expect("no function being compiled");
+        let mut proto = state.proto;
+        proto.locals = state.next_slot;
+        proto.captures = state.upvalues.iter().map(|up| up.capture).collect();
+
+        proto
+    }
+
+    fn current(&mut self) -> &mut FunctionState {
+        self.frames.last_mut().expect("no function being compiled")
+    }
+
+    fn emit(&mut self, op: Op) -> usize {
+        let line = self.line;
+        let state = self.current();
+        state.proto.code.push(op);
+        state.proto.lines.push(line);
+
+        state.proto.code.len() - 1
+    }
+
+    fn next_ip(&mut self) -> u32 {
+        self.current().proto.code.len() as u32
+    }
+
+    fn patch_jump(&mut self, at: usize) {
+        let target = self.next_ip();
+        let state = self.current();
+        state.furthest_jump = state.furthest_jump.max(target);
+        let op = &mut state.proto.code[at];
+        *op = match *op {
+            Op::Jump(_) => Op::Jump(target),
+            Op::JumpIfFalse(_) => Op::JumpIfFalse(target),
+            Op::JumpIfTrue(_) => Op::JumpIfTrue(target),
+            other => other,
+        };
+    }
+
+    fn constant(&mut self, constant: Constant) -> u32 {
+        self.module.add_constant(constant)
+    }
+
+    fn declare_local(&mut self, name: &str, type_name: Option<String>) -> u16 {
+        let state = self.current();
+        let slot = state.next_slot;
+        state.next_slot += 1;
+        state.locals.push(Local {
+            name: name.into(),
+            slot,
+            type_name,
+        });
+
+        slot
+    }
+
+    fn scoped<T>(&mut self, f: impl FnOnce(&mut Self) -> CompileResult<T>) -> CompileResult<T> {
+        let depth = self.current().locals.len();
+        let result = f(self);
+        self.current().locals.truncate(depth);
+
+        result
+    }
+
+    fn resolve(&mut self, name: &str) -> Option<Resolved> {
+        let depth = self.frames.len() - 1;
+        self.resolve_at(depth, name)
+    }
+
+    fn resolve_at(&mut self, depth: usize, name: &str) -> Option<Resolved> {
+        if let Some(local) = self.frames[depth].resolve_local(name) {
+            return Some(Resolved::Local(local.slot, local.type_name.clone()));
+        }
+        if let Some(index) = self.frames[depth]
+            .upvalues
+            .iter()
+            .position(|up| up.name == name)
+        {
+            let type_name = self.frames[depth].upvalues[index].type_name.clone();
+            return Some(Resolved::Upvalue(index as u16, type_name));
+        }
+        if depth == 0 {
+            return None;
+        }
+
+        // Not declared here, see whether an enclosing function has it and capture it if so.
+        let (capture, type_name) = match self.resolve_at(depth - 1, name)? {
+            Resolved::Local(slot, type_name) => (Capture::Local(slot), type_name),
+            Resolved::Upvalue(index, type_name) => (Capture::Upvalue(index), type_name),
+        };
+        let upvalues = &mut self.frames[depth].upvalues;
+        upvalues.push(Upvalue {
+            name: name.into(),
+            capture,
+            type_name: type_name.clone(),
+        });
+
+        Some(Resolved::Upvalue((upvalues.len() - 1) as u16, type_name))
+    }
+
+    fn compile_stmt(&mut self, stmt: &Stmt) -> CompileResult<()> {
+        self.line = stmt.span.line;
+
+        match &stmt.kind {
+            StmtKind::Let {
+                name, ty, value, ..
+            } => {
+                let inferred = match value {
+                    Some(value) => self.compile_expr(value)?,
+                    None => {
+                        self.emit(Op::Nil);
+                        None
+                    }
+                };
+                let type_name = ty.named().map(String::from).or(inferred);
+                let slot = self.declare_local(name, type_name);
+                self.emit(Op::StoreLocal(slot));
+            }
+            StmtKind::Assign { target, op, value } => self.compile_assign(target, *op, value)?,
+            StmtKind::Expr(expr) => {
+                self.compile_expr(expr)?;
+                self.emit(Op::Pop);
+            }
+            StmtKind::Return(value) => {
+                match value {
+                    Some(value) => {
+                        self.compile_expr(value)?;
+                    }
+                    None => {
+                        self.emit(Op::Nil);
+                    }
+                }
+                self.emit(Op::Return);
+            }
+            StmtKind::If {
+                condition,
+                then_body,
+                else_body,
+            } => {
+                self.compile_expr(condition)?;
+                let to_else = self.emit(Op::JumpIfFalse(0));
+                self.scoped(|c| then_body.iter().try_for_each(|s| c.compile_stmt(s)))?;
+                let to_end = self.emit(Op::Jump(0));
+                self.patch_jump(to_else);
+                self.scoped(|c| else_body.iter().try_for_each(|s| c.compile_stmt(s)))?;
+                self.patch_jump(to_end);
+            }
+            StmtKind::For {
+                binding,
+                iterable,
+                body,
+            } => self.scoped(|c| c.compile_for(binding, iterable, body))?,
+        }
+
+        Ok(())
+    }
+
+    fn compile_for(&mut self, binding: &str, iterable: &Expr, body: &[Stmt]) -> CompileResult<()> {
+        self.compile_expr(iterable)?;
+        let items = self.declare_local("<items>", None);
+        self.emit(Op::StoreLocal(items));
+        let zero = self.constant(Constant::Int(0));
+        self.emit(Op::Constant(zero));
+        let index = self.declare_local("<index>", None);
+        self.emit(Op::StoreLocal(index));
+        let item = self.declare_local(binding, None);
+
+        let loop_start = self.next_ip();
+        self.emit(Op::LoadLocal(index));
+        self.emit(Op::LoadLocal(items));
+        self.emit(Op::Len);
+        self.emit(Op::Lt);
+        let to_end = self.emit(Op::JumpIfFalse(0));
+        self.emit(Op::LoadLocal(items));
+        self.emit(Op::LoadLocal(index));
+        self.emit(Op::Index);
+        self.emit(Op::StoreLocal(item));
+
+        self.scoped(|c| body.iter().try_for_each(|s| c.compile_stmt(s)))?;
+
+        let one = self.constant(Constant::Int(1));
+        self.emit(Op::LoadLocal(index));
+        self.emit(Op::Constant(one));
+        self.emit(Op::Add);
+        self.emit(Op::StoreLocal(index));
+        self.emit(Op::Jump(loop_start));
+        self.patch_jump(to_end);
+
+        Ok(())
+    }
+
+    fn compile_assign(
+        &mut self,
+        target: &Expr,
+        op: Option<BinaryOp>,
+        value: &Expr,
+    ) -> CompileResult<()> {
+        match target {
+            Expr::Local(name) => match self.resolve(name) {
+                Some(Resolved::Local(slot, _)) => {
+                    if let Some(op) = op {
+                        self.emit(Op::LoadLocal(slot));
+                        self.compile_expr(value)?;
+                        self.emit(binary_op(op));
+                    } else {
+                        self.compile_expr(value)?;
+                    }
+                    self.emit(Op::StoreLocal(slot));
+                }
+                Some(Resolved::Upvalue(..)) => {
+                    return self.error(format!(
+                        "`{}` is captured by this closure and can't be assigned to",
+                        name
+                    ))
+                }
+                None => return self.compile_assign(&Expr::Global(name.clone()), op, value),
+            },
+            Expr::Global(name) => {
+                if !self.ir.globals.iter().any(|global| &global.name == name) {
+                    return self.error(format!("can't assign to `{}`", name));
+                }
+                let index = self.module.add_global(name);
+                if let Some(op) = op {
+                    self.emit(Op::LoadGlobal(index));
+                    self.compile_expr(value)?;
+                    self.emit(binary_op(op));
+                } else {
+                    self.compile_expr(value)?;
+                }
+                self.emit(Op::StoreGlobal(index));
+            }
+            Expr::Field { base, name } => {
+                let base_type = self.compile_expr(base)?;
+                let slot = self.field_slot(base_type.as_deref(), name);
+                if let Some(op) = op {
+                    self.emit(Op::Dup);
+                    self.emit_get_field(slot, name);
+                    self.compile_expr(value)?;
+                    self.emit(binary_op(op));
+                } else {
+                    self.compile_expr(value)?;
+                }
+                match slot {
+                    Some(slot) => self.emit(Op::SetField(slot)),
+                    None => {
+                        let name = self.constant(Constant::String(name.clone()));
+                        self.emit(Op::SetFieldNamed(name))
+                    }
+                };
+            }
+            _ => return self.error("can only assign to variables and fields"),
+        }
+
+        Ok(())
+    }
+
+    fn field_slot(&self, type_name: Option<&str>, field: &str) -> Option<u16> {
+        self.ir
+            .object(type_name?)?
+            .field_index(field)
+            .map(|index| index as u16)
+    }
+
+    fn field_type(&self, type_name: Option<&str>, field: &str) -> Option<String> {
+        let object = self.ir.object(type_name?)?;
+        let index = object.field_index(field)?;
+
+        object.fields[index].ty.named().map(String::from)
+    }
+
+    fn emit_get_field(&mut self, slot: Option<u16>, name: &str) {
+        match slot {
+            Some(slot) => self.emit(Op::GetField(slot)),
+            None => {
+                let name = self.constant(Constant::String(name.into()));
+                self.emit(Op::GetFieldNamed(name))
+            }
+        };
+    }
+
+    // Finds the enum and tag for `Enum.VARIANT` or a contextual `.VARIANT`.
+    fn variant(&self, enum_name: Option<&str>, variant: &str) -> Option<(u16, u16, u8)> {
+        let r#enum = match enum_name {
+            Some(name) => self.ir.r#enum(name)?,
+            None => self.ir.enum_for_variant(variant)?,
+        };
+        let tag = r#enum.variant_index(variant)?;
+        let type_index = self.module.type_index(&r#enum.name)?;
+
+        Some((
+            type_index,
+            tag as u16,
+            r#enum.variants[tag].payload.len() as u8,
+        ))
+    }
+
+    fn qualified_variant(&self, name: &str) -> Option<(u16, u16, u8)> {
+        let (enum_name, variant) = name.rsplit_once('.')?;
+        self.variant(Some(enum_name), variant)
+    }
+
+    fn emit_variant(&mut self, variant: (u16, u16, u8), args: &[Expr]) -> CompileResult<()> {
+        let (type_index, tag, arity) = variant;
+        if args.len() != arity as usize {
+            return self.error(format!(
+                "`{}` takes {} values but {} were given",
+                self.module.types[type_index as usize].name(),
+                arity,
+                args.len()
+            ));
+        }
+        for arg in args {
+            self.compile_expr(arg)?;
+        }
+        self.emit(Op::NewVariant {
+            type_index,
+            tag,
+            arity,
+        });
+
+        Ok(())
+    }
+
+    fn function_for(&self, name: &str, argc: usize) -> Option<u32> {
+        self.functions
+            .get(name)?
+            .iter()
+            .find(|(_, arity)| *arity as usize == argc)
+            .map(|(index, _)| *index)
+    }
+
+    fn return_type(&self, index: u32) -> Option<String> {
+        self.ir
+            .functions
+            .get(index as usize)?
+            .ret
+            .named()
+            .map(String::from)
+    }
+
+    // Compiles an expression leaving its value on the stack, returning the object or enum type
+    // of the value if it could be worked out.
+    fn compile_expr(&mut self, expr: &Expr) -> CompileResult<Option<String>> {
+        match expr {
+            Expr::Literal(literal) => {
+                match literal {
+                    Literal::Nil => self.emit(Op::Nil),
+                    Literal::Bool(true) => self.emit(Op::True),
+                    Literal::Bool(false) => self.emit(Op::False),
+                    Literal::Int(value) => {
+                        let index = self.constant(Constant::Int(*value));
+                        self.emit(Op::Constant(index))
+                    }
+                    Literal::Float(value) => {
+                        let index = self.constant(Constant::Float(*value));
+                        self.emit(Op::Constant(index))
+                    }
+                    Literal::String(value) => {
+                        let index = self.constant(Constant::String(value.clone()));
+                        self.emit(Op::Constant(index))
+                    }
+                };
+                Ok(None)
+            }
+            Expr::Local(name) => match self.resolve(name) {
+                Some(Resolved::Local(slot, type_name)) => {
+                    self.emit(Op::LoadLocal(slot));
+                    Ok(type_name)
+                }
+                Some(Resolved::Upvalue(index, type_name)) => {
+                    self.emit(Op::LoadUpvalue(index));
+                    Ok(type_name)
+                }
+                None => self.compile_expr(&Expr::Global(name.clone())),
+            },
+            Expr::Global(name) => {
+                if let Some(variant) = self.qualified_variant(name) {
+                    self.emit_variant(variant, &[])?;
+                    return Ok(Some(self.module.types[variant.0 as usize].name().into()));
+                }
+                // Locals declared by the lowering as globals still win if they're in scope, e.g.
+                // a closure referring to a variable declared after it was lowered.
+                if let Some(resolved) = self.resolve(name) {
+                    return Ok(match resolved {
+                        Resolved::Local(slot, type_name) => {
+                            self.emit(Op::LoadLocal(slot));
+                            type_name
+                        }
+                        Resolved::Upvalue(index, type_name) => {
+                            self.emit(Op::LoadUpvalue(index));
+                            type_name
+                        }
+                    });
+                }
+                let index = self.module.add_global(name);
+                self.emit(Op::LoadGlobal(index));
+                Ok(None)
+            }
+            Expr::Field { base, name } => {
+                if let Expr::Global(enum_name) = &**base {
+                    if let Some(variant) = self.variant(Some(enum_name), name) {
+                        self.emit_variant(variant, &[])?;
+                        return Ok(Some(enum_name.clone()));
+                    }
+                }
+                let base_type = self.compile_expr(base)?;
+                let slot = self.field_slot(base_type.as_deref(), name);
+                self.emit_get_field(slot, name);
+
+                Ok(self.field_type(base_type.as_deref(), name))
+            }
+            Expr::Call { callee, args } => self.compile_call(callee, args),
+            Expr::MethodCall {
+                receiver,
+                method,
+                args,
+            } => {
+                let receiver_type = self.compile_expr(receiver)?;
+                // Known at compile time, call the function directly with the receiver as `self`.
+                if let Some(index) = receiver_type
+                    .as_deref()
+                    .and_then(|ty| self.function_for(&format!("{}.{}", ty, method), args.len() + 1))
+                {
+                    for arg in args {
+                        self.compile_expr(arg)?;
+                    }
+                    self.emit(Op::CallFunction {
+                        function: index,
+                        argc: (args.len() + 1) as u8,
+                    });
+                    return Ok(self.return_type(index));
+                }
+
+                for arg in args {
+                    self.compile_expr(arg)?;
+                }
+                let name = self.constant(Constant::String(method.clone()));
+                self.emit(Op::CallMethod {
+                    name,
+                    argc: args.len() as u8,
+                });
+                Ok(None)
+            }
+            Expr::Binary { op, lhs, rhs } => {
+                self.compile_expr(lhs)?;
+                match op {
+                    BinaryOp::And | BinaryOp::Or => {
+                        self.emit(Op::Dup);
+                        let short_circuit = self.emit(if *op == BinaryOp::And {
+                            Op::JumpIfFalse(0)
+                        } else {
+                            Op::JumpIfTrue(0)
+                        });
+                        self.emit(Op::Pop);
+                        self.compile_expr(rhs)?;
+                        self.patch_jump(short_circuit);
+                    }
+                    op => {
+                        self.compile_expr(rhs)?;
+                        self.emit(binary_op(*op));
+                    }
+                }
+                Ok(None)
+            }
+            Expr::Unary { op, operand } => {
+                self.compile_expr(operand)?;
+                self.emit(match op {
+                    UnaryOp::Neg => Op::Neg,
+                    UnaryOp::Not => Op::Not,
+                });
+                Ok(None)
+            }
+            Expr::Object { name, fields } => {
+                let Some(object) = self.ir.object(name) else {
+                    return self.error(format!("unknown object `{}`", name));
+                };
+                if let Some((unknown, _)) = fields
+                    .iter()
+                    .find(|(field, _)| object.field_index(field).is_none())
+                {
+                    return self.error(format!("`{}` has no field `{}`", name, unknown));
+                }
+
+                for field in &object.fields {
+                    match fields.iter().find(|(given, _)| *given == field.name) {
+                        Some((_, value)) => {
+                            self.compile_expr(value)?;
+                        }
+                        None => match &field.default {
+                            Some(default) => {
+                                self.compile_expr(default)?;
+                            }
+                            None => {
+                                self.emit(Op::Nil);
+                            }
+                        },
+                    }
+                }
+                let type_index = self.module.type_index(name).unwrap_or_default();
+                self.emit(Op::NewObject(type_index));
+
+                Ok(Some(name.clone()))
+            }
+            Expr::Variant {
+                enum_name,
+                variant,
+                args,
+            } => {
+                let Some(resolved) = self.variant(enum_name.as_deref(), variant) else {
+                    return self.error(format!("can't work out which enum `.{}` is", variant));
+                };
+                self.emit_variant(resolved, args)?;
+
+                Ok(Some(self.module.types[resolved.0 as usize].name().into()))
+            }
+            Expr::Array(items) => {
+                for item in items {
+                    self.compile_expr(item)?;
+                }
+                self.emit(Op::NewArray(items.len() as u16));
+                Ok(None)
+            }
+            Expr::Closure(closure) => {
+                self.compile_closure(closure)?;
+                Ok(None)
+            }
+            Expr::Match { scrutinee, arms } => self.scoped(|c| c.compile_match(scrutinee, arms)),
+            Expr::Element(element) => self.error(format!(
+                "`{}` is an element, elements are only supported by the web backend",
+                element.name
+            )),
+        }
+    }
+
+    fn compile_call(&mut self, callee: &Expr, args: &[Expr]) -> CompileResult<Option<String>> {
+        if let Expr::Global(name) = callee {
+            if let Some(variant) = self.qualified_variant(name) {
+                self.emit_variant(variant, args)?;
+                return Ok(Some(self.module.types[variant.0 as usize].name().into()));
+            }
+            if self.resolve(name).is_none() {
+                if let Some(index) = self.function_for(name, args.len()) {
+                    for arg in args {
+                        self.compile_expr(arg)?;
+                    }
+                    self.emit(Op::CallFunction {
+                        function: index,
+                        argc: args.len() as u8,
+                    });
+                    return Ok(self.return_type(index));
+                }
+            }
+        }
+
+        self.compile_expr(callee)?;
+        for arg in args {
+            self.compile_expr(arg)?;
+        }
+        self.emit(Op::Call(args.len() as u8));
+
+        Ok(None)
+    }
+
+    fn compile_closure(&mut self, closure: &mir::Closure) -> CompileResult<()> {
+        // Named after the function they're in, so errors and coverage can say where they are.
+        let name = format!("{}.<closure>", self.current().proto.name);
+        self.frames
+            .push(FunctionState::new(name, closure.params.len() as u8));
+        for param in &closure.params {
+            self.declare_local(&param.name, param.ty.named().map(String::from));
+        }
+        let result = self.compile_body(&closure.body);
+        let proto = self.finish_function();
+        result?;
+
+        self.module.functions.push(proto);
+        let index = (self.module.functions.len() - 1) as u32;
+        self.emit(Op::Closure(index));
+
+        Ok(())
+    }
+
+    fn compile_match(
+        &mut self,
+        scrutinee: &Expr,
+        arms: &[mir::MatchArm],
+    ) -> CompileResult<Option<String>> {
+        let scrutinee_type = self.compile_expr(scrutinee)?;
+        let subject = self.declare_local("<match>", scrutinee_type.clone());
+        self.emit(Op::StoreLocal(subject));
+
+        if let Some(table) = self.switch_table(scrutinee_type.as_deref(), arms) {
+            return self.compile_switch(subject, table, arms);
+        }
+
+        let mut to_end = vec![];
+        for arm in arms {
+            self.scoped(|c| {
+                let to_next = c.compile_pattern_test(subject, &arm.pattern)?;
+                c.bind_pattern(subject, &arm.pattern);
+                c.compile_expr(&arm.body)?;
+                to_end.push(c.emit(Op::Jump(0)));
+                if let Some(to_next) = to_next {
+                    c.patch_jump(to_next);
+                }
+                Ok(())
+            })?;
+        }
+        self.emit(Op::Unreachable);
+        for jump in to_end {
+            self.patch_jump(jump);
+        }
+
+        Ok(None)
+    }
+
+    // When every arm matches a variant of the same enum we can dispatch on the tag in one go
+    // instead of testing each arm in turn. Returns the enum's type index and per tag arm index.
+    fn switch_table(
+        &self,
+        scrutinee_type: Option<&str>,
+        arms: &[mir::MatchArm],
+    ) -> Option<(u16, Vec<Option<usize>>, Option<usize>)> {
+        let first_variant = arms.iter().find_map(|arm| match &arm.pattern {
+            Pattern::Variant { name, .. } => Some(name.as_str()),
+            _ => None,
+        })?;
+        let r#enum = match scrutinee_type.and_then(|ty| self.ir.r#enum(ty)) {
+            Some(r#enum) => r#enum,
+            None => self.ir.enum_for_variant(first_variant)?,
+        };
+
+        let mut tags = vec![None; r#enum.variants.len()];
+        let mut default = None;
+        for (index, arm) in arms.iter().enumerate() {
+            match &arm.pattern {
+                Pattern::Variant { name, .. } => {
+                    let tag = r#enum.variant_index(name)?;
+                    tags[tag].get_or_insert(index);
+                }
+                Pattern::Wildcard | Pattern::Binding(_) if index == arms.len() - 1 => {
+                    default = Some(index)
+                }
+                _ => return None,
+            }
+        }
+
+        Some((self.module.type_index(&r#enum.name)?, tags, default))
+    }
+
+    fn compile_switch(
+        &mut self,
+        subject: u16,
+        (type_index, tags, default): (u16, Vec<Option<usize>>, Option<usize>),
+        arms: &[mir::MatchArm],
+    ) -> CompileResult<Option<String>> {
+        let table_index = self.current().proto.switches.len() as u16;
+        self.current().proto.switches.push(SwitchTable {
+            targets: vec![],
+            default: 0,
+        });
+        self.emit(Op::LoadLocal(subject));
+        self.emit(Op::SwitchTag(table_index));
+
+        // Nothing matched and there's no default arm.
+        let no_match = self.next_ip();
+        self.emit(Op::Unreachable);
+
+        let mut arm_starts = vec![];
+        let mut to_end = vec![];
+        for arm in arms {
+            arm_starts.push(self.next_ip());
+            self.scoped(|c| {
+                c.bind_pattern(subject, &arm.pattern);
+                c.compile_expr(&arm.body)?;
+                to_end.push(c.emit(Op::Jump(0)));
+                Ok(())
+            })?;
+        }
+        for jump in to_end {
+            self.patch_jump(jump);
+        }
+
+        let table = &mut self.current().proto.switches[table_index as usize];
+        table.targets = tags
+            .iter()
+            .map(|arm| arm.or(default).map_or(no_match, |arm| arm_starts[arm]))
+            .collect();
+        table.default = default.map_or(no_match, |arm| arm_starts[arm]);
+
+        Ok(Some(self.module.types[type_index as usize].name().into()))
+    }
+
+    // Leaves a jump on the stack to patch to the next arm if the pattern can fail to match.
+    fn compile_pattern_test(
+        &mut self,
+        subject: u16,
+        pattern: &Pattern,
+    ) -> CompileResult<Option<usize>> {
+        match pattern {
+            Pattern::Wildcard | Pattern::Binding(_) => return Ok(None),
+            Pattern::Range {
+                start: None,
+                end: None,
+            } => return Ok(None),
+            Pattern::Literal(literal) => {
+                self.emit(Op::LoadLocal(subject));
+                self.compile_expr(&Expr::Literal(literal.clone()))?;
+                self.emit(Op::Eq);
+            }
+            Pattern::Range { start, end } => {
+                // Both ends are inclusive, `400..499` matches 499.
+                let mut checks = 0;
+                if let Some(start) = start {
+                    self.emit(Op::LoadLocal(subject));
+                    self.compile_expr(&Expr::Literal(start.clone()))?;
+                    self.emit(Op::Ge);
+                    checks += 1;
+                }
+                if let Some(end) = end {
+                    self.emit(Op::LoadLocal(subject));
+                    self.compile_expr(&Expr::Literal(end.clone()))?;
+                    self.emit(Op::Le);
+                    checks += 1;
+                }
+                if checks == 2 {
+                    // Both checks are booleans so multiplying them out is an `and`.
+                    self.emit(Op::BitAnd);
+                }
+            }
+            Pattern::Variant { name, .. } => {
+                let Some((_, tag, _)) = self.variant(None, name) else {
+                    return self.error(format!("can't work out which enum `{}` is", name));
+                };
+                self.emit(Op::LoadLocal(subject));
+                self.emit(Op::TagEquals(tag));
+            }
+        }
+
+        Ok(Some(self.emit(Op::JumpIfFalse(0))))
+    }
+
+    fn bind_pattern(&mut self, subject: u16, pattern: &Pattern) {
+        match pattern {
+            Pattern::Binding(name) => {
+                self.emit(Op::LoadLocal(subject));
+                let slot = self.declare_local(name, None);
+                self.emit(Op::StoreLocal(slot));
+            }
+            Pattern::Variant { bindings, .. } => {
+                for (index, binding) in bindings.iter().enumerate() {
+                    self.emit(Op::LoadLocal(subject));
+                    self.emit(Op::GetPayload(index as u8));
+                    let slot = self.declare_local(binding, None);
+                    self.emit(Op::StoreLocal(slot));
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn binary_op(op: BinaryOp) -> Op {
+    match op {
+        BinaryOp::Add => Op::Add,
+        BinaryOp::Sub => Op::Sub,
+        BinaryOp::Mul => Op::Mul,
+        BinaryOp::Div => Op::Div,
+        BinaryOp::Mod => Op::Mod,
+        BinaryOp::Pow => Op::Pow,
+        BinaryOp::Eq => Op::Eq,
+        BinaryOp::Ne => Op::Ne,
+        BinaryOp::Lt => Op::Lt,
+        BinaryOp::Le => Op::Le,
+        BinaryOp::Gt => Op::Gt,
+        BinaryOp::Ge => Op::Ge,
+        // Only reached for compound assignment, `and`/`or` expressions short circuit.
+        BinaryOp::And | BinaryOp::BitAnd => Op::BitAnd,
+        BinaryOp::Or | BinaryOp::BitOr => Op::BitOr,
+        BinaryOp::Shl => Op::Shl,
+        BinaryOp::Shr => Op::Shr,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use elp_ir::mir::{Function, Param, Span, Type};
+    use pretty_assertions::assert_eq;
+
+    fn stmt(kind: StmtKind) -> Stmt {
+        Stmt {
+            span: Span {
+                start: 0,
+                end: 0,
+                line: 1,
+            },
+            kind,
+        }
+    }
+
+    fn function(name: &str, params: Vec<Param>, body: Vec<Stmt>) -> Function {
+        Function {
+            span: Span::default(),
+            name: name.into(),
+            receiver: None,
+            generics: vec![],
+            params,
+            ret: Type::Unknown,
+            body,
+            exported: false,
+        }
+    }
+
+    #[test]
+    fn compiles_a_simple_function() {
+        let ir = mir::Module {
+            name: "test".into(),
+            functions: vec![function(
+                "add",
+                vec![
+                    Param {
+                        name: "a".into(),
+                        ty: Type::Unknown,
+                    },
+                    Param {
+                        name: "b".into(),
+                        ty: Type::Unknown,
+                    },
+                ],
+                vec![stmt(StmtKind::Return(Some(Expr::Binary {
+                    op: BinaryOp::Add,
+                    lhs: Box::new(Expr::Local("a".into())),
+                    rhs: Box::new(Expr::Local("b".into())),
+                })))],
+            )],
+            ..Default::default()
+        };
+
+        let module = compile_module(&ir).unwrap();
+        assert_eq!(
+            module.functions[0].code,
+            vec![Op::LoadLocal(0), Op::LoadLocal(1), Op::Add, Op::Return]
+        );
+        assert_eq!(module.functions[0].arity, 2);
+        assert_eq!(module.functions[0].locals, 2);
+        assert_eq!(module.functions[0].lines, vec![1, 1, 1, 1]);
+    }
+
+    #[test]
+    fn closures_capture_enclosing_locals() {
+        let ir = mir::Module {
+            functions: vec![function(
+                "outer",
+                vec![],
+                vec![
+                    stmt(StmtKind::Let {
+                        name: "step".into(),
+                        mutable: false,
+                        ty: Type::Unknown,
+                        value: Some(Expr::Literal(Literal::Int(2))),
+                    }),
+                    stmt(StmtKind::Return(Some(Expr::Closure(Box::new(
+                        mir::Closure {
+                            params: vec![],
+                            ret: Type::Unknown,
+                            body: vec![stmt(StmtKind::Return(Some(Expr::Local("step".into()))))],
+                        },
+                    ))))),
+                ],
+            )],
+            ..Default::default()
+        };
+
+        let module = compile_module(&ir).unwrap();
+        let closure = &module.functions[1];
+        assert_eq!(closure.captures, vec![Capture::Local(0)]);
+        assert_eq!(closure.code, vec![Op::LoadUpvalue(0), Op::Return]);
+        assert_eq!(
+            module.functions[0].code,
+            vec![
+                Op::Constant(0),
+                Op::StoreLocal(0),
+                Op::Closure(1),
+                Op::Return
+            ]
+        );
+    }
+
+    #[test]
+    fn assigning_to_a_capture_is_an_error() {
+        let ir = mir::Module {
+            functions: vec![function(
+                "outer",
+                vec![],
+                vec![
+                    stmt(StmtKind::Let {
+                        name: "count".into(),
+                        mutable: true,
+                        ty: Type::Unknown,
+                        value: Some(Expr::Literal(Literal::Int(0))),
+                    }),
+                    stmt(StmtKind::Expr(Expr::Closure(Box::new(mir::Closure {
+                        params: vec![],
+                        ret: Type::Unknown,
+                        body: vec![stmt(StmtKind::Assign {
+                            target: Expr::Local("count".into()),
+                            op: Some(BinaryOp::Add),
+                            value: Expr::Literal(Literal::Int(1)),
+                        })],
+                    })))),
+                ],
+            )],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            compile_module(&ir).unwrap_err().message,
+            "`count` is captured by this closure and can't be assigned to"
+        );
+    }
+}
@@ -0,0 +1,256 @@
+use std::fmt::Write;
+
+use crate::{
+    module::{Capture, Constant, FunctionProto, Module, TypeInfo},
+    op::Op,
+};
+
+fn constant(module: &Module, index: u32) -> String {
+    match module.constants.get(index as usize) {
+        Some(Constant::Int(value)) => value.to_string(),
+        Some(Constant::Float(value)) => format!("{:?}", value),
+        Some(Constant::String(value)) => format!("{:?}", value),
+        None => "?".into(),
+    }
+}
+
+fn type_name(module: &Module, index: u16) -> &str {
+    module
+        .types
+        .get(index as usize)
+        .map(TypeInfo::name)
+        .unwrap_or("?")
+}
+
+// What an operand refers to, shown after the op so the listing can be read without looking
+// the indexes up by hand.
+fn annotation(module: &Module, op: &Op) -> Option<String> {
+    Some(match *op {
+        Op::Constant(index) => constant(module, index),
+        Op::LoadGlobal(index) | Op::StoreGlobal(index) => {
+            module.globals.get(index as usize)?.clone()
+        }
+        Op::NewObject(index) => type_name(module, index).into(),
+        Op::GetFieldNamed(name) | Op::SetFieldNamed(name) | Op::CallMethod { name, .. } => {
+            module.string_constant(name)?.into()
+        }
+        Op::NewVariant {
+            type_index, tag, ..
+        } => match module.types.get(type_index as usize)? {
+            TypeInfo::Enum { name, variants } => {
+                format!("{}.{}", name, variants.get(tag as usize)?.0)
+            }
+            TypeInfo::Object { .. } => return None,
+        },
+        Op::CallFunction { function, .. } | Op::Closure(function) => {
+            module.functions.get(function as usize)?.name.clone()
+        }
+        _ => return None,
+    })
+}
+
+fn operands(op: &Op) -> String {
+    match *op {
+        Op::Constant(value)
+        | Op::LoadGlobal(value)
+        | Op::StoreGlobal(value)
+        | Op::GetFieldNamed(value)
+        | Op::SetFieldNamed(value)
+        | Op::Jump(value)
+        | Op::JumpIfFalse(value)
+        | Op::JumpIfTrue(value)
+        | Op::Closure(value) => value.to_string(),
+        Op::LoadLocal(value)
+        | Op::StoreLocal(value)
+        | Op::LoadUpvalue(value)
+        | Op::NewObject(value)
+        | Op::GetField(value)
+        | Op::SetField(value)
+        | Op::SwitchTag(value)
+        | Op::TagEquals(value)
+        | Op::NewArray(value) => value.to_string(),
+        Op::GetPayload(value) | Op::Call(value) => value.to_string(),
+        Op::NewVariant {
+            type_index,
+            tag,
+            arity,
+        } => format!("{} {} {}", type_index, tag, arity),
+        Op::CallFunction { function, argc } => format!("{} {}", function, argc),
+        Op::CallMethod { name, argc } => format!("{} {}", name, argc),
+        _ => String::new(),
+    }
+}
+
+pub fn disassemble_function(module: &Module, function: &FunctionProto) -> String {
+    let mut out = String::new();
+    let captures = function
+        .captures
+        .iter()
+        .map(|capture| match capture {
+            Capture::Local(slot) => format!("local {}", slot),
+            Capture::Upvalue(index) => format!("upvalue {}", index),
+        })
+        .collect::<Vec<_>>();
+
+    let _ = write!(
+        out,
+        "fn {} (arity {}, locals {}",
+        function.name, function.arity, function.locals
+    );
+    if !captures.is_empty() {
+        let _ = write!(out, ", captures [{}]", captures.join(", "));
+    }
+    out.push_str(")\n");
+
+    let mut previous_line = None;
+    for (ip, op) in function.code.iter().enumerate() {
+        let line = function.lines.get(ip).copied().unwrap_or_default();
+        let line_column = if previous_line == Some(line) {
+            "   |".to_string()
+        } else {
+            format!("{:>4}", line)
+        };
+        previous_line = Some(line);
+
+        let mut text = format!("{} {:04} {:<16}", line_column, ip, op.mnemonic());
+        let operands = operands(op);
+        if !operands.is_empty() {
+            let _ = write!(text, " {:<8}", operands);
+        }
+        if let Some(annotation) = annotation(module, op) {
+            let _ = write!(text, " ; {}", annotation);
+        }
+        out.push_str(text.trim_end());
+        out.push('\n');
+    }
+
+    for (index, table) in function.switches.iter().enumerate() {
+        let targets = table
+            .targets
+            .iter()
+            .map(|target| format!("{:04}", target))
+            .collect::<Vec<_>>();
+        let _ = writeln!(
+            out,
+            "  switch {}: [{}] default {:04}",
+            index,
+            targets.join(", "),
+            table.default
+        );
+    }
+
+    out
+}
+
+// A human readable listing of a whole module, used by `elp dump` and in golden tests.
+pub fn disassemble(module: &Module) -> String {
+    let mut out = format!("module {}\n", module.name);
+
+    if !module.constants.is_empty() {
+        out.push_str("\nconstants:\n");
+        for index in 0..module.constants.len() {
+            let _ = writeln!(out, "  {:>4} {}", index, constant(module, index as u32));
+        }
+    }
+
+    if !module.types.is_empty() {
+        out.push_str("\ntypes:\n");
+        for (index, info) in module.types.iter().enumerate() {
+            let _ = match info {
+                TypeInfo::Object { name, fields } => writeln!(
+                    out,
+                    "  {:>4} object {} {{ {} }}",
+                    index,
+                    name,
+                    fields.join(", ")
+                ),
+                TypeInfo::Enum { name, variants } => writeln!(
+                    out,
+                    "  {:>4} enum {} {{ {} }}",
+                    index,
+                    name,
+                    variants
+                        .iter()
+                        .map(|(name, arity)| format!("{}/{}", name, arity))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            };
+        }
+    }
+
+    if !module.globals.is_empty() {
+        out.push_str("\nglobals:\n");
+        for (index, name) in module.globals.iter().enumerate() {
+            let _ = writeln!(out, "  {:>4} {}", index, name);
+        }
+    }
+
+    for function in &module.functions {
+        out.push('\n');
+        out.push_str(&disassemble_function(module, function));
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn disassembles_functions() {
+        let mut module = Module {
+            name: "test".into(),
+            types: vec![TypeInfo::Enum {
+                name: "Colour".into(),
+                variants: vec![("RED".into(), 0), ("RGB".into(), 3)],
+            }],
+            ..Default::default()
+        };
+        let greeting = module.add_constant(Constant::String("hello".into()));
+        let print = module.add_global("println");
+        module.functions.push(FunctionProto {
+            name: "main".into(),
+            arity: 0,
+            locals: 1,
+            captures: vec![],
+            code: vec![
+                Op::LoadGlobal(print),
+                Op::Constant(greeting),
+                Op::Call(1),
+                Op::NewVariant {
+                    type_index: 0,
+                    tag: 0,
+                    arity: 0,
+                },
+                Op::Return,
+            ],
+            lines: vec![2, 2, 2, 3, 3],
+            switches: vec![],
+        });
+
+        assert_eq!(
+            disassemble(&module),
+            "module test
+
+constants:
+     0 \"hello\"
+
+types:
+     0 enum Colour { RED/0, RGB/3 }
+
+globals:
+     0 println
+
+fn main (arity 0, locals 1)
+   2 0000 LOAD_GLOBAL      0        ; println
+   | 0001 CONSTANT         0        ; \"hello\"
+   | 0002 CALL             1
+   3 0003 NEW_VARIANT      0 0 0    ; Colour.RED
+   | 0004 RETURN
+"
+        );
+    }
+}
@@ -0,0 +1,843 @@
+// The on disk format for compiled modules. Everything is little endian, integers that are usually
+// small (indexes, counts, lines) are LEB128 encoded and source lines are run length encoded since
+// most lines produce several ops.
+//
+//   magic "ELPB", version u16
+//   name, constants, types, globals, init, functions
+
+use std::fmt;
+
+use crate::{
+    module::{Capture, Constant, FunctionProto, Module, SwitchTable, TypeInfo},
+    op::Op,
+};
+
+const MAGIC: &[u8; 4] = b"ELPB";
+pub const VERSION: u16 = 1;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum FormatError {
+    BadMagic,
+    UnsupportedVersion(u16),
+    UnexpectedEnd,
+    InvalidTag { what: &'static str, tag: u8 },
+    InvalidString,
+    TrailingBytes,
+    // An operand pointing past what it indexes, the VM would index out of bounds running it.
+    InvalidIndex { what: &'static str, index: u64 },
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FormatError::BadMagic => write!(f, "not an elp bytecode module"),
+            FormatError::UnsupportedVersion(version) => write!(
+                f,
+                "bytecode version {} isn't supported, expected {}",
+                version, VERSION
+            ),
+            FormatError::UnexpectedEnd => write!(f, "unexpected end of module"),
+            FormatError::InvalidTag { what, tag } => write!(f, "invalid {} tag {}", what, tag),
+            FormatError::InvalidString => write!(f, "string isn't valid UTF-8"),
+            FormatError::TrailingBytes => write!(f, "unexpected bytes after the module"),
+            FormatError::InvalidIndex { what, index } => {
+                write!(f, "invalid {} index {}", what, index)
+            }
+        }
+    }
+}
+
+impl std::error::Error for FormatError {}
+
+struct Writer {
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    fn byte(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    fn uleb(&mut self, mut value: u64) {
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+            if value == 0 {
+                self.byte(byte);
+                return;
+            }
+            self.byte(byte | 0x80);
+        }
+    }
+
+    fn sleb(&mut self, mut value: i64) {
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+            let done = (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0);
+            if done {
+                self.byte(byte);
+                return;
+            }
+            self.byte(byte | 0x80);
+        }
+    }
+
+    fn string(&mut self, value: &str) {
+        self.uleb(value.len() as u64);
+        self.bytes.extend_from_slice(value.as_bytes());
+    }
+
+    fn op(&mut self, op: &Op) {
+        match *op {
+            Op::Constant(index) => {
+                self.byte(0);
+                self.uleb(index as u64)
+            }
+            Op::Nil => self.byte(1),
+            Op::True => self.byte(2),
+            Op::False => self.byte(3),
+            Op::Pop => self.byte(4),
+            Op::Dup => self.byte(5),
+            Op::LoadLocal(slot) => {
+                self.byte(6);
+                self.uleb(slot as u64)
+            }
+            Op::StoreLocal(slot) => {
+                self.byte(7);
+                self.uleb(slot as u64)
+            }
+            Op::LoadUpvalue(index) => {
+                self.byte(8);
+                self.uleb(index as u64)
+            }
+            Op::LoadGlobal(index) => {
+                self.byte(9);
+                self.uleb(index as u64)
+            }
+            Op::StoreGlobal(index) => {
+                self.byte(10);
+                self.uleb(index as u64)
+            }
+            Op::NewObject(index) => {
+                self.byte(11);
+                self.uleb(index as u64)
+            }
+            Op::GetField(slot) => {
+                self.byte(12);
+                self.uleb(slot as u64)
+            }
+            Op::SetField(slot) => {
+                self.byte(13);
+                self.uleb(slot as u64)
+            }
+            Op::GetFieldNamed(name) => {
+                self.byte(14);
+                self.uleb(name as u64)
+            }
+            Op::SetFieldNamed(name) => {
+                self.byte(15);
+                self.uleb(name as u64)
+            }
+            Op::NewVariant {
+                type_index,
+                tag,
+                arity,
+            } => {
+                self.byte(16);
+                self.uleb(type_index as u64);
+                self.uleb(tag as u64);
+                self.byte(arity)
+            }
+            Op::SwitchTag(table) => {
+                self.byte(17);
+                self.uleb(table as u64)
+            }
+            Op::TagEquals(tag) => {
+                self.byte(18);
+                self.uleb(tag as u64)
+            }
+            Op::GetPayload(index) => {
+                self.byte(19);
+                self.byte(index)
+            }
+            Op::NewArray(count) => {
+                self.byte(20);
+                self.uleb(count as u64)
+            }
+            Op::Len => self.byte(21),
+            Op::Index => self.byte(22),
+            Op::Add => self.byte(23),
+            Op::Sub => self.byte(24),
+            Op::Mul => self.byte(25),
+            Op::Div => self.byte(26),
+            Op::Mod => self.byte(27),
+            Op::Pow => self.byte(28),
+            Op::Neg => self.byte(29),
+            Op::Not => self.byte(30),
+            Op::Eq => self.byte(31),
+            Op::Ne => self.byte(32),
+            Op::Lt => self.byte(33),
+            Op::Le => self.byte(34),
+            Op::Gt => self.byte(35),
+            Op::Ge => self.byte(36),
+            Op::BitAnd => self.byte(37),
+            Op::BitOr => self.byte(38),
+            Op::Shl => self.byte(39),
+            Op::Shr => self.byte(40),
+            Op::Jump(target) => {
+                self.byte(41);
+                self.uleb(target as u64)
+            }
+            Op::JumpIfFalse(target) => {
+                self.byte(42);
+                self.uleb(target as u64)
+            }
+            Op::JumpIfTrue(target) => {
+                self.byte(43);
+                self.uleb(target as u64)
+            }
+            Op::Call(argc) => {
+                self.byte(44);
+                self.byte(argc)
+            }
+            Op::CallFunction { function, argc } => {
+                self.byte(45);
+                self.uleb(function as u64);
+                self.byte(argc)
+            }
+            Op::CallMethod { name, argc } => {
+                self.byte(46);
+                self.uleb(name as u64);
+                self.byte(argc)
+            }
+            Op::Closure(function) => {
+                self.byte(47);
+                self.uleb(function as u64)
+            }
+            Op::Return => self.byte(48),
+            Op::Unreachable => self.byte(49),
+        }
+    }
+
+    fn function(&mut self, function: &FunctionProto) {
+        self.string(&function.name);
+        self.byte(function.arity);
+        self.uleb(function.locals as u64);
+
+        self.uleb(function.captures.len() as u64);
+        for capture in &function.captures {
+            match capture {
+                Capture::Local(slot) => {
+                    self.byte(0);
+                    self.uleb(*slot as u64)
+                }
+                Capture::Upvalue(index) => {
+                    self.byte(1);
+                    self.uleb(*index as u64)
+                }
+            }
+        }
+
+        self.uleb(function.code.len() as u64);
+        for op in &function.code {
+            self.op(op);
+        }
+
+        let mut runs: Vec<(u32, u64)> = vec![];
+        for line in &function.lines {
+            match runs.last_mut() {
+                Some((previous, count)) if previous == line => *count += 1,
+                _ => runs.push((*line, 1)),
+            }
+        }
+        self.uleb(runs.len() as u64);
+        for (line, count) in runs {
+            self.uleb(line as u64);
+            self.uleb(count);
+        }
+
+        self.uleb(function.switches.len() as u64);
+        for table in &function.switches {
+            self.uleb(table.targets.len() as u64);
+            for target in &table.targets {
+                self.uleb(*target as u64);
+            }
+            self.uleb(table.default as u64);
+        }
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    at: usize,
+}
+
+type ReadResult<T> = Result<T, FormatError>;
+
+impl Reader<'_> {
+    fn byte(&mut self) -> ReadResult<u8> {
+        let byte = *self.bytes.get(self.at).ok_or(FormatError::UnexpectedEnd)?;
+        self.at += 1;
+        Ok(byte)
+    }
+
+    fn take(&mut self, len: usize) -> ReadResult<&[u8]> {
+        let end = self.at.checked_add(len).ok_or(FormatError::UnexpectedEnd)?;
+        let bytes = self
+            .bytes
+            .get(self.at..end)
+            .ok_or(FormatError::UnexpectedEnd)?;
+        self.at = end;
+        Ok(bytes)
+    }
+
+    fn uleb(&mut self) -> ReadResult<u64> {
+        let mut value = 0u64;
+        let mut shift = 0;
+        loop {
+            let byte = self.byte()?;
+            if shift < 64 {
+                value |= ((byte & 0x7f) as u64) << shift;
+            }
+            shift += 7;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+    }
+
+    fn sleb(&mut self) -> ReadResult<i64> {
+        let mut value = 0i64;
+        let mut shift = 0;
+        loop {
+            let byte = self.byte()?;
+            if shift < 64 {
+                value |= ((byte & 0x7f) as i64) << shift;
+            }
+            shift += 7;
+            if byte & 0x80 == 0 {
+                if shift < 64 && byte & 0x40 != 0 {
+                    value |= -1 << shift;
+                }
+                return Ok(value);
+            }
+        }
+    }
+
+    // Indexes are written from narrower integers so anything that doesn't fit was corrupted.
+    fn index<T: TryFrom<u64>>(&mut self) -> ReadResult<T> {
+        T::try_from(self.uleb()?).map_err(|_| FormatError::UnexpectedEnd)
+    }
+
+    fn count(&mut self) -> ReadResult<usize> {
+        let count = self.index::<usize>()?;
+        // Every element takes at least a byte, this stops a corrupt count from allocating.
+        if count > self.bytes.len() - self.at {
+            return Err(FormatError::UnexpectedEnd);
+        }
+        Ok(count)
+    }
+
+    fn string(&mut self) -> ReadResult<String> {
+        let len = self.count()?;
+        String::from_utf8(self.take(len)?.to_vec()).map_err(|_| FormatError::InvalidString)
+    }
+
+    fn op(&mut self) -> ReadResult<Op> {
+        Ok(match self.byte()? {
+            0 => Op::Constant(self.index()?),
+            1 => Op::Nil,
+            2 => Op::True,
+            3 => Op::False,
+            4 => Op::Pop,
+            5 => Op::Dup,
+            6 => Op::LoadLocal(self.index()?),
+            7 => Op::StoreLocal(self.index()?),
+            8 => Op::LoadUpvalue(self.index()?),
+            9 => Op::LoadGlobal(self.index()?),
+            10 => Op::StoreGlobal(self.index()?),
+            11 => Op::NewObject(self.index()?),
+            12 => Op::GetField(self.index()?),
+            13 => Op::SetField(self.index()?),
+            14 => Op::GetFieldNamed(self.index()?),
+            15 => Op::SetFieldNamed(self.index()?),
+            16 => Op::NewVariant {
+                type_index: self.index()?,
+                tag: self.index()?,
+                arity: self.byte()?,
+            },
+            17 => Op::SwitchTag(self.index()?),
+            18 => Op::TagEquals(self.index()?),
+            19 => Op::GetPayload(self.byte()?),
+            20 => Op::NewArray(self.index()?),
+            21 => Op::Len,
+            22 => Op::Index,
+            23 => Op::Add,
+            24 => Op::Sub,
+            25 => Op::Mul,
+            26 => Op::Div,
+            27 => Op::Mod,
+            28 => Op::Pow,
+            29 => Op::Neg,
+            30 => Op::Not,
+            31 => Op::Eq,
+            32 => Op::Ne,
+            33 => Op::Lt,
+            34 => Op::Le,
+            35 => Op::Gt,
+            36 => Op::Ge,
+            37 => Op::BitAnd,
+            38 => Op::BitOr,
+            39 => Op::Shl,
+            40 => Op::Shr,
+            41 => Op::Jump(self.index()?),
+            42 => Op::JumpIfFalse(self.index()?),
+            43 => Op::JumpIfTrue(self.index()?),
+            44 => Op::Call(self.byte()?),
+            45 => Op::CallFunction {
+                function: self.index()?,
+                argc: self.byte()?,
+            },
+            46 => Op::CallMethod {
+                name: self.index()?,
+                argc: self.byte()?,
+            },
+            47 => Op::Closure(self.index()?),
+            48 => Op::Return,
+            49 => Op::Unreachable,
+            tag => return Err(FormatError::InvalidTag { what: "op", tag }),
+        })
+    }
+
+    fn function(&mut self) -> ReadResult<FunctionProto> {
+        let name = self.string()?;
+        let arity = self.byte()?;
+        let locals = self.index()?;
+
+        let mut captures = vec![];
+        for _ in 0..self.count()? {
+            captures.push(match self.byte()? {
+                0 => Capture::Local(self.index()?),
+                1 => Capture::Upvalue(self.index()?),
+                tag => {
+                    return Err(FormatError::InvalidTag {
+                        what: "capture",
+                        tag,
+                    })
+                }
+            });
+        }
+
+        let mut code = vec![];
+        for _ in 0..self.count()? {
+            code.push(self.op()?);
+        }
+
+        let mut lines = vec![];
+        for _ in 0..self.count()? {
+            let line: u32 = self.index()?;
+            // A run can be longer than what's left of the module, but never longer than the code.
+            let count = self.index::<usize>()?.min(code.len() - lines.len());
+            lines.extend(std::iter::repeat_n(line, count));
+        }
+
+        let mut switches = vec![];
+        for _ in 0..self.count()? {
+            let mut targets = vec![];
+            for _ in 0..self.count()? {
+                targets.push(self.index()?);
+            }
+            switches.push(SwitchTable {
+                targets,
+                default: self.index()?,
+            });
+        }
+
+        Ok(FunctionProto {
+            name,
+            arity,
+            locals,
+            captures,
+            code,
+            lines,
+            switches,
+        })
+    }
+}
+
+impl Module {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = Writer { bytes: vec![] };
+        writer.bytes.extend_from_slice(MAGIC);
+        writer.bytes.extend_from_slice(&VERSION.to_le_bytes());
+        writer.string(&self.name);
+
+        writer.uleb(self.constants.len() as u64);
+        for constant in &self.constants {
+            match constant {
+                Constant::Int(value) => {
+                    writer.byte(0);
+                    writer.sleb(*value)
+                }
+                Constant::Float(value) => {
+                    writer.byte(1);
+                    writer.bytes.extend_from_slice(&value.to_le_bytes())
+                }
+                Constant::String(value) => {
+                    writer.byte(2);
+                    writer.string(value)
+                }
+            }
+        }
+
+        writer.uleb(self.types.len() as u64);
+        for info in &self.types {
+            match info {
+                TypeInfo::Object { name, fields } => {
+                    writer.byte(0);
+                    writer.string(name);
+                    writer.uleb(fields.len() as u64);
+                    for field in fields {
+                        writer.string(field);
+                    }
+                }
+                TypeInfo::Enum { name, variants } => {
+                    writer.byte(1);
+                    writer.string(name);
+                    writer.uleb(variants.len() as u64);
+                    for (variant, arity) in variants {
+                        writer.string(variant);
+                        writer.byte(*arity);
+                    }
+                }
+            }
+        }
+
+        writer.uleb(self.globals.len() as u64);
+        for global in &self.globals {
+            writer.string(global);
+        }
+
+        // 0 means there's no initialiser, anything else is the function index plus one.
+        writer.uleb(self.init.map(|init| init as u64 + 1).unwrap_or_default());
+
+        writer.uleb(self.functions.len() as u64);
+        for function in &self.functions {
+            writer.function(function);
+        }
+
+        writer.bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, FormatError> {
+        let mut reader = Reader { bytes, at: 0 };
+        if reader.take(4).map_err(|_| FormatError::BadMagic)? != MAGIC {
+            return Err(FormatError::BadMagic);
+        }
+        let version = u16::from_le_bytes([reader.byte()?, reader.byte()?]);
+        if version != VERSION {
+            return Err(FormatError::UnsupportedVersion(version));
+        }
+
+        let name = reader.string()?;
+
+        let mut constants = vec![];
+        for _ in 0..reader.count()? {
+            constants.push(match reader.byte()? {
+                0 => Constant::Int(reader.sleb()?),
+                1 => {
+                    let bytes = reader.take(8)?;
+                    Constant::Float(f64::from_le_bytes(
+                        bytes.try_into().expect("took exactly 8 bytes"),
+                    ))
+                }
+                2 => Constant::String(reader.string()?),
+                tag => {
+                    return Err(FormatError::InvalidTag {
+                        what: "constant",
+                        tag,
+                    })
+                }
+            });
+        }
+
+        let mut types = vec![];
+        for _ in 0..reader.count()? {
+            types.push(match reader.byte()? {
+                0 => {
+                    let name = reader.string()?;
+                    let mut fields = vec![];
+                    for _ in 0..reader.count()? {
+                        fields.push(reader.string()?);
+                    }
+                    TypeInfo::Object { name, fields }
+                }
+                1 => {
+                    let name = reader.string()?;
+                    let mut variants = vec![];
+                    for _ in 0..reader.count()? {
+                        variants.push((reader.string()?, reader.byte()?));
+                    }
+                    TypeInfo::Enum { name, variants }
+                }
+                tag => return Err(FormatError::InvalidTag { what: "type", tag }),
+            });
+        }
+
+        let mut globals = vec![];
+        for _ in 0..reader.count()? {
+            globals.push(reader.string()?);
+        }
+
+        let init = match reader.index::<u32>()? {
+            0 => None,
+            index => Some(index - 1),
+        };
+
+        let mut functions = vec![];
+        for _ in 0..reader.count()? {
+            functions.push(reader.function()?);
+        }
+
+        if reader.at != bytes.len() {
+            return Err(FormatError::TrailingBytes);
+        }
+
+        let module = Module {
+            name,
+            constants,
+            types,
+            functions,
+            globals,
+            init,
+        };
+        module.check_indexes()?;
+        Ok(module)
+    }
+
+    // Indexes into the module and the function's own tables are checked once here so the VM can
+    // trust them. Field slots and how deep the stack is depend on the values at runtime, the VM
+    // checks those as it goes.
+    fn check_indexes(&self) -> Result<(), FormatError> {
+        let check = |what, index: u64, len: usize| {
+            if index < len as u64 {
+                Ok(())
+            } else {
+                Err(FormatError::InvalidIndex { what, index })
+            }
+        };
+
+        if let Some(init) = self.init {
+            check("function", init as u64, self.functions.len())?;
+        }
+        for function in &self.functions {
+            // Jumping to the end of the code is how a function falls off it.
+            let jump = |target: u32| check("jump", target as u64, function.code.len() + 1);
+            for op in &function.code {
+                match *op {
+                    Op::Constant(index)
+                    | Op::GetFieldNamed(index)
+                    | Op::SetFieldNamed(index)
+                    | Op::CallMethod { name: index, .. } => {
+                        check("constant", index as u64, self.constants.len())?
+                    }
+                    Op::LoadLocal(slot) | Op::StoreLocal(slot) => {
+                        check("local", slot as u64, function.locals as usize)?
+                    }
+                    Op::LoadUpvalue(index) => {
+                        check("upvalue", index as u64, function.captures.len())?
+                    }
+                    Op::LoadGlobal(index) | Op::StoreGlobal(index) => {
+                        check("global", index as u64, self.globals.len())?
+                    }
+                    Op::NewObject(index) => {
+                        check("type", index as u64, self.types.len())?;
+                        if !matches!(self.types[index as usize], TypeInfo::Object { .. }) {
+                            return Err(FormatError::InvalidIndex {
+                                what: "object type",
+                                index: index as u64,
+                            });
+                        }
+                    }
+                    Op::NewVariant { type_index, .. } => {
+                        check("type", type_index as u64, self.types.len())?
+                    }
+                    Op::SwitchTag(index) => check("switch", index as u64, function.switches.len())?,
+                    Op::Jump(target) | Op::JumpIfFalse(target) | Op::JumpIfTrue(target) => {
+                        jump(target)?
+                    }
+                    Op::CallFunction {
+                        function: index, ..
+                    } => check("function", index as u64, self.functions.len())?,
+                    Op::Closure(index) => {
+                        check("function", index as u64, self.functions.len())?;
+                        // What a closure captures is read out of the function creating it.
+                        for capture in &self.functions[index as usize].captures {
+                            match *capture {
+                                Capture::Local(slot) => {
+                                    check("local", slot as u64, function.locals as usize)?
+                                }
+                                Capture::Upvalue(index) => {
+                                    check("upvalue", index as u64, function.captures.len())?
+                                }
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            for switch in &function.switches {
+                for &target in switch.targets.iter().chain([&switch.default]) {
+                    jump(target)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn module() -> Module {
+        let mut module = Module {
+            name: "shapes".into(),
+            types: vec![
+                TypeInfo::Object {
+                    name: "Point".into(),
+                    fields: vec!["x".into(), "y".into()],
+                },
+                TypeInfo::Enum {
+                    name: "Shape".into(),
+                    variants: vec![("CIRCLE".into(), 1), ("EMPTY".into(), 0)],
+                },
+            ],
+            globals: vec!["println".into()],
+            init: Some(0),
+            ..Default::default()
+        };
+        module.add_constant(Constant::Int(-300));
+        module.add_constant(Constant::Float(1.5));
+        module.add_constant(Constant::String("radius".into()));
+        module.functions.push(FunctionProto {
+            name: "main".into(),
+            arity: 1,
+            locals: 3,
+            captures: vec![Capture::Local(1), Capture::Upvalue(0)],
+            code: vec![
+                Op::LoadLocal(0),
+                Op::SwitchTag(0),
+                Op::Constant(0),
+                Op::Return,
+                Op::NewVariant {
+                    type_index: 1,
+                    tag: 0,
+                    arity: 1,
+                },
+                Op::CallMethod { name: 2, argc: 0 },
+                Op::Jump(8),
+                Op::Return,
+            ],
+            lines: vec![1, 1, 1, 2, 2, 300, 300, 301],
+            switches: vec![SwitchTable {
+                targets: vec![2, 4],
+                default: 7,
+            }],
+        });
+        module
+    }
+
+    #[test]
+    fn modules_round_trip() {
+        let module = module();
+        let bytes = module.to_bytes();
+
+        assert_eq!(&bytes[..4], b"ELPB");
+        assert_eq!(Module::from_bytes(&bytes), Ok(module));
+    }
+
+    #[test]
+    fn rejects_broken_modules() {
+        let bytes = module().to_bytes();
+
+        assert_eq!(Module::from_bytes(b"nope"), Err(FormatError::BadMagic));
+        assert_eq!(
+            Module::from_bytes(b"ELPB\x09\x00"),
+            Err(FormatError::UnsupportedVersion(9))
+        );
+        assert_eq!(
+            Module::from_bytes(&bytes[..bytes.len() - 3]),
+            Err(FormatError::UnexpectedEnd)
+        );
+
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert_eq!(
+            Module::from_bytes(&trailing),
+            Err(FormatError::TrailingBytes)
+        );
+    }
+
+    #[test]
+    fn rejects_out_of_range_operands() {
+        let broken = |change: fn(&mut Module)| {
+            let mut module = module();
+            change(&mut module);
+            Module::from_bytes(&module.to_bytes())
+        };
+        let invalid = |what, index| Err(FormatError::InvalidIndex { what, index });
+
+        assert_eq!(
+            broken(|module| module.functions[0].code[2] = Op::Constant(3)),
+            invalid("constant", 3)
+        );
+        assert_eq!(
+            broken(|module| module.functions[0].code[0] = Op::StoreLocal(3)),
+            invalid("local", 3)
+        );
+        assert_eq!(
+            broken(|module| module.functions[0].code[6] = Op::Jump(9)),
+            invalid("jump", 9)
+        );
+        assert_eq!(
+            broken(|module| module.functions[0].switches[0].default = 200),
+            invalid("jump", 200)
+        );
+        assert_eq!(
+            broken(|module| module.functions[0].code[4] = Op::NewObject(1)),
+            invalid("object type", 1)
+        );
+        assert_eq!(
+            broken(|module| module.functions[0].code[0] = Op::LoadUpvalue(2)),
+            invalid("upvalue", 2)
+        );
+        assert_eq!(
+            broken(|module| module.init = Some(1)),
+            invalid("function", 1)
+        );
+    }
+
+    #[test]
+    fn leb128_handles_extremes() {
+        for value in [0, 1, -1, 63, -64, 64, -65, i64::MAX, i64::MIN] {
+            let mut writer = Writer { bytes: vec![] };
+            writer.sleb(value);
+            let mut reader = Reader {
+                bytes: &writer.bytes,
+                at: 0,
+            };
+            assert_eq!(reader.sleb(), Ok(value));
+        }
+
+        let mut writer = Writer { bytes: vec![] };
+        writer.uleb(u64::MAX);
+        let mut reader = Reader {
+            bytes: &writer.bytes,
+            at: 0,
+        };
+        assert_eq!(reader.uleb(), Ok(u64::MAX));
+    }
+}
@@ -0,0 +1,6 @@
+pub mod compiler;
+pub mod disassembler;
+pub mod format;
+pub mod module;
+pub mod op;
+pub mod vm;
@@ -0,0 +1,145 @@
+use crate::op::Op;
+
+#[derive(Debug, Clone)]
+pub enum Constant {
+    Int(i64),
+    Float(f64),
+    String(String),
+}
+
+// Floats are compared by their bits so that the constant pool can deduplicate them, NaN included.
+impl PartialEq for Constant {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Constant::Int(a), Constant::Int(b)) => a == b,
+            (Constant::Float(a), Constant::Float(b)) => a.to_bits() == b.to_bits(),
+            (Constant::String(a), Constant::String(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+// Closures copy the values they capture when they are created, a capture either comes from a
+// local slot of the enclosing function or from one of its own captures.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Capture {
+    Local(u16),
+    Upvalue(u16),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SwitchTable {
+    // Indexed by enum tag.
+    pub targets: Vec<u32>,
+    pub default: u32,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct FunctionProto {
+    pub name: String,
+    pub arity: u8,
+    pub locals: u16,
+    pub captures: Vec<Capture>,
+    pub code: Vec<Op>,
+    // The source line of every op in `code`, used for runtime errors and coverage.
+    pub lines: Vec<u32>,
+    pub switches: Vec<SwitchTable>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum TypeInfo {
+    Object {
+        name: String,
+        fields: Vec<String>,
+    },
+    Enum {
+        name: String,
+        // Name and payload arity of every variant, in tag order.
+        variants: Vec<(String, u8)>,
+    },
+}
+
+impl TypeInfo {
+    pub fn name(&self) -> &str {
+        match self {
+            TypeInfo::Object { name, .. } | TypeInfo::Enum { name, .. } => name,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Module {
+    pub name: String,
+    pub constants: Vec<Constant>,
+    pub types: Vec<TypeInfo>,
+    pub functions: Vec<FunctionProto>,
+    // Names that are resolved when the module is loaded: module level variables, functions
+    // called indirectly and natives provided by the host.
+    pub globals: Vec<String>,
+    // Runs once on load to initialise module level variables.
+    pub init: Option<u32>,
+}
+
+impl Module {
+    pub fn add_constant(&mut self, constant: Constant) -> u32 {
+        match self.constants.iter().position(|c| *c == constant) {
+            Some(index) => index as u32,
+            None => {
+                self.constants.push(constant);
+                (self.constants.len() - 1) as u32
+            }
+        }
+    }
+
+    pub fn add_global(&mut self, name: &str) -> u32 {
+        match self.globals.iter().position(|global| global == name) {
+            Some(index) => index as u32,
+            None => {
+                self.globals.push(name.into());
+                (self.globals.len() - 1) as u32
+            }
+        }
+    }
+
+    pub fn string_constant(&self, index: u32) -> Option<&str> {
+        match self.constants.get(index as usize) {
+            Some(Constant::String(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn type_index(&self, name: &str) -> Option<u16> {
+        self.types
+            .iter()
+            .position(|info| info.name() == name)
+            .map(|index| index as u16)
+    }
+
+    pub fn function_index(&self, name: &str) -> Option<u32> {
+        self.functions
+            .iter()
+            .position(|function| function.name == name)
+            .map(|index| index as u32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn constants_are_deduplicated() {
+        let mut module = Module::default();
+        let a = module.add_constant(Constant::String("hello".into()));
+        let b = module.add_constant(Constant::Float(1.5));
+        let c = module.add_constant(Constant::String("hello".into()));
+        let d = module.add_constant(Constant::Int(1));
+
+        assert_eq!(a, c);
+        assert_ne!(a, b);
+        assert_eq!(d, 2);
+        assert_eq!(module.constants.len(), 3);
+        assert_eq!(module.string_constant(a), Some("hello"));
+        assert_eq!(module.string_constant(b), None);
+    }
+}
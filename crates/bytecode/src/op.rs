@@ -0,0 +1,154 @@
+// The instruction set of the stack VM. Operands index into the module's constant pool, the
+// current frame's local slots or the module's type table, they never carry values themselves
+// which keeps every op at 8 bytes in memory and a handful of bytes once serialised.
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Op {
+    // Values.
+    Constant(u32),
+    Nil,
+    True,
+    False,
+    Pop,
+    Dup,
+
+    // Variables.
+    LoadLocal(u16),
+    StoreLocal(u16),
+    LoadUpvalue(u16),
+    LoadGlobal(u32),
+    StoreGlobal(u32),
+
+    // Objects, the slot index is resolved at compile time whenever the type is known.
+    NewObject(u16),
+    GetField(u16),
+    SetField(u16),
+    // Fallbacks for when the compiler couldn't work out the object's type, the operand is a
+    // string constant holding the field name.
+    GetFieldNamed(u32),
+    SetFieldNamed(u32),
+
+    // Enums.
+    NewVariant {
+        type_index: u16,
+        tag: u16,
+        arity: u8,
+    },
+    // Pops the enum on top of the stack and jumps through the function's switch table at the
+    // given index using its tag.
+    SwitchTag(u16),
+    // Pops the enum on top of the stack and pushes whether it has the given tag.
+    TagEquals(u16),
+    GetPayload(u8),
+
+    // Arrays.
+    NewArray(u16),
+    Len,
+    Index,
+
+    // Arithmetic and comparisons.
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Pow,
+    Neg,
+    Not,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    BitAnd,
+    BitOr,
+    Shl,
+    Shr,
+
+    // Control flow, jump targets are absolute instruction indexes.
+    Jump(u32),
+    JumpIfFalse(u32),
+    JumpIfTrue(u32),
+
+    // Calls.
+    Call(u8),
+    CallFunction {
+        function: u32,
+        argc: u8,
+    },
+    CallMethod {
+        name: u32,
+        argc: u8,
+    },
+    Closure(u32),
+    Return,
+    Unreachable,
+}
+
+impl Op {
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            Op::Constant(_) => "CONSTANT",
+            Op::Nil => "NIL",
+            Op::True => "TRUE",
+            Op::False => "FALSE",
+            Op::Pop => "POP",
+            Op::Dup => "DUP",
+            Op::LoadLocal(_) => "LOAD_LOCAL",
+            Op::StoreLocal(_) => "STORE_LOCAL",
+            Op::LoadUpvalue(_) => "LOAD_UPVALUE",
+            Op::LoadGlobal(_) => "LOAD_GLOBAL",
+            Op::StoreGlobal(_) => "STORE_GLOBAL",
+            Op::NewObject(_) => "NEW_OBJECT",
+            Op::GetField(_) => "GET_FIELD",
+            Op::SetField(_) => "SET_FIELD",
+            Op::GetFieldNamed(_) => "GET_FIELD_NAMED",
+            Op::SetFieldNamed(_) => "SET_FIELD_NAMED",
+            Op::NewVariant { .. } => "NEW_VARIANT",
+            Op::SwitchTag(_) => "SWITCH_TAG",
+            Op::TagEquals(_) => "TAG_EQUALS",
+            Op::GetPayload(_) => "GET_PAYLOAD",
+            Op::NewArray(_) => "NEW_ARRAY",
+            Op::Len => "LEN",
+            Op::Index => "INDEX",
+            Op::Add => "ADD",
+            Op::Sub => "SUB",
+            Op::Mul => "MUL",
+            Op::Div => "DIV",
+            Op::Mod => "MOD",
+            Op::Pow => "POW",
+            Op::Neg => "NEG",
+            Op::Not => "NOT",
+            Op::Eq => "EQ",
+            Op::Ne => "NE",
+            Op::Lt => "LT",
+            Op::Le => "LE",
+            Op::Gt => "GT",
+            Op::Ge => "GE",
+            Op::BitAnd => "BIT_AND",
+            Op::BitOr => "BIT_OR",
+            Op::Shl => "SHL",
+            Op::Shr => "SHR",
+            Op::Jump(_) => "JUMP",
+            Op::JumpIfFalse(_) => "JUMP_IF_FALSE",
+            Op::JumpIfTrue(_) => "JUMP_IF_TRUE",
+            Op::Call(_) => "CALL",
+            Op::CallFunction { .. } => "CALL_FUNCTION",
+            Op::CallMethod { .. } => "CALL_METHOD",
+            Op::Closure(_) => "CLOSURE",
+            Op::Return => "RETURN",
+            Op::Unreachable => "UNREACHABLE",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ops_stay_small() {
+        assert!(std::mem::size_of::<Op>() <= 8);
+    }
+}
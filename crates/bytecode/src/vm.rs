@@ -0,0 +1,1438 @@
+// A stack VM for running compiled modules. Every call gets a frame whose local slots sit at the
+// bottom of its part of the value stack, temporaries are pushed above them. Objects, arrays and
+// enums are reference counted so copying a value around is always cheap.
+
+use std::{cell::RefCell, collections::HashMap, fmt, rc::Rc};
+
+use crate::{
+    module::{Capture, Constant, Module, TypeInfo},
+    op::Op,
+};
+
+#[derive(Debug, PartialEq)]
+pub struct ObjectValue {
+    pub type_index: u16,
+    pub fields: Vec<Value>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct VariantValue {
+    pub type_index: u16,
+    pub tag: u16,
+    pub payload: Vec<Value>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ClosureValue {
+    pub function: u32,
+    pub captures: Vec<Value>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Value {
+    Nil,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(Rc<str>),
+    Array(Rc<RefCell<Vec<Value>>>),
+    Object(Rc<RefCell<ObjectValue>>),
+    Variant(Rc<VariantValue>),
+    Closure(Rc<ClosureValue>),
+    Native(u32),
+}
+
+// Equality is structural, two objects are equal when their fields are, which is what
+// `assert_equal` in tests wants.
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Nil, Value::Nil) => true,
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Int(a), Value::Int(b)) => a == b,
+            (Value::Float(a), Value::Float(b)) => a == b,
+            (Value::Int(a), Value::Float(b)) | (Value::Float(b), Value::Int(a)) => *a as f64 == *b,
+            (Value::String(a), Value::String(b)) => a == b,
+            (Value::Array(a), Value::Array(b)) => a == b,
+            (Value::Object(a), Value::Object(b)) => a == b,
+            (Value::Variant(a), Value::Variant(b)) => a == b,
+            (Value::Closure(a), Value::Closure(b)) => Rc::ptr_eq(a, b),
+            (Value::Native(a), Value::Native(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl Value {
+    pub fn string(value: &str) -> Self {
+        Value::String(value.into())
+    }
+
+    pub fn array(values: Vec<Value>) -> Self {
+        Value::Array(Rc::new(RefCell::new(values)))
+    }
+
+    fn is_truthy(&self) -> bool {
+        !matches!(self, Value::Nil | Value::Bool(false))
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Nil => "nil",
+            Value::Bool(_) => "bool",
+            Value::Int(_) => "int",
+            Value::Float(_) => "float",
+            Value::String(_) => "string",
+            Value::Array(_) => "array",
+            Value::Object(_) => "object",
+            Value::Variant(_) => "enum",
+            Value::Closure(_) | Value::Native(_) => "function",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct RuntimeError {
+    pub message: String,
+    pub function: String,
+    pub line: u32,
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} (in {} on line {})",
+            self.message, self.function, self.line
+        )
+    }
+}
+
+impl std::error::Error for RuntimeError {}
+
+pub type NativeFn = Rc<dyn Fn(&Vm, &[Value]) -> Result<Value, String>>;
+
+#[derive(Debug)]
+struct Frame {
+    closure: Rc<ClosureValue>,
+    ip: usize,
+    // Where local slot 0 lives on the stack.
+    base: usize,
+    // What to truncate the stack to when this frame returns.
+    return_to: usize,
+}
+
+pub struct Vm {
+    module: Rc<Module>,
+    natives: Vec<(String, NativeFn)>,
+    globals: Vec<Option<Value>>,
+    stack: Vec<Value>,
+    frames: Vec<Frame>,
+    // Keyed by qualified name and arity.
+    functions: HashMap<(String, u8), u32>,
+    initialised: bool,
+    // How many times each op of each function has run, only counted once coverage is asked for.
+    hits: Option<Vec<Vec<u64>>>,
+}
+
+impl Vm {
+    pub fn new(module: Module) -> Self {
+        let functions = module
+            .functions
+            .iter()
+            .enumerate()
+            .map(|(index, function)| ((function.name.clone(), function.arity), index as u32))
+            .collect();
+
+        Self {
+            globals: vec![None; module.globals.len()],
+            module: Rc::new(module),
+            natives: vec![],
+            stack: vec![],
+            frames: vec![],
+            functions,
+            initialised: false,
+            hits: None,
+        }
+    }
+
+    pub fn module(&self) -> &Module {
+        &self.module
+    }
+
+    // Starts counting the ops that run, for `elp test --coverage`. It's off by default since it
+    // costs something on every op.
+    pub fn record_coverage(&mut self) {
+        self.hits = Some(
+            self.module
+                .functions
+                .iter()
+                .map(|function| vec![0; function.code.len()])
+                .collect(),
+        );
+    }
+
+    // Indexed by function and then op, the same as the module's code.
+    pub fn hits(&self) -> Option<&[Vec<u64>]> {
+        self.hits.as_deref()
+    }
+
+    pub fn register_native(
+        &mut self,
+        name: &str,
+        native: impl Fn(&Vm, &[Value]) -> Result<Value, String> + 'static,
+    ) {
+        self.natives.push((name.into(), Rc::new(native)));
+    }
+
+    // Resolves globals against the module's functions and the registered natives, then runs the
+    // module initialiser. Called automatically by `call` the first time.
+    pub fn initialise(&mut self) -> Result<(), RuntimeError> {
+        if self.initialised {
+            return Ok(());
+        }
+        self.initialised = true;
+
+        for (index, name) in self.module.globals.iter().enumerate() {
+            if let Some(function) = self.module.function_index(name) {
+                self.globals[index] = Some(Value::Closure(Rc::new(ClosureValue {
+                    function,
+                    captures: vec![],
+                })));
+            } else if let Some(native) = self.natives.iter().position(|(n, _)| n == name) {
+                self.globals[index] = Some(Value::Native(native as u32));
+            }
+        }
+
+        if let Some(init) = self.module.init {
+            self.call_index(init, vec![])?;
+        }
+
+        Ok(())
+    }
+
+    pub fn call(&mut self, name: &str, args: Vec<Value>) -> Result<Value, RuntimeError> {
+        self.initialise()?;
+        let Some(&function) = self.functions.get(&(name.to_string(), args.len() as u8)) else {
+            return Err(RuntimeError {
+                message: format!("no function `{}` taking {} arguments", name, args.len()),
+                function: name.into(),
+                line: 0,
+            });
+        };
+
+        self.call_index(function, args)
+    }
+
+    fn call_index(&mut self, function: u32, args: Vec<Value>) -> Result<Value, RuntimeError> {
+        let depth = self.frames.len();
+        let return_to = self.stack.len();
+        let argc = args.len() as u8;
+        self.stack.extend(args);
+        let closure = Rc::new(ClosureValue {
+            function,
+            captures: vec![],
+        });
+
+        let result = self
+            .push_frame(closure, argc, return_to)
+            .and_then(|_| self.run(depth));
+        if result.is_err() {
+            self.frames.truncate(depth);
+            self.stack.truncate(return_to);
+        }
+
+        result
+    }
+
+    fn error(&self, message: impl Into<String>) -> RuntimeError {
+        let (function, line) = match self.frames.last() {
+            Some(frame) => {
+                let proto = &self.module.functions[frame.closure.function as usize];
+                (
+                    proto.name.clone(),
+                    proto
+                        .lines
+                        .get(frame.ip.saturating_sub(1))
+                        .copied()
+                        .unwrap_or_default(),
+                )
+            }
+            None => (String::new(), 0),
+        };
+
+        RuntimeError {
+            message: message.into(),
+            function,
+            line,
+        }
+    }
+
+    fn push_frame(
+        &mut self,
+        closure: Rc<ClosureValue>,
+        argc: u8,
+        return_to: usize,
+    ) -> Result<(), RuntimeError> {
+        let proto = &self.module.functions[closure.function as usize];
+        if proto.arity != argc {
+            return Err(self.error(format!(
+                "`{}` takes {} arguments but {} were given",
+                proto.name, proto.arity, argc
+            )));
+        }
+        let base = self.below(argc as usize)?;
+        let extra = proto.locals.saturating_sub(argc as u16);
+        self.stack
+            .extend(std::iter::repeat_n(Value::Nil, extra as usize));
+        self.frames.push(Frame {
+            closure,
+            ip: 0,
+            base,
+            return_to,
+        });
+
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack.pop().unwrap_or(Value::Nil)
+    }
+
+    // Where the top `count` values on the stack start. Code loaded from disk only has its indexes
+    // checked, so a call or constructor can still ask for more values than there are.
+    fn below(&self, count: usize) -> Result<usize, RuntimeError> {
+        self.stack
+            .len()
+            .checked_sub(count)
+            .ok_or_else(|| self.error("stack underflow"))
+    }
+
+    fn split(&mut self, count: usize) -> Result<Vec<Value>, RuntimeError> {
+        let at = self.below(count)?;
+        Ok(self.stack.split_off(at))
+    }
+
+    fn local(&self, index: usize) -> Result<&Value, RuntimeError> {
+        self.stack
+            .get(index)
+            .ok_or_else(|| self.error("local slot is past the top of the stack"))
+    }
+
+    fn string_constant(&self, index: u32) -> String {
+        self.module
+            .string_constant(index)
+            .unwrap_or_default()
+            .to_string()
+    }
+
+    fn run(&mut self, depth: usize) -> Result<Value, RuntimeError> {
+        let module = self.module.clone();
+
+        loop {
+            let frame = self.frames.last_mut().expect("no frame to run");
+            let proto = &module.functions[frame.closure.function as usize];
+            let Some(&op) = proto.code.get(frame.ip) else {
+                return Err(self.error("ran off the end of the function"));
+            };
+            if let Some(hits) = &mut self.hits {
+                hits[frame.closure.function as usize][frame.ip] += 1;
+            }
+            frame.ip += 1;
+            let base = frame.base;
+
+            match op {
+                Op::Constant(index) => self.stack.push(match &module.constants[index as usize] {
+                    Constant::Int(value) => Value::Int(*value),
+                    Constant::Float(value) => Value::Float(*value),
+                    Constant::String(value) => Value::string(value),
+                }),
+                Op::Nil => self.stack.push(Value::Nil),
+                Op::True => self.stack.push(Value::Bool(true)),
+                Op::False => self.stack.push(Value::Bool(false)),
+                Op::Pop => {
+                    self.pop();
+                }
+                Op::Dup => {
+                    let Some(value) = self.stack.last().cloned() else {
+                        return Err(self.error("stack underflow"));
+                    };
+                    self.stack.push(value);
+                }
+                Op::LoadLocal(slot) => {
+                    let value = self.local(base + slot as usize)?.clone();
+                    self.stack.push(value);
+                }
+                Op::StoreLocal(slot) => {
+                    let value = self.pop();
+                    match self.stack.get_mut(base + slot as usize) {
+                        Some(local) => *local = value,
+                        None => return Err(self.error("local slot is past the top of the stack")),
+                    }
+                }
+                Op::LoadUpvalue(index) => {
+                    let frame = self.frames.last().expect("no frame to run");
+                    // A function that captures can still be called directly, without a closure.
+                    let Some(value) = frame.closure.captures.get(index as usize).cloned() else {
+                        return Err(self.error("function was called without its captures"));
+                    };
+                    self.stack.push(value);
+                }
+                Op::LoadGlobal(index) => match &self.globals[index as usize] {
+                    Some(value) => self.stack.push(value.clone()),
+                    None => {
+                        return Err(self.error(format!(
+                            "`{}` isn't defined",
+                            module.globals[index as usize]
+                        )))
+                    }
+                },
+                Op::StoreGlobal(index) => {
+                    let value = self.pop();
+                    self.globals[index as usize] = Some(value);
+                }
+                Op::NewObject(type_index) => {
+                    let TypeInfo::Object { fields, .. } = &module.types[type_index as usize] else {
+                        return Err(self.error("not an object type"));
+                    };
+                    let fields = self.split(fields.len())?;
+                    self.stack
+                        .push(Value::Object(Rc::new(RefCell::new(ObjectValue {
+                            type_index,
+                            fields,
+                        }))));
+                }
+                Op::GetField(slot) => {
+                    let value = match self.pop() {
+                        Value::Object(object) => object.borrow().fields.get(slot as usize).cloned(),
+                        other => {
+                            return Err(
+                                self.error(format!("can't read a field of {}", other.type_name()))
+                            )
+                        }
+                    };
+                    let Some(value) = value else {
+                        return Err(self.error(format!("object has no field {}", slot)));
+                    };
+                    self.stack.push(value);
+                }
+                Op::SetField(slot) => {
+                    let value = self.pop();
+                    match self.pop() {
+                        Value::Object(object) => {
+                            match object.borrow_mut().fields.get_mut(slot as usize) {
+                                Some(field) => *field = value,
+                                None => {
+                                    return Err(self.error(format!("object has no field {}", slot)))
+                                }
+                            }
+                        }
+                        other => {
+                            return Err(
+                                self.error(format!("can't set a field of {}", other.type_name()))
+                            )
+                        }
+                    }
+                }
+                Op::GetFieldNamed(name) => {
+                    let name = self.string_constant(name);
+                    let object = self.pop();
+                    let slot = self.field_slot(&object, &name)?;
+                    let Value::Object(object) = object else {
+                        unreachable!("field_slot only succeeds for objects")
+                    };
+                    let value = object.borrow().fields[slot].clone();
+                    self.stack.push(value);
+                }
+                Op::SetFieldNamed(name) => {
+                    let name = self.string_constant(name);
+                    let value = self.pop();
+                    let object = self.pop();
+                    let slot = self.field_slot(&object, &name)?;
+                    if let Value::Object(object) = object {
+                        object.borrow_mut().fields[slot] = value;
+                    }
+                }
+                Op::NewVariant {
+                    type_index,
+                    tag,
+                    arity,
+                } => {
+                    let payload = self.split(arity as usize)?;
+                    self.stack.push(Value::Variant(Rc::new(VariantValue {
+                        type_index,
+                        tag,
+                        payload,
+                    })));
+                }
+                Op::SwitchTag(table) => {
+                    let Value::Variant(variant) = self.pop() else {
+                        return Err(self.error("can only match variants against an enum"));
+                    };
+                    let table = &proto.switches[table as usize];
+                    let target = table
+                        .targets
+                        .get(variant.tag as usize)
+                        .copied()
+                        .unwrap_or(table.default);
+                    self.frames.last_mut().expect("no frame to run").ip = target as usize;
+                }
+                Op::TagEquals(tag) => {
+                    let matches =
+                        matches!(self.pop(), Value::Variant(variant) if variant.tag == tag);
+                    self.stack.push(Value::Bool(matches));
+                }
+                Op::GetPayload(index) => {
+                    let value = match self.pop() {
+                        Value::Variant(variant) => variant
+                            .payload
+                            .get(index as usize)
+                            .cloned()
+                            .unwrap_or(Value::Nil),
+                        other => {
+                            return Err(self
+                                .error(format!("{} doesn't carry any values", other.type_name())))
+                        }
+                    };
+                    self.stack.push(value);
+                }
+                Op::NewArray(count) => {
+                    let items = self.split(count as usize)?;
+                    self.stack.push(Value::array(items));
+                }
+                Op::Len => {
+                    let len = match self.pop() {
+                        Value::Array(items) => items.borrow().len(),
+                        Value::String(value) => value.chars().count(),
+                        other => {
+                            return Err(
+                                self.error(format!("{} doesn't have a length", other.type_name()))
+                            )
+                        }
+                    };
+                    self.stack.push(Value::Int(len as i64));
+                }
+                Op::Index => {
+                    let index = self.pop();
+                    let items = self.pop();
+                    let value = match (&items, &index) {
+                        (Value::Array(items), Value::Int(index)) => {
+                            items.borrow().get(*index as usize).cloned()
+                        }
+                        _ => None,
+                    };
+                    match value {
+                        Some(value) => self.stack.push(value),
+                        None => return Err(self.error("index out of bounds")),
+                    }
+                }
+                Op::Add
+                | Op::Sub
+                | Op::Mul
+                | Op::Div
+                | Op::Mod
+                | Op::Pow
+                | Op::Lt
+                | Op::Le
+                | Op::Gt
+                | Op::Ge
+                | Op::BitAnd
+                | Op::BitOr
+                | Op::Shl
+                | Op::Shr => {
+                    let rhs = self.pop();
+                    let lhs = self.pop();
+                    let value = binary(op, lhs, rhs).map_err(|message| self.error(message))?;
+                    self.stack.push(value);
+                }
+                Op::Eq => {
+                    let rhs = self.pop();
+                    let lhs = self.pop();
+                    self.stack.push(Value::Bool(lhs == rhs));
+                }
+                Op::Ne => {
+                    let rhs = self.pop();
+                    let lhs = self.pop();
+                    self.stack.push(Value::Bool(lhs != rhs));
+                }
+                Op::Neg => {
+                    let value = match self.pop() {
+                        Value::Int(value) => match value.checked_neg() {
+                            Some(value) => Value::Int(value),
+                            None => return Err(self.error("integer overflow")),
+                        },
+                        Value::Float(value) => Value::Float(-value),
+                        other => {
+                            return Err(self.error(format!("can't negate {}", other.type_name())))
+                        }
+                    };
+                    self.stack.push(value);
+                }
+                Op::Not => {
+                    let value = self.pop();
+                    self.stack.push(Value::Bool(!value.is_truthy()));
+                }
+                Op::Jump(target) => {
+                    self.frames.last_mut().expect("no frame to run").ip = target as usize
+                }
+                Op::JumpIfFalse(target) => {
+                    if !self.pop().is_truthy() {
+                        self.frames.last_mut().expect("no frame to run").ip = target as usize;
+                    }
+                }
+                Op::JumpIfTrue(target) => {
+                    if self.pop().is_truthy() {
+                        self.frames.last_mut().expect("no frame to run").ip = target as usize;
+                    }
+                }
+                Op::Call(argc) => {
+                    let callee_at = self.below(argc as usize + 1)?;
+                    match self.stack[callee_at].clone() {
+                        Value::Closure(closure) => self.push_frame(closure, argc, callee_at)?,
+                        Value::Native(index) => {
+                            let args = self.stack.split_off(callee_at + 1);
+                            let native = self.natives[index as usize].1.clone();
+                            let value =
+                                native(self, &args).map_err(|message| self.error(message))?;
+                            self.stack.truncate(callee_at);
+                            self.stack.push(value);
+                        }
+                        other => {
+                            return Err(self.error(format!("can't call {}", other.type_name())))
+                        }
+                    }
+                }
+                Op::CallFunction { function, argc } => {
+                    let return_to = self.below(argc as usize)?;
+                    let closure = Rc::new(ClosureValue {
+                        function,
+                        captures: vec![],
+                    });
+                    self.push_frame(closure, argc, return_to)?;
+                }
+                Op::CallMethod { name, argc } => {
+                    let name = self.string_constant(name);
+                    self.call_method(&name, argc)?;
+                }
+                Op::Closure(function) => {
+                    let frame = self.frames.last().expect("no frame to run");
+                    let captures = module.functions[function as usize]
+                        .captures
+                        .iter()
+                        .map(|capture| match capture {
+                            Capture::Local(slot) => {
+                                self.local(frame.base + *slot as usize).cloned()
+                            }
+                            Capture::Upvalue(index) => frame
+                                .closure
+                                .captures
+                                .get(*index as usize)
+                                .cloned()
+                                .ok_or_else(|| {
+                                    self.error("function was called without its captures")
+                                }),
+                        })
+                        .collect::<Result<_, _>>()?;
+                    self.stack
+                        .push(Value::Closure(Rc::new(ClosureValue { function, captures })));
+                }
+                Op::Return => {
+                    let value = self.pop();
+                    let frame = self.frames.pop().expect("no frame to return from");
+                    self.stack.truncate(frame.return_to);
+                    if self.frames.len() == depth {
+                        return Ok(value);
+                    }
+                    self.stack.push(value);
+                }
+                Op::Unreachable => return Err(self.error("no match arm matched the value")),
+            }
+        }
+    }
+
+    fn field_slot(&self, value: &Value, name: &str) -> Result<usize, RuntimeError> {
+        let Value::Object(object) = value else {
+            return Err(self.error(format!("can't access `{}` on {}", name, value.type_name())));
+        };
+        let type_index = object.borrow().type_index;
+        match &self.module.types[type_index as usize] {
+            TypeInfo::Object { fields, name: ty } => fields
+                .iter()
+                .position(|field| field == name)
+                .ok_or_else(|| self.error(format!("`{}` has no field `{}`", ty, name))),
+            TypeInfo::Enum { .. } => Err(self.error("not an object")),
+        }
+    }
+
+    // Methods are looked up on the receiver's type at runtime when the compiler couldn't see what
+    // it was, values that aren't objects get a handful of built in methods.
+    fn call_method(&mut self, name: &str, argc: u8) -> Result<(), RuntimeError> {
+        let receiver_at = self.below(argc as usize + 1)?;
+        let receiver = self.stack[receiver_at].clone();
+
+        let type_name = match &receiver {
+            Value::Object(object) => Some(object.borrow().type_index),
+            Value::Variant(variant) => Some(variant.type_index),
+            _ => None,
+        }
+        .map(|index| self.module.types[index as usize].name().to_string());
+
+        if let Some(type_name) = type_name {
+            let qualified = format!("{}.{}", type_name, name);
+            if let Some(&function) = self.functions.get(&(qualified.clone(), argc + 1)) {
+                let closure = Rc::new(ClosureValue {
+                    function,
+                    captures: vec![],
+                });
+                return self.push_frame(closure, argc + 1, receiver_at);
+            }
+            // A static function called through an instance, drop the receiver.
+            if let Some(&function) = self.functions.get(&(qualified, argc)) {
+                self.stack.remove(receiver_at);
+                let closure = Rc::new(ClosureValue {
+                    function,
+                    captures: vec![],
+                });
+                return self.push_frame(closure, argc, receiver_at);
+            }
+        }
+
+        let args = self.stack.split_off(receiver_at + 1);
+        self.stack.truncate(receiver_at);
+        let value = self.builtin_method(&receiver, name, &args)?;
+        self.stack.push(value);
+
+        Ok(())
+    }
+
+    fn builtin_method(
+        &self,
+        receiver: &Value,
+        name: &str,
+        args: &[Value],
+    ) -> Result<Value, RuntimeError> {
+        match (receiver, name, args) {
+            (Value::Array(items), "append", [value]) => {
+                let mut items = items.borrow_mut();
+                items.push(value.clone());
+                Ok(Value::Int(items.len() as i64 - 1))
+            }
+            (Value::Array(items), "len" | "length", []) => {
+                Ok(Value::Int(items.borrow().len() as i64))
+            }
+            (Value::String(value), "len" | "length", []) => {
+                Ok(Value::Int(value.chars().count() as i64))
+            }
+            (value, "toString", []) => Ok(Value::string(&self.display(value))),
+            (value, "notNil", []) => Ok(Value::Bool(!matches!(value, Value::Nil))),
+            _ => Err(self.error(format!(
+                "{} has no method `{}` taking {} arguments",
+                receiver.type_name(),
+                name,
+                args.len()
+            ))),
+        }
+    }
+
+    // How a value is shown by `println`, strings print as they are.
+    pub fn display(&self, value: &Value) -> String {
+        match value {
+            Value::String(value) => value.to_string(),
+            other => self.debug(other),
+        }
+    }
+
+    // How a value is shown inside other values and in test failures.
+    pub fn debug(&self, value: &Value) -> String {
+        match value {
+            Value::Nil => "nil".into(),
+            Value::Bool(value) => value.to_string(),
+            Value::Int(value) => value.to_string(),
+            Value::Float(value) => format!("{:?}", value),
+            Value::String(value) => format!("{:?}", value),
+            Value::Array(items) => format!(
+                "[{}]",
+                items
+                    .borrow()
+                    .iter()
+                    .map(|item| self.debug(item))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            Value::Object(object) => {
+                let object = object.borrow();
+                match &self.module.types[object.type_index as usize] {
+                    TypeInfo::Object { name, fields } => format!(
+                        "{} {{ {} }}",
+                        name,
+                        fields
+                            .iter()
+                            .zip(&object.fields)
+                            .map(|(field, value)| format!(".{} = {}", field, self.debug(value)))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ),
+                    TypeInfo::Enum { name, .. } => name.clone(),
+                }
+            }
+            Value::Variant(variant) => {
+                let (enum_name, variant_name) =
+                    match &self.module.types[variant.type_index as usize] {
+                        TypeInfo::Enum { name, variants } => (
+                            name.as_str(),
+                            variants
+                                .get(variant.tag as usize)
+                                .map(|(name, _)| name.as_str())
+                                .unwrap_or("?"),
+                        ),
+                        TypeInfo::Object { name, .. } => (name.as_str(), "?"),
+                    };
+                if variant.payload.is_empty() {
+                    format!("{}.{}", enum_name, variant_name)
+                } else {
+                    format!(
+                        "{}.{}({})",
+                        enum_name,
+                        variant_name,
+                        variant
+                            .payload
+                            .iter()
+                            .map(|value| self.debug(value))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    )
+                }
+            }
+            Value::Closure(closure) => format!(
+                "<fn {}>",
+                self.module.functions[closure.function as usize].name
+            ),
+            Value::Native(index) => format!("<native {}>", self.natives[*index as usize].0),
+        }
+    }
+}
+
+fn binary(op: Op, lhs: Value, rhs: Value) -> Result<Value, String> {
+    use Value::{Bool, Float, Int};
+
+    let overflow = || "integer overflow".to_string();
+    Ok(match (op, &lhs, &rhs) {
+        (Op::Add, Int(a), Int(b)) => Int(a.checked_add(*b).ok_or_else(overflow)?),
+        (Op::Sub, Int(a), Int(b)) => Int(a.checked_sub(*b).ok_or_else(overflow)?),
+        (Op::Mul, Int(a), Int(b)) => Int(a.checked_mul(*b).ok_or_else(overflow)?),
+        (Op::Div | Op::Mod, Int(_), Int(0)) => return Err("division by zero".into()),
+        (Op::Div, Int(a), Int(b)) => Int(a.checked_div(*b).ok_or_else(overflow)?),
+        (Op::Mod, Int(a), Int(b)) => Int(a.checked_rem(*b).ok_or_else(overflow)?),
+        // There's no integer `2 ** -1`, floats are for fractions.
+        (Op::Pow, Int(_), Int(b)) if *b < 0 => {
+            return Err("an integer can't be raised to a negative power, use a float".into())
+        }
+        (Op::Pow, Int(a), Int(b)) => Int(u32::try_from(*b)
+            .ok()
+            .and_then(|b| a.checked_pow(b))
+            .ok_or_else(overflow)?),
+        (Op::BitAnd, Int(a), Int(b)) => Int(a & b),
+        (Op::BitOr, Int(a), Int(b)) => Int(a | b),
+        (Op::BitAnd, Bool(a), Bool(b)) => Bool(*a && *b),
+        (Op::BitOr, Bool(a), Bool(b)) => Bool(*a || *b),
+        (Op::Shl, Int(a), Int(b)) => Int(a.checked_shl(*b as u32).ok_or_else(overflow)?),
+        (Op::Shr, Int(a), Int(b)) => Int(a.checked_shr(*b as u32).ok_or_else(overflow)?),
+        (Op::Add, Value::String(a), Value::String(b)) => Value::string(&format!("{}{}", a, b)),
+        (Op::Lt, Value::String(a), Value::String(b)) => Bool(a < b),
+        (Op::Le, Value::String(a), Value::String(b)) => Bool(a <= b),
+        (Op::Gt, Value::String(a), Value::String(b)) => Bool(a > b),
+        (Op::Ge, Value::String(a), Value::String(b)) => Bool(a >= b),
+        (_, Int(_) | Float(_), Int(_) | Float(_)) => {
+            let as_float = |value: &Value| match value {
+                Int(value) => *value as f64,
+                Float(value) => *value,
+                _ => unreachable!(),
+            };
+            let (a, b) = (as_float(&lhs), as_float(&rhs));
+            match op {
+                Op::Add => Float(a + b),
+                Op::Sub => Float(a - b),
+                Op::Mul => Float(a * b),
+                Op::Div => Float(a / b),
+                Op::Mod => Float(a % b),
+                Op::Pow => Float(a.powf(b)),
+                Op::Lt => Bool(a < b),
+                Op::Le => Bool(a <= b),
+                Op::Gt => Bool(a > b),
+                Op::Ge => Bool(a >= b),
+                _ => return Err(format!("`{}` needs integers", op.mnemonic())),
+            }
+        }
+        _ => {
+            return Err(format!(
+                "can't {} {} and {}",
+                op.mnemonic().to_lowercase(),
+                lhs.type_name(),
+                rhs.type_name()
+            ))
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compiler::compile_module, module::FunctionProto};
+    use elp_ir::{
+        lower::lower_module,
+        mir::{
+            self, BinaryOp, EnumDef, Expr, FieldDef, Function, Literal, MatchArm, ObjectDef, Param,
+            Pattern, Span, Stmt, StmtKind, Type, UnaryOp, VariantDef,
+        },
+    };
+    use elp_parser::{
+        ast::{module::ASTModule, traits::FromCST},
+        cst::CSTModule,
+        parser::{ElpParser, Rule},
+    };
+    use from_pest::FromPest;
+    use pest::Parser;
+    use pretty_assertions::assert_eq;
+
+    fn run_source(source: &str, function: &str, args: Vec<Value>) -> Result<Value, RuntimeError> {
+        let mut pairs = ElpParser::parse(Rule::module, source).unwrap();
+        let cst = CSTModule::from_pest(&mut pairs).unwrap();
+        let ast = ASTModule::from_cst(&cst);
+        let ir = lower_module("test", &ast).unwrap();
+        let mut vm = Vm::new(compile_module(&ir).unwrap());
+
+        vm.call(function, args)
+    }
+
+    fn stmt(kind: StmtKind) -> Stmt {
+        Stmt {
+            span: Span {
+                start: 0,
+                end: 0,
+                line: 1,
+            },
+            kind,
+        }
+    }
+
+    fn param(name: &str, ty: Type) -> Param {
+        Param {
+            name: name.into(),
+            ty,
+        }
+    }
+
+    fn function(name: &str, params: Vec<Param>, body: Vec<Stmt>) -> Function {
+        Function {
+            span: Span::default(),
+            name: name.into(),
+            receiver: None,
+            generics: vec![],
+            params,
+            ret: Type::Unknown,
+            body,
+            exported: false,
+        }
+    }
+
+    fn local(name: &str) -> Box<Expr> {
+        Box::new(Expr::Local(name.into()))
+    }
+
+    fn int(value: i64) -> Expr {
+        Expr::Literal(Literal::Int(value))
+    }
+
+    #[test]
+    fn runs_functions_from_source() {
+        let source = "
+            fn double(value int) -> int {
+                var result = value
+                result *= 2
+                return result
+            }
+
+            fn main -> int {
+                return double(21)
+            }
+        ";
+
+        assert_eq!(run_source(source, "main", vec![]), Ok(Value::Int(42)));
+        assert_eq!(
+            run_source(source, "double", vec![Value::Int(4)]),
+            Ok(Value::Int(8))
+        );
+    }
+
+    #[test]
+    fn counts_the_ops_that_run_for_coverage() {
+        let source = "
+            fn double(value int) -> int {
+                return value
+            }
+
+            fn unused -> int {
+                return 1
+            }
+
+            fn main -> int {
+                return double(21)
+            }
+        ";
+        let mut pairs = ElpParser::parse(Rule::module, source).unwrap();
+        let cst = CSTModule::from_pest(&mut pairs).unwrap();
+        let ast = ASTModule::from_cst(&cst);
+        let ir = lower_module("test", &ast).unwrap();
+        let mut vm = Vm::new(compile_module(&ir).unwrap());
+        assert_eq!(vm.hits(), None);
+
+        vm.record_coverage();
+        vm.call("main", vec![]).unwrap();
+        vm.call("main", vec![]).unwrap();
+        let runs = |name: &str| {
+            let function = vm.module().function_index(name).unwrap() as usize;
+            vm.hits().unwrap()[function].iter().max().copied()
+        };
+        assert_eq!(runs("double"), Some(2));
+        assert_eq!(runs("unused"), Some(0));
+    }
+
+    #[test]
+    fn methods_and_fields_from_source() {
+        let source = "
+            object Counter {
+                .count int = 0,
+            }
+
+            fn Counter.increment(&self, by int) -> int {
+                self.count += by
+                return self.count
+            }
+        ";
+        let mut pairs = ElpParser::parse(Rule::module, source).unwrap();
+        let cst = CSTModule::from_pest(&mut pairs).unwrap();
+        let ast = ASTModule::from_cst(&cst);
+        let mut ir = lower_module("test", &ast).unwrap();
+        // Object literals can't be written in source yet.
+        ir.functions.push(function(
+            "main",
+            vec![],
+            vec![
+                stmt(StmtKind::Let {
+                    name: "counter".into(),
+                    mutable: false,
+                    ty: Type::Unknown,
+                    value: Some(Expr::Object {
+                        name: "Counter".into(),
+                        fields: vec![],
+                    }),
+                }),
+                stmt(StmtKind::Expr(Expr::MethodCall {
+                    receiver: local("counter"),
+                    method: "increment".into(),
+                    args: vec![int(2)],
+                })),
+                stmt(StmtKind::Return(Some(Expr::MethodCall {
+                    receiver: local("counter"),
+                    method: "increment".into(),
+                    args: vec![int(3)],
+                }))),
+            ],
+        ));
+
+        let module = compile_module(&ir).unwrap();
+        // The receiver's type is known so the field is accessed by slot.
+        assert!(module.functions[0].code.contains(&Op::GetField(0)));
+        let mut vm = Vm::new(module);
+        assert_eq!(vm.call("main", vec![]), Ok(Value::Int(5)));
+    }
+
+    #[test]
+    fn closures_keep_their_captures() {
+        let ir = mir::Module {
+            functions: vec![
+                function(
+                    "adder",
+                    vec![param("step", Type::Unknown)],
+                    vec![stmt(StmtKind::Return(Some(Expr::Closure(Box::new(
+                        mir::Closure {
+                            params: vec![param("value", Type::Unknown)],
+                            ret: Type::Unknown,
+                            body: vec![stmt(StmtKind::Return(Some(Expr::Binary {
+                                op: BinaryOp::Add,
+                                lhs: local("value"),
+                                rhs: local("step"),
+                            })))],
+                        },
+                    )))))],
+                ),
+                function(
+                    "main",
+                    vec![],
+                    vec![
+                        stmt(StmtKind::Let {
+                            name: "add_three".into(),
+                            mutable: false,
+                            ty: Type::Unknown,
+                            value: Some(Expr::Call {
+                                callee: Box::new(Expr::Global("adder".into())),
+                                args: vec![int(3)],
+                            }),
+                        }),
+                        stmt(StmtKind::Return(Some(Expr::Call {
+                            callee: local("add_three"),
+                            args: vec![int(4)],
+                        }))),
+                    ],
+                ),
+            ],
+            ..Default::default()
+        };
+
+        let mut vm = Vm::new(compile_module(&ir).unwrap());
+        assert_eq!(vm.call("main", vec![]), Ok(Value::Int(7)));
+    }
+
+    fn shapes() -> mir::Module {
+        mir::Module {
+            enums: vec![EnumDef {
+                span: Span::default(),
+                name: "Shape".into(),
+                variants: vec![
+                    VariantDef {
+                        name: "SQUARE".into(),
+                        payload: vec![Type::Unknown],
+                    },
+                    VariantDef {
+                        name: "RECT".into(),
+                        payload: vec![Type::Unknown, Type::Unknown],
+                    },
+                    VariantDef {
+                        name: "EMPTY".into(),
+                        payload: vec![],
+                    },
+                ],
+                implements: vec![],
+                exported: false,
+            }],
+            functions: vec![function(
+                "area",
+                vec![param(
+                    "shape",
+                    Type::Named {
+                        name: "Shape".into(),
+                        generics: vec![],
+                    },
+                )],
+                vec![stmt(StmtKind::Return(Some(Expr::Match {
+                    scrutinee: local("shape"),
+                    arms: vec![
+                        MatchArm {
+                            pattern: Pattern::Variant {
+                                name: "SQUARE".into(),
+                                bindings: vec!["side".into()],
+                            },
+                            body: Expr::Binary {
+                                op: BinaryOp::Mul,
+                                lhs: local("side"),
+                                rhs: local("side"),
+                            },
+                        },
+                        MatchArm {
+                            pattern: Pattern::Variant {
+                                name: "RECT".into(),
+                                bindings: vec!["w".into(), "h".into()],
+                            },
+                            body: Expr::Binary {
+                                op: BinaryOp::Mul,
+                                lhs: local("w"),
+                                rhs: local("h"),
+                            },
+                        },
+                        MatchArm {
+                            pattern: Pattern::Wildcard,
+                            body: int(0),
+                        },
+                    ],
+                })))],
+            )],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn matches_dispatch_on_enum_tags() {
+        let module = compile_module(&shapes()).unwrap();
+        assert!(module.functions[0].code.contains(&Op::SwitchTag(0)));
+
+        let mut vm = Vm::new(module);
+        let variant = |tag, payload| {
+            Value::Variant(Rc::new(VariantValue {
+                type_index: 0,
+                tag,
+                payload,
+            }))
+        };
+        assert_eq!(
+            vm.call("area", vec![variant(0, vec![Value::Int(3)])]),
+            Ok(Value::Int(9))
+        );
+        assert_eq!(
+            vm.call("area", vec![variant(1, vec![Value::Int(2), Value::Int(5)])]),
+            Ok(Value::Int(10))
+        );
+        assert_eq!(vm.call("area", vec![variant(2, vec![])]), Ok(Value::Int(0)));
+    }
+
+    #[test]
+    fn macro_generated_methods_run() {
+        let source = "
+            @error
+            enum LoginError {
+                .NO_HANDLER,
+                .BAD_REQUEST(string),
+            }
+
+            fn describe() -> string {
+                const err LoginError = .BAD_REQUEST(\"no email\")
+                return err.toString()
+            }
+        ";
+
+        assert_eq!(
+            run_source(source, "describe", vec![]),
+            Ok(Value::String("BAD_REQUEST: no email".into()))
+        );
+    }
+
+    #[test]
+    fn matches_ranges_in_order() {
+        let ir = mir::Module {
+            functions: vec![function(
+                "classify",
+                vec![param("code", Type::Unknown)],
+                vec![stmt(StmtKind::Return(Some(Expr::Match {
+                    scrutinee: local("code"),
+                    arms: vec![
+                        MatchArm {
+                            pattern: Pattern::Range {
+                                start: Some(Literal::Int(400)),
+                                end: Some(Literal::Int(499)),
+                            },
+                            body: Expr::Literal(Literal::String("user".into())),
+                        },
+                        MatchArm {
+                            pattern: Pattern::Literal(Literal::Int(500)),
+                            body: Expr::Literal(Literal::String("server".into())),
+                        },
+                        MatchArm {
+                            pattern: Pattern::Range {
+                                start: None,
+                                end: None,
+                            },
+                            body: Expr::Literal(Literal::String("unknown".into())),
+                        },
+                    ],
+                })))],
+            )],
+            ..Default::default()
+        };
+
+        let mut vm = Vm::new(compile_module(&ir).unwrap());
+        for (code, expected) in [
+            (404, "user"),
+            (499, "user"),
+            (500, "server"),
+            (200, "unknown"),
+        ] {
+            assert_eq!(
+                vm.call("classify", vec![Value::Int(code)]),
+                Ok(Value::string(expected))
+            );
+        }
+    }
+
+    #[test]
+    fn natives_and_builtin_methods() {
+        let ir = mir::Module {
+            objects: vec![ObjectDef {
+                span: Span::default(),
+                name: "Graph".into(),
+                generics: vec![],
+                fields: vec![FieldDef {
+                    span: Span::default(),
+                    name: "nodes".into(),
+                    ty: Type::Unknown,
+                    default: Some(Expr::Array(vec![])),
+                    public: true,
+                    tags: vec![],
+                }],
+                implements: vec![],
+                exported: false,
+            }],
+            functions: vec![function(
+                "main",
+                vec![param("graph", Type::Unknown)],
+                vec![
+                    stmt(StmtKind::Expr(Expr::MethodCall {
+                        receiver: Box::new(Expr::Field {
+                            base: local("graph"),
+                            name: "nodes".into(),
+                        }),
+                        method: "append".into(),
+                        args: vec![Expr::Literal(Literal::String("hello".into()))],
+                    })),
+                    stmt(StmtKind::Return(Some(Expr::Call {
+                        callee: Box::new(Expr::Global("describe".into())),
+                        args: vec![Expr::Local("graph".into())],
+                    }))),
+                ],
+            )],
+            ..Default::default()
+        };
+        let module = compile_module(&ir).unwrap();
+        // `graph` has no type so the field has to be looked up by name.
+        assert!(matches!(module.functions[0].code[1], Op::GetFieldNamed(_)));
+
+        let mut vm = Vm::new(module);
+        vm.register_native("describe", |vm, args| {
+            Ok(Value::string(&vm.debug(&args[0])))
+        });
+        let graph = Value::Object(Rc::new(RefCell::new(ObjectValue {
+            type_index: 0,
+            fields: vec![Value::array(vec![])],
+        })));
+
+        assert_eq!(
+            vm.call("main", vec![graph]),
+            Ok(Value::string("Graph { .nodes = [\"hello\"] }"))
+        );
+    }
+
+    #[test]
+    fn integer_arithmetic_that_doesnt_fit_is_an_error() {
+        use Value::Int;
+
+        let overflow = Err("integer overflow".to_string());
+        assert_eq!(binary(Op::Div, Int(i64::MIN), Int(-1)), overflow);
+        assert_eq!(binary(Op::Mod, Int(i64::MIN), Int(-1)), overflow);
+        assert_eq!(
+            binary(Op::Pow, Int(2), Int(-1)),
+            Err("an integer can't be raised to a negative power, use a float".into())
+        );
+        assert_eq!(
+            binary(Op::Pow, Value::Float(2.0), Int(-1)),
+            Ok(Value::Float(0.5))
+        );
+
+        let ir = mir::Module {
+            functions: vec![function(
+                "main",
+                vec![],
+                vec![stmt(StmtKind::Return(Some(Expr::Unary {
+                    op: UnaryOp::Neg,
+                    operand: Box::new(int(i64::MIN)),
+                })))],
+            )],
+            ..Default::default()
+        };
+        let mut vm = Vm::new(compile_module(&ir).unwrap());
+        assert_eq!(
+            vm.call("main", vec![]).map_err(|err| err.message),
+            Err("integer overflow".into())
+        );
+    }
+
+    #[test]
+    fn runtime_errors_carry_the_line() {
+        let ir = mir::Module {
+            functions: vec![function(
+                "main",
+                vec![],
+                vec![Stmt {
+                    span: Span {
+                        start: 0,
+                        end: 0,
+                        line: 7,
+                    },
+                    kind: StmtKind::Return(Some(Expr::Binary {
+                        op: BinaryOp::Div,
+                        lhs: Box::new(int(1)),
+                        rhs: Box::new(int(0)),
+                    })),
+                }],
+            )],
+            ..Default::default()
+        };
+
+        let mut vm = Vm::new(compile_module(&ir).unwrap());
+        assert_eq!(
+            vm.call("main", vec![]),
+            Err(RuntimeError {
+                message: "division by zero".into(),
+                function: "main".into(),
+                line: 7,
+            })
+        );
+    }
+
+    // Loading a module only checks the indexes in its code, these pass that but would read past
+    // the end of an object or the stack if the VM trusted them.
+    #[test]
+    fn bad_modules_from_disk_are_runtime_errors() {
+        let run = |code: Vec<Op>| {
+            let mut module = Module {
+                types: vec![TypeInfo::Object {
+                    name: "Empty".into(),
+                    fields: vec![],
+                }],
+                ..Default::default()
+            };
+            module.add_constant(Constant::Int(1));
+            module.functions.push(FunctionProto {
+                name: "main".into(),
+                arity: 0,
+                locals: 0,
+                captures: vec![],
+                lines: vec![1; code.len()],
+                code,
+                switches: vec![],
+            });
+            let module = Module::from_bytes(&module.to_bytes()).unwrap();
+            Vm::new(module)
+                .call("main", vec![])
+                .map_err(|err| err.message)
+        };
+
+        assert_eq!(
+            run(vec![Op::NewObject(0), Op::GetField(5), Op::Return]),
+            Err("object has no field 5".into())
+        );
+        assert_eq!(
+            run(vec![
+                Op::NewObject(0),
+                Op::Constant(0),
+                Op::SetField(1),
+                Op::Nil,
+                Op::Return
+            ]),
+            Err("object has no field 1".into())
+        );
+        assert_eq!(
+            run(vec![Op::Call(3), Op::Return]),
+            Err("stack underflow".into())
+        );
+        assert_eq!(
+            run(vec![Op::Constant(0), Op::NewArray(2), Op::Return]),
+            Err("stack underflow".into())
+        );
+        assert_eq!(
+            run(vec![Op::CallMethod { name: 0, argc: 1 }, Op::Return]),
+            Err("stack underflow".into())
+        );
+        assert_eq!(
+            run(vec![Op::Dup, Op::Return]),
+            Err("stack underflow".into())
+        );
+    }
+}
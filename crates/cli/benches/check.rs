@@ -0,0 +1,166 @@
+// How fast `elp check` gets through a package, on one thread and on all of them. It writes a
+// package of generated modules importing each other, or checks one that's given:
+//
+//     cargo bench -p elp --bench check
+//     cargo bench -p elp --bench check -- --files 2000
+//     cargo bench -p elp --bench check -- examples/Graph
+//
+// The best of a few runs is reported as files and lines per second. The time is the whole `elp`
+// process, start up included, so small packages mostly measure that.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+    thread,
+    time::{Duration, Instant},
+};
+
+const RUNS: usize = 5;
+
+fn main() {
+    let mut files = 500;
+    let mut package = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--files" => {
+                files = args
+                    .next()
+                    .and_then(|files| files.parse().ok())
+                    .expect("--files takes a number")
+            }
+            // cargo passes `--bench` to every benchmark.
+            arg if arg.starts_with("--") => {}
+            path => package = Some(PathBuf::from(path)),
+        }
+    }
+
+    let generated = tempfile::tempdir().unwrap();
+    let root = match package {
+        Some(package) => package,
+        None => {
+            generate(generated.path(), files);
+            generated.path().to_path_buf()
+        }
+    };
+    let (files, lines) = measure(&root);
+    println!("{}: {} files, {} lines", root.display(), files, lines);
+
+    let cores = thread::available_parallelism().map_or(1, |cores| cores.get());
+    let mut jobs = vec![1, cores];
+    jobs.dedup();
+    for jobs in jobs {
+        let best = (0..RUNS).map(|_| check(&root, jobs)).min().unwrap();
+        let seconds = best.as_secs_f64();
+        println!(
+            "{:>3} threads: {:>8.1}ms {:>10.0} files/s {:>12.0} lines/s",
+            jobs,
+            seconds * 1000.0,
+            files as f64 / seconds,
+            lines as f64 / seconds
+        );
+    }
+}
+
+fn check(root: &Path, jobs: usize) -> Duration {
+    let start = Instant::now();
+    let output = Command::new(env!("CARGO_BIN_EXE_elp"))
+        .arg("check")
+        .arg("--jobs")
+        .arg(jobs.to_string())
+        .arg("--manifest-path")
+        .arg(root)
+        .output()
+        .expect("elp runs");
+    let elapsed = start.elapsed();
+    if !output.status.success() {
+        panic!(
+            "checking {} failed:\n{}",
+            root.display(),
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    elapsed
+}
+
+// A package of `files` modules under `lib/`. Each imports the two before it, so the import graph
+// is deep as well as wide, and `main.elp` imports all of them.
+fn generate(root: &Path, files: usize) {
+    fs::create_dir_all(root.join("lib")).unwrap();
+    fs::write(
+        root.join("Package.elp"),
+        "export Package {\n\t.name = \"bench\",\n\t.entry = \"main.elp\",\n}\n",
+    )
+    .unwrap();
+
+    for module in 0..files {
+        let mut source = String::new();
+        let imported: Vec<usize> = [module.checked_sub(1), module.checked_sub(2)]
+            .into_iter()
+            .flatten()
+            .collect();
+        for &import in &imported {
+            source.push_str(&format!(
+                "import {{ value{0} }} from \"$lib/module{0}\"\n",
+                import
+            ));
+        }
+        source.push_str(&format!(
+            "
+export object Point{0} {{
+\t.x int,
+\t.y int,
+}}
+
+fn Point{0}.sum(self) -> int {{
+\treturn self.x
+}}
+
+fn double{0}(value int) -> int {{
+\tconst twice = value
+\treturn twice
+}}
+
+export fn value{0}(point Point{0}) -> int {{
+\tconst total = point.sum()
+\treturn double{0}(total)
+}}
+",
+            module
+        ));
+        fs::write(root.join(format!("lib/module{}.elp", module)), source).unwrap();
+    }
+
+    let mut main = String::new();
+    for module in 0..files {
+        main.push_str(&format!(
+            "import {{ value{0} }} from \"$lib/module{0}\"\n",
+            module
+        ));
+    }
+    main.push_str("\nfn main {\n}\n");
+    fs::write(root.join("main.elp"), main).unwrap();
+}
+
+fn measure(root: &Path) -> (usize, usize) {
+    let mut files = 0;
+    let mut lines = 0;
+    let mut directories = vec![root.to_path_buf()];
+    while let Some(directory) = directories.pop() {
+        for entry in fs::read_dir(&directory).unwrap().flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                directories.push(path);
+            } else if path
+                .extension()
+                .is_some_and(|extension| extension == "elp" || extension == "velp")
+                && entry.file_name() != "Package.elp"
+            {
+                files += 1;
+                lines += fs::read_to_string(&path).unwrap().lines().count();
+            }
+        }
+    }
+    (files, lines)
+}
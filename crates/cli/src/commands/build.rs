@@ -0,0 +1,272 @@
+// `elp build --target <target>`: checks the package, links it into one module and hands it to a
+// backend. Everything lands in `build/<target>` next to `Package.elp` unless `--out-dir` says
+// otherwise. `#if` directives are decided for `--platform`, one of the package's `.targets`.
+
+use std::{fs, path::PathBuf};
+
+use clap::ValueEnum;
+use elp_bytecode::compiler::compile_module;
+use elp_codegen::{c, llvm, wasm, web};
+use elp_ir::mir::Module;
+use elp_parser::precomp::TARGETS;
+
+use super::{check::check, stop_on_errors, CommandResult, Failure};
+use crate::{
+    diagnostics::{Diagnostic, Message, Reporter},
+    manifest::Manifest,
+    package::{display_path, Package},
+};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, ValueEnum)]
+pub enum Target {
+    // `.elpb` modules for the VM.
+    Bytecode,
+    C,
+    Llvm,
+    Wasm,
+    Web,
+}
+
+impl Target {
+    pub fn name(self) -> &'static str {
+        match self {
+            Target::Bytecode => "bytecode",
+            Target::C => "c",
+            Target::Llvm => "llvm",
+            Target::Wasm => "wasm",
+            Target::Web => "web",
+        }
+    }
+}
+
+// The files a backend produced, by file name.
+fn emit(target: Target, module: &Module) -> Result<Vec<(String, Vec<u8>)>, String> {
+    let name = &module.name;
+    Ok(match target {
+        Target::Bytecode => {
+            let bytecode = compile_module(module).map_err(|err| err.message)?;
+            vec![(format!("{}.elpb", name), bytecode.to_bytes())]
+        }
+        Target::C => {
+            let output = c::emit_module(module).map_err(|err| err.message)?;
+            let mut files = vec![];
+            if let Some(header) = output.header {
+                files.push((format!("{}.h", name), header.into_bytes()));
+            }
+            files.push((format!("{}.c", name), output.source.into_bytes()));
+            files
+        }
+        Target::Llvm => {
+            let output = llvm::emit_module(module).map_err(|err| err.message)?;
+            vec![(format!("{}.ll", name), output.into_bytes())]
+        }
+        Target::Wasm => {
+            let output = wasm::emit_module(module).map_err(|err| err.message)?;
+            vec![
+                (format!("{}.wat", name), output.wat.into_bytes()),
+                (format!("{}.wasm", name), output.wasm),
+            ]
+        }
+        Target::Web => {
+            let output = web::emit_module(module).map_err(|err| err.message)?;
+            let mut files = vec![(format!("{}.js", name), output.script.into_bytes())];
+            if let Some(stylesheet) = output.stylesheet {
+                files.push((format!("{}.css", name), stylesheet.into_bytes()));
+            }
+            if let Some(html) = output.html {
+                files.push(("index.html".into(), html.into_bytes()));
+            }
+            files
+        }
+    })
+}
+
+// The platform `#if` directives are decided for. Without `--platform` the web backend builds for
+// `Web` when the package has it and everything else for the first of the package's targets.
+pub fn platform(
+    manifest: &Manifest,
+    target: Target,
+    platform: Option<String>,
+) -> Result<Option<String>, Failure> {
+    let declared = &manifest.targets;
+    let Some(platform) = platform else {
+        let web = declared.iter().find(|declared| *declared == "Web");
+        return Ok(match target {
+            Target::Web => web.or(declared.first()),
+            _ => declared.first(),
+        }
+        .cloned());
+    };
+
+    if !declared.is_empty() && !declared.contains(&platform) {
+        return Err(Failure::Usage(format!(
+            "`{}` isn't one of the package's targets, it has {}",
+            platform,
+            declared.join(", ")
+        )));
+    }
+    if !TARGETS.contains(&platform.as_str()) {
+        return Err(Failure::Usage(format!(
+            "there's no platform `{}`, it can be one of {}",
+            platform,
+            TARGETS.join(", ")
+        )));
+    }
+    Ok(Some(platform))
+}
+
+pub fn run(
+    manifest: Manifest,
+    target: Target,
+    platform_name: Option<String>,
+    out_dir: Option<PathBuf>,
+    reporter: &mut Reporter,
+) -> CommandResult {
+    let platform = platform(&manifest, target, platform_name)?;
+    let entry = manifest.entry_path();
+    let package = Package::load_for(manifest, platform.as_deref(), &[entry], reporter);
+    check(&package, reporter);
+    stop_on_errors(reporter)?;
+
+    let files = emit(target, &package.link(0)).map_err(|message| {
+        reporter.diagnostic(
+            Diagnostic::error(message)
+                .with_note(format!("reported by the {} backend", target.name())),
+        );
+        Failure::Reported
+    })?;
+
+    let out_dir =
+        out_dir.unwrap_or_else(|| package.manifest.root.join("build").join(target.name()));
+    fs::create_dir_all(&out_dir)
+        .map_err(|err| Failure::Usage(format!("can't create `{}`: {}", out_dir.display(), err)))?;
+    for (name, contents) in files {
+        let path = out_dir.join(name);
+        fs::write(&path, contents)
+            .map_err(|err| Failure::Usage(format!("can't write `{}`: {}", path.display(), err)))?;
+        reporter.emit(Message::Artifact {
+            target: target.name().into(),
+            path: display_path(&path),
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{errors, messages, package, reporter};
+    use pretty_assertions::assert_eq;
+
+    const MAIN: &str = "import { println } from \"elp/stdio\"
+
+export fn greet(name string) -> string {
+	return name
+}
+
+fn main {
+	println(greet(\"hello\"))
+}
+";
+
+    fn artifacts(target: Target) -> Vec<String> {
+        let (dir, manifest) = package(&[("main.elp", MAIN)]);
+        let (mut reporter, out) = reporter();
+
+        assert_eq!(run(manifest, target, None, None, &mut reporter), Ok(()));
+        let build = dir
+            .path()
+            .canonicalize()
+            .unwrap()
+            .join("build")
+            .join(target.name());
+        messages(&out)
+            .iter()
+            .map(|message| {
+                assert_eq!(message["type"], "artifact");
+                let path = PathBuf::from(message["path"].as_str().unwrap());
+                assert!(build.join(path.file_name().unwrap()).is_file());
+                path.file_name().unwrap().to_string_lossy().into_owned()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn builds_every_target() {
+        assert_eq!(artifacts(Target::Bytecode), vec!["test.elpb"]);
+        assert_eq!(artifacts(Target::C), vec!["test.h", "test.c"]);
+        assert_eq!(artifacts(Target::Llvm), vec!["test.ll"]);
+        assert_eq!(artifacts(Target::Wasm), vec!["test.wat", "test.wasm"]);
+        assert_eq!(artifacts(Target::Web), vec!["test.js", "index.html"]);
+    }
+
+    #[test]
+    fn builds_for_a_platform() {
+        let (dir, mut manifest) = package(&[(
+            "main.elp",
+            "#if(iOS)
+fn greeting -> string {
+	return vibrate()
+}
+#else
+fn greeting -> string {
+	return \"hello\"
+}
+
+fn main {
+	greeting()
+}
+",
+        )]);
+        manifest.targets = vec!["Web".into(), "iOS".into()];
+        let build = |platform: Option<&str>| {
+            let (mut reporter, out) = reporter();
+            let out_dir = Some(dir.path().join("out"));
+            let platform = platform.map(String::from);
+            let result = run(
+                manifest.clone(),
+                Target::Web,
+                platform,
+                out_dir,
+                &mut reporter,
+            );
+            (result, errors(&out))
+        };
+
+        assert_eq!(build(None), (Ok(()), vec![]));
+        let (result, errors) = build(Some("iOS"));
+        assert_eq!(result, Err(Failure::Reported));
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            build(Some("Android")).0,
+            Err(Failure::Usage(
+                "`Android` isn't one of the package's targets, it has Web, iOS".into()
+            ))
+        );
+    }
+
+    #[test]
+    fn backend_errors_are_reported() {
+        let (dir, manifest) = package(&[(
+            "main.elp",
+            "import { Text } from \"elp/app\"\n\nfn main {\n\tText(\"hi\") {\n\t}\n}\n",
+        )]);
+        let (mut reporter, out) = reporter();
+        let out_dir = dir.path().join("out");
+
+        let result = run(
+            manifest,
+            Target::C,
+            None,
+            Some(out_dir.clone()),
+            &mut reporter,
+        );
+        assert_eq!(result, Err(Failure::Reported));
+        assert_eq!(
+            errors(&out),
+            vec!["`Text` is an element, elements are only supported by the web backend"]
+        );
+        assert!(!out_dir.exists());
+    }
+}
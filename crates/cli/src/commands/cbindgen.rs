@@ -0,0 +1,125 @@
+// `elp cbindgen`: a C header declaring what the package exports, for C code linking against what
+// `elp build --target llvm` builds. It lands in `build/include/<package>.h` unless `--out-dir`
+// says otherwise. `elp build --target c` writes its own header next to its source.
+
+use std::{fs, path::PathBuf};
+
+use elp_codegen::c;
+
+use super::{
+    build::{platform, Target},
+    check::check,
+    stop_on_errors, CommandResult, Failure,
+};
+use crate::{
+    diagnostics::{Diagnostic, Message, Reporter},
+    manifest::Manifest,
+    package::{display_path, Package},
+};
+
+pub fn run(
+    manifest: Manifest,
+    platform_name: Option<String>,
+    out_dir: Option<PathBuf>,
+    reporter: &mut Reporter,
+) -> CommandResult {
+    let platform = platform(&manifest, Target::Llvm, platform_name)?;
+    let entry = manifest.entry_path();
+    let package = Package::load_for(manifest, platform.as_deref(), &[entry], reporter);
+    check(&package, reporter);
+    stop_on_errors(reporter)?;
+
+    let module = package.link(0);
+    let header = c::emit_header(&module).map_err(|err| {
+        reporter.diagnostic(
+            Diagnostic::error(err.message).with_note("reported while generating the C header"),
+        );
+        Failure::Reported
+    })?;
+    let Some(header) = header else {
+        return Err(Failure::Usage(format!(
+            "`{}` doesn't export any functions or types for C to use",
+            module.name
+        )));
+    };
+
+    let out_dir = out_dir.unwrap_or_else(|| package.manifest.root.join("build").join("include"));
+    fs::create_dir_all(&out_dir)
+        .map_err(|err| Failure::Usage(format!("can't create `{}`: {}", out_dir.display(), err)))?;
+    let path = out_dir.join(format!("{}.h", module.name));
+    fs::write(&path, header)
+        .map_err(|err| Failure::Usage(format!("can't write `{}`: {}", path.display(), err)))?;
+    reporter.emit(Message::Artifact {
+        target: "cbindgen".into(),
+        path: display_path(&path),
+    });
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{messages, package, reporter};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn writes_a_header_for_the_exports() {
+        let (dir, manifest) = package(&[(
+            "main.elp",
+            "export object Counter {
+	.count int32,
+}
+
+export fn Counter.increment(self) -> int32 {
+	return self.count
+}
+
+export fn version() -> int32 {
+	return 1
+}
+
+fn main {
+	version()
+}
+",
+        )]);
+        let (mut reporter, out) = reporter();
+
+        assert_eq!(run(manifest, None, None, &mut reporter), Ok(()));
+        let path = dir
+            .path()
+            .canonicalize()
+            .unwrap()
+            .join("build/include/test.h");
+        assert_eq!(
+            messages(&out)
+                .iter()
+                .map(|message| (message["target"].clone(), message["path"].clone()))
+                .collect::<Vec<_>>(),
+            vec![(
+                "cbindgen".into(),
+                display_path(&path).to_string_lossy().into()
+            )]
+        );
+        let header = fs::read_to_string(path).unwrap();
+        assert!(header.contains("struct Counter {\n    int32_t count;\n};\n"));
+        assert!(header.contains(
+            "int32_t Counter_increment(Counter *self) ELP_SYMBOL(\"Counter.increment\");\n"
+        ));
+        assert!(header.contains("int32_t version(void);\n"));
+    }
+
+    #[test]
+    fn there_has_to_be_something_exported() {
+        let (_dir, manifest) = package(&[("main.elp", "fn main {}\n")]);
+        let (mut reporter, _) = reporter();
+
+        assert_eq!(
+            run(manifest, None, None, &mut reporter),
+            Err(Failure::Usage(
+                "`test` doesn't export any functions or types for C to use".into()
+            ))
+        );
+    }
+}
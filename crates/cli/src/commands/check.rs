@@ -0,0 +1,180 @@
+// `elp check`: parse, resolve and type-check every file reachable from the package's entry
+// without building anything.
+
+use elp_codegen::check::warnings;
+use rayon::prelude::*;
+
+use super::{stop_on_errors, CommandResult};
+use crate::{
+    diagnostics::{Diagnostic, Reporter},
+    manifest::Manifest,
+    package::Package,
+};
+
+pub fn run(manifest: Manifest, reporter: &mut Reporter) -> CommandResult {
+    let entry = manifest.entry_path();
+    let package = Package::load(manifest, &[entry], reporter);
+    check(&package, reporter);
+    stop_on_errors(reporter)?;
+
+    reporter.status(format!(
+        "Checked {} ({} files)",
+        package.manifest.name,
+        package.files.len()
+    ));
+    Ok(())
+}
+
+// Checks every loaded file's imports and types, anything wrong is reported. Each layer of the
+// import graph is checked in parallel once the one before it is done. What's found is reported in
+// the order the files were loaded, whichever thread checked them.
+pub fn check(package: &Package, reporter: &mut Reporter) {
+    let mut found: Vec<Vec<Diagnostic>> = vec![vec![]; package.files.len()];
+    for layer in package.layers() {
+        let checked: Vec<Vec<Diagnostic>> = layer
+            .par_iter()
+            .map(|&index| check_file(package, index))
+            .collect();
+        for (index, diagnostics) in layer.into_iter().zip(checked) {
+            found[index] = diagnostics;
+        }
+    }
+
+    for diagnostic in found.into_iter().flatten() {
+        reporter.diagnostic(diagnostic);
+    }
+}
+
+fn check_file(package: &Package, index: usize) -> Vec<Diagnostic> {
+    let file = &package.files[index];
+    let warnings = warnings(&file.module).into_iter().map(|warning| {
+        Diagnostic::warning(warning.message)
+            .in_file(&file.file.path)
+            .at_span(&file.file.source, warning.span)
+    });
+    package
+        .problems(index)
+        .into_iter()
+        .chain(warnings)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        commands::Failure,
+        test_support::{errors, messages, package, reporter},
+    };
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn checks_every_file_in_the_package() {
+        let (_dir, manifest) = package(&[
+            (
+                "main.elp",
+                "import { greet } from \"$greeting\"
+
+fn main {
+	greet(\"hello\", \"world\")
+	missing()
+}
+",
+            ),
+            (
+                "greeting.elp",
+                "export fn greet(name string) -> string {
+	const greeting = name
+	greeting = \"hi\"
+	return greeting
+}
+",
+            ),
+        ]);
+        let (mut reporter, out) = reporter();
+
+        assert_eq!(run(manifest, &mut reporter), Err(Failure::Reported));
+        assert_eq!(
+            errors(&out),
+            vec![
+                "`greet` takes 1 arguments but 2 were given",
+                "`missing` isn't defined",
+                "`greeting` is a constant and can't be assigned to",
+            ]
+        );
+        let files = messages(&out)
+            .iter()
+            .map(|message| message["file"].as_str().unwrap().to_string())
+            .collect::<Vec<_>>();
+        assert!(files[0].ends_with("main.elp") && files[2].ends_with("greeting.elp"));
+    }
+
+    #[test]
+    fn clean_packages_pass() {
+        let (_dir, manifest) = package(&[(
+            "main.elp",
+            "import { println } from \"elp/stdio\"
+
+fn main {
+	println(\"hello\")
+}
+",
+        )]);
+        let (mut reporter, out) = reporter();
+
+        assert_eq!(run(manifest, &mut reporter), Ok(()));
+        assert_eq!(out.text(), "");
+    }
+
+    #[test]
+    fn warnings_are_reported_without_failing() {
+        let (_dir, manifest) = package(&[(
+            "main.elp",
+            "import { Form, Input } from \"elp/ui\"
+
+component LoginForm() {
+	var attempts = 0
+	var email = \"\"
+
+	fn handleSubmit() {
+		attempts += 1
+	}
+
+	return Form(onSubmit = handleSubmit) {
+		Input(value = email) {}
+	}
+}
+",
+        )]);
+        let (mut reporter, out) = reporter();
+
+        assert_eq!(run(manifest, &mut reporter), Ok(()));
+        let warnings = messages(&out);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0]["severity"], "warning");
+        assert_eq!(
+            warnings[0]["message"],
+            "`attempts` is changed but never rendered, it doesn't need to be a `var`"
+        );
+        assert_eq!(warnings[0]["line"], 4);
+    }
+
+    #[test]
+    fn misspelt_tags_are_warnings() {
+        let (_dir, manifest) = package(&[(
+            "main.elp",
+            "object Post {\n\t.title string `jsno: \"title\"`,\n}\n",
+        )]);
+        let (mut reporter, out) = reporter();
+
+        assert_eq!(run(manifest, &mut reporter), Ok(()));
+        let warnings = messages(&out);
+        assert_eq!(
+            (&warnings[0]["message"], &warnings[0]["line"]),
+            (
+                &"nothing reads a `jsno` tag, did you mean `json`?".into(),
+                &2.into()
+            )
+        );
+    }
+}
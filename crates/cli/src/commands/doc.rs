@@ -0,0 +1,236 @@
+// `elp doc`: API documentation for the package from its `///` comments, as HTML or Markdown in
+// `build/doc` unless `--out-dir` says otherwise. Every source file is a module, tests and the
+// manifest aren't documented.
+
+use std::{fs, path::PathBuf};
+
+use clap::ValueEnum;
+use elp_db::queries::module_name;
+use elp_parser::ast::{module::ASTModule, traits::FromCST};
+
+use super::{stop_on_errors, CommandResult, Failure};
+use crate::{
+    diagnostics::{Message, Reporter},
+    doc::{html, markdown, DocModule, DocPackage},
+    manifest::{Manifest, MANIFEST},
+    package::{display_path, parse, source_files, SourceFile},
+};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, ValueEnum)]
+pub enum Format {
+    Html,
+    Markdown,
+}
+
+impl Format {
+    pub fn name(self) -> &'static str {
+        match self {
+            Format::Html => "html",
+            Format::Markdown => "markdown",
+        }
+    }
+}
+
+pub fn run(
+    manifest: Manifest,
+    format: Format,
+    out_dir: Option<PathBuf>,
+    private: bool,
+    reporter: &mut Reporter,
+) -> CommandResult {
+    let mut package = DocPackage {
+        name: manifest.name.clone(),
+        modules: vec![],
+    };
+
+    for path in source_files(&manifest.root) {
+        let is_test = path.to_string_lossy().ends_with(".test.elp");
+        if is_test || path == manifest.root.join(MANIFEST) {
+            continue;
+        }
+
+        let file = SourceFile::read(&path).map_err(|err| Failure::Usage(err.message))?;
+        let cst = match parse(&file) {
+            Ok(cst) => cst,
+            Err(diagnostic) => {
+                reporter.diagnostic(diagnostic);
+                continue;
+            }
+        };
+        let module = DocModule::from_ast(
+            &module_name(&manifest.root, &path),
+            &ASTModule::from_cst(&cst),
+            private,
+        );
+        if !module.items.is_empty() {
+            package.modules.push(module);
+        }
+    }
+    stop_on_errors(reporter)?;
+
+    let pages = match format {
+        Format::Html => html::render(&package),
+        Format::Markdown => markdown::render(&package),
+    };
+
+    let out_dir = out_dir.unwrap_or_else(|| manifest.root.join("build").join("doc"));
+    for (name, contents) in pages {
+        let path = out_dir.join(name);
+        let parent = path
+            .parent()
+            .expect("pages are inside the output directory");
+        fs::create_dir_all(parent).map_err(|err| {
+            Failure::Usage(format!("can't create `{}`: {}", parent.display(), err))
+        })?;
+        fs::write(&path, contents)
+            .map_err(|err| Failure::Usage(format!("can't write `{}`: {}", path.display(), err)))?;
+        reporter.emit(Message::Artifact {
+            target: format.name().into(),
+            path: display_path(&path),
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{errors, messages, package, reporter};
+    use pretty_assertions::assert_eq;
+
+    const SHAPES: &str = "import { Named } from \"$lib/names\"
+
+/// Something with an area.
+///
+/// For example:
+///     square.area()
+export interface Shape {
+	fn area(self) -> float
+}
+
+/// A square with a name.
+export object Square implements Shape, Named {
+	/// How long each side is.
+	public .side float,
+	.name string
+}
+
+/// How much room it takes up.
+fn Square.area(self) -> float {
+	return self.side
+}
+
+fn helper(a int) -> int {
+	return a
+}
+";
+
+    const NAMES: &str = "/// Things people call by name.
+export interface Named {
+	.name string
+}
+";
+
+    fn written(format: Format, private: bool) -> (tempfile::TempDir, Vec<String>) {
+        let (dir, manifest) = package(&[
+            ("main.elp", SHAPES),
+            ("lib/names.elp", NAMES),
+            (
+                "main.test.elp",
+                "/// Not documented.\nexport fn test_it {\n}\n",
+            ),
+        ]);
+        let (mut reporter, out) = reporter();
+
+        assert_eq!(run(manifest, format, None, private, &mut reporter), Ok(()));
+        let doc = dir.path().canonicalize().unwrap().join("build").join("doc");
+        let paths = messages(&out)
+            .iter()
+            .map(|message| {
+                assert_eq!(message["type"], "artifact");
+                assert_eq!(message["target"], format.name());
+                let path = PathBuf::from(message["path"].as_str().unwrap());
+                let relative = path.strip_prefix(&doc).unwrap();
+                assert!(doc.join(relative).is_file());
+                relative.to_string_lossy().replace('\\', "/")
+            })
+            .collect();
+        (dir, paths)
+    }
+
+    fn page(dir: &tempfile::TempDir, name: &str) -> String {
+        fs::read_to_string(dir.path().join("build").join("doc").join(name)).unwrap()
+    }
+
+    #[test]
+    fn writes_html() {
+        let (dir, paths) = written(Format::Html, false);
+        assert_eq!(paths, vec!["index.html", "lib/names.html", "main.html"]);
+
+        let main = page(&dir, "main.html");
+        assert!(main.contains("<a href=\"index.html\">Index</a>"));
+        assert!(main.contains(
+            "<pre class=\"signature\"><code>object Square implements <a href=\"#Shape\">Shape</a>, <a href=\"lib/names.html#Named\">Named</a></code></pre>"
+        ));
+        assert!(main.contains("<p>A square with a name.</p>"));
+        assert!(main.contains("<dt id=\"Square.side\"><code>public .side float</code></dt>\n<dd><p>How long each side is.</p>\n</dd>"));
+        assert!(main.contains("<div id=\"Square.area\">\n<pre class=\"signature\"><code>fn Square.area(self) -&gt; float</code></pre>\n<p>How much room it takes up.</p>"));
+        assert!(main.contains("<pre><code>square.area()</code></pre>"));
+        assert!(!main.contains("helper"));
+
+        let names = page(&dir, "lib/names.html");
+        assert!(names.contains("<a href=\"../index.html\">Index</a>"));
+        assert!(names.contains(
+            "<h4>Implemented by</h4>\n<ul>\n<li><a href=\"../main.html#Square\"><code>Square</code></a></li>"
+        ));
+
+        let index = page(&dir, "index.html");
+        assert!(index.contains(
+            "<a href=\"main.html#Square\"><code>Square</code></a> A square with a name.</li>"
+        ));
+    }
+
+    #[test]
+    fn writes_markdown() {
+        let (dir, paths) = written(Format::Markdown, true);
+        assert_eq!(paths, vec!["index.md", "lib/names.md", "main.md"]);
+
+        let main = page(&dir, "main.md");
+        assert!(main.contains(
+            "\n<a id=\"Shape\"></a>\n### Shape\n\n<pre><code>interface Shape</code></pre>\n\nSomething with an area.\n\nFor example:\n    square.area()\n"
+        ));
+        assert!(main.contains(
+            "- <a id=\"Square.side\"></a><code>public .side float</code> How long each side is.\n"
+        ));
+        assert!(main.contains("<pre><code>fn helper(a int) -&gt; int</code></pre>"));
+
+        let names = page(&dir, "lib/names.md");
+        assert!(names.starts_with("[Index](../index.md)\n\n# Module `lib/names`\n"));
+        assert!(names.contains("#### Implemented by\n\n- [`Square`](../main.md#Square)\n"));
+
+        assert_eq!(
+            page(&dir, "index.md"),
+            "# test
+
+- [`lib/names`](lib/names.md)
+  - [`Named`](lib/names.md#Named) Things people call by name.
+- [`main`](main.md)
+  - [`Shape`](main.md#Shape) Something with an area.
+  - [`Square`](main.md#Square) A square with a name.
+  - [`helper`](main.md#helper)
+"
+        );
+    }
+
+    #[test]
+    fn parse_errors_stop_it() {
+        let (dir, manifest) = package(&[("main.elp", "export object {\n")]);
+        let (mut reporter, out) = reporter();
+
+        let result = run(manifest, Format::Html, None, false, &mut reporter);
+        assert_eq!(result, Err(Failure::Reported));
+        assert_eq!(errors(&out).len(), 1);
+        assert!(!dir.path().join("build").exists());
+    }
+}
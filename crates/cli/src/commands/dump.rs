@@ -0,0 +1,246 @@
+// `elp dump --stage <stage>`: prints what the compiler makes of a file at one stage. `hir` is the
+// module straight out of lowering, `mir` is after monomorphization, which is what the native
+// backends actually see. `styles` is each component's elements with the properties the app's global
+// styles and then the file's own stylesheets give them, as a backend without a browser's cascade
+// styles them.
+
+use std::path::PathBuf;
+
+use clap::ValueEnum;
+use elp_codegen::mono::monomorphize;
+use elp_db::queries::{module_name, package_root};
+use elp_ir::{
+    cascade::{global_styles_import, Cascade, StyleNode},
+    mir::Module,
+    style::StyleSheet,
+};
+use elp_parser::ast::{module::ASTModule, traits::FromCST};
+
+use super::{find_manifest, CommandResult, Failure};
+use crate::{
+    diagnostics::{Diagnostic, Message, Reporter},
+    package::{lower, parse, stylesheet, SourceFile},
+};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, ValueEnum)]
+pub enum Stage {
+    Cst,
+    Ast,
+    Hir,
+    Mir,
+    Styles,
+}
+
+impl Stage {
+    fn name(self) -> &'static str {
+        match self {
+            Stage::Cst => "cst",
+            Stage::Ast => "ast",
+            Stage::Hir => "hir",
+            Stage::Mir => "mir",
+            Stage::Styles => "styles",
+        }
+    }
+}
+
+fn dump(stage: Stage, file: &SourceFile) -> Result<String, Diagnostic> {
+    let name = file
+        .path
+        .file_stem()
+        .map_or("main".into(), |stem| stem.to_string_lossy().into_owned());
+
+    Ok(match stage {
+        Stage::Cst => format!("{:#?}", parse(file)?),
+        Stage::Ast => format!("{:#?}", ASTModule::from_cst(&parse(file)?)),
+        Stage::Hir => format!("{:#?}", lower(file, &name)?),
+        Stage::Mir => {
+            let module = monomorphize(&lower(file, &name)?).map_err(|err| {
+                Diagnostic::error(err.message)
+                    .in_file(&file.path)
+                    .at_span(&file.source, err.span)
+            })?;
+            format!("{:#?}", module)
+        }
+        Stage::Styles => {
+            let module = lower(file, &name)?;
+            let global = global_sheet(file, &module)?;
+            let cascade = match &global {
+                Some((origin, sheet)) => Cascade::new().with_sheet(origin.clone(), sheet),
+                None => Cascade::new(),
+            }
+            .with_sheet(name, &module.styles);
+            module
+                .components
+                .iter()
+                .map(|component| {
+                    let tree = cascade.resolve(&StyleNode::from_body(&component.body));
+                    let elements: String = tree.iter().map(ToString::to_string).collect();
+                    format!("component {}\n{}", component.name, elements)
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+        }
+    })
+}
+
+// The `.selp` file the module's `App` takes its global styles from, along with its module name.
+fn global_sheet(
+    file: &SourceFile,
+    module: &Module,
+) -> Result<Option<(String, StyleSheet)>, Diagnostic> {
+    let Some(module_path) = global_styles_import(module) else {
+        return Ok(None);
+    };
+    let root = package_root(&file.path);
+    let relative = module_path.trim_start_matches('$').trim_start_matches('/');
+    let path = root.join(relative).with_extension("selp");
+    let sheet = SourceFile::read(&path)?;
+
+    Ok(Some((module_name(&root, &path), stylesheet(&sheet)?)))
+}
+
+// Dumps `file`, or the package's entry when there isn't one.
+pub fn run(
+    stage: Stage,
+    file: Option<PathBuf>,
+    manifest_path: Option<PathBuf>,
+    reporter: &mut Reporter,
+) -> CommandResult {
+    let path = match file {
+        Some(path) => path,
+        None => find_manifest(manifest_path.as_deref(), reporter)?.entry_path(),
+    };
+    let file = SourceFile::read(&path).map_err(|err| Failure::Usage(err.message))?;
+
+    match dump(stage, &file) {
+        Ok(output) => {
+            reporter.emit(Message::Dump {
+                stage: stage.name().into(),
+                file: file.path,
+                output,
+            });
+            Ok(())
+        }
+        Err(diagnostic) => {
+            reporter.diagnostic(diagnostic);
+            Err(Failure::Reported)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{messages, package, reporter};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn dumps_each_stage() {
+        let (_dir, manifest) = package(&[(
+            "main.elp",
+            "fn identity<T>(value T) -> T {\n\treturn value\n}\n\nfn main {\n\tidentity(1)\n}\n",
+        )]);
+
+        let outputs = [Stage::Cst, Stage::Ast, Stage::Hir, Stage::Mir].map(|stage| {
+            let (mut reporter, out) = reporter();
+            assert_eq!(
+                run(stage, None, Some(manifest.root.clone()), &mut reporter),
+                Ok(())
+            );
+            let messages = messages(&out);
+            assert_eq!(messages[0]["stage"], stage.name());
+            messages[0]["output"].as_str().unwrap().to_string()
+        });
+
+        assert!(outputs[0].starts_with("CSTModule {"));
+        assert!(outputs[1].starts_with("ASTModule {"));
+        assert!(outputs[2].starts_with("Module {") && outputs[2].contains("\"identity\""));
+        // Monomorphization replaces the generic function with an instance for int64.
+        assert!(!outputs[3].contains("name: \"identity\","));
+    }
+
+    #[test]
+    fn dumps_the_styles_each_element_ends_up_with() {
+        let (_dir, manifest) = package(&[(
+            "main.elp",
+            "component Home {
+	return Column(class = \"page\") {
+		Text(\"hello\")
+	}
+}
+
+Stylesheet {
+	Column { .color = #333, .padding = 8 }
+	.page { .padding = 16 }
+	Text { .fontWeight = .BOLD }
+}
+",
+        )]);
+        let (mut reporter, out) = reporter();
+
+        assert_eq!(
+            run(
+                Stage::Styles,
+                None,
+                Some(manifest.root.clone()),
+                &mut reporter
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            messages(&out)[0]["output"],
+            "component Home
+Column.page
+	.color = #333333  // Column in main:8
+	.padding = 16  // .page in main:9
+	Text
+		.color = #333333  // inherited from Column in main:8
+		.fontWeight = .BOLD  // Text in main:10
+"
+        );
+    }
+
+    #[test]
+    fn global_styles_come_before_the_files_own() {
+        let (_dir, manifest) = package(&[
+            (
+                "main.velp",
+                "import { Styles } from \"$/styles\"
+
+component Main {
+	return App(globalStyles = Styles()) {
+		Text(\"hello\")
+	}
+}
+
+Stylesheet {
+	Text { .padding = 16 }
+}
+",
+            ),
+            ("styles.selp", "Text { .padding = 8, .color = #333 }\n"),
+        ]);
+        let (mut reporter, out) = reporter();
+
+        assert_eq!(
+            run(
+                Stage::Styles,
+                Some(manifest.root.join("main.velp")),
+                None,
+                &mut reporter
+            ),
+            Ok(())
+        );
+        // The global rule is as specific as the file's, so the file's wins where they both set
+        // something.
+        assert_eq!(
+            messages(&out)[0]["output"],
+            "component Main
+App
+	Text
+		.color = #333333  // Text in styles:1
+		.padding = 16  // Text in main:10
+"
+        );
+    }
+}
@@ -0,0 +1,209 @@
+// `elp fmt`: rewrites files in the canonical layout, or with `--check` only reports the ones that
+// aren't. The layout itself lives in `elp_parser::formatter`, this is just the files and flags.
+// `--range` formats only the top level items touching a span of lines in a single file, for
+// editors that format a selection.
+
+use std::{fs, ops::Range, path::PathBuf};
+
+use elp_db::database::Database;
+use elp_parser::formatter::{format_range, format_source};
+
+use super::{find_manifest, stop_on_errors, CommandResult, Failure};
+use crate::{
+    diagnostics::{Message, Reporter},
+    package::{display_path, parse_error, source_files, SourceFile},
+};
+
+// A range of lines as given on the command line, `START:END`, 1-based and inclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl std::str::FromStr for LineRange {
+    type Err = String;
+
+    fn from_str(range: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("`{}` isn't a range of lines like `3:12`", range);
+        let (start, end) = range.split_once(':').ok_or_else(invalid)?;
+        let start = start.trim().parse().map_err(|_| invalid())?;
+        let end = end.trim().parse().map_err(|_| invalid())?;
+        match start >= 1 && start <= end {
+            true => Ok(LineRange { start, end }),
+            false => Err(invalid()),
+        }
+    }
+}
+
+impl LineRange {
+    // The bytes from the start of the first line to the end of the last, clamped to the source.
+    fn offsets(&self, source: &str) -> Range<usize> {
+        let mut starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(offset, _)| offset + 1))
+            .skip(self.start - 1);
+        let start = starts.next().unwrap_or(source.len());
+        let end = starts
+            .nth(self.end - self.start)
+            .map(|next| next - 1)
+            .unwrap_or(source.len());
+
+        start..end.max(start)
+    }
+}
+
+pub fn run(
+    files: Vec<PathBuf>,
+    check: bool,
+    range: Option<LineRange>,
+    manifest_path: Option<PathBuf>,
+    reporter: &mut Reporter,
+) -> CommandResult {
+    if range.is_some() && files.len() != 1 {
+        return Err(Failure::Usage("`--range` needs exactly one file".into()));
+    }
+
+    let files = match files.is_empty() {
+        true => source_files(&find_manifest(manifest_path.as_deref(), reporter)?.root),
+        false => files,
+    };
+
+    // Files are read and parsed through the database, the same as every other command.
+    let db = Database::new();
+    let mut unformatted = false;
+    for path in files {
+        let Some(source) = db.source(&path).as_ref().clone() else {
+            return Err(Failure::Usage(format!("can't read `{}`", path.display())));
+        };
+        let file = SourceFile {
+            path: display_path(&path),
+            source,
+        };
+        // Only well formed files are touched, the formatter can't know what broken ones meant.
+        if let Err(problem) = db.parse(&path).as_ref() {
+            reporter.diagnostic(file.problem(problem));
+            continue;
+        }
+        let formatted = match range {
+            Some(range) => format_range(&file.source, range.offsets(&file.source)),
+            None => format_source(&file.source),
+        };
+        let formatted = match formatted {
+            Ok(formatted) => formatted,
+            Err(err) => {
+                reporter.diagnostic(parse_error(&file.path, &file.source, *err));
+                continue;
+            }
+        };
+
+        let changed = formatted != file.source;
+        if changed && !check {
+            fs::write(&path, formatted).map_err(|err| {
+                Failure::Usage(format!("can't write `{}`: {}", path.display(), err))
+            })?;
+        }
+        unformatted |= changed && check;
+        reporter.emit(Message::Formatted {
+            file: display_path(&file.path),
+            changed,
+            check,
+        });
+    }
+
+    stop_on_errors(reporter)?;
+    match unformatted {
+        true => Err(Failure::Reported),
+        false => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{errors, messages, package, reporter};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn line_ranges() {
+        let source = "one\ntwo\nthree\n";
+        let range = |range: &str| range.parse::<LineRange>().unwrap().offsets(source);
+
+        assert_eq!(range("1:1"), 0..3);
+        assert_eq!(range("2:3"), 4..13);
+        assert_eq!(range("3:9"), 8..14);
+        assert_eq!(range("9:9"), 14..14);
+        assert!("3".parse::<LineRange>().is_err());
+        assert!("0:2".parse::<LineRange>().is_err());
+        assert!("4:2".parse::<LineRange>().is_err());
+    }
+
+    #[test]
+    fn checks_and_rewrites_packages() {
+        let (dir, manifest) = package(&[
+            ("main.elp", "fn main {  \n}\n\n"),
+            ("lib/ok.elp", "fn ok {}\n"),
+        ]);
+        let main = dir.path().join("main.elp");
+
+        let (mut checking, out) = reporter();
+        let result = run(
+            vec![],
+            true,
+            None,
+            Some(manifest.root.clone()),
+            &mut checking,
+        );
+        assert_eq!(result, Err(Failure::Reported));
+        assert_eq!(
+            messages(&out)
+                .iter()
+                .filter(|message| message["changed"] == true)
+                .count(),
+            1
+        );
+        assert_eq!(fs::read_to_string(&main).unwrap(), "fn main {  \n}\n\n");
+
+        let (mut rewriting, _) = reporter();
+        assert_eq!(
+            run(vec![main.clone()], false, None, None, &mut rewriting),
+            Ok(())
+        );
+        assert_eq!(fs::read_to_string(&main).unwrap(), "fn main {}\n");
+    }
+
+    #[test]
+    fn formats_a_range_of_lines() {
+        let source = "fn one {\n1\n}\n\nfn two {\n2\n}\n";
+        let (dir, _) = package(&[("main.elp", source)]);
+        let main = dir.path().join("main.elp");
+        let (mut reporter, _) = reporter();
+
+        let range = Some("5:5".parse().unwrap());
+        assert_eq!(
+            run(vec![main.clone()], false, range, None, &mut reporter),
+            Ok(())
+        );
+        assert_eq!(
+            fs::read_to_string(&main).unwrap(),
+            "fn one {\n1\n}\n\nfn two {\n\t2\n}\n"
+        );
+        assert_eq!(
+            run(vec![], false, range, None, &mut reporter),
+            Err(Failure::Usage("`--range` needs exactly one file".into()))
+        );
+    }
+
+    #[test]
+    fn leaves_broken_files_alone() {
+        let (dir, _) = package(&[("main.elp", "fn main {  \n")]);
+        let main = dir.path().join("main.elp");
+        let (mut reporter, out) = reporter();
+
+        assert_eq!(
+            run(vec![main.clone()], false, None, None, &mut reporter),
+            Err(Failure::Reported)
+        );
+        assert_eq!(errors(&out).len(), 1);
+        assert_eq!(fs::read_to_string(&main).unwrap(), "fn main {  \n");
+    }
+}
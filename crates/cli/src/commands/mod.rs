@@ -0,0 +1,57 @@
+pub mod build;
+pub mod cbindgen;
+pub mod check;
+pub mod doc;
+pub mod dump;
+pub mod fmt;
+pub mod run;
+pub mod test;
+
+use std::path::{Path, PathBuf};
+
+use crate::{diagnostics::Reporter, manifest::Manifest, package::SourceFile};
+
+// How a command failed, each maps to one of the exit codes in `main`.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Failure {
+    // Errors in the code, failing tests or unformatted files. All of it has been reported.
+    Reported,
+    // Bad arguments or files that couldn't be found, read or written.
+    Usage(String),
+    // `elp run` hands back the exit code of the program it ran.
+    Exit(u8),
+}
+
+pub type CommandResult = Result<(), Failure>;
+
+// The package a command works on, `--manifest-path` or the closest `Package.elp` to the working
+// directory.
+pub fn find_manifest(
+    manifest_path: Option<&Path>,
+    reporter: &mut Reporter,
+) -> Result<Manifest, Failure> {
+    let start = manifest_path.map_or_else(|| PathBuf::from("."), Path::to_path_buf);
+    let Some(path) = Manifest::find(&start) else {
+        return Err(Failure::Usage(format!(
+            "can't find `Package.elp` in `{}` or any of its parents",
+            start.display()
+        )));
+    };
+
+    let file = SourceFile::read(&path).map_err(|err| Failure::Usage(err.message))?;
+    let mut manifest = Manifest::parse(&file.path, &file.source).map_err(|diagnostic| {
+        reporter.diagnostic(diagnostic);
+        Failure::Reported
+    })?;
+    // `file.path` is for showing, it's empty when the package is the working directory.
+    manifest.root = path.parent().expect("manifests are files").to_path_buf();
+    Ok(manifest)
+}
+
+// Commands stop after a stage that reported errors rather than carrying on with broken input.
+pub fn stop_on_errors(reporter: &Reporter) -> CommandResult {
+    match reporter.error_count() {
+        0 => Ok(()),
+        _ => Err(Failure::Reported),
+    }
+}
@@ -0,0 +1,135 @@
+// `elp run`: builds the package to bytecode and runs its `main` function in the VM. When `main`
+// returns an int it becomes the exit code, like C.
+
+use std::{cell::RefCell, io::Write, rc::Rc};
+
+use elp_bytecode::{
+    compiler::compile_module,
+    vm::{RuntimeError, Value, Vm},
+};
+
+use super::{check::check, stop_on_errors, CommandResult, Failure};
+use crate::{
+    diagnostics::{Diagnostic, Reporter},
+    manifest::Manifest,
+    package::Package,
+};
+
+pub type Output = Rc<RefCell<dyn Write>>;
+
+// The functions programs get from the host, `println` and `print` write to `out`.
+pub fn register_natives(vm: &mut Vm, out: &Output) {
+    for (name, newline) in [("println", true), ("print", false)] {
+        let out = out.clone();
+        vm.register_native(name, move |vm, args| {
+            let text = args
+                .iter()
+                .map(|arg| vm.display(arg))
+                .collect::<Vec<_>>()
+                .join(" ");
+            let mut out = out.borrow_mut();
+            let written = match newline {
+                true => writeln!(out, "{}", text),
+                false => write!(out, "{}", text),
+            };
+            written.map_err(|err| err.to_string())?;
+            Ok(Value::Nil)
+        });
+    }
+}
+
+pub fn runtime_error(err: RuntimeError) -> Diagnostic {
+    Diagnostic::error(err.message).with_note(format!("in `{}` on line {}", err.function, err.line))
+}
+
+pub fn run(manifest: Manifest, reporter: &mut Reporter) -> CommandResult {
+    let out: Output = Rc::new(RefCell::new(std::io::stdout()));
+    run_with_output(manifest, reporter, &out)
+}
+
+pub fn run_with_output(manifest: Manifest, reporter: &mut Reporter, out: &Output) -> CommandResult {
+    let entry = manifest.entry_path();
+    let package = Package::load(manifest, &[entry], reporter);
+    check(&package, reporter);
+    stop_on_errors(reporter)?;
+
+    let entry = &package.files[0];
+    if entry.module.function("main").is_none() {
+        reporter.diagnostic(
+            Diagnostic::error("there's no `main` function to run").in_file(&entry.file.path),
+        );
+        return Err(Failure::Reported);
+    }
+
+    let bytecode = compile_module(&package.link(0)).map_err(|err| {
+        reporter.diagnostic(
+            Diagnostic::error(err.message).with_note("reported by the bytecode compiler"),
+        );
+        Failure::Reported
+    })?;
+    let mut vm = Vm::new(bytecode);
+    register_natives(&mut vm, out);
+
+    match vm.call("main", vec![]) {
+        Ok(Value::Int(0)) | Ok(Value::Nil) => Ok(()),
+        Ok(Value::Int(code)) => Err(Failure::Exit(code.clamp(1, 255) as u8)),
+        Ok(_) => Ok(()),
+        Err(err) => {
+            reporter.diagnostic(runtime_error(err));
+            Err(Failure::Reported)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{errors, package, reporter, Captured};
+    use pretty_assertions::assert_eq;
+
+    fn run_package(main: &str) -> (CommandResult, String, Vec<String>) {
+        let (_dir, manifest) = package(&[("main.elp", main)]);
+        let (mut reporter, out) = reporter();
+        let stdout = Captured::default();
+        let output: Output = Rc::new(RefCell::new(stdout.clone()));
+
+        let result = run_with_output(manifest, &mut reporter, &output);
+        (result, stdout.text(), errors(&out))
+    }
+
+    #[test]
+    fn runs_main() {
+        let (result, stdout, errors) = run_package(
+            "import { println, print } from \"elp/stdio\"
+
+fn greet(name string) -> string {
+	return name
+}
+
+fn main {
+	print(\"hello \")
+	println(greet(\"world\"), 42)
+}
+",
+        );
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(stdout, "hello world 42\n");
+        assert_eq!(errors, Vec::<String>::new());
+    }
+
+    #[test]
+    fn int_results_are_the_exit_code() {
+        let (result, _, _) = run_package("fn main -> int {\n\treturn 3\n}\n");
+
+        assert_eq!(result, Err(Failure::Exit(3)));
+    }
+
+    #[test]
+    fn needs_a_main_function() {
+        let (result, _, errors) = run_package("fn start {\n}\n");
+
+        assert_eq!(result, Err(Failure::Reported));
+        assert_eq!(errors, vec!["there's no `main` function to run"]);
+    }
+}
@@ -0,0 +1,365 @@
+// `elp test`: finds the package's `*.test.elp` files and runs each function in them whose name
+// starts with `test_` in a fresh VM. A test fails when it hits a runtime error, including a failed
+// `assert` or `assert_equal`, or returns `false`. The results can also be written as JUnit XML,
+// and which lines the tests ran as lcov.
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::Instant,
+};
+
+use elp_bytecode::{
+    compiler::compile_module,
+    vm::{Value, Vm},
+};
+
+use super::{
+    check::check,
+    run::{register_natives, Output},
+    stop_on_errors, CommandResult, Failure,
+};
+use crate::{
+    diagnostics::{Diagnostic, Message, Reporter, TestStatus},
+    manifest::Manifest,
+    package::{display_path, source_files, Package},
+    testing::{
+        coverage::Coverage,
+        diff,
+        junit::{self, Case},
+    },
+};
+
+pub fn is_test_file(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name.to_string_lossy().ends_with(".test.elp"))
+}
+
+fn register_assertions(vm: &mut Vm) {
+    vm.register_native("assert", |_, args| match args {
+        [Value::Bool(true)] => Ok(Value::Nil),
+        [Value::Bool(false)] => Err("assertion failed".into()),
+        _ => Err("`assert` takes a single bool".into()),
+    });
+    vm.register_native("assert_equal", |vm, args| match args {
+        [actual, expected] if actual == expected => Ok(Value::Nil),
+        [actual, expected] => Err(diff::describe(vm, actual, expected)),
+        _ => Err("`assert_equal` takes two values".into()),
+    });
+}
+
+pub fn run(
+    manifest: Manifest,
+    filter: Option<String>,
+    junit: Option<PathBuf>,
+    coverage: Option<PathBuf>,
+    reporter: &mut Reporter,
+) -> CommandResult {
+    let out: Output = Rc::new(RefCell::new(std::io::stdout()));
+    run_with_output(manifest, filter, junit, coverage, reporter, &out)
+}
+
+pub fn run_with_output(
+    manifest: Manifest,
+    filter: Option<String>,
+    junit: Option<PathBuf>,
+    coverage: Option<PathBuf>,
+    reporter: &mut Reporter,
+    out: &Output,
+) -> CommandResult {
+    let tests: Vec<PathBuf> = source_files(&manifest.root)
+        .into_iter()
+        .filter(|path| is_test_file(path))
+        .collect();
+    let package = Package::load(manifest, &tests, reporter);
+    check(&package, reporter);
+    stop_on_errors(reporter)?;
+
+    let mut cases: Vec<Case> = vec![];
+    let mut covered = Coverage::default();
+    for (index, file) in package.files.iter().enumerate() {
+        if !is_test_file(&file.file.path) {
+            continue;
+        }
+        let bytecode = match compile_module(&package.link(index)) {
+            Ok(bytecode) => bytecode,
+            Err(err) => {
+                reporter.diagnostic(
+                    Diagnostic::error(err.message)
+                        .in_file(&file.file.path)
+                        .with_note("reported by the bytecode compiler"),
+                );
+                continue;
+            }
+        };
+        let origins = origins(&package, index);
+
+        let tests = file.module.functions.iter().filter(|function| {
+            function.receiver.is_none()
+                && function.params.is_empty()
+                && function.name.starts_with("test_")
+                && filter
+                    .as_ref()
+                    .is_none_or(|filter| function.name.contains(filter.as_str()))
+        });
+        for test in tests {
+            let mut vm = Vm::new(bytecode.clone());
+            register_natives(&mut vm, out);
+            register_assertions(&mut vm);
+            if coverage.is_some() {
+                vm.record_coverage();
+            }
+
+            let start = Instant::now();
+            let failure = match vm.call(&test.name, vec![]) {
+                Ok(Value::Bool(false)) => Some("returned false".to_string()),
+                Ok(_) => None,
+                Err(err) => Some(err.message),
+            };
+            let time = start.elapsed();
+            if let Some(hits) = vm.hits() {
+                covered.add(vm.module(), hits, |name| {
+                    origins.get(name).map(PathBuf::as_path)
+                });
+            }
+
+            reporter.emit(Message::Test {
+                name: test.name.clone(),
+                file: file.file.path.clone(),
+                status: match failure {
+                    None => TestStatus::Passed,
+                    Some(_) => TestStatus::Failed,
+                },
+                message: failure.clone(),
+            });
+            cases.push(Case {
+                name: test.name.clone(),
+                file: file.file.path.clone(),
+                time,
+                failure,
+            });
+        }
+    }
+
+    let failed = cases.iter().filter(|case| case.failure.is_some()).count();
+    reporter.emit(Message::TestSummary {
+        passed: cases.len() - failed,
+        failed,
+    });
+    if let Some(path) = junit {
+        write(
+            &path,
+            junit::render(&package.manifest.name, &cases),
+            "junit",
+            reporter,
+        )?;
+    }
+    if let Some(path) = coverage {
+        write(&path, covered.to_lcov(), "lcov", reporter)?;
+    }
+
+    match failed {
+        0 => stop_on_errors(reporter),
+        _ => Err(Failure::Reported),
+    }
+}
+
+// The file each function linked for the test file at `index` comes from, leaving out test files
+// themselves. The first file to declare a name is the one linking keeps, imports renamed with
+// `as` come from the file they're imported from.
+fn origins(package: &Package, index: usize) -> HashMap<String, PathBuf> {
+    let mut origins = HashMap::new();
+    let reachable = package.reachable(index);
+    for &index in &reachable {
+        let file = &package.files[index];
+        for function in &file.module.functions {
+            origins
+                .entry(function.qualified_name())
+                .or_insert_with(|| file.file.path.clone());
+        }
+    }
+    for &index in &reachable {
+        let file = &package.files[index];
+        for (import, target) in &file.resolved {
+            for (_, alias) in &file.module.imports[*import].names {
+                if let Some(alias) = alias {
+                    origins
+                        .entry(alias.clone())
+                        .or_insert_with(|| package.files[*target].file.path.clone());
+                }
+            }
+        }
+    }
+    origins.retain(|_, path| !is_test_file(path));
+    origins
+}
+
+fn write(path: &Path, contents: String, target: &str, reporter: &mut Reporter) -> CommandResult {
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        fs::create_dir_all(parent).map_err(|err| {
+            Failure::Usage(format!("can't create `{}`: {}", parent.display(), err))
+        })?;
+    }
+    fs::write(path, contents)
+        .map_err(|err| Failure::Usage(format!("can't write `{}`: {}", path.display(), err)))?;
+    reporter.emit(Message::Artifact {
+        target: target.into(),
+        path: display_path(path),
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{messages, package, reporter, Captured};
+    use pretty_assertions::assert_eq;
+
+    const GREETING: &str = "export fn greet(name string) -> string {
+	return name
+}
+";
+
+    const TESTS: &str = "import { assert_equal } from \"elp/testing\"
+import { greet } from \"$/greeting\"
+
+fn test_greets {
+	assert_equal(greet(\"world\"), \"world\")
+}
+
+fn test_shouts {
+	assert_equal(greet(\"world\"), \"WORLD\")
+}
+
+fn helper {
+}
+";
+
+    fn run_tests(filter: Option<&str>) -> (CommandResult, Vec<serde_json::Value>) {
+        let (_dir, manifest) = package(&[
+            ("main.elp", "fn main {\n}\n"),
+            ("greeting.elp", GREETING),
+            ("tests/greeting.test.elp", TESTS),
+        ]);
+        let (mut reporter, out) = reporter();
+        let stdout: Output = Rc::new(RefCell::new(Captured::default()));
+
+        let result = run_with_output(
+            manifest,
+            filter.map(Into::into),
+            None,
+            None,
+            &mut reporter,
+            &stdout,
+        );
+        (result, messages(&out))
+    }
+
+    #[test]
+    fn runs_test_functions() {
+        let (result, messages) = run_tests(None);
+
+        assert_eq!(result, Err(Failure::Reported));
+        assert_eq!(
+            messages
+                .iter()
+                .map(|message| (
+                    message["type"].as_str().unwrap(),
+                    message["name"].as_str().unwrap_or_default(),
+                    message["status"].as_str().unwrap_or_default(),
+                    message["message"].as_str().unwrap_or_default()
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                ("test", "test_greets", "passed", ""),
+                (
+                    "test",
+                    "test_shouts",
+                    "failed",
+                    "assertion failed, expected \"WORLD\" but found \"world\""
+                ),
+                ("test_summary", "", "", ""),
+            ]
+        );
+    }
+
+    #[test]
+    fn filters_by_name() {
+        let (result, messages) = run_tests(Some("greets"));
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[1]["passed"], 1);
+        assert_eq!(messages[1]["failed"], 0);
+    }
+
+    #[test]
+    fn writes_junit_and_coverage() {
+        let (dir, manifest) = package(&[
+            ("main.elp", "fn main {\n}\n"),
+            (
+                "greeting.elp",
+                "export fn greet(name string) -> string {
+	return name
+}
+
+export fn shout(name string) -> string {
+	return name
+}
+",
+            ),
+            (
+                "greeting.test.elp",
+                "import { assert_equal } from \"elp/testing\"
+import { greet } from \"$greeting\"
+
+fn test_greets {
+	assert_equal(greet(\"a\"), \"a\")
+	assert_equal(greet(\"b\"), \"c\")
+}
+",
+            ),
+        ]);
+        let (mut reporter, out) = reporter();
+        let stdout: Output = Rc::new(RefCell::new(Captured::default()));
+        let [junit, lcov] = ["results.xml", "coverage/lcov.info"].map(|file| dir.path().join(file));
+
+        let result = run_with_output(
+            manifest,
+            None,
+            Some(junit.clone()),
+            Some(lcov.clone()),
+            &mut reporter,
+            &stdout,
+        );
+        assert_eq!(result, Err(Failure::Reported));
+        let messages = messages(&out);
+        assert_eq!(
+            messages[0]["message"],
+            "assertion failed, expected \"c\" but found \"b\""
+        );
+        assert_eq!(
+            messages[2..]
+                .iter()
+                .map(|message| message["target"].as_str().unwrap())
+                .collect::<Vec<_>>(),
+            vec!["junit", "lcov"]
+        );
+
+        let junit = fs::read_to_string(junit).unwrap();
+        assert!(junit.contains("<testsuites name=\"test\" tests=\"1\" failures=\"1\""));
+        assert!(junit.contains("<testcase name=\"test_greets\""));
+
+        // `greet` ran, `shout` didn't, and the test file isn't covered itself.
+        let lcov = fs::read_to_string(lcov).unwrap();
+        assert!(lcov.starts_with("TN:\nSF:") && lcov.contains("greeting.elp\n"));
+        assert!(!lcov.contains("test.elp"));
+        assert!(lcov.contains("DA:2,2\nDA:6,0\nLF:2\nLH:1\n"));
+    }
+}
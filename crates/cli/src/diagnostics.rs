@@ -0,0 +1,350 @@
+// Everything a command has to say goes through the `Reporter` so it comes out the same way for
+// every subcommand: readable text by default or one JSON object per line with
+// `--message-format json` for editors and CI. Human output sends diagnostics to stderr and
+// results to stdout, JSON puts everything on stdout.
+
+use std::{
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use clap::ValueEnum;
+use elp_ir::mir::Span;
+use serde::Serialize;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, ValueEnum)]
+pub enum MessageFormat {
+    Human,
+    Json,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub file: Option<PathBuf>,
+    // Both 1 based, missing when the problem isn't tied to a place in the file.
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub notes: Vec<String>,
+    // The line being pointed at, only shown in human output.
+    #[serde(skip)]
+    pub source_line: Option<String>,
+}
+
+impl Diagnostic {
+    fn new(severity: Severity, message: impl Into<String>) -> Self {
+        Self {
+            severity,
+            message: message.into(),
+            file: None,
+            line: None,
+            column: None,
+            notes: vec![],
+            source_line: None,
+        }
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Self::new(Severity::Error, message)
+    }
+
+    pub fn warning(message: impl Into<String>) -> Self {
+        Self::new(Severity::Warning, message)
+    }
+
+    pub fn in_file(mut self, file: &Path) -> Self {
+        self.file = Some(file.to_path_buf());
+        self
+    }
+
+    // Points at a byte offset into `source`.
+    pub fn at(mut self, source: &str, offset: usize) -> Self {
+        let offset = offset.min(source.len());
+        let line_start = source[..offset].rfind('\n').map_or(0, |index| index + 1);
+        let line_end = source[offset..]
+            .find('\n')
+            .map_or(source.len(), |index| offset + index);
+
+        self.line = Some(source[..offset].matches('\n').count() as u32 + 1);
+        self.column = Some(source[line_start..offset].chars().count() as u32 + 1);
+        self.source_line = Some(source[line_start..line_end].trim_end().to_string());
+        self
+    }
+
+    // Spans the lowering and backends produce, statements built by hand have no position.
+    pub fn at_span(self, source: &str, span: Span) -> Self {
+        match span.line {
+            0 => self,
+            _ => self.at(source, span.start),
+        }
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    fn render(&self) -> String {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        let mut out = format!("{}: {}\n", severity, self.message);
+
+        let gutter = self.line.map_or(0, |line| line.to_string().len());
+        if let Some(file) = &self.file {
+            out.push_str(&format!("{}--> {}", " ".repeat(gutter + 1), file.display()));
+            if let (Some(line), Some(column)) = (self.line, self.column) {
+                out.push_str(&format!(":{}:{}", line, column));
+            }
+            out.push('\n');
+        }
+        if let (Some(line), Some(column), Some(source_line)) =
+            (self.line, self.column, &self.source_line)
+        {
+            let pad = " ".repeat(gutter);
+            // Keep tabs so the caret lines up with the source however wide they're shown.
+            let indent: String = source_line
+                .chars()
+                .take(column as usize - 1)
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect();
+            out.push_str(&format!("{} |\n", pad));
+            out.push_str(&format!("{} | {}\n", line, source_line));
+            out.push_str(&format!("{} | {}^\n", pad, indent));
+        }
+        for note in &self.notes {
+            out.push_str(&format!("{} = note: {}\n", " ".repeat(gutter), note));
+        }
+
+        out
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TestStatus {
+    Passed,
+    Failed,
+}
+
+// Everything other than diagnostics that a command reports.
+#[derive(Debug, PartialEq, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Message {
+    Diagnostic(Diagnostic),
+    // A file written by `elp build` or `elp doc`.
+    Artifact {
+        target: String,
+        path: PathBuf,
+    },
+    Test {
+        name: String,
+        file: PathBuf,
+        status: TestStatus,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        message: Option<String>,
+    },
+    TestSummary {
+        passed: usize,
+        failed: usize,
+    },
+    // `elp fmt` rewrote a file, or with `--check` found one that needs it.
+    Formatted {
+        file: PathBuf,
+        changed: bool,
+        check: bool,
+    },
+    // `elp dump`, the output is the stage's debug representation.
+    Dump {
+        stage: String,
+        file: PathBuf,
+        output: String,
+    },
+    // Progress for people, JSON consumers don't get these.
+    Status {
+        message: String,
+    },
+}
+
+pub struct Reporter {
+    format: MessageFormat,
+    out: Box<dyn Write>,
+    err: Box<dyn Write>,
+    errors: usize,
+}
+
+impl Reporter {
+    pub fn new(format: MessageFormat) -> Self {
+        Self::with_output(format, Box::new(io::stdout()), Box::new(io::stderr()))
+    }
+
+    pub fn with_output(format: MessageFormat, out: Box<dyn Write>, err: Box<dyn Write>) -> Self {
+        Self {
+            format,
+            out,
+            err,
+            errors: 0,
+        }
+    }
+
+    pub fn error_count(&self) -> usize {
+        self.errors
+    }
+
+    pub fn diagnostic(&mut self, diagnostic: Diagnostic) {
+        self.emit(Message::Diagnostic(diagnostic));
+    }
+
+    pub fn status(&mut self, message: impl Into<String>) {
+        self.emit(Message::Status {
+            message: message.into(),
+        });
+    }
+
+    pub fn emit(&mut self, message: Message) {
+        if let Message::Diagnostic(Diagnostic {
+            severity: Severity::Error,
+            ..
+        }) = &message
+        {
+            self.errors += 1;
+        }
+
+        // Output going nowhere (a closed pipe) isn't worth failing a build over.
+        let _ = match self.format {
+            MessageFormat::Json => match &message {
+                Message::Status { .. } => Ok(()),
+                message => writeln!(
+                    self.out,
+                    "{}",
+                    serde_json::to_string(message).expect("messages always serialise")
+                ),
+            },
+            MessageFormat::Human => self.render(&message),
+        };
+    }
+
+    fn render(&mut self, message: &Message) -> io::Result<()> {
+        match message {
+            Message::Diagnostic(diagnostic) => writeln!(self.err, "{}", diagnostic.render()),
+            Message::Artifact { target, path } => {
+                writeln!(self.out, "{:>10} {} ({})", "Wrote", path.display(), target)
+            }
+            Message::Test {
+                name,
+                file,
+                status,
+                message,
+            } => {
+                let status = match status {
+                    TestStatus::Passed => "ok",
+                    TestStatus::Failed => "FAILED",
+                };
+                writeln!(self.out, "test {}::{} ... {}", file.display(), name, status)?;
+                for line in message.iter().flat_map(|message| message.lines()) {
+                    writeln!(self.out, "    {}", line)?;
+                }
+                Ok(())
+            }
+            Message::TestSummary { passed, failed } => writeln!(
+                self.out,
+                "\ntest result: {}. {} passed; {} failed",
+                if *failed == 0 { "ok" } else { "FAILED" },
+                passed,
+                failed
+            ),
+            Message::Formatted {
+                file,
+                changed: true,
+                check,
+            } => match check {
+                true => writeln!(self.out, "Would reformat {}", file.display()),
+                false => writeln!(self.out, "Reformatted {}", file.display()),
+            },
+            Message::Formatted { changed: false, .. } => Ok(()),
+            Message::Dump { output, .. } => writeln!(self.out, "{}", output),
+            Message::Status { message } => writeln!(self.err, "{:>10} {}", "", message),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::Captured;
+    use pretty_assertions::assert_eq;
+
+    fn capture(format: MessageFormat) -> (Reporter, Captured, Captured) {
+        let (out, err) = (Captured::default(), Captured::default());
+        let reporter = Reporter::with_output(format, Box::new(out.clone()), Box::new(err.clone()));
+        (reporter, out, err)
+    }
+
+    #[test]
+    fn locates_offsets() {
+        let source = "fn main {\n\tmissing(1)\n}\n";
+        let diagnostic = Diagnostic::error("`missing` isn't defined").at(source, 11);
+
+        assert_eq!(diagnostic.line, Some(2));
+        assert_eq!(diagnostic.column, Some(2));
+        assert_eq!(diagnostic.source_line.as_deref(), Some("\tmissing(1)"));
+    }
+
+    #[test]
+    fn human_diagnostics_point_at_the_source() {
+        let (mut reporter, out, err) = capture(MessageFormat::Human);
+        reporter.diagnostic(
+            Diagnostic::error("`missing` isn't defined")
+                .in_file(Path::new("lib/main.elp"))
+                .at("fn main {\n\tmissing(1)\n}\n", 11)
+                .with_note("imports are resolved from the package root"),
+        );
+
+        assert_eq!(reporter.error_count(), 1);
+        assert_eq!(out.text(), "");
+        assert_eq!(
+            err.text(),
+            "error: `missing` isn't defined
+  --> lib/main.elp:2:2
+  |
+2 | \tmissing(1)
+  | \t^
+  = note: imports are resolved from the package root
+
+"
+        );
+    }
+
+    #[test]
+    fn json_messages_are_one_per_line() {
+        let (mut reporter, out, err) = capture(MessageFormat::Json);
+        reporter.diagnostic(Diagnostic::error("unused").in_file(Path::new("main.elp")));
+        reporter.status("Checked main");
+        reporter.emit(Message::TestSummary {
+            passed: 2,
+            failed: 0,
+        });
+
+        assert_eq!(reporter.error_count(), 1);
+        assert_eq!(err.text(), "");
+        assert_eq!(
+            out.text(),
+            concat!(
+                r#"{"type":"diagnostic","severity":"error","message":"unused","file":"main.elp","line":null,"column":null}"#,
+                "\n",
+                r#"{"type":"test_summary","passed":2,"failed":0}"#,
+                "\n"
+            )
+        );
+    }
+}
@@ -0,0 +1,219 @@
+// Self contained HTML pages, one per module plus an index, with the stylesheet inlined so the
+// output can be opened straight from disk.
+
+use std::path::PathBuf;
+
+use super::{escape_html, signature_html, summary, DocPackage, Item, ItemKind, Link};
+
+const EXTENSION: &str = "html";
+
+const STYLE: &str = "body { font-family: sans-serif; max-width: 50em; margin: 2em auto; padding: 0 1em; line-height: 1.5; color: #222; }
+pre, code { font-family: monospace; }
+pre { background: #f4f4f4; padding: 0.5em 1em; overflow-x: auto; }
+pre.signature a { color: #0645ad; text-decoration: none; }
+section.item { border-top: 1px solid #ddd; margin-top: 1.5em; }
+dl.members dt { margin-top: 0.5em; }
+dl.members dd { margin-left: 2em; }
+nav { font-size: 0.9em; }
+";
+
+pub fn render(package: &DocPackage) -> Vec<(PathBuf, String)> {
+    let mut pages = vec![(PathBuf::from("index.html"), index(package))];
+    for module in 0..package.modules.len() {
+        pages.push((
+            PathBuf::from(package.page(module, EXTENSION)),
+            module_page(package, module),
+        ));
+    }
+    pages
+}
+
+fn page(title: &str, root: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>\n{}</style>\n</head>\n<body>\n<nav><a href=\"{}index.html\">Index</a></nav>\n{}</body>\n</html>\n",
+        escape_html(title),
+        STYLE,
+        root,
+        body
+    )
+}
+
+fn index(package: &DocPackage) -> String {
+    let mut body = format!("<h1>{}</h1>\n<ul>\n", escape_html(&package.name));
+    for (index, module) in package.modules.iter().enumerate() {
+        body.push_str(&format!(
+            "<li><a href=\"{}\">{}</a><ul>\n",
+            escape_html(&package.page(index, EXTENSION)),
+            escape_html(&module.name)
+        ));
+        for item in &module.items {
+            body.push_str(&format!(
+                "<li><a href=\"{}#{}\"><code>{}</code></a>",
+                escape_html(&package.page(index, EXTENSION)),
+                escape_html(&item.name),
+                escape_html(&item.name)
+            ));
+            if let Some(docs) = &item.docs {
+                body.push_str(&format!(" {}", escape_html(summary(docs))));
+            }
+            body.push_str("</li>\n");
+        }
+        body.push_str("</ul></li>\n");
+    }
+    body.push_str("</ul>\n");
+    page(&package.name, "", &body)
+}
+
+fn module_page(package: &DocPackage, module: usize) -> String {
+    let name = &package.modules[module].name;
+    let mut body = format!("<h1>Module <code>{}</code></h1>\n", escape_html(name));
+
+    for kind in [
+        ItemKind::Interface,
+        ItemKind::Object,
+        ItemKind::Enum,
+        ItemKind::Component,
+        ItemKind::Function,
+    ] {
+        let items: Vec<&Item> = package.modules[module]
+            .items
+            .iter()
+            .filter(|item| item.kind == kind)
+            .collect();
+        if items.is_empty() {
+            continue;
+        }
+
+        body.push_str(&format!("<h2>{}</h2>\n", kind.heading()));
+        for item in items {
+            body.push_str(&item_html(package, module, item));
+        }
+    }
+
+    page(
+        &format!("{} - {}", name, package.name),
+        &package.root(module),
+        &body,
+    )
+}
+
+fn item_html(package: &DocPackage, module: usize, item: &Item) -> String {
+    let mut html = format!(
+        "<section class=\"item\" id=\"{}\">\n<h3>{}</h3>\n",
+        escape_html(&item.name),
+        escape_html(&item.name)
+    );
+    html.push_str(&signature(package, module, item));
+    if let Some(docs) = &item.docs {
+        html.push_str(&docs_html(docs));
+    }
+
+    if !item.members.is_empty() {
+        html.push_str("<dl class=\"members\">\n");
+        for member in &item.members {
+            html.push_str(&format!(
+                "<dt id=\"{}.{}\"><code>{}</code></dt>\n",
+                escape_html(&item.name),
+                escape_html(&member.name),
+                signature_html(package, module, &member.signature, EXTENSION)
+            ));
+            if let Some(docs) = &member.docs {
+                html.push_str(&format!("<dd>{}</dd>\n", docs_html(docs)));
+            }
+        }
+        html.push_str("</dl>\n");
+    }
+
+    if item.kind == ItemKind::Interface {
+        let implementors = package.implementors(&Link {
+            module,
+            anchor: item.name.clone(),
+        });
+        if !implementors.is_empty() {
+            html.push_str("<h4>Implemented by</h4>\n<ul>\n");
+            for link in implementors {
+                html.push_str(&format!(
+                    "<li><a href=\"{}\"><code>{}</code></a></li>\n",
+                    escape_html(&package.href(module, &link, EXTENSION)),
+                    escape_html(&link.anchor)
+                ));
+            }
+            html.push_str("</ul>\n");
+        }
+    }
+
+    if !item.methods.is_empty() {
+        html.push_str("<h4>Methods</h4>\n");
+        for method in &item.methods {
+            html.push_str(&format!("<div id=\"{}\">\n", escape_html(&method.name)));
+            html.push_str(&signature(package, module, method));
+            if let Some(docs) = &method.docs {
+                html.push_str(&docs_html(docs));
+            }
+            html.push_str("</div>\n");
+        }
+    }
+
+    html.push_str("</section>\n");
+    html
+}
+
+fn signature(package: &DocPackage, module: usize, item: &Item) -> String {
+    format!(
+        "<pre class=\"signature\"><code>{}</code></pre>\n",
+        signature_html(package, module, &item.signature, EXTENSION)
+    )
+}
+
+// Paragraphs are separated by blank lines, lines indented by four spaces are code.
+fn docs_html(docs: &str) -> String {
+    let mut html = String::new();
+    let mut paragraph: Vec<&str> = vec![];
+    let mut code: Vec<&str> = vec![];
+
+    let flush = |html: &mut String, paragraph: &mut Vec<&str>, code: &mut Vec<&str>| {
+        if !paragraph.is_empty() {
+            html.push_str(&format!("<p>{}</p>\n", escape_html(&paragraph.join(" "))));
+            paragraph.clear();
+        }
+        if !code.is_empty() {
+            html.push_str(&format!(
+                "<pre><code>{}</code></pre>\n",
+                escape_html(&code.join("\n"))
+            ));
+            code.clear();
+        }
+    };
+
+    for line in docs.lines() {
+        if let Some(line) = line.strip_prefix("    ") {
+            if !paragraph.is_empty() {
+                flush(&mut html, &mut paragraph, &mut vec![]);
+            }
+            code.push(line);
+        } else if line.trim().is_empty() {
+            flush(&mut html, &mut paragraph, &mut code);
+        } else {
+            if !code.is_empty() {
+                flush(&mut html, &mut vec![], &mut code);
+            }
+            paragraph.push(line.trim());
+        }
+    }
+    flush(&mut html, &mut paragraph, &mut code);
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn docs_paragraphs_and_code() {
+        assert_eq!(
+            docs_html("Adds <two> numbers\ntogether.\n\nLike so:\n    add(1, 2)\n    add(3, 4)\nDone."),
+            "<p>Adds &lt;two&gt; numbers together.</p>\n<p>Like so:</p>\n<pre><code>add(1, 2)\nadd(3, 4)</code></pre>\n<p>Done.</p>\n"
+        );
+    }
+}
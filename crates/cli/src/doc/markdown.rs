@@ -0,0 +1,128 @@
+// Markdown pages for hosting next to a repository. Docs are Markdown already so they're copied as
+// they are, signatures stay HTML since Markdown can't link inside code.
+
+use std::path::PathBuf;
+
+use super::{signature_html, summary, DocPackage, Item, ItemKind, Link};
+
+const EXTENSION: &str = "md";
+
+pub fn render(package: &DocPackage) -> Vec<(PathBuf, String)> {
+    let mut pages = vec![(PathBuf::from("index.md"), index(package))];
+    for module in 0..package.modules.len() {
+        pages.push((
+            PathBuf::from(package.page(module, EXTENSION)),
+            module_page(package, module),
+        ));
+    }
+    pages
+}
+
+fn index(package: &DocPackage) -> String {
+    let mut markdown = format!("# {}\n\n", package.name);
+    for (index, module) in package.modules.iter().enumerate() {
+        let page = package.page(index, EXTENSION);
+        markdown.push_str(&format!("- [`{}`]({})\n", module.name, page));
+        for item in &module.items {
+            markdown.push_str(&format!("  - [`{}`]({}#{})", item.name, page, item.name));
+            if let Some(docs) = &item.docs {
+                markdown.push_str(&format!(" {}", summary(docs)));
+            }
+            markdown.push('\n');
+        }
+    }
+    markdown
+}
+
+fn module_page(package: &DocPackage, module: usize) -> String {
+    let mut markdown = format!(
+        "[Index]({}index.md)\n\n# Module `{}`\n",
+        package.root(module),
+        package.modules[module].name
+    );
+
+    for kind in [
+        ItemKind::Interface,
+        ItemKind::Object,
+        ItemKind::Enum,
+        ItemKind::Component,
+        ItemKind::Function,
+    ] {
+        let items: Vec<&Item> = package.modules[module]
+            .items
+            .iter()
+            .filter(|item| item.kind == kind)
+            .collect();
+        if items.is_empty() {
+            continue;
+        }
+
+        markdown.push_str(&format!("\n## {}\n", kind.heading()));
+        for item in items {
+            markdown.push_str(&item_markdown(package, module, item));
+        }
+    }
+
+    markdown
+}
+
+fn item_markdown(package: &DocPackage, module: usize, item: &Item) -> String {
+    let mut markdown = format!("\n<a id=\"{}\"></a>\n### {}\n\n", item.name, item.name);
+    markdown.push_str(&signature(package, module, item));
+    if let Some(docs) = &item.docs {
+        markdown.push_str(&format!("\n{}\n", docs));
+    }
+
+    if !item.members.is_empty() {
+        markdown.push('\n');
+        for member in &item.members {
+            markdown.push_str(&format!(
+                "- <a id=\"{}.{}\"></a><code>{}</code>",
+                item.name,
+                member.name,
+                signature_html(package, module, &member.signature, EXTENSION)
+            ));
+            if let Some(docs) = &member.docs {
+                markdown.push_str(&format!(" {}", docs.replace('\n', "\n  ")));
+            }
+            markdown.push('\n');
+        }
+    }
+
+    if item.kind == ItemKind::Interface {
+        let implementors = package.implementors(&Link {
+            module,
+            anchor: item.name.clone(),
+        });
+        if !implementors.is_empty() {
+            markdown.push_str("\n#### Implemented by\n\n");
+            for link in implementors {
+                markdown.push_str(&format!(
+                    "- [`{}`]({})\n",
+                    link.anchor,
+                    package.href(module, &link, EXTENSION)
+                ));
+            }
+        }
+    }
+
+    if !item.methods.is_empty() {
+        markdown.push_str("\n#### Methods\n");
+        for method in &item.methods {
+            markdown.push_str(&format!("\n<a id=\"{}\"></a>\n", method.name));
+            markdown.push_str(&signature(package, module, method));
+            if let Some(docs) = &method.docs {
+                markdown.push_str(&format!("\n{}\n", docs));
+            }
+        }
+    }
+
+    markdown
+}
+
+fn signature(package: &DocPackage, module: usize, item: &Item) -> String {
+    format!(
+        "<pre><code>{}</code></pre>\n",
+        signature_html(package, module, &item.signature, EXTENSION)
+    )
+}
@@ -0,0 +1,705 @@
+// The model behind `elp doc`. Every file in a package is a module with a page of its own listing
+// the objects, enums, interfaces, components and functions in it, along with their `///` docs.
+// Signatures are kept as tokens so the renderers can link any type name that's documented
+// somewhere in the package, whichever module it's in.
+
+pub mod html;
+pub mod markdown;
+
+use elp_parser::ast::{
+    component::ASTComponentDef,
+    elp_type::{ASTElpType, ASTPointerSemantics},
+    expression::ASTExpression,
+    function::{ASTFunctionArgument, ASTFunctionHeaderDef},
+    interface::{ASTInterface, ASTInterfaceMember},
+    module::ASTModule,
+    object::{ASTObject, ASTVisibility},
+    r#enum::ASTEnum,
+};
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Token {
+    Text(String),
+    // Linked to where it's documented, when it is.
+    Type(String),
+}
+
+pub type Signature = Vec<Token>;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, PartialOrd, Ord)]
+pub enum ItemKind {
+    Interface,
+    Object,
+    Enum,
+    Component,
+    Function,
+}
+
+impl ItemKind {
+    pub fn heading(self) -> &'static str {
+        match self {
+            ItemKind::Interface => "Interfaces",
+            ItemKind::Object => "Objects",
+            ItemKind::Enum => "Enums",
+            ItemKind::Component => "Components",
+            ItemKind::Function => "Functions",
+        }
+    }
+}
+
+// A field of an object or interface, a member of an enum or a method of an interface.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Member {
+    pub name: String,
+    pub signature: Signature,
+    pub docs: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Item {
+    pub kind: ItemKind,
+    pub name: String,
+    pub signature: Signature,
+    pub docs: Option<String>,
+    // The names after `implements`.
+    pub implements: Vec<String>,
+    pub members: Vec<Member>,
+    // `fn Name.method` from the same module.
+    pub methods: Vec<Item>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Import {
+    // What the module calls it, the alias when there is one.
+    pub name: String,
+    pub original: String,
+    pub module_path: String,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct DocModule {
+    pub name: String,
+    pub items: Vec<Item>,
+    pub imports: Vec<Import>,
+}
+
+impl DocModule {
+    // Only what's exported is documented unless `private` is set. Methods go with their object or
+    // enum, there's no exporting them separately.
+    pub fn from_ast(name: &str, ast: &ASTModule, private: bool) -> Self {
+        let mut module = DocModule {
+            name: name.into(),
+            items: vec![],
+            imports: vec![],
+        };
+        let mut methods = vec![];
+
+        for expression in &ast.expressions {
+            let (expression, exported) = match expression {
+                ASTExpression::Export(export) => (&export.value, true),
+                expression => (expression, false),
+            };
+            let item = match expression {
+                ASTExpression::Import(import) => {
+                    module
+                        .imports
+                        .extend(import.names.iter().map(|(original, alias)| Import {
+                            name: alias.clone().unwrap_or_else(|| original.clone()),
+                            original: original.clone(),
+                            module_path: import.module_path.clone(),
+                        }));
+                    continue;
+                }
+                ASTExpression::FunctionDef(function) if function.name.names.len() > 1 => {
+                    methods.push(function_item(&function.header(), "fn"));
+                    continue;
+                }
+                ASTExpression::Object(object) => object_item(object),
+                ASTExpression::Enum(r#enum) => enum_item(r#enum),
+                ASTExpression::Interface(interface) => interface_item(interface),
+                ASTExpression::Component(component) => component_item(component),
+                ASTExpression::FunctionDef(function) => function_item(&function.header(), "fn"),
+                ASTExpression::FunctionHeaderDef(header) => function_item(header, "fn"),
+                _ => continue,
+            };
+            if exported || private {
+                module.items.push(item);
+            }
+        }
+
+        for method in methods {
+            let (owner, _) = method.name.split_once('.').expect("methods have a dot");
+            match module.items.iter_mut().find(|item| item.name == owner) {
+                Some(item) => item.methods.push(method),
+                // The object isn't documented, or it's from another module.
+                None if private => module.items.push(method),
+                None => {}
+            }
+        }
+
+        module
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct DocPackage {
+    pub name: String,
+    pub modules: Vec<DocModule>,
+}
+
+// Somewhere on one of the pages.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Link {
+    pub module: usize,
+    pub anchor: String,
+}
+
+impl DocPackage {
+    // Where `name`, as it's written in `module`, is documented. Imports from the package are
+    // followed, anything else isn't part of the package's docs.
+    pub fn resolve(&self, module: usize, name: &str) -> Option<Link> {
+        let here = &self.modules[module];
+        if here.items.iter().any(|item| item.name == name) {
+            return Some(Link {
+                module,
+                anchor: name.into(),
+            });
+        }
+
+        let import = here.imports.iter().find(|import| import.name == name)?;
+        let path = import
+            .module_path
+            .strip_prefix('$')?
+            .trim_start_matches('/');
+        let target = self.modules.iter().position(|module| module.name == path)?;
+        self.modules[target]
+            .items
+            .iter()
+            .any(|item| item.name == import.original)
+            .then(|| Link {
+                module: target,
+                anchor: import.original.clone(),
+            })
+    }
+
+    // Everything that says it implements the interface at `link`.
+    pub fn implementors(&self, link: &Link) -> Vec<Link> {
+        let mut implementors = vec![];
+        for (index, module) in self.modules.iter().enumerate() {
+            for item in &module.items {
+                if item
+                    .implements
+                    .iter()
+                    .any(|name| self.resolve(index, name).as_ref() == Some(link))
+                {
+                    implementors.push(Link {
+                        module: index,
+                        anchor: item.name.clone(),
+                    });
+                }
+            }
+        }
+        implementors
+    }
+
+    // The page for each module, `lib/dag` is `lib/dag.html`.
+    pub fn page(&self, module: usize, extension: &str) -> String {
+        format!("{}.{}", self.modules[module].name, extension)
+    }
+
+    // From the page of `from` to `link`, pages link to each other relatively so the docs can be
+    // opened from anywhere.
+    pub fn href(&self, from: usize, link: &Link, extension: &str) -> String {
+        if from == link.module {
+            return format!("#{}", link.anchor);
+        }
+        format!(
+            "{}{}#{}",
+            self.root(from),
+            self.page(link.module, extension),
+            link.anchor
+        )
+    }
+
+    // From the page of `module` back up to where the index is.
+    pub fn root(&self, module: usize) -> String {
+        "../".repeat(self.modules[module].name.matches('/').count())
+    }
+}
+
+fn object_item(object: &ASTObject) -> Item {
+    let implements: Vec<&ASTElpType> = object
+        .implements
+        .iter()
+        .flat_map(|implements| &implements.types)
+        .collect();
+    let mut signature = vec![text(format!("object {}", object.name))];
+    push_implements(&mut signature, &implements);
+
+    Item {
+        kind: ItemKind::Object,
+        name: object.name.clone(),
+        signature,
+        docs: object.docs.clone(),
+        implements: implements.iter().map(|ty| ty.name.clone()).collect(),
+        members: object
+            .members
+            .iter()
+            .map(|member| {
+                let mut signature = vec![];
+                if member.visibility == Some(ASTVisibility::Public) {
+                    signature.push(text("public "));
+                }
+                signature.push(text(format!(".{}", member.name)));
+                if let Some(ty) = &member.type_annotation {
+                    signature.push(text(" "));
+                    push_type(&mut signature, ty);
+                }
+                Member {
+                    name: member.name.clone(),
+                    signature,
+                    docs: member.docs.clone(),
+                }
+            })
+            .collect(),
+        methods: vec![],
+    }
+}
+
+fn enum_item(r#enum: &ASTEnum) -> Item {
+    let implements: Vec<&ASTElpType> = r#enum.implements.iter().collect();
+    let mut signature = vec![text(format!("enum {}", r#enum.name))];
+    push_implements(&mut signature, &implements);
+
+    Item {
+        kind: ItemKind::Enum,
+        name: r#enum.name.clone(),
+        signature,
+        docs: r#enum.docs.clone(),
+        implements: implements.iter().map(|ty| ty.name.clone()).collect(),
+        members: r#enum
+            .members
+            .iter()
+            .map(|member| {
+                let mut signature = vec![text(format!(".{}", member.name))];
+                if !member.parameters.is_empty() {
+                    signature.push(text("("));
+                    push_list(&mut signature, &member.parameters, push_type);
+                    signature.push(text(")"));
+                }
+                Member {
+                    name: member.name.clone(),
+                    signature,
+                    docs: None,
+                }
+            })
+            .collect(),
+        methods: vec![],
+    }
+}
+
+fn interface_item(interface: &ASTInterface) -> Item {
+    let mut signature = vec![text(format!("interface {}", interface.name))];
+    push_generics(&mut signature, &interface.generics);
+
+    Item {
+        kind: ItemKind::Interface,
+        name: interface.name.clone(),
+        signature,
+        docs: interface.docs.clone(),
+        implements: vec![],
+        members: interface
+            .members
+            .iter()
+            .map(|member| match member {
+                ASTInterfaceMember::Field(field) => {
+                    let mut signature = vec![text(format!(".{}", field.name))];
+                    if let Some(ty) = &field.type_annotation {
+                        signature.push(text(" "));
+                        push_type(&mut signature, ty);
+                    }
+                    Member {
+                        name: field.name.clone(),
+                        signature,
+                        docs: None,
+                    }
+                }
+                ASTInterfaceMember::Method(method) => {
+                    let item = function_item(method, "fn");
+                    Member {
+                        name: item.name,
+                        signature: item.signature,
+                        docs: item.docs,
+                    }
+                }
+            })
+            .collect(),
+        methods: vec![],
+    }
+}
+
+fn component_item(component: &ASTComponentDef) -> Item {
+    Item {
+        kind: ItemKind::Component,
+        ..function_item(&component.header(), "component")
+    }
+}
+
+// `fn name<T>(argument T) -> T`, and the same for components.
+fn function_item(header: &ASTFunctionHeaderDef, keyword: &str) -> Item {
+    let name = header.name.names.join(".");
+    let pointer = match header.pointer_semantics {
+        Some(ASTPointerSemantics::Pointer) => "*",
+        Some(ASTPointerSemantics::Reference) => "&",
+        None => "",
+    };
+
+    let mut signature = vec![text(format!("{}{} {}", pointer, keyword, name))];
+    push_generics(&mut signature, &header.generics);
+    signature.push(text("("));
+    push_list(&mut signature, &header.arguments.arguments, push_argument);
+    signature.push(text(")"));
+    if !header.return_type.type_annotations.is_empty() {
+        signature.push(text(" -> "));
+        push_list(
+            &mut signature,
+            &header.return_type.type_annotations,
+            push_type,
+        );
+    }
+
+    Item {
+        kind: ItemKind::Function,
+        name,
+        signature,
+        docs: header.docs.clone(),
+        implements: vec![],
+        members: vec![],
+        methods: vec![],
+    }
+}
+
+fn push_argument(signature: &mut Signature, argument: &ASTFunctionArgument) {
+    let pointer = match argument.pointer_semantics {
+        Some(ASTPointerSemantics::Pointer) => "*",
+        Some(ASTPointerSemantics::Reference) => "&",
+        None => "",
+    };
+    signature.push(text(format!("{}{}", pointer, argument.name)));
+    if let Some(ty) = &argument.type_annotation {
+        signature.push(text(" "));
+        push_type(signature, ty);
+    }
+}
+
+fn push_type(signature: &mut Signature, ty: &ASTElpType) {
+    match ty.pointer_semantics {
+        Some(ASTPointerSemantics::Pointer) => signature.push(text("*")),
+        Some(ASTPointerSemantics::Reference) => signature.push(text("&")),
+        None => {}
+    }
+
+    // `[T]` comes out of the AST as `Array<T>`.
+    if ty.name == "Array" && ty.generic_parameters.len() == 1 {
+        signature.push(text("["));
+        push_type(signature, &ty.generic_parameters[0]);
+        signature.push(text("]"));
+    } else {
+        signature.push(Token::Type(ty.name.clone()));
+        push_generics(signature, &ty.generic_parameters);
+    }
+
+    if !ty.type_constraints.is_empty() {
+        signature.push(text(": "));
+        for (index, constraint) in ty.type_constraints.iter().enumerate() {
+            if index > 0 {
+                signature.push(text(" + "));
+            }
+            push_type(signature, constraint);
+        }
+    }
+}
+
+fn push_generics(signature: &mut Signature, generics: &[ASTElpType]) {
+    if generics.is_empty() {
+        return;
+    }
+    signature.push(text("<"));
+    push_list(signature, generics, push_type);
+    signature.push(text(">"));
+}
+
+fn push_implements(signature: &mut Signature, implements: &[&ASTElpType]) {
+    for (index, ty) in implements.iter().enumerate() {
+        signature.push(text(if index == 0 { " implements " } else { ", " }));
+        push_type(signature, ty);
+    }
+}
+
+fn push_list<T>(signature: &mut Signature, items: &[T], push: fn(&mut Signature, &T)) {
+    for (index, item) in items.iter().enumerate() {
+        if index > 0 {
+            signature.push(text(", "));
+        }
+        push(signature, item);
+    }
+}
+
+fn text(text: impl Into<String>) -> Token {
+    Token::Text(text.into())
+}
+
+// The first sentence or line of some docs, for lists of items.
+pub fn summary(docs: &str) -> &str {
+    let first_line = docs.lines().next().unwrap_or("").trim();
+    match first_line.find(". ") {
+        Some(end) => &first_line[..=end],
+        None => first_line,
+    }
+}
+
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// Signatures are HTML in both formats, Markdown can't link inside code.
+pub fn signature_html(
+    package: &DocPackage,
+    module: usize,
+    signature: &Signature,
+    extension: &str,
+) -> String {
+    signature
+        .iter()
+        .map(|token| match token {
+            Token::Text(text) => escape_html(text),
+            Token::Type(name) => match package.resolve(module, name) {
+                Some(link) => format!(
+                    "<a href=\"{}\">{}</a>",
+                    escape_html(&package.href(module, &link, extension)),
+                    escape_html(name)
+                ),
+                None => escape_html(name),
+            },
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use elp_parser::{
+        ast::traits::FromCST,
+        cst::CSTModule,
+        parser::{ElpParser, Rule},
+    };
+    use from_pest::FromPest;
+    use pest::Parser;
+    use pretty_assertions::assert_eq;
+
+    pub fn module(name: &str, source: &str, private: bool) -> DocModule {
+        let mut pairs = ElpParser::parse(Rule::module, source).unwrap();
+        let cst = CSTModule::from_pest(&mut pairs).unwrap();
+        DocModule::from_ast(name, &ASTModule::from_cst(&cst), private)
+    }
+
+    fn rendered(signature: &Signature) -> String {
+        signature
+            .iter()
+            .map(|token| match token {
+                Token::Text(text) | Token::Type(text) => text.as_str(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn documents_exported_items() {
+        let module = module(
+            "lib/shapes",
+            "import { Named } from \"$lib/names\"
+
+/// Something with an area.
+export interface Shape<T: Number> {
+	.sides int,
+	fn area(self) -> T
+}
+
+/// A square.
+export object Square implements Shape<float>, Named {
+	/// How long each side is.
+	public .side float,
+	.cached *[float]
+}
+
+/// Its area.
+fn Square.area(self) -> float {
+	return self.side
+}
+
+export enum Corner {
+	.ROUND(float, Unit),
+	.SQUARE,
+}
+
+fn helper(a int) -> int {
+	return a
+}
+
+/// Draws the shape.
+export component Canvas(shape *Shape, scale float) -> Component {
+	return shape
+}
+",
+            false,
+        );
+
+        assert_eq!(
+            module
+                .items
+                .iter()
+                .map(|item| (item.kind, rendered(&item.signature)))
+                .collect::<Vec<_>>(),
+            vec![
+                (ItemKind::Interface, "interface Shape<T: Number>".into()),
+                (
+                    ItemKind::Object,
+                    "object Square implements Shape<float>, Named".into()
+                ),
+                (ItemKind::Enum, "enum Corner".into()),
+                (
+                    ItemKind::Component,
+                    "component Canvas(shape *Shape, scale float) -> Component".into()
+                ),
+            ]
+        );
+
+        let square = &module.items[1];
+        assert_eq!(square.docs.as_deref(), Some("A square."));
+        assert_eq!(square.implements, vec!["Shape", "Named"]);
+        assert_eq!(
+            square
+                .members
+                .iter()
+                .map(|member| (rendered(&member.signature), member.docs.as_deref()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("public .side float".into(), Some("How long each side is.")),
+                (".cached *[float]".into(), None),
+            ]
+        );
+        assert_eq!(
+            rendered(&square.methods[0].signature),
+            "fn Square.area(self) -> float"
+        );
+        assert_eq!(square.methods[0].docs.as_deref(), Some("Its area."));
+
+        assert_eq!(
+            module.items[0]
+                .members
+                .iter()
+                .map(|member| rendered(&member.signature))
+                .collect::<Vec<_>>(),
+            vec![".sides int", "fn area(self) -> T"]
+        );
+        assert_eq!(
+            module.items[2]
+                .members
+                .iter()
+                .map(|member| rendered(&member.signature))
+                .collect::<Vec<_>>(),
+            vec![".ROUND(float, Unit)", ".SQUARE"]
+        );
+        assert_eq!(module.items[3].docs.as_deref(), Some("Draws the shape."));
+    }
+
+    #[test]
+    fn private_items_on_request() {
+        let source = "fn helper(a int) -> int {\n\treturn a\n}\n";
+
+        assert!(module("main", source, false).items.is_empty());
+        assert_eq!(
+            rendered(&module("main", source, true).items[0].signature),
+            "fn helper(a int) -> int"
+        );
+    }
+
+    #[test]
+    fn links_across_modules() {
+        let package = DocPackage {
+            name: "shapes".into(),
+            modules: vec![
+                module(
+                    "lib/names",
+                    "export interface Named {\n\t.name string\n}\n",
+                    false,
+                ),
+                module(
+                    "main",
+                    "import { Named as Called } from \"$/lib/names\"
+
+export object Dog implements Called {
+	.name string
+}
+
+export fn rename(dog Dog, name Called) -> Dog {
+	return dog
+}
+",
+                    false,
+                ),
+            ],
+        };
+
+        assert_eq!(
+            package.resolve(1, "Called"),
+            Some(Link {
+                module: 0,
+                anchor: "Named".into()
+            })
+        );
+        assert_eq!(package.resolve(1, "string"), None);
+        assert_eq!(package.resolve(0, "Dog"), None);
+        assert_eq!(
+            package.implementors(&Link {
+                module: 0,
+                anchor: "Named".into()
+            }),
+            vec![Link {
+                module: 1,
+                anchor: "Dog".into()
+            }]
+        );
+        assert_eq!(
+            signature_html(&package, 1, &package.modules[1].items[1].signature, "html"),
+            "fn rename(dog <a href=\"#Dog\">Dog</a>, name <a href=\"lib/names.html#Named\">Called</a>) -&gt; <a href=\"#Dog\">Dog</a>"
+        );
+        assert_eq!(
+            package.href(
+                0,
+                &Link {
+                    module: 1,
+                    anchor: "Dog".into()
+                },
+                "md"
+            ),
+            "../main.md#Dog"
+        );
+    }
+
+    #[test]
+    fn summaries() {
+        assert_eq!(summary("A square. With sides.\nMore."), "A square.");
+        assert_eq!(summary("Just a line\nand more"), "Just a line");
+        assert_eq!(summary(""), "");
+    }
+}
@@ -0,0 +1,221 @@
+mod commands;
+mod diagnostics;
+mod doc;
+mod manifest;
+mod package;
+#[cfg(test)]
+mod test_support;
+mod testing;
+
+use std::{
+    num::NonZeroUsize,
+    panic,
+    path::PathBuf,
+    process::ExitCode,
+    sync::{Arc, Mutex},
+};
+
+use clap::{Parser, Subcommand};
+use commands::{build::Target, dump::Stage, find_manifest, CommandResult, Failure};
+use diagnostics::{Diagnostic, MessageFormat, Reporter};
+
+// Errors in the code, failing tests or unformatted files.
+const FAILURE: u8 = 1;
+// The command line itself was wrong, clap uses the same code.
+const USAGE: u8 = 2;
+// The compiler panicked, the same code Rust uses.
+const INTERNAL_ERROR: u8 = 101;
+
+/// The elp compiler and package tool
+#[derive(Debug, Parser)]
+#[command(name = "elp", version)]
+struct Cli {
+    /// How to print diagnostics and results
+    #[arg(long, global = true, value_enum, default_value = "human")]
+    message_format: MessageFormat,
+
+    /// The `Package.elp` to use, or a directory to look for one in
+    #[arg(long, global = true)]
+    manifest_path: Option<PathBuf>,
+
+    /// How many files to work on at once, one per core by default
+    #[arg(long, short, global = true)]
+    jobs: Option<NonZeroUsize>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Type check the package without building it
+    Check,
+    /// Compile the package for a target
+    Build {
+        #[arg(long, value_enum, default_value = "bytecode")]
+        target: Target,
+
+        /// The platform `#if` directives are decided for, one of the package's `.targets`
+        #[arg(long)]
+        platform: Option<String>,
+
+        /// Where to put the output, `build/<target>` by default
+        #[arg(long)]
+        out_dir: Option<PathBuf>,
+    },
+    /// Write a C header for the package's exports, to call into what `--target llvm` builds
+    Cbindgen {
+        /// The platform `#if` directives are decided for, one of the package's `.targets`
+        #[arg(long)]
+        platform: Option<String>,
+
+        /// Where to put the header, `build/include` by default
+        #[arg(long)]
+        out_dir: Option<PathBuf>,
+    },
+    /// Build the package and run its `main` function
+    Run,
+    /// Format source files, the whole package when none are given
+    Fmt {
+        files: Vec<PathBuf>,
+
+        /// Report unformatted files instead of rewriting them
+        #[arg(long)]
+        check: bool,
+
+        /// Only format the items on these lines of a single file, as `START:END`
+        #[arg(long, value_name = "START:END")]
+        range: Option<commands::fmt::LineRange>,
+    },
+    /// Run the test functions in the package's `*.test.elp` files
+    Test {
+        /// Only run tests whose name contains this
+        filter: Option<String>,
+
+        /// Also write the results as JUnit XML to this file
+        #[arg(long, value_name = "FILE")]
+        junit: Option<PathBuf>,
+
+        /// Write which lines the tests ran to this file, in lcov format
+        #[arg(long, value_name = "FILE")]
+        coverage: Option<PathBuf>,
+    },
+    /// Write API documentation from the package's `///` comments
+    Doc {
+        #[arg(long, value_enum, default_value = "html")]
+        format: commands::doc::Format,
+
+        /// Where to put the pages, `build/doc` by default
+        #[arg(long)]
+        out_dir: Option<PathBuf>,
+
+        /// Document items that aren't exported as well
+        #[arg(long)]
+        private: bool,
+    },
+    /// Print what the compiler makes of a file at one stage
+    Dump {
+        #[arg(long, value_enum, default_value = "hir")]
+        stage: Stage,
+
+        /// The file to dump, the package entry by default
+        file: Option<PathBuf>,
+    },
+}
+
+fn execute(cli: Cli, reporter: &mut Reporter) -> CommandResult {
+    let manifest_path = cli.manifest_path;
+    match cli.command {
+        Command::Check => {
+            commands::check::run(find_manifest(manifest_path.as_deref(), reporter)?, reporter)
+        }
+        Command::Build {
+            target,
+            platform,
+            out_dir,
+        } => commands::build::run(
+            find_manifest(manifest_path.as_deref(), reporter)?,
+            target,
+            platform,
+            out_dir,
+            reporter,
+        ),
+        Command::Cbindgen { platform, out_dir } => commands::cbindgen::run(
+            find_manifest(manifest_path.as_deref(), reporter)?,
+            platform,
+            out_dir,
+            reporter,
+        ),
+        Command::Run => {
+            commands::run::run(find_manifest(manifest_path.as_deref(), reporter)?, reporter)
+        }
+        Command::Fmt {
+            files,
+            check,
+            range,
+        } => commands::fmt::run(files, check, range, manifest_path, reporter),
+        Command::Test {
+            filter,
+            junit,
+            coverage,
+        } => commands::test::run(
+            find_manifest(manifest_path.as_deref(), reporter)?,
+            filter,
+            junit,
+            coverage,
+            reporter,
+        ),
+        Command::Doc {
+            format,
+            out_dir,
+            private,
+        } => commands::doc::run(
+            find_manifest(manifest_path.as_deref(), reporter)?,
+            format,
+            out_dir,
+            private,
+            reporter,
+        ),
+        Command::Dump { stage, file } => commands::dump::run(stage, file, manifest_path, reporter),
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let mut reporter = Reporter::new(cli.message_format);
+    if let Some(jobs) = cli.jobs {
+        // This only fails if the pool has been made already, nothing does that before here.
+        let _ = rayon::ThreadPoolBuilder::new()
+            .num_threads(jobs.get())
+            .build_global();
+    }
+
+    // A crash in the compiler is still reported like any other error, so editors and CI that read
+    // `--message-format json` see it.
+    let panic_message = Arc::new(Mutex::new(None));
+    let hook_message = panic_message.clone();
+    panic::set_hook(Box::new(move |info| {
+        *hook_message.lock().unwrap() = Some(info.to_string());
+    }));
+
+    let result = panic::catch_unwind(panic::AssertUnwindSafe(|| execute(cli, &mut reporter)));
+    let _ = panic::take_hook();
+
+    match result {
+        Ok(Ok(())) => ExitCode::SUCCESS,
+        Ok(Err(Failure::Reported)) => ExitCode::from(FAILURE),
+        Ok(Err(Failure::Usage(message))) => {
+            reporter.diagnostic(Diagnostic::error(message));
+            ExitCode::from(USAGE)
+        }
+        Ok(Err(Failure::Exit(code))) => ExitCode::from(code),
+        Err(_) => {
+            let mut diagnostic = Diagnostic::error("the compiler crashed, this is a bug");
+            if let Some(message) = panic_message.lock().unwrap().take() {
+                diagnostic = diagnostic.with_note(message);
+            }
+            reporter.diagnostic(diagnostic);
+            ExitCode::from(INTERNAL_ERROR)
+        }
+    }
+}
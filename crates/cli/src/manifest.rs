@@ -0,0 +1,209 @@
+// `Package.elp` describes a package: its name, version, the file everything starts from and the
+// platforms it's built for. It's ordinary elp (`export Package { .. }`) but object instantiation
+// doesn't make it through the CST yet, so the manifest is read straight from the parse tree.
+
+use std::path::{Path, PathBuf};
+
+use elp_parser::parser::{ElpParser, Rule};
+use pest::{iterators::Pair, Parser};
+
+use crate::{diagnostics::Diagnostic, package::parse_error};
+
+pub const MANIFEST: &str = "Package.elp";
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Manifest {
+    // The directory `Package.elp` is in, imports starting with `$` are resolved from here.
+    pub root: PathBuf,
+    pub name: String,
+    pub description: Option<String>,
+    pub version: Option<String>,
+    // Relative to `root`.
+    pub entry: PathBuf,
+    // `.targets = [.iOS, .Web]`, without the dots.
+    pub targets: Vec<String>,
+}
+
+impl Manifest {
+    // The closest `Package.elp` in `start` or any of its parents.
+    pub fn find(start: &Path) -> Option<PathBuf> {
+        let start = start.canonicalize().ok()?;
+        if start.is_file() && start.file_name().is_some_and(|name| name == MANIFEST) {
+            return Some(start);
+        }
+
+        start
+            .ancestors()
+            .map(|dir| dir.join(MANIFEST))
+            .find(|candidate| candidate.is_file())
+    }
+
+    pub fn parse(path: &Path, source: &str) -> Result<Self, Diagnostic> {
+        let error = |message: &str, offset: usize| {
+            Err(Diagnostic::error(message).in_file(path).at(source, offset))
+        };
+
+        let module = ElpParser::parse(Rule::module, source)
+            .map_err(|err| parse_error(path, source, err))?
+            .next()
+            .expect("a module always has a pair");
+        let package = module
+            .into_inner()
+            .filter(|pair| pair.as_rule() == Rule::expression)
+            .filter_map(|pair| only_child(pair, Rule::export))
+            .filter_map(|export| only_child(export, Rule::expression))
+            .find_map(|expression| only_child(expression, Rule::object_instantiation));
+        let Some(package) = package else {
+            return error("`Package.elp` has to `export Package { .. }`", 0);
+        };
+
+        let mut members = package.into_inner();
+        let object = members
+            .next()
+            .expect("object instantiations start with a name");
+        if object.as_str() != "Package" {
+            return error(
+                &format!("expected `Package`, found `{}`", object.as_str()),
+                object.as_span().start(),
+            );
+        }
+
+        let mut manifest = Manifest {
+            root: path.parent().unwrap_or(Path::new(".")).to_path_buf(),
+            name: String::new(),
+            description: None,
+            version: None,
+            entry: PathBuf::new(),
+            targets: vec![],
+        };
+        for member in members {
+            let offset = member.as_span().start();
+            let mut parts = member.into_inner();
+            let (Some(key), Some(value)) = (parts.next(), parts.next()) else {
+                return error("packages can't be spread into", offset);
+            };
+            let value_offset = value.as_span().start();
+
+            let valid = match key.as_str() {
+                "name" => string(value).map(|name| manifest.name = name),
+                "description" => string(value).map(|text| manifest.description = Some(text)),
+                "version" => version(value).map(|version| manifest.version = Some(version)),
+                "entry" => string(value).map(|entry| manifest.entry = entry.into()),
+                "targets" => targets(value).map(|targets| manifest.targets = targets),
+                other => return error(&format!("packages don't have a `{}`", other), offset),
+            };
+            if valid.is_none() {
+                return error(
+                    &format!("`{}` doesn't have the right type", key.as_str()),
+                    value_offset,
+                );
+            }
+        }
+
+        if manifest.name.is_empty() {
+            return error("packages need a `.name`", 0);
+        }
+        if manifest.entry.as_os_str().is_empty() {
+            return error("packages need an `.entry`", 0);
+        }
+
+        Ok(manifest)
+    }
+
+    pub fn entry_path(&self) -> PathBuf {
+        self.root.join(&self.entry)
+    }
+}
+
+// The single child of `pair` when it's the given rule, expressions wrap everything.
+fn only_child(pair: Pair<Rule>, rule: Rule) -> Option<Pair<Rule>> {
+    let mut inner = pair.into_inner();
+    let child = inner.next()?;
+    (child.as_rule() == rule && inner.next().is_none()).then_some(child)
+}
+
+fn string(expression: Pair<Rule>) -> Option<String> {
+    let string = only_child(expression, Rule::string)?;
+    Some(string.into_inner().next()?.as_str().to_string())
+}
+
+// `Version(1, 0, 0, 0)` or `.new(1, 0, 0, 0)` become "1.0.0.0".
+fn version(expression: Pair<Rule>) -> Option<String> {
+    let call = only_child(expression, Rule::function_call)?;
+    let parts = call
+        .into_inner()
+        .filter(|pair| pair.as_rule() == Rule::expression)
+        .map(|argument| only_child(argument, Rule::number).map(|n| n.as_str().to_string()))
+        .collect::<Option<Vec<_>>>()?;
+
+    Some(parts.join("."))
+}
+
+fn targets(expression: Pair<Rule>) -> Option<Vec<String>> {
+    only_child(expression, Rule::array)?
+        .into_inner()
+        .map(|item| {
+            let access = only_child(item, Rule::contextual_variable_access)?;
+            Some(access.into_inner().next()?.as_str().to_string())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn reads_the_graph_example() {
+        let path = Path::new("../../examples/Graph/Package.elp");
+        let source = std::fs::read_to_string(path).unwrap();
+
+        assert_eq!(
+            Manifest::parse(path, &source),
+            Ok(Manifest {
+                root: PathBuf::from("../../examples/Graph"),
+                name: "graph".into(),
+                description: None,
+                version: Some("1.0.0.0".into()),
+                entry: PathBuf::from("lib/dag.elp"),
+                targets: vec!["SharedLibrary".into()],
+            })
+        );
+    }
+
+    #[test]
+    fn contextual_versions_and_descriptions() {
+        let source = "import { Package, Version } from \"elp\"
+
+export Package {
+	.name = \"launcher\",
+	.description = \"Launches games.\",
+	.version = .new(2, 1),
+	.entry = \"main.velp\",
+	.targets = [.Windows, .Linux],
+}";
+        let manifest = Manifest::parse(Path::new("Package.elp"), source).unwrap();
+
+        assert_eq!(manifest.description.as_deref(), Some("Launches games."));
+        assert_eq!(manifest.version.as_deref(), Some("2.1"));
+        assert_eq!(manifest.targets, vec!["Windows", "Linux"]);
+    }
+
+    #[test]
+    fn bad_manifests_point_at_the_problem() {
+        let path = Path::new("Package.elp");
+        let missing_entry = Manifest::parse(path, "export Package { .name = \"graph\" }");
+        assert_eq!(
+            missing_entry.unwrap_err().message,
+            "packages need an `.entry`"
+        );
+
+        let wrong_type = Manifest::parse(path, "export Package {\n\t.name = 1,\n}").unwrap_err();
+        assert_eq!(wrong_type.message, "`name` doesn't have the right type");
+        assert_eq!((wrong_type.line, wrong_type.column), (Some(2), Some(10)));
+
+        let unknown = Manifest::parse(path, "export Package { .colour = \"red\" }").unwrap_err();
+        assert_eq!(unknown.message, "packages don't have a `colour`");
+    }
+}
@@ -0,0 +1,645 @@
+// Loading a package: every file reachable from its roots through `$` imports is parsed and lowered
+// on its own, then stitched back together. The files are asked for through the same `Database` the
+// language server uses, which resolves imports and checks a file against what it imports. Building
+// links every reachable file into a single module since backends only ever see one.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use elp_db::{
+    database::Database,
+    problem::Problem,
+    queries::{lower_source_for, Lowered, EXTENSIONS},
+};
+use elp_ir::{lower::lower_stylesheet, mir::Module, style::StyleSheet};
+use elp_parser::{
+    ast::{style::ASTStyleSheet, traits::FromCST},
+    cst::{self, style::CSTStyleSheetFile, CSTModule},
+    parser::{ElpParser, Rule},
+    precomp::Targets,
+};
+use from_pest::FromPest;
+use pest::{error::InputLocation, Parser};
+use rayon::prelude::*;
+
+use crate::{diagnostics::Diagnostic, diagnostics::Reporter, manifest::Manifest};
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SourceFile {
+    pub path: PathBuf,
+    pub source: String,
+}
+
+impl SourceFile {
+    pub fn read(path: &Path) -> Result<Self, Diagnostic> {
+        match fs::read_to_string(path) {
+            Ok(source) => Ok(Self {
+                path: display_path(path),
+                source,
+            }),
+            Err(err) => Err(Diagnostic::error(format!(
+                "can't read `{}`: {}",
+                path.display(),
+                err
+            ))),
+        }
+    }
+
+    // An error the database found in this file.
+    pub fn problem(&self, problem: &Problem) -> Diagnostic {
+        diagnostic(
+            self,
+            Diagnostic::error(problem.message.clone()),
+            problem.clone(),
+        )
+    }
+}
+
+// Paths are shown relative to where elp was run from when they're inside it.
+pub fn display_path(path: &Path) -> PathBuf {
+    std::env::current_dir()
+        .ok()
+        .and_then(|cwd| path.strip_prefix(cwd).ok().map(Path::to_path_buf))
+        .unwrap_or_else(|| path.to_path_buf())
+}
+
+pub fn parse_error(path: &Path, source: &str, err: pest::error::Error<Rule>) -> Diagnostic {
+    let offset = match err.location {
+        InputLocation::Pos(offset) => offset,
+        InputLocation::Span((start, _)) => start,
+    };
+
+    Diagnostic::error(format!("couldn't parse this, {}", err.variant.message()))
+        .in_file(path)
+        .at(source, offset)
+}
+
+pub fn parse(file: &SourceFile) -> Result<CSTModule<'_>, Diagnostic> {
+    let mut pairs = ElpParser::parse(Rule::module, &file.source)
+        .map_err(|err| parse_error(&file.path, &file.source, err))?;
+
+    CSTModule::from_pest(&mut pairs).map_err(|_| unsupported(file))
+}
+
+// Lowering is shared with the language server, its problems are turned into diagnostics here.
+pub fn lower(file: &SourceFile, name: &str) -> Result<Module, Diagnostic> {
+    lowered(
+        file,
+        lower_source_for(&file.source, name, &Targets::default()),
+    )
+    .map(|(module, _)| module)
+}
+
+// A `.selp` file, the whole of which is one stylesheet.
+pub fn stylesheet(file: &SourceFile) -> Result<StyleSheet, Diagnostic> {
+    let mut pairs = ElpParser::parse(Rule::stylesheet_file, &file.source)
+        .map_err(|err| parse_error(&file.path, &file.source, err))?;
+    let cst = CSTStyleSheetFile::from_pest(&mut pairs).map_err(|_| unsupported(file))?;
+
+    lower_stylesheet(&ASTStyleSheet::from_cst(&cst)).map_err(|err| {
+        Diagnostic::error(err.message)
+            .in_file(&file.path)
+            .at_span(&file.source, err.span)
+    })
+}
+
+// The module, along with the warnings `#if` directives gave.
+fn lowered(file: &SourceFile, lowered: Lowered) -> Result<(Module, Vec<Diagnostic>), Diagnostic> {
+    let warnings = lowered
+        .warnings
+        .into_iter()
+        .map(|problem| diagnostic(file, Diagnostic::warning(problem.message.clone()), problem))
+        .collect();
+    lowered
+        .module
+        .map(|module| (module, warnings))
+        .map_err(|problem| file.problem(&problem))
+}
+
+fn diagnostic(file: &SourceFile, diagnostic: Diagnostic, problem: Problem) -> Diagnostic {
+    let diagnostic = diagnostic.in_file(&file.path);
+    let diagnostic = match problem.range {
+        Some(range) => diagnostic.at(&file.source, range.start),
+        None => diagnostic,
+    };
+    problem
+        .notes
+        .into_iter()
+        .fold(diagnostic, |diagnostic, note| diagnostic.with_note(note))
+}
+
+// The grammar accepts more than the CST can represent so far, the error points at the innermost
+// expression that doesn't convert on its own.
+fn unsupported(file: &SourceFile) -> Diagnostic {
+    let diagnostic = match cst::unsupported(&file.source) {
+        Some((construct, offset)) => {
+            Diagnostic::error(format!("{} isn't supported yet", construct))
+                .in_file(&file.path)
+                .at(&file.source, offset)
+        }
+        None => {
+            Diagnostic::error("this file uses syntax that isn't supported yet").in_file(&file.path)
+        }
+    };
+
+    diagnostic.with_note("the parser understands this but the compiler can't use it yet")
+}
+
+// Every elp source file under `root`, skipping build output and hidden directories.
+pub fn source_files(root: &Path) -> Vec<PathBuf> {
+    let mut files = vec![];
+    let Ok(entries) = fs::read_dir(root) else {
+        return files;
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if path.is_dir() {
+            if !name.starts_with('.') && name != "build" && name != "target" {
+                files.extend(source_files(&path));
+            }
+        } else if path
+            .extension()
+            .is_some_and(|extension| EXTENSIONS.iter().any(|e| extension == *e))
+        {
+            files.push(path);
+        }
+    }
+
+    files.sort();
+    files
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct PackageFile {
+    // Where it is, as the database knows it.
+    pub path: PathBuf,
+    pub file: SourceFile,
+    pub module: Module,
+    // Imports from inside the package, as (index into `module.imports`, index into
+    // `Package::files`).
+    pub resolved: Vec<(usize, usize)>,
+}
+
+// A file lowered on its own, with the warnings lowering it gave.
+type Loaded = Result<(Module, Vec<Diagnostic>, SourceFile), Diagnostic>;
+
+#[derive(Debug, Clone)]
+pub struct Package {
+    pub manifest: Manifest,
+    // In the order they were loaded, the roots come first.
+    pub files: Vec<PackageFile>,
+    // What the files were loaded through. Checking asks it as well, so nothing is lowered twice.
+    pub db: Database,
+}
+
+impl Package {
+    // Loads `roots` and everything they import from the package. Files that don't lower are
+    // reported and left out, so a package with errors still loads as much as it can.
+    pub fn load(manifest: Manifest, roots: &[PathBuf], reporter: &mut Reporter) -> Package {
+        Package::load_for(manifest, None, roots, reporter)
+    }
+
+    // Like `load`, with `#if` directives decided for `platform`. Without one every branch is
+    // loaded, as checking does.
+    pub fn load_for(
+        manifest: Manifest,
+        platform: Option<&str>,
+        roots: &[PathBuf],
+        reporter: &mut Reporter,
+    ) -> Package {
+        let mut db = Database::new();
+        db.set_targets(Targets {
+            declared: manifest.targets.clone(),
+            active: platform.map(String::from),
+        });
+        let mut package = Package {
+            manifest,
+            files: vec![],
+            db,
+        };
+        let mut indexes: HashMap<PathBuf, Option<usize>> = HashMap::new();
+        let mut pending: Vec<(usize, usize, PathBuf)> = vec![];
+        let mut wave: Vec<PathBuf> = roots.iter().map(|root| canonical(root)).collect();
+
+        // Files are loaded a wave at a time, everything the last wave imported that isn't loaded
+        // yet. Each file in a wave is lowered on its own in parallel, then they're added in order,
+        // so the files and what's reported come out the same however the threads ran.
+        while !wave.is_empty() {
+            let mut seen = HashSet::new();
+            let paths: Vec<PathBuf> = wave
+                .into_iter()
+                .filter(|path| !indexes.contains_key(path) && seen.insert(path.clone()))
+                .collect();
+            let db = &package.db;
+            let loaded: Vec<Loaded> = paths
+                .par_iter()
+                .map(|path| {
+                    let file = SourceFile {
+                        path: display_path(path),
+                        source: db.source(path).as_ref().clone().unwrap_or_default(),
+                    };
+                    let (module, warnings) = lowered(&file, db.lower(path).as_ref().clone())?;
+                    Ok((module, warnings, file))
+                })
+                .collect();
+
+            wave = vec![];
+            for (path, loaded) in paths.into_iter().zip(loaded) {
+                let (module, file) = match loaded {
+                    Ok((module, warnings, file)) => {
+                        for warning in warnings {
+                            reporter.diagnostic(warning);
+                        }
+                        (module, file)
+                    }
+                    Err(diagnostic) => {
+                        reporter.diagnostic(diagnostic);
+                        indexes.insert(path, None);
+                        continue;
+                    }
+                };
+
+                // Imports `#if` directives left out aren't followed. What can't be found is
+                // reported when the file is checked.
+                let index = package.files.len();
+                indexes.insert(path.clone(), Some(index));
+                for resolved in package.db.resolve(&path).iter() {
+                    let import = module
+                        .imports
+                        .iter()
+                        .position(|import| *import == resolved.import);
+                    if let (Some(import), Some(target)) = (import, &resolved.target) {
+                        pending.push((index, import, target.clone()));
+                        wave.push(target.clone());
+                    }
+                }
+                package.files.push(PackageFile {
+                    path,
+                    file,
+                    module,
+                    resolved: vec![],
+                });
+            }
+        }
+
+        for (file, import, path) in pending {
+            // Files that failed to load have already been reported.
+            if let Some(Some(target)) = indexes.get(&path) {
+                package.files[file].resolved.push((import, *target));
+            }
+        }
+
+        package
+    }
+
+    // Everything wrong with a loaded file, from its imports through to type checking it against
+    // what it imports.
+    pub fn problems(&self, index: usize) -> Vec<Diagnostic> {
+        let file = &self.files[index];
+        self.db
+            .diagnostics(&file.path)
+            .iter()
+            .map(|problem| file.file.problem(problem))
+            .collect()
+    }
+
+    // The files in the order the import graph allows checking them, a layer at a time. Every file
+    // comes after the files it imports, files importing each other in a circle share the last
+    // layer. Files in the same layer don't depend on each other, so they can be checked at once.
+    pub fn layers(&self) -> Vec<Vec<usize>> {
+        let mut layer_of: Vec<Option<usize>> = vec![None; self.files.len()];
+        let mut layers: Vec<Vec<usize>> = vec![];
+        loop {
+            let ready: Vec<usize> = (0..self.files.len())
+                .filter(|&index| layer_of[index].is_none())
+                .filter(|&index| {
+                    self.files[index]
+                        .resolved
+                        .iter()
+                        .all(|(_, target)| *target != index && layer_of[*target].is_some())
+                })
+                .collect();
+            if ready.is_empty() {
+                break;
+            }
+            for &index in &ready {
+                layer_of[index] = Some(layers.len());
+            }
+            layers.push(ready);
+        }
+
+        let cycles: Vec<usize> = (0..self.files.len())
+            .filter(|&index| layer_of[index].is_none())
+            .collect();
+        if !cycles.is_empty() {
+            layers.push(cycles);
+        }
+        layers
+    }
+
+    // `index` and every file it imports from, directly or not, nearest first.
+    pub fn reachable(&self, index: usize) -> Vec<usize> {
+        let mut reachable = vec![index];
+        let mut next = 0;
+        while next < reachable.len() {
+            for (_, target) in &self.files[reachable[next]].resolved {
+                if !reachable.contains(target) {
+                    reachable.push(*target);
+                }
+            }
+            next += 1;
+        }
+        reachable
+    }
+
+    // Every file reachable from `index` linked into one module named after the package.
+    pub fn link(&self, index: usize) -> Module {
+        let reachable = self.reachable(index);
+
+        let mut linked = Module {
+            name: self.manifest.name.clone(),
+            ..Default::default()
+        };
+        for &index in &reachable {
+            let module = &self.files[index].module;
+            for import in &module.imports {
+                if !import.module_path.starts_with('$') && !linked.imports.contains(import) {
+                    linked.imports.push(import.clone());
+                }
+            }
+            for object in &module.objects {
+                if linked.object(&object.name).is_none() {
+                    linked.objects.push(object.clone());
+                }
+            }
+            for r#enum in &module.enums {
+                if linked.r#enum(&r#enum.name).is_none() {
+                    linked.enums.push(r#enum.clone());
+                }
+            }
+            for function in &module.functions {
+                if linked.function(&function.qualified_name()).is_none() {
+                    linked.functions.push(function.clone());
+                }
+            }
+            for global in &module.globals {
+                if !linked.globals.iter().any(|g| g.name == global.name) {
+                    linked.globals.push(global.clone());
+                }
+            }
+            for external in &module.externals {
+                if !linked.externals.iter().any(|e| e.name == external.name) {
+                    linked.externals.push(external.clone());
+                }
+            }
+            for component in &module.components {
+                if linked.component(&component.name).is_none() {
+                    linked.components.push(component.clone());
+                }
+            }
+            linked
+                .styles
+                .rules
+                .extend(module.styles.rules.iter().cloned());
+        }
+        // Aliased imports need their items under the new name as well.
+        for &index in &reachable {
+            let file = &self.files[index];
+            for (import, target) in &file.resolved {
+                let module = &self.files[*target].module;
+                linked.import_from(module, &file.module.imports[*import].names);
+            }
+        }
+
+        linked
+    }
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        commands::check::check,
+        test_support::{messages, package, reporter},
+    };
+    use pretty_assertions::assert_eq;
+
+    fn load(manifest: Manifest) -> (Package, Vec<serde_json::Value>) {
+        let (mut reporter, out) = reporter();
+        let entry = manifest.entry_path();
+        let package = Package::load(manifest, &[entry], &mut reporter);
+        (package, messages(&out))
+    }
+
+    #[test]
+    fn loads_and_links_imports() {
+        let (_dir, manifest) = package(&[
+            (
+                "main.elp",
+                "import { println } from \"elp/stdio\"
+import { greet as hello } from \"$lib/greeting\"
+
+fn main {
+	println(hello(\"world\"))
+}
+",
+            ),
+            (
+                "lib/greeting.velp",
+                "export fn greet(name string) -> string {
+	return name
+}
+",
+            ),
+        ]);
+        let (package, messages) = load(manifest);
+
+        assert_eq!(messages, Vec::<serde_json::Value>::new());
+        assert_eq!(
+            package
+                .files
+                .iter()
+                .map(|file| file.module.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["main", "lib/greeting"]
+        );
+        assert_eq!(package.files[0].resolved, vec![(1, 1)]);
+
+        let context = package.db.context(&package.files[0].path);
+        let context = context.as_ref().as_ref().unwrap();
+        assert!(context.function("hello").is_some());
+        assert!(context.function("greet").is_none());
+
+        let linked = package.link(0);
+        assert_eq!(linked.name, "test");
+        assert_eq!(linked.imports.len(), 1);
+        assert_eq!(
+            linked
+                .functions
+                .iter()
+                .map(|function| function.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["main", "greet", "hello"]
+        );
+    }
+
+    #[test]
+    fn loads_the_same_on_any_number_of_threads() {
+        let (_dir, manifest) = package(&[
+            (
+                "main.elp",
+                "import { left } from \"$left\"
+import { right } from \"$right\"
+import { broken } from \"$broken\"
+",
+            ),
+            (
+                "left.elp",
+                "import { shared } from \"$shared\"
+
+export fn left -> int {
+	return shared()
+}
+",
+            ),
+            (
+                "right.elp",
+                "import { shared } from \"$shared\"
+
+export fn right -> int {
+	return shared()
+}
+",
+            ),
+            ("shared.elp", "export fn shared -> int {\n\treturn 1\n}\n"),
+            ("broken.elp", "export fn broken {\n\tconst = 1\n}\n"),
+        ]);
+        let on = |threads: usize| {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap();
+            pool.install(|| load(manifest.clone()))
+        };
+        let (package, messages) = on(1);
+
+        assert_eq!(
+            package
+                .files
+                .iter()
+                .map(|file| file.module.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["main", "left", "right", "shared"]
+        );
+        assert_eq!(messages.len(), 1);
+        assert_eq!(package.layers(), vec![vec![3], vec![1, 2], vec![0]]);
+        for threads in [2, 8] {
+            let (other, other_messages) = on(threads);
+            assert_eq!(
+                (other.files, other_messages),
+                (package.files.clone(), messages.clone())
+            );
+        }
+    }
+
+    #[test]
+    fn reports_unresolved_imports() {
+        let (_dir, manifest) = package(&[
+            (
+                "main.elp",
+                "import { greet, shout, missing } from \"$greeting\"
+import { Thing } from \"$nowhere\"
+",
+            ),
+            (
+                "greeting.elp",
+                "export fn greet(name string) -> string {
+	return name
+}
+
+fn shout(name string) -> string {
+	return name
+}
+",
+            ),
+        ]);
+        let (mut reporter, out) = reporter();
+        let entry = manifest.entry_path();
+        let package = Package::load(manifest, &[entry], &mut reporter);
+        assert_eq!(messages(&out), Vec::<serde_json::Value>::new());
+        check(&package, &mut reporter);
+
+        assert_eq!(
+            messages(&out)
+                .iter()
+                .map(|message| (
+                    message["message"].as_str().unwrap(),
+                    message["line"].as_u64().unwrap(),
+                    message["column"].as_u64().unwrap()
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                ("`shout` isn't exported from `$greeting`", 1, 17),
+                ("`$greeting` has no `missing`", 1, 24),
+                ("can't find `$nowhere`", 2, 24),
+            ]
+        );
+    }
+
+    #[test]
+    fn points_at_unsupported_syntax() {
+        let (_dir, manifest) = package(&[(
+            "main.elp",
+            "fn main {
+	const point = Point {
+		.x = 1,
+	}
+}
+",
+        )]);
+        let (package, messages) = load(manifest);
+
+        assert!(package.files.is_empty());
+        assert_eq!(messages.len(), 1);
+        assert_eq!(
+            messages[0]["message"],
+            "object instantiation isn't supported yet"
+        );
+        assert_eq!(
+            (&messages[0]["line"], &messages[0]["column"]),
+            (&2.into(), &16.into())
+        );
+    }
+
+    #[test]
+    fn finds_source_files() {
+        let (dir, manifest) = package(&[
+            ("main.elp", ""),
+            ("lib/a.velp", ""),
+            ("build/web/out.elp", ""),
+            (".hidden/b.elp", ""),
+            ("notes.md", ""),
+        ]);
+        let root = dir.path().canonicalize().unwrap();
+
+        assert_eq!(manifest.root, root);
+        assert_eq!(
+            source_files(&manifest.root),
+            vec![
+                root.join("Package.elp"),
+                root.join("lib/a.velp"),
+                root.join("main.elp")
+            ]
+        );
+    }
+}
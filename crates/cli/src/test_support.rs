@@ -0,0 +1,77 @@
+// Shorthands for the command tests, packages are written to a temporary directory and everything
+// is reported as JSON so tests can look at exactly what a command said.
+
+use std::{
+    cell::RefCell,
+    fs,
+    io::{self, Write},
+    rc::Rc,
+};
+
+use crate::{
+    diagnostics::{MessageFormat, Reporter},
+    manifest::Manifest,
+};
+
+// An in memory writer, for reporters and for what programs print.
+#[derive(Debug, Default, Clone)]
+pub struct Captured(Rc<RefCell<Vec<u8>>>);
+
+impl Captured {
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.0.borrow()).into_owned()
+    }
+}
+
+impl Write for Captured {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// Writes `files` into a fresh directory next to a `Package.elp` whose entry is `main.elp`.
+pub fn package(files: &[(&str, &str)]) -> (tempfile::TempDir, Manifest) {
+    let dir = tempfile::tempdir().unwrap();
+    let manifest = "export Package {\n\t.name  = \"test\",\n\t.entry = \"main.elp\",\n}\n";
+    fs::write(dir.path().join("Package.elp"), manifest).unwrap();
+    for (path, source) in files {
+        let path = dir.path().join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, source).unwrap();
+    }
+
+    let path = Manifest::find(dir.path()).unwrap();
+    let manifest = Manifest::parse(&path, &fs::read_to_string(&path).unwrap()).unwrap();
+    (dir, manifest)
+}
+
+pub fn reporter() -> (Reporter, Captured) {
+    let out = Captured::default();
+    let reporter = Reporter::with_output(
+        MessageFormat::Json,
+        Box::new(out.clone()),
+        Box::new(Captured::default()),
+    );
+    (reporter, out)
+}
+
+pub fn messages(out: &Captured) -> Vec<serde_json::Value> {
+    out.text()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+// The message of every diagnostic reported.
+pub fn errors(out: &Captured) -> Vec<String> {
+    messages(out)
+        .into_iter()
+        .filter(|message| message["type"] == "diagnostic")
+        .map(|message| message["message"].as_str().unwrap().to_string())
+        .collect()
+}
@@ -0,0 +1,114 @@
+// `elp test --coverage`, which lines of the package the tests ran, written as lcov for editors
+// and coverage services to read. Every line with code on it counts, the number of times it ran is
+// the most any op on it did.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use elp_bytecode::module::Module;
+
+#[derive(Debug, PartialEq, Default)]
+pub struct Coverage {
+    files: BTreeMap<PathBuf, BTreeMap<u32, u64>>,
+}
+
+impl Coverage {
+    // Adds a run of `module`, runs of the same file from different tests add up. `origin` says
+    // which file a function is from, those it doesn't know aren't counted.
+    pub fn add<'p>(
+        &mut self,
+        module: &Module,
+        hits: &[Vec<u64>],
+        origin: impl Fn(&str) -> Option<&'p Path>,
+    ) {
+        let mut run: BTreeMap<&Path, BTreeMap<u32, u64>> = BTreeMap::new();
+        for (function, hits) in module.functions.iter().zip(hits) {
+            // Closures are named after the function they're in, `main.<closure>`.
+            let name = function.name.split(".<closure>").next().unwrap_or_default();
+            let Some(file) = origin(name) else {
+                continue;
+            };
+            let lines = run.entry(file).or_default();
+            for (&line, &count) in function.lines.iter().zip(hits) {
+                if line == 0 {
+                    continue;
+                }
+                let hit = lines.entry(line).or_default();
+                *hit = (*hit).max(count);
+            }
+        }
+
+        for (file, lines) in run {
+            let counted = self.files.entry(file.to_path_buf()).or_default();
+            for (line, count) in lines {
+                *counted.entry(line).or_default() += count;
+            }
+        }
+    }
+
+    pub fn to_lcov(&self) -> String {
+        let mut lcov = String::new();
+        for (file, lines) in &self.files {
+            lcov.push_str("TN:\n");
+            lcov.push_str(&format!("SF:{}\n", file.display()));
+            for (line, count) in lines {
+                lcov.push_str(&format!("DA:{},{}\n", line, count));
+            }
+            lcov.push_str(&format!("LF:{}\n", lines.len()));
+            lcov.push_str(&format!(
+                "LH:{}\n",
+                lines.values().filter(|&&count| count > 0).count()
+            ));
+            lcov.push_str("end_of_record\n");
+        }
+        lcov
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use elp_bytecode::{module::FunctionProto, op::Op};
+    use pretty_assertions::assert_eq;
+
+    fn function(name: &str, lines: Vec<u32>) -> FunctionProto {
+        FunctionProto {
+            name: name.into(),
+            arity: 0,
+            locals: 0,
+            captures: vec![],
+            code: vec![Op::Nil; lines.len()],
+            lines,
+            switches: vec![],
+        }
+    }
+
+    #[test]
+    fn counts_lines_by_file() {
+        let module = Module {
+            functions: vec![
+                function("greet", vec![2, 2, 3]),
+                function("greet.<closure>", vec![4]),
+                function("<init>", vec![1]),
+                function("test_greets", vec![7]),
+            ],
+            ..Default::default()
+        };
+        let origin = |name: &str| match name {
+            "greet" => Some(Path::new("greeting.elp")),
+            _ => None,
+        };
+
+        let mut coverage = Coverage::default();
+        for _ in 0..2 {
+            coverage.add(&module, &[vec![1, 1, 0], vec![0], vec![1], vec![1]], origin);
+        }
+
+        assert_eq!(
+            coverage.to_lcov(),
+            "TN:\nSF:greeting.elp\nDA:2,2\nDA:3,0\nDA:4,0\nLF:3\nLH:1\nend_of_record\n"
+        );
+    }
+}
@@ -0,0 +1,163 @@
+// What `assert_equal` says when the values differ. Values with structure are walked together so a
+// failure points at the fields and items that are different rather than leaving it to whoever
+// reads two long lines to spot them.
+
+use elp_bytecode::{
+    module::TypeInfo,
+    vm::{Value, Vm},
+};
+
+// Paths to the parts that differ start from here, `value[1].y`.
+const ROOT: &str = "value";
+
+pub fn describe(vm: &Vm, actual: &Value, expected: &Value) -> String {
+    let mut message = format!(
+        "assertion failed, expected {} but found {}",
+        vm.debug(expected),
+        vm.debug(actual)
+    );
+    let mut differences = vec![];
+    differ(vm, ROOT.into(), actual, expected, &mut differences);
+    // A difference in the values themselves is what the first line says already.
+    if differences.iter().any(|(path, _)| path != ROOT) {
+        for (path, difference) in differences {
+            message.push_str(&format!("\n  {}: {}", path, difference));
+        }
+    }
+    message
+}
+
+// Every place `actual` and `expected` differ, as a path from the top and what's different there.
+fn differ(
+    vm: &Vm,
+    path: String,
+    actual: &Value,
+    expected: &Value,
+    differences: &mut Vec<(String, String)>,
+) {
+    if actual == expected {
+        return;
+    }
+    match (actual, expected) {
+        (Value::Array(actual), Value::Array(expected)) => {
+            let (actual, expected) = (actual.borrow(), expected.borrow());
+            if actual.len() != expected.len() {
+                differences.push((
+                    path.clone(),
+                    format!(
+                        "expected {} items but found {}",
+                        expected.len(),
+                        actual.len()
+                    ),
+                ));
+            }
+            for index in 0..actual.len().max(expected.len()) {
+                let path = format!("{}[{}]", path, index);
+                match (actual.get(index), expected.get(index)) {
+                    (Some(actual), Some(expected)) => {
+                        differ(vm, path, actual, expected, differences)
+                    }
+                    (Some(actual), None) => differences.push((
+                        path,
+                        format!("expected nothing but found {}", vm.debug(actual)),
+                    )),
+                    (None, Some(expected)) => differences.push((
+                        path,
+                        format!("expected {} but found nothing", vm.debug(expected)),
+                    )),
+                    (None, None) => {}
+                }
+            }
+        }
+        (Value::Object(actual), Value::Object(expected))
+            if actual.borrow().type_index == expected.borrow().type_index =>
+        {
+            let (actual, expected) = (actual.borrow(), expected.borrow());
+            let fields = match &vm.module().types[actual.type_index as usize] {
+                TypeInfo::Object { fields, .. } => fields.clone(),
+                TypeInfo::Enum { .. } => vec![],
+            };
+            for (index, (actual, expected)) in
+                actual.fields.iter().zip(&expected.fields).enumerate()
+            {
+                let field = fields
+                    .get(index)
+                    .cloned()
+                    .unwrap_or_else(|| index.to_string());
+                differ(
+                    vm,
+                    format!("{}.{}", path, field),
+                    actual,
+                    expected,
+                    differences,
+                );
+            }
+        }
+        (Value::Variant(actual), Value::Variant(expected))
+            if actual.type_index == expected.type_index && actual.tag == expected.tag =>
+        {
+            for (index, (actual, expected)) in
+                actual.payload.iter().zip(&expected.payload).enumerate()
+            {
+                differ(
+                    vm,
+                    format!("{}({})", path, index),
+                    actual,
+                    expected,
+                    differences,
+                );
+            }
+        }
+        _ => differences.push((
+            path,
+            format!(
+                "expected {} but found {}",
+                vm.debug(expected),
+                vm.debug(actual)
+            ),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use elp_bytecode::module::Module;
+    use pretty_assertions::assert_eq;
+    use std::{cell::RefCell, rc::Rc};
+
+    fn point(x: i64, y: i64) -> Value {
+        Value::Object(Rc::new(RefCell::new(elp_bytecode::vm::ObjectValue {
+            type_index: 0,
+            fields: vec![Value::Int(x), Value::Int(y)],
+        })))
+    }
+
+    #[test]
+    fn points_at_what_differs() {
+        let vm = Vm::new(Module {
+            types: vec![TypeInfo::Object {
+                name: "Point".into(),
+                fields: vec!["x".into(), "y".into()],
+            }],
+            ..Default::default()
+        });
+
+        assert_eq!(
+            describe(&vm, &Value::string("world"), &Value::string("WORLD")),
+            "assertion failed, expected \"WORLD\" but found \"world\""
+        );
+        assert_eq!(
+            describe(
+                &vm,
+                &Value::array(vec![point(0, 1), point(2, 3), point(4, 5)]),
+                &Value::array(vec![point(0, 1), point(2, 4)]),
+            ),
+            "assertion failed, expected [Point { .x = 0, .y = 1 }, Point { .x = 2, .y = 4 }] but \
+             found [Point { .x = 0, .y = 1 }, Point { .x = 2, .y = 3 }, Point { .x = 4, .y = 5 }]
+  value: expected 2 items but found 3
+  value[1].y: expected 4 but found 3
+  value[2]: expected nothing but found Point { .x = 4, .y = 5 }"
+        );
+    }
+}
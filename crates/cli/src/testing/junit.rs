@@ -0,0 +1,130 @@
+// `elp test --junit`, the results as JUnit XML for CI systems to show. Every test file is a suite
+// and its test functions are the cases in it.
+
+use std::{path::PathBuf, time::Duration};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Case {
+    pub name: String,
+    pub file: PathBuf,
+    pub time: Duration,
+    pub failure: Option<String>,
+}
+
+pub fn render(package: &str, cases: &[Case]) -> String {
+    let mut suites: Vec<(&PathBuf, Vec<&Case>)> = vec![];
+    for case in cases {
+        match suites.iter_mut().find(|(file, _)| **file == case.file) {
+            Some((_, suite)) => suite.push(case),
+            None => suites.push((&case.file, vec![case])),
+        }
+    }
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(&format!(
+        "<testsuites name=\"{}\" tests=\"{}\" failures=\"{}\" time=\"{}\">\n",
+        escape(package),
+        cases.len(),
+        failures(cases.iter()),
+        seconds(cases.iter())
+    ));
+    for (file, suite) in suites {
+        let file = escape(&file.display().to_string());
+        xml.push_str(&format!(
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" time=\"{}\">\n",
+            file,
+            suite.len(),
+            failures(suite.iter().copied()),
+            seconds(suite.iter().copied())
+        ));
+        for case in suite {
+            let open = format!(
+                "    <testcase name=\"{}\" classname=\"{}\" time=\"{}\"",
+                escape(&case.name),
+                file,
+                seconds([case].into_iter())
+            );
+            match &case.failure {
+                None => xml.push_str(&format!("{}/>\n", open)),
+                Some(failure) => xml.push_str(&format!(
+                    "{}>\n      <failure message=\"{}\">{}</failure>\n    </testcase>\n",
+                    open,
+                    escape(failure.lines().next().unwrap_or_default()),
+                    escape(failure)
+                )),
+            }
+        }
+        xml.push_str("  </testsuite>\n");
+    }
+    xml.push_str("</testsuites>\n");
+    xml
+}
+
+fn failures<'c>(cases: impl Iterator<Item = &'c Case>) -> usize {
+    cases.filter(|case| case.failure.is_some()).count()
+}
+
+fn seconds<'c>(cases: impl Iterator<Item = &'c Case>) -> String {
+    format!(
+        "{:.3}",
+        cases.map(|case| case.time).sum::<Duration>().as_secs_f64()
+    )
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn groups_cases_by_file() {
+        let case = |name: &str, file: &str, failure: Option<&str>| Case {
+            name: name.into(),
+            file: file.into(),
+            time: Duration::from_millis(2),
+            failure: failure.map(Into::into),
+        };
+        let cases = [
+            case("test_greets", "greeting.test.elp", None),
+            case("test_sums", "maths.test.elp", None),
+            case(
+                "test_shouts",
+                "greeting.test.elp",
+                Some("assertion failed, expected \"WORLD\" but found \"world\"\n  more"),
+            ),
+        ];
+
+        assert_eq!(
+            render("test", &cases),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites name="test" tests="3" failures="1" time="0.006">
+  <testsuite name="greeting.test.elp" tests="2" failures="1" time="0.004">
+    <testcase name="test_greets" classname="greeting.test.elp" time="0.002"/>
+    <testcase name="test_shouts" classname="greeting.test.elp" time="0.002">
+      <failure message="assertion failed, expected &quot;WORLD&quot; but found &quot;world&quot;">assertion failed, expected &quot;WORLD&quot; but found &quot;world&quot;
+  more</failure>
+    </testcase>
+  </testsuite>
+  <testsuite name="maths.test.elp" tests="1" failures="0" time="0.002">
+    <testcase name="test_sums" classname="maths.test.elp" time="0.002"/>
+  </testsuite>
+</testsuites>
+"#
+        );
+    }
+}
@@ -0,0 +1,6 @@
+// What `elp test` reports besides whether each test passed: the differences a failed
+// `assert_equal` found, JUnit XML for CI and lcov line coverage.
+
+pub mod coverage;
+pub mod diff;
+pub mod junit;
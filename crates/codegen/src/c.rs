@@ -0,0 +1,2133 @@
+// Lowers the MIR to portable C11 that any C compiler can build, for the targets LLVM doesn't reach.
+// Objects are heap allocated structs passed around by pointer, enums are tagged unions passed by
+// value and arrays are pointers to a small runtime header, the same layout the LLVM backend uses.
+// C has no expression blocks so matches are hoisted into statements ahead of the expression that
+// uses them, which means they run before the rest of that expression does.
+
+use std::{
+    collections::{BTreeSet, HashSet},
+    fmt::Write,
+};
+
+use elp_ir::{
+    ffi::{self, CallingConvention},
+    mir::{
+        BinaryOp, Expr, Function, Global, Literal, MatchArm, Module, Pattern, Stmt, StmtKind, Type,
+        UnaryOp,
+    },
+};
+
+use crate::{
+    mono::monomorphize,
+    typing::{is_builtin, CodegenResult, TypeContext, INT},
+};
+
+// The generated code for a module. When the module exports anything `header` declares it for other
+// C code, the source includes it as `<module name>.h` so both have to be written side by side.
+#[derive(Debug, PartialEq, Clone)]
+pub struct COutput {
+    pub header: Option<String>,
+    pub source: String,
+}
+
+#[derive(Debug, Clone)]
+struct Value {
+    ty: Type,
+    repr: String,
+}
+
+impl Value {
+    fn new(ty: Type, repr: impl Into<String>) -> Self {
+        Self {
+            ty,
+            repr: repr.into(),
+        }
+    }
+}
+
+const KEYWORDS: &[&str] = &[
+    "auto", "break", "case", "char", "const", "continue", "default", "do", "double", "else",
+    "enum", "extern", "float", "for", "goto", "if", "inline", "int", "long", "register",
+    "restrict", "return", "short", "signed", "sizeof", "static", "struct", "switch", "typedef",
+    "union", "unsigned", "void", "volatile", "while", "bool", "true", "false", "main",
+];
+
+// C identifiers are letters, digits and underscores, `Point.sum` becomes `Point_sum`.
+fn ident(name: &str) -> String {
+    let ident = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect::<String>();
+    if ident.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{}", ident)
+    } else {
+        ident
+    }
+}
+
+fn pointer_to(c_type: &str) -> String {
+    if c_type.ends_with('*') {
+        format!("{}*", c_type)
+    } else {
+        format!("{} *", c_type)
+    }
+}
+
+// `int32_t x` and `const char *x`.
+fn declaration(c_type: &str, name: &str) -> String {
+    if c_type.ends_with('*') {
+        format!("{}{}", c_type, name)
+    } else {
+        format!("{} {}", c_type, name)
+    }
+}
+
+// Comes after the name in a prototype, `int32_t MessageBeep __attribute__((stdcall))(..)`.
+fn calling_convention(convention: CallingConvention) -> &'static str {
+    match convention {
+        CallingConvention::C => "",
+        CallingConvention::StdCall => " __attribute__((stdcall))",
+        CallingConvention::FastCall => " __attribute__((fastcall))",
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'"' => "\\\"".into(),
+            b'\\' => "\\\\".into(),
+            b'\n' => "\\n".into(),
+            b'\t' => "\\t".into(),
+            b' '..=b'~' => (byte as char).to_string(),
+            // Octal escapes stop after three digits, hex ones would swallow any digits after them.
+            _ => format!("\\{:03o}", byte),
+        })
+        .collect()
+}
+
+fn float_literal(value: f64, bits: u8) -> String {
+    let mut out = if value.is_nan() {
+        "NAN".into()
+    } else if value.is_infinite() {
+        if value > 0.0 { "INFINITY" } else { "-INFINITY" }.into()
+    } else {
+        let out = format!("{:?}", value);
+        if out.contains(['.', 'e']) {
+            out
+        } else {
+            format!("{}.0", out)
+        }
+    };
+    if bits == 32 && value.is_finite() {
+        out.push('f');
+    }
+    out
+}
+
+// The names of the objects and enums a type refers to.
+fn referenced_types(ty: &Type, out: &mut Vec<String>) {
+    match ty {
+        Type::Named { name, generics } => {
+            out.push(name.clone());
+            generics.iter().for_each(|ty| referenced_types(ty, out));
+        }
+        Type::Array(inner) | Type::Pointer(inner) | Type::Reference(inner) => {
+            referenced_types(inner, out)
+        }
+        Type::Function { params, ret } => {
+            params.iter().for_each(|ty| referenced_types(ty, out));
+            referenced_types(ret, out);
+        }
+        _ => {}
+    }
+}
+
+fn uses_arrays(ty: &Type) -> bool {
+    match ty {
+        Type::Array(_) => true,
+        Type::Pointer(inner) | Type::Reference(inner) => uses_arrays(inner),
+        Type::Function { params, ret } => params.iter().any(uses_arrays) || uses_arrays(ret),
+        _ => false,
+    }
+}
+
+const ARRAY_TYPE: &str = "#ifndef ELP_ARRAY
+#define ELP_ARRAY
+typedef struct elp_array {
+    void *data;
+    int64_t len;
+    int64_t cap;
+} elp_array;
+#endif
+";
+
+// `__asm__` labels name a symbol exactly, without the `_` some platforms put before C's.
+const SYMBOL_LABEL: &str = "#define ELP_STRINGIFY(x) #x
+#define ELP_PREFIX(prefix) ELP_STRINGIFY(prefix)
+#define ELP_SYMBOL(name) __asm__(ELP_PREFIX(__USER_LABEL_PREFIX__) name)
+";
+
+const ALLOC_HELPER: &str = "static void *elp_alloc(const void *value, size_t size) {
+    void *out = malloc(size);
+    memcpy(out, value, size);
+    return out;
+}
+";
+
+const ARRAY_HELPERS: &str =
+    "static elp_array *elp_array_new(size_t size, int64_t len, const void *items) {
+    elp_array *array = malloc(sizeof(elp_array));
+    array->data = malloc(size * (size_t)(len > 0 ? len : 1));
+    if (len > 0) {
+        memcpy(array->data, items, size * (size_t)len);
+    }
+    array->len = len;
+    array->cap = len;
+    return array;
+}
+
+static int64_t elp_array_append(elp_array *array, size_t size, const void *item) {
+    if (array->len == array->cap) {
+        array->cap = array->cap * 2 > 4 ? array->cap * 2 : 4;
+        array->data = realloc(array->data, size * (size_t)array->cap);
+    }
+    memcpy((char *)array->data + size * (size_t)array->len, item, size);
+    return array->len++;
+}
+";
+
+const STRING_HELPERS: &str = "static const char *elp_string_concat(const char *a, const char *b) {
+    size_t a_len = strlen(a);
+    size_t b_len = strlen(b);
+    char *out = malloc(a_len + b_len + 1);
+    memcpy(out, a, a_len);
+    memcpy(out + a_len, b, b_len + 1);
+    return out;
+}
+";
+
+// Whose symbols a header declares. The C backend calls a method `Point_sum` where LLVM keeps
+// `Point.sum`, which isn't a C identifier.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Symbols {
+    C,
+    Llvm,
+}
+
+struct Emitter<'m> {
+    ctx: TypeContext<'m>,
+    includes: BTreeSet<&'static str>,
+    alloc_helper: bool,
+    array_helpers: bool,
+    string_helpers: bool,
+
+    // Per function state.
+    code: String,
+    indent: usize,
+    // The C names of the locals in scope, innermost last.
+    locals: Vec<(String, String)>,
+    // Every C name taken in the function, C doesn't allow shadowing within a block.
+    used: HashSet<String>,
+    next_temp: u32,
+    ret: Type,
+    is_main: bool,
+}
+
+impl<'m> Emitter<'m> {
+    fn new(module: &'m Module) -> Self {
+        Self {
+            ctx: TypeContext::new(module),
+            includes: BTreeSet::from(["stdbool.h", "stdint.h"]),
+            alloc_helper: false,
+            array_helpers: false,
+            string_helpers: false,
+            code: String::new(),
+            indent: 0,
+            locals: vec![],
+            used: HashSet::new(),
+            next_temp: 0,
+            ret: Type::Void,
+            is_main: false,
+        }
+    }
+
+    fn c_type(&self, ty: &Type) -> CodegenResult<String> {
+        Ok(match ty {
+            Type::Void | Type::Bool | Type::Int { .. } | Type::Float { .. } | Type::String => {
+                match (ffi::c_type(ty), ty) {
+                    (Some(primitive), _) => primitive.into(),
+                    (None, Type::Int { bits, .. }) => {
+                        return self.ctx.error(format!("C has no {} bit integers", bits))
+                    }
+                    (None, _) => unreachable!("every other primitive has a C type"),
+                }
+            }
+            Type::Array(_) => "elp_array *".into(),
+            Type::Pointer(_) | Type::Reference(_) | Type::Function { .. } => "void *".into(),
+            Type::Named { name, .. } => {
+                if self.ctx.module.object(name).is_some() {
+                    format!("{} *", ident(name))
+                } else if self.ctx.module.r#enum(name).is_some() {
+                    ident(name)
+                } else {
+                    return self.ctx.error(format!("unknown type `{}`", name));
+                }
+            }
+            Type::Unknown => {
+                return self
+                    .ctx
+                    .error("native code needs to know this type, add a type annotation")
+            }
+        })
+    }
+
+    fn line(&mut self, text: impl AsRef<str>) {
+        for _ in 0..self.indent {
+            self.code.push_str("    ");
+        }
+        self.code.push_str(text.as_ref());
+        self.code.push('\n');
+    }
+
+    // Runs `f` with its statements going into a fresh buffer one level deeper, for code that has to
+    // end up inside a block opened afterwards.
+    fn capture<T>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> CodegenResult<T>,
+    ) -> CodegenResult<(String, T)> {
+        let outer = std::mem::take(&mut self.code);
+        self.indent += 1;
+        let result = f(self);
+        self.indent -= 1;
+        let code = std::mem::replace(&mut self.code, outer);
+        Ok((code, result?))
+    }
+
+    fn fresh(&mut self, name: &str) -> String {
+        let name = ident(name);
+        let mut candidate = name.clone();
+        while KEYWORDS.contains(&candidate.as_str()) || self.used.contains(&candidate) {
+            self.next_temp += 1;
+            candidate = format!("{}_{}", name, self.next_temp);
+        }
+        self.used.insert(candidate.clone());
+        candidate
+    }
+
+    fn temp(&mut self) -> String {
+        self.next_temp += 1;
+        let name = format!("tmp{}", self.next_temp);
+        self.fresh(&name)
+    }
+
+    // Declares a C variable for `value` and returns it, for values that are used more than once.
+    fn spill(&mut self, value: Value) -> CodegenResult<Value> {
+        let name = self.temp();
+        let c_type = self.c_type(&value.ty)?;
+        self.line(format!("{} = {};", declaration(&c_type, &name), value.repr));
+        Ok(Value::new(value.ty, name))
+    }
+
+    fn declare_local(&mut self, name: &str, ty: Type, init: &str) -> CodegenResult<()> {
+        let c_name = self.fresh(name);
+        let c_type = self.c_type(&ty)?;
+        self.line(format!("{} = {};", declaration(&c_type, &c_name), init));
+        self.ctx.declare(name, ty);
+        self.locals.push((name.into(), c_name));
+        Ok(())
+    }
+
+    fn local(&self, name: &str) -> Option<&str> {
+        self.locals
+            .iter()
+            .rev()
+            .find(|(local, _)| local == name)
+            .map(|(_, c_name)| c_name.as_str())
+    }
+
+    fn scoped<T>(&mut self, f: impl FnOnce(&mut Self) -> CodegenResult<T>) -> CodegenResult<T> {
+        let depth = self.locals.len();
+        self.ctx.push_scope();
+        let result = f(self);
+        self.ctx.pop_scope();
+        self.locals.truncate(depth);
+        result
+    }
+
+    fn coerce(&mut self, value: Value, to: &Type) -> CodegenResult<Value> {
+        let from = self.c_type(&value.ty)?;
+        let c_type = self.c_type(to)?;
+        if from == c_type {
+            return Ok(Value::new(to.clone(), value.repr));
+        }
+
+        match (&value.ty, to) {
+            (
+                Type::Int { .. } | Type::Float { .. } | Type::Bool,
+                Type::Int { .. } | Type::Float { .. },
+            ) => Ok(Value::new(
+                to.clone(),
+                format!("(({}){})", c_type, value.repr),
+            )),
+            // `nil` fits anything passed around by pointer.
+            (Type::Pointer(_), _) if c_type.ends_with('*') => {
+                Ok(Value::new(to.clone(), value.repr))
+            }
+            _ => self.ctx.error(format!(
+                "expected a value of type {} but found {}",
+                c_type, from
+            )),
+        }
+    }
+
+    // The typedefs and struct definitions of `names`, enums are ordered so every payload is defined
+    // before the union holding it.
+    fn emit_types(&self, names: &BTreeSet<String>) -> CodegenResult<String> {
+        let module = self.ctx.module;
+        let mut out = String::new();
+
+        for object in module.objects.iter().filter(|o| names.contains(&o.name)) {
+            let name = ident(&object.name);
+            let _ = writeln!(out, "typedef struct {} {};", name, name);
+        }
+
+        let mut order = vec![];
+        for r#enum in module.enums.iter().filter(|e| names.contains(&e.name)) {
+            self.order_enum(&r#enum.name, &mut vec![], &mut order)?;
+        }
+        for r#enum in order.iter().filter_map(|name| module.r#enum(name)) {
+            let name = ident(&r#enum.name);
+            if !out.is_empty() {
+                out.push('\n');
+            }
+            let tags = r#enum
+                .variants
+                .iter()
+                .map(|variant| format!("{}_{}", name, ident(&variant.name)))
+                .collect::<Vec<_>>();
+            let _ = writeln!(
+                out,
+                "typedef enum {{ {} }} {}_Tag;\n",
+                tags.join(", "),
+                name
+            );
+            let _ = writeln!(out, "typedef struct {} {{\n    {}_Tag tag;", name, name);
+            if r#enum.variants.iter().any(|v| !v.payload.is_empty()) {
+                out.push_str("    union {\n");
+                for variant in r#enum.variants.iter().filter(|v| !v.payload.is_empty()) {
+                    let mut fields = vec![];
+                    for (index, ty) in variant.payload.iter().enumerate() {
+                        let c_type = self.c_type(ty)?;
+                        fields.push(format!("{};", declaration(&c_type, &format!("_{}", index))));
+                    }
+                    let _ = writeln!(
+                        out,
+                        "        struct {{ {} }} {};",
+                        fields.join(" "),
+                        ident(&variant.name)
+                    );
+                }
+                out.push_str("    } as;\n");
+            }
+            let _ = writeln!(out, "}} {};", name);
+        }
+
+        for object in module.objects.iter().filter(|o| names.contains(&o.name)) {
+            let _ = writeln!(out, "\nstruct {} {{", ident(&object.name));
+            for field in &object.fields {
+                let c_type = self.c_type(&field.ty)?;
+                let _ = writeln!(out, "    {};", declaration(&c_type, &ident(&field.name)));
+            }
+            out.push_str("};\n");
+        }
+
+        Ok(out)
+    }
+
+    fn order_enum(
+        &self,
+        name: &str,
+        visiting: &mut Vec<String>,
+        order: &mut Vec<String>,
+    ) -> CodegenResult<()> {
+        if order.iter().any(|done| done == name) {
+            return Ok(());
+        }
+        if visiting.iter().any(|open| open == name) {
+            return self.ctx.error(format!(
+                "`{}` contains itself, it needs an object in between to be stored",
+                name
+            ));
+        }
+        let Some(r#enum) = self.ctx.module.r#enum(name) else {
+            return Ok(());
+        };
+
+        visiting.push(name.into());
+        for ty in r#enum.variants.iter().flat_map(|v| &v.payload) {
+            // Only enums are stored by value, everything else is behind a pointer.
+            if let Type::Named { name, .. } = ty {
+                self.order_enum(name, visiting, order)?;
+            }
+        }
+        visiting.pop();
+        order.push(name.into());
+
+        Ok(())
+    }
+
+    fn prototype(&self, function: &Function) -> CodegenResult<String> {
+        if self.is_entry_point(function) {
+            return Ok("int main(void)".into());
+        }
+        let params = function
+            .params
+            .iter()
+            .map(|param| Ok(declaration(&self.c_type(&param.ty)?, &ident(&param.name))))
+            .collect::<CodegenResult<Vec<_>>>()?;
+
+        Ok(format!(
+            "{}{}({})",
+            if function.exported { "" } else { "static " },
+            declaration(
+                &self.c_type(&function.ret)?,
+                &ident(&function.qualified_name())
+            ),
+            if params.is_empty() {
+                "void".into()
+            } else {
+                params.join(", ")
+            }
+        ))
+    }
+
+    fn is_entry_point(&self, function: &Function) -> bool {
+        function.name == "main" && function.receiver.is_none()
+    }
+
+    // Exported types, the types they mention and the types in exported signatures go in the
+    // header, everything else stays private to the source. Also whether any of them are arrays.
+    fn header_types(&self) -> (BTreeSet<String>, bool) {
+        let module = self.ctx.module;
+        let mut header_types = BTreeSet::new();
+        let mut pending = vec![];
+        let mut header_arrays = false;
+        for object in module.objects.iter().filter(|o| o.exported) {
+            pending.push(object.name.clone());
+        }
+        for r#enum in module.enums.iter().filter(|e| e.exported) {
+            pending.push(r#enum.name.clone());
+        }
+        for function in module
+            .functions
+            .iter()
+            .filter(|f| f.exported && !self.is_entry_point(f))
+        {
+            for ty in function.params.iter().map(|p| &p.ty).chain([&function.ret]) {
+                referenced_types(ty, &mut pending);
+                header_arrays |= uses_arrays(ty);
+            }
+        }
+        for global in module.globals.iter().filter(|g| g.exported) {
+            referenced_types(&global.ty, &mut pending);
+            header_arrays |= uses_arrays(&global.ty);
+        }
+        while let Some(name) = pending.pop() {
+            if !header_types.insert(name.clone()) {
+                continue;
+            }
+            let fields = match (module.object(&name), module.r#enum(&name)) {
+                (Some(object), _) => object.fields.iter().map(|f| &f.ty).collect::<Vec<_>>(),
+                (_, Some(r#enum)) => r#enum.variants.iter().flat_map(|v| &v.payload).collect(),
+                _ => vec![],
+            };
+            for ty in fields {
+                referenced_types(ty, &mut pending);
+                header_arrays |= uses_arrays(ty);
+            }
+        }
+        (header_types, header_arrays)
+    }
+
+    // `const char *const NAME`, a module level value with its type.
+    fn global_declaration(&mut self, global: &Global) -> CodegenResult<(Type, String)> {
+        self.ctx.span = global.span;
+        let ty = self.ctx.global(&global.name)?;
+        let c_type = self.c_type(&ty)?;
+        let name = ident(&global.name);
+        let name = match (global.mutable, c_type.ends_with('*')) {
+            (true, _) => declaration(&c_type, &name),
+            // The pointer itself is what can't change.
+            (false, true) => format!("{}const {}", c_type, name),
+            (false, false) => format!("const {}", declaration(&c_type, &name)),
+        };
+        Ok((ty, name))
+    }
+
+    // What's exported for other C code to use, None when nothing is.
+    fn header(&mut self, symbols: Symbols) -> CodegenResult<Option<String>> {
+        let module = self.ctx.module;
+        let (header_types, header_arrays) = self.header_types();
+
+        let mut header_globals = String::new();
+        for global in module.globals.iter().filter(|g| g.exported) {
+            let (_, name) = self.global_declaration(global)?;
+            let _ = writeln!(header_globals, "extern {};", name);
+        }
+
+        let mut header_prototypes = String::new();
+        let mut labels = false;
+        for function in module.functions.iter().filter(|f| f.exported) {
+            if self.is_entry_point(function) {
+                continue;
+            }
+            self.ctx.span = function.span;
+            let name = function.qualified_name();
+            match symbols {
+                // `Point.sum` in LLVM, which C can only name through a label.
+                Symbols::Llvm if ident(&name) != name => {
+                    labels = true;
+                    let _ = writeln!(
+                        header_prototypes,
+                        "{} ELP_SYMBOL(\"{}\");",
+                        self.prototype(function)?,
+                        escape(&name)
+                    );
+                }
+                _ => {
+                    let _ = writeln!(header_prototypes, "{};", self.prototype(function)?);
+                }
+            }
+        }
+
+        if header_types.is_empty() && header_prototypes.is_empty() && header_globals.is_empty() {
+            return Ok(None);
+        }
+        let guard = format!("ELP_{}_H", ident(&module.name).to_uppercase());
+        let mut out = format!(
+            "// Generated from {}.elp.\n\n#ifndef {}\n#define {}\n\n#include <stdbool.h>\n#include <stdint.h>\n",
+            module.name, guard, guard
+        );
+        if header_arrays {
+            out.push('\n');
+            out.push_str(ARRAY_TYPE);
+        }
+        if labels {
+            out.push('\n');
+            out.push_str(SYMBOL_LABEL);
+        }
+        for section in [
+            self.emit_types(&header_types)?,
+            header_globals,
+            header_prototypes,
+        ] {
+            if !section.is_empty() {
+                out.push('\n');
+                out.push_str(&section);
+            }
+        }
+        let _ = write!(out, "\n#endif\n");
+        Ok(Some(out))
+    }
+
+    fn emit_module(mut self) -> CodegenResult<COutput> {
+        let module = self.ctx.module;
+
+        let (header_types, header_arrays) = self.header_types();
+        let source_types = module
+            .objects
+            .iter()
+            .map(|o| &o.name)
+            .chain(module.enums.iter().map(|e| &e.name))
+            .filter(|name| !header_types.contains(*name))
+            .cloned()
+            .collect::<BTreeSet<_>>();
+
+        // Check every signature first so errors point at the declaration rather than a call.
+        for function in &module.functions {
+            self.ctx.span = function.span;
+            for param in &function.params {
+                self.c_type(&param.ty)?;
+            }
+            self.c_type(&function.ret)?;
+        }
+
+        let header = self.header(Symbols::C)?;
+
+        let mut globals = String::new();
+        for global in &module.globals {
+            let (ty, name) = self.global_declaration(global)?;
+            let initialiser = match &global.value {
+                Expr::Literal(literal) => self.literal(literal, Some(&ty))?.repr,
+                _ => {
+                    return self
+                        .ctx
+                        .error("module level values have to be literals in native code")
+                }
+            };
+            if global.exported {
+                let _ = writeln!(globals, "{} = {};", name, initialiser);
+            } else {
+                let _ = writeln!(globals, "static {} = {};", name, initialiser);
+            }
+        }
+
+        let mut prototypes = String::new();
+        for function in &module.functions {
+            if self.is_entry_point(function) || function.exported {
+                continue;
+            }
+            let _ = writeln!(prototypes, "{};", self.prototype(function)?);
+        }
+
+        let mut functions = String::new();
+        for function in &module.functions {
+            functions.push('\n');
+            functions.push_str(&self.emit_function(function)?);
+        }
+
+        let mut externals = String::new();
+        for external in &module.externals {
+            self.ctx.span = external.span;
+            let params = external
+                .params
+                .iter()
+                .map(|param| Ok(declaration(&self.c_type(&param.ty)?, &ident(&param.name))))
+                .collect::<CodegenResult<Vec<_>>>()?;
+            let _ = writeln!(
+                externals,
+                "{}{}({});",
+                declaration(&self.c_type(&external.ret)?, &ident(&external.name)),
+                calling_convention(external.abi.calling_convention()),
+                if params.is_empty() {
+                    "void".into()
+                } else {
+                    params.join(", ")
+                }
+            );
+        }
+
+        if self.alloc_helper || self.array_helpers || self.string_helpers {
+            self.includes.insert("stdlib.h");
+            self.includes.insert("string.h");
+        }
+
+        let mut source = format!("// Generated from {}.elp.\n\n", module.name);
+        for include in &self.includes {
+            let _ = writeln!(source, "#include <{}>", include);
+        }
+        if header.is_some() {
+            let _ = writeln!(source, "\n#include \"{}.h\"", module.name);
+        }
+        if self.array_helpers && !header_arrays {
+            source.push('\n');
+            source.push_str(ARRAY_TYPE);
+        }
+        for section in [
+            self.emit_types(&source_types)?,
+            externals,
+            globals,
+            prototypes,
+        ] {
+            if !section.is_empty() {
+                source.push('\n');
+                source.push_str(&section);
+            }
+        }
+        for (used, helpers) in [
+            (self.alloc_helper, ALLOC_HELPER),
+            (self.array_helpers, ARRAY_HELPERS),
+            (self.string_helpers, STRING_HELPERS),
+        ] {
+            if used {
+                source.push('\n');
+                source.push_str(helpers);
+            }
+        }
+        source.push_str(&functions);
+
+        Ok(COutput { header, source })
+    }
+
+    fn emit_function(&mut self, function: &Function) -> CodegenResult<String> {
+        self.ctx.span = function.span;
+
+        self.code.clear();
+        self.indent = 1;
+        self.locals.clear();
+        self.used = function.params.iter().map(|p| ident(&p.name)).collect();
+        self.next_temp = 0;
+        self.ret = function.ret.clone();
+        self.is_main = self.is_entry_point(function);
+
+        self.ctx.push_scope();
+        for param in &function.params {
+            self.ctx.declare(&param.name, param.ty.clone());
+            self.locals.push((param.name.clone(), ident(&param.name)));
+        }
+        let result = function
+            .body
+            .iter()
+            .try_for_each(|stmt| self.emit_stmt(stmt));
+        self.ctx.pop_scope();
+        result?;
+
+        if self.is_main
+            && function.ret == Type::Void
+            && !matches!(
+                function.body.last().map(|s| &s.kind),
+                Some(StmtKind::Return(_))
+            )
+        {
+            self.line("return 0;");
+        }
+
+        Ok(format!(
+            "{} {{\n{}}}\n",
+            self.prototype(function)?,
+            self.code
+        ))
+    }
+
+    fn emit_block(&mut self, body: &[Stmt]) -> CodegenResult<()> {
+        self.indent += 1;
+        let result = self.scoped(|e| body.iter().try_for_each(|stmt| e.emit_stmt(stmt)));
+        self.indent -= 1;
+        result
+    }
+
+    fn emit_stmt(&mut self, stmt: &Stmt) -> CodegenResult<()> {
+        self.ctx.span = stmt.span;
+
+        match &stmt.kind {
+            StmtKind::Let {
+                name, ty, value, ..
+            } => {
+                let ty = match (ty, value) {
+                    (Type::Unknown, Some(value)) => self.ctx.type_of(value)?,
+                    (Type::Unknown, None) => {
+                        return self
+                            .ctx
+                            .error(format!("`{}` needs a type or a value", name))
+                    }
+                    (ty, _) => ty.clone(),
+                };
+                let init = match value {
+                    Some(value) => {
+                        let value = self.emit_expr(value, Some(&ty))?;
+                        self.coerce(value, &ty)?.repr
+                    }
+                    None => "{0}".into(),
+                };
+                self.declare_local(name, ty, &init)?;
+            }
+            StmtKind::Assign { target, op, value } => {
+                let (place, ty) = self.place(target)?;
+                let value = match op {
+                    Some(op) => self.emit_binary(*op, target, value)?,
+                    None => self.emit_expr(value, Some(&ty))?,
+                };
+                let value = self.coerce(value, &ty)?;
+                self.line(format!("{} = {};", place, value.repr));
+            }
+            StmtKind::Expr(expr) => {
+                let value = self.emit_expr(expr, None)?;
+                match expr {
+                    _ if value.repr.is_empty() => {}
+                    Expr::Call { .. } | Expr::MethodCall { .. } => {
+                        self.line(format!("{};", value.repr))
+                    }
+                    _ => self.line(format!("(void){};", value.repr)),
+                }
+            }
+            StmtKind::Return(value) => match value {
+                Some(value) if self.is_main && self.ret != Type::Void => {
+                    let value = self.emit_expr(value, Some(&self.ret.clone()))?;
+                    self.line(format!("return (int){};", value.repr));
+                }
+                _ if self.is_main => self.line("return 0;"),
+                Some(value) => {
+                    let ret = self.ret.clone();
+                    let value = self.emit_expr(value, Some(&ret))?;
+                    let value = self.coerce(value, &ret)?;
+                    self.line(format!("return {};", value.repr));
+                }
+                None => self.line("return;"),
+            },
+            StmtKind::If {
+                condition,
+                then_body,
+                else_body,
+            } => {
+                let condition = self.emit_condition(condition)?;
+                self.line(format!("if ({}) {{", condition.repr));
+                self.emit_block(then_body)?;
+                if else_body.is_empty() {
+                    self.line("}");
+                } else {
+                    self.line("} else {");
+                    self.emit_block(else_body)?;
+                    self.line("}");
+                }
+            }
+            StmtKind::For {
+                binding,
+                iterable,
+                body,
+            } => self.emit_for(binding, iterable, body)?,
+        }
+
+        Ok(())
+    }
+
+    fn emit_for(&mut self, binding: &str, iterable: &Expr, body: &[Stmt]) -> CodegenResult<()> {
+        let array = self.emit_expr(iterable, None)?;
+        let Type::Array(item) = array.ty.clone() else {
+            return self
+                .ctx
+                .error("only arrays can be looped over in native code");
+        };
+        self.line("{");
+        self.indent += 1;
+        let array = self.spill(array)?;
+        let index = self.temp();
+        self.line(format!(
+            "for (int64_t {} = 0; {} < {}->len; {}++) {{",
+            index, index, array.repr, index
+        ));
+        let c_type = self.c_type(&item)?;
+        self.indent += 1;
+        let result = self.scoped(|e| {
+            e.declare_local(
+                binding,
+                *item,
+                &format!(
+                    "(({})({})->data)[{}]",
+                    pointer_to(&c_type),
+                    array.repr,
+                    index
+                ),
+            )?;
+            body.iter().try_for_each(|stmt| e.emit_stmt(stmt))
+        });
+        self.indent -= 1;
+        result?;
+        self.line("}");
+        self.indent -= 1;
+        self.line("}");
+
+        Ok(())
+    }
+
+    fn emit_condition(&mut self, condition: &Expr) -> CodegenResult<Value> {
+        let value = self.emit_expr(condition, Some(&Type::Bool))?;
+        if value.ty != Type::Bool {
+            return self.ctx.error("conditions have to be booleans");
+        }
+        Ok(value)
+    }
+
+    // The C lvalue for an assignment target and the type stored there.
+    fn place(&mut self, target: &Expr) -> CodegenResult<(String, Type)> {
+        match target {
+            Expr::Local(name) => {
+                let ty = self.ctx.type_of(target)?;
+                match self.local(name) {
+                    Some(c_name) => Ok((c_name.to_string(), ty)),
+                    None => self.ctx.error(format!("`{}` isn't defined", name)),
+                }
+            }
+            Expr::Global(name) => Ok((ident(name), self.ctx.global(name)?)),
+            Expr::Field { base, name } => {
+                let base = self.emit_expr(base, None)?;
+                let (object, index) = self.ctx.field(&base.ty, name)?;
+                Ok((
+                    format!("{}->{}", base.repr, ident(name)),
+                    object.fields[index].ty.clone(),
+                ))
+            }
+            _ => self.ctx.error("can't assign to this expression"),
+        }
+    }
+
+    fn literal(&mut self, literal: &Literal, expected: Option<&Type>) -> CodegenResult<Value> {
+        Ok(match (literal, expected) {
+            (Literal::Nil, _) => Value::new(Type::Pointer(Box::new(Type::Void)), "NULL"),
+            (Literal::Bool(value), _) => Value::new(Type::Bool, value.to_string()),
+            (Literal::Int(value), Some(ty @ Type::Int { .. })) => {
+                Value::new(ty.clone(), value.to_string())
+            }
+            (Literal::Int(value), Some(Type::Float { bits })) => self.float(*value as f64, *bits),
+            (Literal::Int(value), _) => Value::new(INT, value.to_string()),
+            (Literal::Float(value), Some(Type::Float { bits })) => self.float(*value, *bits),
+            (Literal::Float(value), _) => self.float(*value, 64),
+            (Literal::String(value), _) => {
+                Value::new(Type::String, format!("\"{}\"", escape(value)))
+            }
+        })
+    }
+
+    fn float(&mut self, value: f64, bits: u8) -> Value {
+        if !value.is_finite() {
+            self.includes.insert("math.h");
+        }
+        Value::new(Type::Float { bits }, float_literal(value, bits))
+    }
+
+    fn emit_expr(&mut self, expr: &Expr, expected: Option<&Type>) -> CodegenResult<Value> {
+        match expr {
+            Expr::Literal(literal) => self.literal(literal, expected),
+            Expr::Local(name) => {
+                let ty = self.ctx.type_of(expr)?;
+                match self.local(name) {
+                    Some(c_name) => Ok(Value::new(ty, c_name)),
+                    None => self.ctx.error(format!("`{}` isn't defined", name)),
+                }
+            }
+            Expr::Global(name) => {
+                if self.ctx.signature(name).is_some() {
+                    return self.ctx.error(format!(
+                        "`{}` can only be called, functions aren't values in native code yet",
+                        name
+                    ));
+                }
+                Ok(Value::new(self.ctx.global(name)?, ident(name)))
+            }
+            Expr::Field { .. } => {
+                let (place, ty) = self.place(expr)?;
+                Ok(Value::new(ty, place))
+            }
+            Expr::Call { callee, args } => self.emit_call(callee, args),
+            Expr::MethodCall {
+                receiver,
+                method,
+                args,
+            } => self.emit_method_call(receiver, method, args),
+            Expr::Binary { op, lhs, rhs } => self.emit_binary(*op, lhs, rhs),
+            Expr::Unary { op, operand } => {
+                let value = self.emit_expr(operand, expected)?;
+                match (op, &value.ty) {
+                    (UnaryOp::Not, Type::Bool) => {
+                        Ok(Value::new(Type::Bool, format!("(!{})", value.repr)))
+                    }
+                    (UnaryOp::Neg, Type::Int { .. } | Type::Float { .. }) => {
+                        Ok(Value::new(value.ty.clone(), format!("(-{})", value.repr)))
+                    }
+                    _ => {
+                        let c_type = self.c_type(&value.ty)?;
+                        self.ctx.error(format!(
+                            "can't apply {:?} to a value of type {}",
+                            op, c_type
+                        ))
+                    }
+                }
+            }
+            Expr::Object { name, fields } => self.emit_object(name, fields),
+            Expr::Variant {
+                enum_name,
+                variant,
+                args,
+            } => self.emit_variant(enum_name.as_deref(), variant, args, expected),
+            Expr::Array(items) => {
+                let ty = match expected {
+                    Some(ty @ Type::Array(_)) => ty.clone(),
+                    _ => self.ctx.type_of(expr)?,
+                };
+                let Type::Array(item) = &ty else {
+                    unreachable!("array literals are always arrays")
+                };
+                self.array_helpers = true;
+                let c_type = self.c_type(item)?;
+                let mut values = vec![];
+                for value in items {
+                    let value = self.emit_expr(value, Some(item))?;
+                    values.push(self.coerce(value, item)?.repr);
+                }
+                let items = match values.is_empty() {
+                    true => "NULL".into(),
+                    false => format!("({}[]){{ {} }}", c_type, values.join(", ")),
+                };
+                Ok(Value::new(
+                    ty.clone(),
+                    format!(
+                        "elp_array_new(sizeof({}), {}, {})",
+                        c_type,
+                        values.len(),
+                        items
+                    ),
+                ))
+            }
+            Expr::Closure(_) => self
+                .ctx
+                .error("closures aren't supported by the C backend yet"),
+            Expr::Match { scrutinee, arms } => self.emit_match(scrutinee, arms, expected),
+            Expr::Element(element) => self.ctx.error(format!(
+                "`{}` is an element, elements are only supported by the web backend",
+                element.name
+            )),
+        }
+    }
+
+    fn emit_args(&mut self, params: &[Type], args: &[Value]) -> CodegenResult<Vec<String>> {
+        let mut out = vec![];
+        for (param, arg) in params.iter().zip(args) {
+            out.push(self.coerce(arg.clone(), param)?.repr);
+        }
+        Ok(out)
+    }
+
+    fn emit_call(&mut self, callee: &Expr, args: &[Expr]) -> CodegenResult<Value> {
+        let Expr::Global(name) = callee else {
+            return self
+                .ctx
+                .error("only functions can be called directly in native code");
+        };
+        if is_builtin(name) && self.ctx.signature(name).is_none() {
+            return self.emit_print(name, args);
+        }
+        let Some(signature) = self.ctx.signature(name) else {
+            return self.ctx.error(format!("`{}` isn't defined", name));
+        };
+        if signature.params.len() != args.len() {
+            return self.ctx.error(format!(
+                "`{}` takes {} arguments but {} were given",
+                name,
+                signature.params.len(),
+                args.len()
+            ));
+        }
+
+        let mut values = vec![];
+        for (param, arg) in signature.params.iter().zip(args) {
+            values.push(self.emit_expr(arg, Some(param))?);
+        }
+        let args = self.emit_args(&signature.params, &values)?;
+        Ok(Value::new(
+            signature.ret,
+            format!("{}({})", ident(&signature.name), args.join(", ")),
+        ))
+    }
+
+    fn emit_method_call(
+        &mut self,
+        receiver: &Expr,
+        method: &str,
+        args: &[Expr],
+    ) -> CodegenResult<Value> {
+        let value = self.emit_expr(receiver, None)?;
+
+        if let Type::Array(item) = value.ty.clone() {
+            return match (method, args) {
+                ("append", [arg]) => {
+                    let arg = self.emit_expr(arg, Some(&item))?;
+                    let arg = self.coerce(arg, &item)?;
+                    let c_type = self.c_type(&item)?;
+                    self.array_helpers = true;
+                    Ok(Value::new(
+                        INT,
+                        format!(
+                            "elp_array_append({}, sizeof({}), &({}){{ {} }})",
+                            value.repr, c_type, c_type, arg.repr
+                        ),
+                    ))
+                }
+                ("len" | "length", []) => Ok(Value::new(INT, format!("{}->len", value.repr))),
+                _ => self
+                    .ctx
+                    .error(format!("arrays don't have a `{}` method", method)),
+            };
+        }
+        if value.ty == Type::String && matches!(method, "len" | "length") && args.is_empty() {
+            self.includes.insert("string.h");
+            return Ok(Value::new(
+                INT,
+                format!("((int64_t)strlen({}))", value.repr),
+            ));
+        }
+
+        let Some(signature) = self.ctx.method(&value.ty, method) else {
+            return self.ctx.error(format!("no method `{}` found", method));
+        };
+        let is_method = self
+            .ctx
+            .module
+            .function(&signature.name)
+            .is_some_and(Function::is_method);
+
+        let mut values = vec![];
+        let params = if is_method {
+            values.push(value);
+            &signature.params[1..]
+        } else {
+            &signature.params[..]
+        };
+        if params.len() != args.len() {
+            return self.ctx.error(format!(
+                "`{}` takes {} arguments but {} were given",
+                signature.name,
+                params.len(),
+                args.len()
+            ));
+        }
+        for (param, arg) in params.iter().zip(args) {
+            values.push(self.emit_expr(arg, Some(param))?);
+        }
+        let args = self.emit_args(&signature.params, &values)?;
+        Ok(Value::new(
+            signature.ret,
+            format!("{}({})", ident(&signature.name), args.join(", ")),
+        ))
+    }
+
+    fn emit_print(&mut self, name: &str, args: &[Expr]) -> CodegenResult<Value> {
+        let [arg] = args else {
+            return self
+                .ctx
+                .error(format!("`{}` takes exactly one argument", name));
+        };
+        let value = self.emit_expr(arg, None)?;
+        let newline = if name == "println" { "\\n" } else { "" };
+        let (format, value) = match &value.ty {
+            Type::String => ("%s", value.repr),
+            Type::Bool => ("%s", format!("({} ? \"true\" : \"false\")", value.repr)),
+            Type::Int { signed: true, .. } => ("%lld", format!("(long long){}", value.repr)),
+            Type::Int { signed: false, .. } => {
+                ("%llu", format!("(unsigned long long){}", value.repr))
+            }
+            Type::Float { .. } => ("%f", format!("(double){}", value.repr)),
+            _ => {
+                return self
+                    .ctx
+                    .error("only strings, numbers and bools can be printed")
+            }
+        };
+        self.includes.insert("stdio.h");
+
+        Ok(Value::new(
+            Type::Void,
+            format!("printf(\"{}{}\", {})", format, newline, value),
+        ))
+    }
+
+    fn emit_object(&mut self, name: &str, fields: &[(String, Expr)]) -> CodegenResult<Value> {
+        let Some(object) = self.ctx.module.object(name) else {
+            return self.ctx.error(format!("unknown object `{}`", name));
+        };
+        for (field, _) in fields {
+            if object.field_index(field).is_none() {
+                return self
+                    .ctx
+                    .error(format!("`{}` has no field `{}`", name, field));
+            }
+        }
+
+        // Fields without a value or a default are left zeroed by the compound literal.
+        let mut values = vec![];
+        for field in &object.fields {
+            let value = match fields.iter().find(|(name, _)| *name == field.name) {
+                Some((_, value)) => Some(value),
+                None => field.default.as_ref(),
+            };
+            if let Some(value) = value {
+                let value = self.emit_expr(value, Some(&field.ty))?;
+                let value = self.coerce(value, &field.ty)?;
+                values.push(format!(".{} = {}", ident(&field.name), value.repr));
+            }
+        }
+        let c_name = ident(name);
+        let values = match values.is_empty() {
+            true => "0".into(),
+            false => values.join(", "),
+        };
+        self.alloc_helper = true;
+
+        Ok(Value::new(
+            Type::Named {
+                name: name.into(),
+                generics: vec![],
+            },
+            format!(
+                "(({} *)elp_alloc(&({}){{ {} }}, sizeof({})))",
+                c_name, c_name, values, c_name
+            ),
+        ))
+    }
+
+    fn emit_variant(
+        &mut self,
+        enum_name: Option<&str>,
+        variant: &str,
+        args: &[Expr],
+        expected: Option<&Type>,
+    ) -> CodegenResult<Value> {
+        let (r#enum, tag) = self.ctx.variant(enum_name, variant, expected)?;
+        let payload = &r#enum.variants[tag].payload;
+        if payload.len() != args.len() {
+            return self.ctx.error(format!(
+                "`{}.{}` carries {} values but {} were given",
+                r#enum.name,
+                variant,
+                payload.len(),
+                args.len()
+            ));
+        }
+
+        let name = ident(&r#enum.name);
+        let mut repr = format!("({}){{ .tag = {}_{}", name, name, ident(variant));
+        if !args.is_empty() {
+            let mut values = vec![];
+            for (ty, arg) in payload.iter().zip(args) {
+                let value = self.emit_expr(arg, Some(ty))?;
+                values.push(self.coerce(value, ty)?.repr);
+            }
+            let _ = write!(
+                repr,
+                ", .as.{} = {{ {} }}",
+                ident(variant),
+                values.join(", ")
+            );
+        }
+        repr.push_str(" }");
+
+        Ok(Value::new(
+            Type::Named {
+                name: r#enum.name.clone(),
+                generics: vec![],
+            },
+            repr,
+        ))
+    }
+
+    fn emit_binary(&mut self, op: BinaryOp, lhs: &Expr, rhs: &Expr) -> CodegenResult<Value> {
+        if matches!(op, BinaryOp::And | BinaryOp::Or) {
+            return self.emit_logical(op, lhs, rhs);
+        }
+
+        let ty = self.ctx.operand_type(lhs, rhs)?;
+        let lhs = self.emit_expr(lhs, Some(&ty))?;
+        let lhs = self.coerce(lhs, &ty)?;
+        let rhs = self.emit_expr(rhs, Some(&ty))?;
+        let rhs = self.coerce(rhs, &ty)?;
+        let c_type = self.c_type(&ty)?;
+
+        let comparison = match op {
+            BinaryOp::Eq => Some("=="),
+            BinaryOp::Ne => Some("!="),
+            BinaryOp::Lt => Some("<"),
+            BinaryOp::Le => Some("<="),
+            BinaryOp::Gt => Some(">"),
+            BinaryOp::Ge => Some(">="),
+            _ => None,
+        };
+
+        if let Some(operator) = comparison {
+            return match &ty {
+                Type::Int { .. } | Type::Float { .. } | Type::Bool => Ok(Value::new(
+                    Type::Bool,
+                    format!("({} {} {})", lhs.repr, operator, rhs.repr),
+                )),
+                Type::String => {
+                    self.includes.insert("string.h");
+                    Ok(Value::new(
+                        Type::Bool,
+                        format!("(strcmp({}, {}) {} 0)", lhs.repr, rhs.repr, operator),
+                    ))
+                }
+                ty if self.ctx.enum_of(ty).is_some()
+                    && matches!(op, BinaryOp::Eq | BinaryOp::Ne) =>
+                {
+                    Ok(Value::new(
+                        Type::Bool,
+                        format!("({}.tag {} {}.tag)", lhs.repr, operator, rhs.repr),
+                    ))
+                }
+                _ if c_type.ends_with('*') && matches!(op, BinaryOp::Eq | BinaryOp::Ne) => {
+                    Ok(Value::new(
+                        Type::Bool,
+                        format!("({} {} {})", lhs.repr, operator, rhs.repr),
+                    ))
+                }
+                _ => self
+                    .ctx
+                    .error(format!("can't compare values of type {}", c_type)),
+            };
+        }
+
+        let operator = match (&ty, op) {
+            (Type::Int { .. } | Type::Float { .. }, BinaryOp::Add) => "+",
+            (Type::Int { .. } | Type::Float { .. }, BinaryOp::Sub) => "-",
+            (Type::Int { .. } | Type::Float { .. }, BinaryOp::Mul) => "*",
+            (Type::Int { .. } | Type::Float { .. }, BinaryOp::Div) => "/",
+            (Type::Int { .. }, BinaryOp::Mod) => "%",
+            (Type::Int { .. } | Type::Bool, BinaryOp::BitAnd) => "&",
+            (Type::Int { .. } | Type::Bool, BinaryOp::BitOr) => "|",
+            (Type::Int { .. }, BinaryOp::Shl) => "<<",
+            (Type::Int { .. }, BinaryOp::Shr) => ">>",
+            (Type::Float { .. }, BinaryOp::Mod) => {
+                self.includes.insert("math.h");
+                return Ok(Value::new(
+                    ty.clone(),
+                    format!("(({})fmod({}, {}))", c_type, lhs.repr, rhs.repr),
+                ));
+            }
+            (Type::Int { .. } | Type::Float { .. }, BinaryOp::Pow) => {
+                self.includes.insert("math.h");
+                return Ok(Value::new(
+                    ty.clone(),
+                    format!(
+                        "(({})pow((double){}, (double){}))",
+                        c_type, lhs.repr, rhs.repr
+                    ),
+                ));
+            }
+            (Type::String, BinaryOp::Add) => {
+                self.string_helpers = true;
+                return Ok(Value::new(
+                    Type::String,
+                    format!("elp_string_concat({}, {})", lhs.repr, rhs.repr),
+                ));
+            }
+            _ => {
+                return self
+                    .ctx
+                    .error(format!("can't apply {:?} to values of type {}", op, c_type))
+            }
+        };
+
+        // C does arithmetic on anything narrower than an int as an int, cast back to keep the
+        // wrapping of the declared type.
+        let repr = match &ty {
+            Type::Int { bits: 8 | 16, .. } => {
+                format!("(({})({} {} {}))", c_type, lhs.repr, operator, rhs.repr)
+            }
+            _ => format!("({} {} {})", lhs.repr, operator, rhs.repr),
+        };
+        Ok(Value::new(ty, repr))
+    }
+
+    // `and`/`or` only evaluate their right hand side when they have to. `&&` and `||` already do
+    // that, unless the right hand side had statements hoisted out of it which then have to be
+    // guarded by an `if`.
+    fn emit_logical(&mut self, op: BinaryOp, lhs: &Expr, rhs: &Expr) -> CodegenResult<Value> {
+        let lhs = self.emit_condition(lhs)?;
+        let (code, rhs) = self.capture(|e| e.emit_condition(rhs))?;
+        let operator = if op == BinaryOp::And { "&&" } else { "||" };
+        if code.is_empty() {
+            return Ok(Value::new(
+                Type::Bool,
+                format!("({} {} {})", lhs.repr, operator, rhs.repr),
+            ));
+        }
+
+        let result = self.spill(lhs)?;
+        self.line(format!(
+            "if ({}{}) {{",
+            if op == BinaryOp::And { "" } else { "!" },
+            result.repr
+        ));
+        self.code.push_str(&code);
+        self.indent += 1;
+        self.line(format!("{} = {};", result.repr, rhs.repr));
+        self.indent -= 1;
+        self.line("}");
+        Ok(result)
+    }
+
+    fn emit_match(
+        &mut self,
+        scrutinee: &Expr,
+        arms: &[MatchArm],
+        expected: Option<&Type>,
+    ) -> CodegenResult<Value> {
+        let subject = self.emit_expr(scrutinee, None)?;
+        let ty = match expected {
+            Some(ty) if *ty != Type::Void => ty.clone(),
+            _ => self.ctx.type_of(&Expr::Match {
+                scrutinee: Box::new(scrutinee.clone()),
+                arms: arms.to_vec(),
+            })?,
+        };
+        let result = match ty {
+            Type::Void => None,
+            _ => {
+                let name = self.temp();
+                let c_type = self.c_type(&ty)?;
+                self.line(format!("{};", declaration(&c_type, &name)));
+                Some(name)
+            }
+        };
+        let subject = self.spill(subject)?;
+        self.includes.insert("stdlib.h");
+
+        if let Some(r#enum) = self.ctx.enum_of(&subject.ty) {
+            // Every arm is a variant or a catch all, dispatch on the tag in one go.
+            self.line(format!("switch ({}.tag) {{", subject.repr));
+            let mut seen = vec![];
+            let mut has_default = false;
+            for arm in arms {
+                match &arm.pattern {
+                    Pattern::Variant { name, .. } => {
+                        let (_, tag) = self.ctx.variant(None, name, Some(&subject.ty))?;
+                        if seen.contains(&tag) || has_default {
+                            continue;
+                        }
+                        seen.push(tag);
+                        self.line(format!("case {}_{}: {{", ident(&r#enum.name), ident(name)));
+                    }
+                    Pattern::Wildcard | Pattern::Binding(_) => {
+                        if has_default {
+                            continue;
+                        }
+                        has_default = true;
+                        self.line("default: {");
+                    }
+                    _ => {
+                        return self.ctx.error(format!(
+                            "`{}` can only be matched by its variants",
+                            r#enum.name
+                        ))
+                    }
+                }
+                self.indent += 1;
+                self.emit_arm(&subject, arm, result.as_deref(), &ty)?;
+                self.line("break;");
+                self.indent -= 1;
+                self.line("}");
+            }
+            if !has_default {
+                self.line("default:");
+                self.indent += 1;
+                self.line("abort();");
+                self.indent -= 1;
+            }
+            self.line("}");
+        } else {
+            let mut catch_all = false;
+            for (index, arm) in arms.iter().enumerate() {
+                let test = self.emit_pattern_test(&subject, &arm.pattern)?;
+                let keyword = if index == 0 { "if" } else { "} else if" };
+                match test {
+                    Some(test) => self.line(format!("{} ({}) {{", keyword, test)),
+                    None if index == 0 => self.line("{"),
+                    None => self.line("} else {"),
+                }
+                self.indent += 1;
+                self.emit_arm(&subject, arm, result.as_deref(), &ty)?;
+                self.indent -= 1;
+                if test_is_catch_all(&arm.pattern) {
+                    catch_all = true;
+                    break;
+                }
+            }
+            if !catch_all {
+                self.line(if arms.is_empty() { "{" } else { "} else {" });
+                self.indent += 1;
+                self.line("abort();");
+                self.indent -= 1;
+            }
+            self.line("}");
+        }
+
+        Ok(match result {
+            Some(result) => Value::new(ty, result),
+            None => Value::new(Type::Void, ""),
+        })
+    }
+
+    fn emit_arm(
+        &mut self,
+        subject: &Value,
+        arm: &MatchArm,
+        result: Option<&str>,
+        ty: &Type,
+    ) -> CodegenResult<()> {
+        self.scoped(|e| {
+            for (name, binding_ty) in e.ctx.pattern_bindings(&subject.ty, &arm.pattern)? {
+                let value = match &arm.pattern {
+                    Pattern::Variant {
+                        name: variant,
+                        bindings,
+                    } => {
+                        let index = bindings.iter().position(|b| *b == name).unwrap_or(0);
+                        format!("{}.as.{}._{}", subject.repr, ident(variant), index)
+                    }
+                    _ => subject.repr.clone(),
+                };
+                e.declare_local(&name, binding_ty, &value)?;
+            }
+            let value = e.emit_expr(&arm.body, result.map(|_| ty))?;
+            match result {
+                Some(result) => {
+                    let value = e.coerce(value, ty)?;
+                    e.line(format!("{} = {};", result, value.repr));
+                }
+                None if value.repr.is_empty() => {}
+                None => e.line(format!("{};", value.repr)),
+            }
+            Ok(())
+        })
+    }
+
+    // None means the pattern always matches.
+    fn emit_pattern_test(
+        &mut self,
+        subject: &Value,
+        pattern: &Pattern,
+    ) -> CodegenResult<Option<String>> {
+        let compare =
+            |e: &mut Self, op: BinaryOp, literal: &Literal| e.compare_value(op, subject, literal);
+
+        Ok(match pattern {
+            Pattern::Wildcard | Pattern::Binding(_) => None,
+            Pattern::Literal(literal) => Some(compare(self, BinaryOp::Eq, literal)?),
+            Pattern::Range { start, end } => {
+                let lower = match start {
+                    Some(start) => Some(compare(self, BinaryOp::Ge, start)?),
+                    None => None,
+                };
+                let upper = match end {
+                    Some(end) => Some(compare(self, BinaryOp::Le, end)?),
+                    None => None,
+                };
+                match (lower, upper) {
+                    (Some(lower), Some(upper)) => Some(format!("{} && {}", lower, upper)),
+                    (lower, upper) => lower.or(upper),
+                }
+            }
+            Pattern::Variant { .. } => {
+                return self.ctx.error("variant patterns can only match enums")
+            }
+        })
+    }
+
+    fn compare_value(
+        &mut self,
+        op: BinaryOp,
+        subject: &Value,
+        literal: &Literal,
+    ) -> CodegenResult<String> {
+        let rhs = self.literal(literal, Some(&subject.ty))?;
+        let rhs = self.coerce(rhs, &subject.ty)?;
+        let operator = match op {
+            BinaryOp::Eq => "==",
+            BinaryOp::Ge => ">=",
+            _ => "<=",
+        };
+        Ok(match &subject.ty {
+            Type::String => {
+                self.includes.insert("string.h");
+                format!("strcmp({}, {}) {} 0", subject.repr, rhs.repr, operator)
+            }
+            _ => format!("{} {} {}", subject.repr, operator, rhs.repr),
+        })
+    }
+}
+
+fn test_is_catch_all(pattern: &Pattern) -> bool {
+    matches!(
+        pattern,
+        Pattern::Wildcard
+            | Pattern::Binding(_)
+            | Pattern::Range {
+                start: None,
+                end: None
+            }
+    )
+}
+
+pub fn emit_module(module: &Module) -> CodegenResult<COutput> {
+    let module = monomorphize(module)?;
+    Emitter::new(&module).emit_module()
+}
+
+// A header for C code calling into the library the LLVM backend builds from `module`, the same
+// declarations the C backend writes alongside its source. None when nothing is exported.
+pub fn emit_header(module: &Module) -> CodegenResult<Option<String>> {
+    let module = monomorphize(module)?;
+    Emitter::new(&module).header(Symbols::Llvm)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::*;
+    use elp_ir::mir::{FieldDef, ObjectDef, Param, Span};
+    use pretty_assertions::assert_eq;
+    use std::process::Command;
+
+    #[test]
+    fn emits_functions_and_externals() {
+        let module = lower_source(
+            "fn puts(text string) -> int32
+
+            fn main {
+                puts(\"hello\")
+            }",
+        );
+
+        assert_eq!(
+            emit_module(&module).unwrap(),
+            COutput {
+                header: None,
+                source: r#"// Generated from test.elp.
+
+#include <stdbool.h>
+#include <stdint.h>
+
+int32_t puts(const char *text);
+
+int main(void) {
+    puts("hello");
+    return 0;
+}
+"#
+                .into()
+            }
+        );
+    }
+
+    #[test]
+    fn externals_are_called_the_way_their_abi_says() {
+        let module = lower_source(
+            "external \"stdcall\" {
+                fn MessageBeep(kind uint32) -> bool
+            }
+
+            fn main {
+                MessageBeep(0)
+            }",
+        );
+
+        let source = emit_module(&module).unwrap().source;
+        assert!(
+            source.contains("bool MessageBeep __attribute__((stdcall))(uint32_t kind);\n"),
+            "{}",
+            source
+        );
+    }
+
+    #[test]
+    fn headers_for_llvm_libraries() {
+        let module = lower_source(
+            "export object Point {
+                .x int32,
+                .y int32,
+            }
+
+            export fn Point.sum(self) -> int32 {
+                return self.x
+            }
+
+            export fn scale(point *Point, by float64) -> bool {
+                return true
+            }
+
+            fn hidden() -> int32 {
+                return 1
+            }",
+        );
+
+        assert_eq!(
+            emit_header(&module).unwrap().as_deref(),
+            Some(
+                r#"// Generated from test.elp.
+
+#ifndef ELP_TEST_H
+#define ELP_TEST_H
+
+#include <stdbool.h>
+#include <stdint.h>
+
+#define ELP_STRINGIFY(x) #x
+#define ELP_PREFIX(prefix) ELP_STRINGIFY(prefix)
+#define ELP_SYMBOL(name) __asm__(ELP_PREFIX(__USER_LABEL_PREFIX__) name)
+
+typedef struct Point Point;
+
+struct Point {
+    int32_t x;
+    int32_t y;
+};
+
+int32_t Point_sum(Point *self) ELP_SYMBOL("Point.sum");
+bool scale(void *point, double by);
+
+#endif
+"#
+            )
+        );
+        // The C backend's own header uses its own names.
+        assert!(emit_module(&module)
+            .unwrap()
+            .header
+            .unwrap()
+            .contains("int32_t Point_sum(Point *self);\n"));
+        assert_eq!(emit_header(&lower_source("fn hidden() {}")).unwrap(), None);
+    }
+
+    #[test]
+    fn objects_are_structs_behind_pointers() {
+        let point = Type::Named {
+            name: "Point".into(),
+            generics: vec![],
+        };
+        let module = Module {
+            objects: vec![ObjectDef {
+                span: Span::default(),
+                name: "Point".into(),
+                generics: vec![],
+                fields: vec![
+                    FieldDef {
+                        span: Span::default(),
+                        name: "x".into(),
+                        ty: int32(),
+                        default: None,
+                        public: true,
+                        tags: vec![],
+                    },
+                    FieldDef {
+                        span: Span::default(),
+                        name: "y".into(),
+                        ty: int32(),
+                        default: Some(int(2)),
+                        public: true,
+                        tags: vec![],
+                    },
+                ],
+                implements: vec![],
+                exported: false,
+            }],
+            functions: vec![
+                Function {
+                    receiver: Some("Point".into()),
+                    params: vec![Param {
+                        name: "self".into(),
+                        ty: point.clone(),
+                    }],
+                    ret: int32(),
+                    ..function(
+                        "sum",
+                        vec![ret(binary(
+                            BinaryOp::Add,
+                            field(local("self"), "x"),
+                            field(local("self"), "y"),
+                        ))],
+                    )
+                },
+                Function {
+                    ret: int32(),
+                    ..function(
+                        "main",
+                        vec![
+                            let_(
+                                "point",
+                                Expr::Object {
+                                    name: "Point".into(),
+                                    fields: vec![("x".into(), int(40))],
+                                },
+                            ),
+                            ret(Expr::MethodCall {
+                                receiver: Box::new(local("point")),
+                                method: "sum".into(),
+                                args: vec![],
+                            }),
+                        ],
+                    )
+                },
+            ],
+            ..Default::default()
+        };
+
+        let output = emit_module(&module).unwrap();
+        assert_eq!(output.header, None);
+        assert!(output.source.contains(
+            "typedef struct Point Point;\n\nstruct Point {\n    int32_t x;\n    int32_t y;\n};\n"
+        ));
+        assert!(output
+            .source
+            .contains("static int32_t Point_sum(Point *self);\n"));
+        assert!(output.source.contains(
+            "Point *point = ((Point *)elp_alloc(&(Point){ .x = 40, .y = 2 }, sizeof(Point)));"
+        ));
+        assert!(output.source.contains("return (int)Point_sum(point);"));
+        assert_runs(&output, 42, "");
+    }
+
+    #[test]
+    fn enums_are_tagged_unions_declared_in_the_header() {
+        let mut module = shapes();
+        module.functions.push(Function {
+            ret: int32(),
+            ..function(
+                "main",
+                vec![ret(Expr::Call {
+                    callee: Box::new(Expr::Global("area".into())),
+                    args: vec![Expr::Variant {
+                        enum_name: None,
+                        variant: "RECT".into(),
+                        args: vec![int(6), int(7)],
+                    }],
+                })],
+            )
+        });
+
+        let output = emit_module(&module).unwrap();
+        assert_eq!(
+            output.header.as_deref(),
+            Some(
+                "// Generated from test.elp.
+
+#ifndef ELP_TEST_H
+#define ELP_TEST_H
+
+#include <stdbool.h>
+#include <stdint.h>
+
+typedef enum { Shape_SQUARE, Shape_RECT, Shape_EMPTY } Shape_Tag;
+
+typedef struct Shape {
+    Shape_Tag tag;
+    union {
+        struct { int32_t _0; } SQUARE;
+        struct { int32_t _0; int32_t _1; } RECT;
+    } as;
+} Shape;
+
+int32_t area(Shape shape);
+
+#endif
+"
+            )
+        );
+        assert!(output.source.contains("#include \"test.h\"\n"));
+        assert!(output
+            .source
+            .contains("area((Shape){ .tag = Shape_RECT, .as.RECT = { 6, 7 } })"));
+        assert!(output.source.contains("    switch (tmp2.tag) {\n    case Shape_SQUARE: {\n        int32_t side = tmp2.as.SQUARE._0;\n"));
+        assert_runs(&output, 42, "");
+    }
+
+    #[test]
+    fn arrays_and_printing() {
+        let module = Module {
+            functions: vec![function(
+                "main",
+                vec![
+                    let_(
+                        "names",
+                        Expr::Array(vec![
+                            Expr::Literal(Literal::String("a".into())),
+                            Expr::Literal(Literal::String("b".into())),
+                        ]),
+                    ),
+                    stmt(StmtKind::Expr(Expr::MethodCall {
+                        receiver: Box::new(local("names")),
+                        method: "append".into(),
+                        args: vec![Expr::Literal(Literal::String("c".into()))],
+                    })),
+                    stmt(StmtKind::For {
+                        binding: "name".into(),
+                        iterable: local("names"),
+                        body: vec![stmt(StmtKind::Expr(Expr::Call {
+                            callee: Box::new(Expr::Global("println".into())),
+                            args: vec![binary(
+                                BinaryOp::Add,
+                                local("name"),
+                                Expr::Literal(Literal::String("!".into())),
+                            )],
+                        }))],
+                    }),
+                    stmt(StmtKind::Expr(Expr::Call {
+                        callee: Box::new(Expr::Global("println".into())),
+                        args: vec![Expr::MethodCall {
+                            receiver: Box::new(local("names")),
+                            method: "len".into(),
+                            args: vec![],
+                        }],
+                    })),
+                ],
+            )],
+            ..Default::default()
+        };
+
+        let output = emit_module(&module).unwrap();
+        assert!(output
+            .source
+            .contains("elp_array *names = elp_array_new(sizeof(const char *), 2, (const char *[]){ \"a\", \"b\" });"));
+        assert!(output
+            .source
+            .contains("const char *name = ((const char **)(tmp1)->data)[tmp2];"));
+        assert_runs(&output, 0, "a!\nb!\nc!\n3\n");
+    }
+
+    #[test]
+    fn generic_functions_are_monomorphized() {
+        let t = named("T");
+        let module = Module {
+            functions: vec![
+                Function {
+                    generics: vec!["T".into()],
+                    params: vec![
+                        Param {
+                            name: "a".into(),
+                            ty: t.clone(),
+                        },
+                        Param {
+                            name: "b".into(),
+                            ty: t.clone(),
+                        },
+                    ],
+                    ret: t,
+                    ..function(
+                        "max",
+                        vec![ret(Expr::Match {
+                            scrutinee: Box::new(binary(BinaryOp::Gt, local("a"), local("b"))),
+                            arms: vec![
+                                MatchArm {
+                                    pattern: Pattern::Literal(Literal::Bool(true)),
+                                    body: local("a"),
+                                },
+                                MatchArm {
+                                    pattern: Pattern::Wildcard,
+                                    body: local("b"),
+                                },
+                            ],
+                        })],
+                    )
+                },
+                function(
+                    "main",
+                    vec![
+                        stmt(StmtKind::Let {
+                            name: "small".into(),
+                            mutable: false,
+                            ty: int32(),
+                            value: Some(int(3)),
+                        }),
+                        stmt(StmtKind::Expr(Expr::Call {
+                            callee: Box::new(Expr::Global("println".into())),
+                            args: vec![Expr::Call {
+                                callee: Box::new(Expr::Global("max".into())),
+                                args: vec![local("small"), int(9)],
+                            }],
+                        })),
+                        stmt(StmtKind::Expr(Expr::Call {
+                            callee: Box::new(Expr::Global("println".into())),
+                            args: vec![Expr::Call {
+                                callee: Box::new(Expr::Global("max".into())),
+                                args: vec![
+                                    Expr::Literal(Literal::Float(2.5)),
+                                    Expr::Literal(Literal::Float(1.0)),
+                                ],
+                            }],
+                        })),
+                    ],
+                ),
+            ],
+            ..Default::default()
+        };
+
+        let output = emit_module(&module).unwrap();
+        assert!(output
+            .source
+            .contains("static int32_t max__int32(int32_t a, int32_t b);\n"));
+        assert!(output
+            .source
+            .contains("static double max__float64(double a, double b);\n"));
+        assert_runs(&output, 0, "9\n2.500000\n");
+    }
+
+    #[test]
+    fn hoisted_right_hand_sides_still_short_circuit() {
+        let module = Module {
+            functions: vec![
+                Function {
+                    ret: Type::Bool,
+                    ..function(
+                        "noisy",
+                        vec![
+                            stmt(StmtKind::Expr(Expr::Call {
+                                callee: Box::new(Expr::Global("println".into())),
+                                args: vec![Expr::Literal(Literal::String("evaluated".into()))],
+                            })),
+                            ret(Expr::Literal(Literal::Bool(true))),
+                        ],
+                    )
+                },
+                function(
+                    "main",
+                    vec![
+                        let_("flag", Expr::Literal(Literal::Bool(false))),
+                        stmt(StmtKind::Expr(Expr::Call {
+                            callee: Box::new(Expr::Global("println".into())),
+                            args: vec![binary(
+                                BinaryOp::And,
+                                local("flag"),
+                                Expr::Match {
+                                    scrutinee: Box::new(Expr::Call {
+                                        callee: Box::new(Expr::Global("noisy".into())),
+                                        args: vec![],
+                                    }),
+                                    arms: vec![MatchArm {
+                                        pattern: Pattern::Binding("value".into()),
+                                        body: local("value"),
+                                    }],
+                                },
+                            )],
+                        })),
+                    ],
+                ),
+            ],
+            ..Default::default()
+        };
+
+        let output = emit_module(&module).unwrap();
+        assert!(output
+            .source
+            .contains("    bool tmp3 = flag;\n    if (tmp3) {\n"));
+        assert_runs(&output, 0, "false\n");
+    }
+
+    // Compiles the output with the system C compiler and checks its exit code and output. Skipped
+    // when there's no `cc` so the tests still pass without one.
+    fn assert_runs(output: &COutput, code: i32, stdout: &str) {
+        let dir = std::env::temp_dir().join(format!(
+            "elp-c-{}-{}",
+            std::process::id(),
+            output.source.len()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("test.c"), &output.source).unwrap();
+        if let Some(header) = &output.header {
+            std::fs::write(dir.join("test.h"), header).unwrap();
+        }
+
+        let Ok(compiled) = Command::new("cc")
+            .args(["-std=c11", "-pedantic", "-Wall", "-Werror", "-o"])
+            .arg(dir.join("test"))
+            .arg(dir.join("test.c"))
+            .arg("-lm")
+            .output()
+        else {
+            let _ = std::fs::remove_dir_all(&dir);
+            return;
+        };
+        assert_eq!(
+            String::from_utf8_lossy(&compiled.stderr),
+            "",
+            "{}",
+            output.source
+        );
+
+        let ran = Command::new(dir.join("test")).output().unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+        assert_eq!(
+            (
+                ran.status.code(),
+                String::from_utf8_lossy(&ran.stdout).to_string()
+            ),
+            (Some(code), stdout.to_string())
+        );
+    }
+}
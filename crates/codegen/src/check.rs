@@ -0,0 +1,1008 @@
+// Type checks a module without generating any code. Backends stop at the first thing they can't
+// emit, this keeps going so `elp check` can report every problem in a module at once. Names that
+// come from imports the module can't see into (the standard library) are taken on trust and
+// anything derived from them isn't checked.
+
+use elp_ir::{
+    macros::Macros,
+    mir::{BinaryOp, Expr, Function, Literal, Module, Stmt, StmtKind, Type, UnaryOp},
+    reactivity::Reactivity,
+    tags::Tags,
+};
+
+use crate::typing::{is_builtin, CodegenError, CodegenResult, TypeContext, INT};
+
+struct Checker<'m> {
+    ctx: TypeContext<'m>,
+    // Whether each local is mutable, scoped the same way as the context's locals.
+    mutability: Vec<Vec<(String, bool)>>,
+    // The type parameters of the function being checked, anything typed as one of these is open.
+    generics: Vec<String>,
+    ret: Type,
+    // The component being checked, whose `return` is what it renders rather than a value.
+    rendering: Option<String>,
+    errors: Vec<CodegenError>,
+}
+
+impl<'m> Checker<'m> {
+    fn new(module: &'m Module) -> Self {
+        Self {
+            ctx: TypeContext::new(module),
+            mutability: vec![],
+            generics: vec![],
+            ret: Type::Void,
+            rendering: None,
+            errors: vec![],
+        }
+    }
+
+    fn error(&mut self, message: impl Into<String>) {
+        self.errors.push(CodegenError {
+            span: self.ctx.span,
+            message: message.into(),
+        });
+    }
+
+    fn report<T>(&mut self, result: CodegenResult<T>) -> Option<T> {
+        result.map_err(|err| self.errors.push(err)).ok()
+    }
+
+    fn push_scope(&mut self) {
+        self.ctx.push_scope();
+        self.mutability.push(vec![]);
+    }
+
+    fn pop_scope(&mut self) {
+        self.ctx.pop_scope();
+        self.mutability.pop();
+    }
+
+    fn is_imported(&self, name: &str) -> bool {
+        self.ctx.module.imports.iter().any(|import| {
+            import
+                .names
+                .iter()
+                .any(|(imported, alias)| alias.as_deref().unwrap_or(imported) == name)
+        })
+    }
+
+    fn declare(&mut self, name: &str, ty: Type, mutable: bool) {
+        self.ctx.declare(name, ty);
+        if let Some(scope) = self.mutability.last_mut() {
+            scope.push((name.into(), mutable));
+        }
+    }
+
+    fn is_constant(&self, name: &str) -> bool {
+        self.mutability
+            .iter()
+            .rev()
+            .flat_map(|scope| scope.iter().rev())
+            .find(|(local, _)| local == name)
+            .is_some_and(|(_, mutable)| !mutable)
+    }
+
+    // Unknown types and type parameters could be anything, nothing is checked against them.
+    fn is_open(&self, ty: &Type) -> bool {
+        match ty {
+            Type::Unknown => true,
+            Type::Named { name, generics } => generics.is_empty() && self.generics.contains(name),
+            _ => false,
+        }
+    }
+
+    fn assignable(&self, expected: &Type, actual: &Type, value: Option<&Expr>) -> bool {
+        if self.is_open(expected) || self.is_open(actual) || expected == actual {
+            return true;
+        }
+
+        match (expected, actual) {
+            // Untyped literals take on whichever numeric type they're used as.
+            (Type::Int { .. } | Type::Float { .. }, _) if is_int_literal(value) => true,
+            (Type::Float { .. }, _) => matches!(value, Some(Expr::Literal(Literal::Float(_)))),
+            (Type::Pointer(_) | Type::Reference(_), _)
+                if matches!(value, Some(Expr::Literal(Literal::Nil))) =>
+            {
+                true
+            }
+            (Type::Named { name, .. }, _)
+                if name == "Optional" && matches!(value, Some(Expr::Literal(Literal::Nil))) =>
+            {
+                true
+            }
+            (Type::Reference(expected), actual) => self.assignable(expected, actual, value),
+            (expected, Type::Reference(actual)) => self.assignable(expected, actual, value),
+            (Type::Array(expected), Type::Array(actual)) => self.assignable(expected, actual, None),
+            (
+                Type::Named {
+                    name: expected,
+                    generics: expected_generics,
+                },
+                Type::Named {
+                    name: actual,
+                    generics: actual_generics,
+                },
+            ) => {
+                expected == actual
+                    && (expected_generics.is_empty()
+                        || actual_generics.is_empty()
+                        || expected_generics
+                            .iter()
+                            .zip(actual_generics)
+                            .all(|(expected, actual)| self.assignable(expected, actual, None)))
+            }
+            _ => false,
+        }
+    }
+
+    fn expect(&mut self, expected: &Type, actual: &Type, value: &Expr) {
+        if !self.assignable(expected, actual, Some(value)) {
+            self.error(format!("expected `{}`, found `{}`", expected, actual));
+        }
+    }
+
+    fn check_function(&mut self, function: &Function) {
+        self.ctx.span = function.span;
+        if let Some(receiver) = &function.receiver {
+            if self.ctx.module.object(receiver).is_none()
+                && self.ctx.module.r#enum(receiver).is_none()
+                && !self.is_imported(receiver)
+            {
+                self.error(format!("`{}` isn't defined", receiver));
+            }
+        }
+
+        self.generics = function.generics.clone();
+        self.ret = function.ret.clone();
+        self.push_scope();
+        for param in &function.params {
+            self.declare(&param.name, param.ty.clone(), true);
+        }
+        self.check_block(&function.body);
+        self.pop_scope();
+        self.generics.clear();
+    }
+
+    fn check_block(&mut self, body: &[Stmt]) {
+        for stmt in body {
+            self.check_stmt(stmt);
+        }
+    }
+
+    fn check_scoped_block(&mut self, body: &[Stmt]) {
+        self.push_scope();
+        self.check_block(body);
+        self.pop_scope();
+    }
+
+    fn check_stmt(&mut self, stmt: &Stmt) {
+        self.ctx.span = stmt.span;
+
+        match &stmt.kind {
+            StmtKind::Let {
+                name,
+                mutable,
+                ty,
+                value,
+            } => {
+                let expected = (*ty != Type::Unknown).then_some(ty);
+                let actual = value
+                    .as_ref()
+                    .map(|value| (self.check_expr(value, expected), value));
+                let ty = match (expected, actual) {
+                    (Some(expected), Some((actual, value))) => {
+                        self.expect(expected, &actual, value);
+                        expected.clone()
+                    }
+                    (Some(expected), None) => expected.clone(),
+                    (None, Some((actual, _))) => actual,
+                    (None, None) => {
+                        self.error(format!("`{}` needs a type or a value", name));
+                        Type::Unknown
+                    }
+                };
+                self.declare(name, ty, *mutable);
+            }
+            StmtKind::Assign { target, op, value } => {
+                match target {
+                    Expr::Local(name) if self.is_constant(name) => {
+                        self.error(format!("`{}` is a constant and can't be assigned to", name))
+                    }
+                    Expr::Local(_) | Expr::Field { .. } => {}
+                    _ => self.error("only variables and fields can be assigned to"),
+                }
+                let target_ty = self.check_expr(target, None);
+                let value_ty = self.check_expr(value, Some(&target_ty));
+                match op {
+                    Some(op) => {
+                        self.check_binary(*op, (target, target_ty), (value, value_ty));
+                    }
+                    None => self.expect(&target_ty, &value_ty, value),
+                }
+            }
+            StmtKind::Expr(expr) => {
+                self.check_expr(expr, None);
+            }
+            StmtKind::Return(Some(value)) if self.rendering.is_some() => {
+                let actual = self.check_expr(value, None);
+                // An element that isn't a component has already been reported.
+                if !matches!(value, Expr::Element(_)) && !self.renders(&actual) {
+                    let component = self.rendering.clone().unwrap_or_default();
+                    self.error(format!(
+                        "`{}` has to return something that implements `Component`, found `{}`",
+                        component, actual
+                    ));
+                }
+            }
+            StmtKind::Return(value) => {
+                let ret = self.ret.clone();
+                match value {
+                    Some(value) => {
+                        let actual = self.check_expr(value, Some(&ret));
+                        if ret == Type::Void {
+                            self.error("this function doesn't return a value");
+                        } else {
+                            self.expect(&ret, &actual, value);
+                        }
+                    }
+                    None if ret != Type::Void && ret != Type::Unknown => {
+                        self.error(format!("expected a `{}` to be returned", ret))
+                    }
+                    None => {}
+                }
+            }
+            StmtKind::If {
+                condition,
+                then_body,
+                else_body,
+            } => {
+                self.check_condition(condition);
+                self.check_scoped_block(then_body);
+                self.check_scoped_block(else_body);
+            }
+            StmtKind::For {
+                binding,
+                iterable,
+                body,
+            } => {
+                let item = match self.check_expr(iterable, None) {
+                    Type::Array(item) => *item,
+                    Type::Unknown => Type::Unknown,
+                    other => {
+                        self.error(format!("can't loop over a `{}`", other));
+                        Type::Unknown
+                    }
+                };
+                self.push_scope();
+                self.declare(binding, item, false);
+                self.check_block(body);
+                self.pop_scope();
+            }
+        }
+    }
+
+    // Elements, other components and anything declaring `implements Component` can be rendered.
+    // Nothing is rendered for nil.
+    fn renders(&self, ty: &Type) -> bool {
+        let module = self.ctx.module;
+        let implements =
+            |implements: &[Type]| implements.iter().any(|ty| ty.named() == Some("Component"));
+        match ty {
+            Type::Named { name, .. } => {
+                name == "Component"
+                    || module.component(name).is_some()
+                    || self.is_imported(name)
+                    || module
+                        .object(name)
+                        .is_some_and(|object| implements(&object.implements))
+                    || module
+                        .r#enum(name)
+                        .is_some_and(|def| implements(&def.implements))
+            }
+            Type::Pointer(inner) => **inner == Type::Void || self.renders(inner),
+            ty => self.is_open(ty),
+        }
+    }
+
+    fn check_condition(&mut self, condition: &Expr) {
+        let ty = self.check_expr(condition, Some(&Type::Bool));
+        if !self.is_open(&ty) && ty != Type::Bool {
+            self.error(format!("conditions have to be `bool`, found `{}`", ty));
+        }
+    }
+
+    fn check_args(&mut self, name: &str, params: &[Type], args: &[Expr]) {
+        if params.len() != args.len() {
+            self.error(format!(
+                "`{}` takes {} arguments but {} were given",
+                name,
+                params.len(),
+                args.len()
+            ));
+        }
+        for (param, arg) in params.iter().zip(args) {
+            let actual = self.check_expr(arg, Some(param));
+            self.expect(param, &actual, arg);
+        }
+        for arg in args.iter().skip(params.len()) {
+            self.check_expr(arg, None);
+        }
+    }
+
+    fn check_exprs(&mut self, exprs: &[Expr]) {
+        for expr in exprs {
+            self.check_expr(expr, None);
+        }
+    }
+
+    // The type of an expression, Unknown when it can't be worked out. Whatever stopped it being
+    // worked out has already been reported.
+    fn check_expr(&mut self, expr: &Expr, expected: Option<&Type>) -> Type {
+        match expr {
+            Expr::Literal(_) => self.ctx.type_of(expr).unwrap_or(Type::Unknown),
+            Expr::Local(name) => match self.ctx.local(name) {
+                Some(ty) => ty.clone(),
+                None => {
+                    self.error(format!("`{}` isn't defined", name));
+                    Type::Unknown
+                }
+            },
+            Expr::Global(name) => self.check_global(name),
+            Expr::Field { base, name } => match self.check_expr(base, None) {
+                Type::Unknown => Type::Unknown,
+                base => match self.report(self.ctx.field(&base, name)) {
+                    Some((object, index)) => object.fields[index].ty.clone(),
+                    None => Type::Unknown,
+                },
+            },
+            Expr::Call { callee, args } => self.check_call(expr, callee, args),
+            Expr::MethodCall {
+                receiver,
+                method,
+                args,
+            } => self.check_method_call(receiver, method, args),
+            Expr::Binary { op, lhs, rhs } => {
+                let lhs_ty = self.check_expr(lhs, None);
+                let rhs_ty = self.check_expr(rhs, Some(&lhs_ty));
+                self.check_binary(*op, (lhs, lhs_ty), (rhs, rhs_ty))
+            }
+            Expr::Unary { op, operand } => {
+                let ty = self.check_expr(operand, expected);
+                match op {
+                    UnaryOp::Not if !self.is_open(&ty) && ty != Type::Bool => {
+                        self.error(format!("`!` needs a `bool`, found `{}`", ty));
+                        Type::Bool
+                    }
+                    UnaryOp::Not => Type::Bool,
+                    UnaryOp::Neg if !self.is_open(&ty) && !is_numeric(&ty) => {
+                        self.error(format!("`-` needs a number, found `{}`", ty));
+                        Type::Unknown
+                    }
+                    UnaryOp::Neg => ty,
+                }
+            }
+            Expr::Object { name, fields } => self.check_object(name, fields),
+            Expr::Variant {
+                enum_name,
+                variant,
+                args,
+            } => {
+                let Some((r#enum, tag)) =
+                    self.report(self.ctx.variant(enum_name.as_deref(), variant, expected))
+                else {
+                    self.check_exprs(args);
+                    return Type::Unknown;
+                };
+                let payload = r#enum.variants[tag].payload.clone();
+                self.check_args(&format!("{}.{}", r#enum.name, variant), &payload, args);
+                Type::Named {
+                    name: r#enum.name.clone(),
+                    generics: vec![],
+                }
+            }
+            Expr::Array(items) => {
+                let expected_item = match expected {
+                    Some(Type::Array(item)) => Some(&**item),
+                    _ => None,
+                };
+                let mut item_ty: Option<Type> = expected_item.cloned();
+                for item in items {
+                    let ty = self.check_expr(item, item_ty.as_ref());
+                    match &item_ty {
+                        Some(expected) => self.expect(&expected.clone(), &ty, item),
+                        None => item_ty = Some(ty),
+                    }
+                }
+                Type::Array(Box::new(item_ty.unwrap_or(Type::Unknown)))
+            }
+            Expr::Closure(closure) => {
+                let ret = std::mem::replace(&mut self.ret, closure.ret.clone());
+                self.push_scope();
+                for param in &closure.params {
+                    self.declare(&param.name, param.ty.clone(), true);
+                }
+                self.check_block(&closure.body);
+                self.pop_scope();
+                self.ret = ret;
+                Type::Function {
+                    params: closure.params.iter().map(|p| p.ty.clone()).collect(),
+                    ret: Box::new(closure.ret.clone()),
+                }
+            }
+            Expr::Match { scrutinee, arms } => {
+                let scrutinee = self.check_expr(scrutinee, None);
+                let mut result = None;
+                for arm in arms {
+                    self.push_scope();
+                    if !self.is_open(&scrutinee) {
+                        let bindings = self.ctx.pattern_bindings(&scrutinee, &arm.pattern);
+                        for (name, ty) in self.report(bindings).unwrap_or_default() {
+                            self.declare(&name, ty, false);
+                        }
+                    }
+                    let ty = self.check_expr(&arm.body, expected);
+                    self.pop_scope();
+                    result.get_or_insert(ty);
+                }
+                result.unwrap_or(Type::Void)
+            }
+            Expr::Element(element) => {
+                if self.ctx.module.component(&element.name).is_none()
+                    && !self.is_imported(&element.name)
+                {
+                    self.error(format!("`{}` isn't a component", element.name));
+                }
+                self.check_exprs(&element.args);
+                for (_, prop) in &element.props {
+                    self.check_expr(prop, None);
+                }
+                self.check_scoped_block(&element.children);
+                Type::Named {
+                    name: element.name.clone(),
+                    generics: vec![],
+                }
+            }
+        }
+    }
+
+    fn check_global(&mut self, name: &str) -> Type {
+        let module = self.ctx.module;
+        if self.ctx.signature(name).is_some() || module.globals.iter().any(|g| g.name == name) {
+            return self
+                .ctx
+                .type_of(&Expr::Global(name.into()))
+                .unwrap_or_else(|err| {
+                    self.errors.push(err);
+                    Type::Unknown
+                });
+        }
+        if !self.is_imported(name) && !is_builtin(name) {
+            self.error(format!("`{}` isn't defined", name));
+        }
+        Type::Unknown
+    }
+
+    fn check_call(&mut self, call: &Expr, callee: &Expr, args: &[Expr]) -> Type {
+        if let Expr::Global(name) = callee {
+            if is_builtin(name) && self.ctx.signature(name).is_none() {
+                self.check_exprs(args);
+                return Type::Void;
+            }
+            if let Some(signature) = self.ctx.signature(name) {
+                if self.ctx.is_generic(name) {
+                    self.check_exprs(args);
+                    return self.report(self.ctx.type_of(call)).unwrap_or(Type::Unknown);
+                }
+                self.check_args(name, &signature.params, args);
+                return signature.ret;
+            }
+        }
+
+        match self.check_expr(callee, None) {
+            Type::Function { params, ret } => {
+                self.check_args("this function", &params, args);
+                *ret
+            }
+            Type::Unknown => {
+                self.check_exprs(args);
+                Type::Unknown
+            }
+            other => {
+                self.error(format!("a `{}` can't be called", other));
+                self.check_exprs(args);
+                Type::Unknown
+            }
+        }
+    }
+
+    fn check_method_call(&mut self, receiver: &Expr, method: &str, args: &[Expr]) -> Type {
+        let receiver = self.check_expr(receiver, None);
+        match (&receiver, method) {
+            (Type::Unknown, _) => {
+                self.check_exprs(args);
+                Type::Unknown
+            }
+            (Type::Array(item), "append") => {
+                let item = (**item).clone();
+                self.check_args("append", &[item], args);
+                INT
+            }
+            (Type::Array(_) | Type::String, "len" | "length") => {
+                self.check_args(method, &[], args);
+                INT
+            }
+            (Type::Pointer(_), "notNil") => {
+                self.check_args(method, &[], args);
+                Type::Bool
+            }
+            (Type::Named { name, .. }, "notNil") if name == "Optional" => {
+                self.check_args(method, &[], args);
+                Type::Bool
+            }
+            // Methods of imported types are checked by the module they come from.
+            (Type::Named { name, .. }, _)
+                if self.is_imported(name)
+                    && self.ctx.module.object(name).is_none()
+                    && self.ctx.module.r#enum(name).is_none() =>
+            {
+                self.check_exprs(args);
+                Type::Unknown
+            }
+            _ => match self.ctx.method(&receiver, method) {
+                Some(signature) => {
+                    let is_method = self
+                        .ctx
+                        .module
+                        .function(&signature.name)
+                        .is_some_and(Function::is_method);
+                    let params = match is_method {
+                        true => &signature.params[1..],
+                        false => &signature.params[..],
+                    };
+                    self.check_args(&signature.name, params, args);
+                    signature.ret
+                }
+                None => {
+                    self.error(format!("`{}` has no method `{}`", receiver, method));
+                    self.check_exprs(args);
+                    Type::Unknown
+                }
+            },
+        }
+    }
+
+    fn check_binary(&mut self, op: BinaryOp, lhs: (&Expr, Type), rhs: (&Expr, Type)) -> Type {
+        let ((lhs, lhs_ty), (rhs, rhs_ty)) = (lhs, rhs);
+        let open = self.is_open(&lhs_ty) || self.is_open(&rhs_ty);
+        let result = match op {
+            BinaryOp::Eq
+            | BinaryOp::Ne
+            | BinaryOp::Lt
+            | BinaryOp::Le
+            | BinaryOp::Gt
+            | BinaryOp::Ge
+            | BinaryOp::And
+            | BinaryOp::Or => Type::Bool,
+            _ if open => Type::Unknown,
+            _ => self
+                .report(self.ctx.operand_type(lhs, rhs))
+                .unwrap_or(Type::Unknown),
+        };
+        if open {
+            return result;
+        }
+
+        let operands_match = self.assignable(&lhs_ty, &rhs_ty, Some(rhs))
+            || self.assignable(&rhs_ty, &lhs_ty, Some(lhs));
+        let allowed = match op {
+            BinaryOp::And | BinaryOp::Or => lhs_ty == Type::Bool && rhs_ty == Type::Bool,
+            BinaryOp::Eq | BinaryOp::Ne => operands_match,
+            BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge => {
+                operands_match && (is_numeric(&lhs_ty) || lhs_ty == Type::String)
+            }
+            BinaryOp::Add => {
+                operands_match
+                    && (is_numeric(&lhs_ty) || (lhs_ty == Type::String && rhs_ty == Type::String))
+            }
+            BinaryOp::BitAnd | BinaryOp::BitOr | BinaryOp::Shl | BinaryOp::Shr => {
+                matches!(lhs_ty, Type::Int { .. }) && matches!(rhs_ty, Type::Int { .. })
+            }
+            BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div | BinaryOp::Mod | BinaryOp::Pow => {
+                operands_match && is_numeric(&lhs_ty)
+            }
+        };
+        if !allowed {
+            self.error(format!(
+                "`{}` can't be used with `{}` and `{}`",
+                symbol(op),
+                lhs_ty,
+                rhs_ty
+            ));
+        }
+
+        result
+    }
+
+    fn check_object(&mut self, name: &str, fields: &[(String, Expr)]) -> Type {
+        let Some(object) = self.ctx.module.object(name) else {
+            if !self.is_imported(name) {
+                self.error(format!("`{}` isn't an object", name));
+            }
+            for (_, value) in fields {
+                self.check_expr(value, None);
+            }
+            return Type::Unknown;
+        };
+
+        for (field, value) in fields {
+            match object.field_index(field) {
+                Some(index) => {
+                    let expected = object.fields[index].ty.clone();
+                    let actual = self.check_expr(value, Some(&expected));
+                    // Generic objects are checked once they're instantiated by a backend.
+                    if object.generics.is_empty() {
+                        self.expect(&expected, &actual, value);
+                    }
+                }
+                None => {
+                    self.error(format!("`{}` has no field `{}`", name, field));
+                    self.check_expr(value, None);
+                }
+            }
+        }
+
+        Type::Named {
+            name: name.into(),
+            generics: vec![],
+        }
+    }
+}
+
+fn is_int_literal(value: Option<&Expr>) -> bool {
+    match value {
+        Some(Expr::Literal(Literal::Int(_))) => true,
+        Some(Expr::Unary {
+            op: UnaryOp::Neg,
+            operand,
+        }) => is_int_literal(Some(operand)),
+        _ => false,
+    }
+}
+
+fn is_numeric(ty: &Type) -> bool {
+    matches!(ty, Type::Int { .. } | Type::Float { .. })
+}
+
+fn symbol(op: BinaryOp) -> &'static str {
+    match op {
+        BinaryOp::Add => "+",
+        BinaryOp::Sub => "-",
+        BinaryOp::Mul => "*",
+        BinaryOp::Div => "/",
+        BinaryOp::Mod => "%",
+        BinaryOp::Pow => "**",
+        BinaryOp::Eq => "==",
+        BinaryOp::Ne => "!=",
+        BinaryOp::Lt => "<",
+        BinaryOp::Le => "<=",
+        BinaryOp::Gt => ">",
+        BinaryOp::Ge => ">=",
+        BinaryOp::And => "&&",
+        BinaryOp::Or => "||",
+        BinaryOp::BitAnd => "&",
+        BinaryOp::BitOr => "|",
+        BinaryOp::Shl => "<<",
+        BinaryOp::Shr => ">>",
+    }
+}
+
+// Every type error in the module, in the order they appear.
+pub fn check_module(module: &Module) -> Vec<CodegenError> {
+    check_module_in(module, module)
+}
+
+// Checks the items of `module` but looks names up in `context`, which is the module plus whatever
+// it imports from the rest of its package.
+pub fn check_module_in(module: &Module, context: &Module) -> Vec<CodegenError> {
+    let mut checker = Checker::new(context);
+
+    for object in &module.objects {
+        checker.ctx.span = object.span;
+        checker.generics = object.generics.clone();
+        for field in &object.fields {
+            if let Some(default) = &field.default {
+                let actual = checker.check_expr(default, Some(&field.ty));
+                checker.expect(&field.ty, &actual, default);
+            }
+        }
+        checker.generics.clear();
+    }
+
+    for global in &module.globals {
+        checker.ctx.span = global.span;
+        let actual = checker.check_expr(&global.value, Some(&global.ty));
+        if global.ty != Type::Unknown {
+            checker.expect(&global.ty, &actual, &global.value);
+        }
+    }
+
+    for function in &module.functions {
+        checker.check_function(function);
+    }
+
+    for component in &module.components {
+        checker.ctx.span = component.span;
+        checker.ret = Type::Void;
+        checker.rendering = Some(component.name.clone());
+        checker.push_scope();
+        for param in &component.params {
+            checker.declare(&param.name, param.ty.clone(), true);
+        }
+        for prop in &component.props {
+            if let Some(default) = &prop.default {
+                checker.ctx.span = prop.span;
+                let actual = checker.check_expr(default, Some(&prop.ty));
+                checker.expect(&prop.ty, &actual, default);
+            }
+            checker.declare(&prop.name, prop.ty.clone(), true);
+        }
+        checker.check_block(&component.body);
+        checker.pop_scope();
+        checker.rendering = None;
+    }
+
+    checker.errors.extend(
+        module
+            .styles
+            .check()
+            .into_iter()
+            .map(|diagnostic| CodegenError {
+                span: diagnostic.span,
+                message: diagnostic.message,
+            }),
+    );
+
+    checker.errors
+}
+
+// Things that aren't wrong but are probably not what was meant, they don't stop a build. The tags
+// read are the compiler's own and those of the macros every module can use.
+pub fn warnings(module: &Module) -> Vec<CodegenError> {
+    warnings_with(module, &Tags::builtin().with_macros(&Macros::builtin()))
+}
+
+// Warnings with `tags` as the tag keys something reads.
+pub fn warnings_with(module: &Module, tags: &Tags) -> Vec<CodegenError> {
+    let reactivity = module
+        .components
+        .iter()
+        .flat_map(|component| Reactivity::of(component).warnings())
+        .map(|warning| CodegenError {
+            span: warning.span,
+            message: warning.message,
+        });
+    let tags = tags.check(module).into_iter().map(|warning| CodegenError {
+        span: warning.span,
+        message: warning.message,
+    });
+    reactivity.chain(tags).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{
+        binary, field, function, int, int32, let_, local, lower_source, named, ret, shapes, stmt,
+    };
+    use elp_ir::mir::{FieldDef, ObjectDef, Span};
+    use pretty_assertions::assert_eq;
+
+    fn messages(module: &Module) -> Vec<String> {
+        check_module(module)
+            .into_iter()
+            .map(|err| err.message)
+            .collect()
+    }
+
+    #[test]
+    fn well_typed_source_has_no_errors() {
+        let module = lower_source(
+            "import { println } from \"elp/stdio\"
+
+            fn greet(name string) -> string {
+                const greeting = name
+                var greeting = greeting
+                greeting = \"hello\"
+                return greeting
+            }
+
+            fn main {
+                println(greet(\"world\"))
+            }",
+        );
+
+        assert_eq!(messages(&module), Vec::<String>::new());
+        assert_eq!(messages(&shapes()), Vec::<String>::new());
+    }
+
+    #[test]
+    fn derived_json_conversions_check() {
+        let module = lower_source(
+            "object Author implements FromTo<JSON> {
+                .name string `json: \"name\"`,
+            }
+
+            object Post implements FromTo<JSON> {
+                .title string `json: \"headline\"`,
+                .author Author,
+                .tags Array<string>,
+                .subtitle Optional<string>,
+                .calls = 0,
+            }",
+        );
+
+        assert_eq!(module.functions.len(), 4);
+        assert_eq!(messages(&module), Vec::<String>::new());
+    }
+
+    #[test]
+    fn reports_every_error_not_just_the_first() {
+        let module = lower_source(
+            "fn greet(name string) -> string {
+                return name
+            }
+
+            fn main {
+                const greeting = greet(\"hello\", \"world\")
+                greeting = \"bye\"
+                missing(1)
+            }",
+        );
+
+        let errors = check_module(&module);
+        assert_eq!(
+            errors
+                .iter()
+                .map(|err| (err.span.line, err.message.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                (6, "`greet` takes 1 arguments but 2 were given"),
+                (7, "`greeting` is a constant and can't be assigned to"),
+                (8, "`missing` isn't defined"),
+            ]
+        );
+    }
+
+    #[test]
+    fn imported_names_are_taken_on_trust() {
+        let module = lower_source(
+            "import { read_file } from \"elp/fs\"
+
+            fn main {
+                const text = read_file(\"Package.elp\")
+                text.lines()
+            }",
+        );
+
+        assert_eq!(messages(&module), Vec::<String>::new());
+    }
+
+    #[test]
+    fn mismatched_types() {
+        let mut module = Module {
+            name: "test".into(),
+            objects: vec![ObjectDef {
+                span: Span::default(),
+                name: "Point".into(),
+                generics: vec![],
+                fields: vec![FieldDef {
+                    span: Span::default(),
+                    name: "x".into(),
+                    ty: int32(),
+                    default: None,
+                    public: true,
+                    tags: vec![],
+                }],
+                implements: vec![],
+                exported: false,
+            }],
+            ..Default::default()
+        };
+        let mut main = function(
+            "main",
+            vec![
+                let_(
+                    "point",
+                    Expr::Object {
+                        name: "Point".into(),
+                        fields: vec![("x".into(), Expr::Literal(Literal::Bool(true)))],
+                    },
+                ),
+                let_(
+                    "sum",
+                    binary(BinaryOp::Add, field(local("point"), "x"), int(1)),
+                ),
+                let_(
+                    "bad",
+                    binary(
+                        BinaryOp::Add,
+                        local("sum"),
+                        Expr::Literal(Literal::String("one".into())),
+                    ),
+                ),
+                stmt(StmtKind::If {
+                    condition: local("sum"),
+                    then_body: vec![ret(local("sum"))],
+                    else_body: vec![],
+                }),
+                ret(field(local("point"), "y")),
+            ],
+        );
+        main.ret = named("Point");
+        module.functions.push(main);
+
+        assert_eq!(
+            messages(&module),
+            vec![
+                "expected `int32`, found `bool`",
+                "`+` can't be used with `int32` and `string`",
+                "conditions have to be `bool`, found `int32`",
+                "expected `Point`, found `int32`",
+                "`Point` has no field `y`",
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_style_properties_that_dont_fit() {
+        let module = lower_source(
+            "Stylesheet {
+                Text, .note {
+                    .padding = 16,
+                    .fontWeight = .SEMIBOLD,
+                    .colour = #333,
+                    .color = \"red\",
+                }
+            }",
+        );
+
+        assert_eq!(
+            messages(&module),
+            vec![
+                "there's no style property `colour`",
+                "`color` takes a colour, not a string",
+            ]
+        );
+    }
+
+    #[test]
+    fn components_have_to_render_something() {
+        let module = lower_source(
+            "import { Text } from \"elp/ui\"
+
+            object Badge implements Component {}
+
+            component Label() {
+                export var text string = 1
+                return text
+            }
+
+            component Tag(badge Badge) {
+                return badge
+            }
+
+            component Title() {
+                export var text Optional<string>
+                return Text(text)
+            }",
+        );
+
+        assert_eq!(
+            messages(&module),
+            vec![
+                "expected `string`, found `int64`",
+                "`Label` has to return something that implements `Component`, found `string`",
+            ]
+        );
+    }
+}
@@ -0,0 +1,9 @@
+pub mod c;
+pub mod check;
+pub mod llvm;
+pub mod mono;
+#[cfg(test)]
+mod test_support;
+pub mod typing;
+pub mod wasm;
+pub mod web;
@@ -0,0 +1,1819 @@
+// Lowers the MIR to textual LLVM IR (`.ll`) that `llc`/`clang` can assemble and link into a native
+// binary. Objects are heap allocated structs passed around by pointer, enums are tagged unions
+// passed by value and arrays are pointers to a small runtime header. Every local lives in a stack
+// slot, LLVM's mem2reg turns them into registers.
+
+use std::{collections::BTreeMap, fmt::Write};
+
+use elp_ir::{
+    ffi::CallingConvention,
+    mir::{
+        BinaryOp, EnumDef, Expr, Function, Literal, MatchArm, Module, Pattern, Stmt, StmtKind,
+        Type, UnaryOp,
+    },
+};
+
+use crate::{
+    mono::monomorphize,
+    typing::{is_builtin, CodegenResult, Signature, TypeContext, INT},
+};
+
+// An operand that can be used in an instruction, a register, a constant or a global.
+#[derive(Debug, Clone)]
+struct Value {
+    ty: Type,
+    repr: String,
+}
+
+impl Value {
+    fn new(ty: Type, repr: impl Into<String>) -> Self {
+        Self {
+            ty,
+            repr: repr.into(),
+        }
+    }
+
+    fn void() -> Self {
+        Self::new(Type::Void, "")
+    }
+}
+
+// Written before the return type of a declaration or call, C's is LLVM's default.
+fn calling_convention(convention: CallingConvention) -> &'static str {
+    match convention {
+        CallingConvention::C => "",
+        CallingConvention::StdCall => "x86_stdcallcc ",
+        CallingConvention::FastCall => "x86_fastcallcc ",
+    }
+}
+
+// LLVM identifiers can contain letters, digits and `$._-`, anything else has to be quoted.
+fn symbol(prefix: char, name: &str) -> String {
+    if !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "$._-".contains(c))
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+    {
+        format!("{}{}", prefix, name)
+    } else {
+        format!("{}\"{}\"", prefix, name)
+    }
+}
+
+fn escape(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|byte| match byte {
+            b' '..=b'~' if *byte != b'"' && *byte != b'\\' => (*byte as char).to_string(),
+            _ => format!("\\{:02X}", byte),
+        })
+        .collect()
+}
+
+fn float_constant(value: f64, bits: u8) -> String {
+    // Hex is the only way to write every double exactly, floats are written as the double they
+    // widen to.
+    let value = if bits == 32 {
+        value as f32 as f64
+    } else {
+        value
+    };
+    format!("0x{:016X}", value.to_bits())
+}
+
+// The size of a value once stored, rounded up to 8 bytes. Only used to size the payload area of
+// tagged unions so it errs on the side of too big.
+fn slot_size(module: &Module, ty: &Type) -> usize {
+    match ty {
+        Type::Named { name, .. } => match module.r#enum(name) {
+            Some(r#enum) => 8 + payload_words(module, r#enum) * 8,
+            None => 8,
+        },
+        _ => 8,
+    }
+}
+
+fn payload_words(module: &Module, r#enum: &EnumDef) -> usize {
+    r#enum
+        .variants
+        .iter()
+        .map(|variant| {
+            variant
+                .payload
+                .iter()
+                .map(|ty| slot_size(module, ty))
+                .sum::<usize>()
+                / 8
+        })
+        .max()
+        .unwrap_or_default()
+}
+
+const ARRAY_TYPE: &str = "%elp.array = type { ptr, i64, i64 }\n";
+
+const ARRAY_HELPERS: &str = "define internal ptr @elp.array.new(i64 %size, i64 %len) {
+entry:
+  %header = call ptr @malloc(i64 24)
+  %bytes = mul i64 %size, %len
+  %data = call ptr @malloc(i64 %bytes)
+  %data.ptr = getelementptr inbounds %elp.array, ptr %header, i32 0, i32 0
+  store ptr %data, ptr %data.ptr
+  %len.ptr = getelementptr inbounds %elp.array, ptr %header, i32 0, i32 1
+  store i64 %len, ptr %len.ptr
+  %cap.ptr = getelementptr inbounds %elp.array, ptr %header, i32 0, i32 2
+  store i64 %len, ptr %cap.ptr
+  ret ptr %header
+}
+
+define internal ptr @elp.array.push(ptr %array, i64 %size) {
+entry:
+  %data.ptr = getelementptr inbounds %elp.array, ptr %array, i32 0, i32 0
+  %len.ptr = getelementptr inbounds %elp.array, ptr %array, i32 0, i32 1
+  %cap.ptr = getelementptr inbounds %elp.array, ptr %array, i32 0, i32 2
+  %len = load i64, ptr %len.ptr
+  %cap = load i64, ptr %cap.ptr
+  %full = icmp eq i64 %len, %cap
+  br i1 %full, label %grow, label %store
+
+grow:
+  %doubled = mul i64 %cap, 2
+  %new.cap = call i64 @llvm.umax.i64(i64 %doubled, i64 4)
+  %bytes = mul i64 %new.cap, %size
+  %old = load ptr, ptr %data.ptr
+  %new = call ptr @realloc(ptr %old, i64 %bytes)
+  store ptr %new, ptr %data.ptr
+  store i64 %new.cap, ptr %cap.ptr
+  br label %store
+
+store:
+  %next = add i64 %len, 1
+  store i64 %next, ptr %len.ptr
+  %data = load ptr, ptr %data.ptr
+  %offset = mul i64 %len, %size
+  %slot = getelementptr inbounds i8, ptr %data, i64 %offset
+  ret ptr %slot
+}
+";
+
+const STRING_HELPERS: &str = "define internal ptr @elp.string.concat(ptr %a, ptr %b) {
+entry:
+  %a.len = call i64 @strlen(ptr %a)
+  %b.len = call i64 @strlen(ptr %b)
+  %len = add i64 %a.len, %b.len
+  %size = add i64 %len, 1
+  %out = call ptr @malloc(i64 %size)
+  call ptr @memcpy(ptr %out, ptr %a, i64 %a.len)
+  %tail = getelementptr inbounds i8, ptr %out, i64 %a.len
+  call ptr @memcpy(ptr %tail, ptr %b, i64 %b.len)
+  %end = getelementptr inbounds i8, ptr %out, i64 %len
+  store i8 0, ptr %end
+  ret ptr %out
+}
+";
+
+struct Emitter<'m> {
+    ctx: TypeContext<'m>,
+    strings: Vec<String>,
+    // Declarations of C functions and intrinsics keyed by name so each is declared once.
+    declarations: BTreeMap<String, String>,
+    array_helpers: bool,
+    string_helpers: bool,
+
+    // Per function state.
+    allocas: String,
+    code: String,
+    // Stack slots of the locals in scope, innermost last.
+    slots: Vec<(String, String)>,
+    next_temp: u32,
+    next_label: u32,
+    block: String,
+    terminated: bool,
+    ret: Type,
+    // `fn main` without a return type still has to hand the OS an exit code.
+    returns_exit_code: bool,
+}
+
+impl<'m> Emitter<'m> {
+    fn new(module: &'m Module) -> Self {
+        Self {
+            ctx: TypeContext::new(module),
+            strings: vec![],
+            declarations: BTreeMap::new(),
+            array_helpers: false,
+            string_helpers: false,
+            allocas: String::new(),
+            code: String::new(),
+            slots: vec![],
+            next_temp: 0,
+            next_label: 0,
+            block: String::new(),
+            terminated: false,
+            ret: Type::Void,
+            returns_exit_code: false,
+        }
+    }
+
+    fn llvm_type(&self, ty: &Type) -> CodegenResult<String> {
+        Ok(match ty {
+            Type::Void => "void".into(),
+            Type::Bool => "i1".into(),
+            Type::Int { bits, .. } => format!("i{}", bits),
+            Type::Float { bits: 32 } => "float".into(),
+            Type::Float { .. } => "double".into(),
+            Type::String
+            | Type::Array(_)
+            | Type::Pointer(_)
+            | Type::Reference(_)
+            | Type::Function { .. } => "ptr".into(),
+            Type::Named { name, .. } => {
+                if self.ctx.module.object(name).is_some() {
+                    "ptr".into()
+                } else if self.ctx.module.r#enum(name).is_some() {
+                    symbol('%', name)
+                } else {
+                    return self.ctx.error(format!("unknown type `{}`", name));
+                }
+            }
+            Type::Unknown => {
+                return self
+                    .ctx
+                    .error("native code needs to know this type, add a type annotation")
+            }
+        })
+    }
+
+    fn declare(&mut self, name: &str, declaration: &str) {
+        self.declarations
+            .entry(name.into())
+            .or_insert_with(|| declaration.into());
+    }
+
+    fn string_constant(&mut self, value: &str) -> String {
+        let index = match self.strings.iter().position(|s| s == value) {
+            Some(index) => index,
+            None => {
+                self.strings.push(value.into());
+                self.strings.len() - 1
+            }
+        };
+        format!("@.str.{}", index)
+    }
+
+    fn temp(&mut self) -> String {
+        self.next_temp += 1;
+        format!("%t{}", self.next_temp)
+    }
+
+    fn label(&mut self, name: &str) -> String {
+        self.next_label += 1;
+        format!("{}{}", name, self.next_label)
+    }
+
+    // Code after a `ret` or `br` still needs a block to live in, even if nothing jumps to it.
+    fn ensure_block(&mut self) {
+        if self.terminated {
+            let label = self.label("dead");
+            self.start_block(&label);
+        }
+    }
+
+    fn inst(&mut self, instruction: impl AsRef<str>) {
+        self.ensure_block();
+        self.code.push_str("  ");
+        self.code.push_str(instruction.as_ref());
+        self.code.push('\n');
+    }
+
+    // Emits an instruction that produces a value.
+    fn assign(&mut self, ty: Type, instruction: impl AsRef<str>) -> Value {
+        let temp = self.temp();
+        self.inst(format!("{} = {}", temp, instruction.as_ref()));
+        Value::new(ty, temp)
+    }
+
+    fn terminate(&mut self, instruction: impl AsRef<str>) {
+        self.inst(instruction);
+        self.terminated = true;
+    }
+
+    fn start_block(&mut self, label: &str) {
+        if !self.terminated {
+            self.code.push_str(&format!("  br label %{}\n", label));
+        }
+        let _ = writeln!(self.code, "\n{}:", label);
+        self.block = label.into();
+        self.terminated = false;
+    }
+
+    fn alloca(&mut self, name: &str, ty: &Type) -> CodegenResult<String> {
+        let slot = format!("%{}.{}", name, self.next_temp);
+        self.next_temp += 1;
+        let _ = writeln!(self.allocas, "  {} = alloca {}", slot, self.llvm_type(ty)?);
+        Ok(slot)
+    }
+
+    fn load(&mut self, ty: &Type, pointer: &str) -> CodegenResult<Value> {
+        let llvm = self.llvm_type(ty)?;
+        Ok(self.assign(ty.clone(), format!("load {}, ptr {}", llvm, pointer)))
+    }
+
+    fn store(&mut self, value: &Value, pointer: &str) -> CodegenResult<()> {
+        let llvm = self.llvm_type(&value.ty)?;
+        self.inst(format!("store {} {}, ptr {}", llvm, value.repr, pointer));
+        Ok(())
+    }
+
+    fn size_of(&mut self, ty: &Type) -> CodegenResult<Value> {
+        let llvm = self.llvm_type(ty)?;
+        Ok(self.size_of_llvm(&llvm))
+    }
+
+    // The target independent way of asking for a type's size, the address of the second element
+    // of an array starting at null.
+    fn size_of_llvm(&mut self, llvm: &str) -> Value {
+        let end = self.assign(
+            Type::Void,
+            format!("getelementptr {}, ptr null, i32 1", llvm),
+        );
+        self.assign(INT, format!("ptrtoint ptr {} to i64", end.repr))
+    }
+
+    fn declare_local(&mut self, name: &str, value: Value) -> CodegenResult<()> {
+        let slot = self.alloca(name, &value.ty)?;
+        self.store(&value, &slot)?;
+        self.ctx.declare(name, value.ty);
+        self.slots.push((name.into(), slot));
+        Ok(())
+    }
+
+    fn slot(&self, name: &str) -> Option<&str> {
+        self.slots
+            .iter()
+            .rev()
+            .find(|(local, _)| local == name)
+            .map(|(_, slot)| slot.as_str())
+    }
+
+    fn scoped<T>(&mut self, f: impl FnOnce(&mut Self) -> CodegenResult<T>) -> CodegenResult<T> {
+        let depth = self.slots.len();
+        self.ctx.push_scope();
+        let result = f(self);
+        self.ctx.pop_scope();
+        self.slots.truncate(depth);
+        result
+    }
+
+    fn coerce(&mut self, value: Value, to: &Type) -> CodegenResult<Value> {
+        let from = self.llvm_type(&value.ty)?;
+        let llvm = self.llvm_type(to)?;
+        if from == llvm {
+            return Ok(Value::new(to.clone(), value.repr));
+        }
+
+        let cast = match (&value.ty, to) {
+            (Type::Int { bits: a, signed }, Type::Int { bits: b, .. }) => {
+                if a > b {
+                    "trunc"
+                } else if *signed {
+                    "sext"
+                } else {
+                    "zext"
+                }
+            }
+            (Type::Int { signed: true, .. }, Type::Float { .. }) => "sitofp",
+            (Type::Int { signed: false, .. }, Type::Float { .. }) => "uitofp",
+            (Type::Float { .. }, Type::Int { signed: true, .. }) => "fptosi",
+            (Type::Float { .. }, Type::Int { signed: false, .. }) => "fptoui",
+            (Type::Float { bits: a }, Type::Float { bits: b }) => {
+                if a > b {
+                    "fptrunc"
+                } else {
+                    "fpext"
+                }
+            }
+            (Type::Bool, Type::Int { .. }) => "zext",
+            _ => {
+                return self.ctx.error(format!(
+                    "expected a value of type {} but found {}",
+                    llvm, from
+                ))
+            }
+        };
+
+        Ok(self.assign(
+            to.clone(),
+            format!("{} {} {} to {}", cast, from, value.repr, llvm),
+        ))
+    }
+
+    fn emit_module(mut self) -> CodegenResult<String> {
+        let module = self.ctx.module;
+        let mut out = format!(
+            "; ModuleID = '{}'\nsource_filename = \"{}.elp\"\n",
+            module.name, module.name
+        );
+
+        let mut types = String::new();
+        for object in &module.objects {
+            let fields = object
+                .fields
+                .iter()
+                .map(|field| self.llvm_type(&field.ty))
+                .collect::<CodegenResult<Vec<_>>>()?;
+            let _ = writeln!(
+                types,
+                "{} = type {{ {} }}",
+                symbol('%', &object.name),
+                fields.join(", ")
+            );
+        }
+        for r#enum in &module.enums {
+            let _ = writeln!(
+                types,
+                "{} = type {{ i32, [{} x i64] }}",
+                symbol('%', &r#enum.name),
+                payload_words(module, r#enum)
+            );
+            for variant in r#enum.variants.iter().filter(|v| !v.payload.is_empty()) {
+                let mut fields = vec![];
+                for ty in &variant.payload {
+                    fields.push(self.llvm_type(ty)?);
+                }
+                let _ = writeln!(
+                    types,
+                    "{} = type {{ {} }}",
+                    symbol('%', &format!("{}.{}", r#enum.name, variant.name)),
+                    fields.join(", ")
+                );
+            }
+        }
+
+        let mut globals = String::new();
+        for global in &module.globals {
+            self.ctx.span = global.span;
+            let ty = self.ctx.global(&global.name)?;
+            let initialiser = match &global.value {
+                Expr::Literal(Literal::String(value)) => self.string_constant(value),
+                Expr::Literal(literal) => self.literal(literal, Some(&ty))?.repr,
+                _ => {
+                    return self
+                        .ctx
+                        .error("module level values have to be literals in native code")
+                }
+            };
+            let _ = writeln!(
+                globals,
+                "{} = {}{} {} {}",
+                symbol('@', &global.name),
+                if global.exported { "" } else { "internal " },
+                if global.mutable { "global" } else { "constant" },
+                self.llvm_type(&ty)?,
+                initialiser
+            );
+        }
+
+        // Check every signature first so errors point at the declaration rather than a call.
+        for function in &module.functions {
+            self.ctx.span = function.span;
+            for param in &function.params {
+                self.llvm_type(&param.ty)?;
+            }
+            self.llvm_type(&function.ret)?;
+        }
+
+        let mut functions = String::new();
+        for function in &module.functions {
+            functions.push('\n');
+            functions.push_str(&self.emit_function(function)?);
+        }
+
+        for external in &module.externals {
+            self.ctx.span = external.span;
+            let params = external
+                .params
+                .iter()
+                .map(|param| self.llvm_type(&param.ty))
+                .collect::<CodegenResult<Vec<_>>>()?;
+            let declaration = format!(
+                "declare {}{} {}({})",
+                calling_convention(external.abi.calling_convention()),
+                self.llvm_type(&external.ret)?,
+                symbol('@', &external.name),
+                params.join(", ")
+            );
+            self.declare(&external.name, &declaration);
+        }
+
+        if self.array_helpers {
+            self.declare("malloc", "declare ptr @malloc(i64)");
+            self.declare("realloc", "declare ptr @realloc(ptr, i64)");
+            self.declare("llvm.umax.i64", "declare i64 @llvm.umax.i64(i64, i64)");
+        }
+        if self.string_helpers {
+            self.declare("malloc", "declare ptr @malloc(i64)");
+            self.declare("strlen", "declare i64 @strlen(ptr)");
+            self.declare("memcpy", "declare ptr @memcpy(ptr, ptr, i64)");
+        }
+
+        if self.array_helpers {
+            types.push_str(ARRAY_TYPE);
+        }
+        if !types.is_empty() {
+            out.push('\n');
+            out.push_str(&types);
+        }
+        if !self.strings.is_empty() || !globals.is_empty() {
+            out.push('\n');
+        }
+        for (index, value) in self.strings.iter().enumerate() {
+            let _ = writeln!(
+                out,
+                "@.str.{} = private unnamed_addr constant [{} x i8] c\"{}\\00\"",
+                index,
+                value.len() + 1,
+                escape(value.as_bytes())
+            );
+        }
+        out.push_str(&globals);
+        out.push_str(&functions);
+        if self.array_helpers {
+            out.push('\n');
+            out.push_str(ARRAY_HELPERS);
+        }
+        if self.string_helpers {
+            out.push('\n');
+            out.push_str(STRING_HELPERS);
+        }
+        if !self.declarations.is_empty() {
+            out.push('\n');
+            for declaration in self.declarations.values() {
+                out.push_str(declaration);
+                out.push('\n');
+            }
+        }
+
+        Ok(out)
+    }
+
+    fn emit_function(&mut self, function: &Function) -> CodegenResult<String> {
+        self.ctx.span = function.span;
+
+        self.allocas.clear();
+        self.code.clear();
+        self.slots.clear();
+        self.next_temp = 0;
+        self.next_label = 0;
+        self.block = "entry".into();
+        self.terminated = false;
+        self.ret = function.ret.clone();
+        self.returns_exit_code =
+            function.name == "main" && function.receiver.is_none() && function.ret == Type::Void;
+
+        let name = function.qualified_name();
+        let public = function.exported || (function.name == "main" && function.receiver.is_none());
+        let ret = if self.returns_exit_code {
+            "i32".to_string()
+        } else {
+            self.llvm_type(&function.ret)?
+        };
+
+        let mut params = vec![];
+        self.ctx.push_scope();
+        for param in &function.params {
+            let ty = self.llvm_type(&param.ty)?;
+            let register = symbol('%', &format!("{}.arg", param.name));
+            params.push(format!("{} {}", ty, register));
+            self.declare_local(&param.name, Value::new(param.ty.clone(), register))?;
+        }
+        for stmt in &function.body {
+            self.emit_stmt(stmt)?;
+        }
+        self.ctx.pop_scope();
+
+        if !self.terminated {
+            if self.returns_exit_code {
+                self.terminate("ret i32 0");
+            } else if function.ret == Type::Void {
+                self.terminate("ret void");
+            } else {
+                self.terminate("unreachable");
+            }
+        }
+
+        Ok(format!(
+            "define {}{} {}({}) {{\nentry:\n{}{}}}\n",
+            if public { "" } else { "internal " },
+            ret,
+            symbol('@', &name),
+            params.join(", "),
+            self.allocas,
+            self.code
+        ))
+    }
+
+    fn emit_block(&mut self, body: &[Stmt]) -> CodegenResult<()> {
+        self.scoped(|e| {
+            for stmt in body {
+                e.emit_stmt(stmt)?;
+            }
+            Ok(())
+        })
+    }
+
+    fn emit_stmt(&mut self, stmt: &Stmt) -> CodegenResult<()> {
+        self.ctx.span = stmt.span;
+
+        match &stmt.kind {
+            StmtKind::Let {
+                name, ty, value, ..
+            } => {
+                let ty = match (ty, value) {
+                    (Type::Unknown, Some(value)) => self.ctx.type_of(value)?,
+                    (Type::Unknown, None) => {
+                        return self
+                            .ctx
+                            .error(format!("`{}` needs a type or a value", name))
+                    }
+                    (ty, _) => ty.clone(),
+                };
+                let value = match value {
+                    Some(value) => {
+                        let value = self.emit_expr(value, Some(&ty))?;
+                        self.coerce(value, &ty)?
+                    }
+                    None => Value::new(ty.clone(), "zeroinitializer"),
+                };
+                self.declare_local(name, value)?;
+            }
+            StmtKind::Assign { target, op, value } => {
+                let (pointer, ty) = self.place(target)?;
+                let value = match op {
+                    Some(op) => self.emit_binary(*op, target, value)?,
+                    None => self.emit_expr(value, Some(&ty))?,
+                };
+                let value = self.coerce(value, &ty)?;
+                self.store(&value, &pointer)?;
+            }
+            StmtKind::Expr(expr) => {
+                self.emit_expr(expr, None)?;
+            }
+            StmtKind::Return(value) => match value {
+                _ if self.returns_exit_code => self.terminate("ret i32 0"),
+                Some(value) => {
+                    let ret = self.ret.clone();
+                    let value = self.emit_expr(value, Some(&ret))?;
+                    let value = self.coerce(value, &ret)?;
+                    let llvm = self.llvm_type(&ret)?;
+                    self.terminate(format!("ret {} {}", llvm, value.repr));
+                }
+                None => self.terminate("ret void"),
+            },
+            StmtKind::If {
+                condition,
+                then_body,
+                else_body,
+            } => {
+                let condition = self.emit_condition(condition)?;
+                let then_label = self.label("then");
+                let else_label = self.label("else");
+                let end_label = self.label("endif");
+                self.terminate(format!(
+                    "br i1 {}, label %{}, label %{}",
+                    condition.repr, then_label, else_label
+                ));
+                self.start_block(&then_label);
+                self.emit_block(then_body)?;
+                self.start_block(&else_label);
+                self.emit_block(else_body)?;
+                self.start_block(&end_label);
+            }
+            StmtKind::For {
+                binding,
+                iterable,
+                body,
+            } => self.emit_for(binding, iterable, body)?,
+        }
+
+        Ok(())
+    }
+
+    fn emit_for(&mut self, binding: &str, iterable: &Expr, body: &[Stmt]) -> CodegenResult<()> {
+        let array = self.emit_expr(iterable, None)?;
+        let Type::Array(item) = array.ty.clone() else {
+            return self
+                .ctx
+                .error("only arrays can be looped over in native code");
+        };
+        let len = self.array_len(&array)?;
+        let index = self.alloca("index", &INT)?;
+        self.store(&Value::new(INT, "0"), &index)?;
+
+        let check = self.label("for");
+        let step = self.label("body");
+        let end = self.label("endfor");
+        self.start_block(&check);
+        let current = self.load(&INT, &index)?;
+        let more = self.assign(
+            Type::Bool,
+            format!("icmp slt i64 {}, {}", current.repr, len.repr),
+        );
+        self.terminate(format!(
+            "br i1 {}, label %{}, label %{}",
+            more.repr, step, end
+        ));
+
+        self.start_block(&step);
+        let element = self.array_element(&array, &item, &current)?;
+        let value = self.load(&item, &element.repr)?;
+        self.scoped(|e| {
+            e.declare_local(binding, value)?;
+            for stmt in body {
+                e.emit_stmt(stmt)?;
+            }
+            Ok(())
+        })?;
+        let current = self.load(&INT, &index)?;
+        let next = self.assign(INT, format!("add i64 {}, 1", current.repr));
+        self.store(&next, &index)?;
+        self.terminate(format!("br label %{}", check));
+        self.start_block(&end);
+
+        Ok(())
+    }
+
+    fn emit_condition(&mut self, condition: &Expr) -> CodegenResult<Value> {
+        let value = self.emit_expr(condition, Some(&Type::Bool))?;
+        if value.ty != Type::Bool {
+            return self.ctx.error("conditions have to be booleans");
+        }
+        Ok(value)
+    }
+
+    // Where an assignment target lives and the type stored there.
+    fn place(&mut self, target: &Expr) -> CodegenResult<(String, Type)> {
+        match target {
+            Expr::Local(name) => {
+                let ty = self.ctx.type_of(target)?;
+                match self.slot(name) {
+                    Some(slot) => Ok((slot.to_string(), ty)),
+                    None => self.ctx.error(format!("`{}` isn't defined", name)),
+                }
+            }
+            Expr::Global(name) => Ok((symbol('@', name), self.ctx.global(name)?)),
+            Expr::Field { base, name } => {
+                let base = self.emit_expr(base, None)?;
+                let (object, index) = self.ctx.field(&base.ty, name)?;
+                let pointer = self.assign(
+                    Type::Void,
+                    format!(
+                        "getelementptr inbounds {}, ptr {}, i32 0, i32 {}",
+                        symbol('%', &object.name),
+                        base.repr,
+                        index
+                    ),
+                );
+                Ok((pointer.repr, object.fields[index].ty.clone()))
+            }
+            _ => self.ctx.error("can't assign to this expression"),
+        }
+    }
+
+    fn literal(&mut self, literal: &Literal, expected: Option<&Type>) -> CodegenResult<Value> {
+        Ok(match (literal, expected) {
+            (Literal::Nil, _) => Value::new(Type::Pointer(Box::new(Type::Void)), "null"),
+            (Literal::Bool(value), _) => Value::new(Type::Bool, value.to_string()),
+            (Literal::Int(value), Some(ty @ Type::Int { .. })) => {
+                Value::new(ty.clone(), value.to_string())
+            }
+            (Literal::Int(value), Some(Type::Float { bits })) => Value::new(
+                Type::Float { bits: *bits },
+                float_constant(*value as f64, *bits),
+            ),
+            (Literal::Int(value), _) => Value::new(INT, value.to_string()),
+            (Literal::Float(value), Some(Type::Float { bits })) => {
+                Value::new(Type::Float { bits: *bits }, float_constant(*value, *bits))
+            }
+            (Literal::Float(value), _) => {
+                Value::new(Type::Float { bits: 64 }, float_constant(*value, 64))
+            }
+            (Literal::String(value), _) => Value::new(Type::String, self.string_constant(value)),
+        })
+    }
+
+    fn emit_expr(&mut self, expr: &Expr, expected: Option<&Type>) -> CodegenResult<Value> {
+        match expr {
+            Expr::Literal(literal) => self.literal(literal, expected),
+            Expr::Local(name) => {
+                let ty = self.ctx.type_of(expr)?;
+                let Some(slot) = self.slot(name).map(str::to_string) else {
+                    return self.ctx.error(format!("`{}` isn't defined", name));
+                };
+                self.load(&ty, &slot)
+            }
+            Expr::Global(name) => {
+                if self.ctx.signature(name).is_some() {
+                    return self.ctx.error(format!(
+                        "`{}` can only be called, functions aren't values in native code yet",
+                        name
+                    ));
+                }
+                let ty = self.ctx.global(name)?;
+                self.load(&ty, &symbol('@', name))
+            }
+            Expr::Field { .. } => {
+                let (pointer, ty) = self.place(expr)?;
+                self.load(&ty, &pointer)
+            }
+            Expr::Call { callee, args } => self.emit_call(callee, args),
+            Expr::MethodCall {
+                receiver,
+                method,
+                args,
+            } => self.emit_method_call(receiver, method, args),
+            Expr::Binary { op, lhs, rhs } => self.emit_binary(*op, lhs, rhs),
+            Expr::Unary { op, operand } => {
+                let value = self.emit_expr(operand, expected)?;
+                let llvm = self.llvm_type(&value.ty)?;
+                match (op, &value.ty) {
+                    (UnaryOp::Not, Type::Bool) => {
+                        Ok(self.assign(Type::Bool, format!("xor i1 {}, true", value.repr)))
+                    }
+                    (UnaryOp::Neg, Type::Int { .. }) => Ok(
+                        self.assign(value.ty.clone(), format!("sub {} 0, {}", llvm, value.repr))
+                    ),
+                    (UnaryOp::Neg, Type::Float { .. }) => {
+                        Ok(self.assign(value.ty.clone(), format!("fneg {} {}", llvm, value.repr)))
+                    }
+                    _ => self
+                        .ctx
+                        .error(format!("can't apply {:?} to a value of type {}", op, llvm)),
+                }
+            }
+            Expr::Object { name, fields } => self.emit_object(name, fields),
+            Expr::Variant {
+                enum_name,
+                variant,
+                args,
+            } => self.emit_variant(enum_name.as_deref(), variant, args, expected),
+            Expr::Array(items) => {
+                let ty = match (items.first(), expected) {
+                    (_, Some(ty @ Type::Array(_))) => ty.clone(),
+                    _ => self.ctx.type_of(expr)?,
+                };
+                let Type::Array(item) = &ty else {
+                    unreachable!("array literals are always arrays")
+                };
+                self.array_helpers = true;
+                let size = self.size_of(item)?;
+                let array = self.assign(
+                    ty.clone(),
+                    format!(
+                        "call ptr @elp.array.new(i64 {}, i64 {})",
+                        size.repr,
+                        items.len()
+                    ),
+                );
+                for (index, value) in items.iter().enumerate() {
+                    let value = self.emit_expr(value, Some(item))?;
+                    let value = self.coerce(value, item)?;
+                    let element =
+                        self.array_element(&array, item, &Value::new(INT, index.to_string()))?;
+                    self.store(&value, &element.repr)?;
+                }
+                Ok(array)
+            }
+            Expr::Closure(_) => self
+                .ctx
+                .error("closures aren't supported by the LLVM backend yet"),
+            Expr::Match { scrutinee, arms } => self.emit_match(scrutinee, arms, expected),
+            Expr::Element(element) => self.ctx.error(format!(
+                "`{}` is an element, elements are only supported by the web backend",
+                element.name
+            )),
+        }
+    }
+
+    fn array_len(&mut self, array: &Value) -> CodegenResult<Value> {
+        let pointer = self.assign(
+            Type::Void,
+            format!(
+                "getelementptr inbounds %elp.array, ptr {}, i32 0, i32 1",
+                array.repr
+            ),
+        );
+        self.load(&INT, &pointer.repr)
+    }
+
+    fn array_element(&mut self, array: &Value, item: &Type, index: &Value) -> CodegenResult<Value> {
+        let data_pointer = self.assign(
+            Type::Void,
+            format!(
+                "getelementptr inbounds %elp.array, ptr {}, i32 0, i32 0",
+                array.repr
+            ),
+        );
+        let data = self.assign(Type::Void, format!("load ptr, ptr {}", data_pointer.repr));
+        let llvm = self.llvm_type(item)?;
+        Ok(self.assign(
+            Type::Void,
+            format!(
+                "getelementptr inbounds {}, ptr {}, i64 {}",
+                llvm, data.repr, index.repr
+            ),
+        ))
+    }
+
+    fn emit_args(&mut self, params: &[Type], args: &[Value]) -> CodegenResult<Vec<String>> {
+        let mut out = vec![];
+        for (param, arg) in params.iter().zip(args) {
+            let value = self.coerce(arg.clone(), param)?;
+            out.push(format!("{} {}", self.llvm_type(param)?, value.repr));
+        }
+        Ok(out)
+    }
+
+    fn emit_call(&mut self, callee: &Expr, args: &[Expr]) -> CodegenResult<Value> {
+        let Expr::Global(name) = callee else {
+            return self
+                .ctx
+                .error("only functions can be called directly in native code");
+        };
+        if is_builtin(name) && self.ctx.signature(name).is_none() {
+            return self.emit_print(name, args);
+        }
+        let Some(signature) = self.ctx.signature(name) else {
+            return self.ctx.error(format!("`{}` isn't defined", name));
+        };
+        if signature.params.len() != args.len() {
+            return self.ctx.error(format!(
+                "`{}` takes {} arguments but {} were given",
+                name,
+                signature.params.len(),
+                args.len()
+            ));
+        }
+
+        let mut values = vec![];
+        for (param, arg) in signature.params.iter().zip(args) {
+            values.push(self.emit_expr(arg, Some(param))?);
+        }
+        self.call(&signature, &values)
+    }
+
+    fn call(&mut self, signature: &Signature, values: &[Value]) -> CodegenResult<Value> {
+        let args = self.emit_args(&signature.params, values)?;
+        let callee = symbol('@', &signature.name);
+        let cc = calling_convention(signature.convention);
+        if signature.ret == Type::Void {
+            self.inst(format!("call {}void {}({})", cc, callee, args.join(", ")));
+            Ok(Value::void())
+        } else {
+            let ret_llvm = self.llvm_type(&signature.ret)?;
+            Ok(self.assign(
+                signature.ret.clone(),
+                format!("call {}{} {}({})", cc, ret_llvm, callee, args.join(", ")),
+            ))
+        }
+    }
+
+    fn emit_method_call(
+        &mut self,
+        receiver: &Expr,
+        method: &str,
+        args: &[Expr],
+    ) -> CodegenResult<Value> {
+        let value = self.emit_expr(receiver, None)?;
+
+        if let Type::Array(item) = value.ty.clone() {
+            return match (method, args) {
+                ("append", [arg]) => {
+                    let arg = self.emit_expr(arg, Some(&item))?;
+                    let arg = self.coerce(arg, &item)?;
+                    let index = self.array_len(&value)?;
+                    let size = self.size_of(&item)?;
+                    let slot = self.assign(
+                        Type::Void,
+                        format!(
+                            "call ptr @elp.array.push(ptr {}, i64 {})",
+                            value.repr, size.repr
+                        ),
+                    );
+                    self.store(&arg, &slot.repr)?;
+                    Ok(index)
+                }
+                ("len" | "length", []) => self.array_len(&value),
+                _ => self
+                    .ctx
+                    .error(format!("arrays don't have a `{}` method", method)),
+            };
+        }
+        if value.ty == Type::String && matches!(method, "len" | "length") && args.is_empty() {
+            self.declare("strlen", "declare i64 @strlen(ptr)");
+            return Ok(self.assign(INT, format!("call i64 @strlen(ptr {})", value.repr)));
+        }
+
+        let Some(signature) = self.ctx.method(&value.ty, method) else {
+            return self.ctx.error(format!("no method `{}` found", method));
+        };
+        let is_method = self
+            .ctx
+            .module
+            .function(&signature.name)
+            .is_some_and(Function::is_method);
+
+        let mut values = vec![];
+        let params = if is_method {
+            values.push(value);
+            &signature.params[1..]
+        } else {
+            &signature.params[..]
+        };
+        if params.len() != args.len() {
+            return self.ctx.error(format!(
+                "`{}` takes {} arguments but {} were given",
+                signature.name,
+                params.len(),
+                args.len()
+            ));
+        }
+        for (param, arg) in params.iter().zip(args) {
+            values.push(self.emit_expr(arg, Some(param))?);
+        }
+        self.call(&signature, &values)
+    }
+
+    fn emit_print(&mut self, name: &str, args: &[Expr]) -> CodegenResult<Value> {
+        let [arg] = args else {
+            return self
+                .ctx
+                .error(format!("`{}` takes exactly one argument", name));
+        };
+        let value = self.emit_expr(arg, None)?;
+        let newline = if name == "println" { "\n" } else { "" };
+        let (format, value) = match &value.ty {
+            Type::String => ("%s", value),
+            Type::Bool => {
+                let yes = self.string_constant("true");
+                let no = self.string_constant("false");
+                let text = self.assign(
+                    Type::String,
+                    format!("select i1 {}, ptr {}, ptr {}", value.repr, yes, no),
+                );
+                ("%s", text)
+            }
+            Type::Int { signed, .. } => {
+                let format = if *signed { "%lld" } else { "%llu" };
+                (format, self.coerce(value, &INT)?)
+            }
+            Type::Float { .. } => ("%f", self.coerce(value, &Type::Float { bits: 64 })?),
+            _ => {
+                return self
+                    .ctx
+                    .error("only strings, numbers and bools can be printed")
+            }
+        };
+        let format = self.string_constant(&format!("{}{}", format, newline));
+        let llvm = self.llvm_type(&value.ty)?;
+        self.declare("printf", "declare i32 @printf(ptr, ...)");
+        self.inst(format!(
+            "call i32 (ptr, ...) @printf(ptr {}, {} {})",
+            format, llvm, value.repr
+        ));
+
+        Ok(Value::void())
+    }
+
+    fn emit_object(&mut self, name: &str, fields: &[(String, Expr)]) -> CodegenResult<Value> {
+        let Some(object) = self.ctx.module.object(name) else {
+            return self.ctx.error(format!("unknown object `{}`", name));
+        };
+        let ty = Type::Named {
+            name: name.into(),
+            generics: vec![],
+        };
+        for (field, _) in fields {
+            if object.field_index(field).is_none() {
+                return self
+                    .ctx
+                    .error(format!("`{}` has no field `{}`", name, field));
+            }
+        }
+
+        // Objects are pointers, the allocation needs the size of the struct itself.
+        let size = self.size_of_llvm(&symbol('%', name));
+        self.declare("malloc", "declare ptr @malloc(i64)");
+        let pointer = self.assign(ty.clone(), format!("call ptr @malloc(i64 {})", size.repr));
+        for (index, field) in object.fields.iter().enumerate() {
+            let value = match fields.iter().find(|(name, _)| *name == field.name) {
+                Some((_, value)) => Some(value),
+                None => field.default.as_ref(),
+            };
+            let value = match value {
+                Some(value) => {
+                    let value = self.emit_expr(value, Some(&field.ty))?;
+                    self.coerce(value, &field.ty)?
+                }
+                None => Value::new(field.ty.clone(), "zeroinitializer"),
+            };
+            let slot = self.assign(
+                Type::Void,
+                format!(
+                    "getelementptr inbounds {}, ptr {}, i32 0, i32 {}",
+                    symbol('%', name),
+                    pointer.repr,
+                    index
+                ),
+            );
+            self.store(&value, &slot.repr)?;
+        }
+
+        Ok(pointer)
+    }
+
+    fn emit_variant(
+        &mut self,
+        enum_name: Option<&str>,
+        variant: &str,
+        args: &[Expr],
+        expected: Option<&Type>,
+    ) -> CodegenResult<Value> {
+        let (r#enum, tag) = self.ctx.variant(enum_name, variant, expected)?;
+        let payload = &r#enum.variants[tag].payload;
+        if payload.len() != args.len() {
+            return self.ctx.error(format!(
+                "`{}.{}` carries {} values but {} were given",
+                r#enum.name,
+                variant,
+                payload.len(),
+                args.len()
+            ));
+        }
+        let ty = Type::Named {
+            name: r#enum.name.clone(),
+            generics: vec![],
+        };
+        if args.is_empty() {
+            let words = payload_words(self.ctx.module, r#enum);
+            return Ok(Value::new(
+                ty,
+                format!("{{ i32 {}, [{} x i64] zeroinitializer }}", tag, words),
+            ));
+        }
+
+        let slot = self.alloca("variant", &ty)?;
+        self.inst(format!("store i32 {}, ptr {}", tag, slot));
+        for (index, (ty, arg)) in payload.iter().zip(args).enumerate() {
+            let value = self.emit_expr(arg, Some(ty))?;
+            let value = self.coerce(value, ty)?;
+            let field = self.payload_field(&r#enum.name, variant, &slot, index);
+            self.store(&value, &field.repr)?;
+        }
+        self.load(&ty, &slot)
+    }
+
+    // Payloads live after the tag in the union's payload area, laid out as the variant's struct.
+    fn payload_field(&mut self, enum_name: &str, variant: &str, slot: &str, index: usize) -> Value {
+        let payload = self.assign(
+            Type::Void,
+            format!(
+                "getelementptr inbounds {}, ptr {}, i32 0, i32 1",
+                symbol('%', enum_name),
+                slot
+            ),
+        );
+        self.assign(
+            Type::Void,
+            format!(
+                "getelementptr inbounds {}, ptr {}, i32 0, i32 {}",
+                symbol('%', &format!("{}.{}", enum_name, variant)),
+                payload.repr,
+                index
+            ),
+        )
+    }
+
+    fn emit_binary(&mut self, op: BinaryOp, lhs: &Expr, rhs: &Expr) -> CodegenResult<Value> {
+        if matches!(op, BinaryOp::And | BinaryOp::Or) {
+            return self.emit_logical(op, lhs, rhs);
+        }
+
+        let ty = self.ctx.operand_type(lhs, rhs)?;
+        let lhs = self.emit_expr(lhs, Some(&ty))?;
+        let lhs = self.coerce(lhs, &ty)?;
+        let rhs = self.emit_expr(rhs, Some(&ty))?;
+        let rhs = self.coerce(rhs, &ty)?;
+        let llvm = self.llvm_type(&ty)?;
+        let operands = format!("{} {}, {}", llvm, lhs.repr, rhs.repr);
+
+        let comparison = match op {
+            BinaryOp::Eq => Some(("eq", "eq", "oeq")),
+            BinaryOp::Ne => Some(("ne", "ne", "une")),
+            BinaryOp::Lt => Some(("slt", "ult", "olt")),
+            BinaryOp::Le => Some(("sle", "ule", "ole")),
+            BinaryOp::Gt => Some(("sgt", "ugt", "ogt")),
+            BinaryOp::Ge => Some(("sge", "uge", "oge")),
+            _ => None,
+        };
+
+        if let Some((signed, unsigned, float)) = comparison {
+            return match &ty {
+                Type::Int { signed: true, .. } | Type::Bool => {
+                    Ok(self.assign(Type::Bool, format!("icmp {} {}", signed, operands)))
+                }
+                Type::Int { signed: false, .. } => {
+                    Ok(self.assign(Type::Bool, format!("icmp {} {}", unsigned, operands)))
+                }
+                Type::Float { .. } => {
+                    Ok(self.assign(Type::Bool, format!("fcmp {} {}", float, operands)))
+                }
+                Type::String => {
+                    self.declare("strcmp", "declare i32 @strcmp(ptr, ptr)");
+                    let order = self.assign(
+                        Type::Int {
+                            bits: 32,
+                            signed: true,
+                        },
+                        format!("call i32 @strcmp(ptr {}, ptr {})", lhs.repr, rhs.repr),
+                    );
+                    Ok(self.assign(Type::Bool, format!("icmp {} i32 {}, 0", signed, order.repr)))
+                }
+                ty if self.ctx.enum_of(ty).is_some()
+                    && matches!(op, BinaryOp::Eq | BinaryOp::Ne) =>
+                {
+                    let a = self.assign(INT, format!("extractvalue {} {}, 0", llvm, lhs.repr));
+                    let b = self.assign(INT, format!("extractvalue {} {}, 0", llvm, rhs.repr));
+                    Ok(self.assign(
+                        Type::Bool,
+                        format!("icmp {} i32 {}, {}", signed, a.repr, b.repr),
+                    ))
+                }
+                _ if llvm == "ptr" && matches!(op, BinaryOp::Eq | BinaryOp::Ne) => {
+                    Ok(self.assign(Type::Bool, format!("icmp {} {}", signed, operands)))
+                }
+                _ => self
+                    .ctx
+                    .error(format!("can't compare values of type {}", llvm)),
+            };
+        }
+
+        let instruction = match (&ty, op) {
+            (Type::Int { .. }, BinaryOp::Add) => "add",
+            (Type::Int { .. }, BinaryOp::Sub) => "sub",
+            (Type::Int { .. }, BinaryOp::Mul) => "mul",
+            (Type::Int { signed: true, .. }, BinaryOp::Div) => "sdiv",
+            (Type::Int { signed: false, .. }, BinaryOp::Div) => "udiv",
+            (Type::Int { signed: true, .. }, BinaryOp::Mod) => "srem",
+            (Type::Int { signed: false, .. }, BinaryOp::Mod) => "urem",
+            (Type::Int { .. } | Type::Bool, BinaryOp::BitAnd) => "and",
+            (Type::Int { .. } | Type::Bool, BinaryOp::BitOr) => "or",
+            (Type::Int { .. }, BinaryOp::Shl) => "shl",
+            (Type::Int { signed: true, .. }, BinaryOp::Shr) => "ashr",
+            (Type::Int { signed: false, .. }, BinaryOp::Shr) => "lshr",
+            (Type::Float { .. }, BinaryOp::Add) => "fadd",
+            (Type::Float { .. }, BinaryOp::Sub) => "fsub",
+            (Type::Float { .. }, BinaryOp::Mul) => "fmul",
+            (Type::Float { .. }, BinaryOp::Div) => "fdiv",
+            (Type::Float { .. }, BinaryOp::Mod) => "frem",
+            (Type::Int { .. } | Type::Float { .. }, BinaryOp::Pow) => {
+                return self.emit_pow(&ty, lhs, rhs)
+            }
+            (Type::String, BinaryOp::Add) => {
+                self.string_helpers = true;
+                return Ok(self.assign(
+                    Type::String,
+                    format!(
+                        "call ptr @elp.string.concat(ptr {}, ptr {})",
+                        lhs.repr, rhs.repr
+                    ),
+                ));
+            }
+            _ => {
+                return self
+                    .ctx
+                    .error(format!("can't apply {:?} to values of type {}", op, llvm))
+            }
+        };
+
+        Ok(self.assign(ty, format!("{} {}", instruction, operands)))
+    }
+
+    fn emit_pow(&mut self, ty: &Type, lhs: Value, rhs: Value) -> CodegenResult<Value> {
+        let double = Type::Float { bits: 64 };
+        let lhs = self.coerce(lhs, &double)?;
+        let rhs = self.coerce(rhs, &double)?;
+        self.declare(
+            "llvm.pow.f64",
+            "declare double @llvm.pow.f64(double, double)",
+        );
+        let result = self.assign(
+            double,
+            format!(
+                "call double @llvm.pow.f64(double {}, double {})",
+                lhs.repr, rhs.repr
+            ),
+        );
+        self.coerce(result, ty)
+    }
+
+    // `and`/`or` only evaluate their right hand side when they have to.
+    fn emit_logical(&mut self, op: BinaryOp, lhs: &Expr, rhs: &Expr) -> CodegenResult<Value> {
+        let lhs = self.emit_condition(lhs)?;
+        let lhs_block = self.block.clone();
+        let rhs_label = self.label("rhs");
+        let end_label = self.label("logic");
+        let (on_true, on_false) = match op {
+            BinaryOp::And => (&rhs_label, &end_label),
+            _ => (&end_label, &rhs_label),
+        };
+        self.terminate(format!(
+            "br i1 {}, label %{}, label %{}",
+            lhs.repr, on_true, on_false
+        ));
+
+        self.start_block(&rhs_label);
+        let rhs = self.emit_condition(rhs)?;
+        let rhs_block = self.block.clone();
+        self.start_block(&end_label);
+
+        let short_circuit = if op == BinaryOp::And { "false" } else { "true" };
+        Ok(self.assign(
+            Type::Bool,
+            format!(
+                "phi i1 [ {}, %{} ], [ {}, %{} ]",
+                short_circuit, lhs_block, rhs.repr, rhs_block
+            ),
+        ))
+    }
+
+    fn emit_match(
+        &mut self,
+        scrutinee: &Expr,
+        arms: &[MatchArm],
+        expected: Option<&Type>,
+    ) -> CodegenResult<Value> {
+        let subject = self.emit_expr(scrutinee, None)?;
+        let ty = match expected {
+            Some(ty) if *ty != Type::Void => ty.clone(),
+            _ => self.ctx.type_of(&Expr::Match {
+                scrutinee: Box::new(scrutinee.clone()),
+                arms: arms.to_vec(),
+            })?,
+        };
+        let result = match ty {
+            Type::Void => None,
+            _ => Some(self.alloca("match", &ty)?),
+        };
+        let subject_slot = self.alloca("subject", &subject.ty)?;
+        self.store(&subject, &subject_slot)?;
+        let end = self.label("endmatch");
+
+        let arm_labels = arms.iter().map(|_| self.label("arm")).collect::<Vec<_>>();
+        let no_match = self.label("nomatch");
+
+        if let Some(r#enum) = self.ctx.enum_of(&subject.ty) {
+            // Every arm is a variant or a catch all, dispatch on the tag in one go.
+            let mut cases = vec![];
+            let mut default = None;
+            for (index, arm) in arms.iter().enumerate() {
+                match &arm.pattern {
+                    Pattern::Variant { name, .. } => {
+                        let (_, tag) = self.ctx.variant(None, name, Some(&subject.ty))?;
+                        if !cases.iter().any(|(t, _)| *t == tag) {
+                            cases.push((tag, &arm_labels[index]));
+                        }
+                    }
+                    Pattern::Wildcard | Pattern::Binding(_) => {
+                        default.get_or_insert(&arm_labels[index]);
+                    }
+                    _ => {
+                        return self.ctx.error(format!(
+                            "`{}` can only be matched by its variants",
+                            r#enum.name
+                        ))
+                    }
+                }
+            }
+            let tag = self.assign(
+                Type::Int {
+                    bits: 32,
+                    signed: true,
+                },
+                format!(
+                    "extractvalue {} {}, 0",
+                    symbol('%', &r#enum.name),
+                    subject.repr
+                ),
+            );
+            let cases = cases
+                .iter()
+                .map(|(tag, label)| format!("i32 {}, label %{}", tag, label))
+                .collect::<Vec<_>>();
+            self.terminate(format!(
+                "switch i32 {}, label %{} [ {} ]",
+                tag.repr,
+                default.unwrap_or(&no_match),
+                cases.join(" ")
+            ));
+        } else {
+            for (index, arm) in arms.iter().enumerate() {
+                let next = self.label("next");
+                let matched = self.emit_pattern_test(&subject, &arm.pattern)?;
+                match matched {
+                    Some(matched) => self.terminate(format!(
+                        "br i1 {}, label %{}, label %{}",
+                        matched.repr, arm_labels[index], next
+                    )),
+                    None => self.terminate(format!("br label %{}", arm_labels[index])),
+                }
+                self.start_block(&next);
+            }
+            self.terminate(format!("br label %{}", no_match));
+        }
+
+        for (arm, label) in arms.iter().zip(&arm_labels) {
+            self.start_block(label);
+            self.scoped(|e| {
+                for (name, ty) in e.ctx.pattern_bindings(&subject.ty, &arm.pattern)? {
+                    let value = match &arm.pattern {
+                        Pattern::Variant {
+                            name: variant,
+                            bindings,
+                        } => {
+                            let (r#enum, _) = e.ctx.variant(None, variant, Some(&subject.ty))?;
+                            let index = bindings.iter().position(|b| *b == name).unwrap_or(0);
+                            let field =
+                                e.payload_field(&r#enum.name, variant, &subject_slot, index);
+                            e.load(&ty, &field.repr)?
+                        }
+                        _ => e.load(&ty, &subject_slot)?,
+                    };
+                    e.declare_local(&name, value)?;
+                }
+                let value = e.emit_expr(&arm.body, result.as_ref().map(|_| &ty))?;
+                if let Some(result) = &result {
+                    let value = e.coerce(value, &ty)?;
+                    e.store(&value, result)?;
+                }
+                Ok(())
+            })?;
+            self.terminate(format!("br label %{}", end));
+        }
+
+        self.start_block(&no_match);
+        self.terminate("unreachable");
+        self.start_block(&end);
+
+        match result {
+            Some(result) => self.load(&ty, &result),
+            None => Ok(Value::void()),
+        }
+    }
+
+    // None means the pattern always matches.
+    fn emit_pattern_test(
+        &mut self,
+        subject: &Value,
+        pattern: &Pattern,
+    ) -> CodegenResult<Option<Value>> {
+        let compare = |e: &mut Self, op: BinaryOp, literal: &Literal| {
+            e.compare_value(op, subject, &Expr::Literal(literal.clone()))
+        };
+
+        Ok(match pattern {
+            Pattern::Wildcard | Pattern::Binding(_) => None,
+            Pattern::Literal(literal) => Some(compare(self, BinaryOp::Eq, literal)?),
+            Pattern::Range { start, end } => {
+                let lower = match start {
+                    Some(start) => Some(compare(self, BinaryOp::Ge, start)?),
+                    None => None,
+                };
+                let upper = match end {
+                    Some(end) => Some(compare(self, BinaryOp::Le, end)?),
+                    None => None,
+                };
+                match (lower, upper) {
+                    (Some(lower), Some(upper)) => Some(
+                        self.assign(Type::Bool, format!("and i1 {}, {}", lower.repr, upper.repr)),
+                    ),
+                    (lower, upper) => lower.or(upper),
+                }
+            }
+            Pattern::Variant { .. } => {
+                return self.ctx.error("variant patterns can only match enums")
+            }
+        })
+    }
+
+    fn compare_value(
+        &mut self,
+        op: BinaryOp,
+        subject: &Value,
+        literal: &Expr,
+    ) -> CodegenResult<Value> {
+        let rhs = self.emit_expr(literal, Some(&subject.ty))?;
+        let rhs = self.coerce(rhs, &subject.ty)?;
+        let llvm = self.llvm_type(&subject.ty)?;
+        let (signed, unsigned, float) = match op {
+            BinaryOp::Eq => ("eq", "eq", "oeq"),
+            BinaryOp::Ge => ("sge", "uge", "oge"),
+            _ => ("sle", "ule", "ole"),
+        };
+        Ok(match &subject.ty {
+            Type::Float { .. } => self.assign(
+                Type::Bool,
+                format!("fcmp {} {} {}, {}", float, llvm, subject.repr, rhs.repr),
+            ),
+            Type::Int { signed: false, .. } => self.assign(
+                Type::Bool,
+                format!("icmp {} {} {}, {}", unsigned, llvm, subject.repr, rhs.repr),
+            ),
+            Type::String => {
+                self.declare("strcmp", "declare i32 @strcmp(ptr, ptr)");
+                let order = self.assign(
+                    INT,
+                    format!("call i32 @strcmp(ptr {}, ptr {})", subject.repr, rhs.repr),
+                );
+                self.assign(Type::Bool, format!("icmp {} i32 {}, 0", signed, order.repr))
+            }
+            _ => self.assign(
+                Type::Bool,
+                format!("icmp {} {} {}, {}", signed, llvm, subject.repr, rhs.repr),
+            ),
+        })
+    }
+}
+
+pub fn emit_module(module: &Module) -> CodegenResult<String> {
+    let module = monomorphize(module)?;
+    Emitter::new(&module).emit_module()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::*;
+    use elp_ir::mir::{FieldDef, ObjectDef, Param, Span};
+    use pretty_assertions::assert_eq;
+    use std::{io::Write, process::Command};
+
+    #[test]
+    fn emits_functions_and_externals() {
+        let module = lower_source(
+            "fn puts(text string) -> int32
+
+            fn main {
+                puts(\"hello\")
+            }",
+        );
+
+        assert_eq!(
+            emit_module(&module).unwrap(),
+            r#"; ModuleID = 'test'
+source_filename = "test.elp"
+
+@.str.0 = private unnamed_addr constant [6 x i8] c"hello\00"
+
+define i32 @main() {
+entry:
+  %t1 = call i32 @puts(ptr @.str.0)
+  ret i32 0
+}
+
+declare i32 @puts(ptr)
+"#
+        );
+    }
+
+    #[test]
+    fn externals_are_called_the_way_their_abi_says() {
+        let module = lower_source(
+            "external \"stdcall\" fn MessageBeep(kind uint32) -> bool
+
+            fn main {
+                MessageBeep(0)
+            }",
+        );
+
+        let ir = emit_module(&module).unwrap();
+        assert!(
+            ir.contains("= call x86_stdcallcc i1 @MessageBeep(i32 0)\n"),
+            "{}",
+            ir
+        );
+        assert!(
+            ir.contains("declare x86_stdcallcc i1 @MessageBeep(i32)\n"),
+            "{}",
+            ir
+        );
+    }
+
+    #[test]
+    fn objects_are_structs_behind_pointers() {
+        let module = Module {
+            objects: vec![ObjectDef {
+                span: Span::default(),
+                name: "Point".into(),
+                generics: vec![],
+                fields: vec![
+                    FieldDef {
+                        span: Span::default(),
+                        name: "x".into(),
+                        ty: int32(),
+                        default: None,
+                        public: true,
+                        tags: vec![],
+                    },
+                    FieldDef {
+                        span: Span::default(),
+                        name: "y".into(),
+                        ty: int32(),
+                        default: Some(Expr::Literal(Literal::Int(2))),
+                        public: true,
+                        tags: vec![],
+                    },
+                ],
+                implements: vec![],
+                exported: false,
+            }],
+            functions: vec![
+                Function {
+                    receiver: Some("Point".into()),
+                    params: vec![Param {
+                        name: "self".into(),
+                        ty: Type::Reference(Box::new(named("Point"))),
+                    }],
+                    ret: int32(),
+                    ..function(
+                        "sum",
+                        vec![ret(binary(
+                            BinaryOp::Add,
+                            field(local("self"), "x"),
+                            field(local("self"), "y"),
+                        ))],
+                    )
+                },
+                Function {
+                    ret: int32(),
+                    ..function(
+                        "main",
+                        vec![
+                            let_(
+                                "point",
+                                Expr::Object {
+                                    name: "Point".into(),
+                                    fields: vec![("x".into(), Expr::Literal(Literal::Int(40)))],
+                                },
+                            ),
+                            ret(Expr::MethodCall {
+                                receiver: Box::new(local("point")),
+                                method: "sum".into(),
+                                args: vec![],
+                            }),
+                        ],
+                    )
+                },
+            ],
+            ..Default::default()
+        };
+
+        let ir = emit_module(&module).unwrap();
+        assert!(ir.contains("%Point = type { i32, i32 }\n"));
+        assert!(ir.contains("define internal i32 @Point.sum(ptr %self.arg) {"));
+        assert!(ir.contains("getelementptr inbounds %Point, ptr %t3, i32 0, i32 1"));
+        assert!(ir.contains("getelementptr %Point, ptr null, i32 1"));
+        assert!(ir.contains("call i32 @Point.sum(ptr %t"));
+        assert_runs(&ir, 42, "");
+    }
+
+    #[test]
+    fn enums_are_tagged_unions() {
+        let mut module = shapes();
+        module.functions.push(Function {
+            ret: int32(),
+            ..function(
+                "main",
+                vec![ret(Expr::Call {
+                    callee: Box::new(Expr::Global("area".into())),
+                    args: vec![Expr::Variant {
+                        enum_name: None,
+                        variant: "RECT".into(),
+                        args: vec![
+                            Expr::Literal(Literal::Int(6)),
+                            Expr::Literal(Literal::Int(7)),
+                        ],
+                    }],
+                })],
+            )
+        });
+
+        let ir = emit_module(&module).unwrap();
+        assert!(ir.contains("%Shape = type { i32, [2 x i64] }\n"));
+        assert!(ir.contains("%Shape.SQUARE = type { i32 }\n"));
+        assert!(ir.contains("%Shape.RECT = type { i32, i32 }\n"));
+        assert!(
+            ir.contains("switch i32 %t5, label %arm4 [ i32 0, label %arm2 i32 1, label %arm3 ]")
+        );
+        assert_runs(&ir, 42, "");
+    }
+
+    #[test]
+    fn arrays_and_printing() {
+        let module = Module {
+            functions: vec![function(
+                "main",
+                vec![
+                    let_(
+                        "names",
+                        Expr::Array(vec![
+                            Expr::Literal(Literal::String("a".into())),
+                            Expr::Literal(Literal::String("b".into())),
+                        ]),
+                    ),
+                    stmt(StmtKind::Expr(Expr::MethodCall {
+                        receiver: Box::new(local("names")),
+                        method: "append".into(),
+                        args: vec![Expr::Literal(Literal::String("c".into()))],
+                    })),
+                    stmt(StmtKind::For {
+                        binding: "name".into(),
+                        iterable: local("names"),
+                        body: vec![stmt(StmtKind::Expr(Expr::Call {
+                            callee: Box::new(Expr::Global("println".into())),
+                            args: vec![binary(
+                                BinaryOp::Add,
+                                local("name"),
+                                Expr::Literal(Literal::String("!".into())),
+                            )],
+                        }))],
+                    }),
+                    stmt(StmtKind::Expr(Expr::Call {
+                        callee: Box::new(Expr::Global("println".into())),
+                        args: vec![Expr::MethodCall {
+                            receiver: Box::new(local("names")),
+                            method: "len".into(),
+                            args: vec![],
+                        }],
+                    })),
+                ],
+            )],
+            ..Default::default()
+        };
+
+        let ir = emit_module(&module).unwrap();
+        assert!(ir.contains("%elp.array = type { ptr, i64, i64 }"));
+        assert!(ir.contains("call ptr @elp.array.push(ptr"));
+        assert_runs(&ir, 0, "a!\nb!\nc!\n3\n");
+    }
+
+    #[test]
+    fn untyped_parameters_are_an_error() {
+        let module = lower_source(
+            "fn main {
+                fn_with_untyped(1)
+            }
+            fn fn_with_untyped(value) {
+                return
+            }",
+        );
+
+        let err = emit_module(&module).unwrap_err();
+        assert_eq!(
+            err.message,
+            "native code needs to know this type, add a type annotation"
+        );
+        assert_eq!(err.span.line, 4);
+    }
+
+    // Runs the module with `lli` and checks its exit code and output. Skipped when LLVM isn't
+    // installed so the tests still pass without it.
+    fn assert_runs(ir: &str, code: i32, stdout: impl Into<String>) {
+        let path =
+            std::env::temp_dir().join(format!("elp-llvm-{}-{}.ll", std::process::id(), ir.len()));
+        std::fs::File::create(&path)
+            .unwrap()
+            .write_all(ir.as_bytes())
+            .unwrap();
+
+        // LLVM 14 only understands `ptr` with opaque pointers turned on, later versions always do.
+        let mut result = None;
+        for flags in [&[][..], &["-opaque-pointers"][..]] {
+            let Ok(output) = Command::new("lli").args(flags).arg(&path).output() else {
+                break;
+            };
+            result = Some((
+                output.status.code(),
+                String::from_utf8_lossy(&output.stdout).to_string(),
+                String::from_utf8_lossy(&output.stderr).to_string(),
+            ));
+            if output.stderr.is_empty() {
+                break;
+            }
+        }
+        let _ = std::fs::remove_file(&path);
+
+        if let Some(result) = result {
+            assert_eq!(result, (Some(code), stdout.into(), String::new()));
+        }
+    }
+}
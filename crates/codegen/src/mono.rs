@@ -0,0 +1,493 @@
+// Native backends can't call a function whose parameter types aren't known, so every generic
+// function is copied once per set of type arguments it's called with and the calls are pointed at
+// the copies. Generic functions that are never called don't make it into the output at all.
+
+use elp_ir::mir::{Closure, Expr, Function, Module, Param, Stmt, StmtKind, Type};
+
+use crate::typing::{substitute, CodegenResult, TypeContext};
+
+// The suffix a specialisation gets for a type argument, `max<int32>` becomes `max__int32`.
+fn type_suffix(ty: &Type) -> String {
+    match ty {
+        Type::Unknown => "unknown".into(),
+        Type::Void => "void".into(),
+        Type::Bool => "bool".into(),
+        Type::Int { bits, signed } => {
+            format!("{}int{}", if *signed { "" } else { "u" }, bits)
+        }
+        Type::Float { bits } => format!("float{}", bits),
+        Type::String => "string".into(),
+        Type::Array(inner) => format!("Array_{}", type_suffix(inner)),
+        Type::Pointer(inner) => format!("ptr_{}", type_suffix(inner)),
+        Type::Reference(inner) => format!("ref_{}", type_suffix(inner)),
+        Type::Named { name, generics } => std::iter::once(name.clone())
+            .chain(generics.iter().map(type_suffix))
+            .collect::<Vec<_>>()
+            .join("_"),
+        Type::Function { .. } => "fn".into(),
+    }
+}
+
+struct Instance {
+    generic: String,
+    types: Vec<Type>,
+    name: String,
+}
+
+struct Monomorphizer<'m> {
+    ctx: TypeContext<'m>,
+    instances: Vec<Instance>,
+    pending: Vec<usize>,
+}
+
+impl<'m> Monomorphizer<'m> {
+    // The qualified name of the specialisation, queuing it up if it's new.
+    fn instance(&mut self, generic: &str, types: Vec<Type>) -> String {
+        if let Some(instance) = self
+            .instances
+            .iter()
+            .find(|instance| instance.generic == generic && instance.types == types)
+        {
+            return instance.name.clone();
+        }
+
+        let name = format!(
+            "{}__{}",
+            generic,
+            types.iter().map(type_suffix).collect::<Vec<_>>().join("_")
+        );
+        self.instances.push(Instance {
+            generic: generic.into(),
+            types,
+            name: name.clone(),
+        });
+        self.pending.push(self.instances.len() - 1);
+
+        name
+    }
+
+    fn specialise(&self, instance: &Instance) -> Function {
+        let generic = self
+            .ctx
+            .module
+            .function(&instance.generic)
+            .expect("instances are only created for functions in the module");
+        let sub = |ty: &Type| substitute(ty, &generic.generics, &instance.types);
+
+        Function {
+            span: generic.span,
+            name: match instance.name.rsplit_once('.') {
+                Some((_, name)) => name.into(),
+                None => instance.name.clone(),
+            },
+            receiver: generic.receiver.clone(),
+            generics: vec![],
+            params: generic
+                .params
+                .iter()
+                .map(|param| Param {
+                    name: param.name.clone(),
+                    ty: sub(&param.ty),
+                })
+                .collect(),
+            ret: sub(&generic.ret),
+            body: generic
+                .body
+                .iter()
+                .map(|stmt| substitute_stmt(stmt, &sub))
+                .collect(),
+            exported: generic.exported,
+        }
+    }
+
+    fn function(&mut self, mut function: Function) -> CodegenResult<Function> {
+        self.ctx.span = function.span;
+        self.ctx.push_scope();
+        for param in &function.params {
+            self.ctx.declare(&param.name, param.ty.clone());
+        }
+        let result = self.stmts(&mut function.body);
+        self.ctx.pop_scope();
+        result?;
+
+        Ok(function)
+    }
+
+    fn stmts(&mut self, stmts: &mut [Stmt]) -> CodegenResult<()> {
+        self.ctx.push_scope();
+        let result = stmts.iter_mut().try_for_each(|stmt| self.stmt(stmt));
+        self.ctx.pop_scope();
+        result
+    }
+
+    // Types are always worked out before rewriting, the type context only knows the original
+    // generic functions and not their specialisations.
+    fn stmt(&mut self, stmt: &mut Stmt) -> CodegenResult<()> {
+        self.ctx.span = stmt.span;
+
+        match &mut stmt.kind {
+            StmtKind::Let {
+                name, ty, value, ..
+            } => {
+                let declared = match (&ty, &value) {
+                    (Type::Unknown, Some(value)) => self.ctx.type_of(value)?,
+                    _ => ty.clone(),
+                };
+                if let Some(value) = value {
+                    self.expr(value)?;
+                }
+                self.ctx.declare(name, declared);
+            }
+            StmtKind::Assign { target, value, .. } => {
+                self.expr(target)?;
+                self.expr(value)?;
+            }
+            StmtKind::Expr(expr) | StmtKind::Return(Some(expr)) => self.expr(expr)?,
+            StmtKind::Return(None) => {}
+            StmtKind::If {
+                condition,
+                then_body,
+                else_body,
+            } => {
+                self.expr(condition)?;
+                self.stmts(then_body)?;
+                self.stmts(else_body)?;
+            }
+            StmtKind::For {
+                binding,
+                iterable,
+                body,
+            } => {
+                let item = match self.ctx.type_of(iterable)? {
+                    Type::Array(item) => *item,
+                    _ => Type::Unknown,
+                };
+                self.expr(iterable)?;
+                self.ctx.push_scope();
+                self.ctx.declare(binding, item);
+                let result = self.stmts(body);
+                self.ctx.pop_scope();
+                result?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn expr(&mut self, expr: &mut Expr) -> CodegenResult<()> {
+        match expr {
+            Expr::Call { callee, args } => {
+                let instance = match &**callee {
+                    Expr::Global(name) if self.ctx.is_generic(name) => {
+                        let generic = self.ctx.module.function(name).unwrap();
+                        Some((name.clone(), self.ctx.instantiate(generic, args)?))
+                    }
+                    _ => None,
+                };
+                for arg in args.iter_mut() {
+                    self.expr(arg)?;
+                }
+                match instance {
+                    Some((name, types)) => **callee = Expr::Global(self.instance(&name, types)),
+                    None => self.expr(callee)?,
+                }
+            }
+            Expr::Match { scrutinee, arms } => {
+                let subject = self.ctx.type_of(scrutinee)?;
+                self.expr(scrutinee)?;
+                for arm in arms {
+                    self.ctx.push_scope();
+                    let result =
+                        self.ctx
+                            .pattern_bindings(&subject, &arm.pattern)
+                            .and_then(|bindings| {
+                                for (name, ty) in bindings {
+                                    self.ctx.declare(&name, ty);
+                                }
+                                self.expr(&mut arm.body)
+                            });
+                    self.ctx.pop_scope();
+                    result?;
+                }
+            }
+            Expr::Closure(closure) => {
+                self.ctx.push_scope();
+                for param in &closure.params {
+                    self.ctx.declare(&param.name, param.ty.clone());
+                }
+                let result = self.stmts(&mut closure.body);
+                self.ctx.pop_scope();
+                result?;
+            }
+            Expr::Field { base, .. } => self.expr(base)?,
+            Expr::MethodCall { receiver, args, .. } => {
+                self.expr(receiver)?;
+                for arg in args {
+                    self.expr(arg)?;
+                }
+            }
+            Expr::Binary { lhs, rhs, .. } => {
+                self.expr(lhs)?;
+                self.expr(rhs)?;
+            }
+            Expr::Unary { operand, .. } => self.expr(operand)?,
+            Expr::Object { fields, .. } => {
+                for (_, value) in fields {
+                    self.expr(value)?;
+                }
+            }
+            Expr::Variant { args: items, .. } | Expr::Array(items) => {
+                for item in items {
+                    self.expr(item)?;
+                }
+            }
+            Expr::Element(element) => {
+                for arg in &mut element.args {
+                    self.expr(arg)?;
+                }
+                for (_, value) in &mut element.props {
+                    self.expr(value)?;
+                }
+                self.stmts(&mut element.children)?;
+            }
+            Expr::Literal(_) | Expr::Local(_) | Expr::Global(_) => {}
+        }
+
+        Ok(())
+    }
+}
+
+fn substitute_stmt(stmt: &Stmt, sub: &impl Fn(&Type) -> Type) -> Stmt {
+    let kind = match &stmt.kind {
+        StmtKind::Let {
+            name,
+            mutable,
+            ty,
+            value,
+        } => StmtKind::Let {
+            name: name.clone(),
+            mutable: *mutable,
+            ty: sub(ty),
+            value: value.as_ref().map(|value| substitute_expr(value, sub)),
+        },
+        StmtKind::Assign { target, op, value } => StmtKind::Assign {
+            target: substitute_expr(target, sub),
+            op: *op,
+            value: substitute_expr(value, sub),
+        },
+        StmtKind::Expr(expr) => StmtKind::Expr(substitute_expr(expr, sub)),
+        StmtKind::Return(value) => {
+            StmtKind::Return(value.as_ref().map(|value| substitute_expr(value, sub)))
+        }
+        StmtKind::If {
+            condition,
+            then_body,
+            else_body,
+        } => StmtKind::If {
+            condition: substitute_expr(condition, sub),
+            then_body: then_body.iter().map(|s| substitute_stmt(s, sub)).collect(),
+            else_body: else_body.iter().map(|s| substitute_stmt(s, sub)).collect(),
+        },
+        StmtKind::For {
+            binding,
+            iterable,
+            body,
+        } => StmtKind::For {
+            binding: binding.clone(),
+            iterable: substitute_expr(iterable, sub),
+            body: body.iter().map(|s| substitute_stmt(s, sub)).collect(),
+        },
+    };
+
+    Stmt {
+        span: stmt.span,
+        kind,
+    }
+}
+
+// Only closures carry types inside expressions.
+fn substitute_expr(expr: &Expr, sub: &impl Fn(&Type) -> Type) -> Expr {
+    let mut expr = expr.clone();
+    if let Expr::Closure(closure) = &mut expr {
+        **closure = Closure {
+            params: closure
+                .params
+                .iter()
+                .map(|param| Param {
+                    name: param.name.clone(),
+                    ty: sub(&param.ty),
+                })
+                .collect(),
+            ret: sub(&closure.ret),
+            body: closure
+                .body
+                .iter()
+                .map(|s| substitute_stmt(s, sub))
+                .collect(),
+        };
+    }
+    expr
+}
+
+pub fn monomorphize(module: &Module) -> CodegenResult<Module> {
+    let mut mono = Monomorphizer {
+        ctx: TypeContext::new(module),
+        instances: vec![],
+        pending: vec![],
+    };
+
+    let mut functions = vec![];
+    for function in module.functions.iter().filter(|f| f.generics.is_empty()) {
+        functions.push(mono.function(function.clone())?);
+    }
+    // Specialisations can call other generic functions, keep going until nothing new turns up.
+    while let Some(index) = mono.pending.pop() {
+        let function = mono.specialise(&mono.instances[index]);
+        functions.push(mono.function(function)?);
+    }
+
+    Ok(Module {
+        functions,
+        ..module.clone()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::*;
+    use elp_ir::mir::{BinaryOp, Expr, Span};
+    use pretty_assertions::assert_eq;
+
+    fn generic(name: &str) -> Type {
+        named(name)
+    }
+
+    fn max() -> Function {
+        Function {
+            generics: vec!["T".into()],
+            params: vec![
+                Param {
+                    name: "a".into(),
+                    ty: generic("T"),
+                },
+                Param {
+                    name: "b".into(),
+                    ty: generic("T"),
+                },
+            ],
+            ret: generic("T"),
+            ..function(
+                "max",
+                vec![
+                    let_("bigger", binary(BinaryOp::Gt, local("a"), local("b"))),
+                    ret(local("a")),
+                ],
+            )
+        }
+    }
+
+    #[test]
+    fn specialises_generic_functions_per_type() {
+        let module = Module {
+            functions: vec![
+                max(),
+                Function {
+                    params: vec![Param {
+                        name: "x".into(),
+                        ty: int32(),
+                    }],
+                    ..function(
+                        "main",
+                        vec![
+                            let_(
+                                "a",
+                                Expr::Call {
+                                    callee: Box::new(Expr::Global("max".into())),
+                                    args: vec![local("x"), local("x")],
+                                },
+                            ),
+                            // Nested calls are worked out from the original generic signature.
+                            let_(
+                                "b",
+                                Expr::Call {
+                                    callee: Box::new(Expr::Global("max".into())),
+                                    args: vec![
+                                        Expr::Call {
+                                            callee: Box::new(Expr::Global("max".into())),
+                                            args: vec![
+                                                Expr::Literal(elp_ir::mir::Literal::Float(1.0)),
+                                                Expr::Literal(elp_ir::mir::Literal::Float(2.0)),
+                                            ],
+                                        },
+                                        Expr::Literal(elp_ir::mir::Literal::Float(3.0)),
+                                    ],
+                                },
+                            ),
+                        ],
+                    )
+                },
+            ],
+            ..Default::default()
+        };
+
+        let mono = monomorphize(&module).unwrap();
+        let names = mono
+            .functions
+            .iter()
+            .map(|f| f.qualified_name())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["main", "max__float64", "max__int32"]);
+
+        let int_max = mono.function("max__int32").unwrap();
+        assert!(int_max.generics.is_empty());
+        assert_eq!(int_max.params[0].ty, int32());
+        assert_eq!(int_max.ret, int32());
+
+        let StmtKind::Let {
+            value: Some(Expr::Call { callee, args }),
+            ..
+        } = &mono.function("main").unwrap().body[1].kind
+        else {
+            panic!("expected a call");
+        };
+        assert_eq!(**callee, Expr::Global("max__float64".into()));
+        assert!(matches!(
+            &args[0],
+            Expr::Call { callee, .. } if **callee == Expr::Global("max__float64".into())
+        ));
+    }
+
+    #[test]
+    fn unresolvable_type_parameters_are_an_error() {
+        let module = Module {
+            functions: vec![
+                Function {
+                    generics: vec!["T".into()],
+                    ret: generic("T"),
+                    ..function("make", vec![])
+                },
+                Function {
+                    span: Span {
+                        start: 0,
+                        end: 0,
+                        line: 3,
+                    },
+                    ..function(
+                        "main",
+                        vec![stmt(StmtKind::Expr(Expr::Call {
+                            callee: Box::new(Expr::Global("make".into())),
+                            args: vec![],
+                        }))],
+                    )
+                },
+            ],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            monomorphize(&module).unwrap_err().message,
+            "can't work out what `T` is in this call to `make`"
+        );
+    }
+}
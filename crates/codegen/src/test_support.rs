@@ -0,0 +1,162 @@
+// Shorthands for building MIR by hand in backend tests, most expressions can't be written in
+// source yet.
+
+use elp_ir::{
+    lower::lower_module,
+    mir::{
+        BinaryOp, EnumDef, Expr, Function, Literal, MatchArm, Module, Param, Pattern, Span, Stmt,
+        StmtKind, Type, VariantDef,
+    },
+};
+use elp_parser::{
+    ast::{module::ASTModule, traits::FromCST},
+    cst::CSTModule,
+    parser::{ElpParser, Rule},
+};
+use from_pest::FromPest;
+use pest::Parser;
+
+pub fn lower_source(source: &str) -> Module {
+    let mut pairs = ElpParser::parse(Rule::module, source).unwrap();
+    let cst = CSTModule::from_pest(&mut pairs).unwrap();
+    let ast = ASTModule::from_cst(&cst);
+
+    lower_module("test", &ast).unwrap()
+}
+
+pub fn int32() -> Type {
+    Type::Int {
+        bits: 32,
+        signed: true,
+    }
+}
+
+pub fn named(name: &str) -> Type {
+    Type::Named {
+        name: name.into(),
+        generics: vec![],
+    }
+}
+
+pub fn stmt(kind: StmtKind) -> Stmt {
+    Stmt {
+        span: Span {
+            start: 0,
+            end: 0,
+            line: 1,
+        },
+        kind,
+    }
+}
+
+pub fn let_(name: &str, value: Expr) -> Stmt {
+    stmt(StmtKind::Let {
+        name: name.into(),
+        mutable: true,
+        ty: Type::Unknown,
+        value: Some(value),
+    })
+}
+
+pub fn ret(value: Expr) -> Stmt {
+    stmt(StmtKind::Return(Some(value)))
+}
+
+pub fn local(name: &str) -> Expr {
+    Expr::Local(name.into())
+}
+
+pub fn int(value: i64) -> Expr {
+    Expr::Literal(Literal::Int(value))
+}
+
+pub fn field(base: Expr, name: &str) -> Expr {
+    Expr::Field {
+        base: Box::new(base),
+        name: name.into(),
+    }
+}
+
+pub fn binary(op: BinaryOp, lhs: Expr, rhs: Expr) -> Expr {
+    Expr::Binary {
+        op,
+        lhs: Box::new(lhs),
+        rhs: Box::new(rhs),
+    }
+}
+
+pub fn function(name: &str, body: Vec<Stmt>) -> Function {
+    Function {
+        span: Span::default(),
+        name: name.into(),
+        receiver: None,
+        generics: vec![],
+        params: vec![],
+        ret: Type::Void,
+        body,
+        exported: false,
+    }
+}
+
+// `enum Shape { .SQUARE(int32), .RECT(int32, int32), .EMPTY }` with an `area` function matching
+// on it.
+pub fn shapes() -> Module {
+    Module {
+        name: "test".into(),
+        enums: vec![EnumDef {
+            span: Span::default(),
+            name: "Shape".into(),
+            variants: vec![
+                VariantDef {
+                    name: "SQUARE".into(),
+                    payload: vec![int32()],
+                },
+                VariantDef {
+                    name: "RECT".into(),
+                    payload: vec![int32(), int32()],
+                },
+                VariantDef {
+                    name: "EMPTY".into(),
+                    payload: vec![],
+                },
+            ],
+            implements: vec![],
+            exported: true,
+        }],
+        functions: vec![Function {
+            params: vec![Param {
+                name: "shape".into(),
+                ty: named("Shape"),
+            }],
+            ret: int32(),
+            exported: true,
+            ..function(
+                "area",
+                vec![ret(Expr::Match {
+                    scrutinee: Box::new(local("shape")),
+                    arms: vec![
+                        MatchArm {
+                            pattern: Pattern::Variant {
+                                name: "SQUARE".into(),
+                                bindings: vec!["side".into()],
+                            },
+                            body: binary(BinaryOp::Mul, local("side"), local("side")),
+                        },
+                        MatchArm {
+                            pattern: Pattern::Variant {
+                                name: "RECT".into(),
+                                bindings: vec!["w".into(), "h".into()],
+                            },
+                            body: binary(BinaryOp::Mul, local("w"), local("h")),
+                        },
+                        MatchArm {
+                            pattern: Pattern::Wildcard,
+                            body: int(0),
+                        },
+                    ],
+                })],
+            )
+        }],
+        ..Default::default()
+    }
+}
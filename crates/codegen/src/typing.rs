@@ -0,0 +1,440 @@
+// Native backends need to know the type of every value up front, the MIR only records the types
+// that were written down. This works out the rest from literals, declarations and signatures,
+// walking the same scopes the backends walk while they emit code.
+
+use std::fmt;
+
+use elp_ir::{
+    ffi::CallingConvention,
+    mir::{
+        BinaryOp, EnumDef, Expr, Function, Literal, Module, ObjectDef, Pattern, Span, Type, UnaryOp,
+    },
+};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct CodegenError {
+    pub span: Span,
+    pub message: String,
+}
+
+impl fmt::Display for CodegenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.span.line, self.message)
+    }
+}
+
+impl std::error::Error for CodegenError {}
+
+pub type CodegenResult<T> = Result<T, CodegenError>;
+
+pub const INT: Type = Type::Int {
+    bits: 64,
+    signed: true,
+};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Signature {
+    // The name code should call, methods are qualified with their receiver.
+    pub name: String,
+    pub params: Vec<Type>,
+    pub ret: Type,
+    pub external: bool,
+    pub convention: CallingConvention,
+}
+
+pub struct TypeContext<'m> {
+    pub module: &'m Module,
+    scopes: Vec<Vec<(String, Type)>>,
+    // Where errors are reported, backends move this along as they go through statements.
+    pub span: Span,
+}
+
+impl<'m> TypeContext<'m> {
+    pub fn new(module: &'m Module) -> Self {
+        Self {
+            module,
+            scopes: vec![],
+            span: Span::default(),
+        }
+    }
+
+    pub fn error<T>(&self, message: impl Into<String>) -> CodegenResult<T> {
+        Err(CodegenError {
+            span: self.span,
+            message: message.into(),
+        })
+    }
+
+    pub fn push_scope(&mut self) {
+        self.scopes.push(vec![]);
+    }
+
+    pub fn pop_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    pub fn declare(&mut self, name: &str, ty: Type) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.push((name.into(), ty));
+        }
+    }
+
+    pub fn local(&self, name: &str) -> Option<&Type> {
+        self.scopes
+            .iter()
+            .rev()
+            .flat_map(|scope| scope.iter().rev())
+            .find(|(local, _)| local == name)
+            .map(|(_, ty)| ty)
+    }
+
+    pub fn object_of(&self, ty: &Type) -> Option<&'m ObjectDef> {
+        self.module.object(ty.named()?)
+    }
+
+    pub fn enum_of(&self, ty: &Type) -> Option<&'m EnumDef> {
+        self.module.r#enum(ty.named()?)
+    }
+
+    // Finds the enum a variant belongs to and its tag. Contextual variants use the expected type
+    // when there is one and fall back to the only enum with a variant of that name.
+    pub fn variant(
+        &self,
+        enum_name: Option<&str>,
+        variant: &str,
+        expected: Option<&Type>,
+    ) -> CodegenResult<(&'m EnumDef, usize)> {
+        let r#enum = match enum_name {
+            Some(name) => self.module.r#enum(name),
+            None => expected
+                .and_then(|ty| self.enum_of(ty))
+                .filter(|r#enum| r#enum.variant_index(variant).is_some())
+                .or_else(|| self.module.enum_for_variant(variant)),
+        };
+        let Some(r#enum) = r#enum else {
+            return self.error(format!(
+                "can't work out which enum `.{}` belongs to",
+                variant
+            ));
+        };
+        match r#enum.variant_index(variant) {
+            Some(tag) => Ok((r#enum, tag)),
+            None => self.error(format!("`{}` has no variant `{}`", r#enum.name, variant)),
+        }
+    }
+
+    pub fn field(&self, base: &Type, name: &str) -> CodegenResult<(&'m ObjectDef, usize)> {
+        let Some(object) = self.object_of(base) else {
+            return self.error(format!(
+                "can't access `{}` on a value that isn't an object",
+                name
+            ));
+        };
+        match object.field_index(name) {
+            Some(index) => Ok((object, index)),
+            None => self.error(format!("`{}` has no field `{}`", object.name, name)),
+        }
+    }
+
+    // Functions, static functions and externals called by name.
+    pub fn signature(&self, name: &str) -> Option<Signature> {
+        if let Some(function) = self.module.function(name) {
+            return Some(Signature {
+                name: function.qualified_name(),
+                params: function.params.iter().map(|p| p.ty.clone()).collect(),
+                ret: function.ret.clone(),
+                external: false,
+                convention: CallingConvention::C,
+            });
+        }
+
+        self.module
+            .externals
+            .iter()
+            .find(|external| external.name == name)
+            .map(|external| Signature {
+                name: external.name.clone(),
+                params: external.params.iter().map(|p| p.ty.clone()).collect(),
+                ret: external.ret.clone(),
+                external: true,
+                convention: external.abi.calling_convention(),
+            })
+    }
+
+    pub fn is_generic(&self, name: &str) -> bool {
+        self.module
+            .function(name)
+            .is_some_and(|function| !function.generics.is_empty())
+    }
+
+    // Works out what each of a generic function's type parameters is from the arguments of a call.
+    pub fn instantiate(&self, function: &Function, args: &[Expr]) -> CodegenResult<Vec<Type>> {
+        let mut bindings = vec![None; function.generics.len()];
+        for (param, arg) in function.params.iter().zip(args) {
+            unify(
+                &param.ty,
+                &self.type_of(arg)?,
+                &function.generics,
+                &mut bindings,
+            );
+        }
+
+        bindings
+            .into_iter()
+            .zip(&function.generics)
+            .map(|(binding, generic)| match binding {
+                Some(ty) => Ok(ty),
+                None => self.error(format!(
+                    "can't work out what `{}` is in this call to `{}`",
+                    generic,
+                    function.qualified_name()
+                )),
+            })
+            .collect()
+    }
+
+    pub fn method(&self, receiver: &Type, method: &str) -> Option<Signature> {
+        self.signature(&format!("{}.{}", receiver.named()?, method))
+    }
+
+    // The names a match pattern binds and their types.
+    pub fn pattern_bindings(
+        &self,
+        scrutinee: &Type,
+        pattern: &Pattern,
+    ) -> CodegenResult<Vec<(String, Type)>> {
+        Ok(match pattern {
+            Pattern::Binding(name) => vec![(name.clone(), scrutinee.clone())],
+            Pattern::Variant { name, bindings } => {
+                let (r#enum, tag) = self.variant(None, name, Some(scrutinee))?;
+                let payload = &r#enum.variants[tag].payload;
+                if bindings.len() > payload.len() {
+                    return self.error(format!(
+                        "`{}` carries {} values but {} were bound",
+                        name,
+                        payload.len(),
+                        bindings.len()
+                    ));
+                }
+                bindings
+                    .iter()
+                    .cloned()
+                    .zip(payload.iter().cloned())
+                    .collect()
+            }
+            Pattern::Wildcard | Pattern::Literal(_) | Pattern::Range { .. } => vec![],
+        })
+    }
+
+    pub fn global(&self, name: &str) -> CodegenResult<Type> {
+        match self
+            .module
+            .globals
+            .iter()
+            .find(|global| global.name == name)
+        {
+            Some(global) if global.ty != Type::Unknown => Ok(global.ty.clone()),
+            Some(global) => self.type_of(&global.value),
+            None => self.error(format!("`{}` isn't defined", name)),
+        }
+    }
+
+    // The type an untyped literal takes on next to another operand, `x + 1` with `x int32` adds
+    // two int32s.
+    pub fn operand_type(&self, lhs: &Expr, rhs: &Expr) -> CodegenResult<Type> {
+        match (lhs, rhs) {
+            (Expr::Literal(Literal::Int(_)), other) if !matches!(other, Expr::Literal(_)) => {
+                self.type_of(other)
+            }
+            (Expr::Literal(Literal::Int(_)), Expr::Literal(Literal::Float(_))) => {
+                Ok(Type::Float { bits: 64 })
+            }
+            _ => self.type_of(lhs),
+        }
+    }
+
+    pub fn type_of(&self, expr: &Expr) -> CodegenResult<Type> {
+        Ok(match expr {
+            Expr::Literal(literal) => match literal {
+                Literal::Nil => Type::Pointer(Box::new(Type::Void)),
+                Literal::Bool(_) => Type::Bool,
+                Literal::Int(_) => INT,
+                Literal::Float(_) => Type::Float { bits: 64 },
+                Literal::String(_) => Type::String,
+            },
+            Expr::Local(name) => match self.local(name) {
+                Some(ty) => ty.clone(),
+                None => return self.error(format!("`{}` isn't defined", name)),
+            },
+            Expr::Global(name) => match self.signature(name) {
+                Some(signature) => Type::Function {
+                    params: signature.params,
+                    ret: Box::new(signature.ret),
+                },
+                None => self.global(name)?,
+            },
+            Expr::Field { base, name } => {
+                let (object, index) = self.field(&self.type_of(base)?, name)?;
+                object.fields[index].ty.clone()
+            }
+            Expr::Call { callee, args } => match &**callee {
+                Expr::Global(name) if is_builtin(name) && self.signature(name).is_none() => {
+                    Type::Void
+                }
+                Expr::Global(name) if self.is_generic(name) => {
+                    let function = self.module.function(name).expect("checked by is_generic");
+                    let types = self.instantiate(function, args)?;
+                    substitute(&function.ret, &function.generics, &types)
+                }
+                _ => match self.type_of(callee)? {
+                    Type::Function { ret, .. } => *ret,
+                    _ => {
+                        return self.error(format!(
+                            "can't call a value that isn't a function with {} arguments",
+                            args.len()
+                        ))
+                    }
+                },
+            },
+            Expr::MethodCall {
+                receiver, method, ..
+            } => {
+                let receiver = self.type_of(receiver)?;
+                match (&receiver, method.as_str()) {
+                    (Type::Array(_), "append" | "len" | "length") => INT,
+                    (Type::String, "len" | "length") => INT,
+                    _ => match self.method(&receiver, method) {
+                        Some(signature) => signature.ret,
+                        None => return self.error(format!("no method `{}` found", method)),
+                    },
+                }
+            }
+            Expr::Binary { op, lhs, rhs } => match op {
+                BinaryOp::Eq
+                | BinaryOp::Ne
+                | BinaryOp::Lt
+                | BinaryOp::Le
+                | BinaryOp::Gt
+                | BinaryOp::Ge
+                | BinaryOp::And
+                | BinaryOp::Or => Type::Bool,
+                _ => self.operand_type(lhs, rhs)?,
+            },
+            Expr::Unary { op, operand } => match op {
+                UnaryOp::Not => Type::Bool,
+                UnaryOp::Neg => self.type_of(operand)?,
+            },
+            Expr::Object { name, .. } => Type::Named {
+                name: name.clone(),
+                generics: vec![],
+            },
+            Expr::Variant {
+                enum_name, variant, ..
+            } => Type::Named {
+                name: self
+                    .variant(enum_name.as_deref(), variant, None)?
+                    .0
+                    .name
+                    .clone(),
+                generics: vec![],
+            },
+            Expr::Array(items) => match items.first() {
+                Some(first) => Type::Array(Box::new(self.type_of(first)?)),
+                None => return self.error("the type of an empty array literal can't be inferred"),
+            },
+            Expr::Element(element) => Type::Named {
+                name: element.name.clone(),
+                generics: vec![],
+            },
+            Expr::Closure(closure) => Type::Function {
+                params: closure.params.iter().map(|p| p.ty.clone()).collect(),
+                ret: Box::new(closure.ret.clone()),
+            },
+            Expr::Match { scrutinee, arms } => match arms.first() {
+                Some(arm) => {
+                    let scrutinee = self.type_of(scrutinee)?;
+                    let mut inner = TypeContext {
+                        module: self.module,
+                        scopes: self.scopes.clone(),
+                        span: self.span,
+                    };
+                    inner.push_scope();
+                    for (name, ty) in self.pattern_bindings(&scrutinee, &arm.pattern)? {
+                        inner.declare(&name, ty);
+                    }
+                    inner.type_of(&arm.body)?
+                }
+                None => Type::Void,
+            },
+        })
+    }
+}
+
+fn unify(param: &Type, arg: &Type, generics: &[String], bindings: &mut [Option<Type>]) {
+    match (param, arg) {
+        (
+            Type::Named {
+                name,
+                generics: inner,
+            },
+            _,
+        ) if inner.is_empty() && generics.contains(name) => {
+            let index = generics.iter().position(|g| g == name).unwrap();
+            bindings[index].get_or_insert_with(|| arg.clone());
+        }
+        (Type::Array(param), Type::Array(arg))
+        | (Type::Pointer(param), Type::Pointer(arg))
+        | (Type::Reference(param), Type::Reference(arg)) => unify(param, arg, generics, bindings),
+        // `&T` accepts a plain value, objects are always passed by reference anyway.
+        (Type::Reference(param), arg) => unify(param, arg, generics, bindings),
+        (
+            Type::Named {
+                generics: params, ..
+            },
+            Type::Named { generics: args, .. },
+        ) => {
+            for (param, arg) in params.iter().zip(args) {
+                unify(param, arg, generics, bindings);
+            }
+        }
+        _ => {}
+    }
+}
+
+// Replaces a generic function's type parameters with concrete types.
+pub fn substitute(ty: &Type, generics: &[String], types: &[Type]) -> Type {
+    match ty {
+        Type::Named {
+            name,
+            generics: inner,
+        } if inner.is_empty() && generics.contains(name) => {
+            types[generics.iter().position(|g| g == name).unwrap()].clone()
+        }
+        Type::Named {
+            name,
+            generics: inner,
+        } => Type::Named {
+            name: name.clone(),
+            generics: inner
+                .iter()
+                .map(|ty| substitute(ty, generics, types))
+                .collect(),
+        },
+        Type::Array(inner) => Type::Array(Box::new(substitute(inner, generics, types))),
+        Type::Pointer(inner) => Type::Pointer(Box::new(substitute(inner, generics, types))),
+        Type::Reference(inner) => Type::Reference(Box::new(substitute(inner, generics, types))),
+        Type::Function { params, ret } => Type::Function {
+            params: params
+                .iter()
+                .map(|ty| substitute(ty, generics, types))
+                .collect(),
+            ret: Box::new(substitute(ret, generics, types)),
+        },
+        ty => ty.clone(),
+    }
+}
+
+// Functions every backend provides itself rather than through the module.
+pub fn is_builtin(name: &str) -> bool {
+    matches!(name, "println" | "print")
+}
@@ -0,0 +1,2330 @@
+// Lowers the MIR to a WebAssembly module, written as text (`.wat`) and assembled into the binary
+// format (`.wasm`). Everything that isn't a number lives in linear memory behind an `i32` pointer:
+// objects and enums are heap blocks, arrays point at a small header and strings are NUL
+// terminated like they are in the native backends. Printing goes through WASI so a module with a
+// `main` runs in any WASI runtime, `external` functions are imported from `env`.
+
+use std::fmt::Write;
+
+use elp_ir::mir::{
+    BinaryOp, EnumDef, Expr, Function, Literal, MatchArm, Module, ObjectDef, Pattern, Span, Stmt,
+    StmtKind, Type, UnaryOp,
+};
+
+use crate::{
+    mono::monomorphize,
+    typing::{is_builtin, CodegenError, CodegenResult, TypeContext, INT},
+};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct WasmOutput {
+    // The text format, mostly for reading and golden tests.
+    pub wat: String,
+    // The same module assembled into the binary format.
+    pub wasm: Vec<u8>,
+}
+
+// The first 64 bytes of memory are scratch space for WASI calls and formatting numbers, constant
+// data starts after them.
+const DATA_START: u32 = 64;
+
+// Names in the text format can use these besides letters and digits, anything else is quoted.
+fn symbol(name: &str) -> String {
+    if !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "!#$%&'*+-./:<=>?@\\^_`|~".contains(c))
+    {
+        format!("${}", name)
+    } else {
+        format!("$\"{}\"", name)
+    }
+}
+
+fn escape(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|byte| match byte {
+            b' '..=b'~' if *byte != b'"' && *byte != b'\\' => (*byte as char).to_string(),
+            _ => format!("\\{:02x}", byte),
+        })
+        .collect()
+}
+
+fn align(offset: u32, to: u32) -> u32 {
+    offset.div_ceil(to) * to
+}
+
+fn float_constant(value: f64) -> String {
+    if value.is_nan() {
+        "nan".into()
+    } else if value.is_infinite() {
+        if value > 0.0 { "inf" } else { "-inf" }.into()
+    } else {
+        format!("{:?}", value)
+    }
+}
+
+// Imports, helper functions and the functions they need. Helpers are only emitted when used.
+struct Helper {
+    name: &'static str,
+    needs: &'static [&'static str],
+    code: &'static str,
+}
+
+const HELPERS: &[Helper] = &[
+    Helper {
+        name: "wasi.fd_write",
+        needs: &[],
+        code: "(import \"wasi_snapshot_preview1\" \"fd_write\" (func $wasi.fd_write (param i32 i32 i32 i32) (result i32)))",
+    },
+    Helper {
+        name: "wasi.proc_exit",
+        needs: &[],
+        code: "(import \"wasi_snapshot_preview1\" \"proc_exit\" (func $wasi.proc_exit (param i32)))",
+    },
+    Helper {
+        name: "elp.alloc",
+        needs: &[],
+        code: "(func $elp.alloc (export \"elp_alloc\") (param $size i32) (result i32)
+    (local $pointer i32)
+    global.get $elp.heap
+    local.set $pointer
+    ;; Sizes are rounded up to 8 so every allocation is aligned for any value.
+    global.get $elp.heap
+    local.get $size
+    i32.const 7
+    i32.add
+    i32.const -8
+    i32.and
+    i32.add
+    global.set $elp.heap
+    block $fits
+      global.get $elp.heap
+      memory.size
+      i32.const 16
+      i32.shl
+      i32.le_u
+      br_if $fits
+      global.get $elp.heap
+      memory.size
+      i32.const 16
+      i32.shl
+      i32.sub
+      i32.const 65535
+      i32.add
+      i32.const 16
+      i32.shr_u
+      memory.grow
+      i32.const -1
+      i32.ne
+      br_if $fits
+      unreachable
+    end
+    local.get $pointer
+  )",
+    },
+    Helper {
+        name: "elp.string.len",
+        needs: &[],
+        code: "(func $elp.string.len (param $string i32) (result i32)
+    (local $end i32)
+    local.get $string
+    local.set $end
+    block $done
+      loop $next
+        local.get $end
+        i32.load8_u
+        i32.eqz
+        br_if $done
+        local.get $end
+        i32.const 1
+        i32.add
+        local.set $end
+        br $next
+      end
+    end
+    local.get $end
+    local.get $string
+    i32.sub
+  )",
+    },
+    Helper {
+        name: "elp.string.concat",
+        needs: &["elp.alloc", "elp.string.len"],
+        code: "(func $elp.string.concat (param $a i32) (param $b i32) (result i32)
+    (local $a.len i32)
+    (local $b.len i32)
+    (local $out i32)
+    local.get $a
+    call $elp.string.len
+    local.set $a.len
+    local.get $b
+    call $elp.string.len
+    local.set $b.len
+    local.get $a.len
+    local.get $b.len
+    i32.add
+    i32.const 1
+    i32.add
+    call $elp.alloc
+    local.set $out
+    local.get $out
+    local.get $a
+    local.get $a.len
+    memory.copy
+    local.get $out
+    local.get $a.len
+    i32.add
+    local.get $b
+    local.get $b.len
+    memory.copy
+    local.get $out
+    local.get $a.len
+    i32.add
+    local.get $b.len
+    i32.add
+    i32.const 0
+    i32.store8
+    local.get $out
+  )",
+    },
+    Helper {
+        name: "elp.string.compare",
+        needs: &[],
+        code: ";; -1, 0 or 1 like `strcmp`.
+  (func $elp.string.compare (param $a i32) (param $b i32) (result i32)
+    (local $x i32)
+    (local $y i32)
+    loop $next
+      local.get $a
+      i32.load8_u
+      local.set $x
+      local.get $b
+      i32.load8_u
+      local.set $y
+      local.get $x
+      local.get $y
+      i32.ne
+      if
+        i32.const -1
+        i32.const 1
+        local.get $x
+        local.get $y
+        i32.lt_u
+        select
+        return
+      end
+      local.get $x
+      i32.eqz
+      if
+        i32.const 0
+        return
+      end
+      local.get $a
+      i32.const 1
+      i32.add
+      local.set $a
+      local.get $b
+      i32.const 1
+      i32.add
+      local.set $b
+      br $next
+    end
+    unreachable
+  )",
+    },
+    Helper {
+        name: "elp.array.new",
+        needs: &["elp.alloc"],
+        code: ";; Arrays are a header of a pointer to the items, the length and the capacity.
+  (func $elp.array.new (param $size i32) (param $len i32) (result i32)
+    (local $array i32)
+    i32.const 12
+    call $elp.alloc
+    local.set $array
+    local.get $array
+    local.get $size
+    local.get $len
+    i32.mul
+    call $elp.alloc
+    i32.store
+    local.get $array
+    local.get $len
+    i32.store offset=4
+    local.get $array
+    local.get $len
+    i32.store offset=8
+    local.get $array
+  )",
+    },
+    Helper {
+        name: "elp.array.push",
+        needs: &["elp.alloc"],
+        code: ";; Makes room for one more item and returns where it goes.
+  (func $elp.array.push (param $array i32) (param $size i32) (result i32)
+    (local $len i32)
+    (local $cap i32)
+    (local $data i32)
+    local.get $array
+    i32.load offset=4
+    local.set $len
+    local.get $array
+    i32.load offset=8
+    local.set $cap
+    local.get $len
+    local.get $cap
+    i32.eq
+    if
+      local.get $cap
+      i32.const 1
+      i32.shl
+      local.set $cap
+      local.get $cap
+      i32.const 4
+      local.get $cap
+      i32.const 4
+      i32.gt_u
+      select
+      local.set $cap
+      local.get $cap
+      local.get $size
+      i32.mul
+      call $elp.alloc
+      local.set $data
+      local.get $data
+      local.get $array
+      i32.load
+      local.get $len
+      local.get $size
+      i32.mul
+      memory.copy
+      local.get $array
+      local.get $data
+      i32.store
+      local.get $array
+      local.get $cap
+      i32.store offset=8
+    end
+    local.get $array
+    local.get $len
+    i32.const 1
+    i32.add
+    i32.store offset=4
+    local.get $array
+    i32.load
+    local.get $len
+    local.get $size
+    i32.mul
+    i32.add
+  )",
+    },
+    Helper {
+        name: "elp.pow.i64",
+        needs: &[],
+        code: ";; Squares and multiplies, negative exponents round down to 0.
+  (func $elp.pow.i64 (param $base i64) (param $exponent i64) (result i64)
+    (local $result i64)
+    i64.const 1
+    local.set $result
+    local.get $exponent
+    i64.const 0
+    i64.lt_s
+    if
+      i64.const 0
+      return
+    end
+    block $done
+      loop $next
+        local.get $exponent
+        i64.eqz
+        br_if $done
+        local.get $exponent
+        i64.const 1
+        i64.and
+        i64.const 1
+        i64.eq
+        if
+          local.get $result
+          local.get $base
+          i64.mul
+          local.set $result
+        end
+        local.get $base
+        local.get $base
+        i64.mul
+        local.set $base
+        local.get $exponent
+        i64.const 1
+        i64.shr_u
+        local.set $exponent
+        br $next
+      end
+    end
+    local.get $result
+  )",
+    },
+    Helper {
+        name: "elp.pow.f64",
+        needs: &["elp.pow.i64"],
+        code: ";; There's no libm, only whole exponents are supported and anything else is NaN.
+  (func $elp.pow.f64 (param $base f64) (param $exponent f64) (result f64)
+    (local $result f64)
+    (local $whole i64)
+    local.get $exponent
+    f64.trunc
+    local.get $exponent
+    f64.ne
+    if
+      f64.const nan
+      return
+    end
+    f64.const 1
+    local.set $result
+    local.get $exponent
+    f64.abs
+    i64.trunc_sat_f64_u
+    local.set $whole
+    block $done
+      loop $next
+        local.get $whole
+        i64.eqz
+        br_if $done
+        local.get $whole
+        i64.const 1
+        i64.and
+        i64.const 1
+        i64.eq
+        if
+          local.get $result
+          local.get $base
+          f64.mul
+          local.set $result
+        end
+        local.get $base
+        local.get $base
+        f64.mul
+        local.set $base
+        local.get $whole
+        i64.const 1
+        i64.shr_u
+        local.set $whole
+        br $next
+      end
+    end
+    local.get $exponent
+    f64.const 0
+    f64.lt
+    if
+      f64.const 1
+      local.get $result
+      f64.div
+      return
+    end
+    local.get $result
+  )",
+    },
+    Helper {
+        name: "elp.rem.f64",
+        needs: &[],
+        code: "(func $elp.rem.f64 (param $a f64) (param $b f64) (result f64)
+    local.get $a
+    local.get $a
+    local.get $b
+    f64.div
+    f64.trunc
+    local.get $b
+    f64.mul
+    f64.sub
+  )",
+    },
+    Helper {
+        name: "elp.write",
+        needs: &["wasi.fd_write"],
+        code: ";; Writes bytes to stdout, the iovec lives at 0 and the count written at 8.
+  (func $elp.write (param $pointer i32) (param $len i32)
+    i32.const 0
+    local.get $pointer
+    i32.store
+    i32.const 4
+    local.get $len
+    i32.store
+    i32.const 1
+    i32.const 0
+    i32.const 1
+    i32.const 8
+    call $wasi.fd_write
+    drop
+  )",
+    },
+    Helper {
+        name: "elp.print.char",
+        needs: &["elp.write"],
+        code: "(func $elp.print.char (param $char i32)
+    i32.const 63
+    local.get $char
+    i32.store8
+    i32.const 63
+    i32.const 1
+    call $elp.write
+  )",
+    },
+    Helper {
+        name: "elp.print.str",
+        needs: &["elp.write", "elp.string.len"],
+        code: "(func $elp.print.str (param $string i32)
+    local.get $string
+    local.get $string
+    call $elp.string.len
+    call $elp.write
+  )",
+    },
+    Helper {
+        name: "elp.digits",
+        needs: &[],
+        code: ";; Writes the digits backwards from the end of the scratch space and returns where they start.
+  (func $elp.digits (param $value i64) (result i32)
+    (local $at i32)
+    i32.const 64
+    local.set $at
+    loop $digit
+      local.get $at
+      i32.const 1
+      i32.sub
+      local.tee $at
+      local.get $value
+      i64.const 10
+      i64.rem_u
+      i32.wrap_i64
+      i32.const 48
+      i32.add
+      i32.store8
+      local.get $value
+      i64.const 10
+      i64.div_u
+      local.tee $value
+      i64.const 0
+      i64.ne
+      br_if $digit
+    end
+    local.get $at
+  )",
+    },
+    Helper {
+        name: "elp.print.u64",
+        needs: &["elp.write", "elp.digits"],
+        code: "(func $elp.print.u64 (param $value i64)
+    (local $at i32)
+    local.get $value
+    call $elp.digits
+    local.tee $at
+    i32.const 64
+    local.get $at
+    i32.sub
+    call $elp.write
+  )",
+    },
+    Helper {
+        name: "elp.print.i64",
+        needs: &["elp.print.char", "elp.print.u64"],
+        code: "(func $elp.print.i64 (param $value i64)
+    local.get $value
+    i64.const 0
+    i64.lt_s
+    if
+      i32.const 45
+      call $elp.print.char
+      i64.const 0
+      local.get $value
+      i64.sub
+      local.set $value
+    end
+    local.get $value
+    call $elp.print.u64
+  )",
+    },
+    Helper {
+        name: "elp.print.f64",
+        needs: &["elp.print.char", "elp.print.u64", "elp.digits", "elp.write"],
+        code: ";; Six decimal places like `%f`.
+  (func $elp.print.f64 (param $value f64)
+    (local $scaled i64)
+    (local $at i32)
+    local.get $value
+    f64.const 0
+    f64.lt
+    if
+      i32.const 45
+      call $elp.print.char
+      local.get $value
+      f64.neg
+      local.set $value
+    end
+    local.get $value
+    f64.const 1000000
+    f64.mul
+    f64.nearest
+    i64.trunc_sat_f64_u
+    local.set $scaled
+    local.get $scaled
+    i64.const 1000000
+    i64.div_u
+    call $elp.print.u64
+    i32.const 46
+    call $elp.print.char
+    ;; Adding a million keeps the leading zeros, the 1 in front is skipped.
+    local.get $scaled
+    i64.const 1000000
+    i64.rem_u
+    i64.const 1000000
+    i64.add
+    call $elp.digits
+    i32.const 1
+    i32.add
+    local.tee $at
+    i32.const 64
+    local.get $at
+    i32.sub
+    call $elp.write
+  )",
+    },
+];
+
+struct Emitter<'m> {
+    ctx: TypeContext<'m>,
+    // Constant data placed at `DATA_START`, strings are interned.
+    data: Vec<u8>,
+    strings: Vec<(String, u32)>,
+    helpers: Vec<&'static str>,
+
+    // Per function state.
+    code: String,
+    indent: usize,
+    locals: Vec<(String, &'static str)>,
+    // The wasm local each elp local in scope lives in, innermost last.
+    scopes: Vec<(String, String)>,
+    next_label: u32,
+    ret: Type,
+}
+
+impl<'m> Emitter<'m> {
+    fn new(module: &'m Module) -> Self {
+        Self {
+            ctx: TypeContext::new(module),
+            data: vec![],
+            strings: vec![],
+            helpers: vec![],
+            code: String::new(),
+            indent: 2,
+            locals: vec![],
+            scopes: vec![],
+            next_label: 0,
+            ret: Type::Void,
+        }
+    }
+
+    // The value type something is passed around as, `None` for void.
+    fn wasm_type(&self, ty: &Type) -> CodegenResult<Option<&'static str>> {
+        Ok(Some(match ty {
+            Type::Void => return Ok(None),
+            Type::Int { bits: 64, .. } => "i64",
+            Type::Bool | Type::Int { .. } => "i32",
+            Type::Float { bits: 32 } => "f32",
+            Type::Float { .. } => "f64",
+            Type::String
+            | Type::Array(_)
+            | Type::Pointer(_)
+            | Type::Reference(_)
+            | Type::Function { .. } => "i32",
+            Type::Named { name, .. } => {
+                if self.ctx.module.object(name).is_none() && self.ctx.module.r#enum(name).is_none()
+                {
+                    return self.ctx.error(format!("unknown type `{}`", name));
+                }
+                "i32"
+            }
+            Type::Unknown => {
+                return self
+                    .ctx
+                    .error("WebAssembly needs to know this type, add a type annotation")
+            }
+        }))
+    }
+
+    fn value_type(&self, ty: &Type) -> CodegenResult<&'static str> {
+        match self.wasm_type(ty)? {
+            Some(wasm) => Ok(wasm),
+            None => self.ctx.error("expected a value but this is void"),
+        }
+    }
+
+    // Bytes a value takes up in memory, everything is stored at its natural size.
+    fn size_of(&self, ty: &Type) -> CodegenResult<u32> {
+        Ok(match self.value_type(ty)? {
+            "i64" | "f64" => 8,
+            _ => 4,
+        })
+    }
+
+    // Field offsets and the total size, fields are aligned to their size.
+    fn object_layout(&self, object: &ObjectDef) -> CodegenResult<(Vec<u32>, u32)> {
+        let mut offsets = vec![];
+        let mut end = 0;
+        for field in &object.fields {
+            let size = self.size_of(&field.ty)?;
+            let offset = align(end, size);
+            offsets.push(offset);
+            end = offset + size;
+        }
+        Ok((offsets, align(end, 8).max(8)))
+    }
+
+    // Enums are a tag followed by the payload of the variant, laid out like an object.
+    fn payload_offsets(&self, r#enum: &EnumDef, tag: usize) -> CodegenResult<(Vec<u32>, u32)> {
+        let mut offsets = vec![];
+        let mut end = 8;
+        for ty in &r#enum.variants[tag].payload {
+            let size = self.size_of(ty)?;
+            let offset = align(end, size);
+            offsets.push(offset);
+            end = offset + size;
+        }
+        Ok((offsets, align(end, 8)))
+    }
+
+    fn helper(&mut self, name: &'static str) {
+        if self.helpers.contains(&name) {
+            return;
+        }
+        self.helpers.push(name);
+        let helper = HELPERS.iter().find(|helper| helper.name == name);
+        for need in helper.map(|helper| helper.needs).unwrap_or_default() {
+            self.helper(need);
+        }
+    }
+
+    fn constant(&mut self, bytes: &[u8], alignment: u32) -> u32 {
+        while !(self.data.len() as u32).is_multiple_of(alignment) {
+            self.data.push(0);
+        }
+        let address = DATA_START + self.data.len() as u32;
+        self.data.extend_from_slice(bytes);
+        address
+    }
+
+    fn string_constant(&mut self, value: &str) -> u32 {
+        if let Some((_, address)) = self.strings.iter().find(|(s, _)| s == value) {
+            return *address;
+        }
+        let mut bytes = value.as_bytes().to_vec();
+        bytes.push(0);
+        let address = self.constant(&bytes, 1);
+        self.strings.push((value.into(), address));
+        address
+    }
+
+    fn label(&mut self, name: &str) -> String {
+        self.next_label += 1;
+        format!("${}{}", name, self.next_label)
+    }
+
+    fn inst(&mut self, instruction: impl AsRef<str>) {
+        for _ in 0..self.indent {
+            self.code.push_str("  ");
+        }
+        self.code.push_str(instruction.as_ref());
+        self.code.push('\n');
+    }
+
+    // Opens a block, `end` closes it.
+    fn open(&mut self, instruction: impl AsRef<str>) {
+        self.inst(instruction);
+        self.indent += 1;
+    }
+
+    fn close(&mut self, instruction: &str) {
+        self.indent -= 1;
+        self.inst(instruction);
+    }
+
+    // A fresh wasm local, named after the elp local when there is one.
+    fn new_local(&mut self, name: &str, wasm: &'static str) -> String {
+        let mut local = symbol(name);
+        let mut suffix = 0;
+        while self.locals.iter().any(|(used, _)| *used == local) {
+            suffix += 1;
+            local = symbol(&format!("{}.{}", name, suffix));
+        }
+        self.locals.push((local.clone(), wasm));
+        local
+    }
+
+    fn temp(&mut self, ty: &Type) -> CodegenResult<String> {
+        let wasm = self.value_type(ty)?;
+        Ok(self.new_local("tmp", wasm))
+    }
+
+    // Takes the value on top of the stack and keeps it in a new local.
+    fn declare_local(&mut self, name: &str, ty: Type) -> CodegenResult<()> {
+        let wasm = self.value_type(&ty)?;
+        let local = self.new_local(name, wasm);
+        self.inst(format!("local.set {}", local));
+        self.ctx.declare(name, ty);
+        self.scopes.push((name.into(), local));
+        Ok(())
+    }
+
+    fn local(&self, name: &str) -> Option<String> {
+        self.scopes
+            .iter()
+            .rev()
+            .find(|(local, _)| local == name)
+            .map(|(_, wasm)| wasm.clone())
+    }
+
+    fn scoped<T>(&mut self, f: impl FnOnce(&mut Self) -> CodegenResult<T>) -> CodegenResult<T> {
+        let depth = self.scopes.len();
+        self.ctx.push_scope();
+        let result = f(self);
+        self.ctx.pop_scope();
+        self.scopes.truncate(depth);
+        result
+    }
+
+    fn load(&mut self, ty: &Type, offset: u32) -> CodegenResult<()> {
+        let wasm = self.value_type(ty)?;
+        match offset {
+            0 => self.inst(format!("{}.load", wasm)),
+            offset => self.inst(format!("{}.load offset={}", wasm, offset)),
+        }
+        Ok(())
+    }
+
+    fn store(&mut self, ty: &Type, offset: u32) -> CodegenResult<()> {
+        let wasm = self.value_type(ty)?;
+        match offset {
+            0 => self.inst(format!("{}.store", wasm)),
+            offset => self.inst(format!("{}.store offset={}", wasm, offset)),
+        }
+        Ok(())
+    }
+
+    fn zero(&mut self, ty: &Type) -> CodegenResult<()> {
+        let wasm = self.value_type(ty)?;
+        self.inst(format!("{}.const 0", wasm));
+        Ok(())
+    }
+
+    // Integers narrower than their wasm type wrap like they would natively.
+    fn wrap(&mut self, ty: &Type) {
+        match ty {
+            Type::Int {
+                bits: 8,
+                signed: true,
+            } => self.inst("i32.extend8_s"),
+            Type::Int {
+                bits: 16,
+                signed: true,
+            } => self.inst("i32.extend16_s"),
+            Type::Int {
+                bits: bits @ (8 | 16),
+                signed: false,
+            } => {
+                self.inst(format!("i32.const {}", (1u32 << bits) - 1));
+                self.inst("i32.and");
+            }
+            _ => {}
+        }
+    }
+
+    // Converts the value on top of the stack.
+    fn coerce(&mut self, from: &Type, to: &Type) -> CodegenResult<()> {
+        let from_wasm = self.wasm_type(from)?;
+        let to_wasm = self.wasm_type(to)?;
+
+        let conversion = match (from, to) {
+            (Type::Int { bits: a, .. }, Type::Int { bits: b, .. }) if a > b => {
+                if from_wasm != to_wasm {
+                    self.inst("i32.wrap_i64");
+                }
+                self.wrap(to);
+                return Ok(());
+            }
+            (Type::Int { .. } | Type::Bool, Type::Int { .. })
+                if from_wasm == Some("i32") && to_wasm == Some("i64") =>
+            {
+                let signed = matches!(from, Type::Int { signed: true, .. });
+                Some(format!("i64.extend_i32_{}", if signed { "s" } else { "u" }))
+            }
+            (Type::Int { signed, .. }, Type::Float { .. }) => Some(format!(
+                "{}.convert_{}_{}",
+                to_wasm.unwrap_or_default(),
+                from_wasm.unwrap_or_default(),
+                if *signed { "s" } else { "u" }
+            )),
+            (Type::Float { .. }, Type::Int { signed, .. }) => Some(format!(
+                "{}.trunc_sat_{}_{}",
+                to_wasm.unwrap_or_default(),
+                from_wasm.unwrap_or_default(),
+                if *signed { "s" } else { "u" }
+            )),
+            (Type::Float { bits: 32 }, Type::Float { bits: 64 }) => Some("f64.promote_f32".into()),
+            (Type::Float { bits: 64 }, Type::Float { bits: 32 }) => Some("f32.demote_f64".into()),
+            _ if from_wasm == to_wasm => None,
+            _ => {
+                return self.ctx.error(format!(
+                    "expected a value of type {} but found {}",
+                    to_wasm.unwrap_or("void"),
+                    from_wasm.unwrap_or("void")
+                ))
+            }
+        };
+        if let Some(conversion) = conversion {
+            self.inst(conversion);
+        }
+        Ok(())
+    }
+
+    fn emit_module(mut self) -> CodegenResult<String> {
+        let module = self.ctx.module;
+        let mut out = format!(";; Generated from {}.elp.\n(module\n", module.name);
+
+        let mut imports = String::new();
+        for external in &module.externals {
+            self.ctx.span = external.span;
+            let mut signature = String::new();
+            for param in &external.params {
+                let _ = write!(signature, " (param {})", self.value_type(&param.ty)?);
+            }
+            if let Some(ret) = self.wasm_type(&external.ret)? {
+                let _ = write!(signature, " (result {})", ret);
+            }
+            let _ = writeln!(
+                imports,
+                "  (import \"env\" \"{}\" (func {}{}))",
+                external.name,
+                symbol(&external.name),
+                signature
+            );
+        }
+
+        let mut globals = String::new();
+        for global in &module.globals {
+            self.ctx.span = global.span;
+            let ty = self.ctx.global(&global.name)?;
+            let wasm = self.value_type(&ty)?;
+            let value = match &global.value {
+                Expr::Literal(Literal::String(value)) => self.string_constant(value).to_string(),
+                Expr::Literal(Literal::Bool(value)) => (*value as i32).to_string(),
+                Expr::Literal(Literal::Int(value)) if wasm.starts_with('f') => {
+                    float_constant(*value as f64)
+                }
+                Expr::Literal(Literal::Int(value)) => value.to_string(),
+                Expr::Literal(Literal::Float(value)) => float_constant(*value),
+                Expr::Literal(Literal::Nil) => "0".into(),
+                _ => {
+                    return self
+                        .ctx
+                        .error("module level values have to be literals in WebAssembly")
+                }
+            };
+            let _ = writeln!(
+                globals,
+                "  (global {}{} {} ({}.const {}))",
+                symbol(&global.name),
+                match global.exported {
+                    true => format!(" (export \"{}\")", global.name),
+                    false => String::new(),
+                },
+                match global.mutable {
+                    true => format!("(mut {})", wasm),
+                    false => wasm.to_string(),
+                },
+                wasm,
+                value
+            );
+        }
+
+        // Check every signature first so errors point at the declaration rather than a call.
+        for function in &module.functions {
+            self.ctx.span = function.span;
+            for param in &function.params {
+                self.value_type(&param.ty)?;
+            }
+            self.wasm_type(&function.ret)?;
+        }
+
+        let mut functions = String::new();
+        for function in &module.functions {
+            functions.push_str(&self.emit_function(function)?);
+        }
+
+        // A WASI command starts at `_start`, `main`'s result becomes the exit code.
+        if let Some(main) = module.function("main").filter(|f| f.receiver.is_none()) {
+            functions.push_str("  (func $_start (export \"_start\")\n    call $main\n");
+            if let Some(ret) = self.wasm_type(&main.ret)? {
+                if ret == "i64" {
+                    functions.push_str("    i32.wrap_i64\n");
+                }
+                functions.push_str("    call $wasi.proc_exit\n");
+                self.helper("wasi.proc_exit");
+            }
+            functions.push_str("  )\n");
+        }
+
+        // Hosts need to allocate to pass anything that lives in memory to an exported function.
+        for function in module.functions.iter().filter(|f| f.exported) {
+            for param in &function.params {
+                if self.value_type(&param.ty)? == "i32"
+                    && !matches!(param.ty, Type::Bool | Type::Int { .. })
+                {
+                    self.helper("elp.alloc");
+                }
+            }
+        }
+
+        let helpers = HELPERS
+            .iter()
+            .filter(|helper| self.helpers.contains(&helper.name))
+            .collect::<Vec<_>>();
+        for helper in helpers
+            .iter()
+            .filter(|helper| helper.name.starts_with("wasi."))
+        {
+            let _ = writeln!(imports, "  {}", helper.code);
+        }
+
+        out.push_str(&imports);
+        out.push_str("  (memory (export \"memory\") 1)\n");
+        if self.helpers.contains(&"elp.alloc") {
+            let heap = align(DATA_START + self.data.len() as u32, 8);
+            let _ = writeln!(out, "  (global $elp.heap (mut i32) (i32.const {}))", heap);
+        }
+        out.push_str(&globals);
+        if !self.data.is_empty() {
+            let _ = writeln!(
+                out,
+                "  (data (i32.const {}) \"{}\")",
+                DATA_START,
+                escape(&self.data)
+            );
+        }
+        out.push_str(&functions);
+        for helper in helpers
+            .iter()
+            .filter(|helper| !helper.name.starts_with("wasi."))
+        {
+            let _ = writeln!(out, "  {}", helper.code);
+        }
+        out.push_str(")\n");
+
+        Ok(out)
+    }
+
+    fn emit_function(&mut self, function: &Function) -> CodegenResult<String> {
+        self.ctx.span = function.span;
+
+        self.code.clear();
+        self.indent = 2;
+        self.locals.clear();
+        self.scopes.clear();
+        self.next_label = 0;
+        self.ret = function.ret.clone();
+
+        let name = function.qualified_name();
+        let mut header = format!("  (func {}", symbol(&name));
+        if function.exported {
+            let _ = write!(header, " (export \"{}\")", name);
+        }
+
+        self.ctx.push_scope();
+        for param in &function.params {
+            let wasm = self.value_type(&param.ty)?;
+            let local = self.new_local(&param.name, wasm);
+            let _ = write!(header, " (param {} {})", local, wasm);
+            self.ctx.declare(&param.name, param.ty.clone());
+            self.scopes.push((param.name.clone(), local));
+        }
+        let params = self.locals.len();
+        if let Some(ret) = self.wasm_type(&function.ret)? {
+            let _ = write!(header, " (result {})", ret);
+        }
+        for stmt in &function.body {
+            self.emit_stmt(stmt)?;
+        }
+        self.ctx.pop_scope();
+
+        // Falling off the end of a function that returns a value can't happen after type checking.
+        if function.ret != Type::Void
+            && !matches!(
+                function.body.last().map(|stmt| &stmt.kind),
+                Some(StmtKind::Return(_))
+            )
+        {
+            self.inst("unreachable");
+        }
+
+        let mut out = header;
+        out.push('\n');
+        for (local, wasm) in &self.locals[params..] {
+            let _ = writeln!(out, "    (local {} {})", local, wasm);
+        }
+        out.push_str(&self.code);
+        out.push_str("  )\n");
+        Ok(out)
+    }
+
+    fn emit_block(&mut self, body: &[Stmt]) -> CodegenResult<()> {
+        self.scoped(|e| body.iter().try_for_each(|stmt| e.emit_stmt(stmt)))
+    }
+
+    fn emit_stmt(&mut self, stmt: &Stmt) -> CodegenResult<()> {
+        self.ctx.span = stmt.span;
+
+        match &stmt.kind {
+            StmtKind::Let {
+                name, ty, value, ..
+            } => {
+                let ty = match (ty, value) {
+                    (Type::Unknown, Some(value)) => self.ctx.type_of(value)?,
+                    (Type::Unknown, None) => {
+                        return self
+                            .ctx
+                            .error(format!("`{}` needs a type or a value", name))
+                    }
+                    (ty, _) => ty.clone(),
+                };
+                match value {
+                    Some(value) => {
+                        let found = self.emit_expr(value, Some(&ty))?;
+                        self.coerce(&found, &ty)?;
+                    }
+                    None => self.zero(&ty)?,
+                }
+                self.declare_local(name, ty)?;
+            }
+            StmtKind::Assign { target, op, value } => self.emit_assign(target, *op, value)?,
+            StmtKind::Expr(expr) => {
+                let ty = self.emit_expr(expr, None)?;
+                if self.wasm_type(&ty)?.is_some() {
+                    self.inst("drop");
+                }
+            }
+            StmtKind::Return(value) => {
+                if let Some(value) = value {
+                    let ret = self.ret.clone();
+                    let found = self.emit_expr(value, Some(&ret))?;
+                    self.coerce(&found, &ret)?;
+                }
+                self.inst("return");
+            }
+            StmtKind::If {
+                condition,
+                then_body,
+                else_body,
+            } => {
+                self.emit_condition(condition)?;
+                self.open("if");
+                self.emit_block(then_body)?;
+                if !else_body.is_empty() {
+                    self.close("else");
+                    self.indent += 1;
+                    self.emit_block(else_body)?;
+                }
+                self.close("end");
+            }
+            StmtKind::For {
+                binding,
+                iterable,
+                body,
+            } => self.emit_for(binding, iterable, body)?,
+        }
+
+        Ok(())
+    }
+
+    fn emit_assign(
+        &mut self,
+        target: &Expr,
+        op: Option<BinaryOp>,
+        value: &Expr,
+    ) -> CodegenResult<()> {
+        let emit_value = |e: &mut Self, ty: &Type| -> CodegenResult<()> {
+            let found = match op {
+                Some(op) => e.emit_binary(op, target, value)?,
+                None => e.emit_expr(value, Some(ty))?,
+            };
+            e.coerce(&found, ty)
+        };
+
+        match target {
+            Expr::Local(name) => {
+                let ty = self.ctx.type_of(target)?;
+                let Some(local) = self.local(name) else {
+                    return self.ctx.error(format!("`{}` isn't defined", name));
+                };
+                emit_value(self, &ty)?;
+                self.inst(format!("local.set {}", local));
+            }
+            Expr::Global(name) => {
+                let ty = self.ctx.global(name)?;
+                emit_value(self, &ty)?;
+                self.inst(format!("global.set {}", symbol(name)));
+            }
+            Expr::Field { base, name } => {
+                let base = self.emit_expr(base, None)?;
+                let (object, index) = self.ctx.field(&base, name)?;
+                let (offsets, _) = self.object_layout(object)?;
+                let ty = object.fields[index].ty.clone();
+                emit_value(self, &ty)?;
+                self.store(&ty, offsets[index])?;
+            }
+            _ => return self.ctx.error("can't assign to this expression"),
+        }
+        Ok(())
+    }
+
+    fn emit_for(&mut self, binding: &str, iterable: &Expr, body: &[Stmt]) -> CodegenResult<()> {
+        let ty = self.emit_expr(iterable, None)?;
+        let Type::Array(item) = ty else {
+            return self
+                .ctx
+                .error("only arrays can be looped over in WebAssembly");
+        };
+        let size = self.size_of(&item)?;
+        let array = self.new_local("array", "i32");
+        let index = self.new_local("index", "i32");
+        self.inst(format!("local.set {}", array));
+        self.inst("i32.const 0");
+        self.inst(format!("local.set {}", index));
+
+        let end = self.label("endfor");
+        let next = self.label("for");
+        self.open(format!("block {}", end));
+        self.open(format!("loop {}", next));
+        self.inst(format!("local.get {}", index));
+        self.inst(format!("local.get {}", array));
+        self.inst("i32.load offset=4");
+        self.inst("i32.ge_s");
+        self.inst(format!("br_if {}", end));
+        self.inst(format!("local.get {}", array));
+        self.inst("i32.load");
+        self.inst(format!("local.get {}", index));
+        self.inst(format!("i32.const {}", size));
+        self.inst("i32.mul");
+        self.inst("i32.add");
+        self.load(&item, 0)?;
+        self.scoped(|e| {
+            e.declare_local(binding, *item)?;
+            body.iter().try_for_each(|stmt| e.emit_stmt(stmt))
+        })?;
+        self.inst(format!("local.get {}", index));
+        self.inst("i32.const 1");
+        self.inst("i32.add");
+        self.inst(format!("local.set {}", index));
+        self.inst(format!("br {}", next));
+        self.close("end");
+        self.close("end");
+
+        Ok(())
+    }
+
+    fn emit_condition(&mut self, condition: &Expr) -> CodegenResult<()> {
+        let ty = self.emit_expr(condition, Some(&Type::Bool))?;
+        if ty != Type::Bool {
+            return self.ctx.error("conditions have to be booleans");
+        }
+        Ok(())
+    }
+
+    fn literal(&mut self, literal: &Literal, expected: Option<&Type>) -> CodegenResult<Type> {
+        let (ty, instruction) = match (literal, expected) {
+            (Literal::Nil, _) => (Type::Pointer(Box::new(Type::Void)), "i32.const 0".into()),
+            (Literal::Bool(value), _) => (Type::Bool, format!("i32.const {}", *value as i32)),
+            (Literal::Int(value), Some(ty @ Type::Int { .. })) => {
+                let wasm = self.value_type(ty)?;
+                (ty.clone(), format!("{}.const {}", wasm, value))
+            }
+            (Literal::Int(value), Some(ty @ Type::Float { .. })) => {
+                let wasm = self.value_type(ty)?;
+                (
+                    ty.clone(),
+                    format!("{}.const {}", wasm, float_constant(*value as f64)),
+                )
+            }
+            (Literal::Int(value), _) => (INT, format!("i64.const {}", value)),
+            (Literal::Float(value), Some(ty @ Type::Float { .. })) => {
+                let wasm = self.value_type(ty)?;
+                (
+                    ty.clone(),
+                    format!("{}.const {}", wasm, float_constant(*value)),
+                )
+            }
+            (Literal::Float(value), _) => (
+                Type::Float { bits: 64 },
+                format!("f64.const {}", float_constant(*value)),
+            ),
+            (Literal::String(value), _) => (
+                Type::String,
+                format!("i32.const {}", self.string_constant(value)),
+            ),
+        };
+        self.inst(instruction);
+        Ok(ty)
+    }
+
+    // Emits the code that leaves the value of the expression on the stack and returns its type.
+    fn emit_expr(&mut self, expr: &Expr, expected: Option<&Type>) -> CodegenResult<Type> {
+        match expr {
+            Expr::Literal(literal) => self.literal(literal, expected),
+            Expr::Local(name) => {
+                let ty = self.ctx.type_of(expr)?;
+                let Some(local) = self.local(name) else {
+                    return self.ctx.error(format!("`{}` isn't defined", name));
+                };
+                self.inst(format!("local.get {}", local));
+                Ok(ty)
+            }
+            Expr::Global(name) => {
+                if self.ctx.signature(name).is_some() {
+                    return self.ctx.error(format!(
+                        "`{}` can only be called, functions aren't values in WebAssembly yet",
+                        name
+                    ));
+                }
+                let ty = self.ctx.global(name)?;
+                self.inst(format!("global.get {}", symbol(name)));
+                Ok(ty)
+            }
+            Expr::Field { base, name } => {
+                let base = self.emit_expr(base, None)?;
+                let (object, index) = self.ctx.field(&base, name)?;
+                let (offsets, _) = self.object_layout(object)?;
+                let ty = object.fields[index].ty.clone();
+                self.load(&ty, offsets[index])?;
+                Ok(ty)
+            }
+            Expr::Call { callee, args } => self.emit_call(callee, args),
+            Expr::MethodCall {
+                receiver,
+                method,
+                args,
+            } => self.emit_method_call(receiver, method, args),
+            Expr::Binary { op, lhs, rhs } => self.emit_binary(*op, lhs, rhs),
+            Expr::Unary { op, operand } => {
+                let ty = match self.ctx.type_of(operand)? {
+                    Type::Int { .. } => match expected {
+                        Some(ty @ Type::Int { .. }) => ty.clone(),
+                        _ => self.ctx.type_of(operand)?,
+                    },
+                    ty => ty,
+                };
+                match (op, &ty) {
+                    (UnaryOp::Not, Type::Bool) => {
+                        self.emit_expr(operand, expected)?;
+                        self.inst("i32.eqz");
+                    }
+                    (UnaryOp::Neg, Type::Int { .. }) => {
+                        let wasm = self.value_type(&ty)?;
+                        self.inst(format!("{}.const 0", wasm));
+                        let found = self.emit_expr(operand, Some(&ty))?;
+                        self.coerce(&found, &ty)?;
+                        self.inst(format!("{}.sub", wasm));
+                        self.wrap(&ty);
+                    }
+                    (UnaryOp::Neg, Type::Float { .. }) => {
+                        let found = self.emit_expr(operand, expected)?;
+                        self.coerce(&found, &ty)?;
+                        let wasm = self.value_type(&ty)?;
+                        self.inst(format!("{}.neg", wasm));
+                    }
+                    _ => {
+                        return self
+                            .ctx
+                            .error(format!("can't apply {:?} to a value of type {:?}", op, ty))
+                    }
+                }
+                Ok(ty)
+            }
+            Expr::Object { name, fields } => self.emit_object(name, fields),
+            Expr::Variant {
+                enum_name,
+                variant,
+                args,
+            } => self.emit_variant(enum_name.as_deref(), variant, args, expected),
+            Expr::Array(items) => {
+                let ty = match expected {
+                    Some(ty @ Type::Array(_)) => ty.clone(),
+                    _ => self.ctx.type_of(expr)?,
+                };
+                let Type::Array(item) = &ty else {
+                    unreachable!("array literals are always arrays")
+                };
+                let size = self.size_of(item)?;
+                self.helper("elp.array.new");
+                self.inst(format!("i32.const {}", size));
+                self.inst(format!("i32.const {}", items.len()));
+                self.inst("call $elp.array.new");
+                let array = self.temp(&ty)?;
+                self.inst(format!("local.set {}", array));
+                for (index, value) in items.iter().enumerate() {
+                    self.inst(format!("local.get {}", array));
+                    self.inst("i32.load");
+                    let found = self.emit_expr(value, Some(item))?;
+                    self.coerce(&found, item)?;
+                    self.store(item, index as u32 * size)?;
+                }
+                self.inst(format!("local.get {}", array));
+                Ok(ty)
+            }
+            Expr::Closure(_) => self
+                .ctx
+                .error("closures aren't supported by the WebAssembly backend yet"),
+            Expr::Match { scrutinee, arms } => self.emit_match(scrutinee, arms, expected),
+            Expr::Element(element) => self.ctx.error(format!(
+                "`{}` is an element, elements are only supported by the web backend",
+                element.name
+            )),
+        }
+    }
+
+    fn emit_args(&mut self, params: &[Type], args: &[Expr]) -> CodegenResult<()> {
+        for (param, arg) in params.iter().zip(args) {
+            let found = self.emit_expr(arg, Some(param))?;
+            self.coerce(&found, param)?;
+        }
+        Ok(())
+    }
+
+    fn emit_call(&mut self, callee: &Expr, args: &[Expr]) -> CodegenResult<Type> {
+        let Expr::Global(name) = callee else {
+            return self
+                .ctx
+                .error("only functions can be called directly in WebAssembly");
+        };
+        if is_builtin(name) && self.ctx.signature(name).is_none() {
+            return self.emit_print(name, args);
+        }
+        let Some(signature) = self.ctx.signature(name) else {
+            return self.ctx.error(format!("`{}` isn't defined", name));
+        };
+        if signature.params.len() != args.len() {
+            return self.ctx.error(format!(
+                "`{}` takes {} arguments but {} were given",
+                name,
+                signature.params.len(),
+                args.len()
+            ));
+        }
+
+        self.emit_args(&signature.params, args)?;
+        self.inst(format!("call {}", symbol(&signature.name)));
+        Ok(signature.ret)
+    }
+
+    fn emit_method_call(
+        &mut self,
+        receiver: &Expr,
+        method: &str,
+        args: &[Expr],
+    ) -> CodegenResult<Type> {
+        let receiver_ty = self.ctx.type_of(receiver)?;
+
+        if let Type::Array(item) = &receiver_ty {
+            return match (method, args) {
+                ("append", [arg]) => {
+                    // `append` hands back the index of the new item.
+                    self.emit_expr(receiver, None)?;
+                    let array = self.temp(&receiver_ty)?;
+                    let index = self.temp(&INT)?;
+                    self.inst(format!("local.set {}", array));
+                    self.inst(format!("local.get {}", array));
+                    self.inst("i32.load offset=4");
+                    self.inst("i64.extend_i32_u");
+                    self.inst(format!("local.set {}", index));
+                    self.helper("elp.array.push");
+                    self.inst(format!("local.get {}", array));
+                    self.inst(format!("i32.const {}", self.size_of(item)?));
+                    self.inst("call $elp.array.push");
+                    let found = self.emit_expr(arg, Some(item))?;
+                    self.coerce(&found, item)?;
+                    self.store(item, 0)?;
+                    self.inst(format!("local.get {}", index));
+                    Ok(INT)
+                }
+                ("len" | "length", []) => {
+                    self.emit_expr(receiver, None)?;
+                    self.inst("i32.load offset=4");
+                    self.inst("i64.extend_i32_u");
+                    Ok(INT)
+                }
+                _ => self
+                    .ctx
+                    .error(format!("arrays don't have a `{}` method", method)),
+            };
+        }
+        if receiver_ty == Type::String && matches!(method, "len" | "length") && args.is_empty() {
+            self.emit_expr(receiver, None)?;
+            self.helper("elp.string.len");
+            self.inst("call $elp.string.len");
+            self.inst("i64.extend_i32_u");
+            return Ok(INT);
+        }
+
+        let Some(signature) = self.ctx.method(&receiver_ty, method) else {
+            return self.ctx.error(format!("no method `{}` found", method));
+        };
+        let is_method = self
+            .ctx
+            .module
+            .function(&signature.name)
+            .is_some_and(Function::is_method);
+
+        let params = if is_method {
+            let found = self.emit_expr(receiver, None)?;
+            self.coerce(&found, &signature.params[0])?;
+            &signature.params[1..]
+        } else {
+            &signature.params[..]
+        };
+        if params.len() != args.len() {
+            return self.ctx.error(format!(
+                "`{}` takes {} arguments but {} were given",
+                signature.name,
+                params.len(),
+                args.len()
+            ));
+        }
+        self.emit_args(params, args)?;
+        self.inst(format!("call {}", symbol(&signature.name)));
+        Ok(signature.ret)
+    }
+
+    fn emit_print(&mut self, name: &str, args: &[Expr]) -> CodegenResult<Type> {
+        let [arg] = args else {
+            return self
+                .ctx
+                .error(format!("`{}` takes exactly one argument", name));
+        };
+        let ty = self.emit_expr(arg, None)?;
+        match &ty {
+            Type::String => {}
+            Type::Bool => {
+                let yes = self.string_constant("true");
+                let no = self.string_constant("false");
+                self.open("if (result i32)");
+                self.inst(format!("i32.const {}", yes));
+                self.close("else");
+                self.indent += 1;
+                self.inst(format!("i32.const {}", no));
+                self.close("end");
+            }
+            Type::Int { signed, .. } => {
+                let int = Type::Int {
+                    bits: 64,
+                    signed: *signed,
+                };
+                self.coerce(&ty, &int)?;
+            }
+            Type::Float { .. } => self.coerce(&ty, &Type::Float { bits: 64 })?,
+            _ => {
+                return self
+                    .ctx
+                    .error("only strings, numbers and bools can be printed")
+            }
+        }
+        let helper = match &ty {
+            Type::Int { signed: true, .. } => "elp.print.i64",
+            Type::Int { signed: false, .. } => "elp.print.u64",
+            Type::Float { .. } => "elp.print.f64",
+            _ => "elp.print.str",
+        };
+        self.helper(helper);
+        self.inst(format!("call ${}", helper));
+        if name == "println" {
+            self.helper("elp.print.char");
+            self.inst("i32.const 10");
+            self.inst("call $elp.print.char");
+        }
+
+        Ok(Type::Void)
+    }
+
+    fn emit_object(&mut self, name: &str, fields: &[(String, Expr)]) -> CodegenResult<Type> {
+        let Some(object) = self.ctx.module.object(name) else {
+            return self.ctx.error(format!("unknown object `{}`", name));
+        };
+        let ty = Type::Named {
+            name: name.into(),
+            generics: vec![],
+        };
+        for (field, _) in fields {
+            if object.field_index(field).is_none() {
+                return self
+                    .ctx
+                    .error(format!("`{}` has no field `{}`", name, field));
+            }
+        }
+
+        let (offsets, size) = self.object_layout(object)?;
+        self.helper("elp.alloc");
+        self.inst(format!("i32.const {}", size));
+        self.inst("call $elp.alloc");
+        let pointer = self.temp(&ty)?;
+        self.inst(format!("local.set {}", pointer));
+        for (field, offset) in object.fields.iter().zip(offsets) {
+            let value = match fields.iter().find(|(name, _)| *name == field.name) {
+                Some((_, value)) => Some(value),
+                None => field.default.as_ref(),
+            };
+            self.inst(format!("local.get {}", pointer));
+            match value {
+                Some(value) => {
+                    let found = self.emit_expr(value, Some(&field.ty))?;
+                    self.coerce(&found, &field.ty)?;
+                }
+                None => self.zero(&field.ty)?,
+            }
+            self.store(&field.ty, offset)?;
+        }
+        self.inst(format!("local.get {}", pointer));
+
+        Ok(ty)
+    }
+
+    fn emit_variant(
+        &mut self,
+        enum_name: Option<&str>,
+        variant: &str,
+        args: &[Expr],
+        expected: Option<&Type>,
+    ) -> CodegenResult<Type> {
+        let (r#enum, tag) = self.ctx.variant(enum_name, variant, expected)?;
+        let payload = &r#enum.variants[tag].payload;
+        if payload.len() != args.len() {
+            return self.ctx.error(format!(
+                "`{}.{}` carries {} values but {} were given",
+                r#enum.name,
+                variant,
+                payload.len(),
+                args.len()
+            ));
+        }
+        let ty = Type::Named {
+            name: r#enum.name.clone(),
+            generics: vec![],
+        };
+
+        // Variants without a payload never change so they're shared constants.
+        if args.is_empty() {
+            let mut bytes = (tag as u32).to_le_bytes().to_vec();
+            bytes.resize(8, 0);
+            let address = self.constant(&bytes, 8);
+            self.inst(format!("i32.const {}", address));
+            return Ok(ty);
+        }
+
+        let (offsets, size) = self.payload_offsets(r#enum, tag)?;
+        self.helper("elp.alloc");
+        self.inst(format!("i32.const {}", size));
+        self.inst("call $elp.alloc");
+        let pointer = self.temp(&ty)?;
+        self.inst(format!("local.set {}", pointer));
+        self.inst(format!("local.get {}", pointer));
+        self.inst(format!("i32.const {}", tag));
+        self.inst("i32.store");
+        for ((payload, arg), offset) in payload.iter().zip(args).zip(offsets) {
+            self.inst(format!("local.get {}", pointer));
+            let found = self.emit_expr(arg, Some(payload))?;
+            self.coerce(&found, payload)?;
+            self.store(payload, offset)?;
+        }
+        self.inst(format!("local.get {}", pointer));
+
+        Ok(ty)
+    }
+
+    fn emit_binary(&mut self, op: BinaryOp, lhs: &Expr, rhs: &Expr) -> CodegenResult<Type> {
+        if matches!(op, BinaryOp::And | BinaryOp::Or) {
+            return self.emit_logical(op, lhs, rhs);
+        }
+
+        let ty = self.ctx.operand_type(lhs, rhs)?;
+        if op == BinaryOp::Pow {
+            return self.emit_pow(&ty, lhs, rhs);
+        }
+        let is_enum = self.ctx.enum_of(&ty).is_some();
+        for operand in [lhs, rhs] {
+            let found = self.emit_expr(operand, Some(&ty))?;
+            self.coerce(&found, &ty)?;
+            // Enums compare by their tag.
+            if is_enum {
+                self.inst("i32.load");
+            }
+        }
+        let wasm = self.value_type(&ty)?;
+
+        let comparison = match op {
+            BinaryOp::Eq => Some(("eq", "eq", "eq")),
+            BinaryOp::Ne => Some(("ne", "ne", "ne")),
+            BinaryOp::Lt => Some(("lt_s", "lt_u", "lt")),
+            BinaryOp::Le => Some(("le_s", "le_u", "le")),
+            BinaryOp::Gt => Some(("gt_s", "gt_u", "gt")),
+            BinaryOp::Ge => Some(("ge_s", "ge_u", "ge")),
+            _ => None,
+        };
+
+        if let Some((signed, unsigned, float)) = comparison {
+            match &ty {
+                Type::Int { signed: true, .. } => self.inst(format!("{}.{}", wasm, signed)),
+                Type::Int { signed: false, .. } | Type::Bool => {
+                    self.inst(format!("{}.{}", wasm, unsigned))
+                }
+                Type::Float { .. } => self.inst(format!("{}.{}", wasm, float)),
+                Type::String => {
+                    self.helper("elp.string.compare");
+                    self.inst("call $elp.string.compare");
+                    self.inst("i32.const 0");
+                    self.inst(format!("i32.{}", signed));
+                }
+                _ if matches!(op, BinaryOp::Eq | BinaryOp::Ne) => {
+                    self.inst(format!("i32.{}", signed))
+                }
+                _ => {
+                    return self
+                        .ctx
+                        .error(format!("can't compare values of type {:?}", ty))
+                }
+            }
+            return Ok(Type::Bool);
+        }
+
+        let instruction = match (&ty, op) {
+            (Type::Int { .. } | Type::Float { .. }, BinaryOp::Add) => "add",
+            (Type::Int { .. } | Type::Float { .. }, BinaryOp::Sub) => "sub",
+            (Type::Int { .. } | Type::Float { .. }, BinaryOp::Mul) => "mul",
+            (Type::Int { signed: true, .. }, BinaryOp::Div) => "div_s",
+            (Type::Int { signed: false, .. }, BinaryOp::Div) => "div_u",
+            (Type::Float { .. }, BinaryOp::Div) => "div",
+            (Type::Int { signed: true, .. }, BinaryOp::Mod) => "rem_s",
+            (Type::Int { signed: false, .. }, BinaryOp::Mod) => "rem_u",
+            (Type::Int { .. } | Type::Bool, BinaryOp::BitAnd) => "and",
+            (Type::Int { .. } | Type::Bool, BinaryOp::BitOr) => "or",
+            (Type::Int { .. }, BinaryOp::Shl) => "shl",
+            (Type::Int { signed: true, .. }, BinaryOp::Shr) => "shr_s",
+            (Type::Int { signed: false, .. }, BinaryOp::Shr) => "shr_u",
+            (Type::Float { bits }, BinaryOp::Mod) => {
+                self.helper("elp.rem.f64");
+                if *bits == 32 {
+                    // Both operands are already on the stack, widen them through a local.
+                    let rhs = self.new_local("rhs", "f32");
+                    self.inst(format!("local.set {}", rhs));
+                    self.inst("f64.promote_f32");
+                    self.inst(format!("local.get {}", rhs));
+                    self.inst("f64.promote_f32");
+                    self.inst("call $elp.rem.f64");
+                    self.inst("f32.demote_f64");
+                } else {
+                    self.inst("call $elp.rem.f64");
+                }
+                return Ok(ty);
+            }
+            (Type::String, BinaryOp::Add) => {
+                self.helper("elp.string.concat");
+                self.inst("call $elp.string.concat");
+                return Ok(ty);
+            }
+            _ => {
+                return self
+                    .ctx
+                    .error(format!("can't apply {:?} to values of type {:?}", op, ty))
+            }
+        };
+
+        self.inst(format!("{}.{}", wasm, instruction));
+        if matches!(
+            op,
+            BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Shl
+        ) {
+            self.wrap(&ty);
+        }
+        Ok(ty)
+    }
+
+    // Powers are worked out in 64 bits and converted back.
+    fn emit_pow(&mut self, ty: &Type, lhs: &Expr, rhs: &Expr) -> CodegenResult<Type> {
+        let wide = match ty {
+            Type::Int { signed, .. } => Type::Int {
+                bits: 64,
+                signed: *signed,
+            },
+            Type::Float { .. } => Type::Float { bits: 64 },
+            _ => {
+                return self
+                    .ctx
+                    .error(format!("can't raise a value of type {:?} to a power", ty))
+            }
+        };
+        for operand in [lhs, rhs] {
+            let found = self.emit_expr(operand, Some(ty))?;
+            self.coerce(&found, ty)?;
+            self.coerce(ty, &wide)?;
+        }
+        let helper = match wide {
+            Type::Float { .. } => "elp.pow.f64",
+            _ => "elp.pow.i64",
+        };
+        self.helper(helper);
+        self.inst(format!("call ${}", helper));
+        self.coerce(&wide, ty)?;
+        Ok(ty.clone())
+    }
+
+    // `and`/`or` only evaluate their right hand side when they have to.
+    fn emit_logical(&mut self, op: BinaryOp, lhs: &Expr, rhs: &Expr) -> CodegenResult<Type> {
+        self.emit_condition(lhs)?;
+        self.open("if (result i32)");
+        if op == BinaryOp::And {
+            self.emit_condition(rhs)?;
+            self.close("else");
+            self.indent += 1;
+            self.inst("i32.const 0");
+        } else {
+            self.inst("i32.const 1");
+            self.close("else");
+            self.indent += 1;
+            self.emit_condition(rhs)?;
+        }
+        self.close("end");
+        Ok(Type::Bool)
+    }
+
+    fn emit_match(
+        &mut self,
+        scrutinee: &Expr,
+        arms: &[MatchArm],
+        expected: Option<&Type>,
+    ) -> CodegenResult<Type> {
+        let subject_ty = self.emit_expr(scrutinee, None)?;
+        let ty = match expected {
+            Some(ty) if *ty != Type::Void => ty.clone(),
+            _ => self.ctx.type_of(&Expr::Match {
+                scrutinee: Box::new(scrutinee.clone()),
+                arms: arms.to_vec(),
+            })?,
+        };
+        let subject = self.temp(&subject_ty)?;
+        self.inst(format!("local.set {}", subject));
+        let result = self
+            .wasm_type(&ty)?
+            .map(|wasm| self.new_local("match", wasm));
+        let r#enum = self.ctx.enum_of(&subject_ty);
+
+        let end = self.label("match");
+        self.open(format!("block {}", end));
+        for arm in arms {
+            let tested = match (&arm.pattern, r#enum) {
+                (Pattern::Variant { name, .. }, Some(_)) => {
+                    let (_, tag) = self.ctx.variant(None, name, Some(&subject_ty))?;
+                    self.inst(format!("local.get {}", subject));
+                    self.inst("i32.load");
+                    self.inst(format!("i32.const {}", tag));
+                    self.inst("i32.eq");
+                    true
+                }
+                (Pattern::Variant { .. }, None) => {
+                    return self.ctx.error("variant patterns can only match enums")
+                }
+                (Pattern::Wildcard | Pattern::Binding(_), _) => false,
+                (_, Some(r#enum)) => {
+                    return self.ctx.error(format!(
+                        "`{}` can only be matched by its variants",
+                        r#enum.name
+                    ))
+                }
+                (pattern, None) => self.emit_pattern_test(&subject, &subject_ty, pattern)?,
+            };
+            if tested {
+                self.open("if");
+            }
+            self.scoped(|e| {
+                for (name, binding_ty) in e.ctx.pattern_bindings(&subject_ty, &arm.pattern)? {
+                    e.inst(format!("local.get {}", subject));
+                    if let Pattern::Variant {
+                        name: variant,
+                        bindings,
+                    } = &arm.pattern
+                    {
+                        let (r#enum, tag) = e.ctx.variant(None, variant, Some(&subject_ty))?;
+                        let (offsets, _) = e.payload_offsets(r#enum, tag)?;
+                        let index = bindings.iter().position(|b| *b == name).unwrap_or(0);
+                        e.load(&binding_ty, offsets[index])?;
+                    }
+                    e.declare_local(&name, binding_ty)?;
+                }
+                let found = e.emit_expr(&arm.body, result.as_ref().map(|_| &ty))?;
+                match &result {
+                    Some(result) => {
+                        e.coerce(&found, &ty)?;
+                        e.inst(format!("local.set {}", result));
+                    }
+                    None if e.wasm_type(&found)?.is_some() => e.inst("drop"),
+                    None => {}
+                }
+                Ok(())
+            })?;
+            self.inst(format!("br {}", end));
+            if tested {
+                self.close("end");
+            }
+        }
+        self.inst("unreachable");
+        self.close("end");
+
+        if let Some(result) = result {
+            self.inst(format!("local.get {}", result));
+        }
+        Ok(ty)
+    }
+
+    // Leaves whether the subject matches on the stack, false means the pattern always matches and
+    // nothing was emitted.
+    fn emit_pattern_test(
+        &mut self,
+        subject: &str,
+        subject_ty: &Type,
+        pattern: &Pattern,
+    ) -> CodegenResult<bool> {
+        match pattern {
+            Pattern::Wildcard | Pattern::Binding(_) | Pattern::Variant { .. } => Ok(false),
+            Pattern::Literal(literal) => {
+                self.compare_value(BinaryOp::Eq, subject, subject_ty, literal)?;
+                Ok(true)
+            }
+            Pattern::Range { start, end } => {
+                if let Some(start) = start {
+                    self.compare_value(BinaryOp::Ge, subject, subject_ty, start)?;
+                }
+                if let Some(end) = end {
+                    self.compare_value(BinaryOp::Le, subject, subject_ty, end)?;
+                }
+                match (start, end) {
+                    (Some(_), Some(_)) => self.inst("i32.and"),
+                    (None, None) => return Ok(false),
+                    _ => {}
+                }
+                Ok(true)
+            }
+        }
+    }
+
+    fn compare_value(
+        &mut self,
+        op: BinaryOp,
+        subject: &str,
+        subject_ty: &Type,
+        literal: &Literal,
+    ) -> CodegenResult<()> {
+        self.inst(format!("local.get {}", subject));
+        let found = self.literal(literal, Some(subject_ty))?;
+        self.coerce(&found, subject_ty)?;
+        let wasm = self.value_type(subject_ty)?;
+        let (signed, unsigned, float) = match op {
+            BinaryOp::Eq => ("eq", "eq", "eq"),
+            BinaryOp::Ge => ("ge_s", "ge_u", "ge"),
+            _ => ("le_s", "le_u", "le"),
+        };
+        match subject_ty {
+            Type::Float { .. } => self.inst(format!("{}.{}", wasm, float)),
+            Type::Int { signed: false, .. } | Type::Bool => {
+                self.inst(format!("{}.{}", wasm, unsigned))
+            }
+            Type::String => {
+                self.helper("elp.string.compare");
+                self.inst("call $elp.string.compare");
+                self.inst("i32.const 0");
+                self.inst(format!("i32.{}", signed));
+            }
+            _ => self.inst(format!("{}.{}", wasm, signed)),
+        }
+        Ok(())
+    }
+}
+
+pub fn emit_module(module: &Module) -> CodegenResult<WasmOutput> {
+    let module = monomorphize(module)?;
+    let wat = Emitter::new(&module).emit_module()?;
+    let wasm = wat::parse_str(&wat).map_err(|err| CodegenError {
+        span: Span::default(),
+        message: format!("generated WebAssembly doesn't assemble: {}", err),
+    })?;
+    Ok(WasmOutput { wat, wasm })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::*;
+    use elp_ir::mir::{Closure, FieldDef, ObjectDef, Param};
+    use pretty_assertions::assert_eq;
+    use std::process::Command;
+
+    #[test]
+    fn emits_functions_and_externals() {
+        let mut module = lower_source(
+            "fn puts(text string) -> int32
+
+            fn main {
+                puts(\"hello\")
+            }",
+        );
+        module.functions.insert(
+            0,
+            Function {
+                params: vec![
+                    Param {
+                        name: "a".into(),
+                        ty: int32(),
+                    },
+                    Param {
+                        name: "b".into(),
+                        ty: int32(),
+                    },
+                ],
+                ret: int32(),
+                exported: true,
+                ..function(
+                    "add",
+                    vec![ret(binary(BinaryOp::Add, local("a"), local("b")))],
+                )
+            },
+        );
+
+        let output = emit_module(&module).unwrap();
+        assert_eq!(
+            output.wat,
+            r#";; Generated from test.elp.
+(module
+  (import "env" "puts" (func $puts (param i32) (result i32)))
+  (memory (export "memory") 1)
+  (data (i32.const 64) "hello\00")
+  (func $add (export "add") (param $a i32) (param $b i32) (result i32)
+    local.get $a
+    local.get $b
+    i32.add
+    return
+  )
+  (func $main
+    i32.const 64
+    call $puts
+    drop
+  )
+  (func $_start (export "_start")
+    call $main
+  )
+)
+"#
+        );
+        assert!(output.wasm.starts_with(b"\0asm"));
+        assert_runs(
+            &output,
+            "env.puts = (text) => { process.stdout.write(string(text) + \"\\n\"); return 0; };",
+            "",
+            0,
+            "hello\n",
+        );
+    }
+
+    #[test]
+    fn exported_functions_can_be_called_by_the_host() {
+        let point = named("Point");
+        let module = Module {
+            name: "test".into(),
+            objects: vec![ObjectDef {
+                span: Span::default(),
+                name: "Point".into(),
+                generics: vec![],
+                fields: vec![
+                    FieldDef {
+                        span: Span::default(),
+                        name: "x".into(),
+                        ty: INT,
+                        default: None,
+                        public: true,
+                        tags: vec![],
+                    },
+                    FieldDef {
+                        span: Span::default(),
+                        name: "y".into(),
+                        ty: int32(),
+                        default: Some(int(2)),
+                        public: true,
+                        tags: vec![],
+                    },
+                ],
+                implements: vec![],
+                exported: true,
+            }],
+            functions: vec![
+                Function {
+                    receiver: Some("Point".into()),
+                    params: vec![Param {
+                        name: "self".into(),
+                        ty: point.clone(),
+                    }],
+                    ret: INT,
+                    exported: true,
+                    ..function(
+                        "sum",
+                        vec![ret(binary(
+                            BinaryOp::Add,
+                            field(local("self"), "x"),
+                            field(local("self"), "y"),
+                        ))],
+                    )
+                },
+                Function {
+                    params: vec![Param {
+                        name: "x".into(),
+                        ty: INT,
+                    }],
+                    ret: INT,
+                    exported: true,
+                    ..function(
+                        "point_sum",
+                        vec![
+                            let_(
+                                "point",
+                                Expr::Object {
+                                    name: "Point".into(),
+                                    fields: vec![("x".into(), local("x"))],
+                                },
+                            ),
+                            ret(Expr::MethodCall {
+                                receiver: Box::new(local("point")),
+                                method: "sum".into(),
+                                args: vec![],
+                            }),
+                        ],
+                    )
+                },
+            ],
+            ..Default::default()
+        };
+
+        let output = emit_module(&module).unwrap();
+        assert!(output
+            .wat
+            .contains("(func $Point.sum (export \"Point.sum\") (param $self i32) (result i64)"));
+        assert!(output.wat.contains("    i64.store\n"));
+        assert!(output.wat.contains("    i32.store offset=8\n"));
+        assert_runs(
+            &output,
+            "",
+            "console.log(String(exports.point_sum(40n)));",
+            0,
+            "42\n",
+        );
+    }
+
+    #[test]
+    fn enums_and_matches() {
+        let mut module = shapes();
+        module.functions.push(Function {
+            ret: int32(),
+            ..function(
+                "main",
+                vec![ret(Expr::Call {
+                    callee: Box::new(Expr::Global("area".into())),
+                    args: vec![Expr::Variant {
+                        enum_name: Some("Shape".into()),
+                        variant: "RECT".into(),
+                        args: vec![int(6), int(7)],
+                    }],
+                })],
+            )
+        });
+
+        let output = emit_module(&module).unwrap();
+        assert!(output.wat.contains("call $wasi.proc_exit"));
+        assert_runs(&output, "", "", 42, "");
+    }
+
+    #[test]
+    fn arrays_and_printing() {
+        let module = Module {
+            functions: vec![function(
+                "main",
+                vec![
+                    let_(
+                        "names",
+                        Expr::Array(vec![
+                            Expr::Literal(Literal::String("a".into())),
+                            Expr::Literal(Literal::String("b".into())),
+                        ]),
+                    ),
+                    stmt(StmtKind::Expr(Expr::MethodCall {
+                        receiver: Box::new(local("names")),
+                        method: "append".into(),
+                        args: vec![Expr::Literal(Literal::String("c".into()))],
+                    })),
+                    stmt(StmtKind::For {
+                        binding: "name".into(),
+                        iterable: local("names"),
+                        body: vec![stmt(StmtKind::Expr(Expr::Call {
+                            callee: Box::new(Expr::Global("println".into())),
+                            args: vec![binary(
+                                BinaryOp::Add,
+                                local("name"),
+                                Expr::Literal(Literal::String("!".into())),
+                            )],
+                        }))],
+                    }),
+                    println(Expr::MethodCall {
+                        receiver: Box::new(local("names")),
+                        method: "len".into(),
+                        args: vec![],
+                    }),
+                    println(binary(BinaryOp::Sub, int(1), int(43))),
+                    println(Expr::Literal(Literal::Float(-2.5))),
+                    println(binary(BinaryOp::Lt, int(1), int(2))),
+                    println(binary(BinaryOp::Pow, int(2), int(10))),
+                ],
+            )],
+            ..Default::default()
+        };
+
+        let output = emit_module(&module).unwrap();
+        assert!(output.wat.contains("call $elp.array.push"));
+        assert_runs(
+            &output,
+            "",
+            "",
+            0,
+            "a!\nb!\nc!\n3\n-42\n-2.500000\ntrue\n1024\n",
+        );
+    }
+
+    #[test]
+    fn narrow_integers_wrap() {
+        let int8 = Type::Int {
+            bits: 8,
+            signed: true,
+        };
+        let module = Module {
+            functions: vec![function(
+                "main",
+                vec![
+                    stmt(StmtKind::Let {
+                        name: "small".into(),
+                        mutable: true,
+                        ty: int8.clone(),
+                        value: Some(int(127)),
+                    }),
+                    stmt(StmtKind::Assign {
+                        target: local("small"),
+                        op: Some(BinaryOp::Add),
+                        value: int(1),
+                    }),
+                    println(local("small")),
+                ],
+            )],
+            ..Default::default()
+        };
+
+        let output = emit_module(&module).unwrap();
+        assert!(output.wat.contains("i32.extend8_s"));
+        assert_runs(&output, "", "", 0, "-128\n");
+    }
+
+    #[test]
+    fn closures_are_an_error() {
+        let module = Module {
+            functions: vec![function(
+                "main",
+                vec![let_(
+                    "noop",
+                    Expr::Closure(Box::new(Closure {
+                        params: vec![],
+                        ret: Type::Void,
+                        body: vec![],
+                    })),
+                )],
+            )],
+            ..Default::default()
+        };
+
+        let err = emit_module(&module).unwrap_err();
+        assert_eq!(
+            err.message,
+            "closures aren't supported by the WebAssembly backend yet"
+        );
+    }
+
+    fn println(value: Expr) -> Stmt {
+        stmt(StmtKind::Expr(Expr::Call {
+            callee: Box::new(Expr::Global("println".into())),
+            args: vec![value],
+        }))
+    }
+
+    // Instantiates the module in node with WASI and `env` set up by `imports`, then either runs
+    // `_start` or the given script against its exports and checks the exit code and output.
+    // Skipped when there's no `node` so the tests still pass without one.
+    fn assert_runs(output: &WasmOutput, imports: &str, script: &str, code: i32, stdout: &str) {
+        let dir = std::env::temp_dir().join(format!(
+            "elp-wasm-{}-{}",
+            std::process::id(),
+            output.wat.len()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("test.wasm"), &output.wasm).unwrap();
+        let run = match script {
+            "" => "process.exitCode = wasi.start(instance);".to_string(),
+            script => format!("const exports = instance.exports;\n{}", script),
+        };
+        std::fs::write(
+            dir.join("run.mjs"),
+            format!(
+                r#"import {{ readFile }} from "node:fs/promises";
+import {{ WASI }} from "node:wasi";
+
+const wasi = new WASI({{ version: "preview1", returnOnExit: true }});
+const env = {{}};
+let memory;
+const string = (pointer) => {{
+  const bytes = new Uint8Array(memory.buffer, pointer);
+  return new TextDecoder().decode(bytes.subarray(0, bytes.indexOf(0)));
+}};
+{}
+const module = await WebAssembly.compile(await readFile(new URL("./test.wasm", import.meta.url)));
+const instance = await WebAssembly.instantiate(module, {{ ...wasi.getImportObject(), env }});
+memory = instance.exports.memory;
+{}
+"#,
+                imports, run
+            ),
+        )
+        .unwrap();
+
+        let Ok(ran) = Command::new("node")
+            .arg("--no-warnings")
+            .arg(dir.join("run.mjs"))
+            .output()
+        else {
+            let _ = std::fs::remove_dir_all(&dir);
+            return;
+        };
+        let _ = std::fs::remove_dir_all(&dir);
+        assert_eq!(
+            (
+                ran.status.code(),
+                String::from_utf8_lossy(&ran.stdout).to_string(),
+                String::from_utf8_lossy(&ran.stderr).to_string()
+            ),
+            (Some(code), stdout.to_string(), String::new()),
+            "{}",
+            output.wat
+        );
+    }
+}
@@ -0,0 +1,1358 @@
+// Lowers the MIR to an ES module for the web, plus a stylesheet and an HTML page to load them.
+// JavaScript is dynamically typed so unlike the native backends nothing has to be annotated, types
+// are only looked at where JavaScript would otherwise get the answer wrong such as integer
+// division. Components become functions returning DOM nodes, their `var`s are state and assigning
+// to one re-renders the component on the next microtask.
+
+use std::fmt::Write;
+
+use elp_ir::{
+    mir::{
+        BinaryOp, ComponentDef, Element, Expr, Function, Literal, MatchArm, Module, Pattern, Stmt,
+        StmtKind, Type, UnaryOp,
+    },
+    style::{Selector, StyleSheet, StyleValue},
+};
+
+use crate::typing::{is_builtin, CodegenResult, TypeContext};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct WebOutput {
+    // The ES module, meant to be saved as `<module name>.js`.
+    pub script: String,
+    // Saved as `<module name>.css`, only there when the module has components or styles.
+    pub stylesheet: Option<String>,
+    // A page that loads the module and mounts its entry point, only there when there is one.
+    pub html: Option<String>,
+}
+
+const RESERVED: &[&str] = &[
+    "arguments",
+    "await",
+    "break",
+    "case",
+    "catch",
+    "class",
+    "const",
+    "continue",
+    "debugger",
+    "default",
+    "delete",
+    "do",
+    "else",
+    "enum",
+    "eval",
+    "export",
+    "extends",
+    "false",
+    "finally",
+    "for",
+    "function",
+    "if",
+    "implements",
+    "import",
+    "in",
+    "instanceof",
+    "interface",
+    "let",
+    "new",
+    "null",
+    "package",
+    "private",
+    "protected",
+    "public",
+    "return",
+    "static",
+    "super",
+    "switch",
+    "this",
+    "throw",
+    "true",
+    "try",
+    "typeof",
+    "undefined",
+    "var",
+    "void",
+    "while",
+    "with",
+    "yield",
+];
+
+// `Point.sum` becomes `Point$sum`, reserved words get a trailing `$`.
+fn ident(name: &str) -> String {
+    let ident = name.replace('.', "$");
+    if RESERVED.contains(&ident.as_str()) {
+        format!("{}$", ident)
+    } else {
+        ident
+    }
+}
+
+fn string_literal(value: &str) -> String {
+    let mut out = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+// `$components/message` is `./components/message.js`, the `elp/..` standard library is built into
+// the runtime.
+fn import_path(path: &str) -> Option<String> {
+    if path == "elp" || path.starts_with("elp/") {
+        return None;
+    }
+    let path = match path.strip_prefix('$') {
+        Some(rest) => format!("./{}", rest),
+        None if path.starts_with('.') => path.to_string(),
+        None => format!("./{}", path),
+    };
+    Some(format!("{}.js", path))
+}
+
+// `fontFamily` becomes `font-family`.
+fn kebab_case(name: &str) -> String {
+    let mut out = String::new();
+    for c in name.chars() {
+        if c.is_ascii_uppercase() {
+            out.push('-');
+            out.push(c.to_ascii_lowercase());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+// CSS properties that take plain numbers, everything else numeric is in pixels.
+const UNITLESS: &[&str] = &[
+    "flex",
+    "flex-grow",
+    "flex-shrink",
+    "font-weight",
+    "line-height",
+    "opacity",
+    "order",
+    "z-index",
+];
+
+fn font_weight(variant: &str) -> Option<&'static str> {
+    Some(match variant {
+        "THIN" => "100",
+        "EXTRA_LIGHT" => "200",
+        "LIGHT" => "300",
+        "REGULAR" | "NORMAL" => "400",
+        "MEDIUM" => "500",
+        "SEMIBOLD" => "600",
+        "BOLD" => "700",
+        "EXTRA_BOLD" => "800",
+        "BLACK" => "900",
+        _ => return None,
+    })
+}
+
+const RUNTIME: &str = r#"// Names of the built in elements and the HTML tags they render as.
+const $tags = {
+  App: "div",
+  Window: "main",
+  Column: "div",
+  Row: "div",
+  Text: "span",
+  Button: "button",
+  Form: "form",
+  Label: "label",
+  Input: "input",
+  Link: "a",
+  Image: "img",
+};
+
+// Adds a child to a list of nodes, text becomes a text node and nothing is skipped.
+function $push(nodes, value) {
+  if (value === undefined || value === null) return;
+  if (Array.isArray(value)) {
+    for (const item of value) $push(nodes, item);
+    return;
+  }
+  nodes.push(typeof value === "object" && "nodeType" in value ? value : document.createTextNode(String(value)));
+}
+
+function $h(name, props, args, children) {
+  const tag = $tags[name] ?? (name.toLowerCase() === name ? name : "div");
+  const node = document.createElement(tag);
+  node.className = "elp-" + name;
+  for (const [key, value] of Object.entries(props)) {
+    if (key.startsWith("on") && typeof value === "function") {
+      const event = key.slice(2).toLowerCase();
+      // Forms are handled in the page rather than by navigating away.
+      node.addEventListener(event, (e) => {
+        if (event === "submit") e.preventDefault();
+        value(e);
+      });
+    } else if (key === "class") {
+      node.className += " " + value;
+    } else if (key === "to" && tag === "a") {
+      node.setAttribute("href", String(value));
+    } else if (value === true) {
+      node.setAttribute(key, "");
+    } else if (value !== false && value !== null && value !== undefined) {
+      node.setAttribute(key, String(value));
+    }
+  }
+  const nodes = [];
+  $push(nodes, args);
+  $push(nodes, children);
+  for (const child of nodes) node.append(child);
+  return node;
+}
+
+// A component's view, rendering it again replaces the nodes it rendered last time. Updates are
+// batched so several assignments in one event handler only render once.
+function $component(render) {
+  const view = { node: null, queued: false };
+  const draw = () => {
+    const nodes = [];
+    $push(nodes, render());
+    if (nodes.length === 1) return nodes[0];
+    const fragment = document.createElement("div");
+    fragment.className = "elp-fragment";
+    for (const node of nodes) fragment.append(node);
+    return fragment;
+  };
+  view.mount = () => (view.node = draw());
+  view.update = () => {
+    if (view.queued) return;
+    view.queued = true;
+    queueMicrotask(() => {
+      view.queued = false;
+      const next = draw();
+      view.node.replaceWith(next);
+      view.node = next;
+    });
+  };
+  return view;
+}
+"#;
+
+const BASE_STYLES: &str = ".elp-Column {
+  display: flex;
+  flex-direction: column;
+}
+
+.elp-Row {
+  display: flex;
+  flex-direction: row;
+}
+
+.elp-fragment {
+  display: contents;
+}
+";
+
+struct Emitter<'m> {
+    ctx: TypeContext<'m>,
+    code: String,
+    indent: usize,
+    // The state of the component being emitted, assigning to one of these renders it again.
+    state: Vec<String>,
+}
+
+impl<'m> Emitter<'m> {
+    fn new(module: &'m Module) -> Self {
+        Self {
+            ctx: TypeContext::new(module),
+            code: String::new(),
+            indent: 0,
+            state: vec![],
+        }
+    }
+
+    // Types are only a hint here, anything that can't be worked out is treated as unknown.
+    fn type_of(&self, expr: &Expr) -> Type {
+        self.ctx.type_of(expr).unwrap_or(Type::Unknown)
+    }
+
+    fn line(&mut self, text: impl AsRef<str>) {
+        for _ in 0..self.indent {
+            self.code.push_str("  ");
+        }
+        self.code.push_str(text.as_ref());
+        self.code.push('\n');
+    }
+
+    fn scoped<T>(&mut self, f: impl FnOnce(&mut Self) -> CodegenResult<T>) -> CodegenResult<T> {
+        self.ctx.push_scope();
+        self.indent += 1;
+        let result = f(self);
+        self.indent -= 1;
+        self.ctx.pop_scope();
+        result
+    }
+
+    // Emits statements into their own buffer, for bodies that end up inside an expression.
+    fn capture(&mut self, f: impl FnOnce(&mut Self) -> CodegenResult<()>) -> CodegenResult<String> {
+        let outer = std::mem::take(&mut self.code);
+        let result = self.scoped(f);
+        let code = std::mem::replace(&mut self.code, outer);
+        result?;
+        Ok(code)
+    }
+
+    fn closing_indent(&self) -> String {
+        "  ".repeat(self.indent)
+    }
+
+    fn zero_value(&self, ty: &Type) -> &'static str {
+        match ty {
+            Type::Bool => "false",
+            Type::Int { .. } | Type::Float { .. } => "0",
+            Type::String => "\"\"",
+            Type::Array(_) => "[]",
+            _ => "null",
+        }
+    }
+
+    fn emit_module(mut self) -> CodegenResult<WebOutput> {
+        let module = self.ctx.module;
+        let mut script = format!("// Generated from {}.elp.\n", module.name);
+
+        for import in &module.imports {
+            let Some(path) = import_path(&import.module_path) else {
+                continue;
+            };
+            let names = import
+                .names
+                .iter()
+                .map(|(name, alias)| match alias {
+                    Some(alias) => format!("{} as {}", ident(name), ident(alias)),
+                    None => ident(name),
+                })
+                .collect::<Vec<_>>();
+            if !script.ends_with(";\n") {
+                script.push('\n');
+            }
+            let _ = writeln!(
+                script,
+                "import {{ {} }} from {};",
+                names.join(", "),
+                string_literal(&path)
+            );
+        }
+
+        if !module.components.is_empty() {
+            script.push('\n');
+            script.push_str(RUNTIME);
+        }
+
+        for global in &module.globals {
+            self.ctx.span = global.span;
+            let value = self.emit_expr(&global.value)?;
+            script.push('\n');
+            let _ = writeln!(
+                script,
+                "{}{} {} = {};",
+                if global.exported { "export " } else { "" },
+                if global.mutable { "let" } else { "const" },
+                ident(&global.name),
+                value
+            );
+        }
+
+        for function in &module.functions {
+            script.push('\n');
+            script.push_str(&self.emit_function(function)?);
+        }
+        for component in &module.components {
+            script.push('\n');
+            script.push_str(&self.emit_component(component)?);
+        }
+
+        let stylesheet = match module.components.is_empty() && module.styles.rules.is_empty() {
+            true => None,
+            false => Some(emit_styles(&module.styles)),
+        };
+        let html = self.emit_html(stylesheet.is_some());
+
+        Ok(WebOutput {
+            script,
+            stylesheet,
+            html,
+        })
+    }
+
+    fn emit_function(&mut self, function: &Function) -> CodegenResult<String> {
+        self.ctx.span = function.span;
+        let is_main = function.name == "main" && function.receiver.is_none();
+        let params = function
+            .params
+            .iter()
+            .map(|param| ident(&param.name))
+            .collect::<Vec<_>>();
+
+        self.code.clear();
+        self.indent = 0;
+        self.state.clear();
+        let body = self.capture(|e| {
+            for param in &function.params {
+                e.ctx.declare(&param.name, param.ty.clone());
+            }
+            function.body.iter().try_for_each(|stmt| e.emit_stmt(stmt))
+        })?;
+
+        Ok(format!(
+            "{}function {}({}) {{\n{}}}\n",
+            if function.exported || is_main {
+                "export "
+            } else {
+                ""
+            },
+            ident(&function.qualified_name()),
+            params.join(", "),
+            body
+        ))
+    }
+
+    // Props come in as an object so they can be passed by name, children as a list of nodes.
+    // Only the state is set up once, everything else runs again on every render so values derived
+    // from state stay up to date.
+    fn emit_component(&mut self, component: &ComponentDef) -> CodegenResult<String> {
+        self.ctx.span = component.span;
+        self.code.clear();
+        self.indent = 0;
+        self.state = component
+            .body
+            .iter()
+            .filter_map(|stmt| match &stmt.kind {
+                StmtKind::Let {
+                    name,
+                    mutable: true,
+                    ..
+                } => Some(name.clone()),
+                _ => None,
+            })
+            .collect();
+
+        let body = self.capture(|e| {
+            for param in &component.params {
+                e.ctx.declare(&param.name, param.ty.clone());
+                e.line(format!(
+                    "const {} = $props.{};",
+                    ident(&param.name),
+                    param.name
+                ));
+            }
+            for prop in &component.props {
+                e.ctx.declare(&prop.name, prop.ty.clone());
+                let value = format!("$props.{}", prop.name);
+                let value = match &prop.default {
+                    Some(default) => format!("{} ?? {}", value, e.emit_expr(default)?),
+                    None => value,
+                };
+                e.line(format!("const {} = {};", ident(&prop.name), value));
+            }
+            let (state, render): (Vec<_>, Vec<_>) = component
+                .body
+                .iter()
+                .partition(|stmt| matches!(stmt.kind, StmtKind::Let { mutable: true, .. }));
+            for stmt in state {
+                e.emit_stmt(stmt)?;
+            }
+            e.line("const $view = $component(() => {");
+            e.scoped(|e| e.emit_children(render.into_iter()))?;
+            e.line("});");
+            e.line("return $view.mount();");
+            Ok(())
+        })?;
+        self.state.clear();
+
+        Ok(format!(
+            "{}function {}($props = {{}}, $children = []) {{\n{}}}\n",
+            if component.exported { "export " } else { "" },
+            ident(&component.name),
+            body
+        ))
+    }
+
+    // Statements that build up a list of nodes, every expression statement's value is added to
+    // `$nodes` and a `return` hands back its value instead.
+    fn emit_children<'s>(&mut self, stmts: impl Iterator<Item = &'s Stmt>) -> CodegenResult<()> {
+        self.line("const $nodes = [];");
+        for stmt in stmts {
+            self.emit_child(stmt)?;
+        }
+        self.line("return $nodes;");
+        Ok(())
+    }
+
+    fn emit_child(&mut self, stmt: &Stmt) -> CodegenResult<()> {
+        self.ctx.span = stmt.span;
+        match &stmt.kind {
+            StmtKind::Expr(Expr::Global(name)) if name == "Slot" => {
+                self.line("$push($nodes, $children);");
+            }
+            StmtKind::Expr(Expr::Call { callee, .. }) if matches!(&**callee, Expr::Global(name) if is_builtin(name)) =>
+            {
+                self.emit_stmt(stmt)?;
+            }
+            StmtKind::Expr(expr) => {
+                let value = self.emit_expr(expr)?;
+                self.line(format!("$push($nodes, {});", value));
+            }
+            StmtKind::If {
+                condition,
+                then_body,
+                else_body,
+            } => {
+                let condition = self.emit_expr(condition)?;
+                self.line(format!("if ({}) {{", condition));
+                self.scoped(|e| then_body.iter().try_for_each(|s| e.emit_child(s)))?;
+                if !else_body.is_empty() {
+                    self.line("} else {");
+                    self.scoped(|e| else_body.iter().try_for_each(|s| e.emit_child(s)))?;
+                }
+                self.line("}");
+            }
+            StmtKind::For {
+                binding,
+                iterable,
+                body,
+            } => {
+                let iterable_ty = self.type_of(iterable);
+                let iterable = self.emit_expr(iterable)?;
+                self.line(format!("for (const {} of {}) {{", ident(binding), iterable));
+                self.scoped(|e| {
+                    if let Type::Array(item) = iterable_ty {
+                        e.ctx.declare(binding, *item);
+                    }
+                    body.iter().try_for_each(|s| e.emit_child(s))
+                })?;
+                self.line("}");
+            }
+            _ => self.emit_stmt(stmt)?,
+        }
+        Ok(())
+    }
+
+    fn emit_stmt(&mut self, stmt: &Stmt) -> CodegenResult<()> {
+        self.ctx.span = stmt.span;
+
+        match &stmt.kind {
+            StmtKind::Let {
+                name,
+                mutable,
+                ty,
+                value,
+            } => {
+                let declared = match (ty, value) {
+                    (Type::Unknown, Some(value)) => self.type_of(value),
+                    (ty, _) => ty.clone(),
+                };
+                let value = match value {
+                    Some(value) => self.emit_expr(value)?,
+                    None => self.zero_value(&declared).into(),
+                };
+                self.line(format!(
+                    "{} {} = {};",
+                    if *mutable { "let" } else { "const" },
+                    ident(name),
+                    value
+                ));
+                self.ctx.declare(name, declared);
+            }
+            StmtKind::Assign { target, op, value } => {
+                let place = self.emit_expr(target)?;
+                let value = match op {
+                    Some(op) => self.emit_binary(*op, target, value)?,
+                    None => self.emit_expr(value)?,
+                };
+                self.line(format!("{} = {};", place, value));
+                if self.assigns_state(target) {
+                    self.line("$view.update();");
+                }
+            }
+            StmtKind::Expr(expr) => {
+                let value = self.emit_expr(expr)?;
+                self.line(format!("{};", value));
+            }
+            StmtKind::Return(value) => match value {
+                Some(value) => {
+                    let value = self.emit_expr(value)?;
+                    self.line(format!("return {};", value));
+                }
+                None => self.line("return;"),
+            },
+            StmtKind::If {
+                condition,
+                then_body,
+                else_body,
+            } => {
+                let condition = self.emit_expr(condition)?;
+                self.line(format!("if ({}) {{", condition));
+                self.scoped(|e| then_body.iter().try_for_each(|s| e.emit_stmt(s)))?;
+                if !else_body.is_empty() {
+                    self.line("} else {");
+                    self.scoped(|e| else_body.iter().try_for_each(|s| e.emit_stmt(s)))?;
+                }
+                self.line("}");
+            }
+            StmtKind::For {
+                binding,
+                iterable,
+                body,
+            } => {
+                let iterable_ty = self.type_of(iterable);
+                let iterable = self.emit_expr(iterable)?;
+                self.line(format!("for (const {} of {}) {{", ident(binding), iterable));
+                self.scoped(|e| {
+                    if let Type::Array(item) = iterable_ty {
+                        e.ctx.declare(binding, *item);
+                    }
+                    body.iter().try_for_each(|s| e.emit_stmt(s))
+                })?;
+                self.line("}");
+            }
+        }
+
+        Ok(())
+    }
+
+    // Whether an assignment changes component state, directly or through one of its fields.
+    fn assigns_state(&self, target: &Expr) -> bool {
+        match target {
+            Expr::Local(name) => self.state.contains(name),
+            Expr::Field { base, .. } => self.assigns_state(base),
+            _ => false,
+        }
+    }
+
+    fn literal(&self, literal: &Literal) -> String {
+        match literal {
+            Literal::Nil => "null".into(),
+            Literal::Bool(value) => value.to_string(),
+            Literal::Int(value) => value.to_string(),
+            Literal::Float(value) if value.is_nan() => "NaN".into(),
+            Literal::Float(value) if value.is_infinite() => if *value > 0.0 {
+                "Infinity"
+            } else {
+                "-Infinity"
+            }
+            .into(),
+            Literal::Float(value) => format!("{:?}", value),
+            Literal::String(value) => string_literal(value),
+        }
+    }
+
+    fn emit_exprs(&mut self, exprs: &[Expr]) -> CodegenResult<Vec<String>> {
+        exprs.iter().map(|expr| self.emit_expr(expr)).collect()
+    }
+
+    fn emit_expr(&mut self, expr: &Expr) -> CodegenResult<String> {
+        Ok(match expr {
+            Expr::Literal(literal) => self.literal(literal),
+            Expr::Local(name) => ident(name),
+            Expr::Global(name) => match self.qualified_variant(name) {
+                Some((_, variant)) => self.variant(&variant, vec![]),
+                None => ident(name),
+            },
+            Expr::Field { base, name } => format!("{}.{}", self.emit_expr(base)?, name),
+            Expr::Call { callee, args } => self.emit_call(callee, args)?,
+            Expr::MethodCall {
+                receiver,
+                method,
+                args,
+            } => self.emit_method_call(receiver, method, args)?,
+            Expr::Binary { op, lhs, rhs } => self.emit_binary(*op, lhs, rhs)?,
+            Expr::Unary { op, operand } => {
+                let operand = self.emit_expr(operand)?;
+                match op {
+                    UnaryOp::Not => format!("(!{})", operand),
+                    UnaryOp::Neg => format!("(-{})", operand),
+                }
+            }
+            Expr::Object { name, fields } => self.emit_object(name, fields)?,
+            Expr::Variant { variant, args, .. } => {
+                let args = self.emit_exprs(args)?;
+                self.variant(variant, args)
+            }
+            Expr::Array(items) => format!("[{}]", self.emit_exprs(items)?.join(", ")),
+            Expr::Closure(closure) => {
+                let params = closure
+                    .params
+                    .iter()
+                    .map(|param| ident(&param.name))
+                    .collect::<Vec<_>>();
+                let body = self.capture(|e| {
+                    for param in &closure.params {
+                        e.ctx.declare(&param.name, param.ty.clone());
+                    }
+                    closure.body.iter().try_for_each(|stmt| e.emit_stmt(stmt))
+                })?;
+                format!(
+                    "(({}) => {{\n{}{}}})",
+                    params.join(", "),
+                    body,
+                    self.closing_indent()
+                )
+            }
+            Expr::Match { scrutinee, arms } => self.emit_match(scrutinee, arms)?,
+            Expr::Element(element) => self.emit_element(element)?,
+        })
+    }
+
+    // `Shape.RECT` names a variant rather than a global.
+    fn qualified_variant(&self, name: &str) -> Option<(String, String)> {
+        let (enum_name, variant) = name.rsplit_once('.')?;
+        self.ctx
+            .module
+            .r#enum(enum_name)?
+            .variant_index(variant)
+            .map(|_| (enum_name.to_string(), variant.to_string()))
+    }
+
+    fn variant(&self, variant: &str, args: Vec<String>) -> String {
+        format!(
+            "{{ tag: {}, values: [{}] }}",
+            string_literal(variant),
+            args.join(", ")
+        )
+    }
+
+    fn emit_call(&mut self, callee: &Expr, args: &[Expr]) -> CodegenResult<String> {
+        if let Expr::Global(name) = callee {
+            let module = self.ctx.module;
+            if is_builtin(name) && self.ctx.signature(name).is_none() {
+                let args = self.emit_exprs(args)?;
+                return Ok(format!("console.log({})", args.join(", ")));
+            }
+            if let Some((_, variant)) = self.qualified_variant(name) {
+                let args = self.emit_exprs(args)?;
+                return Ok(self.variant(&variant, args));
+            }
+            // Calls to components and to capitalised names that aren't functions are elements,
+            // `Text("hi")` doesn't need a block.
+            let is_element = module.component(name).is_some()
+                || (self.ctx.signature(name).is_none()
+                    && !self.is_imported(name)
+                    && name.starts_with(|c: char| c.is_ascii_uppercase()));
+            if is_element {
+                return self.emit_element(&Element {
+                    name: name.clone(),
+                    args: args.to_vec(),
+                    props: vec![],
+                    children: vec![],
+                });
+            }
+        }
+
+        let callee = self.emit_expr(callee)?;
+        let args = self.emit_exprs(args)?;
+        Ok(format!("{}({})", callee, args.join(", ")))
+    }
+
+    fn is_imported(&self, name: &str) -> bool {
+        self.ctx.module.imports.iter().any(|import| {
+            import_path(&import.module_path).is_some()
+                && import
+                    .names
+                    .iter()
+                    .any(|(imported, alias)| alias.as_ref().unwrap_or(imported) == name)
+        })
+    }
+
+    fn emit_method_call(
+        &mut self,
+        receiver: &Expr,
+        method: &str,
+        args: &[Expr],
+    ) -> CodegenResult<String> {
+        let receiver_ty = self.type_of(receiver);
+        let value = self.emit_expr(receiver)?;
+        let arg_values = self.emit_exprs(args)?;
+
+        match (&receiver_ty, method, arg_values.as_slice()) {
+            // `append` hands back the index of the new item, `push` the new length.
+            (Type::Array(_) | Type::Unknown, "append", [arg]) => {
+                return Ok(format!("({}.push({}) - 1)", value, arg))
+            }
+            (Type::Array(_) | Type::String | Type::Unknown, "len" | "length", []) => {
+                return Ok(format!("{}.length", value))
+            }
+            _ => {}
+        }
+
+        // Without a known receiver type the method has to be the only one with that name.
+        let function = match receiver_ty.named() {
+            Some(receiver) => self
+                .ctx
+                .module
+                .function(&format!("{}.{}", receiver, method)),
+            None => {
+                let mut candidates = self
+                    .ctx
+                    .module
+                    .functions
+                    .iter()
+                    .filter(|f| f.name == method && f.is_method());
+                match (candidates.next(), candidates.next()) {
+                    (Some(function), None) => Some(function),
+                    _ => None,
+                }
+            }
+        };
+        let Some(function) = function else {
+            return self.ctx.error(format!("no method `{}` found", method));
+        };
+
+        let mut all = vec![];
+        if function.is_method() {
+            all.push(value);
+        }
+        all.extend(arg_values);
+        Ok(format!(
+            "{}({})",
+            ident(&function.qualified_name()),
+            all.join(", ")
+        ))
+    }
+
+    fn emit_binary(&mut self, op: BinaryOp, lhs: &Expr, rhs: &Expr) -> CodegenResult<String> {
+        let ty = self.ctx.operand_type(lhs, rhs).unwrap_or(Type::Unknown);
+        let is_enum = self.ctx.enum_of(&ty).is_some();
+        let lhs = self.emit_expr(lhs)?;
+        let rhs = self.emit_expr(rhs)?;
+
+        let operator = match op {
+            BinaryOp::Eq | BinaryOp::Ne if is_enum => {
+                let operator = if op == BinaryOp::Eq { "===" } else { "!==" };
+                return Ok(format!("({}.tag {} {}.tag)", lhs, operator, rhs));
+            }
+            BinaryOp::Div if matches!(ty, Type::Int { .. }) => {
+                return Ok(format!("Math.trunc({} / {})", lhs, rhs))
+            }
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::Mod => "%",
+            BinaryOp::Pow => "**",
+            BinaryOp::Eq => "===",
+            BinaryOp::Ne => "!==",
+            BinaryOp::Lt => "<",
+            BinaryOp::Le => "<=",
+            BinaryOp::Gt => ">",
+            BinaryOp::Ge => ">=",
+            BinaryOp::And => "&&",
+            BinaryOp::Or => "||",
+            BinaryOp::BitAnd => "&",
+            BinaryOp::BitOr => "|",
+            BinaryOp::Shl => "<<",
+            BinaryOp::Shr => ">>",
+        };
+
+        Ok(format!("({} {} {})", lhs, operator, rhs))
+    }
+
+    fn emit_object(&mut self, name: &str, fields: &[(String, Expr)]) -> CodegenResult<String> {
+        let Some(object) = self.ctx.module.object(name) else {
+            return self.ctx.error(format!("unknown object `{}`", name));
+        };
+        for (field, _) in fields {
+            if object.field_index(field).is_none() {
+                return self
+                    .ctx
+                    .error(format!("`{}` has no field `{}`", name, field));
+            }
+        }
+
+        let mut values = vec![];
+        for field in &object.fields {
+            let value = match fields.iter().find(|(name, _)| *name == field.name) {
+                Some((_, value)) => self.emit_expr(value)?,
+                None => match &field.default {
+                    Some(value) => self.emit_expr(value)?,
+                    None => self.zero_value(&field.ty).into(),
+                },
+            };
+            values.push(format!("{}: {}", field.name, value));
+        }
+
+        Ok(match values.is_empty() {
+            true => "{}".into(),
+            false => format!("{{ {} }}", values.join(", ")),
+        })
+    }
+
+    // Matches are arrow functions called on the spot, every arm returns from it.
+    fn emit_match(&mut self, scrutinee: &Expr, arms: &[MatchArm]) -> CodegenResult<String> {
+        let subject_ty = self.type_of(scrutinee);
+        let subject = self.emit_expr(scrutinee)?;
+
+        let body = self.capture(|e| {
+            for arm in arms {
+                let test = e.pattern_test(&arm.pattern);
+                match &test {
+                    Some(test) => e.line(format!("if ({}) {{", test)),
+                    None => e.line("{"),
+                }
+                e.scoped(|e| {
+                    if let Pattern::Variant { bindings, .. } = &arm.pattern {
+                        for (index, binding) in bindings.iter().enumerate() {
+                            e.line(format!(
+                                "const {} = $subject.values[{}];",
+                                ident(binding),
+                                index
+                            ));
+                        }
+                    }
+                    if let Pattern::Binding(binding) = &arm.pattern {
+                        e.line(format!("const {} = $subject;", ident(binding)));
+                    }
+                    let bindings = e
+                        .ctx
+                        .pattern_bindings(&subject_ty, &arm.pattern)
+                        .unwrap_or_default();
+                    for (name, ty) in bindings {
+                        e.ctx.declare(&name, ty);
+                    }
+                    let value = e.emit_expr(&arm.body)?;
+                    e.line(format!("return {};", value));
+                    Ok(())
+                })?;
+                e.line("}");
+                if test.is_none() {
+                    return Ok(());
+                }
+            }
+            e.line("throw new Error(\"no arm of the match matched\");");
+            Ok(())
+        })?;
+
+        Ok(format!(
+            "(($subject) => {{\n{}{}}})({})",
+            body,
+            self.closing_indent(),
+            subject
+        ))
+    }
+
+    // None means the pattern always matches.
+    fn pattern_test(&self, pattern: &Pattern) -> Option<String> {
+        match pattern {
+            Pattern::Wildcard | Pattern::Binding(_) => None,
+            Pattern::Literal(literal) => Some(format!("$subject === {}", self.literal(literal))),
+            Pattern::Range { start, end } => {
+                let lower = start
+                    .as_ref()
+                    .map(|start| format!("$subject >= {}", self.literal(start)));
+                let upper = end
+                    .as_ref()
+                    .map(|end| format!("$subject <= {}", self.literal(end)));
+                match (lower, upper) {
+                    (Some(lower), Some(upper)) => Some(format!("{} && {}", lower, upper)),
+                    (lower, upper) => lower.or(upper),
+                }
+            }
+            Pattern::Variant { name, .. } => {
+                Some(format!("$subject.tag === {}", string_literal(name)))
+            }
+        }
+    }
+
+    fn emit_element(&mut self, element: &Element) -> CodegenResult<String> {
+        let args = self.emit_exprs(&element.args)?;
+        let mut props = vec![];
+        for (name, value) in &element.props {
+            props.push(format!(
+                "{}: {}",
+                string_literal(name),
+                self.emit_expr(value)?
+            ));
+        }
+        let children = match element.children.is_empty() {
+            true => "[]".to_string(),
+            false => {
+                let body = self.capture(|e| e.emit_children(element.children.iter()))?;
+                format!("(() => {{\n{}{}}})()", body, self.closing_indent())
+            }
+        };
+
+        // Components are called with their positional arguments matched up to their props.
+        if let Some(component) = self.ctx.module.component(&element.name) {
+            for (param, arg) in component.params.iter().zip(&args) {
+                props.push(format!("{}: {}", string_literal(&param.name), arg));
+            }
+            return Ok(format!(
+                "{}({{ {} }}, {})",
+                ident(&component.name),
+                props.join(", "),
+                children
+            ));
+        }
+        if self.is_imported(&element.name) {
+            return Ok(format!(
+                "{}({{ {} }}, {})",
+                ident(&element.name),
+                props.join(", "),
+                children
+            ));
+        }
+
+        Ok(format!(
+            "$h({}, {{ {} }}, [{}], {})",
+            string_literal(&element.name),
+            props.join(", "),
+            args.join(", "),
+            children
+        ))
+    }
+
+    // The page mounts `main` when there is one and otherwise the first exported component.
+    fn emit_html(&self, has_stylesheet: bool) -> Option<String> {
+        let module = self.ctx.module;
+        let entry = match module.function("main") {
+            Some(_) => "main".to_string(),
+            None => ident(&module.components.iter().find(|c| c.exported)?.name),
+        };
+
+        let mut out = format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n",
+            module.name
+        );
+        if has_stylesheet {
+            let _ = writeln!(
+                out,
+                "<link rel=\"stylesheet\" href=\"{}.css\">",
+                module.name
+            );
+        }
+        let _ = write!(
+            out,
+            "</head>\n<body>\n<script type=\"module\">\nimport {{ {} }} from \"./{}.js\";\n\nconst root = {}();\nif (root instanceof Node) document.body.append(root);\n</script>\n</body>\n</html>\n",
+            entry, module.name, entry
+        );
+        Some(out)
+    }
+}
+
+pub fn emit_module(module: &Module) -> CodegenResult<WebOutput> {
+    Emitter::new(module).emit_module()
+}
+
+// Components are matched by the `elp-<name>` class every element gets, classes as they are.
+fn emit_styles(styles: &StyleSheet) -> String {
+    let mut out = String::from(BASE_STYLES);
+    for rule in &styles.rules {
+        let selectors = rule.selectors.iter().map(css_selector).collect::<Vec<_>>();
+        let _ = writeln!(out, "\n{} {{", selectors.join(", "));
+        for property in &rule.properties {
+            let name = kebab_case(&property.name);
+            let value = style_value(&name, &property.value);
+            let _ = writeln!(out, "  {}: {};", name, value);
+        }
+        out.push_str("}\n");
+    }
+    out
+}
+
+fn css_selector(selector: &Selector) -> String {
+    let mut css = selector
+        .component
+        .as_ref()
+        .map(|component| format!(".elp-{}", component))
+        .unwrap_or_default();
+    for class in &selector.classes {
+        let _ = write!(css, ".{}", class);
+    }
+    css
+}
+
+fn style_value(property: &str, value: &StyleValue) -> String {
+    match value {
+        StyleValue::Number(number) if UNITLESS.contains(&property) => number.to_string(),
+        StyleValue::Number(number) => format!("{}px", number),
+        StyleValue::Length(number, unit) => format!("{}{}", number, unit),
+        StyleValue::Colour(colour) => colour.to_string(),
+        StyleValue::String(text) if property == "font-family" => string_literal(text),
+        StyleValue::String(text) => text.clone(),
+        StyleValue::Enum { case, .. } => match font_weight(case) {
+            Some(weight) if property == "font-weight" => weight.into(),
+            _ => case.to_lowercase().replace('_', "-"),
+        },
+        StyleValue::Bool(value) => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::*;
+    use pretty_assertions::assert_eq;
+    use std::process::Command;
+
+    #[test]
+    fn emits_functions_as_an_es_module() {
+        let module = lower_source(
+            "fn greet(name string) -> string {
+                return name
+            }
+
+            fn main {
+                println(greet(\"hello\"))
+            }",
+        );
+
+        let output = emit_module(&module).unwrap();
+        assert_eq!(
+            output.script,
+            r#"// Generated from test.elp.
+
+function greet(name) {
+  return name;
+}
+
+export function main() {
+  console.log(greet("hello"));
+}
+"#
+        );
+        assert_eq!(output.stylesheet, None);
+        assert!(output
+            .html
+            .as_ref()
+            .unwrap()
+            .contains("import { main } from \"./test.js\";"));
+        assert_runs(&output, "main();", "hello\n");
+    }
+
+    #[test]
+    fn enums_and_matches() {
+        let mut module = shapes();
+        module.functions.push(function(
+            "main",
+            vec![stmt(StmtKind::Expr(Expr::Call {
+                callee: Box::new(Expr::Global("println".into())),
+                args: vec![Expr::Call {
+                    callee: Box::new(Expr::Global("area".into())),
+                    args: vec![Expr::Variant {
+                        enum_name: Some("Shape".into()),
+                        variant: "RECT".into(),
+                        args: vec![int(6), int(7)],
+                    }],
+                }],
+            }))],
+        ));
+
+        let output = emit_module(&module).unwrap();
+        assert!(output
+            .script
+            .contains("if ($subject.tag === \"RECT\") {\n      const w = $subject.values[0];"));
+        assert_runs(&output, "main();", "42\n");
+    }
+
+    #[test]
+    fn integer_division_truncates() {
+        let module = Module {
+            name: "test".into(),
+            functions: vec![function(
+                "main",
+                vec![
+                    let_("half", binary(BinaryOp::Div, int(7), int(2))),
+                    stmt(StmtKind::Expr(Expr::Call {
+                        callee: Box::new(Expr::Global("println".into())),
+                        args: vec![local("half")],
+                    })),
+                ],
+            )],
+            ..Default::default()
+        };
+
+        let output = emit_module(&module).unwrap();
+        assert!(output.script.contains("let half = Math.trunc(7 / 2);"));
+        assert_runs(&output, "main();", "3\n");
+    }
+
+    #[test]
+    fn components_render_and_update_on_state_changes() {
+        let module = lower_source(
+            "export component Counter(label string) {
+                var count = 0
+                fn increment() {
+                    count += 1
+                }
+                return Column(class = \"counter\") {
+                    Text(label)
+                    Button(onClick = increment) {
+                        Text(count)
+                    }
+                }
+            }",
+        );
+
+        let output = emit_module(&module).unwrap();
+        assert!(output.script.contains(
+            "export function Counter($props = {}, $children = []) {\n  const label = $props.label;\n  let count = 0;\n"
+        ));
+        assert!(output
+            .html
+            .as_ref()
+            .unwrap()
+            .contains("import { Counter } from \"./test.js\";"));
+        assert_runs(
+            &output,
+            r#"const root = Counter({ label: "clicks" });
+document.body.append(root);
+console.log(document.body.innerHTML);
+find(document.body, "button").click();
+find(document.body, "button").click();
+await null;
+console.log(document.body.innerHTML);"#,
+            r#"<div class="elp-Column counter"><span class="elp-Text">clicks</span><button class="elp-Button"><span class="elp-Text">0</span></button></div>
+<div class="elp-Column counter"><span class="elp-Text">clicks</span><button class="elp-Button"><span class="elp-Text">2</span></button></div>
+"#,
+        );
+    }
+
+    #[test]
+    fn exported_vars_are_props_with_defaults() {
+        let module = lower_source(
+            "component Greeting() {
+                export var name string = \"world\"
+                export *fn onClose() -> None
+                return Text(name)
+            }",
+        );
+
+        let output = emit_module(&module).unwrap();
+        assert!(output.script.contains(
+            "  const name = $props.name ?? \"world\";\n  const onClose = $props.onClose;\n"
+        ));
+    }
+
+    #[test]
+    fn styles_become_css_rules() {
+        let module = lower_source(
+            "Stylesheet {
+                Text, Date.muted {
+                    .padding = 16,
+                    .margin = 1.5rem,
+                    .fontFamily = \"Open Sans\",
+                    .fontWeight = FontWeight.SEMIBOLD,
+                    .opacity = 0.5,
+                }
+
+                .note {
+                    .color = #f80,
+                    .textAlign = .CENTER,
+                }
+            }",
+        );
+
+        let output = emit_module(&module).unwrap();
+        assert_eq!(output.html, None);
+        assert_eq!(
+            output
+                .stylesheet
+                .unwrap()
+                .strip_prefix(BASE_STYLES)
+                .unwrap(),
+            r#"
+.elp-Text, .elp-Date.muted {
+  padding: 16px;
+  margin: 1.5rem;
+  font-family: "Open Sans";
+  font-weight: 600;
+  opacity: 0.5;
+}
+
+.note {
+  color: #ff8800;
+  text-align: center;
+}
+"#
+        );
+    }
+
+    // Just enough of the DOM for the runtime, with `innerHTML` to check what was rendered.
+    const DOM: &str = r#"class Node {
+  constructor(nodeType) { this.nodeType = nodeType; this.parentNode = null; }
+  replaceWith(node) {
+    const siblings = this.parentNode.childNodes;
+    siblings[siblings.indexOf(this)] = node;
+    node.parentNode = this.parentNode;
+  }
+}
+class Text extends Node {
+  constructor(data) { super(3); this.data = data; }
+  get outerHTML() { return this.data; }
+}
+class Element extends Node {
+  constructor(tag) { super(1); this.tag = tag; this.className = ""; this.attributes = {}; this.childNodes = []; this.listeners = {}; }
+  setAttribute(key, value) { this.attributes[key] = value; }
+  append(node) { node.parentNode = this; this.childNodes.push(node); }
+  addEventListener(event, listener) { (this.listeners[event] ??= []).push(listener); }
+  click() { for (const listener of this.listeners.click ?? []) listener({ preventDefault() {} }); }
+  get innerHTML() { return this.childNodes.map((node) => node.outerHTML).join(""); }
+  get outerHTML() {
+    const attributes = Object.entries(this.attributes).map(([key, value]) => ` ${key}="${value}"`).join("");
+    const className = this.className ? ` class="${this.className}"` : "";
+    return `<${this.tag}${className}${attributes}>${this.innerHTML}</${this.tag}>`;
+  }
+}
+globalThis.Node = Node;
+globalThis.document = {
+  body: new Element("body"),
+  createElement: (tag) => new Element(tag),
+  createTextNode: (data) => new Text(data),
+};
+const find = (node, tag) => node.tag === tag ? node : node.childNodes?.map((child) => find(child, tag)).find(Boolean);
+"#;
+
+    // Runs the module in node after the given script and checks what it printed. Skipped when
+    // there's no `node` so the tests still pass without one.
+    fn assert_runs(output: &WebOutput, script: &str, stdout: &str) {
+        let dir = std::env::temp_dir().join(format!(
+            "elp-web-{}-{}",
+            std::process::id(),
+            output.script.len()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("test.js"), &output.script).unwrap();
+        let names = output
+            .html
+            .as_ref()
+            .and_then(|html| html.split("import ").nth(1))
+            .and_then(|import| import.split(" from").next())
+            .unwrap_or("{}");
+        std::fs::write(
+            dir.join("run.mjs"),
+            format!(
+                "{}\nconst {} = await import(\"./test.js\");\n{}\n",
+                DOM, names, script
+            ),
+        )
+        .unwrap();
+
+        let Ok(ran) = Command::new("node").arg(dir.join("run.mjs")).output() else {
+            let _ = std::fs::remove_dir_all(&dir);
+            return;
+        };
+        let _ = std::fs::remove_dir_all(&dir);
+        assert_eq!(
+            String::from_utf8_lossy(&ran.stderr),
+            "",
+            "{}",
+            output.script
+        );
+        assert_eq!(String::from_utf8_lossy(&ran.stdout), stdout);
+    }
+}
@@ -0,0 +1,324 @@
+// The engine behind the queries. Every query's answer is kept along with the queries it asked while
+// working it out, and the revision its answer last changed in. Changing a source file starts a new
+// revision. Asking a query again after that first checks the queries it depends on, and only works
+// the answer out again if one of those changed since it was last checked. An answer that comes out
+// the same as before keeps its old revision, so whatever depends on it doesn't have to be redone
+// either. Typing inside a function body changes that file's parse and module, but not the items it
+// declares, so the files importing it don't resolve their imports again. They're only checked
+// again, against the new module.
+//
+// Queries can be asked from several threads at once, the command line lowers and checks a
+// package's files in parallel. Each thread keeps its own stack of the queries it's running. Two
+// threads asking for the same stale answer might both work it out, they come to the same one and
+// whichever finishes last is kept.
+//
+// This is the same scheme salsa uses, kept small enough to fit here. The queries themselves are in
+// `queries`.
+
+use std::{
+    any::Any,
+    collections::HashMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    thread::{self, ThreadId},
+};
+
+use elp_ir::mir::{Module, Type};
+use elp_parser::precomp::Targets;
+
+use crate::{
+    item_tree::ItemTree,
+    problem::Problem,
+    queries::{Lowered, Parsed, ResolvedImport},
+};
+
+// How many queries can be running inside each other on one thread before the innermost is taken to
+// be asking for its own answer.
+const MAX_DEPTH: usize = 256;
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub enum Query {
+    // What a file holds. It's read from disk the first time it's asked for unless it's been set
+    // already.
+    Source(PathBuf),
+    // The other input, the platforms `#if` directives are decided for. Every branch is kept until
+    // it's set.
+    Targets,
+    // Whether there's a file there. It hardly ever changes when the source does.
+    Exists(PathBuf),
+    Parse(PathBuf),
+    ItemTree(PathBuf),
+    Lower(PathBuf),
+    Resolve(PathBuf),
+    Context(PathBuf),
+    TypeOf(PathBuf, String),
+    Diagnostics(PathBuf),
+}
+
+type Value = Arc<dyn Any + Send + Sync>;
+type Same = fn(&dyn Any, &dyn Any) -> bool;
+
+#[derive(Clone)]
+struct Memo {
+    value: Value,
+    // The revision the value last changed in.
+    changed_at: u64,
+    // The last revision the value was known to be right in.
+    verified_at: u64,
+    dependencies: Vec<Query>,
+}
+
+// Cloning is cheap, answers are shared, so a copy can be changed without touching the original.
+// Completion uses that to look at a document with what's being typed patched out.
+pub struct Database {
+    revision: u64,
+    memos: Mutex<HashMap<Query, Memo>>,
+    // What each query running right now has asked so far, innermost last, for each thread.
+    active: Mutex<HashMap<ThreadId, Vec<Vec<Query>>>>,
+    // Every query worked out, in order, for tests to see what was redone. It's only kept in tests,
+    // an editor's database lives for as long as the editor does.
+    #[cfg(test)]
+    executed: Mutex<Vec<Query>>,
+}
+
+impl Default for Database {
+    fn default() -> Self {
+        Self {
+            revision: 1,
+            memos: Mutex::default(),
+            active: Mutex::default(),
+            #[cfg(test)]
+            executed: Mutex::default(),
+        }
+    }
+}
+
+impl Clone for Database {
+    fn clone(&self) -> Self {
+        Self {
+            revision: self.revision,
+            memos: Mutex::new(lock(&self.memos).clone()),
+            active: Mutex::default(),
+            #[cfg(test)]
+            executed: Mutex::default(),
+        }
+    }
+}
+
+impl fmt::Debug for Database {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Database")
+            .field("revision", &self.revision)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Database {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_source(&mut self, path: &Path, source: impl Into<String>) {
+        self.set_input(Query::Source(path.to_path_buf()), Some(source.into()));
+    }
+
+    // Forgets what the file was set to and goes back to what's on disk, for editors closing a
+    // document they didn't save.
+    pub fn reload(&mut self, path: &Path) {
+        let source = fs::read_to_string(path).ok();
+        self.set_input(Query::Source(path.to_path_buf()), source);
+    }
+
+    // Decides `#if` directives for `targets` from now on, every file is lowered again.
+    pub fn set_targets(&mut self, targets: Targets) {
+        self.set_input(Query::Targets, targets);
+    }
+
+    fn set_input<T: PartialEq + Send + Sync + 'static>(&mut self, key: Query, value: T) {
+        let memos = self.memos.get_mut().unwrap_or_else(PoisonError::into_inner);
+        let unchanged = memos
+            .get(&key)
+            .and_then(|memo| memo.value.downcast_ref::<T>())
+            .is_some_and(|old| *old == value);
+        if unchanged {
+            return;
+        }
+
+        self.revision += 1;
+        memos.insert(
+            key,
+            Memo {
+                value: Arc::new(value),
+                changed_at: self.revision,
+                verified_at: self.revision,
+                dependencies: vec![],
+            },
+        );
+    }
+
+    // The answer to `key`, worked out again only if something it depends on changed. Whichever
+    // query is running on this thread records that it asked.
+    pub(crate) fn fetch<T: Send + Sync + 'static>(&self, key: Query) -> Arc<T> {
+        if let Some(frame) = lock(&self.active)
+            .get_mut(&thread::current().id())
+            .and_then(|stack| stack.last_mut())
+        {
+            frame.push(key.clone());
+        }
+        self.refresh(&key)
+            .1
+            .downcast::<T>()
+            .expect("every query has one type of answer")
+    }
+
+    // The previous answer to `key`, whether or not it's still right. Parsing starts from it.
+    pub(crate) fn stale<T: Send + Sync + 'static>(&self, key: &Query) -> Option<Arc<T>> {
+        let memo = lock(&self.memos).get(key)?.value.clone();
+        memo.downcast::<T>().ok()
+    }
+
+    // Brings `key` up to date, giving its answer and the revision that last changed in.
+    fn refresh(&self, key: &Query) -> (u64, Value) {
+        let memo = lock(&self.memos).get(key).cloned();
+        if let Some(memo) = &memo {
+            if memo.verified_at == self.revision {
+                return (memo.changed_at, memo.value.clone());
+            }
+            let verified_at = memo.verified_at;
+            if memo
+                .dependencies
+                .iter()
+                .all(|dependency| self.refresh(dependency).0 <= verified_at)
+            {
+                if let Some(memo) = lock(&self.memos).get_mut(key) {
+                    memo.verified_at = self.revision;
+                }
+                return (memo.changed_at, memo.value.clone());
+            }
+        }
+
+        // A query that needs its own answer, however indirectly, would never finish. It's given
+        // one saying so instead, which isn't kept, so it's worked out again when it's next asked.
+        if !self.enter() {
+            return (self.revision, cyclic(key));
+        }
+        let (value, same) = self.execute(key);
+        let dependencies = self.leave();
+        #[cfg(test)]
+        lock(&self.executed).push(key.clone());
+
+        let changed_at = match &memo {
+            Some(old) if same(&*old.value, &*value) => old.changed_at,
+            _ => self.revision,
+        };
+        lock(&self.memos).insert(
+            key.clone(),
+            Memo {
+                value: value.clone(),
+                changed_at,
+                verified_at: self.revision,
+                dependencies,
+            },
+        );
+        (changed_at, value)
+    }
+
+    // Starts recording what a query asks on this thread, false when too many are running already.
+    fn enter(&self) -> bool {
+        let mut active = lock(&self.active);
+        let stack = active.entry(thread::current().id()).or_default();
+        if stack.len() >= MAX_DEPTH {
+            return false;
+        }
+        stack.push(vec![]);
+        true
+    }
+
+    // What the innermost query running on this thread asked.
+    fn leave(&self) -> Vec<Query> {
+        let mut active = lock(&self.active);
+        let id = thread::current().id();
+        let stack = active.entry(id).or_default();
+        let dependencies = stack.pop().unwrap_or_default();
+        if stack.is_empty() {
+            active.remove(&id);
+        }
+        dependencies
+    }
+
+    fn execute(&self, key: &Query) -> (Value, Same) {
+        match key {
+            Query::Source(path) => erase(fs::read_to_string(path).ok()),
+            Query::Targets => erase(Targets::default()),
+            Query::Exists(path) => erase(self.source(path).is_some()),
+            Query::Parse(path) => erase(self.execute_parse(path)),
+            Query::ItemTree(path) => erase(self.execute_item_tree(path)),
+            Query::Lower(path) => erase(self.execute_lower(path)),
+            Query::Resolve(path) => erase(self.execute_resolve(path)),
+            Query::Context(path) => erase(self.execute_context(path)),
+            Query::TypeOf(path, name) => erase(self.execute_type_of(path, name)),
+            Query::Diagnostics(path) => erase(self.execute_diagnostics(path)),
+        }
+    }
+
+    // The queries worked out since the last call, oldest first.
+    #[cfg(test)]
+    pub(crate) fn take_executed(&self) -> Vec<Query> {
+        std::mem::take(&mut *lock(&self.executed))
+    }
+}
+
+// The answer a query gets when it needs its own, in the terms it has for something going wrong.
+fn cyclic(key: &Query) -> Value {
+    let problem = || Problem::new(format!("{:?} depends on itself", key));
+    match key {
+        Query::Source(_) => erase(None::<String>).0,
+        Query::Targets => erase(Targets::default()).0,
+        Query::Exists(_) => erase(false).0,
+        Query::Parse(_) => erase::<Parsed>(Err(problem())).0,
+        Query::ItemTree(_) => erase(ItemTree::default()).0,
+        Query::Lower(_) => erase(Lowered::failed(problem())).0,
+        Query::Resolve(_) => erase(Vec::<ResolvedImport>::new()).0,
+        Query::Context(_) => erase(None::<Module>).0,
+        Query::TypeOf(..) => erase(None::<Type>).0,
+        Query::Diagnostics(_) => erase(vec![problem()]).0,
+    }
+}
+
+fn erase<T: PartialEq + Send + Sync + 'static>(value: T) -> (Value, Same) {
+    fn same<T: PartialEq + 'static>(old: &dyn Any, new: &dyn Any) -> bool {
+        old.downcast_ref::<T>() == new.downcast_ref::<T>()
+    }
+    (Arc::new(value), same::<T>)
+}
+
+// A query panicking can't leave a map half changed, so one poisoned by it is still fine to use.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn queries_too_deep_to_finish_are_problems() {
+        let mut db = Database::new();
+        let path = PathBuf::from("main.elp");
+        db.set_source(&path, "fn main {\n}\n");
+
+        lock(&db.active).insert(thread::current().id(), vec![vec![]; MAX_DEPTH]);
+        let problems = db.diagnostics(&path);
+        assert_eq!(problems.len(), 1);
+        assert_eq!(
+            problems[0].message,
+            "Diagnostics(\"main.elp\") depends on itself"
+        );
+
+        // It wasn't kept, asked again with room to work it out it's answered properly.
+        lock(&db.active).clear();
+        assert_eq!(*db.diagnostics(&path), vec![]);
+    }
+}
@@ -0,0 +1,165 @@
+// What a file declares and imports, without any of the bodies or where anything is. Editing inside
+// a function leaves this the same, which is what stops changes to one file spreading to every file
+// importing it. It's read off the parse tree so files the compiler can't lower yet still have one.
+
+use elp_ir::mir::Import;
+use elp_parser::{
+    incremental::{SyntaxItem, SyntaxNode, SyntaxTree},
+    parser::Rule,
+};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ItemKind {
+    Object,
+    Enum,
+    Interface,
+    Function,
+    // Declared on an object, named `Graph.new`.
+    Method,
+    Component,
+    Global,
+    External,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Item {
+    pub name: String,
+    pub kind: ItemKind,
+    pub exported: bool,
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct ItemTree {
+    pub imports: Vec<Import>,
+    pub items: Vec<Item>,
+}
+
+impl ItemTree {
+    pub fn new(tree: &SyntaxTree) -> Self {
+        let mut item_tree = Self::default();
+        for item in tree.items() {
+            let text = Text {
+                source: tree.source(),
+                item,
+            };
+            item_tree.item(&text, inner(&item.node), false);
+        }
+        item_tree
+    }
+
+    // Whether there's an item called `name` that can be imported and if it's exported, the same
+    // answer `Module::exported` gives once the file is lowered.
+    pub fn exported(&self, name: &str) -> Option<bool> {
+        self.items
+            .iter()
+            .find(|item| item.name == name && item.kind != ItemKind::Method)
+            .map(|item| item.exported)
+    }
+
+    fn item(&mut self, text: &Text, node: &SyntaxNode, exported: bool) {
+        let kind = match node.rule {
+            Rule::export => {
+                if let Some(expression) = child(node, Rule::expression) {
+                    self.item(text, inner(expression), true);
+                }
+                return;
+            }
+            Rule::import => {
+                self.import(text, node);
+                return;
+            }
+            Rule::external_symbol | Rule::external_block => {
+                for declaration in &node.children {
+                    let kind = match declaration.rule {
+                        Rule::object_def => ItemKind::Object,
+                        _ => ItemKind::External,
+                    };
+                    self.declare(text, declaration, kind, true);
+                }
+                return;
+            }
+            Rule::object_def => ItemKind::Object,
+            Rule::r#enum => ItemKind::Enum,
+            Rule::interface_def => ItemKind::Interface,
+            Rule::function_def | Rule::fn_header_def => ItemKind::Function,
+            Rule::component_def => ItemKind::Component,
+            Rule::variable_assignment => match child(node, Rule::variable_assignment_target)
+                .and_then(|target| child(target, Rule::variable_declaration))
+            {
+                Some(declaration) => {
+                    return self.declare(text, declaration, ItemKind::Global, exported)
+                }
+                None => return,
+            },
+            Rule::variable_declaration => ItemKind::Global,
+            _ => return,
+        };
+        self.declare(text, node, kind, exported);
+    }
+
+    fn declare(&mut self, text: &Text, node: &SyntaxNode, kind: ItemKind, exported: bool) {
+        let name = match child(node, Rule::variable_access)
+            .and_then(|access| child(access, Rule::variable_access_names))
+        {
+            Some(names) => names
+                .children
+                .iter()
+                .map(|name| text.of(name))
+                .collect::<Vec<_>>()
+                .join("."),
+            None => match child(node, Rule::IDENT) {
+                Some(name) => text.of(name).into(),
+                None => return,
+            },
+        };
+        let kind = match kind {
+            ItemKind::Function if name.contains('.') => ItemKind::Method,
+            kind => kind,
+        };
+        self.items.push(Item {
+            name,
+            kind,
+            exported,
+        });
+    }
+
+    fn import(&mut self, text: &Text, node: &SyntaxNode) {
+        let module_path = child(node, Rule::import_module_path)
+            .and_then(|path| child(path, Rule::string))
+            .and_then(|string| child(string, Rule::inner))
+            .map_or_else(String::new, |inner| text.of(inner).into());
+        let names = node
+            .children
+            .iter()
+            .filter(|name| name.rule == Rule::import_name)
+            .filter_map(|name| {
+                let original = text.of(child(name, Rule::IDENT)?).to_string();
+                let alias = child(name, Rule::import_name_alias)
+                    .and_then(|alias| child(alias, Rule::IDENT))
+                    .map(|alias| text.of(alias).to_string());
+                Some((original, alias))
+            })
+            .collect();
+        self.imports.push(Import { module_path, names });
+    }
+}
+
+// The source of one top level item, its nodes' ranges are relative to where it starts.
+struct Text<'t> {
+    source: &'t str,
+    item: &'t SyntaxItem,
+}
+
+impl Text<'_> {
+    fn of(&self, node: &SyntaxNode) -> &str {
+        &self.source[self.item.start + node.range.start..self.item.start + node.range.end]
+    }
+}
+
+fn inner(node: &SyntaxNode) -> &SyntaxNode {
+    node.children.first().unwrap_or(node)
+}
+
+fn child(node: &SyntaxNode, rule: Rule) -> Option<&SyntaxNode> {
+    node.children.iter().find(|child| child.rule == rule)
+}
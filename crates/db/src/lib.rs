@@ -0,0 +1,4 @@
+pub mod database;
+pub mod item_tree;
+pub mod problem;
+pub mod queries;
@@ -0,0 +1,55 @@
+// Something wrong with a file, the queries' version of a diagnostic. Ranges are bytes into the
+// file's source, the CLI and the language server each turn them into what they show.
+
+use std::ops::Range;
+
+use elp_ir::mir::Span;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Problem {
+    pub message: String,
+    // Missing when the problem isn't tied to a place in the file.
+    pub range: Option<Range<usize>>,
+    pub notes: Vec<String>,
+}
+
+impl Problem {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            range: None,
+            notes: vec![],
+        }
+    }
+
+    // Errors that only know where they start cover the word there.
+    pub fn at(mut self, source: &str, offset: usize) -> Self {
+        let offset = offset.min(source.len());
+        let rest = &source[offset..];
+        let end = match rest.find(|c: char| !c.is_ascii_alphanumeric() && c != '_') {
+            Some(0) => rest.chars().next().map_or(0, char::len_utf8),
+            Some(word) => word,
+            None => rest.len(),
+        };
+        self.range = Some(offset..offset + end);
+        self
+    }
+
+    pub fn spanning(mut self, range: Range<usize>) -> Self {
+        self.range = Some(range);
+        self
+    }
+
+    // Spans the lowering and the checker produce, statements built by hand have no position.
+    pub fn at_span(self, span: Span) -> Self {
+        match span.line {
+            0 => self,
+            _ => self.spanning(span.start..span.end),
+        }
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+}
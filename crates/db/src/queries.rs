@@ -0,0 +1,561 @@
+// The questions the compiler asks about a package, each answered from the answers to smaller ones
+// so `Database` can tell what a change to a file affects. Files are named by path, `$` imports are
+// resolved from the directory `Package.elp` is in.
+
+use std::{
+    panic,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use elp_codegen::{check::check_module_in, typing::TypeContext};
+use elp_ir::{
+    lower::lower_module,
+    mir::{Import, Module, Type},
+};
+use elp_parser::{
+    ast::{module::ASTModule, traits::FromCST},
+    cst::{self, CSTModule},
+    incremental::{SyntaxTree, TextEdit},
+    parser::{ElpParser, Rule},
+    precomp::{preprocess, Severity, Targets},
+};
+use from_pest::FromPest;
+use pest::{error::InputLocation, iterators::Pairs, Parser};
+
+use crate::{
+    database::{Database, Query},
+    item_tree::ItemTree,
+    problem::Problem,
+};
+
+pub const MANIFEST: &str = "Package.elp";
+pub const EXTENSIONS: [&str; 2] = ["elp", "velp"];
+
+pub type Parsed = Result<SyntaxTree, Problem>;
+
+// A file lowered on its own. `#if` directives can warn whether or not it lowers.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Lowered {
+    pub module: Result<Module, Problem>,
+    pub warnings: Vec<Problem>,
+}
+
+impl Lowered {
+    pub fn failed(problem: Problem) -> Self {
+        Self {
+            module: Err(problem),
+            warnings: vec![],
+        }
+    }
+}
+
+// A `$` import and the file it's from, if there is one.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ResolvedImport {
+    pub import: Import,
+    pub target: Option<PathBuf>,
+}
+
+impl Database {
+    // What's in the file, None when it can't be read.
+    pub fn source(&self, path: &Path) -> Arc<Option<String>> {
+        self.fetch(Query::Source(path.to_path_buf()))
+    }
+
+    pub fn exists(&self, path: &Path) -> bool {
+        *self.fetch(Query::Exists(path.to_path_buf()))
+    }
+
+    pub fn targets(&self) -> Arc<Targets> {
+        self.fetch(Query::Targets)
+    }
+
+    pub fn parse(&self, path: &Path) -> Arc<Parsed> {
+        self.fetch(Query::Parse(path.to_path_buf()))
+    }
+
+    pub fn item_tree(&self, path: &Path) -> Arc<ItemTree> {
+        self.fetch(Query::ItemTree(path.to_path_buf()))
+    }
+
+    pub fn lower(&self, path: &Path) -> Arc<Lowered> {
+        self.fetch(Query::Lower(path.to_path_buf()))
+    }
+
+    // The file's imports from inside the package.
+    pub fn resolve(&self, path: &Path) -> Arc<Vec<ResolvedImport>> {
+        self.fetch(Query::Resolve(path.to_path_buf()))
+    }
+
+    // The module a file is checked against, itself plus what it imports. None when it doesn't
+    // lower.
+    pub fn context(&self, path: &Path) -> Arc<Option<Module>> {
+        self.fetch(Query::Context(path.to_path_buf()))
+    }
+
+    // The type of a top level item, written down or inferred.
+    pub fn type_of(&self, path: &Path, name: &str) -> Arc<Option<Type>> {
+        self.fetch(Query::TypeOf(path.to_path_buf(), name.into()))
+    }
+
+    // Everything wrong with the file, from parsing through to type checking.
+    pub fn diagnostics(&self, path: &Path) -> Arc<Vec<Problem>> {
+        self.fetch(Query::Diagnostics(path.to_path_buf()))
+    }
+
+    // `$/lib/graph` and `$lib/graph` are both `lib/graph.elp` (or `.velp`) from the package root.
+    // Looking for the file is a query, so one appearing later is noticed.
+    pub fn resolve_path(&self, from: &Path, module_path: &str) -> Option<PathBuf> {
+        let relative = module_path.strip_prefix('$')?.trim_start_matches('/');
+        let base = package_root(from).join(relative);
+
+        std::iter::once(base.clone())
+            .chain(
+                EXTENSIONS
+                    .iter()
+                    .map(|extension| PathBuf::from(format!("{}.{}", base.display(), extension))),
+            )
+            .find(|candidate| self.exists(candidate))
+    }
+
+    // Starts from the last tree parsed for the file, so only the items that changed are parsed
+    // again.
+    pub(crate) fn execute_parse(&self, path: &Path) -> Parsed {
+        let source = self.source(path);
+        let Some(source) = source.as_deref() else {
+            return Err(Problem::new(format!("can't read `{}`", path.display())));
+        };
+
+        let previous = self.stale::<Parsed>(&Query::Parse(path.to_path_buf()));
+        let tree = match previous.as_deref() {
+            Some(Ok(tree)) => tree.edit(&TextEdit::between(tree.source(), source)),
+            _ => SyntaxTree::parse(source),
+        };
+        tree.map_err(|err| parse_problem(source, &err))
+    }
+
+    pub(crate) fn execute_item_tree(&self, path: &Path) -> ItemTree {
+        match self.parse(path).as_ref() {
+            Ok(tree) => ItemTree::new(tree),
+            Err(_) => ItemTree::default(),
+        }
+    }
+
+    // Lowers the tree parsing kept rather than parsing the file again.
+    pub(crate) fn execute_lower(&self, path: &Path) -> Lowered {
+        match self.parse(path).as_ref() {
+            Ok(tree) => lower_tree(
+                tree,
+                &module_name(&package_root(path), path),
+                &self.targets(),
+            ),
+            Err(problem) => Lowered::failed(problem.clone()),
+        }
+    }
+
+    pub(crate) fn execute_resolve(&self, path: &Path) -> Vec<ResolvedImport> {
+        self.item_tree(path)
+            .imports
+            .iter()
+            .filter(|import| import.module_path.starts_with('$'))
+            .map(|import| ResolvedImport {
+                import: import.clone(),
+                target: self.resolve_path(path, &import.module_path),
+            })
+            .collect()
+    }
+
+    pub(crate) fn execute_context(&self, path: &Path) -> Option<Module> {
+        let mut context = self.lower(path).module.as_ref().ok()?.clone();
+        for resolved in self.resolve(path).iter() {
+            let Some(target) = &resolved.target else {
+                continue;
+            };
+            if let Ok(module) = &self.lower(target).module {
+                context.import_from(module, &resolved.import.names);
+            }
+        }
+        Some(context)
+    }
+
+    pub(crate) fn execute_type_of(&self, path: &Path, name: &str) -> Option<Type> {
+        let context = self.context(path);
+        let context = context.as_ref().as_ref()?;
+        if let Some(function) = context
+            .functions
+            .iter()
+            .find(|function| function.receiver.is_none() && function.name == name)
+        {
+            return Some(Type::Function {
+                params: function
+                    .params
+                    .iter()
+                    .map(|param| param.ty.clone())
+                    .collect(),
+                ret: Box::new(function.ret.clone()),
+            });
+        }
+        if context.object(name).is_some()
+            || context.r#enum(name).is_some()
+            || context.component(name).is_some()
+        {
+            return Some(Type::from_name(name, vec![]));
+        }
+        TypeContext::new(context).global(name).ok()
+    }
+
+    pub(crate) fn execute_diagnostics(&self, path: &Path) -> Vec<Problem> {
+        let source = self.source(path);
+        let source = source.as_deref().unwrap_or_default();
+        if let Err(problem) = self.parse(path).as_ref() {
+            return vec![problem.clone()];
+        }
+
+        let mut problems = vec![];
+        for resolved in self.resolve(path).iter() {
+            let import = &resolved.import;
+            let Some(target) = &resolved.target else {
+                let offset = import_offset(source, &import.module_path);
+                problems.push(
+                    Problem::new(format!("can't find `{}`", import.module_path))
+                        .spanning(offset..offset + import.module_path.len())
+                        .with_note("`$` imports are relative to the directory `Package.elp` is in"),
+                );
+                continue;
+            };
+            // Files that don't parse have problems of their own.
+            if self.parse(target).is_err() {
+                continue;
+            }
+            let items = self.item_tree(target);
+            for (name, _) in &import.names {
+                let message = match items.exported(name) {
+                    Some(true) => continue,
+                    Some(false) => {
+                        format!("`{}` isn't exported from `{}`", name, import.module_path)
+                    }
+                    None => format!("`{}` has no `{}`", import.module_path, name),
+                };
+                problems.push(Problem::new(message).at(source, name_offset(source, import, name)));
+            }
+        }
+
+        match (&self.lower(path).module, self.context(path).as_ref()) {
+            (Ok(module), Some(context)) => problems.extend(
+                check_module_in(module, context)
+                    .into_iter()
+                    .map(|err| Problem::new(err.message).at_span(err.span)),
+            ),
+            (Err(problem), _) => problems.push(problem.clone()),
+            _ => {}
+        }
+        problems
+    }
+}
+
+// Parses and lowers a whole file on its own. The grammar accepts more than the CST and AST can
+// represent so far, those errors point at the innermost expression that doesn't convert.
+pub fn lower_source(source: &str, name: &str) -> Result<Module, Problem> {
+    lower_source_for(source, name, &Targets::default()).module
+}
+
+// Lowers with `#if` directives decided for `targets`. An error in a directive fails lowering like
+// any other.
+pub fn lower_source_for(source: &str, name: &str, targets: &Targets) -> Lowered {
+    match ElpParser::parse(Rule::module, source) {
+        Ok(pairs) => lower_pairs(source, pairs, name, targets),
+        Err(err) => Lowered::failed(parse_problem(source, &err)),
+    }
+}
+
+// Lowers a file that's already been parsed.
+pub fn lower_tree(tree: &SyntaxTree, name: &str, targets: &Targets) -> Lowered {
+    lower_pairs(tree.source(), tree.pairs(), name, targets)
+}
+
+fn lower_pairs(source: &str, mut pairs: Pairs<Rule>, name: &str, targets: &Targets) -> Lowered {
+    let mut warnings = vec![];
+    let module = lower_preprocessed(source, &mut pairs, name, targets, &mut warnings);
+    Lowered { module, warnings }
+}
+
+fn lower_preprocessed(
+    source: &str,
+    pairs: &mut Pairs<Rule>,
+    name: &str,
+    targets: &Targets,
+    warnings: &mut Vec<Problem>,
+) -> Result<Module, Problem> {
+    let unsupported = |problem: Problem| {
+        problem.with_note("the parser understands this but the compiler can't use it yet")
+    };
+    let cst = CSTModule::from_pest(pairs).map_err(|_| {
+        unsupported(match cst::unsupported(source) {
+            Some((construct, offset)) => {
+                Problem::new(format!("{} isn't supported yet", construct)).at(source, offset)
+            }
+            None => Problem::new("this file uses syntax that isn't supported yet"),
+        })
+    })?;
+    // The AST still panics on some of what the CST accepts.
+    let mut ast = panic::catch_unwind(panic::AssertUnwindSafe(|| ASTModule::from_cst(&cst)))
+        .map_err(|_| {
+            unsupported(Problem::new(
+                "this file uses syntax that isn't supported yet",
+            ))
+        })?;
+    for diagnostic in preprocess(&mut ast.expressions, targets) {
+        let problem = Problem::new(diagnostic.message).spanning(diagnostic.start..diagnostic.end);
+        match diagnostic.severity {
+            Severity::Error => return Err(problem),
+            Severity::Warning => warnings.push(problem),
+        }
+    }
+
+    lower_module(name, &ast).map_err(|err| Problem::new(err.message).at_span(err.span))
+}
+
+pub fn parse_problem(source: &str, err: &pest::error::Error<Rule>) -> Problem {
+    let offset = match err.location {
+        InputLocation::Pos(offset) => offset,
+        InputLocation::Span((start, _)) => start,
+    };
+    Problem::new(format!("couldn't parse this, {}", err.variant.message())).at(source, offset)
+}
+
+// The directory `Package.elp` is in, files outside a package use their own directory.
+pub fn package_root(path: &Path) -> PathBuf {
+    let directory = path.parent().unwrap_or(path);
+    directory
+        .ancestors()
+        .find(|ancestor| ancestor.join(MANIFEST).is_file())
+        .unwrap_or(directory)
+        .to_path_buf()
+}
+
+// Modules are named by their path from the package root without the extension, `lib/dag`.
+pub fn module_name(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .with_extension("")
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn import_offset(source: &str, module_path: &str) -> usize {
+    source
+        .find(&format!("\"{}\"", module_path))
+        .map_or(0, |offset| offset + 1)
+}
+
+fn name_offset(source: &str, import: &Import, name: &str) -> usize {
+    let path = import_offset(source, &import.module_path);
+    let statement = source[..path].rfind("import").unwrap_or(0);
+    source[statement..path]
+        .find(name)
+        .map_or(path, |offset| statement + offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const SHAPES: &str = "export object Point {
+\t.x int,
+\t.y int,
+}
+
+export fn Point.sum(self) -> int {
+\treturn self.x
+}
+
+fn hidden() -> int {
+\treturn 1
+}
+";
+
+    const MAIN: &str = "import { Point, hidden } from \"$shapes\"
+
+const limit = 10
+
+fn main(point Point) {
+\tconst total = point.sum()
+}
+";
+
+    fn package(files: &[(&str, &str)]) -> (tempfile::TempDir, Database) {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join(MANIFEST), "").unwrap();
+        for (path, source) in files {
+            fs::write(dir.path().join(path), source).unwrap();
+        }
+        (dir, Database::new())
+    }
+
+    fn messages(problems: &[Problem]) -> Vec<&str> {
+        problems
+            .iter()
+            .map(|problem| problem.message.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn answers_questions_about_a_package() {
+        let (dir, db) = package(&[("shapes.elp", SHAPES), ("main.elp", MAIN)]);
+        let main = dir.path().join("main.elp");
+
+        assert_eq!(
+            messages(&db.diagnostics(&main)),
+            ["`hidden` isn't exported from `$shapes`"]
+        );
+        assert_eq!(
+            db.resolve(&main)[0].target,
+            Some(dir.path().join("shapes.elp"))
+        );
+        assert_eq!(
+            *db.type_of(&main, "limit"),
+            Some(Type::Int {
+                bits: 64,
+                signed: true
+            })
+        );
+        assert_eq!(
+            db.type_of(&main, "main")
+                .as_ref()
+                .as_ref()
+                .map(Type::to_string),
+            Some("fn(Point) -> void".into())
+        );
+        assert!(db
+            .context(&main)
+            .as_ref()
+            .as_ref()
+            .unwrap()
+            .function("Point.sum")
+            .is_some());
+    }
+
+    #[test]
+    fn only_recomputes_what_depends_on_a_change() {
+        let (dir, mut db) = package(&[
+            ("shapes.elp", SHAPES),
+            ("main.elp", MAIN),
+            ("other.elp", "fn other {\n}\n"),
+        ]);
+        let [shapes, main, other] =
+            ["shapes.elp", "main.elp", "other.elp"].map(|file| dir.path().join(file));
+        for path in [&shapes, &main, &other] {
+            db.diagnostics(path);
+        }
+        db.take_executed();
+
+        // Nothing imports `other`, nothing else is redone. Its items stay the same so even its own
+        // imports aren't resolved again.
+        db.set_source(&other, "fn other {\n\tconst a = 1\n}\n");
+        for path in [&shapes, &main, &other] {
+            db.diagnostics(path);
+        }
+        assert_eq!(
+            db.take_executed(),
+            [
+                Query::Parse(other.clone()),
+                Query::ItemTree(other.clone()),
+                Query::Lower(other.clone()),
+                Query::Context(other.clone()),
+                Query::Diagnostics(other.clone()),
+            ]
+        );
+
+        // A body changing keeps the items `shapes` declares the same, so what `main` imports isn't
+        // resolved again, it's only checked against the new module.
+        db.set_source(&shapes, SHAPES.replace("return self.x", "return self.y"));
+        db.diagnostics(&main);
+        assert_eq!(
+            db.take_executed(),
+            [
+                Query::Exists(shapes.clone()),
+                Query::Parse(shapes.clone()),
+                Query::ItemTree(shapes.clone()),
+                Query::Lower(shapes.clone()),
+                Query::Context(main.clone()),
+                Query::Diagnostics(main.clone()),
+            ]
+        );
+
+        // Changing it back and forth is noticed.
+        db.set_source(&shapes, SHAPES.replace("fn hidden", "export fn hidden"));
+        assert_eq!(messages(&db.diagnostics(&main)), Vec::<&str>::new());
+        db.reload(&shapes);
+        assert_eq!(
+            messages(&db.diagnostics(&main)),
+            ["`hidden` isn't exported from `$shapes`"]
+        );
+    }
+
+    #[test]
+    fn notices_files_appearing() {
+        let (dir, mut db) = package(&[(
+            "main.elp",
+            "import { helper } from \"$lib/helper\"\n\nfn main {\n\thelper()\n}\n",
+        )]);
+        let main = dir.path().join("main.elp");
+
+        let problems = db.diagnostics(&main);
+        assert_eq!(messages(&problems), ["can't find `$lib/helper`"]);
+        assert_eq!(problems[0].range, Some(24..35));
+
+        db.set_source(
+            &dir.path().join("lib/helper.velp"),
+            "export fn helper {\n}\n",
+        );
+        assert_eq!(messages(&db.diagnostics(&main)), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn lowers_the_tree_parsing_kept() {
+        let tree = SyntaxTree::parse(MAIN).unwrap();
+        assert_eq!(
+            lower_tree(&tree, "main", &Targets::default()),
+            lower_source_for(MAIN, "main", &Targets::default())
+        );
+
+        let (dir, mut db) = package(&[(
+            "main.elp",
+            "#if(iOS)\nconst platform = \"iOS\"\n#else\nconst platform = \"web\"\n",
+        )]);
+        let main = dir.path().join("main.elp");
+        let platform = |db: &Database| match &db.lower(&main).module {
+            Ok(module) => module.globals.len(),
+            Err(problem) => panic!("{}", problem.message),
+        };
+        assert_eq!(platform(&db), 2);
+
+        // Deciding the directives lowers it again, without parsing it again.
+        db.set_targets(Targets {
+            declared: vec!["iOS".into(), "Web".into()],
+            active: Some("iOS".into()),
+        });
+        db.take_executed();
+        assert_eq!(platform(&db), 1);
+        assert_eq!(db.take_executed(), [Query::Lower(main.clone())]);
+    }
+
+    #[test]
+    fn unsupported_syntax_is_a_problem_not_a_crash() {
+        let problem = lower_source("fn main {\n\tconst a = 1 + 2\n}\n", "main").unwrap_err();
+        assert_eq!(
+            problem.notes,
+            ["the parser understands this but the compiler can't use it yet"]
+        );
+        assert!(lower_source("fn main {", "main")
+            .unwrap_err()
+            .message
+            .starts_with("couldn't parse this"));
+    }
+}
@@ -0,0 +1,509 @@
+// Works out which style applies to each element of a component tree, so backends that don't have
+// a browser's cascade to lean on get the same answer one would. The sheets given to a `Cascade`
+// apply in order, global styles from `App(globalStyles = Styles())` first and then the file's own
+// `Stylesheet` blocks. Between two rules setting the same property the more specific selector
+// wins, and between equally specific ones whichever comes last. Text properties an element
+// doesn't set come from its parent.
+
+use std::{collections::BTreeMap, fmt};
+
+use crate::{
+    mir::{Element, Expr, Literal, Module, Span, Stmt, StmtKind},
+    style::{Selector, StyleSheet, StyleValue},
+};
+
+// Properties an element takes from its parent when nothing sets them on the element itself.
+pub const INHERITED: [&str; 7] = [
+    "color",
+    "fontFamily",
+    "fontSize",
+    "fontWeight",
+    "letterSpacing",
+    "lineHeight",
+    "textAlign",
+];
+
+// An element as far as styles are concerned, what it is and the classes it has.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct StyleNode {
+    pub component: String,
+    pub classes: Vec<String>,
+    pub children: Vec<StyleNode>,
+}
+
+impl StyleNode {
+    pub fn from_element(element: &Element) -> Self {
+        // `class = "row wide"`, a class worked out at runtime can't be matched ahead of time.
+        let classes = element
+            .props
+            .iter()
+            .filter(|(name, _)| name == "class")
+            .flat_map(|(_, value)| match value {
+                Expr::Literal(Literal::String(classes)) => {
+                    classes.split_whitespace().map(String::from).collect()
+                }
+                _ => vec![],
+            })
+            .collect();
+
+        StyleNode {
+            component: element.name.clone(),
+            classes,
+            children: StyleNode::from_body(&element.children),
+        }
+    }
+
+    // The elements a body can make. Both sides of an `if` and the body of a loop are included,
+    // which is every element that could be there rather than the ones that will be.
+    pub fn from_body(body: &[Stmt]) -> Vec<Self> {
+        let mut nodes = vec![];
+        for stmt in body {
+            match &stmt.kind {
+                StmtKind::Expr(expr) | StmtKind::Return(Some(expr)) => {
+                    nodes.extend(StyleNode::from_expr(expr))
+                }
+                StmtKind::If {
+                    then_body,
+                    else_body,
+                    ..
+                } => {
+                    nodes.extend(StyleNode::from_body(then_body));
+                    nodes.extend(StyleNode::from_body(else_body));
+                }
+                StmtKind::For { body, .. } => nodes.extend(StyleNode::from_body(body)),
+                _ => {}
+            }
+        }
+        nodes
+    }
+
+    // `Column { .. }` is an element, so is `Text("hi")` as components are capitalised.
+    fn from_expr(expr: &Expr) -> Option<Self> {
+        match expr {
+            Expr::Element(element) => Some(StyleNode::from_element(element)),
+            Expr::Call { callee, .. } => match &**callee {
+                Expr::Global(name) if name.starts_with(|c: char| c.is_ascii_uppercase()) => {
+                    Some(StyleNode {
+                        component: name.clone(),
+                        classes: vec![],
+                        children: vec![],
+                    })
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn matches(&self, selector: &Selector) -> bool {
+        selector
+            .component
+            .as_ref()
+            .is_none_or(|component| *component == self.component)
+            && selector
+                .classes
+                .iter()
+                .all(|class| self.classes.contains(class))
+    }
+}
+
+// What `App(globalStyles = Styles())` takes its global styles from, `Styles`.
+pub fn global_styles(element: &Element) -> Option<&str> {
+    element
+        .props
+        .iter()
+        .find(|(name, _)| name == "globalStyles")
+        .and_then(|(_, value)| match value {
+            Expr::Call { callee, .. } => match &**callee {
+                Expr::Global(name) => Some(name.as_str()),
+                _ => None,
+            },
+            Expr::Global(name) => Some(name.as_str()),
+            _ => None,
+        })
+}
+
+// The path of the import bringing in what the module's `App` takes its global styles from,
+// `"$/styles"` for `import { Styles } from "$/styles"`. Apps are usually returned from a function
+// rather than a component, so both are looked in.
+pub fn global_styles_import(module: &Module) -> Option<&str> {
+    let name = module
+        .functions
+        .iter()
+        .map(|function| &function.body)
+        .chain(module.components.iter().map(|component| &component.body))
+        .find_map(|body| global_styles_in(body))?;
+    module
+        .imports
+        .iter()
+        .find(|import| {
+            import
+                .names
+                .iter()
+                .any(|(imported, alias)| alias.as_deref().unwrap_or(imported) == name)
+        })
+        .map(|import| import.module_path.as_str())
+}
+
+// The same elements `StyleNode::from_body` finds, searched for the first that sets global styles.
+fn global_styles_in(body: &[Stmt]) -> Option<&str> {
+    body.iter().find_map(|stmt| match &stmt.kind {
+        StmtKind::Expr(Expr::Element(element)) | StmtKind::Return(Some(Expr::Element(element))) => {
+            global_styles(element).or_else(|| global_styles_in(&element.children))
+        }
+        StmtKind::If {
+            then_body,
+            else_body,
+            ..
+        } => global_styles_in(then_body).or_else(|| global_styles_in(else_body)),
+        StmtKind::For { body, .. } => global_styles_in(body),
+        _ => None,
+    })
+}
+
+// Classes count for more than the component, `.title` beats `Text` and `Text.title` beats both.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Default)]
+pub struct Specificity {
+    pub classes: usize,
+    pub components: usize,
+}
+
+impl Specificity {
+    pub fn of(selector: &Selector) -> Self {
+        Specificity {
+            classes: selector.classes.len(),
+            components: usize::from(selector.component.is_some()),
+        }
+    }
+}
+
+// Where a resolved value came from.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Provenance {
+    // The name the sheet was added to the cascade under.
+    pub origin: String,
+    pub rule: Span,
+    pub property: Span,
+    // The selector of the rule that matched, the most specific if more than one did.
+    pub selector: Selector,
+    pub specificity: Specificity,
+    // Set on an ancestor and passed down.
+    pub inherited: bool,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ResolvedProperty {
+    pub value: StyleValue,
+    pub provenance: Provenance,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct StyledNode {
+    pub component: String,
+    pub classes: Vec<String>,
+    pub properties: BTreeMap<String, ResolvedProperty>,
+    pub children: Vec<StyledNode>,
+}
+
+impl StyledNode {
+    pub fn value(&self, property: &str) -> Option<&StyleValue> {
+        self.properties
+            .get(property)
+            .map(|resolved| &resolved.value)
+    }
+
+    fn write(&self, f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
+        let indent = "\t".repeat(depth);
+        write!(f, "{}{}", indent, self.component)?;
+        for class in &self.classes {
+            write!(f, ".{}", class)?;
+        }
+        writeln!(f)?;
+        for (name, resolved) in &self.properties {
+            let provenance = &resolved.provenance;
+            writeln!(
+                f,
+                "{}\t.{} = {}  // {}{} in {}:{}",
+                indent,
+                name,
+                resolved.value,
+                if provenance.inherited {
+                    "inherited from "
+                } else {
+                    ""
+                },
+                provenance.selector,
+                provenance.origin,
+                provenance.rule.line
+            )?;
+        }
+        self.children
+            .iter()
+            .try_for_each(|child| child.write(f, depth + 1))
+    }
+}
+
+// The tree with each element's properties under it and where they came from, for `elp dump`.
+impl fmt::Display for StyledNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f, 0)
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Cascade<'s> {
+    sheets: Vec<(String, &'s StyleSheet)>,
+}
+
+impl<'s> Cascade<'s> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Adds a sheet that takes precedence over the ones before it.
+    pub fn with_sheet(mut self, origin: impl Into<String>, sheet: &'s StyleSheet) -> Self {
+        self.sheets.push((origin.into(), sheet));
+        self
+    }
+
+    pub fn resolve(&self, roots: &[StyleNode]) -> Vec<StyledNode> {
+        roots
+            .iter()
+            .map(|root| self.resolve_node(root, None))
+            .collect()
+    }
+
+    fn resolve_node(&self, node: &StyleNode, parent: Option<&StyledNode>) -> StyledNode {
+        // Every value that applies with what it's ranked by, highest last.
+        let mut candidates = vec![];
+        for (sheet_index, (origin, sheet)) in self.sheets.iter().enumerate() {
+            for (rule_index, rule) in sheet.rules.iter().enumerate() {
+                let Some(selector) = rule
+                    .selectors
+                    .iter()
+                    .filter(|selector| node.matches(selector))
+                    .max_by_key(|selector| Specificity::of(selector))
+                else {
+                    continue;
+                };
+                let specificity = Specificity::of(selector);
+                for (property_index, property) in rule.properties.iter().enumerate() {
+                    let rank = (specificity, sheet_index, rule_index, property_index);
+                    let resolved = ResolvedProperty {
+                        value: property.value.clone(),
+                        provenance: Provenance {
+                            origin: origin.clone(),
+                            rule: rule.span,
+                            property: property.span,
+                            selector: selector.clone(),
+                            specificity,
+                            inherited: false,
+                        },
+                    };
+                    candidates.push((rank, property.name.clone(), resolved));
+                }
+            }
+        }
+        candidates.sort_by_key(|(rank, _, _)| *rank);
+
+        let mut properties = BTreeMap::new();
+        for (_, name, resolved) in candidates {
+            properties.insert(name, resolved);
+        }
+        for (name, resolved) in parent.iter().flat_map(|parent| &parent.properties) {
+            if INHERITED.contains(&name.as_str()) && !properties.contains_key(name) {
+                let mut resolved = resolved.clone();
+                resolved.provenance.inherited = true;
+                properties.insert(name.clone(), resolved);
+            }
+        }
+
+        let mut styled = StyledNode {
+            component: node.component.clone(),
+            classes: node.classes.clone(),
+            properties,
+            children: vec![],
+        };
+        styled.children = node
+            .children
+            .iter()
+            .map(|child| self.resolve_node(child, Some(&styled)))
+            .collect();
+        styled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        lower::{lower_module, lower_stylesheet},
+        style::Colour,
+    };
+    use elp_parser::{
+        ast::{module::ASTModule, style::ASTStyleSheet, traits::FromCST},
+        cst::{style::CSTStyleSheetFile, CSTModule},
+        parser::{ElpParser, Rule},
+    };
+    use from_pest::FromPest;
+    use pest::Parser;
+    use pretty_assertions::assert_eq;
+
+    fn sheet(source: &str) -> StyleSheet {
+        let mut pairs = ElpParser::parse(Rule::stylesheet_file, source).unwrap();
+        let cst = CSTStyleSheetFile::from_pest(&mut pairs).unwrap();
+        lower_stylesheet(&ASTStyleSheet::from_cst(&cst)).unwrap()
+    }
+
+    fn node(component: &str, classes: &[&str], children: Vec<StyleNode>) -> StyleNode {
+        StyleNode {
+            component: component.into(),
+            classes: classes.iter().map(|class| class.to_string()).collect(),
+            children,
+        }
+    }
+
+    fn padding(node: &StyledNode) -> Option<&StyleValue> {
+        node.value("padding")
+    }
+
+    #[test]
+    fn the_most_specific_rule_wins_then_the_last() {
+        let sheet = sheet(
+            ".title { .padding = 1 }
+            Text.title { .padding = 2 }
+            Text { .padding = 3 }
+            Date { .padding = 4 }
+            Date { .padding = 5 }",
+        );
+        let cascade = Cascade::new().with_sheet("styles", &sheet);
+        let styled = cascade.resolve(&[
+            node("Text", &["title"], vec![]),
+            node("Text", &[], vec![]),
+            node("Label", &["title"], vec![]),
+            node("Date", &[], vec![]),
+        ]);
+
+        assert_eq!(
+            styled.iter().map(padding).collect::<Vec<_>>(),
+            vec![
+                Some(&StyleValue::Number(2.0)),
+                Some(&StyleValue::Number(3.0)),
+                Some(&StyleValue::Number(1.0)),
+                Some(&StyleValue::Number(5.0)),
+            ]
+        );
+        let provenance = &styled[0].properties["padding"].provenance;
+        assert_eq!(provenance.origin, "styles");
+        assert_eq!(
+            provenance.specificity,
+            Specificity {
+                classes: 1,
+                components: 1
+            }
+        );
+        assert_eq!(provenance.rule.line, 2);
+    }
+
+    #[test]
+    fn later_sheets_win_between_equally_specific_rules() {
+        let global = sheet("Text { .padding = 1, .margin = 1 } .note { .margin = 2 }");
+        let file = sheet("Text { .padding = 2, .margin = 3 }");
+        let cascade = Cascade::new()
+            .with_sheet("global", &global)
+            .with_sheet("home", &file);
+        let styled = cascade.resolve(&[node("Text", &["note"], vec![])]);
+
+        assert_eq!(padding(&styled[0]), Some(&StyleValue::Number(2.0)));
+        assert_eq!(styled[0].properties["padding"].provenance.origin, "home");
+        // More specific still beats a later sheet.
+        assert_eq!(styled[0].value("margin"), Some(&StyleValue::Number(2.0)));
+        assert_eq!(styled[0].properties["margin"].provenance.origin, "global");
+    }
+
+    #[test]
+    fn text_properties_are_inherited() {
+        let sheet = sheet(
+            "Column { .color = #333, .padding = 8 }
+            Text { .fontWeight = .BOLD }
+            .loud { .color = #f00 }",
+        );
+        let cascade = Cascade::new().with_sheet("styles", &sheet);
+        let styled = cascade.resolve(&[node(
+            "Column",
+            &[],
+            vec![node(
+                "Row",
+                &[],
+                vec![node("Text", &[], vec![]), node("Text", &["loud"], vec![])],
+            )],
+        )]);
+
+        let row = &styled[0].children[0];
+        let (text, loud) = (&row.children[0], &row.children[1]);
+        assert_eq!(padding(row), None);
+        assert_eq!(
+            text.properties["color"].value,
+            StyleValue::Colour(Colour::from_hex("333").unwrap())
+        );
+        assert!(text.properties["color"].provenance.inherited);
+        assert_eq!(
+            text.properties["color"]
+                .provenance
+                .selector
+                .component
+                .as_deref(),
+            Some("Column")
+        );
+        assert!(!text.properties["fontWeight"].provenance.inherited);
+        assert!(!loud.properties["color"].provenance.inherited);
+    }
+
+    #[test]
+    fn trees_from_components() {
+        let source = "component Home {
+            return App(globalStyles = Styles()) {
+                Column(class = \"page wide\") {
+                    Text(\"hello\")
+                    Button() {}
+                }
+            }
+        }";
+        let mut pairs = ElpParser::parse(Rule::module, source).unwrap();
+        let cst = CSTModule::from_pest(&mut pairs).unwrap();
+        let module = lower_module("test", &ASTModule::from_cst(&cst)).unwrap();
+
+        let roots = StyleNode::from_body(&module.components[0].body);
+        assert_eq!(
+            roots,
+            vec![node(
+                "App",
+                &[],
+                vec![node(
+                    "Column",
+                    &["page", "wide"],
+                    vec![node("Text", &[], vec![]), node("Button", &[], vec![])]
+                )]
+            )]
+        );
+
+        let StmtKind::Return(Some(Expr::Element(app))) = &module.components[0].body[0].kind else {
+            panic!("the component returns an element");
+        };
+        assert_eq!(global_styles(app), Some("Styles"));
+    }
+
+    #[test]
+    fn global_styles_come_from_an_import() {
+        let source = "import { Styles as Global } from \"$/styles\"
+
+        fn Main -> App {
+            return App(globalStyles = Global()) {}
+        }";
+        let mut pairs = ElpParser::parse(Rule::module, source).unwrap();
+        let cst = CSTModule::from_pest(&mut pairs).unwrap();
+        let module = lower_module("test", &ASTModule::from_cst(&cst)).unwrap();
+
+        assert_eq!(global_styles_import(&module), Some("$/styles"));
+    }
+}
@@ -0,0 +1,373 @@
+// What a component is made of, pulled out of its definition so backends and tooling don't each
+// have to rediscover it. Following `login-form.velp`, `export var` declares a prop, a plain `var`
+// is state the component re-renders on, nested `fn`s are handlers, `Slot` marks where child
+// content goes and the value returned is what the component renders.
+
+use crate::mir::{ComponentDef, Element, Expr, Literal, Span, Stmt, StmtKind, Type};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ComponentModel<'c> {
+    pub name: &'c str,
+    pub props: Vec<PropModel<'c>>,
+    pub callbacks: Vec<PropModel<'c>>,
+    pub state: Vec<StateVariable<'c>>,
+    pub handlers: Vec<&'c str>,
+    pub slots: Vec<String>,
+    pub render: Option<&'c Expr>,
+}
+
+// A parameter or an `export`ed declaration, both are set by whoever uses the component.
+#[derive(Debug, PartialEq, Clone)]
+pub struct PropModel<'c> {
+    pub name: &'c str,
+    pub ty: &'c Type,
+    pub default: Option<&'c Expr>,
+    pub required: bool,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct StateVariable<'c> {
+    pub span: Span,
+    pub name: &'c str,
+    pub ty: &'c Type,
+    pub initial: Option<&'c Expr>,
+}
+
+impl<'c> ComponentModel<'c> {
+    pub fn of(component: &'c ComponentDef) -> Self {
+        let declared = component
+            .params
+            .iter()
+            .map(|param| (param.name.as_str(), &param.ty, None))
+            .chain(
+                component
+                    .props
+                    .iter()
+                    .map(|prop| (prop.name.as_str(), &prop.ty, prop.default.as_ref())),
+            );
+
+        let mut props = vec![];
+        let mut callbacks = vec![];
+        for (name, ty, default) in declared {
+            let prop = PropModel {
+                name,
+                ty,
+                default,
+                required: default.is_none() && !is_optional(ty),
+            };
+            match is_callback(ty) {
+                true => callbacks.push(prop),
+                false => props.push(prop),
+            }
+        }
+
+        let mut state = vec![];
+        let mut handlers = vec![];
+        let mut render = None;
+        for stmt in &component.body {
+            match &stmt.kind {
+                StmtKind::Let {
+                    name,
+                    value: Some(Expr::Closure(_)),
+                    ..
+                } => handlers.push(name.as_str()),
+                StmtKind::Let {
+                    name,
+                    mutable: true,
+                    ty,
+                    value,
+                } => state.push(StateVariable {
+                    span: stmt.span,
+                    name,
+                    ty,
+                    initial: value.as_ref(),
+                }),
+                StmtKind::Return(Some(value)) => render = Some(value),
+                _ => {}
+            }
+        }
+
+        let mut slots = vec![];
+        find_slots(&component.body, &mut slots);
+
+        ComponentModel {
+            name: &component.name,
+            props,
+            callbacks,
+            state,
+            handlers,
+            slots,
+            render,
+        }
+    }
+
+    // Everything that can be passed in, callbacks included.
+    pub fn prop(&self, name: &str) -> Option<&PropModel<'c>> {
+        self.props
+            .iter()
+            .chain(&self.callbacks)
+            .find(|prop| prop.name == name)
+    }
+}
+
+// `Optional<T>` and pointers can be left out, they're nil until they're given.
+fn is_optional(ty: &Type) -> bool {
+    match ty {
+        Type::Named { name, .. } => name == "Optional",
+        Type::Pointer(_) => true,
+        _ => false,
+    }
+}
+
+fn is_callback(ty: &Type) -> bool {
+    match ty {
+        Type::Function { .. } => true,
+        Type::Pointer(inner) | Type::Reference(inner) => is_callback(inner),
+        _ => false,
+    }
+}
+
+fn find_slots(body: &[Stmt], slots: &mut Vec<String>) {
+    for stmt in body {
+        match &stmt.kind {
+            StmtKind::Let { value: None, .. } | StmtKind::Return(None) => {}
+            StmtKind::Let {
+                value: Some(expr), ..
+            }
+            | StmtKind::Expr(expr)
+            | StmtKind::Return(Some(expr)) => find_slots_in(expr, slots),
+            StmtKind::Assign { target, value, .. } => {
+                find_slots_in(target, slots);
+                find_slots_in(value, slots);
+            }
+            StmtKind::If {
+                condition,
+                then_body,
+                else_body,
+            } => {
+                find_slots_in(condition, slots);
+                find_slots(then_body, slots);
+                find_slots(else_body, slots);
+            }
+            StmtKind::For { iterable, body, .. } => {
+                find_slots_in(iterable, slots);
+                find_slots(body, slots);
+            }
+        }
+    }
+}
+
+// A bare `Slot` or `Slot()` is the default slot, `Slot("footer")` or `Slot(name = "footer")` a
+// named one. They can be anywhere a value can, in a match arm or passed to a call.
+fn find_slots_in(expr: &Expr, slots: &mut Vec<String>) {
+    let name = match expr {
+        Expr::Global(name) if name == "Slot" => "default".into(),
+        Expr::Element(element) if element.name == "Slot" => slot_name(element),
+        Expr::Call { callee, args } if **callee == Expr::Global("Slot".into()) => {
+            match args.first() {
+                Some(Expr::Literal(Literal::String(name))) => name.clone(),
+                _ => "default".into(),
+            }
+        }
+        Expr::Literal(_) | Expr::Local(_) | Expr::Global(_) => return,
+        Expr::Field { base, .. } => return find_slots_in(base, slots),
+        Expr::Call { callee, args } => {
+            find_slots_in(callee, slots);
+            return args.iter().for_each(|arg| find_slots_in(arg, slots));
+        }
+        Expr::MethodCall { receiver, args, .. } => {
+            find_slots_in(receiver, slots);
+            return args.iter().for_each(|arg| find_slots_in(arg, slots));
+        }
+        Expr::Binary { lhs, rhs, .. } => {
+            find_slots_in(lhs, slots);
+            return find_slots_in(rhs, slots);
+        }
+        Expr::Unary { operand, .. } => return find_slots_in(operand, slots),
+        Expr::Object { fields, .. } => {
+            return fields
+                .iter()
+                .for_each(|(_, value)| find_slots_in(value, slots))
+        }
+        Expr::Variant { args, .. } | Expr::Array(args) => {
+            return args.iter().for_each(|arg| find_slots_in(arg, slots))
+        }
+        Expr::Closure(closure) => return find_slots(&closure.body, slots),
+        Expr::Match { scrutinee, arms } => {
+            find_slots_in(scrutinee, slots);
+            return arms.iter().for_each(|arm| find_slots_in(&arm.body, slots));
+        }
+        Expr::Element(element) => {
+            element
+                .args
+                .iter()
+                .chain(element.props.iter().map(|(_, value)| value))
+                .for_each(|arg| find_slots_in(arg, slots));
+            return find_slots(&element.children, slots);
+        }
+    };
+    if !slots.contains(&name) {
+        slots.push(name);
+    }
+}
+
+fn slot_name(element: &Element) -> String {
+    let named = element
+        .props
+        .iter()
+        .find(|(prop, _)| prop == "name")
+        .map(|(_, value)| value);
+    match named.or(element.args.first()) {
+        Some(Expr::Literal(Literal::String(name))) => name.clone(),
+        _ => "default".into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        lower::lower_module,
+        mir::{MatchArm, Pattern},
+    };
+    use elp_parser::{
+        ast::{module::ASTModule, traits::FromCST},
+        cst::CSTModule,
+        parser::{ElpParser, Rule},
+    };
+    use from_pest::FromPest;
+    use pest::Parser;
+    use pretty_assertions::assert_eq;
+
+    fn component(source: &str) -> ComponentDef {
+        let mut pairs = ElpParser::parse(Rule::module, source).unwrap();
+        let cst = CSTModule::from_pest(&mut pairs).unwrap();
+        let module = lower_module("test", &ASTModule::from_cst(&cst)).unwrap();
+        module.components.into_iter().next().unwrap()
+    }
+
+    #[test]
+    fn props_state_handlers_and_slots() {
+        let login = component(
+            "component LoginForm(title string) {
+                export var email Optional<string>
+                export var remember bool = false
+                export *fn onSubmit(username string, password string) -> None
+                var password string
+                var attempts = 0
+                const limit = 3
+
+                fn handleSubmit() {
+                    attempts += 1
+                }
+
+                return Form(onSubmit = handleSubmit) {
+                    Slot(\"header\") {}
+                    Input(value = email) {}
+                    Slot() {}
+                }
+            }",
+        );
+        let model = ComponentModel::of(&login);
+
+        assert_eq!(model.name, "LoginForm");
+        assert_eq!(
+            model
+                .props
+                .iter()
+                .map(|prop| (prop.name, prop.ty.to_string(), prop.required))
+                .collect::<Vec<_>>(),
+            vec![
+                ("title", "string".to_string(), true),
+                ("email", "Optional<string>".to_string(), false),
+                ("remember", "bool".to_string(), false),
+            ]
+        );
+        assert_eq!(
+            model
+                .callbacks
+                .iter()
+                .map(|prop| (prop.name, prop.ty.to_string(), prop.required))
+                .collect::<Vec<_>>(),
+            vec![("onSubmit", "*fn(string, string) -> void".to_string(), false)]
+        );
+        assert_eq!(
+            model
+                .state
+                .iter()
+                .map(|state| state.name)
+                .collect::<Vec<_>>(),
+            vec!["password", "attempts"]
+        );
+        assert_eq!(model.handlers, vec!["handleSubmit"]);
+        assert_eq!(model.slots, vec!["header", "default"]);
+        assert!(matches!(model.render, Some(Expr::Element(element)) if element.name == "Form"));
+        assert!(model.prop("onSubmit").is_some());
+    }
+
+    #[test]
+    fn bare_slots() {
+        // The arms of `examples/ChatRoom/components/message.velp`, which are each a column with a
+        // bare `Slot` in it.
+        let message = component(
+            "component Message(from User, body string) {
+	return Column(class=\"from-me\") {
+		Slot
+	}
+}",
+        );
+        let model = ComponentModel::of(&message);
+        assert_eq!(model.slots, vec!["default"]);
+
+        // The whole `match`, and slots passed to calls.
+        let Some(column) = model.render.cloned() else {
+            panic!("expected Message to render something");
+        };
+        let mut matched = message.clone();
+        matched.body = vec![Stmt {
+            span: matched.body[0].span,
+            kind: StmtKind::Return(Some(Expr::Match {
+                scrutinee: Box::new(Expr::Local("from".into())),
+                arms: vec![
+                    MatchArm {
+                        pattern: Pattern::Wildcard,
+                        body: column,
+                    },
+                    MatchArm {
+                        pattern: Pattern::Wildcard,
+                        body: Expr::Call {
+                            callee: Box::new(Expr::Global("Card".into())),
+                            args: vec![Expr::Call {
+                                callee: Box::new(Expr::Global("Slot".into())),
+                                args: vec![Expr::Literal(Literal::String("footer".into()))],
+                            }],
+                        },
+                    },
+                ],
+            })),
+        }];
+        assert_eq!(
+            ComponentModel::of(&matched).slots,
+            vec!["default", "footer"]
+        );
+    }
+
+    #[test]
+    fn only_declarations_can_be_exported() {
+        let mut pairs = ElpParser::parse(
+            Rule::module,
+            "component Counter() {
+                var count = 0
+                export count = 1
+            }",
+        )
+        .unwrap();
+        let cst = CSTModule::from_pest(&mut pairs).unwrap();
+        let err = lower_module("test", &ASTModule::from_cst(&cst)).unwrap_err();
+
+        assert_eq!(
+            err.message,
+            "only declarations can be exported from a component"
+        );
+    }
+}
@@ -0,0 +1,160 @@
+// Calling code that isn't elp, and being called by it. An `external` declaration names the ABI
+// its items follow, which decides how arguments are passed to them, and its signatures are
+// written in elp types that each stand for exactly one C type. The same mapping is used the other
+// way when a header is generated for C code calling exported elp functions.
+
+use crate::mir::Type;
+
+// How arguments and the return value are passed. Everything elp defines uses C's, the others are
+// for libraries that ask for them, and only mean anything on 32 bit x86 Windows.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum CallingConvention {
+    #[default]
+    C,
+    StdCall,
+    FastCall,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum Abi {
+    #[default]
+    C,
+    StdCall,
+    FastCall,
+    // The Objective-C runtime, messages are sent through C functions like `objc_msgSend` with the
+    // receiver and selector as their first two arguments.
+    ObjectiveC,
+}
+
+// What can come after `external`, `external "stdcall" { .. }`.
+pub const ABIS: [&str; 4] = ["C", "stdcall", "fastcall", "ObjC"];
+
+impl Abi {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "C" => Some(Abi::C),
+            "stdcall" => Some(Abi::StdCall),
+            "fastcall" => Some(Abi::FastCall),
+            "ObjC" => Some(Abi::ObjectiveC),
+            _ => None,
+        }
+    }
+
+    pub fn calling_convention(self) -> CallingConvention {
+        match self {
+            Abi::C | Abi::ObjectiveC => CallingConvention::C,
+            Abi::StdCall => CallingConvention::StdCall,
+            Abi::FastCall => CallingConvention::FastCall,
+        }
+    }
+}
+
+// Each elp primitive and the C type it's passed as. `int` and `float` are elp's 64 bit ones, not
+// C's types of the same name.
+pub const C_TYPES: [(&str, &str); 13] = [
+    ("bool", "bool"),
+    ("int8", "int8_t"),
+    ("int16", "int16_t"),
+    ("int32", "int32_t"),
+    ("int64", "int64_t"),
+    ("uint8", "uint8_t"),
+    ("uint16", "uint16_t"),
+    ("uint32", "uint32_t"),
+    ("uint64", "uint64_t"),
+    ("float32", "float"),
+    ("float64", "double"),
+    ("string", "const char *"),
+    ("void", "void"),
+];
+
+// C's names for types elp has under another name, for pointing signatures copied from a header
+// at the elp type to use. Names elp has a type of its own for aren't here.
+const C_NAMES: [(&str, &str); 14] = [
+    ("char", "int8"),
+    ("short", "int16"),
+    ("unsigned", "uint32"),
+    ("double", "float64"),
+    ("size_t", "uint64"),
+    ("ssize_t", "int64"),
+    ("int8_t", "int8"),
+    ("int16_t", "int16"),
+    ("int32_t", "int32"),
+    ("int64_t", "int64"),
+    ("uint8_t", "uint8"),
+    ("uint16_t", "uint16"),
+    ("uint32_t", "uint32"),
+    ("uint64_t", "uint64"),
+];
+
+// The C type a primitive is passed as, None for objects, enums, arrays and pointers, whose C
+// types depend on how the backend lays them out.
+pub fn c_type(ty: &Type) -> Option<&'static str> {
+    let name = match ty {
+        Type::Bool => "bool",
+        Type::Int { bits, signed } => match (bits, signed) {
+            (8, true) => "int8",
+            (16, true) => "int16",
+            (32, true) => "int32",
+            (64, true) => "int64",
+            (8, false) => "uint8",
+            (16, false) => "uint16",
+            (32, false) => "uint32",
+            (64, false) => "uint64",
+            _ => return None,
+        },
+        Type::Float { bits: 32 } => "float32",
+        Type::Float { .. } => "float64",
+        Type::String => "string",
+        Type::Void => "void",
+        _ => return None,
+    };
+    C_TYPES
+        .iter()
+        .find(|(elp, _)| *elp == name)
+        .map(|(_, c)| *c)
+}
+
+// The elp type to write for one C calls `name`, when elp doesn't have a type called that itself.
+pub fn elp_name(name: &str) -> Option<&'static str> {
+    C_NAMES
+        .iter()
+        .find(|(c, _)| *c == name)
+        .map(|(_, elp)| *elp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn primitives_have_one_c_type_each() {
+        for (elp, c) in C_TYPES {
+            assert_eq!(c_type(&Type::from_name(elp, vec![])), Some(c), "{}", elp);
+        }
+        assert_eq!(c_type(&Type::from_name("int", vec![])), Some("int64_t"));
+        assert_eq!(c_type(&Type::Array(Box::new(Type::Bool))), None);
+        // C's spellings lead back to the same types.
+        for (c, elp) in C_NAMES {
+            assert!(C_TYPES.iter().any(|(name, _)| *name == elp), "{}", c);
+            assert!(
+                matches!(Type::from_name(c, vec![]), Type::Named { .. }),
+                "`{}` is an elp type",
+                c
+            );
+        }
+    }
+
+    #[test]
+    fn abis() {
+        for name in ABIS {
+            assert!(Abi::from_name(name).is_some(), "{}", name);
+        }
+        assert_eq!(Abi::from_name("cdecl"), None);
+        assert_eq!(Abi::ObjectiveC.calling_convention(), CallingConvention::C);
+        assert_eq!(
+            Abi::StdCall.calling_convention(),
+            CallingConvention::StdCall
+        );
+    }
+}
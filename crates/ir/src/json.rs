@@ -0,0 +1,629 @@
+// Deriving JSON conversions for objects. An object implementing `FromTo<JSON>` gets a
+// `toJSON(self) -> JSON` and a static `fromJSON(json JSON) -> Self`, `Into<JSON>` and
+// `From<JSON>` get one of them each. Whatever the object defines itself is left alone. Each
+// field's `json` tag renames its key and `json: "-"` leaves it out, an empty one is an error rather
+// than a way of keeping the field's name. Fields that aren't in the JSON when it's read take their
+// default.
+//
+// The code made works on the `JSON` value from `elp/data/json`, which is imported for it:
+// `JSON.object()`, `JSON.array()` and `JSON.from(value)` make values, `set(key, value)` and
+// `append(value)` fill them, `has(key)`, `get(key)` and `items()` read them and `string()`,
+// `int()`, `float()` and `bool()` turn them back into elp values.
+
+use crate::{
+    lower::{error, LowerResult},
+    macros::Invocation,
+    mir::{
+        Expr, FieldDef, Function, Import, Literal, Module, ObjectDef, Param, Span, Stmt, StmtKind,
+        Tag, Type,
+    },
+};
+
+pub const JSON_MODULE: &str = "elp/data/json";
+
+fn json_type() -> Type {
+    Type::from_name("JSON", vec![])
+}
+
+// Whether `object` asks for `toJSON` and `fromJSON`.
+fn derives(object: &ObjectDef) -> (bool, bool) {
+    let json = json_type();
+    let with_json = |interface: &str| {
+        object
+            .implements
+            .contains(&Type::from_name(interface, vec![json.clone()]))
+    };
+    let both = with_json("FromTo");
+    (both || with_json("Into"), both || with_json("From"))
+}
+
+pub fn derive(module: &mut Module) -> LowerResult<()> {
+    let mut functions = vec![];
+    for object in &module.objects {
+        check_tags(module, object)?;
+
+        let (to_json, from_json) = derives(object);
+        let defined = |name: &str| {
+            module
+                .function(&format!("{}.{}", object.name, name))
+                .is_some()
+        };
+        let mut invocation = Invocation::new(object.span, "JSON", None);
+        if to_json && !defined("toJSON") {
+            functions.push(encode_object(&mut invocation, object));
+        }
+        if from_json && !defined("fromJSON") {
+            functions.push(decode_object(module, &mut invocation, object)?);
+        }
+    }
+
+    let imported = module
+        .imports
+        .iter()
+        .flat_map(|import| &import.names)
+        .any(|(name, alias)| alias.as_deref().unwrap_or(name) == "JSON");
+    if !functions.is_empty() && !imported {
+        module.imports.push(Import {
+            module_path: JSON_MODULE.into(),
+            names: vec![("JSON".into(), None)],
+        });
+    }
+    module.functions.extend(functions);
+    Ok(())
+}
+
+// The key a field is written under, None when it's left out. An empty tag doesn't get this far
+// when deriving, `check_tags` stops it.
+fn key(field: &FieldDef) -> Option<&str> {
+    match field.tag("json") {
+        Some("-") => None,
+        Some(key) => Some(key),
+        None => Some(&field.name),
+    }
+}
+
+fn json_tag(field: &FieldDef) -> Option<&Tag> {
+    field.tags.iter().find(|tag| tag.name == "json")
+}
+
+// Where a problem with how a field converts is reported, its tag if it has one.
+fn field_span(field: &FieldDef) -> Span {
+    json_tag(field).map_or(field.span, |tag| tag.span)
+}
+
+// What a `json` tag can say, for `Tags`.
+pub fn tag_grammar(value: &str) -> Result<(), String> {
+    match value.trim().is_empty() {
+        true => Err("a `json` tag needs a key, or `-` to leave the field out".into()),
+        false => Ok(()),
+    }
+}
+
+// Fields whose type is inferred from their default, `.calls = 0`.
+fn field_type(field: &FieldDef) -> Type {
+    match (&field.ty, &field.default) {
+        (Type::Unknown, Some(Expr::Literal(literal))) => match literal {
+            Literal::Bool(_) => Type::Bool,
+            Literal::Int(_) => Type::from_name("int", vec![]),
+            Literal::Float(_) => Type::from_name("float", vec![]),
+            Literal::String(_) => Type::String,
+            Literal::Nil => Type::Unknown,
+        },
+        (ty, _) => ty.clone(),
+    }
+}
+
+fn optional(ty: &Type) -> Option<&Type> {
+    match ty {
+        Type::Named { name, generics } if name == "Optional" => generics.first(),
+        _ => None,
+    }
+}
+
+// Types from other modules are trusted to convert themselves, objects from this one have to
+// derive JSON as well.
+fn serialisable(module: &Module, ty: &Type) -> bool {
+    match ty {
+        Type::Unknown | Type::Bool | Type::Int { .. } | Type::Float { .. } | Type::String => true,
+        Type::Void | Type::Function { .. } => false,
+        Type::Array(inner) | Type::Pointer(inner) | Type::Reference(inner) => {
+            serialisable(module, inner)
+        }
+        Type::Named { name, generics } => match optional(ty) {
+            Some(inner) => serialisable(module, inner),
+            None if name == "Optional" => generics.is_empty(),
+            None => match module.object(name) {
+                Some(object) => derives(object) != (false, false),
+                None => module.r#enum(name).is_none(),
+            },
+        },
+    }
+}
+
+fn check_tags(module: &Module, object: &ObjectDef) -> LowerResult<()> {
+    let deriving = derives(object) != (false, false);
+    for field in &object.fields {
+        let tag = json_tag(field);
+        // Everywhere else a tag that doesn't fit is only warned about, deriving would have to
+        // guess what it meant.
+        if let (true, Some(tag)) = (deriving, tag) {
+            if let Err(message) = tag_grammar(&tag.value) {
+                return error(tag.span, message);
+            }
+        }
+        let tagged = tag.is_some();
+        if key(field).is_none() || (!tagged && !deriving) {
+            continue;
+        }
+        let ty = field_type(field);
+        if serialisable(module, &ty) {
+            continue;
+        }
+        let message = match tagged {
+            true => format!(
+                "`{}.{}` has a `json` tag but a `{}` can't be converted to JSON",
+                object.name, field.name, ty
+            ),
+            false => format!(
+                "`{}.{}` is a `{}`, which can't be converted to JSON, tag it `json: \"-\"` to leave it out",
+                object.name, field.name, ty
+            ),
+        };
+        return error(field_span(field), message);
+    }
+    Ok(())
+}
+
+fn call(receiver: Expr, method: &str, args: Vec<Expr>) -> Expr {
+    Expr::MethodCall {
+        receiver: Box::new(receiver),
+        method: method.into(),
+        args,
+    }
+}
+
+fn json_call(method: &str, args: Vec<Expr>) -> Expr {
+    call(Expr::Global("JSON".into()), method, args)
+}
+
+fn string(value: &str) -> Expr {
+    Expr::Literal(Literal::String(value.into()))
+}
+
+fn encode_object(invocation: &mut Invocation, object: &ObjectDef) -> Function {
+    let json = invocation.fresh("json");
+    let mut body = vec![invocation.stmt(StmtKind::Let {
+        name: json.clone(),
+        mutable: false,
+        ty: Type::Unknown,
+        value: Some(json_call("object", vec![])),
+    })];
+
+    for field in &object.fields {
+        let Some(key) = key(field) else {
+            continue;
+        };
+        let value = Expr::Field {
+            base: Box::new(Expr::Local("self".into())),
+            name: field.name.clone(),
+        };
+        let ty = field_type(field);
+        let mut set = vec![];
+        let encoded = encode(invocation, value.clone(), &ty, &mut set);
+        set.push(invocation.stmt(StmtKind::Expr(call(
+            Expr::Local(json.clone()),
+            "set",
+            vec![string(key), encoded],
+        ))));
+        // Fields that aren't set aren't written.
+        match optional(&ty).is_some() {
+            true => body.push(invocation.stmt(StmtKind::If {
+                condition: call(value, "notNil", vec![]),
+                then_body: set,
+                else_body: vec![],
+            })),
+            false => body.extend(set),
+        }
+    }
+    body.push(invocation.stmt(StmtKind::Return(Some(Expr::Local(json)))));
+
+    Function {
+        span: object.span,
+        name: "toJSON".into(),
+        receiver: Some(object.name.clone()),
+        generics: vec![],
+        params: vec![Param {
+            name: "self".into(),
+            ty: Type::from_name(&object.name, vec![]),
+        }],
+        ret: json_type(),
+        body,
+        exported: object.exported,
+    }
+}
+
+// `value` as a JSON value, with whatever has to run first to make it added to `body`.
+fn encode(invocation: &mut Invocation, value: Expr, ty: &Type, body: &mut Vec<Stmt>) -> Expr {
+    match ty {
+        Type::Array(inner) => {
+            let items = invocation.fresh("items");
+            let item = invocation.fresh("item");
+            let mut each = vec![];
+            let encoded = encode(invocation, Expr::Local(item.clone()), inner, &mut each);
+            each.push(invocation.stmt(StmtKind::Expr(call(
+                Expr::Local(items.clone()),
+                "append",
+                vec![encoded],
+            ))));
+            body.push(invocation.stmt(StmtKind::Let {
+                name: items.clone(),
+                mutable: false,
+                ty: Type::Unknown,
+                value: Some(json_call("array", vec![])),
+            }));
+            body.push(invocation.stmt(StmtKind::For {
+                binding: item,
+                iterable: value,
+                body: each,
+            }));
+            Expr::Local(items)
+        }
+        Type::Pointer(inner) | Type::Reference(inner) => encode(invocation, value, inner, body),
+        Type::Named { name, .. } if name != "JSON" => match optional(ty) {
+            Some(inner) => encode(invocation, value, inner, body),
+            None => call(value, "toJSON", vec![]),
+        },
+        _ => json_call("from", vec![value]),
+    }
+}
+
+fn decode_object(
+    module: &Module,
+    invocation: &mut Invocation,
+    object: &ObjectDef,
+) -> LowerResult<Function> {
+    let json = Expr::Local("json".into());
+    let mut body = vec![];
+    let mut fields = vec![];
+
+    for field in &object.fields {
+        let ty = field_type(field);
+        let Some(key) = key(field) else {
+            let value = match (&field.default, optional(&ty)) {
+                (Some(default), _) => default.clone(),
+                (None, Some(_)) => Expr::Literal(Literal::Nil),
+                (None, None) => {
+                    return error(
+                        field_span(field),
+                        format!(
+                            "`{}.{}` is left out of JSON, it needs a default for `fromJSON` to make a `{}`",
+                            object.name, field.name, object.name
+                        ),
+                    )
+                }
+            };
+            fields.push((field.name.clone(), value));
+            continue;
+        };
+
+        let local = invocation.fresh(&field.name);
+        let mut read = vec![];
+        let value = call(json.clone(), "get", vec![string(key)]);
+        let decoded = decode(module, invocation, value, &ty, &mut read);
+        match (&field.default, optional(&ty)) {
+            // Without a default the key has to be there.
+            (None, None) => {
+                body.extend(read);
+                body.push(invocation.stmt(StmtKind::Let {
+                    name: local.clone(),
+                    mutable: false,
+                    ty: ty.clone(),
+                    value: Some(decoded),
+                }));
+            }
+            (default, _) => {
+                read.push(invocation.stmt(StmtKind::Assign {
+                    target: Expr::Local(local.clone()),
+                    op: None,
+                    value: decoded,
+                }));
+                body.push(invocation.stmt(StmtKind::Let {
+                    name: local.clone(),
+                    mutable: true,
+                    ty: ty.clone(),
+                    value: Some(default.clone().unwrap_or(Expr::Literal(Literal::Nil))),
+                }));
+                body.push(invocation.stmt(StmtKind::If {
+                    condition: call(json.clone(), "has", vec![string(key)]),
+                    then_body: read,
+                    else_body: vec![],
+                }));
+            }
+        }
+        fields.push((field.name.clone(), Expr::Local(local)));
+    }
+    body.push(invocation.stmt(StmtKind::Return(Some(Expr::Object {
+        name: object.name.clone(),
+        fields,
+    }))));
+
+    Ok(Function {
+        span: object.span,
+        name: "fromJSON".into(),
+        receiver: Some(object.name.clone()),
+        generics: vec![],
+        params: vec![Param {
+            name: "json".into(),
+            ty: json_type(),
+        }],
+        ret: Type::from_name(&object.name, vec![]),
+        body,
+        exported: object.exported,
+    })
+}
+
+// The elp value `json` holds when it's a `ty`, with whatever has to run first added to `body`.
+fn decode(
+    module: &Module,
+    invocation: &mut Invocation,
+    json: Expr,
+    ty: &Type,
+    body: &mut Vec<Stmt>,
+) -> Expr {
+    match ty {
+        Type::String => call(json, "string", vec![]),
+        Type::Bool => call(json, "bool", vec![]),
+        Type::Int { .. } => call(json, "int", vec![]),
+        Type::Float { .. } => call(json, "float", vec![]),
+        Type::Array(inner) => {
+            let items = invocation.fresh("items");
+            let item = invocation.fresh("item");
+            let mut each = vec![];
+            let decoded = decode(
+                module,
+                invocation,
+                Expr::Local(item.clone()),
+                inner,
+                &mut each,
+            );
+            each.push(invocation.stmt(StmtKind::Expr(call(
+                Expr::Local(items.clone()),
+                "append",
+                vec![decoded],
+            ))));
+            body.push(invocation.stmt(StmtKind::Let {
+                name: items.clone(),
+                mutable: false,
+                ty: ty.clone(),
+                value: Some(Expr::Array(vec![])),
+            }));
+            body.push(invocation.stmt(StmtKind::For {
+                binding: item,
+                iterable: call(json, "items", vec![]),
+                body: each,
+            }));
+            Expr::Local(items)
+        }
+        Type::Pointer(inner) | Type::Reference(inner) => {
+            decode(module, invocation, json, inner, body)
+        }
+        Type::Named { name, .. } if name != "JSON" => match optional(ty) {
+            Some(inner) => decode(module, invocation, json, inner, body),
+            // Objects from this module are called directly, anything imported through its type.
+            None if module.object(name).is_some() => Expr::Call {
+                callee: Box::new(Expr::Global(format!("{}.fromJSON", name))),
+                args: vec![json],
+            },
+            None => call(Expr::Global(name.clone()), "fromJSON", vec![json]),
+        },
+        _ => json,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lower::{lower_module, LowerError};
+    use elp_parser::{
+        ast::{module::ASTModule, traits::FromCST},
+        cst::CSTModule,
+        parser::{ElpParser, Rule},
+    };
+    use from_pest::FromPest;
+    use pest::Parser;
+    use pretty_assertions::assert_eq;
+
+    fn lower(source: &str) -> Result<Module, LowerError> {
+        let mut pairs = ElpParser::parse(Rule::module, source).unwrap();
+        let cst = CSTModule::from_pest(&mut pairs).unwrap();
+        lower_module("test", &ASTModule::from_cst(&cst))
+    }
+
+    // The keys a derived function writes or reads, in order.
+    fn keys(body: &[Stmt]) -> Vec<String> {
+        let mut keys = vec![];
+        for stmt in body {
+            match &stmt.kind {
+                StmtKind::Expr(expr)
+                | StmtKind::Let {
+                    value: Some(expr), ..
+                }
+                | StmtKind::Assign { value: expr, .. } => keys_in(expr, &mut keys),
+                StmtKind::If {
+                    condition,
+                    then_body,
+                    ..
+                } => {
+                    keys_in(condition, &mut keys);
+                    keys.extend(self::keys(then_body));
+                }
+                StmtKind::For { iterable, .. } => keys_in(iterable, &mut keys),
+                _ => {}
+            }
+        }
+        keys
+    }
+
+    fn keys_in(expr: &Expr, keys: &mut Vec<String>) {
+        match expr {
+            Expr::MethodCall {
+                receiver,
+                method,
+                args,
+            } => {
+                keys_in(receiver, keys);
+                if let Some(Expr::Literal(Literal::String(key))) = args.first() {
+                    keys.push(format!("{}({})", method, key));
+                }
+            }
+            Expr::Call { args, .. } => args.iter().for_each(|arg| keys_in(arg, keys)),
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn derives_from_tags() {
+        let module = lower(
+            "object Author implements FromTo<JSON> {
+	.name string `json: \"name\"`,
+}
+
+@toJSON
+export object Post {
+	.title string `json: \"headline\"`,
+	.author Author,
+	.tags Array<string>,
+	.subtitle Optional<string>,
+	.draft = false `json: \"-\"`,
+	.calls = 0,
+}
+",
+        )
+        .unwrap();
+
+        assert_eq!(
+            module.imports,
+            vec![Import {
+                module_path: JSON_MODULE.into(),
+                names: vec![("JSON".into(), None)],
+            }]
+        );
+        assert_eq!(
+            module
+                .functions
+                .iter()
+                .map(|function| (function.qualified_name(), function.exported))
+                .collect::<Vec<_>>(),
+            vec![
+                ("Author.toJSON".into(), false),
+                ("Author.fromJSON".into(), false),
+                ("Post.toJSON".into(), true),
+                ("Post.fromJSON".into(), true),
+            ]
+        );
+
+        let to_json = module.function("Post.toJSON").unwrap();
+        assert_eq!(
+            keys(&to_json.body),
+            vec![
+                "set(headline)",
+                "set(author)",
+                "set(tags)",
+                "set(subtitle)",
+                "set(calls)"
+            ]
+        );
+        // Arrays are converted an item at a time and nested objects convert themselves.
+        assert!(matches!(
+            &to_json.body[4].kind,
+            StmtKind::For { binding, .. } if binding == "item$3"
+        ));
+        let StmtKind::Expr(Expr::MethodCall { args, .. }) = &to_json.body[2].kind else {
+            panic!("expected a call, got {:?}", to_json.body[2]);
+        };
+        assert!(matches!(&args[1], Expr::MethodCall { method, .. } if method == "toJSON"));
+
+        let from_json = module.function("Post.fromJSON").unwrap();
+        assert_eq!(
+            keys(&from_json.body),
+            vec![
+                "get(headline)",
+                "get(author)",
+                "get(tags)",
+                "has(subtitle)",
+                "get(subtitle)",
+                "has(calls)",
+                "get(calls)"
+            ]
+        );
+        let Some(Stmt {
+            kind: StmtKind::Return(Some(Expr::Object { fields, .. })),
+            ..
+        }) = from_json.body.last()
+        else {
+            panic!("expected an object, got {:?}", from_json.body.last());
+        };
+        // Fields left out of the JSON take their default.
+        assert_eq!(
+            fields[4],
+            ("draft".into(), Expr::Literal(Literal::Bool(false)))
+        );
+    }
+
+    #[test]
+    fn tagged_fields_have_to_be_serialisable() {
+        let message = |source| lower(source).unwrap_err().message;
+
+        assert_eq!(
+            message(
+                "object Window {\n\t.id int,\n}\n\nobject Button {\n\t.window *Window `json: \"window\"`,\n}\n"
+            ),
+            "`Button.window` has a `json` tag but a `*Window` can't be converted to JSON"
+        );
+        assert_eq!(
+            message(
+                "enum Shape {\n\t.CIRCLE,\n}\n\nobject Drawing implements Into<JSON> {\n\t.shape Shape,\n}\n"
+            ),
+            "`Drawing.shape` is a `Shape`, which can't be converted to JSON, tag it `json: \"-\"` to leave it out"
+        );
+        assert_eq!(
+            message("object Session implements From<JSON> {\n\t.token string `json: \"-\"`,\n}\n"),
+            "`Session.token` is left out of JSON, it needs a default for `fromJSON` to make a `Session`"
+        );
+        // Problems are reported at the field's tag, or the field when it hasn't got one.
+        let reported = |source: &'static str| {
+            let span = lower(source).unwrap_err().span;
+            &source[span.start..span.end]
+        };
+        assert_eq!(
+            reported("object Window {\n\t.id int,\n}\n\nobject Button {\n\t.window *Window `json: \"window\"`,\n}\n"),
+            "json: \"window\""
+        );
+        assert_eq!(
+            reported("enum Shape {\n\t.CIRCLE,\n}\n\nobject Drawing implements Into<JSON> {\n\t.shape Shape,\n}\n"),
+            ".shape Shape"
+        );
+
+        // What's defined by hand is kept.
+        let module = lower(
+            "object Point implements Into<JSON> {\n\t.x int,\n}\n\nfn Point.toJSON(self) -> JSON {\n\treturn self.x\n}\n",
+        )
+        .unwrap();
+        assert_eq!(module.functions.len(), 1);
+        assert!(module.imports.is_empty());
+    }
+
+    #[test]
+    fn an_empty_json_tag_is_an_error() {
+        let source = "object Author implements Into<JSON> {\n\t.name string `json: \"\"`,\n}\n";
+        let err = lower(source).unwrap_err();
+
+        assert_eq!(
+            err.message,
+            "a `json` tag needs a key, or `-` to leave the field out"
+        );
+        assert_eq!(&source[err.span.start..err.span.end], "json: \"\"");
+        // Only deriving reads the key, anywhere else the tag's only warned about.
+        assert!(lower("object Author {\n\t.name string `json: \"\"`,\n}\n").is_ok());
+    }
+}
@@ -0,0 +1,10 @@
+pub mod cascade;
+pub mod component;
+pub mod ffi;
+pub mod json;
+pub mod lower;
+pub mod macros;
+pub mod mir;
+pub mod reactivity;
+pub mod style;
+pub mod tags;
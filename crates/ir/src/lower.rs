@@ -0,0 +1,1311 @@
+// Lowering from the AST to the MIR. This is where names get decided: a `VariableAccess` of
+// `graph.nodes` becomes a field read on the local `graph` whereas `Graph.new` becomes a reference
+// to the global function, depending on what's in scope at the time.
+
+use std::fmt;
+
+use elp_parser::ast::{
+    block::ASTBlock,
+    component::ASTComponentDef,
+    elp_type::{ASTElpType, ASTMutability, ASTPointerSemantics},
+    expression::ASTExpression,
+    external::{ASTExternal, ASTExternalItem},
+    function::{ASTFunctionCall, ASTFunctionCallName, ASTFunctionDef, ASTFunctionHeaderDef},
+    module::ASTModule,
+    number::ParsedNumber,
+    object::{ASTObject, ASTVisibility},
+    r#enum::ASTEnum,
+    r#macro::ASTMacro,
+    style::{ASTStyleSheet, ASTStyleValue},
+    value_assignment::ASTOperand,
+    variable_access::ASTVariableAccess,
+    variable_assignment::{ASTVariableAssignment, ASTVariableAssignmentTarget},
+};
+
+use crate::ffi::{self, Abi, ABIS};
+use crate::json;
+use crate::macros::{Invocation, Macros};
+use crate::mir::{
+    BinaryOp, Closure, ComponentDef, Element, EnumDef, Expr, ExternalFunction, FieldDef, Function,
+    Global, Import, Literal, Module, ObjectDef, Param, Prop, Span, Stmt, StmtKind, Tag, Type,
+    VariantDef,
+};
+use crate::style::{Colour, Selector, StyleProperty, StyleRule, StyleSheet, StyleValue, Unit};
+use crate::tags;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct LowerError {
+    pub span: Span,
+    pub message: String,
+}
+
+impl fmt::Display for LowerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.span.line, self.message)
+    }
+}
+
+impl std::error::Error for LowerError {}
+
+pub type LowerResult<T> = Result<T, LowerError>;
+
+// The positional arguments of a call and its `name = value` ones.
+type Arguments = (Vec<Expr>, Vec<(String, Expr)>);
+
+pub(crate) fn error<T>(span: Span, message: impl Into<String>) -> LowerResult<T> {
+    Err(LowerError {
+        span,
+        message: message.into(),
+    })
+}
+
+pub fn lower_type(elp_type: &ASTElpType) -> Type {
+    let inner = Type::from_name(
+        &elp_type.name,
+        elp_type.generic_parameters.iter().map(lower_type).collect(),
+    );
+
+    match elp_type.pointer_semantics {
+        Some(ASTPointerSemantics::Pointer) => Type::Pointer(Box::new(inner)),
+        Some(ASTPointerSemantics::Reference) => Type::Reference(Box::new(inner)),
+        None => inner,
+    }
+}
+
+pub fn lower_module(name: &str, ast: &ASTModule) -> LowerResult<Module> {
+    lower_module_with(name, ast, &Macros::builtin())
+}
+
+// Lowers with `macros` available to `@` annotations, which are expanded as soon as the item they
+// annotate has been lowered.
+pub fn lower_module_with(name: &str, ast: &ASTModule, macros: &Macros) -> LowerResult<Module> {
+    let mut module = Module {
+        name: name.into(),
+        ..Default::default()
+    };
+    let mut annotations = vec![];
+    // The functions macros have made, with the annotation that made each.
+    let mut generated: Vec<(String, Span, String)> = vec![];
+
+    for expression in &ast.expressions {
+        if let ASTExpression::Macro(annotation) = expression {
+            annotations.push(annotation);
+            continue;
+        }
+        lower_item(&mut module, expression, false)?;
+        for annotation in annotations.drain(..) {
+            expand(&mut module, macros, annotation, expression, &mut generated)?;
+        }
+    }
+
+    if let Some(annotation) = annotations.first() {
+        return error(
+            Span::from_pest(annotation.span),
+            format!(
+                "`@{}` has to come before the item it's for",
+                annotation.name
+            ),
+        );
+    }
+    for (qualified_name, span, macro_name) in &generated {
+        let defined = module
+            .functions
+            .iter()
+            .filter(|function| function.qualified_name() == *qualified_name)
+            .count();
+        if defined > 1 {
+            return error(
+                *span,
+                format!(
+                    "`@{}` defines `{}`, which is already defined",
+                    macro_name, qualified_name
+                ),
+            );
+        }
+    }
+    json::derive(&mut module)?;
+
+    Ok(module)
+}
+
+fn expand(
+    module: &mut Module,
+    macros: &Macros,
+    annotation: &ASTMacro,
+    expression: &ASTExpression,
+    generated: &mut Vec<(String, Span, String)>,
+) -> LowerResult<()> {
+    let span = Span::from_pest(annotation.span);
+    let (item, exported) = match expression {
+        ASTExpression::Export(export) => (&export.value, true),
+        item => (item, false),
+    };
+    let Some(expander) = macros.get(&annotation.name) else {
+        // Macros from other packages are expanded by whatever provides them.
+        if module.imports.iter().any(|import| {
+            import
+                .names
+                .iter()
+                .any(|(imported, alias)| alias.as_deref().unwrap_or(imported) == annotation.name)
+        }) {
+            return Ok(());
+        }
+        return error(span, format!("there's no macro `@{}`", annotation.name));
+    };
+
+    let mut invocation = Invocation::new(
+        span,
+        &annotation.name,
+        annotation.argument.as_ref().map(lower_type),
+    );
+    let expansion = expander(&mut invocation, item)?;
+
+    for mut function in expansion.functions {
+        let qualified_name = function.qualified_name();
+        // Two macros asking for the same method, `@toString` and `@error` say, share it.
+        if generated.iter().any(|(name, ..)| *name == qualified_name) {
+            continue;
+        }
+        function.exported = exported;
+        generated.push((qualified_name, span, annotation.name.clone()));
+        module.functions.push(function);
+    }
+
+    if expansion.implements.is_empty() {
+        return Ok(());
+    }
+    let implements = match item {
+        ASTExpression::Object(object) => module
+            .objects
+            .iter_mut()
+            .find(|def| def.name == object.name)
+            .map(|def| &mut def.implements),
+        ASTExpression::Enum(r#enum) => module
+            .enums
+            .iter_mut()
+            .find(|def| def.name == r#enum.name)
+            .map(|def| &mut def.implements),
+        _ => None,
+    };
+    let Some(implements) = implements else {
+        return error(
+            span,
+            format!(
+                "`@{}` implements interfaces, which only objects and enums can do",
+                annotation.name
+            ),
+        );
+    };
+    for interface in expansion.implements {
+        if !implements.contains(&interface) {
+            implements.push(interface);
+        }
+    }
+    Ok(())
+}
+
+fn lower_item(module: &mut Module, expression: &ASTExpression, exported: bool) -> LowerResult<()> {
+    match expression {
+        ASTExpression::Export(export) => lower_item(module, &export.value, true)?,
+        ASTExpression::Import(import) => module.imports.push(Import {
+            module_path: import.module_path.clone(),
+            names: import.names.clone(),
+        }),
+        ASTExpression::Object(object) => module.objects.push(lower_object(object, exported)?),
+        ASTExpression::Enum(r#enum) => module.enums.push(lower_enum(r#enum, exported)),
+        ASTExpression::FunctionDef(function) => {
+            module.functions.push(lower_function(function, exported)?)
+        }
+        ASTExpression::FunctionHeaderDef(header) => {
+            module.externals.push(lower_function_header(header))
+        }
+        ASTExpression::External(external) => lower_external(module, external, exported)?,
+        ASTExpression::Component(component) => module
+            .components
+            .push(lower_component(component, exported)?),
+        ASTExpression::VariableAssignment(assignment) => {
+            module.globals.push(lower_global(assignment, exported)?)
+        }
+        ASTExpression::StyleSheet(sheet) => {
+            module.styles.rules.extend(lower_stylesheet(sheet)?.rules)
+        }
+        // Only checked against, there's nothing of an interface in the module.
+        ASTExpression::Interface(_) => {}
+        other => return error(expression_span(other), "expected a top level item"),
+    }
+
+    Ok(())
+}
+
+fn lower_object(object: &ASTObject, exported: bool) -> LowerResult<ObjectDef> {
+    let scope = Scope::default();
+    let mut fields = vec![];
+
+    for member in &object.members {
+        let mut tags: Vec<Tag> = vec![];
+        for tag in &member.tags {
+            let span = Span::from_pest(tag.span);
+            if tags.iter().any(|seen| seen.name == tag.name) {
+                return error(
+                    span,
+                    format!("`.{}` has more than one `{}` tag", member.name, tag.name),
+                );
+            }
+            tags.push(Tag {
+                span,
+                name: tag.name.clone(),
+                value: tag.value.clone(),
+            });
+        }
+        fields.push(FieldDef {
+            span: Span::from_pest(member.span),
+            name: member.name.clone(),
+            ty: member
+                .type_annotation
+                .as_ref()
+                .map(lower_type)
+                .unwrap_or(Type::Unknown),
+            default: match &member.default_value {
+                Some(value) => Some(scope.lower_expr(value)?),
+                None => None,
+            },
+            public: member.visibility == Some(ASTVisibility::Public),
+            tags,
+        });
+    }
+
+    Ok(ObjectDef {
+        span: Span::from_pest(object.span),
+        name: object.name.clone(),
+        generics: vec![],
+        fields,
+        implements: object
+            .implements
+            .iter()
+            .flat_map(|implements| implements.types.iter().map(lower_type))
+            .collect(),
+        exported,
+    })
+}
+
+fn lower_enum(r#enum: &ASTEnum, exported: bool) -> EnumDef {
+    EnumDef {
+        span: Span::from_pest(r#enum.span),
+        name: r#enum.name.clone(),
+        variants: r#enum
+            .members
+            .iter()
+            .map(|member| VariantDef {
+                name: member.name.clone(),
+                payload: member.parameters.iter().map(lower_type).collect(),
+            })
+            .collect(),
+        implements: r#enum.implements.iter().map(lower_type).collect(),
+        exported,
+    }
+}
+
+// A `Stylesheet { .. }` block or a `.selp` file. Whether each property takes the value it's given
+// is left to `StyleSheet::check`, only values that aren't anything at all are errors here.
+pub fn lower_stylesheet(sheet: &ASTStyleSheet) -> LowerResult<StyleSheet> {
+    let mut rules = vec![];
+    for rule in &sheet.rules {
+        let mut properties = vec![];
+        for property in &rule.properties {
+            properties.push(StyleProperty {
+                span: Span::from_pest(property.span),
+                name: property.name.clone(),
+                value: lower_style_value(&property.value)?,
+            });
+        }
+        rules.push(StyleRule {
+            span: Span::from_pest(rule.span),
+            selectors: rule
+                .selectors
+                .iter()
+                .map(|selector| Selector {
+                    component: selector.component.clone(),
+                    classes: selector.classes.clone(),
+                })
+                .collect(),
+            properties,
+        });
+    }
+
+    Ok(StyleSheet { rules })
+}
+
+fn lower_style_value(value: &ASTStyleValue) -> LowerResult<StyleValue> {
+    let number = |value: &ParsedNumber| match *value {
+        ParsedNumber::Float(value) => value,
+        ParsedNumber::Integer(value)
+        | ParsedNumber::Hexadecimal(value)
+        | ParsedNumber::Binary(value)
+        | ParsedNumber::Octal(value) => value as f64,
+    };
+
+    Ok(match value {
+        ASTStyleValue::Number(value) => StyleValue::Number(number(&value.value)),
+        ASTStyleValue::Length(value, unit) => StyleValue::Length(
+            number(&value.value),
+            Unit::from_name(unit).expect("the grammar only has known units"),
+        ),
+        ASTStyleValue::Colour(span, digits) => match Colour::from_hex(digits) {
+            Some(colour) => StyleValue::Colour(colour),
+            None => {
+                return error(
+                    Span::from_pest(span),
+                    format!(
+                        "`#{}` isn't a colour, colours have 3, 4, 6 or 8 hex digits",
+                        digits
+                    ),
+                )
+            }
+        },
+        ASTStyleValue::String(string) => StyleValue::String(string.value.clone()),
+        ASTStyleValue::EnumCase(_, case) => StyleValue::Enum {
+            ty: None,
+            case: case.clone(),
+        },
+        ASTStyleValue::VariableAccess(access) => match access.names.as_slice() {
+            [name] if name == "true" || name == "false" => StyleValue::Bool(name == "true"),
+            [ty, case] => StyleValue::Enum {
+                ty: Some(ty.clone()),
+                case: case.clone(),
+            },
+            _ => {
+                return error(
+                    Span::from_pest(access.span),
+                    format!(
+                        "styles can only be set to literal values, `{}` isn't one",
+                        access.names.join(".")
+                    ),
+                )
+            }
+        },
+    })
+}
+
+// `Graph.new` is declared on `Graph`, `main` isn't declared on anything.
+fn split_function_name(name: &ASTVariableAccess) -> (Option<String>, String) {
+    match name.names.split_last() {
+        Some((last, [])) => (None, last.clone()),
+        Some((last, receiver)) => (Some(receiver.join(".")), last.clone()),
+        None => (None, String::new()),
+    }
+}
+
+fn lower_params(
+    receiver: &Option<String>,
+    arguments: &[elp_parser::ast::function::ASTFunctionArgument],
+) -> Vec<Param> {
+    arguments
+        .iter()
+        .map(|argument| {
+            let ty = match (&argument.type_annotation, receiver) {
+                (Some(annotation), _) => lower_type(annotation),
+                // `self` doesn't need annotating, it's always the receiver.
+                (None, Some(receiver)) if argument.name == "self" => {
+                    Type::from_name(receiver, vec![])
+                }
+                (None, _) => Type::Unknown,
+            };
+
+            Param {
+                name: argument.name.clone(),
+                ty: match argument.pointer_semantics {
+                    Some(ASTPointerSemantics::Reference) => Type::Reference(Box::new(ty)),
+                    Some(ASTPointerSemantics::Pointer) => Type::Pointer(Box::new(ty)),
+                    None => ty,
+                },
+            }
+        })
+        .collect()
+}
+
+fn lower_return_type(types: Option<&[ASTElpType]>) -> Type {
+    match types {
+        Some([single]) => lower_type(single),
+        // Multiple return values aren't something the MIR models yet.
+        Some(_) | None => Type::Void,
+    }
+}
+
+fn lower_function(function: &ASTFunctionDef, exported: bool) -> LowerResult<Function> {
+    let (receiver, name) = split_function_name(&function.name);
+    let params = lower_params(
+        &receiver,
+        function
+            .arguments
+            .as_ref()
+            .map(|arguments| arguments.arguments.as_slice())
+            .unwrap_or_default(),
+    );
+    let mut scope = Scope::default();
+    scope.push_names(params.iter().map(|param| param.name.clone()));
+
+    let mut ret = lower_return_type(
+        function
+            .return_type
+            .as_ref()
+            .map(|ret| ret.type_annotations.as_slice()),
+    );
+    if let (Some(receiver), Type::Named { name, .. }) = (&receiver, &mut ret) {
+        if name == "Self" {
+            *name = receiver.clone();
+        }
+    }
+
+    Ok(Function {
+        span: Span::from_pest(function.span),
+        name,
+        receiver,
+        generics: function
+            .generics
+            .iter()
+            .map(|generic| generic.name.clone())
+            .collect(),
+        params,
+        ret,
+        body: scope.lower_block(&function.block)?,
+        exported,
+    })
+}
+
+fn lower_component(component: &ASTComponentDef, exported: bool) -> LowerResult<ComponentDef> {
+    let params = lower_params(
+        &None,
+        component
+            .arguments
+            .as_ref()
+            .map(|arguments| arguments.arguments.as_slice())
+            .unwrap_or_default(),
+    );
+    let mut scope = Scope::default();
+    scope.push_names(params.iter().map(|param| param.name.clone()));
+
+    let mut props = vec![];
+    let mut body = vec![];
+    for expression in &component.block.expressions {
+        match expression {
+            ASTExpression::Export(export) => {
+                let prop = lower_prop(&scope, &export.value)?;
+                scope.push_names([prop.name.clone()]);
+                props.push(prop);
+            }
+            expression => body.push(scope.lower_stmt(expression)?),
+        }
+    }
+
+    Ok(ComponentDef {
+        span: Span::from_pest(component.span),
+        name: component.name.names.join("."),
+        params,
+        props,
+        body,
+        exported,
+    })
+}
+
+fn lower_prop(scope: &Scope, expression: &ASTExpression) -> LowerResult<Prop> {
+    let span = expression_span(expression);
+    let (declaration, default) = match expression {
+        ASTExpression::VariableDeclaration(declaration) => (&**declaration, None),
+        ASTExpression::VariableAssignment(assignment) => {
+            match &assignment.variable_assignment_target {
+                ASTVariableAssignmentTarget::VariableDeclaration(declaration) => (
+                    declaration,
+                    Some(scope.lower_expr(&assignment.value_assignment.value)?),
+                ),
+                ASTVariableAssignmentTarget::VariableAccess(_) => {
+                    return error(span, "only declarations can be exported from a component")
+                }
+            }
+        }
+        ASTExpression::FunctionHeaderDef(header) => {
+            let callback = Type::Function {
+                params: lower_params(&None, &header.arguments.arguments)
+                    .into_iter()
+                    .map(|param| param.ty)
+                    .collect(),
+                ret: Box::new(lower_return_type(Some(
+                    &header.return_type.type_annotations,
+                ))),
+            };
+            return Ok(Prop {
+                span,
+                name: header.name.names.join("."),
+                ty: match header.pointer_semantics {
+                    Some(ASTPointerSemantics::Pointer) => Type::Pointer(Box::new(callback)),
+                    Some(ASTPointerSemantics::Reference) => Type::Reference(Box::new(callback)),
+                    None => callback,
+                },
+                default: None,
+            });
+        }
+        _ => return error(span, "only declarations can be exported from a component"),
+    };
+
+    Ok(Prop {
+        span,
+        name: declaration.name.clone(),
+        ty: declaration
+            .type_annotation
+            .as_deref()
+            .map(lower_type)
+            .unwrap_or(Type::Unknown),
+        default,
+    })
+}
+
+fn lower_function_header(header: &ASTFunctionHeaderDef) -> ExternalFunction {
+    let (receiver, name) = split_function_name(&header.name);
+
+    ExternalFunction {
+        span: Span::from_pest(header.span),
+        name: match receiver {
+            Some(receiver) => format!("{}.{}", receiver, name),
+            None => name,
+        },
+        generics: header
+            .generics
+            .iter()
+            .map(|generic| generic.name.clone())
+            .collect(),
+        params: lower_params(&None, &header.arguments.arguments),
+        ret: lower_return_type(Some(&header.return_type.type_annotations)),
+        abi: Abi::default(),
+    }
+}
+
+// Items defined outside elp. Their objects are laid out like any other, fields in the order
+// they're written, which is how C lays out a struct.
+fn lower_external(module: &mut Module, external: &ASTExternal, exported: bool) -> LowerResult<()> {
+    let abi = match &external.abi {
+        None => Abi::default(),
+        Some(abi) => match Abi::from_name(&abi.name) {
+            Some(known) => known,
+            None => {
+                let message = match tags::suggest(&abi.name, ABIS) {
+                    Some(name) => {
+                        format!("there's no ABI `{}`, did you mean `{}`?", abi.name, name)
+                    }
+                    None => format!(
+                        "there's no ABI `{}`, it can be one of {}",
+                        abi.name,
+                        ABIS.join(", ")
+                    ),
+                };
+                return error(Span::from_pest(abi.span), message);
+            }
+        },
+    };
+
+    for item in &external.items {
+        let header = match item {
+            ASTExternalItem::Function(header) => header,
+            ASTExternalItem::Object(object) => {
+                module.objects.push(lower_object(object, exported)?);
+                continue;
+            }
+        };
+        let mut function = lower_function_header(header);
+        function.abi = abi;
+        // `(T, OBJC_Selector)`, signatures copied from C often only give the types.
+        for (index, (param, argument)) in function
+            .params
+            .iter_mut()
+            .zip(&header.arguments.arguments)
+            .enumerate()
+        {
+            if argument.type_annotation.is_some() {
+                continue;
+            }
+            let ty = Type::from_name(&argument.name, vec![]);
+            param.name = format!("arg{}", index + 1);
+            param.ty = match argument.pointer_semantics {
+                Some(ASTPointerSemantics::Reference) => Type::Reference(Box::new(ty)),
+                Some(ASTPointerSemantics::Pointer) => Type::Pointer(Box::new(ty)),
+                None => ty,
+            };
+        }
+        let types = function.params.iter().map(|param| &param.ty);
+        for name in types.chain([&function.ret]).filter_map(Type::named) {
+            if let Some(elp) = ffi::elp_name(name) {
+                return error(
+                    function.span,
+                    format!("`{}` is C's name for it, in elp it's `{}`", name, elp),
+                );
+            }
+        }
+        module.externals.push(function);
+    }
+    Ok(())
+}
+
+fn lower_global(assignment: &ASTVariableAssignment, exported: bool) -> LowerResult<Global> {
+    let ASTVariableAssignmentTarget::VariableDeclaration(declaration) =
+        &assignment.variable_assignment_target
+    else {
+        return error(
+            Span::from_pest(assignment.span),
+            "only declarations are allowed at the top level",
+        );
+    };
+
+    Ok(Global {
+        span: Span::from_pest(assignment.span),
+        name: declaration.name.clone(),
+        mutable: declaration.mutability == ASTMutability::Mutable,
+        ty: declaration
+            .type_annotation
+            .as_deref()
+            .map(lower_type)
+            .unwrap_or(Type::Unknown),
+        value: Scope::default().lower_expr(&assignment.value_assignment.value)?,
+        exported,
+    })
+}
+
+fn expression_span(expression: &ASTExpression) -> Span {
+    Span::from_pest(match expression {
+        ASTExpression::Block(block) => block.span,
+        ASTExpression::Component(component) => component.span,
+        ASTExpression::ElpType(elp_type) => elp_type.span,
+        ASTExpression::Enum(r#enum) => r#enum.span,
+        ASTExpression::Number(number) => number.span,
+        ASTExpression::Export(export) => export.span,
+        ASTExpression::External(external) => external.span,
+        ASTExpression::FunctionDef(function) => function.span,
+        ASTExpression::FunctionCall(call) => call.span,
+        ASTExpression::FunctionComponentCall(call) => call.span,
+        ASTExpression::FunctionHeaderDef(header) => header.span,
+        ASTExpression::FunctionReturnValue(ret) => ret.span,
+        ASTExpression::Import(import) => import.span,
+        ASTExpression::Interface(interface) => interface.span,
+        ASTExpression::Macro(annotation) => annotation.span,
+        ASTExpression::Object(object) => object.span,
+        ASTExpression::Precomp(directive) => directive.span,
+        ASTExpression::String(string) => string.span,
+        ASTExpression::StyleSheet(sheet) => sheet.span,
+        ASTExpression::ValueAssignment(assignment) => assignment.span,
+        ASTExpression::VariableAccess(access) => access.span,
+        ASTExpression::VariableAssignment(assignment) => assignment.span,
+        ASTExpression::VariableDeclaration(declaration) => declaration.span,
+        // Pointer semantics on their own don't carry a span.
+        ASTExpression::PointerSemantics(_) => return Span::default(),
+    })
+}
+
+fn compound_operator(operand: &ASTOperand) -> Option<Option<BinaryOp>> {
+    match operand {
+        ASTOperand::Equals => Some(None),
+        ASTOperand::Plus => Some(Some(BinaryOp::Add)),
+        ASTOperand::Minus => Some(Some(BinaryOp::Sub)),
+        ASTOperand::Multiply => Some(Some(BinaryOp::Mul)),
+        ASTOperand::Divide => Some(Some(BinaryOp::Div)),
+        ASTOperand::Modulo => Some(Some(BinaryOp::Mod)),
+        ASTOperand::Power => Some(Some(BinaryOp::Pow)),
+        ASTOperand::BitAnd => Some(Some(BinaryOp::BitAnd)),
+        ASTOperand::BitOr => Some(Some(BinaryOp::BitOr)),
+        ASTOperand::LeftShift => Some(Some(BinaryOp::Shl)),
+        ASTOperand::RightShift => Some(Some(BinaryOp::Shr)),
+        ASTOperand::EqualityBitNot
+        | ASTOperand::EqualityEqual
+        | ASTOperand::EqualityNot
+        | ASTOperand::Tilde => None,
+    }
+}
+
+// Tracks which names are locals so that everything else can be treated as a global.
+#[derive(Debug, Default, Clone)]
+struct Scope {
+    names: Vec<String>,
+}
+
+impl Scope {
+    fn push_names(&mut self, names: impl IntoIterator<Item = String>) {
+        self.names.extend(names);
+    }
+
+    fn is_local(&self, name: &str) -> bool {
+        self.names.iter().any(|local| local == name)
+    }
+
+    fn lower_block(&mut self, block: &ASTBlock) -> LowerResult<Vec<Stmt>> {
+        let depth = self.names.len();
+        let stmts = block
+            .expressions
+            .iter()
+            .map(|expression| self.lower_stmt(expression))
+            .collect();
+        self.names.truncate(depth);
+
+        stmts
+    }
+
+    fn lower_stmt(&mut self, expression: &ASTExpression) -> LowerResult<Stmt> {
+        let span = expression_span(expression);
+        let kind = match expression {
+            ASTExpression::VariableAssignment(assignment) => self.lower_assignment(assignment)?,
+            ASTExpression::VariableDeclaration(declaration) => {
+                self.names.push(declaration.name.clone());
+                StmtKind::Let {
+                    name: declaration.name.clone(),
+                    mutable: declaration.mutability == ASTMutability::Mutable,
+                    ty: declaration
+                        .type_annotation
+                        .as_deref()
+                        .map(lower_type)
+                        .unwrap_or(Type::Unknown),
+                    value: None,
+                }
+            }
+            ASTExpression::FunctionReturnValue(ret) => {
+                StmtKind::Return(Some(self.lower_expr(&ret.value)?))
+            }
+            // Functions declared inside a block are closures bound to a local.
+            ASTExpression::FunctionDef(function) if function.name.names.len() == 1 => {
+                let closure = self.lower_closure(function)?;
+                self.names.push(function.name.names[0].clone());
+                StmtKind::Let {
+                    name: function.name.names[0].clone(),
+                    mutable: false,
+                    ty: Type::Unknown,
+                    value: Some(closure),
+                }
+            }
+            other => StmtKind::Expr(self.lower_expr(other)?),
+        };
+
+        Ok(Stmt { span, kind })
+    }
+
+    fn lower_assignment(&mut self, assignment: &ASTVariableAssignment) -> LowerResult<StmtKind> {
+        let value = self.lower_expr(&assignment.value_assignment.value)?;
+
+        match &assignment.variable_assignment_target {
+            ASTVariableAssignmentTarget::VariableDeclaration(declaration) => {
+                if assignment.value_assignment.operand != ASTOperand::Equals {
+                    return error(
+                        Span::from_pest(assignment.span),
+                        "declarations can only be assigned with `=`",
+                    );
+                }
+                self.names.push(declaration.name.clone());
+
+                Ok(StmtKind::Let {
+                    name: declaration.name.clone(),
+                    mutable: declaration.mutability == ASTMutability::Mutable,
+                    ty: declaration
+                        .type_annotation
+                        .as_deref()
+                        .map(lower_type)
+                        .unwrap_or(Type::Unknown),
+                    value: Some(value),
+                })
+            }
+            ASTVariableAssignmentTarget::VariableAccess(access) => {
+                let Some(op) = compound_operator(&assignment.value_assignment.operand) else {
+                    return error(
+                        Span::from_pest(assignment.value_assignment.span),
+                        "comparison operators can't be used to assign a value",
+                    );
+                };
+
+                Ok(StmtKind::Assign {
+                    target: self.lower_access(access),
+                    op,
+                    value,
+                })
+            }
+        }
+    }
+
+    fn lower_closure(&self, function: &ASTFunctionDef) -> LowerResult<Expr> {
+        let params = lower_params(
+            &None,
+            function
+                .arguments
+                .as_ref()
+                .map(|arguments| arguments.arguments.as_slice())
+                .unwrap_or_default(),
+        );
+        let mut scope = self.clone();
+        scope.push_names(params.iter().map(|param| param.name.clone()));
+
+        Ok(Expr::Closure(Box::new(Closure {
+            ret: lower_return_type(
+                function
+                    .return_type
+                    .as_ref()
+                    .map(|ret| ret.type_annotations.as_slice()),
+            ),
+            body: scope.lower_block(&function.block)?,
+            params,
+        })))
+    }
+
+    fn lower_access(&self, access: &ASTVariableAccess) -> Expr {
+        let mut names = access.names.iter();
+        let Some(first) = names.next() else {
+            return Expr::Literal(Literal::Nil);
+        };
+
+        let mut expr = match first.as_str() {
+            "true" => Expr::Literal(Literal::Bool(true)),
+            "false" => Expr::Literal(Literal::Bool(false)),
+            "nil" => Expr::Literal(Literal::Nil),
+            name if self.is_local(name) => Expr::Local(name.into()),
+            name => Expr::Global(name.into()),
+        };
+
+        for name in names {
+            expr = Expr::Field {
+                base: Box::new(expr),
+                name: name.clone(),
+            };
+        }
+
+        expr
+    }
+
+    // `Column(class = "row") { .. }`, the arguments of the call and the statements of its block.
+    fn lower_element(&self, call: &ASTFunctionCall, children: Vec<Stmt>) -> LowerResult<Expr> {
+        let ASTFunctionCallName::VariableAccess(access) = &call.name else {
+            return error(Span::from_pest(call.span), "elements need a name");
+        };
+        let (args, props) = self.lower_arguments(call)?;
+
+        Ok(Expr::Element(Box::new(Element {
+            name: access.names.join("."),
+            args,
+            props,
+            children,
+        })))
+    }
+
+    // Splits the arguments of a call into positional ones and `name = value` ones.
+    fn lower_arguments(&self, call: &ASTFunctionCall) -> LowerResult<Arguments> {
+        let mut args = vec![];
+        let mut props = vec![];
+        for argument in &call.arguments {
+            match argument {
+                ASTExpression::VariableAssignment(assignment)
+                    if assignment.value_assignment.operand == ASTOperand::Equals =>
+                {
+                    let ASTVariableAssignmentTarget::VariableAccess(access) =
+                        &assignment.variable_assignment_target
+                    else {
+                        return error(
+                            Span::from_pest(assignment.span),
+                            "variables can't be declared in arguments",
+                        );
+                    };
+                    props.push((
+                        access.names.join("."),
+                        self.lower_expr(&assignment.value_assignment.value)?,
+                    ));
+                }
+                argument => args.push(self.lower_expr(argument)?),
+            }
+        }
+
+        Ok((args, props))
+    }
+
+    fn lower_call(&self, call: &ASTFunctionCall) -> LowerResult<Expr> {
+        let (args, props) = self.lower_arguments(call)?;
+        // Only elements take named arguments.
+        if !props.is_empty() {
+            return self.lower_element(call, vec![]);
+        }
+
+        Ok(match &call.name {
+            ASTFunctionCallName::ContextualVariableAccess(access) => Expr::Variant {
+                enum_name: None,
+                variant: access.name.clone(),
+                args,
+            },
+            ASTFunctionCallName::VariableAccess(access) => match access.names.split_last() {
+                // `graph.addNode(..)` calls a method on a local.
+                Some((method, receiver)) if !receiver.is_empty() && self.is_local(&receiver[0]) => {
+                    let receiver = self.lower_access(&ASTVariableAccess {
+                        span: access.span,
+                        pointer_semantics: vec![],
+                        names: receiver.to_vec(),
+                    });
+
+                    Expr::MethodCall {
+                        receiver: Box::new(receiver),
+                        method: method.clone(),
+                        args,
+                    }
+                }
+                // `Graph.new(..)` and `println(..)` both call something global.
+                _ => Expr::Call {
+                    callee: Box::new(match access.names.as_slice() {
+                        [single] if self.is_local(single) => Expr::Local(single.clone()),
+                        names => Expr::Global(names.join(".")),
+                    }),
+                    args,
+                },
+            },
+        })
+    }
+
+    fn lower_expr(&self, expression: &ASTExpression) -> LowerResult<Expr> {
+        Ok(match expression {
+            ASTExpression::Number(number) => Expr::Literal(match number.value {
+                ParsedNumber::Float(value) => Literal::Float(value),
+                ParsedNumber::Integer(value)
+                | ParsedNumber::Hexadecimal(value)
+                | ParsedNumber::Binary(value)
+                | ParsedNumber::Octal(value) => Literal::Int(value),
+            }),
+            ASTExpression::String(string) => Expr::Literal(Literal::String(string.value.clone())),
+            ASTExpression::VariableAccess(access) => self.lower_access(access),
+            ASTExpression::FunctionCall(call) => self.lower_call(call)?,
+            ASTExpression::FunctionComponentCall(call) => {
+                let children = self.clone().lower_block(&call.children)?;
+                self.lower_element(&call.call, children)?
+            }
+            other => return error(expression_span(other), "expected an expression"),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use elp_parser::{
+        ast::traits::FromCST,
+        cst::{style::CSTStyleSheetFile, CSTModule},
+        parser::{ElpParser, Rule},
+    };
+    use from_pest::FromPest;
+    use pest::Parser;
+    use pretty_assertions::assert_eq;
+
+    fn lower_source(source: &str) -> LowerResult<Module> {
+        let mut pairs = ElpParser::parse(Rule::module, source).unwrap();
+        let cst = CSTModule::from_pest(&mut pairs).unwrap();
+        let ast = ASTModule::from_cst(&cst);
+
+        lower_module("test", &ast)
+    }
+
+    #[test]
+    fn lowers_externals() {
+        let module = lower_source(
+            "external \"ObjC\" {
+                fn OBJC_msgSend<T: OBJC_Obj + Send>(T, *OBJC_Selector) -> T
+                object OBJC_Selector {
+                    .name string,
+                }
+            }",
+        )
+        .unwrap();
+
+        let external = &module.externals[0];
+        assert_eq!(external.abi, Abi::ObjectiveC);
+        assert_eq!(
+            external.params,
+            vec![
+                Param {
+                    name: "arg1".into(),
+                    ty: Type::from_name("T", vec![]),
+                },
+                Param {
+                    name: "arg2".into(),
+                    ty: Type::Pointer(Box::new(Type::from_name("OBJC_Selector", vec![]))),
+                },
+            ]
+        );
+        assert_eq!(module.objects[0].name, "OBJC_Selector");
+
+        let err =
+            lower_source("external \"StdCall\" fn MessageBeep(kind uint32) -> bool").unwrap_err();
+        assert_eq!(
+            err.message,
+            "there's no ABI `StdCall`, did you mean `stdcall`?"
+        );
+        let err = lower_source("external fn sqrt(value double) -> double").unwrap_err();
+        assert_eq!(
+            err.message,
+            "`double` is C's name for it, in elp it's `float64`"
+        );
+    }
+
+    #[test]
+    fn lowers_methods_and_receivers() {
+        let module = lower_source(
+            "export fn Graph.addNode(&self, node string) -> uint {
+                const id = self.nodes.append(node)
+                return id
+            }",
+        )
+        .unwrap();
+
+        let function = &module.functions[0];
+        assert_eq!(function.qualified_name(), "Graph.addNode");
+        assert!(function.exported);
+        assert!(function.is_method());
+        assert_eq!(
+            function.params,
+            vec![
+                Param {
+                    name: "self".into(),
+                    ty: Type::Reference(Box::new(Type::Named {
+                        name: "Graph".into(),
+                        generics: vec![]
+                    })),
+                },
+                Param {
+                    name: "node".into(),
+                    ty: Type::String,
+                },
+            ]
+        );
+        assert_eq!(
+            function.body[0].kind,
+            StmtKind::Let {
+                name: "id".into(),
+                mutable: false,
+                ty: Type::Unknown,
+                value: Some(Expr::MethodCall {
+                    receiver: Box::new(Expr::Field {
+                        base: Box::new(Expr::Local("self".into())),
+                        name: "nodes".into(),
+                    }),
+                    method: "append".into(),
+                    args: vec![Expr::Local("node".into())],
+                }),
+            }
+        );
+        assert_eq!(
+            function.body[1].kind,
+            StmtKind::Return(Some(Expr::Local("id".into())))
+        );
+        assert_eq!(function.body[1].span.line, 3);
+    }
+
+    #[test]
+    fn lowers_globals_and_compound_assignment() {
+        let module = lower_source(
+            "fn main {
+                var count = 1
+                count += 2
+                println(count)
+            }",
+        )
+        .unwrap();
+
+        let body = &module.functions[0].body;
+        assert_eq!(
+            body[1].kind,
+            StmtKind::Assign {
+                target: Expr::Local("count".into()),
+                op: Some(BinaryOp::Add),
+                value: Expr::Literal(Literal::Int(2)),
+            }
+        );
+        assert_eq!(
+            body[2].kind,
+            StmtKind::Expr(Expr::Call {
+                callee: Box::new(Expr::Global("println".into())),
+                args: vec![Expr::Local("count".into())],
+            })
+        );
+    }
+
+    #[test]
+    fn lowers_objects_and_enums() {
+        let module = lower_source(
+            "object User {
+                public .name string,
+                .age int = 3,
+            }
+            export enum Actions {
+                .LOGIN(User),
+                .LOGOUT,
+            }",
+        )
+        .unwrap();
+
+        assert_eq!(
+            module.objects[0].fields,
+            vec![
+                FieldDef {
+                    span: Span {
+                        start: 30,
+                        end: 49,
+                        line: 2
+                    },
+                    name: "name".into(),
+                    ty: Type::String,
+                    default: None,
+                    public: true,
+                    tags: vec![],
+                },
+                FieldDef {
+                    span: Span {
+                        start: 67,
+                        end: 79,
+                        line: 3
+                    },
+                    name: "age".into(),
+                    ty: Type::Int {
+                        bits: 64,
+                        signed: true
+                    },
+                    default: Some(Expr::Literal(Literal::Int(3))),
+                    public: false,
+                    tags: vec![],
+                },
+            ]
+        );
+        assert_eq!(module.enums[0].variants.len(), 2);
+        assert!(module.enums[0].exported);
+        assert_eq!(
+            module.enums[0].variants[0].payload,
+            vec![Type::Named {
+                name: "User".into(),
+                generics: vec![]
+            }]
+        );
+    }
+
+    #[test]
+    fn nested_functions_become_closures() {
+        let module = lower_source(
+            "fn outer {
+                const step = 2
+                fn add(value int) -> int {
+                    return step
+                }
+            }",
+        )
+        .unwrap();
+
+        let StmtKind::Let {
+            name,
+            value: Some(Expr::Closure(closure)),
+            ..
+        } = &module.functions[0].body[1].kind
+        else {
+            panic!("expected a closure");
+        };
+        assert_eq!(name, "add");
+        assert_eq!(
+            closure.body[0].kind,
+            StmtKind::Return(Some(Expr::Local("step".into())))
+        );
+    }
+
+    #[test]
+    fn lowers_components_and_elements() {
+        let module = lower_source(
+            "export component Counter(label string) {
+                var count = 0
+                return Column(class = \"counter\") {
+                    Text(label)
+                    Button(onClick = count)
+                }
+            }",
+        )
+        .unwrap();
+
+        let component = &module.components[0];
+        assert_eq!(component.name, "Counter");
+        assert!(component.exported);
+        assert_eq!(component.params[0].ty, Type::String);
+
+        let StmtKind::Return(Some(Expr::Element(element))) = &component.body[1].kind else {
+            panic!("expected an element to be returned");
+        };
+        assert_eq!(element.name, "Column");
+        assert_eq!(
+            element.props,
+            vec![(
+                "class".into(),
+                Expr::Literal(Literal::String("counter".into()))
+            )]
+        );
+        let children = element
+            .children
+            .iter()
+            .map(|child| child.kind.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            children,
+            vec![
+                StmtKind::Expr(Expr::Call {
+                    callee: Box::new(Expr::Global("Text".into())),
+                    args: vec![Expr::Local("label".into())],
+                }),
+                StmtKind::Expr(Expr::Element(Box::new(Element {
+                    name: "Button".into(),
+                    args: vec![],
+                    props: vec![("onClick".into(), Expr::Local("count".into()))],
+                    children: vec![],
+                }))),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_expressions_at_the_top_level() {
+        let err = lower_source("\"hello\"").unwrap_err();
+        assert_eq!(err.message, "expected a top level item");
+        assert_eq!(err.span.line, 1);
+    }
+
+    #[test]
+    fn lowers_stylesheet_files() {
+        let source = include_str!("../../../examples/BlogStyle/styles.selp");
+        let mut pairs = ElpParser::parse(Rule::stylesheet_file, source).unwrap();
+        let cst = CSTStyleSheetFile::from_pest(&mut pairs).unwrap();
+        let sheet = lower_stylesheet(&ASTStyleSheet::from_cst(&cst)).unwrap();
+
+        assert_eq!(
+            sheet
+                .rules
+                .iter()
+                .map(|rule| (rule.selectors.clone(), rule.properties[0].value.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    vec![Selector {
+                        component: Some("App".into()),
+                        classes: vec![],
+                    }],
+                    StyleValue::Bool(true),
+                ),
+                (
+                    vec![Selector {
+                        component: Some("Text".into()),
+                        classes: vec![],
+                    }],
+                    StyleValue::String("Patua One".into()),
+                ),
+            ]
+        );
+        assert_eq!(sheet.check(), vec![]);
+    }
+
+    #[test]
+    fn style_values_have_to_be_something() {
+        let err = lower_source("Stylesheet { Text { .padding = spacing } }").unwrap_err();
+        assert_eq!(
+            err.message,
+            "styles can only be set to literal values, `spacing` isn't one"
+        );
+
+        let err = lower_source("Stylesheet { Text { .color = #12345 } }").unwrap_err();
+        assert_eq!(
+            err.message,
+            "`#12345` isn't a colour, colours have 3, 4, 6 or 8 hex digits"
+        );
+    }
+}
@@ -0,0 +1,312 @@
+// `@name` annotations. A macro looks at the AST of the item it annotates and hands back items to
+// add to the module, methods and interfaces for the item to implement. Everything it makes is
+// placed at the annotation, so an error in generated code points at the `@` that asked for it.
+// Locals a macro introduces come from `Invocation::fresh`, whose names have a `$` in them and
+// can't be written in source, so they never capture or shadow the user's own names.
+
+use elp_parser::ast::{expression::ASTExpression, r#enum::ASTEnum};
+
+use crate::{
+    lower::{error, lower_type, LowerResult},
+    mir::{
+        BinaryOp, Expr, Function, Literal, MatchArm, Param, Pattern, Span, Stmt, StmtKind, Type,
+    },
+    tags::TagGrammar,
+};
+
+pub struct Invocation {
+    pub span: Span,
+    pub name: String,
+    // `@name(Type)` is given `Type`.
+    pub argument: Option<Type>,
+    fresh: usize,
+}
+
+impl Invocation {
+    pub fn new(span: Span, name: impl Into<String>, argument: Option<Type>) -> Self {
+        Self {
+            span,
+            name: name.into(),
+            argument,
+            fresh: 0,
+        }
+    }
+
+    // A local no other expansion and nothing in the source can be using.
+    pub fn fresh(&mut self, name: &str) -> String {
+        self.fresh += 1;
+        format!("{}${}", name, self.fresh)
+    }
+
+    pub fn stmt(&self, kind: StmtKind) -> Stmt {
+        Stmt {
+            span: self.span,
+            kind,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Expansion {
+    pub functions: Vec<Function>,
+    // Interfaces the annotated object or enum now implements.
+    pub implements: Vec<Type>,
+}
+
+pub type Expander = fn(&mut Invocation, &ASTExpression) -> LowerResult<Expansion>;
+
+#[derive(Clone, Default)]
+pub struct Macros {
+    expanders: Vec<(String, Expander)>,
+    // Tag keys the macros read off the fields of what they annotate, so they aren't warned about.
+    tags: Vec<(String, TagGrammar)>,
+}
+
+impl Macros {
+    // The macros every module can use without importing them.
+    pub fn builtin() -> Self {
+        Macros::default()
+            .with("toString", to_string)
+            .with("error", error_enum)
+            .with("toJSON", to_json)
+    }
+
+    pub fn with(mut self, name: impl Into<String>, expander: Expander) -> Self {
+        self.expanders.push((name.into(), expander));
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<Expander> {
+        self.expanders
+            .iter()
+            .rev()
+            .find(|(macro_name, _)| macro_name == name)
+            .map(|(_, expander)| *expander)
+    }
+
+    pub fn with_tag(mut self, key: impl Into<String>, grammar: TagGrammar) -> Self {
+        self.tags.push((key.into(), grammar));
+        self
+    }
+
+    pub fn tags(&self) -> impl Iterator<Item = (&str, TagGrammar)> {
+        self.tags
+            .iter()
+            .map(|(key, grammar)| (key.as_str(), *grammar))
+    }
+}
+
+fn annotated_enum<'e, 'a>(
+    invocation: &Invocation,
+    item: &'e ASTExpression<'a>,
+) -> LowerResult<&'e ASTEnum<'a>> {
+    match item {
+        ASTExpression::Enum(r#enum) => Ok(r#enum),
+        _ => error(
+            invocation.span,
+            format!("`@{}` can only be used on enums", invocation.name),
+        ),
+    }
+}
+
+// `@toString` describes each case by its name, followed by what it carries when that's a string:
+// `.BAD_REQUEST("no email")` becomes `BAD_REQUEST: no email`.
+fn to_string(invocation: &mut Invocation, item: &ASTExpression) -> LowerResult<Expansion> {
+    let r#enum = annotated_enum(invocation, item)?;
+    Ok(Expansion {
+        functions: vec![describe_cases(invocation, r#enum)],
+        implements: vec![],
+    })
+}
+
+// `@error` makes an enum an `Error`, which only asks for a `toString`.
+fn error_enum(invocation: &mut Invocation, item: &ASTExpression) -> LowerResult<Expansion> {
+    let r#enum = annotated_enum(invocation, item)?;
+    Ok(Expansion {
+        functions: vec![describe_cases(invocation, r#enum)],
+        implements: vec![Type::from_name("Error", vec![])],
+    })
+}
+
+// `@toJSON` asks for the same conversions as implementing `FromTo<JSON>`, they're derived once
+// the whole module is lowered.
+fn to_json(invocation: &mut Invocation, item: &ASTExpression) -> LowerResult<Expansion> {
+    let ASTExpression::Object(_) = item else {
+        return error(
+            invocation.span,
+            format!("`@{}` can only be used on objects", invocation.name),
+        );
+    };
+    Ok(Expansion {
+        functions: vec![],
+        implements: vec![Type::from_name(
+            "FromTo",
+            vec![Type::from_name("JSON", vec![])],
+        )],
+    })
+}
+
+fn describe_cases(invocation: &mut Invocation, r#enum: &ASTEnum) -> Function {
+    let arms = r#enum
+        .members
+        .iter()
+        .map(|member| {
+            let carries_string = member
+                .parameters
+                .first()
+                .is_some_and(|parameter| lower_type(parameter) == Type::String);
+            match carries_string {
+                true => {
+                    let detail = invocation.fresh("detail");
+                    MatchArm {
+                        pattern: Pattern::Variant {
+                            name: member.name.clone(),
+                            bindings: vec![detail.clone()],
+                        },
+                        body: Expr::Binary {
+                            op: BinaryOp::Add,
+                            lhs: Box::new(Expr::Literal(Literal::String(format!(
+                                "{}: ",
+                                member.name
+                            )))),
+                            rhs: Box::new(Expr::Local(detail)),
+                        },
+                    }
+                }
+                false => MatchArm {
+                    pattern: Pattern::Variant {
+                        name: member.name.clone(),
+                        bindings: vec![],
+                    },
+                    body: Expr::Literal(Literal::String(member.name.clone())),
+                },
+            }
+        })
+        .collect();
+
+    Function {
+        span: invocation.span,
+        name: "toString".into(),
+        receiver: Some(r#enum.name.clone()),
+        generics: vec![],
+        params: vec![Param {
+            name: "self".into(),
+            ty: Type::from_name(&r#enum.name, vec![]),
+        }],
+        ret: Type::String,
+        body: vec![invocation.stmt(StmtKind::Return(Some(Expr::Match {
+            scrutinee: Box::new(Expr::Local("self".into())),
+            arms,
+        })))],
+        exported: false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        lower::{lower_module, LowerError},
+        mir::Module,
+    };
+    use elp_parser::{
+        ast::{module::ASTModule, traits::FromCST},
+        cst::CSTModule,
+        parser::{ElpParser, Rule},
+    };
+    use from_pest::FromPest;
+    use pest::Parser;
+    use pretty_assertions::assert_eq;
+
+    fn lower(source: &str) -> Result<Module, LowerError> {
+        let mut pairs = ElpParser::parse(Rule::module, source).unwrap();
+        let cst = CSTModule::from_pest(&mut pairs).unwrap();
+        lower_module("test", &ASTModule::from_cst(&cst))
+    }
+
+    #[test]
+    fn error_enums_describe_their_cases() {
+        let module = lower(
+            "// Why logging in didn't work.
+@error
+export enum LoginError {
+	.NO_HANDLER,
+	.BAD_REQUEST(string),
+}
+",
+        )
+        .unwrap();
+
+        assert_eq!(
+            module.enums[0].implements,
+            vec![Type::from_name("Error", vec![])]
+        );
+        let to_string = &module.functions[0];
+        assert_eq!(to_string.qualified_name(), "LoginError.toString");
+        assert!(to_string.exported && to_string.is_method());
+        // Generated code is placed at the annotation.
+        assert_eq!((to_string.span.line, to_string.body[0].span.line), (2, 2));
+        let StmtKind::Return(Some(Expr::Match { arms, .. })) = &to_string.body[0].kind else {
+            panic!("expected a match, got {:?}", to_string.body[0]);
+        };
+        assert_eq!(
+            arms,
+            &vec![
+                MatchArm {
+                    pattern: Pattern::Variant {
+                        name: "NO_HANDLER".into(),
+                        bindings: vec![],
+                    },
+                    body: Expr::Literal(Literal::String("NO_HANDLER".into())),
+                },
+                MatchArm {
+                    pattern: Pattern::Variant {
+                        name: "BAD_REQUEST".into(),
+                        bindings: vec!["detail$1".into()],
+                    },
+                    body: Expr::Binary {
+                        op: BinaryOp::Add,
+                        lhs: Box::new(Expr::Literal(Literal::String("BAD_REQUEST: ".into()))),
+                        rhs: Box::new(Expr::Local("detail$1".into())),
+                    },
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn macros_share_what_they_both_define() {
+        let module = lower("@toString\n@error\nenum Shape {\n\t.CIRCLE,\n}\n").unwrap();
+
+        assert_eq!(module.functions.len(), 1);
+        assert_eq!(module.enums[0].implements.len(), 1);
+    }
+
+    #[test]
+    fn misused_macros() {
+        let message = |source| lower(source).unwrap_err().message;
+
+        assert_eq!(
+            message("@toString\nobject Point {\n\t.x int,\n}\n"),
+            "`@toString` can only be used on enums"
+        );
+        assert_eq!(
+            message("@App\nenum Shape {\n\t.CIRCLE,\n}\n"),
+            "there's no macro `@App`"
+        );
+        assert_eq!(
+            message(
+                "@toString\nenum Shape {\n\t.CIRCLE,\n}\n\nfn Shape.toString(self) -> string {\n\treturn \"shape\"\n}\n"
+            ),
+            "`@toString` defines `Shape.toString`, which is already defined"
+        );
+        assert_eq!(
+            message("enum Shape {\n\t.CIRCLE,\n}\n@toString\n"),
+            "`@toString` has to come before the item it's for"
+        );
+        assert!(lower(
+            "import { mergeable } from \"elp/macros\"\n\n@mergeable\nenum Shape {\n\t.CIRCLE,\n}\n"
+        )
+        .is_ok());
+    }
+}
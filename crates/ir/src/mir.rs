@@ -0,0 +1,669 @@
+// The MIR (mid-level IR) is the last tree every backend shares. Unlike the AST it owns all of its
+// data, it has no lifetimes tied back to the source and every name has been split into what it
+// actually is: a local, a global, a field or a method. Backends (bytecode, native, web) walk this
+// and never have to look at the CST or AST again.
+
+use std::fmt;
+
+use crate::{ffi::Abi, style::StyleSheet};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: u32,
+}
+
+impl Span {
+    pub fn from_pest(span: &pest::Span) -> Self {
+        Self {
+            start: span.start(),
+            end: span.end(),
+            line: span.start_pos().line_col().0 as u32,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Type {
+    // Not written down and not yet inferred.
+    Unknown,
+    Void,
+    Bool,
+    Int { bits: u8, signed: bool },
+    Float { bits: u8 },
+    String,
+    Array(Box<Type>),
+    Pointer(Box<Type>),
+    Reference(Box<Type>),
+    Named { name: String, generics: Vec<Type> },
+    Function { params: Vec<Type>, ret: Box<Type> },
+}
+
+impl Type {
+    pub fn from_name(name: &str, generics: Vec<Type>) -> Self {
+        match name {
+            "bool" => Type::Bool,
+            "int" | "int64" => Type::Int {
+                bits: 64,
+                signed: true,
+            },
+            "int8" => Type::Int {
+                bits: 8,
+                signed: true,
+            },
+            "int16" => Type::Int {
+                bits: 16,
+                signed: true,
+            },
+            "int32" => Type::Int {
+                bits: 32,
+                signed: true,
+            },
+            "uint" | "uint64" => Type::Int {
+                bits: 64,
+                signed: false,
+            },
+            "uint8" => Type::Int {
+                bits: 8,
+                signed: false,
+            },
+            "uint16" => Type::Int {
+                bits: 16,
+                signed: false,
+            },
+            "uint32" => Type::Int {
+                bits: 32,
+                signed: false,
+            },
+            "float" | "float64" => Type::Float { bits: 64 },
+            "float32" => Type::Float { bits: 32 },
+            "string" | "String" => Type::String,
+            "None" | "void" => Type::Void,
+            "Array" if generics.len() == 1 => {
+                Type::Array(Box::new(generics.into_iter().next().unwrap()))
+            }
+            _ => Type::Named {
+                name: name.into(),
+                generics,
+            },
+        }
+    }
+
+    // The object or enum name behind any pointer or reference, used when a backend needs to know
+    // which layout a value has.
+    pub fn named(&self) -> Option<&str> {
+        match self {
+            Type::Named { name, .. } => Some(name),
+            Type::Pointer(inner) | Type::Reference(inner) => inner.named(),
+            _ => None,
+        }
+    }
+}
+
+// Types are shown the way they're written in source, diagnostics quote them back to the user.
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Unknown => write!(f, "_"),
+            Type::Void => write!(f, "void"),
+            Type::Bool => write!(f, "bool"),
+            Type::Int { bits, signed } => {
+                write!(f, "{}int{}", if *signed { "" } else { "u" }, bits)
+            }
+            Type::Float { bits } => write!(f, "float{}", bits),
+            Type::String => write!(f, "string"),
+            Type::Array(inner) => write!(f, "[{}]", inner),
+            Type::Pointer(inner) => write!(f, "*{}", inner),
+            Type::Reference(inner) => write!(f, "&{}", inner),
+            Type::Named { name, generics } if generics.is_empty() => write!(f, "{}", name),
+            Type::Named { name, generics } => write!(f, "{}<{}>", name, list(generics)),
+            Type::Function { params, ret } => write!(f, "fn({}) -> {}", list(params), ret),
+        }
+    }
+}
+
+fn list(types: &[Type]) -> String {
+    types
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Literal {
+    Nil,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Pow,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    And,
+    Or,
+    BitAnd,
+    BitOr,
+    Shl,
+    Shr,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum UnaryOp {
+    Neg,
+    Not,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Param {
+    pub name: String,
+    pub ty: Type,
+}
+
+// A piece of UI, `Column(class = "row") { Text("hi") }`. Every statement in `children` that
+// evaluates to an element adds it as a child, so loops and ifs can build them up.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Element {
+    pub name: String,
+    pub args: Vec<Expr>,
+    // Named arguments, `class = "row"`.
+    pub props: Vec<(String, Expr)>,
+    pub children: Vec<Stmt>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Closure {
+    pub params: Vec<Param>,
+    pub ret: Type,
+    pub body: Vec<Stmt>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Pattern {
+    Wildcard,
+    Binding(String),
+    Literal(Literal),
+    // Either end may be open, `..` on its own matches anything.
+    Range {
+        start: Option<Literal>,
+        end: Option<Literal>,
+    },
+    Variant {
+        name: String,
+        bindings: Vec<String>,
+    },
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub body: Expr,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Expr {
+    Literal(Literal),
+    Local(String),
+    Global(String),
+    Field {
+        base: Box<Expr>,
+        name: String,
+    },
+    Call {
+        callee: Box<Expr>,
+        args: Vec<Expr>,
+    },
+    MethodCall {
+        receiver: Box<Expr>,
+        method: String,
+        args: Vec<Expr>,
+    },
+    Binary {
+        op: BinaryOp,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    Unary {
+        op: UnaryOp,
+        operand: Box<Expr>,
+    },
+    Object {
+        name: String,
+        fields: Vec<(String, Expr)>,
+    },
+    // `enum_name` is None for contextual accesses such as `.Directed` where the enum is only
+    // known from the surrounding types.
+    Variant {
+        enum_name: Option<String>,
+        variant: String,
+        args: Vec<Expr>,
+    },
+    Array(Vec<Expr>),
+    Closure(Box<Closure>),
+    Match {
+        scrutinee: Box<Expr>,
+        arms: Vec<MatchArm>,
+    },
+    Element(Box<Element>),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum StmtKind {
+    Let {
+        name: String,
+        mutable: bool,
+        ty: Type,
+        value: Option<Expr>,
+    },
+    Assign {
+        target: Expr,
+        // `a += 1` keeps the operator so backends can emit a compound store.
+        op: Option<BinaryOp>,
+        value: Expr,
+    },
+    Expr(Expr),
+    Return(Option<Expr>),
+    If {
+        condition: Expr,
+        then_body: Vec<Stmt>,
+        else_body: Vec<Stmt>,
+    },
+    For {
+        binding: String,
+        iterable: Expr,
+        body: Vec<Stmt>,
+    },
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Stmt {
+    pub span: Span,
+    pub kind: StmtKind,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Function {
+    pub span: Span,
+    pub name: String,
+    // The object a function is declared on, `Graph` for `fn Graph.new()`.
+    pub receiver: Option<String>,
+    pub generics: Vec<String>,
+    pub params: Vec<Param>,
+    pub ret: Type,
+    pub body: Vec<Stmt>,
+    pub exported: bool,
+}
+
+impl Function {
+    pub fn qualified_name(&self) -> String {
+        match &self.receiver {
+            Some(receiver) => format!("{}.{}", receiver, self.name),
+            None => self.name.clone(),
+        }
+    }
+
+    // Methods take their receiver as the first parameter called `self`, everything else declared
+    // on an object is a static function.
+    pub fn is_method(&self) -> bool {
+        self.receiver.is_some() && self.params.first().is_some_and(|p| p.name == "self")
+    }
+}
+
+// A function that is declared but whose body lives elsewhere, usually in C.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ExternalFunction {
+    pub span: Span,
+    pub name: String,
+    pub generics: Vec<String>,
+    pub params: Vec<Param>,
+    pub ret: Type,
+    pub abi: Abi,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct FieldDef {
+    pub span: Span,
+    pub name: String,
+    pub ty: Type,
+    pub default: Option<Expr>,
+    pub public: bool,
+    pub tags: Vec<Tag>,
+}
+
+impl FieldDef {
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|tag| tag.name == name)
+            .map(|tag| tag.value.as_str())
+    }
+}
+
+// `json: "title"` in the backticks after a field.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Tag {
+    pub span: Span,
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ObjectDef {
+    pub span: Span,
+    pub name: String,
+    pub generics: Vec<String>,
+    pub fields: Vec<FieldDef>,
+    pub implements: Vec<Type>,
+    pub exported: bool,
+}
+
+impl ObjectDef {
+    pub fn field_index(&self, name: &str) -> Option<usize> {
+        self.fields.iter().position(|field| field.name == name)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct VariantDef {
+    pub name: String,
+    pub payload: Vec<Type>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct EnumDef {
+    pub span: Span,
+    pub name: String,
+    pub variants: Vec<VariantDef>,
+    pub implements: Vec<Type>,
+    pub exported: bool,
+}
+
+impl EnumDef {
+    pub fn variant_index(&self, name: &str) -> Option<usize> {
+        self.variants
+            .iter()
+            .position(|variant| variant.name == name)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Global {
+    pub span: Span,
+    pub name: String,
+    pub mutable: bool,
+    pub ty: Type,
+    pub value: Expr,
+    pub exported: bool,
+}
+
+// `component Counter(label string) { .. }`, the `var`s declared directly in the body are its
+// state.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ComponentDef {
+    pub span: Span,
+    pub name: String,
+    pub params: Vec<Param>,
+    // The `export`ed declarations in the body, which are taken out of it.
+    pub props: Vec<Prop>,
+    pub body: Vec<Stmt>,
+    pub exported: bool,
+}
+
+// `export var email Optional<string>` in a component is set by whoever uses it, like a parameter
+// that can be left out if it has a default. `export *fn onSubmit(..)` is a callback.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Prop {
+    pub span: Span,
+    pub name: String,
+    pub ty: Type,
+    pub default: Option<Expr>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Import {
+    pub module_path: String,
+    pub names: Vec<(String, Option<String>)>,
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Module {
+    pub name: String,
+    pub imports: Vec<Import>,
+    pub objects: Vec<ObjectDef>,
+    pub enums: Vec<EnumDef>,
+    pub globals: Vec<Global>,
+    pub functions: Vec<Function>,
+    pub externals: Vec<ExternalFunction>,
+    pub components: Vec<ComponentDef>,
+    pub styles: StyleSheet,
+}
+
+impl Module {
+    pub fn object(&self, name: &str) -> Option<&ObjectDef> {
+        self.objects.iter().find(|object| object.name == name)
+    }
+
+    pub fn r#enum(&self, name: &str) -> Option<&EnumDef> {
+        self.enums.iter().find(|r#enum| r#enum.name == name)
+    }
+
+    // Contextual variants (`.Directed`) don't say which enum they belong to, if exactly one enum
+    // in the module has a variant with that name we can still pin it down.
+    pub fn enum_for_variant(&self, variant: &str) -> Option<&EnumDef> {
+        let mut candidates = self
+            .enums
+            .iter()
+            .filter(|r#enum| r#enum.variant_index(variant).is_some());
+        let found = candidates.next()?;
+
+        match candidates.next() {
+            Some(_) => None,
+            None => Some(found),
+        }
+    }
+
+    pub fn component(&self, name: &str) -> Option<&ComponentDef> {
+        self.components
+            .iter()
+            .find(|component| component.name == name)
+    }
+
+    pub fn function(&self, qualified_name: &str) -> Option<&Function> {
+        self.functions
+            .iter()
+            .find(|function| function.qualified_name() == qualified_name)
+    }
+
+    // Whether the module has a top level item called `name` and if it's exported.
+    pub fn exported(&self, name: &str) -> Option<bool> {
+        if let Some(object) = self.object(name) {
+            return Some(object.exported);
+        }
+        if let Some(r#enum) = self.r#enum(name) {
+            return Some(r#enum.exported);
+        }
+        if let Some(component) = self.component(name) {
+            return Some(component.exported);
+        }
+        if let Some(function) = self
+            .functions
+            .iter()
+            .find(|function| function.receiver.is_none() && function.name == name)
+        {
+            return Some(function.exported);
+        }
+        if let Some(global) = self.globals.iter().find(|global| global.name == name) {
+            return Some(global.exported);
+        }
+        self.externals
+            .iter()
+            .any(|external| external.name == name)
+            .then_some(true)
+    }
+
+    // Copies the items `names` refers to from `from` into this module under the name they're
+    // imported as. Objects and enums bring the functions declared on them along.
+    pub fn import_from(&mut self, from: &Module, names: &[(String, Option<String>)]) {
+        for (name, alias) in names {
+            let local = alias.as_deref().unwrap_or(name);
+
+            if let Some(object) = from.object(name).filter(|_| self.object(local).is_none()) {
+                let mut object = object.clone();
+                object.name = local.into();
+                self.objects.push(object);
+            }
+            if let Some(r#enum) = from.r#enum(name).filter(|_| self.r#enum(local).is_none()) {
+                let mut r#enum = r#enum.clone();
+                r#enum.name = local.into();
+                self.enums.push(r#enum);
+            }
+            if let Some(component) = from
+                .component(name)
+                .filter(|_| self.component(local).is_none())
+            {
+                let mut component = component.clone();
+                component.name = local.into();
+                self.components.push(component);
+            }
+            for function in &from.functions {
+                let mut function = function.clone();
+                match &function.receiver {
+                    Some(receiver) if receiver == name => function.receiver = Some(local.into()),
+                    None if function.name == *name => function.name = local.into(),
+                    _ => continue,
+                }
+                if self.function(&function.qualified_name()).is_none() {
+                    self.functions.push(function);
+                }
+            }
+            for global in &from.globals {
+                if global.name == *name && !self.globals.iter().any(|g| g.name == local) {
+                    let mut global = global.clone();
+                    global.name = local.into();
+                    self.globals.push(global);
+                }
+            }
+            for external in &from.externals {
+                if external.name == *name && !self.externals.iter().any(|e| e.name == local) {
+                    let mut external = external.clone();
+                    external.name = local.into();
+                    self.externals.push(external);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn types_from_names() {
+        assert_eq!(
+            Type::from_name("int32", vec![]),
+            Type::Int {
+                bits: 32,
+                signed: true
+            }
+        );
+        assert_eq!(
+            Type::from_name("uint", vec![]),
+            Type::Int {
+                bits: 64,
+                signed: false
+            }
+        );
+        assert_eq!(Type::from_name("string", vec![]), Type::String);
+        assert_eq!(
+            Type::from_name("Array", vec![Type::Bool]),
+            Type::Array(Box::new(Type::Bool))
+        );
+        assert_eq!(
+            Type::from_name("Graph", vec![Type::String]),
+            Type::Named {
+                name: "Graph".into(),
+                generics: vec![Type::String]
+            }
+        );
+    }
+
+    #[test]
+    fn types_display_as_written() {
+        assert_eq!(Type::from_name("uint8", vec![]).to_string(), "uint8");
+        assert_eq!(
+            Type::Reference(Box::new(Type::from_name(
+                "Graph",
+                vec![Type::String, Type::Array(Box::new(Type::Bool))]
+            )))
+            .to_string(),
+            "&Graph<string, [bool]>"
+        );
+        assert_eq!(
+            Type::Function {
+                params: vec![Type::Float { bits: 32 }],
+                ret: Box::new(Type::Void)
+            }
+            .to_string(),
+            "fn(float32) -> void"
+        );
+    }
+
+    #[test]
+    fn contextual_variant_lookup() {
+        let module = Module {
+            enums: vec![
+                EnumDef {
+                    span: Span::default(),
+                    name: "Direction".into(),
+                    variants: vec![
+                        VariantDef {
+                            name: "Directed".into(),
+                            payload: vec![],
+                        },
+                        VariantDef {
+                            name: "Both".into(),
+                            payload: vec![],
+                        },
+                    ],
+                    implements: vec![],
+                    exported: false,
+                },
+                EnumDef {
+                    span: Span::default(),
+                    name: "Side".into(),
+                    variants: vec![VariantDef {
+                        name: "Both".into(),
+                        payload: vec![],
+                    }],
+                    implements: vec![],
+                    exported: false,
+                },
+            ],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            module.enum_for_variant("Directed").map(|e| e.name.as_str()),
+            Some("Direction")
+        );
+        // Ambiguous, both enums have it.
+        assert_eq!(module.enum_for_variant("Both"), None);
+        assert_eq!(module.enum_for_variant("Nope"), None);
+    }
+}
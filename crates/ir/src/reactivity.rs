@@ -0,0 +1,469 @@
+// Which parts of a component's render depend on which state, and which handlers change that state.
+// A backend can use it to update only the parts of the view that read what a handler wrote rather
+// than rendering the whole component again. Locals worked out from state, `const label = "Hi " +
+// name`, count as reading whatever they were worked out from.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{
+    component::ComponentModel,
+    mir::{ComponentDef, Expr, Span, Stmt, StmtKind},
+};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Reactivity {
+    pub bindings: Vec<Binding>,
+    pub handlers: Vec<HandlerEffects>,
+    state: Vec<(Span, String)>,
+}
+
+// A piece of the render that reads props or state. `path` is the position of the statement that
+// makes it, the child index at each level down from the returned element, which is `[]`. Both
+// sides of an `if` are numbered one after the other.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Binding {
+    pub path: Vec<usize>,
+    pub kind: BindingKind,
+    pub reads: BTreeSet<String>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum BindingKind {
+    // The element's own arguments and props, its children are bindings of their own.
+    Element(String),
+    Condition,
+    Loop,
+    Expr,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct HandlerEffects {
+    pub name: String,
+    pub writes: BTreeSet<String>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ReactivityWarning {
+    pub span: Span,
+    pub message: String,
+}
+
+impl Reactivity {
+    pub fn of(component: &ComponentDef) -> Self {
+        let model = ComponentModel::of(component);
+        let sources: BTreeSet<String> = model
+            .props
+            .iter()
+            .chain(&model.callbacks)
+            .map(|prop| prop.name.to_string())
+            .chain(model.state.iter().map(|state| state.name.to_string()))
+            .collect();
+
+        // Everything read by a local declared in the body, in terms of props and state.
+        let mut derived: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+        let mut handlers: Vec<HandlerEffects> = vec![];
+        for stmt in &component.body {
+            match &stmt.kind {
+                StmtKind::Let {
+                    name,
+                    value: Some(Expr::Closure(closure)),
+                    ..
+                } => {
+                    let mut writes = BTreeSet::new();
+                    let mut calls = BTreeSet::new();
+                    effects(&closure.body, &mut writes, &mut calls);
+                    // Calling another handler changes what that handler changes.
+                    for called in calls {
+                        if let Some(other) = handlers.iter().find(|other| other.name == called) {
+                            writes.extend(other.writes.iter().cloned());
+                        }
+                    }
+                    handlers.push(HandlerEffects {
+                        name: name.clone(),
+                        writes: writes.intersection(&sources).cloned().collect(),
+                    });
+                }
+                StmtKind::Let {
+                    name,
+                    mutable: false,
+                    value: Some(value),
+                    ..
+                } => {
+                    let mut reads = BTreeSet::new();
+                    reads_in(value, &mut reads);
+                    let reads = resolve(&reads, &sources, &derived);
+                    derived.insert(name.clone(), reads);
+                }
+                _ => {}
+            }
+        }
+
+        let mut bindings = vec![];
+        if let Some(render) = model.render {
+            let mut walker = Walker {
+                sources: &sources,
+                derived: &derived,
+                bindings: &mut bindings,
+            };
+            walker.expr(render, vec![]);
+        }
+
+        Reactivity {
+            bindings,
+            handlers,
+            state: model
+                .state
+                .iter()
+                .map(|state| (state.span, state.name.to_string()))
+                .collect(),
+        }
+    }
+
+    // The bindings that have to update when `name` changes.
+    pub fn readers<'r>(&'r self, name: &'r str) -> impl Iterator<Item = &'r Binding> {
+        self.bindings
+            .iter()
+            .filter(move |binding| binding.reads.contains(name))
+    }
+
+    // The bindings running `handler` can change.
+    pub fn updates(&self, handler: &str) -> Vec<&Binding> {
+        let Some(effects) = self.handlers.iter().find(|effects| effects.name == handler) else {
+            return vec![];
+        };
+        self.bindings
+            .iter()
+            .filter(|binding| !binding.reads.is_disjoint(&effects.writes))
+            .collect()
+    }
+
+    // State that's changed by a handler but that nothing rendered reads, changing it never
+    // updates anything on screen.
+    pub fn warnings(&self) -> Vec<ReactivityWarning> {
+        self.state
+            .iter()
+            .filter(|(_, name)| {
+                self.handlers
+                    .iter()
+                    .any(|effects| effects.writes.contains(name))
+                    && self.readers(name).next().is_none()
+            })
+            .map(|(span, name)| ReactivityWarning {
+                span: *span,
+                message: format!(
+                    "`{}` is changed but never rendered, it doesn't need to be a `var`",
+                    name
+                ),
+            })
+            .collect()
+    }
+}
+
+struct Walker<'w> {
+    sources: &'w BTreeSet<String>,
+    derived: &'w BTreeMap<String, BTreeSet<String>>,
+    bindings: &'w mut Vec<Binding>,
+}
+
+impl Walker<'_> {
+    fn bind(&mut self, path: Vec<usize>, kind: BindingKind, exprs: &[&Expr]) {
+        let mut reads = BTreeSet::new();
+        for expr in exprs {
+            reads_in(expr, &mut reads);
+        }
+        let reads = resolve(&reads, self.sources, self.derived);
+        if !reads.is_empty() {
+            self.bindings.push(Binding { path, kind, reads });
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr, path: Vec<usize>) {
+        match expr {
+            Expr::Element(element) => {
+                let exprs: Vec<&Expr> = element
+                    .args
+                    .iter()
+                    .chain(element.props.iter().map(|(_, value)| value))
+                    .collect();
+                self.bind(
+                    path.clone(),
+                    BindingKind::Element(element.name.clone()),
+                    &exprs,
+                );
+                self.body(&element.children, path);
+            }
+            expr => self.bind(path, BindingKind::Expr, &[expr]),
+        }
+    }
+
+    fn body(&mut self, body: &[Stmt], path: Vec<usize>) {
+        for (index, stmt) in body.iter().enumerate() {
+            let path = [path.as_slice(), &[index]].concat();
+            match &stmt.kind {
+                StmtKind::Expr(expr) | StmtKind::Return(Some(expr)) => self.expr(expr, path),
+                StmtKind::If {
+                    condition,
+                    then_body,
+                    else_body,
+                } => {
+                    self.bind(path.clone(), BindingKind::Condition, &[condition]);
+                    let both: Vec<Stmt> = then_body.iter().chain(else_body).cloned().collect();
+                    self.body(&both, path);
+                }
+                StmtKind::For { iterable, body, .. } => {
+                    self.bind(path.clone(), BindingKind::Loop, &[iterable]);
+                    self.body(body, path);
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+// Props and state `names` come down to, following locals back to what they were worked out from.
+fn resolve(
+    names: &BTreeSet<String>,
+    sources: &BTreeSet<String>,
+    derived: &BTreeMap<String, BTreeSet<String>>,
+) -> BTreeSet<String> {
+    let mut resolved = BTreeSet::new();
+    for name in names {
+        if let Some(reads) = derived.get(name) {
+            resolved.extend(reads.iter().cloned());
+        } else if sources.contains(name) {
+            resolved.insert(name.clone());
+        }
+    }
+    resolved
+}
+
+fn reads_in(expr: &Expr, reads: &mut BTreeSet<String>) {
+    match expr {
+        Expr::Local(name) => {
+            reads.insert(name.clone());
+        }
+        Expr::Literal(_) | Expr::Global(_) => {}
+        Expr::Field { base, .. } => reads_in(base, reads),
+        Expr::Call { callee, args } => {
+            reads_in(callee, reads);
+            args.iter().for_each(|arg| reads_in(arg, reads));
+        }
+        Expr::MethodCall { receiver, args, .. } => {
+            reads_in(receiver, reads);
+            args.iter().for_each(|arg| reads_in(arg, reads));
+        }
+        Expr::Binary { lhs, rhs, .. } => {
+            reads_in(lhs, reads);
+            reads_in(rhs, reads);
+        }
+        Expr::Unary { operand, .. } => reads_in(operand, reads),
+        Expr::Object { fields, .. } => fields.iter().for_each(|(_, value)| reads_in(value, reads)),
+        Expr::Variant { args, .. } | Expr::Array(args) => {
+            args.iter().for_each(|arg| reads_in(arg, reads))
+        }
+        // Handlers passed as props run later, what they read isn't read by the render.
+        Expr::Closure(_) => {}
+        Expr::Match { scrutinee, arms } => {
+            reads_in(scrutinee, reads);
+            arms.iter().for_each(|arm| reads_in(&arm.body, reads));
+        }
+        Expr::Element(element) => {
+            element.args.iter().for_each(|arg| reads_in(arg, reads));
+            element
+                .props
+                .iter()
+                .for_each(|(_, value)| reads_in(value, reads));
+            stmts_read(&element.children, reads);
+        }
+    }
+}
+
+fn stmts_read(body: &[Stmt], reads: &mut BTreeSet<String>) {
+    for stmt in body {
+        match &stmt.kind {
+            StmtKind::Let { value, .. } => value.iter().for_each(|value| reads_in(value, reads)),
+            StmtKind::Assign { value, .. } => reads_in(value, reads),
+            StmtKind::Expr(expr) | StmtKind::Return(Some(expr)) => reads_in(expr, reads),
+            StmtKind::Return(None) => {}
+            StmtKind::If {
+                condition,
+                then_body,
+                else_body,
+            } => {
+                reads_in(condition, reads);
+                stmts_read(then_body, reads);
+                stmts_read(else_body, reads);
+            }
+            StmtKind::For { iterable, body, .. } => {
+                reads_in(iterable, reads);
+                stmts_read(body, reads);
+            }
+        }
+    }
+}
+
+// The locals a handler assigns to, `count += 1` or `form.email = ""`, and the ones it calls.
+fn effects(body: &[Stmt], writes: &mut BTreeSet<String>, calls: &mut BTreeSet<String>) {
+    for stmt in body {
+        let exprs: Vec<&Expr> = match &stmt.kind {
+            StmtKind::Assign { target, value, .. } => {
+                if let Some(name) = assigned(target) {
+                    writes.insert(name.into());
+                }
+                vec![value]
+            }
+            StmtKind::Let { value, .. } => value.iter().collect(),
+            StmtKind::Expr(expr) | StmtKind::Return(Some(expr)) => vec![expr],
+            StmtKind::Return(None) => vec![],
+            StmtKind::If {
+                condition,
+                then_body,
+                else_body,
+            } => {
+                effects(then_body, writes, calls);
+                effects(else_body, writes, calls);
+                vec![condition]
+            }
+            StmtKind::For { iterable, body, .. } => {
+                effects(body, writes, calls);
+                vec![iterable]
+            }
+        };
+        for expr in exprs {
+            called(expr, calls);
+        }
+    }
+}
+
+fn assigned(target: &Expr) -> Option<&str> {
+    match target {
+        Expr::Local(name) => Some(name),
+        Expr::Field { base, .. } => assigned(base),
+        _ => None,
+    }
+}
+
+fn called(expr: &Expr, calls: &mut BTreeSet<String>) {
+    if let Expr::Call { callee, args } = expr {
+        if let Expr::Local(name) = &**callee {
+            calls.insert(name.clone());
+        }
+        args.iter().for_each(|arg| called(arg, calls));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lower::lower_module;
+    use elp_parser::{
+        ast::{module::ASTModule, traits::FromCST},
+        cst::CSTModule,
+        parser::{ElpParser, Rule},
+    };
+    use from_pest::FromPest;
+    use pest::Parser;
+    use pretty_assertions::assert_eq;
+
+    fn reactivity(source: &str) -> Reactivity {
+        let mut pairs = ElpParser::parse(Rule::module, source).unwrap();
+        let cst = CSTModule::from_pest(&mut pairs).unwrap();
+        let module = lower_module("test", &ASTModule::from_cst(&cst)).unwrap();
+        Reactivity::of(&module.components[0])
+    }
+
+    fn names(set: &BTreeSet<String>) -> Vec<&str> {
+        set.iter().map(String::as_str).collect()
+    }
+
+    #[test]
+    fn handlers_update_the_bindings_that_read_what_they_write() {
+        let reactivity = reactivity(
+            "component Counter(title string) {
+                var count = 0
+                var step = 1
+                const doubled = double(count)
+
+                fn increment() {
+                    count += step
+                }
+
+                fn reset() {
+                    increment()
+                    step = 1
+                }
+
+                return Column(class = title) {
+                    Text(doubled)
+                    Text(\"Step\")
+                    Button(onClick = increment, disabled = step) {}
+                }
+            }",
+        );
+
+        assert_eq!(
+            reactivity
+                .bindings
+                .iter()
+                .map(|binding| (binding.path.clone(), names(&binding.reads)))
+                .collect::<Vec<_>>(),
+            vec![
+                (vec![], vec!["title"]),
+                (vec![0], vec!["count"]),
+                (vec![2], vec!["step"]),
+            ]
+        );
+        assert_eq!(
+            reactivity
+                .handlers
+                .iter()
+                .map(|effects| (effects.name.as_str(), names(&effects.writes)))
+                .collect::<Vec<_>>(),
+            vec![
+                ("increment", vec!["count"]),
+                ("reset", vec!["count", "step"])
+            ]
+        );
+        assert_eq!(
+            reactivity
+                .updates("increment")
+                .iter()
+                .map(|binding| binding.path.clone())
+                .collect::<Vec<_>>(),
+            vec![vec![0]]
+        );
+        assert_eq!(reactivity.readers("title").count(), 1);
+        assert_eq!(reactivity.warnings(), vec![]);
+    }
+
+    #[test]
+    fn state_that_is_changed_but_never_rendered() {
+        let reactivity = reactivity(
+            "component LoginForm() {
+                var attempts = 0
+                var email = \"\"
+
+                fn handleSubmit() {
+                    attempts += 1
+                    email = \"\"
+                }
+
+                return Form(onSubmit = handleSubmit) {
+                    Input(value = email) {}
+                }
+            }",
+        );
+
+        let warnings = reactivity.warnings();
+        assert_eq!(
+            warnings
+                .iter()
+                .map(|warning| (warning.span.line, warning.message.as_str()))
+                .collect::<Vec<_>>(),
+            vec![(
+                2,
+                "`attempts` is changed but never rendered, it doesn't need to be a `var`"
+            )]
+        );
+    }
+}
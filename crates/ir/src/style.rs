@@ -0,0 +1,424 @@
+// Styles, from `Stylesheet { .. }` blocks and `.selp` files. Values are typed by how they're
+// written, `16px` is a length and `#333` a colour, and `check` holds them up against what each
+// property takes. Properties that aren't listed in `PROPERTIES` are reported rather than passed on
+// to a backend that would quietly ignore them.
+
+use std::fmt;
+
+use crate::mir::Span;
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct StyleSheet {
+    pub rules: Vec<StyleRule>,
+}
+
+// `Text, Date.muted { .padding = 16 }`, the rule applies to anything any of its selectors match.
+#[derive(Debug, PartialEq, Clone)]
+pub struct StyleRule {
+    pub span: Span,
+    pub selectors: Vec<Selector>,
+    pub properties: Vec<StyleProperty>,
+}
+
+// `Text` picks out components by type, `.muted` by class and `Text.muted` needs both.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Selector {
+    pub component: Option<String>,
+    pub classes: Vec<String>,
+}
+
+impl fmt::Display for Selector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.component.as_deref().unwrap_or_default())?;
+        for class in &self.classes {
+            write!(f, ".{}", class)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct StyleProperty {
+    pub span: Span,
+    pub name: String,
+    pub value: StyleValue,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum StyleValue {
+    // Without a unit, which is pixels when the property takes a length.
+    Number(f64),
+    Length(f64, Unit),
+    Colour(Colour),
+    String(String),
+    // `.SEMIBOLD` leaves the enum to the property, `FontWeight.SEMIBOLD` names it.
+    Enum { ty: Option<String>, case: String },
+    Bool(bool),
+}
+
+// Values are shown the way they're written in a stylesheet.
+impl fmt::Display for StyleValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StyleValue::Number(number) => write!(f, "{}", number),
+            StyleValue::Length(number, unit) => write!(f, "{}{}", number, unit),
+            StyleValue::Colour(colour) => write!(f, "{}", colour),
+            StyleValue::String(text) => write!(f, "{:?}", text),
+            StyleValue::Enum { ty, case } => {
+                write!(f, "{}.{}", ty.as_deref().unwrap_or_default(), case)
+            }
+            StyleValue::Bool(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Unit {
+    Px,
+    Pt,
+    Em,
+    Rem,
+    Vw,
+    Vh,
+    Percent,
+}
+
+impl Unit {
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "px" => Unit::Px,
+            "pt" => Unit::Pt,
+            "em" => Unit::Em,
+            "rem" => Unit::Rem,
+            "vw" => Unit::Vw,
+            "vh" => Unit::Vh,
+            "%" => Unit::Percent,
+            _ => return None,
+        })
+    }
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Unit::Px => "px",
+            Unit::Pt => "pt",
+            Unit::Em => "em",
+            Unit::Rem => "rem",
+            Unit::Vw => "vw",
+            Unit::Vh => "vh",
+            Unit::Percent => "%",
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Colour {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+    pub alpha: u8,
+}
+
+impl Colour {
+    // The digits of `#rgb`, `#rgba`, `#rrggbb` or `#rrggbbaa`.
+    pub fn from_hex(digits: &str) -> Option<Self> {
+        if !digits.chars().all(|digit| digit.is_ascii_hexdigit()) {
+            return None;
+        }
+        let channels: Vec<u8> = match digits.len() {
+            3 | 4 => digits
+                .chars()
+                .map(|digit| u8::from_str_radix(&format!("{}{}", digit, digit), 16))
+                .collect::<Result<_, _>>()
+                .ok()?,
+            6 | 8 => (0..digits.len())
+                .step_by(2)
+                .map(|start| u8::from_str_radix(&digits[start..start + 2], 16))
+                .collect::<Result<_, _>>()
+                .ok()?,
+            _ => return None,
+        };
+        Some(Colour {
+            red: channels[0],
+            green: channels[1],
+            blue: channels[2],
+            alpha: channels.get(3).copied().unwrap_or(u8::MAX),
+        })
+    }
+}
+
+impl fmt::Display for Colour {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.red, self.green, self.blue)?;
+        if self.alpha != u8::MAX {
+            write!(f, "{:02x}", self.alpha)?;
+        }
+        Ok(())
+    }
+}
+
+// What a property can be set to.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ValueType {
+    Length,
+    Number,
+    Colour,
+    Font,
+    Bool,
+    Enum(&'static str, &'static [&'static str]),
+}
+
+impl fmt::Display for ValueType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValueType::Length => f.write_str("a length"),
+            ValueType::Number => f.write_str("a number"),
+            ValueType::Colour => f.write_str("a colour"),
+            ValueType::Font => f.write_str("a font name"),
+            ValueType::Bool => f.write_str("true or false"),
+            ValueType::Enum(name, _) => write!(f, "a {}", name),
+        }
+    }
+}
+
+const FONT_WEIGHT: ValueType = ValueType::Enum(
+    "FontWeight",
+    &[
+        "THIN",
+        "EXTRA_LIGHT",
+        "LIGHT",
+        "REGULAR",
+        "NORMAL",
+        "MEDIUM",
+        "SEMIBOLD",
+        "BOLD",
+        "EXTRA_BOLD",
+        "BLACK",
+    ],
+);
+const TEXT_ALIGN: ValueType = ValueType::Enum("TextAlign", &["LEFT", "CENTER", "RIGHT", "JUSTIFY"]);
+
+pub const PROPERTIES: &[(&str, ValueType)] = &[
+    ("backgroundColor", ValueType::Colour),
+    ("borderColor", ValueType::Colour),
+    ("borderRadius", ValueType::Length),
+    ("borderWidth", ValueType::Length),
+    ("color", ValueType::Colour),
+    ("flexGrow", ValueType::Number),
+    ("flexShrink", ValueType::Number),
+    ("fontFamily", ValueType::Font),
+    ("fontSize", ValueType::Length),
+    ("fontWeight", FONT_WEIGHT),
+    ("gap", ValueType::Length),
+    ("height", ValueType::Length),
+    ("ignoreSafeAreas", ValueType::Bool),
+    ("letterSpacing", ValueType::Length),
+    ("lineHeight", ValueType::Number),
+    ("margin", ValueType::Length),
+    ("marginBottom", ValueType::Length),
+    ("marginLeft", ValueType::Length),
+    ("marginRight", ValueType::Length),
+    ("marginTop", ValueType::Length),
+    ("maxHeight", ValueType::Length),
+    ("maxWidth", ValueType::Length),
+    ("minHeight", ValueType::Length),
+    ("minWidth", ValueType::Length),
+    ("opacity", ValueType::Number),
+    ("padding", ValueType::Length),
+    ("paddingBottom", ValueType::Length),
+    ("paddingLeft", ValueType::Length),
+    ("paddingRight", ValueType::Length),
+    ("paddingTop", ValueType::Length),
+    ("textAlign", TEXT_ALIGN),
+    ("width", ValueType::Length),
+];
+
+pub fn property_type(name: &str) -> Option<ValueType> {
+    PROPERTIES
+        .iter()
+        .find(|(property, _)| *property == name)
+        .map(|(_, ty)| *ty)
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct StyleDiagnostic {
+    pub span: Span,
+    pub message: String,
+}
+
+impl StyleSheet {
+    // Every property that isn't known or is set to something it can't take.
+    pub fn check(&self) -> Vec<StyleDiagnostic> {
+        let mut diagnostics = vec![];
+        for property in self.rules.iter().flat_map(|rule| &rule.properties) {
+            let message = match property_type(&property.name) {
+                None => Some(format!("there's no style property `{}`", property.name)),
+                Some(ty) => mismatch(&property.name, ty, &property.value),
+            };
+            if let Some(message) = message {
+                diagnostics.push(StyleDiagnostic {
+                    span: property.span,
+                    message,
+                });
+            }
+        }
+        diagnostics
+    }
+}
+
+fn mismatch(property: &str, ty: ValueType, value: &StyleValue) -> Option<String> {
+    let fits = match (ty, value) {
+        (ValueType::Length, StyleValue::Number(_) | StyleValue::Length(..)) => true,
+        (ValueType::Number, StyleValue::Number(_)) => true,
+        (ValueType::Colour, StyleValue::Colour(_)) => true,
+        (ValueType::Font, StyleValue::String(_)) => true,
+        (ValueType::Bool, StyleValue::Bool(_)) => true,
+        (ValueType::Enum(name, cases), StyleValue::Enum { ty, case })
+            if ty.as_deref().is_none_or(|ty| ty == name) =>
+        {
+            if !cases.contains(&case.as_str()) {
+                return Some(format!("{} has no case `.{}`", name, case));
+            }
+            true
+        }
+        _ => false,
+    };
+    match fits {
+        true => None,
+        false => Some(format!(
+            "`{}` takes {}, not {}",
+            property,
+            ty,
+            describe(value)
+        )),
+    }
+}
+
+fn describe(value: &StyleValue) -> String {
+    match value {
+        StyleValue::Number(_) => "a number".into(),
+        StyleValue::Length(..) => "a length".into(),
+        StyleValue::Colour(_) => "a colour".into(),
+        StyleValue::String(_) => "a string".into(),
+        StyleValue::Enum { ty: Some(ty), .. } => format!("a {}", ty),
+        StyleValue::Enum { ty: None, case } => format!("`.{}`", case),
+        StyleValue::Bool(_) => "true or false".into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn sheet(properties: Vec<(&str, StyleValue)>) -> StyleSheet {
+        StyleSheet {
+            rules: vec![StyleRule {
+                span: Span::default(),
+                selectors: vec![Selector {
+                    component: Some("Text".into()),
+                    classes: vec![],
+                }],
+                properties: properties
+                    .into_iter()
+                    .map(|(name, value)| StyleProperty {
+                        span: Span::default(),
+                        name: name.into(),
+                        value,
+                    })
+                    .collect(),
+            }],
+        }
+    }
+
+    fn messages(sheet: &StyleSheet) -> Vec<String> {
+        sheet
+            .check()
+            .into_iter()
+            .map(|diagnostic| diagnostic.message)
+            .collect()
+    }
+
+    #[test]
+    fn colours_from_hex() {
+        assert_eq!(
+            Colour::from_hex("f80"),
+            Some(Colour {
+                red: 0xff,
+                green: 0x88,
+                blue: 0x00,
+                alpha: 0xff,
+            })
+        );
+        assert_eq!(
+            Colour::from_hex("ff880080").unwrap().to_string(),
+            "#ff880080"
+        );
+        assert_eq!(Colour::from_hex("12345"), None);
+    }
+
+    #[test]
+    fn values_that_fit() {
+        let sheet = sheet(vec![
+            ("padding", StyleValue::Number(16.0)),
+            ("margin", StyleValue::Length(1.5, Unit::Rem)),
+            (
+                "color",
+                StyleValue::Colour(Colour::from_hex("333").unwrap()),
+            ),
+            ("fontFamily", StyleValue::String("Patua One".into())),
+            (
+                "fontWeight",
+                StyleValue::Enum {
+                    ty: None,
+                    case: "SEMIBOLD".into(),
+                },
+            ),
+            (
+                "fontWeight",
+                StyleValue::Enum {
+                    ty: Some("FontWeight".into()),
+                    case: "BOLD".into(),
+                },
+            ),
+            ("ignoreSafeAreas", StyleValue::Bool(true)),
+        ]);
+
+        assert_eq!(messages(&sheet), Vec::<String>::new());
+    }
+
+    #[test]
+    fn unknown_properties_and_bad_values() {
+        let sheet = sheet(vec![
+            ("paddng", StyleValue::Number(16.0)),
+            ("opacity", StyleValue::Length(5.0, Unit::Px)),
+            ("color", StyleValue::String("red".into())),
+            (
+                "fontWeight",
+                StyleValue::Enum {
+                    ty: None,
+                    case: "HEAVY".into(),
+                },
+            ),
+            (
+                "textAlign",
+                StyleValue::Enum {
+                    ty: Some("FontWeight".into()),
+                    case: "BOLD".into(),
+                },
+            ),
+        ]);
+
+        assert_eq!(
+            messages(&sheet),
+            vec![
+                "there's no style property `paddng`",
+                "`opacity` takes a number, not a length",
+                "`color` takes a colour, not a string",
+                "FontWeight has no case `.HEAVY`",
+                "`textAlign` takes a TextAlign, not a FontWeight",
+            ]
+        );
+    }
+}
@@ -0,0 +1,285 @@
+// The tag keys something understands. A tag nothing reads is usually a typo, so keys that aren't
+// declared here are warned about, with the declared key they're closest to. Each key comes with
+// the grammar of its values, a function that explains what's wrong with one that doesn't fit.
+// Tools reading their own tags add them with `Tags::with`, macros declare theirs with
+// `Macros::with_tag` and `Tags::with_macros` takes them from the registry.
+
+use crate::{
+    json,
+    macros::Macros,
+    mir::{Module, Span},
+};
+
+pub type TagGrammar = fn(&str) -> Result<(), String>;
+
+#[derive(Clone, Default)]
+pub struct Tags {
+    keys: Vec<(String, TagGrammar)>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TagWarning {
+    pub span: Span,
+    pub message: String,
+}
+
+impl Tags {
+    // The tags the compiler and its standard library read.
+    pub fn builtin() -> Self {
+        Tags::default()
+            .with("json", json::tag_grammar)
+            .with("db", column)
+            .with("validate", rules)
+            .with("a11y", label)
+    }
+
+    pub fn with(mut self, name: impl Into<String>, grammar: TagGrammar) -> Self {
+        self.keys.push((name.into(), grammar));
+        self
+    }
+
+    pub fn with_macros(self, macros: &Macros) -> Self {
+        macros
+            .tags()
+            .fold(self, |tags, (key, grammar)| tags.with(key, grammar))
+    }
+
+    pub fn get(&self, name: &str) -> Option<TagGrammar> {
+        self.keys
+            .iter()
+            .rev()
+            .find(|(key, _)| key == name)
+            .map(|(_, grammar)| *grammar)
+    }
+
+    pub fn check(&self, module: &Module) -> Vec<TagWarning> {
+        let mut warnings = vec![];
+        let tags = module
+            .objects
+            .iter()
+            .flat_map(|object| &object.fields)
+            .flat_map(|field| &field.tags);
+        for tag in tags {
+            let message = match self.get(&tag.name) {
+                Some(grammar) => match grammar(&tag.value) {
+                    Ok(()) => continue,
+                    Err(message) => message,
+                },
+                None => {
+                    let keys = self.keys.iter().map(|(key, _)| key.as_str());
+                    match suggest(&tag.name, keys) {
+                        Some(key) => format!(
+                            "nothing reads a `{}` tag, did you mean `{}`?",
+                            tag.name, key
+                        ),
+                        None => format!("nothing reads a `{}` tag", tag.name),
+                    }
+                }
+            };
+            warnings.push(TagWarning {
+                span: tag.span,
+                message,
+            });
+        }
+        warnings
+    }
+}
+
+// The candidate `name` was most likely meant to be, if any is close enough to be a typo.
+pub fn suggest<'c>(name: &str, candidates: impl IntoIterator<Item = &'c str>) -> Option<&'c str> {
+    let allowed = name.chars().count().max(3) / 3;
+    candidates
+        .into_iter()
+        .map(|candidate| (distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= allowed)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+// Edits to turn `a` into `b`, swapping two neighbouring characters counts as one.
+fn distance(a: &str, b: &str) -> usize {
+    let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    let mut rows = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in rows[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut best = (rows[i - 1][j] + 1)
+                .min(rows[i][j - 1] + 1)
+                .min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                best = best.min(rows[i - 2][j - 2] + 1);
+            }
+            rows[i][j] = best;
+        }
+    }
+    rows[a.len()][b.len()]
+}
+
+// `db: "post_id"`, the column a field is stored in.
+fn column(value: &str) -> Result<(), String> {
+    match !value.is_empty() && value.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        true => Ok(()),
+        false => Err(format!(
+            "`{}` isn't a column name, they're letters, digits and `_`",
+            value
+        )),
+    }
+}
+
+const RULES: [&str; 7] = ["required", "email", "url", "min", "max", "len", "oneof"];
+// The rules that are given a value, `min=3`.
+const RULES_WITH_VALUES: [&str; 4] = ["min", "max", "len", "oneof"];
+
+// `validate: "required,min=3"`, rules the field's value has to follow.
+fn rules(value: &str) -> Result<(), String> {
+    for rule in value.split(',').map(str::trim) {
+        let (name, argument) = match rule.split_once('=') {
+            Some((name, argument)) => (name.trim(), Some(argument.trim())),
+            None => (rule, None),
+        };
+        if !RULES.contains(&name) {
+            return Err(match suggest(name, RULES) {
+                Some(rule) => format!(
+                    "`{}` isn't a validation rule, did you mean `{}`?",
+                    name, rule
+                ),
+                None => format!(
+                    "`{}` isn't a validation rule, they're {}",
+                    name,
+                    RULES.join(", ")
+                ),
+            });
+        }
+        match (RULES_WITH_VALUES.contains(&name), argument) {
+            (true, None | Some("")) => {
+                return Err(format!("`{}` needs a value, `{}=..`", name, name))
+            }
+            (false, Some(_)) => return Err(format!("`{}` doesn't take a value", name)),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+// `a11y: "Publish"`, what assistive technology announces the field as.
+fn label(value: &str) -> Result<(), String> {
+    match value.trim().is_empty() {
+        true => Err("an `a11y` tag needs a label".into()),
+        false => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lower::lower_module;
+    use elp_parser::{
+        ast::{module::ASTModule, traits::FromCST},
+        cst::CSTModule,
+        parser::{ElpParser, Rule},
+    };
+    use from_pest::FromPest;
+    use pest::Parser;
+    use pretty_assertions::assert_eq;
+
+    fn warnings(source: &str, tags: &Tags) -> Vec<(u32, String)> {
+        let mut pairs = ElpParser::parse(Rule::module, source).unwrap();
+        let cst = CSTModule::from_pest(&mut pairs).unwrap();
+        let module = lower_module("test", &ASTModule::from_cst(&cst)).unwrap();
+        tags.check(&module)
+            .into_iter()
+            .map(|warning| (warning.span.line, warning.message))
+            .collect()
+    }
+
+    #[test]
+    fn unknown_keys_and_values_are_warned_about() {
+        let source = "object Post {
+	.title string `json: \"title\", db: \"title\", validate: \"required,min=3\", a11y: \"Title\"`,
+	.body string `jsno: \"body\", validate: \"requird\"`,
+	.slug string `db: \"post slug\", route: \"slug\"`,
+}
+";
+
+        assert_eq!(
+            warnings(source, &Tags::builtin()),
+            vec![
+                (3, "nothing reads a `jsno` tag, did you mean `json`?".into()),
+                (
+                    3,
+                    "`requird` isn't a validation rule, did you mean `required`?".into()
+                ),
+                (
+                    4,
+                    "`post slug` isn't a column name, they're letters, digits and `_`".into()
+                ),
+                (4, "nothing reads a `route` tag".into()),
+            ]
+        );
+        // A tool reading its own tags declares them.
+        let tags = Tags::builtin().with("route", |_| Ok(()));
+        assert_eq!(warnings(source, &tags).len(), 3);
+    }
+
+    #[test]
+    fn macros_declare_the_tags_they_read() {
+        let source = "object Page {
+	.slug string `route: \"slug\"`,
+	.title string `route: \"\"`,
+}
+";
+        let macros = Macros::builtin().with_tag("route", |value| match value.is_empty() {
+            true => Err("a route segment can't be empty".into()),
+            false => Ok(()),
+        });
+
+        assert_eq!(
+            warnings(source, &Tags::builtin()),
+            vec![
+                (2, "nothing reads a `route` tag".into()),
+                (3, "nothing reads a `route` tag".into()),
+            ]
+        );
+        assert_eq!(
+            warnings(source, &Tags::builtin().with_macros(&macros)),
+            vec![(3, "a route segment can't be empty".into())]
+        );
+    }
+
+    #[test]
+    fn validation_rules() {
+        assert_eq!(rules("required, email, len=8"), Ok(()));
+        assert_eq!(rules("min"), Err("`min` needs a value, `min=..`".into()));
+        assert_eq!(
+            rules("email=yes"),
+            Err("`email` doesn't take a value".into())
+        );
+        assert_eq!(
+            rules("unique"),
+            Err(
+                "`unique` isn't a validation rule, they're required, email, url, min, max, len, oneof"
+                    .into()
+            )
+        );
+    }
+
+    #[test]
+    fn keys_can_only_be_given_once() {
+        let mut pairs = ElpParser::parse(
+            Rule::module,
+            "object Post {\n\t.title string `json: \"title\", json: \"name\"`,\n}\n",
+        )
+        .unwrap();
+        let cst = CSTModule::from_pest(&mut pairs).unwrap();
+        let err = lower_module("test", &ASTModule::from_cst(&cst)).unwrap_err();
+
+        assert_eq!(err.message, "`.title` has more than one `json` tag");
+        assert_eq!(err.span.line, 2);
+    }
+}
@@ -0,0 +1,867 @@
+// Answers to the questions editors ask, worked out from the workspace as it is right now. Every
+// file that's needed is indexed once per `Analysis` and kept for as long as it lives, the server
+// makes a new one whenever a document changes. Parsing, lowering, checking and everything else the
+// compiler does comes from the workspace's database, which keeps it between changes.
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    ops::Range,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use elp_codegen::typing::TypeContext;
+use elp_db::{database::Database, problem::Problem};
+use elp_ir::mir::{Span, Stmt, StmtKind, Type};
+
+use crate::{
+    index::{Found, Index, ReferenceKind, Symbol, SymbolKind},
+    line_index::LineIndex,
+    workspace::Workspace,
+};
+
+const KEYWORDS: [&str; 19] = [
+    "component",
+    "const",
+    "else",
+    "elseif",
+    "enum",
+    "export",
+    "external",
+    "fn",
+    "for",
+    "if",
+    "implements",
+    "import",
+    "in",
+    "interface",
+    "match",
+    "object",
+    "return",
+    "then",
+    "var",
+];
+
+pub struct File {
+    pub path: PathBuf,
+    pub source: String,
+    pub lines: LineIndex,
+    pub index: Result<Index, Problem>,
+}
+
+impl File {
+    fn load(db: &Database, path: &Path, source: String) -> Self {
+        let index = match db.parse(path).as_ref() {
+            Ok(tree) => Ok(Index::new(tree)),
+            Err(problem) => Err(problem.clone()),
+        };
+        Self {
+            path: path.to_path_buf(),
+            lines: LineIndex::new(&source),
+            source,
+            index,
+        }
+    }
+
+    pub fn symbol(&self, symbol: usize) -> Option<&Symbol> {
+        self.index.as_ref().ok()?.symbols.get(symbol)
+    }
+}
+
+// A symbol somewhere in the package.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Def {
+    pub path: PathBuf,
+    pub symbol: usize,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Completion {
+    pub label: String,
+    // None for keywords.
+    pub kind: Option<SymbolKind>,
+    pub detail: Option<String>,
+}
+
+pub struct Analysis<'w> {
+    workspace: &'w Workspace,
+    files: RefCell<HashMap<PathBuf, Rc<File>>>,
+}
+
+impl<'w> Analysis<'w> {
+    pub fn new(workspace: &'w Workspace) -> Self {
+        Self {
+            workspace,
+            files: RefCell::default(),
+        }
+    }
+
+    pub fn file(&self, path: &Path) -> Option<Rc<File>> {
+        if let Some(file) = self.files.borrow().get(path) {
+            return Some(file.clone());
+        }
+        let source = self.workspace.source(path)?;
+        let file = Rc::new(File::load(self.workspace.db(), path, source));
+        self.files
+            .borrow_mut()
+            .insert(path.to_path_buf(), file.clone());
+        Some(file)
+    }
+
+    fn symbol(&self, def: &Def) -> Option<Symbol> {
+        self.file(&def.path)?.symbol(def.symbol).cloned()
+    }
+
+    // Everything wrong with the file, ranges that aren't tied to a place are at its start.
+    pub fn diagnostics(&self, path: &Path) -> Vec<Problem> {
+        if self.workspace.source(path).is_none() {
+            return vec![];
+        }
+        self.workspace.db().diagnostics(path).to_vec()
+    }
+
+    fn found(&self, path: &Path, offset: usize) -> Option<(Rc<File>, Found)> {
+        let file = self.file(path)?;
+        let found = file.index.as_ref().ok()?.at(offset)?;
+        Some((file, found))
+    }
+
+    // What's at `offset`, as the symbol everything referring to it shares. Imports that aren't
+    // renamed are the same symbol as what they import.
+    fn resolve(&self, path: &Path, offset: usize) -> Option<(Range<usize>, Def)> {
+        let (file, found) = self.found(path, offset)?;
+        let index = file.index.as_ref().ok()?;
+        match found {
+            Found::Symbol(symbol) => Some((
+                index.symbols[symbol].name_range.clone(),
+                self.canonical(Def {
+                    path: path.to_path_buf(),
+                    symbol,
+                }),
+            )),
+            Found::Reference(reference) => Some((
+                index.references[reference].range.clone(),
+                self.reference(&file, reference)?,
+            )),
+        }
+    }
+
+    fn reference(&self, file: &File, reference: usize) -> Option<Def> {
+        let index = file.index.as_ref().ok()?;
+        match &index.references[reference].kind {
+            ReferenceKind::Name(symbol) => Some(self.canonical(Def {
+                path: file.path.clone(),
+                symbol: *symbol,
+            })),
+            ReferenceKind::Imported { module_path, name } => {
+                self.imported(&file.path, module_path, name)
+            }
+            ReferenceKind::Member { base, name } => {
+                let base = self.follow(self.reference(file, *base)?);
+                self.member(&base, name)
+            }
+            ReferenceKind::Contextual(name) => self.variant(&file.path, name),
+        }
+    }
+
+    fn imported(&self, from: &Path, module_path: &str, name: &str) -> Option<Def> {
+        let path = self.workspace.resolve(from, module_path)?;
+        let symbol = self.file(&path)?.index.as_ref().ok()?.item(name)?;
+        Some(self.canonical(Def { path, symbol }))
+    }
+
+    // Follows imports that aren't renamed, so far as they lead into the package.
+    fn canonical(&self, def: Def) -> Def {
+        self.chase(def, false)
+    }
+
+    // Follows every import, for jumping to what a name really is.
+    fn follow(&self, def: Def) -> Def {
+        self.chase(def, true)
+    }
+
+    fn chase(&self, mut def: Def, aliased: bool) -> Def {
+        // Files importing from each other in a circle would go on forever.
+        for _ in 0..16 {
+            let Some(import) = self.symbol(&def).and_then(|symbol| symbol.import) else {
+                break;
+            };
+            if import.aliased && !aliased {
+                break;
+            }
+            match self
+                .workspace
+                .resolve(&def.path, &import.module_path)
+                .and_then(|path| {
+                    let symbol = self.file(&path)?.index.as_ref().ok()?.item(&import.name)?;
+                    Some(Def { path, symbol })
+                }) {
+                Some(next) => def = next,
+                None => break,
+            }
+        }
+        def
+    }
+
+    // `name` on whatever `base` is, a static member of a type or a field or method of a value.
+    fn member(&self, base: &Def, name: &str) -> Option<Def> {
+        let file = self.file(&base.path)?;
+        let symbol = file.symbol(base.symbol)?;
+        let owner = match symbol.kind.is_type() {
+            true => base.clone(),
+            false => self.type_def(&base.path, &self.type_of(base)?)?,
+        };
+
+        let file = self.file(&owner.path)?;
+        let index = file.index.as_ref().ok()?;
+        let container = &index.symbols[owner.symbol].name;
+        let symbol = index
+            .members(container)
+            .find(|&member| index.symbols[member].name == name)?;
+        Some(Def {
+            path: owner.path.clone(),
+            symbol,
+        })
+    }
+
+    // All of the members `.` can be followed by after `base`.
+    fn members(&self, base: &Def) -> Vec<Def> {
+        let Some(symbol) = self.symbol(base) else {
+            return vec![];
+        };
+        let is_type = symbol.kind.is_type();
+        let owner = match is_type {
+            true => Some(base.clone()),
+            false => self
+                .type_of(base)
+                .and_then(|ty| self.type_def(&base.path, &ty)),
+        };
+        let Some(owner) = owner else { return vec![] };
+        let Some(file) = self.file(&owner.path) else {
+            return vec![];
+        };
+        let Ok(index) = &file.index else {
+            return vec![];
+        };
+
+        index
+            .members(&index.symbols[owner.symbol].name)
+            // Values don't have variants, only their enum does.
+            .filter(|&member| is_type || index.symbols[member].kind != SymbolKind::Variant)
+            .map(|symbol| Def {
+                path: owner.path.clone(),
+                symbol,
+            })
+            .collect()
+    }
+
+    // The enum type `ty` is in the file `path`.
+    fn type_def(&self, path: &Path, ty: &Type) -> Option<Def> {
+        let symbol = self.file(path)?.index.as_ref().ok()?.item(ty.named()?)?;
+        let def = self.follow(Def {
+            path: path.to_path_buf(),
+            symbol,
+        });
+        self.symbol(&def)?.kind.is_type().then_some(def)
+    }
+
+    // A contextual `.name`, if exactly one enum the file can see has a variant called that.
+    fn variant(&self, path: &Path, name: &str) -> Option<Def> {
+        let mut variants = self
+            .enums(path)
+            .into_iter()
+            .flat_map(|r#enum| self.members(&r#enum))
+            .filter(|variant| {
+                self.symbol(variant)
+                    .is_some_and(|symbol| symbol.name == name)
+            });
+        let found = variants.next()?;
+        variants.next().is_none().then_some(found)
+    }
+
+    fn enums(&self, path: &Path) -> Vec<Def> {
+        let Some(file) = self.file(path) else {
+            return vec![];
+        };
+        let Ok(index) = &file.index else {
+            return vec![];
+        };
+        index
+            .top_level()
+            .iter()
+            .map(|&symbol| {
+                self.follow(Def {
+                    path: path.to_path_buf(),
+                    symbol,
+                })
+            })
+            .filter(|def| {
+                self.symbol(def)
+                    .is_some_and(|symbol| symbol.kind == SymbolKind::Enum)
+            })
+            .collect()
+    }
+
+    // What type a value has, as written or as inferred by the type checker.
+    pub fn type_of(&self, def: &Def) -> Option<Type> {
+        let symbol = self.symbol(def)?;
+        if let Some(ty) = symbol.ty {
+            return Some(ty);
+        }
+        match symbol.kind {
+            SymbolKind::Import => {
+                let followed = self.follow(def.clone());
+                (followed != *def).then(|| self.type_of(&followed))?
+            }
+            SymbolKind::Global => self
+                .workspace
+                .db()
+                .type_of(&def.path, &symbol.name)
+                .as_ref()
+                .clone(),
+            SymbolKind::Local => self.local_type(def, &symbol),
+            _ => None,
+        }
+    }
+
+    // Locals are typed by walking the function they're in the same way the checker does.
+    fn local_type(&self, def: &Def, symbol: &Symbol) -> Option<Type> {
+        let db = self.workspace.db();
+        let lowered = db.lower(&def.path);
+        let module = lowered.module.as_ref().ok()?;
+        let context = db.context(&def.path);
+        let context = context.as_ref().as_ref()?;
+        let offset = symbol.name_range.start;
+        let contains = |span: &Span| span.start <= offset && offset < span.end;
+
+        let (params, body) = module
+            .functions
+            .iter()
+            .find(|function| contains(&function.span))
+            .map(|function| {
+                let params = function.params.iter();
+                (
+                    params.map(|param| (&param.name, &param.ty)).collect(),
+                    &function.body,
+                )
+            })
+            .or_else(|| {
+                module
+                    .components
+                    .iter()
+                    .find(|component| contains(&component.span))
+                    .map(|component| {
+                        // Props are in scope for the whole body, the same as parameters.
+                        let params = component
+                            .params
+                            .iter()
+                            .map(|param| (&param.name, &param.ty));
+                        let props = component.props.iter().map(|prop| (&prop.name, &prop.ty));
+                        (params.chain(props).collect::<Vec<_>>(), &component.body)
+                    })
+            })?;
+
+        let mut locals = Locals {
+            ctx: TypeContext::new(context),
+            found: vec![],
+        };
+        locals.ctx.push_scope();
+        for (name, ty) in params {
+            locals.ctx.declare(name, ty.clone());
+        }
+        locals.block(body);
+
+        locals
+            .found
+            .into_iter()
+            .filter(|(span, name, _)| contains(span) && *name == symbol.name)
+            .min_by_key(|(span, _, _)| span.end - span.start)
+            .map(|(_, _, ty)| ty)
+            .filter(|ty| *ty != Type::Unknown)
+    }
+
+    pub fn definition(&self, path: &Path, offset: usize) -> Option<(PathBuf, Range<usize>)> {
+        let (_, def) = self.resolve(path, offset)?;
+        let def = self.follow(def);
+        let symbol = self.symbol(&def)?;
+        Some((def.path, symbol.name_range))
+    }
+
+    // Markdown for whatever's at `offset` and the range it's about.
+    pub fn hover(&self, path: &Path, offset: usize) -> Option<(String, Range<usize>)> {
+        let (range, def) = self.resolve(path, offset)?;
+        let def = self.follow(def);
+        let symbol = self.symbol(&def)?;
+
+        let mut signature = symbol.signature.clone();
+        let typed_by_signature = matches!(
+            symbol.kind,
+            SymbolKind::Field | SymbolKind::Parameter | SymbolKind::Variant
+        ) && symbol.ty.is_some();
+        if matches!(
+            symbol.kind,
+            SymbolKind::Local | SymbolKind::Global | SymbolKind::Parameter | SymbolKind::Field
+        ) && !typed_by_signature
+        {
+            if let Some(ty) = self.type_of(&def) {
+                signature = format!("{} {}", signature, ty);
+            }
+        }
+        if let (Some(container), true) = (&symbol.container, symbol.kind.is_member()) {
+            if symbol.kind != SymbolKind::Method {
+                signature = format!("{}\n{}", container, signature);
+            }
+        }
+
+        let mut markdown = format!("```elp\n{}\n```", signature);
+        if let Some(docs) = &symbol.docs {
+            markdown.push_str("\n\n");
+            markdown.push_str(docs);
+        }
+        Some((markdown, range))
+    }
+
+    // Everywhere the symbol at `offset` is used, across the package unless it's a local.
+    pub fn references(
+        &self,
+        path: &Path,
+        offset: usize,
+        include_declaration: bool,
+    ) -> Vec<(PathBuf, Range<usize>)> {
+        let Some((_, target)) = self.resolve(path, offset) else {
+            return vec![];
+        };
+        let Some(symbol) = self.symbol(&target) else {
+            return vec![];
+        };
+
+        let files = match symbol.kind {
+            SymbolKind::Local | SymbolKind::Parameter => vec![target.path.clone()],
+            _ => {
+                let mut files = self.workspace.package_files(&target.path);
+                if !files.contains(&path.to_path_buf()) {
+                    files.push(path.to_path_buf());
+                }
+                files
+            }
+        };
+
+        let mut found = vec![];
+        if include_declaration {
+            found.push((target.path.clone(), symbol.name_range.clone()));
+        }
+        for path in files {
+            let Some(file) = self.file(&path) else {
+                continue;
+            };
+            let Ok(index) = &file.index else { continue };
+            for (reference, used) in index.references.iter().enumerate() {
+                if self.reference(&file, reference).as_ref() == Some(&target) {
+                    found.push((path.clone(), used.range.clone()));
+                }
+            }
+        }
+        found.dedup();
+        found
+    }
+
+    // The edits renaming the symbol at `offset` takes, by file.
+    pub fn rename(
+        &self,
+        path: &Path,
+        offset: usize,
+        new_name: &str,
+    ) -> Result<HashMap<PathBuf, Vec<Range<usize>>>, String> {
+        let valid = new_name
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && new_name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_')
+            && !KEYWORDS.contains(&new_name);
+        if !valid {
+            return Err(format!("`{}` isn't a valid name", new_name));
+        }
+
+        let references = self.references(path, offset, true);
+        if references.is_empty() {
+            return Err("there's nothing here to rename".into());
+        }
+        let mut edits: HashMap<PathBuf, Vec<Range<usize>>> = HashMap::new();
+        for (path, range) in references {
+            edits.entry(path).or_default().push(range);
+        }
+        Ok(edits)
+    }
+
+    // What can be typed at `offset`. After a `.` that's the members of what's in front of it, or
+    // the variants of the enums in scope when there's nothing in front.
+    pub fn completions(&self, path: &Path, offset: usize) -> Vec<Completion> {
+        let Some(file) = self.file(path) else {
+            return vec![];
+        };
+        let source = &file.source;
+        let offset = offset.min(source.len());
+        let is_name = |c: char| c.is_ascii_alphanumeric() || c == '_';
+        let word = source[..offset].trim_end_matches(is_name).len();
+        let dot = source[..word].strip_suffix('.').map(str::len);
+
+        // Half typed code rarely parses, so the scopes come from the file with what's being typed
+        // blanked out, or the whole line if that's not enough.
+        let blank_from = dot.unwrap_or(word);
+        let line_start = source[..blank_from]
+            .rfind('\n')
+            .map_or(0, |newline| newline + 1);
+        let line_end = source[offset..]
+            .find('\n')
+            .map_or(source.len(), |newline| offset + newline);
+        // Forking the workspace shares everything worked out so far.
+        let forks: Vec<Workspace> = [blank_from..offset, line_start..line_end]
+            .into_iter()
+            .map(|blank| {
+                let mut patched = source.clone();
+                patched.replace_range(blank.clone(), &" ".repeat(blank.len()));
+                let mut fork = self.workspace.clone();
+                fork.open(path.to_path_buf(), patched);
+                fork
+            })
+            .collect();
+        let analysis = forks
+            .iter()
+            .map(Analysis::new)
+            .find(|analysis| analysis.file(path).is_some_and(|file| file.index.is_ok()));
+        let Some(analysis) = analysis else {
+            return keywords();
+        };
+        let file = analysis.file(path).expect("checked above");
+        let index = file.index.as_ref().expect("only files that parse");
+
+        let defs = match dot {
+            // `graph.nodes.` or `Direction.`
+            Some(dot) => {
+                let chain_start = source[..dot]
+                    .trim_end_matches(|c: char| is_name(c) || c == '.')
+                    .len();
+                let chain: Vec<&str> = source[chain_start..dot].split('.').collect();
+                match chain.as_slice() {
+                    [""] => analysis
+                        .enums(path)
+                        .into_iter()
+                        .flat_map(|r#enum| analysis.members(&r#enum))
+                        .collect(),
+                    [first, rest @ ..] => {
+                        let base = index
+                            .visible(offset)
+                            .into_iter()
+                            .find(|&symbol| index.symbols[symbol].name == *first)
+                            .map(|symbol| {
+                                analysis.follow(Def {
+                                    path: path.to_path_buf(),
+                                    symbol,
+                                })
+                            });
+                        let base = rest.iter().try_fold(base, |base, name| {
+                            Some(base.and_then(|base| analysis.member(&base, name)))
+                        });
+                        match base.flatten() {
+                            Some(base) => analysis.members(&base),
+                            None => vec![],
+                        }
+                    }
+                    [] => vec![],
+                }
+            }
+            None => index
+                .visible(offset)
+                .into_iter()
+                .map(|symbol| Def {
+                    path: path.to_path_buf(),
+                    symbol,
+                })
+                .collect(),
+        };
+
+        let mut completions: Vec<Completion> = vec![];
+        for def in defs {
+            let Some(symbol) = analysis.symbol(&def) else {
+                continue;
+            };
+            if completions.iter().any(|c| c.label == symbol.name) {
+                continue;
+            }
+            let kind = match symbol.kind {
+                SymbolKind::Import => analysis
+                    .symbol(&analysis.follow(def.clone()))
+                    .map_or(SymbolKind::Import, |followed| followed.kind),
+                kind => kind,
+            };
+            completions.push(Completion {
+                label: symbol.name.clone(),
+                kind: Some(kind),
+                detail: Some(symbol.signature.clone()),
+            });
+        }
+        if dot.is_none() {
+            completions.extend(keywords());
+        }
+        completions
+    }
+}
+
+fn keywords() -> Vec<Completion> {
+    KEYWORDS
+        .iter()
+        .map(|keyword| Completion {
+            label: keyword.to_string(),
+            kind: None,
+            detail: None,
+        })
+        .collect()
+}
+
+// The type of every local in a body, with the statement declaring it.
+struct Locals<'m> {
+    ctx: TypeContext<'m>,
+    found: Vec<(Span, String, Type)>,
+}
+
+impl Locals<'_> {
+    fn block(&mut self, body: &[Stmt]) {
+        self.ctx.push_scope();
+        for stmt in body {
+            self.stmt(stmt);
+        }
+        self.ctx.pop_scope();
+    }
+
+    fn declare(&mut self, span: Span, name: &str, ty: Type) {
+        self.ctx.declare(name, ty.clone());
+        self.found.push((span, name.into(), ty));
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        self.ctx.span = stmt.span;
+        match &stmt.kind {
+            StmtKind::Let {
+                name, ty, value, ..
+            } => {
+                let ty = match (ty, value) {
+                    (Type::Unknown, Some(value)) => {
+                        self.ctx.type_of(value).unwrap_or(Type::Unknown)
+                    }
+                    (ty, _) => ty.clone(),
+                };
+                self.declare(stmt.span, name, ty);
+            }
+            StmtKind::If {
+                then_body,
+                else_body,
+                ..
+            } => {
+                self.block(then_body);
+                self.block(else_body);
+            }
+            StmtKind::For {
+                binding,
+                iterable,
+                body,
+            } => {
+                let ty = match self.ctx.type_of(iterable) {
+                    Ok(Type::Array(inner)) => *inner,
+                    _ => Type::Unknown,
+                };
+                self.ctx.push_scope();
+                self.declare(stmt.span, binding, ty);
+                self.block(body);
+                self.ctx.pop_scope();
+            }
+            StmtKind::Assign { .. } | StmtKind::Expr(_) | StmtKind::Return(_) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const SHAPES: &str = "export enum Color {
+	.Red,
+	.Green,
+}
+
+/// A point on the plane.
+export object Point {
+	.x int,
+	.y int,
+}
+
+export fn Point.sum(self) -> int {
+	return self.x
+}
+
+fn hidden() -> int {
+	return 1
+}
+";
+
+    const MAIN: &str = "import { Point, Color, hidden } from \"$shapes\"
+
+fn main(point Point) {
+	const total = point.sum()
+	const color = Color.Red
+}
+";
+
+    fn package() -> (tempfile::TempDir, Workspace) {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("Package.elp"), "").unwrap();
+        fs::write(dir.path().join("shapes.elp"), SHAPES).unwrap();
+        fs::write(dir.path().join("main.elp"), MAIN).unwrap();
+        (dir, Workspace::default())
+    }
+
+    fn at(source: &str, needle: &str) -> usize {
+        source.find(needle).unwrap()
+    }
+
+    #[test]
+    fn definitions_follow_imports_and_members() {
+        let (dir, workspace) = package();
+        let analysis = Analysis::new(&workspace);
+        let main = dir.path().join("main.elp");
+        let shapes = dir.path().join("shapes.elp");
+
+        let point = at(SHAPES, "Point {");
+        assert_eq!(
+            analysis.definition(&main, at(MAIN, "Point)")),
+            Some((shapes.clone(), point..point + 5))
+        );
+        let sum = at(SHAPES, "sum");
+        assert_eq!(
+            analysis.definition(&main, at(MAIN, "sum()")),
+            Some((shapes.clone(), sum..sum + 3))
+        );
+        let red = at(SHAPES, "Red");
+        assert_eq!(
+            analysis.definition(&main, at(MAIN, "Red")),
+            Some((shapes, red..red + 3))
+        );
+    }
+
+    #[test]
+    fn hover_shows_signatures_docs_and_inferred_types() {
+        let (dir, workspace) = package();
+        let analysis = Analysis::new(&workspace);
+        let main = dir.path().join("main.elp");
+
+        let (markdown, _) = analysis.hover(&main, at(MAIN, "Point)")).unwrap();
+        assert_eq!(
+            markdown,
+            "```elp\nobject Point\n```\n\nA point on the plane."
+        );
+        let (markdown, _) = analysis.hover(&main, at(MAIN, "total")).unwrap();
+        assert_eq!(markdown, "```elp\nconst total int64\n```");
+    }
+
+    #[test]
+    fn references_and_rename_cover_the_package() {
+        let (dir, workspace) = package();
+        let analysis = Analysis::new(&workspace);
+        let main = dir.path().join("main.elp");
+        let shapes = dir.path().join("shapes.elp");
+
+        let edits = analysis
+            .rename(&main, at(MAIN, "Point)"), "Vector")
+            .unwrap();
+        let point = |source: &str| {
+            source
+                .match_indices("Point")
+                .map(|(offset, _)| offset..offset + 5)
+                .collect::<Vec<_>>()
+        };
+        let mut in_shapes = edits[&shapes].clone();
+        in_shapes.sort_by_key(|range| range.start);
+        assert_eq!(in_shapes, point(SHAPES));
+        assert_eq!(edits[&main], point(MAIN));
+
+        assert_eq!(
+            analysis.rename(&main, at(MAIN, "Point)"), "fn"),
+            Err("`fn` isn't a valid name".into())
+        );
+    }
+
+    #[test]
+    fn completes_members_and_variants() {
+        let (dir, mut workspace) = package();
+        let main = dir.path().join("main.elp");
+        let mut labels = |typing: String, offset| {
+            workspace.open(main.clone(), typing);
+            Analysis::new(&workspace)
+                .completions(&main, offset)
+                .into_iter()
+                .map(|completion| completion.label)
+                .collect::<Vec<_>>()
+        };
+
+        let typing = MAIN.replace("point.sum()", "point.");
+        assert_eq!(
+            labels(typing.clone(), at(&typing, "point.") + 6),
+            ["x", "y", "sum"]
+        );
+        let typing = MAIN.replace("Color.Red", "Color.G");
+        assert_eq!(
+            labels(typing.clone(), at(&typing, ".G") + 2),
+            ["Red", "Green"]
+        );
+        let typing = MAIN.replace("Color.Red", ".");
+        assert_eq!(
+            labels(typing.clone(), at(&typing, "= .") + 3),
+            ["Red", "Green"]
+        );
+        let typing = MAIN.replace("point.sum()", "po");
+        let names = labels(typing.clone(), at(&typing, "= po") + 4);
+        assert_eq!(names[..5], ["point", "Point", "Color", "hidden", "main"]);
+        assert!(names.contains(&"return".to_string()));
+    }
+
+    #[test]
+    fn diagnostics_cover_imports_and_checking() {
+        let (dir, mut workspace) = package();
+        let main = dir.path().join("main.elp");
+        let source = "import { hidden, Missing } from \"$shapes\"
+import { nothing } from \"$nowhere\"
+
+fn main {
+	undefined()
+}
+";
+        workspace.open(main.clone(), source.into());
+        let analysis = Analysis::new(&workspace);
+
+        let messages: Vec<String> = analysis
+            .diagnostics(&main)
+            .into_iter()
+            .map(|problem| problem.message)
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "`hidden` isn't exported from `$shapes`",
+                "`$shapes` has no `Missing`",
+                "can't find `$nowhere`",
+                "`undefined` isn't defined",
+            ]
+        );
+
+        workspace.open(main.clone(), "fn main {\n\tconst = 1\n}\n".into());
+        let analysis = Analysis::new(&workspace);
+        let problems = analysis.diagnostics(&main);
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].message.starts_with("couldn't parse this"));
+    }
+}
@@ -0,0 +1,842 @@
+// The resolver behind go to definition, references, rename and completion. It works on the parse
+// tree rather than the CST so that it still has something to say about files using syntax the
+// compiler can't handle yet. Every name a file declares is a symbol and every use of a name is a
+// reference. Plain names are resolved here through the scopes they're in, member accesses and
+// imports need types and other files so they're left for `Analysis` to finish off.
+
+use std::ops::Range;
+
+use elp_ir::{lower::lower_type, mir::Type};
+use elp_parser::{
+    ast::{elp_type::ASTElpType, traits::FromCST},
+    cst::{elp_type::CSTElpType, trivia::CSTNode},
+    incremental::SyntaxTree,
+    parser::{ElpParser, Rule},
+};
+use from_pest::FromPest;
+use pest::{iterators::Pair, Parser, Span};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SymbolKind {
+    Object,
+    Enum,
+    Interface,
+    Component,
+    Function,
+    // Declared on an object, `fn Graph.new`, or in an interface.
+    Method,
+    Field,
+    Variant,
+    Global,
+    Local,
+    Parameter,
+    Import,
+}
+
+impl SymbolKind {
+    pub fn is_type(self) -> bool {
+        matches!(
+            self,
+            SymbolKind::Object | SymbolKind::Enum | SymbolKind::Interface
+        )
+    }
+
+    pub fn is_member(self) -> bool {
+        matches!(
+            self,
+            SymbolKind::Method | SymbolKind::Field | SymbolKind::Variant
+        )
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Import {
+    pub module_path: String,
+    // What it's called in the module it's imported from.
+    pub name: String,
+    pub aliased: bool,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    // Just the name, what definitions point at and renames rewrite.
+    pub name_range: Range<usize>,
+    // The whole declaration.
+    pub range: Range<usize>,
+    // The object, enum or interface a member belongs to, or the function a parameter is of.
+    pub container: Option<String>,
+    pub exported: bool,
+    // How it's declared, `fn area(self) -> float`, with the body and defaults left out.
+    pub signature: String,
+    pub docs: Option<String>,
+    // The type written down for it, types that are inferred come from the MIR.
+    pub ty: Option<Type>,
+    pub import: Option<Import>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ReferenceKind {
+    // A name found through the scopes it's used in, a symbol of the same file.
+    Name(usize),
+    // The name of an item in the module it's imported from.
+    Imported { module_path: String, name: String },
+    // `.name` after another reference, `base` indexes the references. `Graph.new`, `graph.nodes`
+    // and the fields of `Graph { .nodes = [] }` are all members.
+    Member { base: usize, name: String },
+    // `.name` with nothing in front, a variant of whichever enum the context wants.
+    Contextual(String),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Reference {
+    pub range: Range<usize>,
+    pub kind: ReferenceKind,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+struct Scope {
+    range: Range<usize>,
+    symbols: Vec<usize>,
+}
+
+// Whatever is under the cursor.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Found {
+    Symbol(usize),
+    Reference(usize),
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Index {
+    pub symbols: Vec<Symbol>,
+    pub references: Vec<Reference>,
+    // Symbols visible anywhere in the module.
+    top_level: Vec<usize>,
+    scopes: Vec<Scope>,
+}
+
+impl Index {
+    // Built from the tree the database already parsed, so a file is only parsed once whether the
+    // compiler or the editor asks about it.
+    pub fn new(tree: &SyntaxTree) -> Self {
+        let module = tree.pairs().next().expect("a module always has a pair");
+
+        let mut builder = Builder::default();
+        builder.items(module.clone());
+        builder.visit(module);
+        builder.index
+    }
+
+    // References win over symbols, the name of an import is both.
+    pub fn at(&self, offset: usize) -> Option<Found> {
+        let contains = |range: &Range<usize>| range.start <= offset && offset <= range.end;
+        if let Some(reference) = self.references.iter().position(|r| contains(&r.range)) {
+            return Some(Found::Reference(reference));
+        }
+        self.symbols
+            .iter()
+            .position(|symbol| contains(&symbol.name_range))
+            .map(Found::Symbol)
+    }
+
+    // The top level item called `name`, as another module importing it would see it.
+    pub fn item(&self, name: &str) -> Option<usize> {
+        self.top_level
+            .iter()
+            .copied()
+            .find(|&symbol| self.symbols[symbol].name == name)
+    }
+
+    pub fn top_level(&self) -> &[usize] {
+        &self.top_level
+    }
+
+    // The members of the type called `container`.
+    pub fn members<'i>(&'i self, container: &'i str) -> impl Iterator<Item = usize> + 'i {
+        (0..self.symbols.len()).filter(move |&symbol| {
+            let symbol = &self.symbols[symbol];
+            symbol.kind.is_member() && symbol.container.as_deref() == Some(container)
+        })
+    }
+
+    // Every name that can be used at `offset`, innermost first so shadowed names come later.
+    pub fn visible(&self, offset: usize) -> Vec<usize> {
+        let mut visible: Vec<usize> = vec![];
+        let scopes = self
+            .scopes
+            .iter()
+            .rev()
+            .filter(|scope| scope.range.start <= offset && offset <= scope.range.end);
+        for scope in scopes {
+            visible.extend(
+                scope
+                    .symbols
+                    .iter()
+                    .rev()
+                    // A local isn't in scope in its own initializer.
+                    .filter(|&&symbol| self.symbols[symbol].range.end <= offset),
+            );
+        }
+        visible.extend(&self.top_level);
+        visible
+    }
+}
+
+#[derive(Default)]
+struct Builder {
+    index: Index,
+    // Indexes into `index.scopes`, innermost last. Empty at the top level.
+    stack: Vec<usize>,
+}
+
+impl Builder {
+    fn symbol(&mut self, symbol: Symbol) -> usize {
+        self.index.symbols.push(symbol);
+        self.index.symbols.len() - 1
+    }
+
+    fn reference(&mut self, range: Range<usize>, kind: ReferenceKind) -> usize {
+        self.index.references.push(Reference { range, kind });
+        self.index.references.len() - 1
+    }
+
+    // The first pass, everything a module declares at the top level. These can be used before
+    // they're declared so they all have to be known before any of the bodies are looked at.
+    fn items(&mut self, module: Pair<Rule>) {
+        for expression in module.into_inner() {
+            if expression.as_rule() != Rule::expression {
+                continue;
+            }
+            let mut item = inner(expression);
+            let exported = item.as_rule() == Rule::export;
+            if exported {
+                match item
+                    .into_inner()
+                    .find(|pair| pair.as_rule() == Rule::expression)
+                {
+                    Some(expression) => item = inner(expression),
+                    None => continue,
+                }
+            }
+            self.item(item, exported);
+        }
+    }
+
+    fn item(&mut self, item: Pair<Rule>, exported: bool) {
+        let first = self.index.symbols.len();
+        match item.as_rule() {
+            Rule::object_def => self.object(item),
+            Rule::r#enum => self.r#enum(item),
+            Rule::interface_def => self.interface(item),
+            Rule::function_def | Rule::component_def | Rule::fn_header_def => {
+                self.function(item);
+            }
+            Rule::variable_assignment | Rule::variable_declaration => self.global(item),
+            Rule::import => self.import(item),
+            Rule::external_symbol | Rule::external_block => {
+                for child in item.into_inner() {
+                    self.item(child, exported);
+                }
+            }
+            _ => {}
+        }
+
+        for symbol in first..self.index.symbols.len() {
+            self.index.symbols[symbol].exported = exported;
+            if !self.index.symbols[symbol].kind.is_member() {
+                self.index.top_level.push(symbol);
+            }
+        }
+    }
+
+    fn object(&mut self, object: Pair<Rule>) {
+        let Some(name) = ident(&object) else { return };
+        self.symbol(Symbol {
+            signature: header(&object),
+            ..declaration(&object, &name, SymbolKind::Object)
+        });
+
+        for member in object.into_inner() {
+            if member.as_rule() != Rule::object_member {
+                continue;
+            }
+            let Some(field) = ident(&member) else {
+                continue;
+            };
+            let text = member.as_str();
+            let declared = text.find(['=', '`']).map_or(text, |end| &text[..end]);
+            self.symbol(Symbol {
+                signature: collapse(declared),
+                container: Some(name.as_str().into()),
+                ty: child(&member, Rule::elp_type).and_then(elp_type),
+                ..declaration(&member, &field, SymbolKind::Field)
+            });
+        }
+    }
+
+    fn r#enum(&mut self, r#enum: Pair<Rule>) {
+        let Some(name) = ident(&r#enum) else { return };
+        self.symbol(Symbol {
+            signature: header(&r#enum),
+            ..declaration(&r#enum, &name, SymbolKind::Enum)
+        });
+
+        for member in r#enum.into_inner() {
+            if member.as_rule() != Rule::enum_member {
+                continue;
+            }
+            if let Some(variant) = ident(&member) {
+                self.symbol(Symbol {
+                    container: Some(name.as_str().into()),
+                    ..declaration(&member, &variant, SymbolKind::Variant)
+                });
+            }
+        }
+    }
+
+    fn interface(&mut self, interface: Pair<Rule>) {
+        let Some(name) = ident(&interface) else {
+            return;
+        };
+        self.symbol(Symbol {
+            signature: header(&interface),
+            ..declaration(&interface, &name, SymbolKind::Interface)
+        });
+
+        for member in interface.into_inner() {
+            if member.as_rule() != Rule::interface_member {
+                continue;
+            }
+            let member = inner(member);
+            if member.as_rule() == Rule::fn_header_def {
+                if let Some(method) = self.function(member) {
+                    let method = &mut self.index.symbols[method];
+                    method.kind = SymbolKind::Method;
+                    method.container = Some(name.as_str().into());
+                }
+            } else if let Some(field) = ident(&member) {
+                self.symbol(Symbol {
+                    container: Some(name.as_str().into()),
+                    ty: child(&member, Rule::elp_type).and_then(elp_type),
+                    ..declaration(&member, &field, SymbolKind::Field)
+                });
+            }
+        }
+    }
+
+    // Functions, components and the headers of external functions. `fn Graph.new` is a method of
+    // `Graph`.
+    fn function(&mut self, function: Pair<Rule>) -> Option<usize> {
+        let names = names(&function)?;
+        let name = names.last()?;
+        let kind = match (function.as_rule(), names.len()) {
+            (_, 2..) => SymbolKind::Method,
+            (Rule::component_def, _) => SymbolKind::Component,
+            _ => SymbolKind::Function,
+        };
+        let signature = match child(&function, Rule::block) {
+            Some(block) => {
+                collapse(&function.get_input()[function.as_span().start()..block.as_span().start()])
+            }
+            None => collapse(function.as_str()),
+        };
+
+        Some(self.symbol(Symbol {
+            signature,
+            container: (names.len() > 1).then(|| names[0].as_str().into()),
+            ..declaration(&function, name, kind)
+        }))
+    }
+
+    fn global(&mut self, assignment: Pair<Rule>) {
+        let declaration_pair = match assignment.as_rule() {
+            Rule::variable_declaration => Some(assignment.clone()),
+            _ => child(&assignment, Rule::variable_assignment_target)
+                .and_then(|target| child(&target, Rule::variable_declaration)),
+        };
+        let Some(declared) = declaration_pair else {
+            return;
+        };
+        if let Some(symbol) = variable(&declared, &assignment, SymbolKind::Global) {
+            self.symbol(symbol);
+        }
+    }
+
+    fn import(&mut self, import: Pair<Rule>) {
+        let module_path = module_path(&import);
+        for name in import.into_inner() {
+            if name.as_rule() != Rule::import_name {
+                continue;
+            }
+            let Some(original) = ident(&name) else {
+                continue;
+            };
+            let alias = child(&name, Rule::import_name_alias).and_then(|alias| ident(&alias));
+            let local = alias.clone().unwrap_or_else(|| original.clone());
+
+            self.symbol(Symbol {
+                signature: format!(
+                    "import {{ {} }} from \"{}\"",
+                    collapse(name.as_str()),
+                    module_path
+                ),
+                import: Some(Import {
+                    module_path: module_path.clone(),
+                    name: original.as_str().into(),
+                    aliased: alias.is_some(),
+                }),
+                docs: None,
+                ..declaration(&name, &local, SymbolKind::Import)
+            });
+        }
+    }
+
+    fn push_scope(&mut self, span: Span) {
+        self.index.scopes.push(Scope {
+            range: span.start()..span.end(),
+            symbols: vec![],
+        });
+        self.stack.push(self.index.scopes.len() - 1);
+    }
+
+    fn pop_scope(&mut self) {
+        self.stack.pop();
+    }
+
+    fn declare(&mut self, symbol: Symbol) {
+        let symbol = self.symbol(symbol);
+        let scope = *self.stack.last().expect("locals are declared in a scope");
+        self.index.scopes[scope].symbols.push(symbol);
+    }
+
+    fn lookup(&self, name: &str) -> Option<usize> {
+        let index = &self.index;
+        self.stack
+            .iter()
+            .rev()
+            .flat_map(|&scope| index.scopes[scope].symbols.iter().rev())
+            .chain(&index.top_level)
+            .copied()
+            .find(|&symbol| index.symbols[symbol].name == name)
+    }
+
+    // Types are only ever declared at the top level.
+    fn lookup_type(&self, name: &str) -> Option<usize> {
+        self.index
+            .item(name)
+            .filter(|&symbol| self.index.symbols[symbol].kind != SymbolKind::Function)
+    }
+
+    // The second pass, uses of names and the locals they might refer to.
+    fn visit(&mut self, pair: Pair<Rule>) {
+        match pair.as_rule() {
+            Rule::function_def | Rule::component_def | Rule::fn_header_def => {
+                self.visit_function(pair)
+            }
+            Rule::block => {
+                self.push_scope(pair.as_span());
+                self.visit_children(pair);
+                self.pop_scope();
+            }
+            Rule::for_loop => self.visit_for(pair),
+            Rule::variable_assignment => {
+                let mut target = None;
+                for child in pair.clone().into_inner() {
+                    match child.as_rule() {
+                        Rule::variable_assignment_target => target = Some(inner(child)),
+                        // The value first, `const count = count + 1` is about an outer `count`.
+                        _ => self.visit(child),
+                    }
+                }
+                match target {
+                    Some(declared) if declared.as_rule() == Rule::variable_declaration => {
+                        self.visit_declaration(declared, &pair)
+                    }
+                    Some(target) => self.visit(target),
+                    None => {}
+                }
+            }
+            Rule::variable_declaration => self.visit_declaration(pair.clone(), &pair),
+            Rule::variable_access => self.visit_access(pair),
+            Rule::contextual_variable_access => {
+                if let Some(name) = ident(&pair) {
+                    self.reference(
+                        range(&name),
+                        ReferenceKind::Contextual(name.as_str().into()),
+                    );
+                }
+            }
+            Rule::elp_type_parameter => {
+                if let Some(name) = ident(&pair) {
+                    if let Some(symbol) = self.lookup_type(name.as_str()) {
+                        self.reference(range(&name), ReferenceKind::Name(symbol));
+                    }
+                }
+                self.visit_children(pair);
+            }
+            Rule::object_instantiation => self.visit_instantiation(pair),
+            Rule::import => {
+                let module_path = module_path(&pair);
+                if !module_path.starts_with('$') {
+                    return;
+                }
+                for name in pair.into_inner() {
+                    if let Some(original) = ident(&name) {
+                        self.reference(
+                            range(&original),
+                            ReferenceKind::Imported {
+                                module_path: module_path.clone(),
+                                name: original.as_str().into(),
+                            },
+                        );
+                    }
+                }
+            }
+            _ => self.visit_children(pair),
+        }
+    }
+
+    fn visit_children(&mut self, pair: Pair<Rule>) {
+        for child in pair.into_inner() {
+            self.visit(child);
+        }
+    }
+
+    fn visit_function(&mut self, function: Pair<Rule>) {
+        let names = names(&function).unwrap_or_default();
+        let receiver = match names.as_slice() {
+            [receiver, _, ..] => {
+                if let Some(symbol) = self.lookup_type(receiver.as_str()) {
+                    self.reference(range(receiver), ReferenceKind::Name(symbol));
+                }
+                Some(receiver.as_str())
+            }
+            _ => None,
+        };
+        let qualified = names.iter().map(Pair::as_str).collect::<Vec<_>>().join(".");
+
+        self.push_scope(function.as_span());
+        for part in function.clone().into_inner() {
+            match part.as_rule() {
+                Rule::variable_access | Rule::pointer_semantics => {}
+                Rule::function_arguments => {
+                    for argument in part.into_inner() {
+                        let Some(name) = ident(&argument) else {
+                            continue;
+                        };
+                        let written = child(&argument, Rule::elp_type);
+                        // Methods take their receiver as `self` without saying what it is.
+                        let ty = match (&written, receiver) {
+                            (Some(ty), _) => elp_type(ty.clone()),
+                            (None, Some(receiver)) if name.as_str() == "self" => {
+                                Some(Type::from_name(receiver, vec![]))
+                            }
+                            _ => None,
+                        };
+                        self.declare(Symbol {
+                            signature: collapse(argument.as_str()),
+                            container: Some(qualified.clone()),
+                            ty,
+                            docs: None,
+                            ..declaration(&argument, &name, SymbolKind::Parameter)
+                        });
+                        if let Some(written) = written {
+                            self.visit(written);
+                        }
+                    }
+                }
+                _ => self.visit(part),
+            }
+        }
+        self.pop_scope();
+    }
+
+    fn visit_for(&mut self, for_loop: Pair<Rule>) {
+        let mut children = for_loop.clone().into_inner();
+        let binding = children.next();
+        let rest: Vec<_> = children.collect();
+        let (iterable, body) = match rest.as_slice() {
+            [iterable, body] => (Some(iterable.clone()), Some(body.clone())),
+            _ => (None, None),
+        };
+        if let Some(iterable) = iterable {
+            self.visit(iterable);
+        }
+
+        self.push_scope(for_loop.as_span());
+        let binding = binding
+            .and_then(|binding| descendant(&binding, Rule::variable_access_names))
+            .and_then(|names| ident(&names));
+        if let Some(name) = binding {
+            self.declare(Symbol {
+                signature: name.as_str().into(),
+                docs: None,
+                ..declaration(&for_loop, &name, SymbolKind::Local)
+            });
+        }
+        if let Some(body) = body {
+            self.visit(body);
+        }
+        self.pop_scope();
+    }
+
+    // Top level declarations are already symbols, the rest are locals of the scope they're in.
+    fn visit_declaration(&mut self, declared: Pair<Rule>, statement: &Pair<Rule>) {
+        if let Some(ty) = child(&declared, Rule::elp_type) {
+            self.visit(ty);
+        }
+        if self.stack.is_empty() {
+            return;
+        }
+        if let Some(symbol) = variable(&declared, statement, SymbolKind::Local) {
+            self.declare(Symbol {
+                docs: None,
+                ..symbol
+            });
+        }
+    }
+
+    fn visit_access(&mut self, access: Pair<Rule>) {
+        let Some(names) = child(&access, Rule::variable_access_names) else {
+            return;
+        };
+        let mut names = names.into_inner();
+        let Some(first) = names.next() else { return };
+        // Anything that isn't declared in the package, the standard library or a typo.
+        let Some(symbol) = self.lookup(first.as_str()) else {
+            return;
+        };
+
+        let mut base = self.reference(range(&first), ReferenceKind::Name(symbol));
+        for name in names {
+            base = self.reference(
+                range(&name),
+                ReferenceKind::Member {
+                    base,
+                    name: name.as_str().into(),
+                },
+            );
+        }
+    }
+
+    fn visit_instantiation(&mut self, instantiation: Pair<Rule>) {
+        let object = ident(&instantiation)
+            .and_then(|name| Some((self.lookup_type(name.as_str())?, name)))
+            .map(|(symbol, name)| self.reference(range(&name), ReferenceKind::Name(symbol)));
+
+        for member in instantiation.into_inner() {
+            if member.as_rule() != Rule::object_instantiation_member {
+                continue;
+            }
+            if let (Some(base), Some(field)) = (object, ident(&member)) {
+                self.reference(
+                    range(&field),
+                    ReferenceKind::Member {
+                        base,
+                        name: field.as_str().into(),
+                    },
+                );
+            }
+            self.visit_children(member);
+        }
+    }
+}
+
+// A declaration of `name`, callers fill in what's particular to the kind of symbol.
+fn declaration(pair: &Pair<Rule>, name: &Pair<Rule>, kind: SymbolKind) -> Symbol {
+    Symbol {
+        name: name.as_str().into(),
+        kind,
+        name_range: range(name),
+        range: range(pair),
+        container: None,
+        exported: false,
+        signature: collapse(pair.as_str()),
+        docs: Node(pair.as_span()).doc_comment(),
+        ty: None,
+        import: None,
+    }
+}
+
+// `const total int` as a global or a local, `statement` is the whole assignment.
+fn variable(declared: &Pair<Rule>, statement: &Pair<Rule>, kind: SymbolKind) -> Option<Symbol> {
+    let name = ident(declared)?;
+    let mutability = child(declared, Rule::mutability_selector)
+        .map_or("const", |mutability| mutability.as_str().trim());
+
+    Some(Symbol {
+        signature: format!("{} {}", mutability, name.as_str()),
+        ty: child(declared, Rule::elp_type).and_then(elp_type),
+        ..declaration(statement, &name, kind)
+    })
+}
+
+// CST nodes get their doc comments from their spans, this gets them for parse tree pairs.
+struct Node<'a>(Span<'a>);
+
+impl<'a> CSTNode<'a> for Node<'a> {
+    fn span(&self) -> Span<'a> {
+        self.0
+    }
+}
+
+// The rule an `expression` is.
+fn inner(pair: Pair<Rule>) -> Pair<Rule> {
+    pair.clone().into_inner().next().unwrap_or(pair)
+}
+
+fn child<'i>(pair: &Pair<'i, Rule>, rule: Rule) -> Option<Pair<'i, Rule>> {
+    pair.clone()
+        .into_inner()
+        .find(|child| child.as_rule() == rule)
+}
+
+fn descendant<'i>(pair: &Pair<'i, Rule>, rule: Rule) -> Option<Pair<'i, Rule>> {
+    pair.clone()
+        .into_inner()
+        .flatten()
+        .find(|child| child.as_rule() == rule)
+}
+
+fn ident<'i>(pair: &Pair<'i, Rule>) -> Option<Pair<'i, Rule>> {
+    child(pair, Rule::IDENT)
+}
+
+// The names of a function, `Graph` and `new` for `fn Graph.new`.
+fn names<'i>(function: &Pair<'i, Rule>) -> Option<Vec<Pair<'i, Rule>>> {
+    let access = child(function, Rule::variable_access)?;
+    let names = child(&access, Rule::variable_access_names)?;
+    Some(names.into_inner().collect())
+}
+
+fn module_path(import: &Pair<Rule>) -> String {
+    descendant(import, Rule::inner).map_or_else(String::new, |inner| inner.as_str().into())
+}
+
+fn range(pair: &Pair<Rule>) -> Range<usize> {
+    pair.as_span().start()..pair.as_span().end()
+}
+
+// Everything before the `{` of an item, `object Square implements Shape`.
+fn header(pair: &Pair<Rule>) -> String {
+    let text = pair.as_str();
+    collapse(text.find('{').map_or(text, |brace| &text[..brace]))
+}
+
+fn collapse(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn elp_type(pair: Pair<Rule>) -> Option<Type> {
+    let mut pairs = ElpParser::parse(Rule::elp_type, pair.as_str()).ok()?;
+    let cst = CSTElpType::from_pest(&mut pairs).ok()?;
+    Some(lower_type(&ASTElpType::from_cst(&cst)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn name(index: &Index, found: Option<Found>) -> Option<(&str, SymbolKind)> {
+        let symbol = match found? {
+            Found::Symbol(symbol) => symbol,
+            Found::Reference(reference) => match &index.references[reference].kind {
+                ReferenceKind::Name(symbol) => *symbol,
+                _ => return None,
+            },
+        };
+        Some((&index.symbols[symbol].name, index.symbols[symbol].kind))
+    }
+
+    #[test]
+    fn names_resolve_through_scopes() {
+        let source = "const limit = 10
+
+fn count(items [int]) -> int {
+	var total = 0
+	for item in items {
+		const limit = item
+		total = limit
+	}
+	return limit
+}
+";
+        let index = Index::new(&SyntaxTree::parse(source).unwrap());
+        let inner = source.find("total = limit").unwrap() + 8;
+        let outer = source.rfind("limit").unwrap();
+
+        assert_eq!(
+            name(&index, index.at(inner)),
+            Some(("limit", SymbolKind::Local))
+        );
+        assert_eq!(
+            name(&index, index.at(outer)),
+            Some(("limit", SymbolKind::Global))
+        );
+        assert_eq!(
+            name(&index, index.at(source.find("in items").unwrap() + 3)),
+            Some(("items", SymbolKind::Parameter))
+        );
+        assert_eq!(
+            index
+                .visible(outer)
+                .iter()
+                .map(|&symbol| index.symbols[symbol].name.as_str())
+                .collect::<Vec<_>>(),
+            ["total", "items", "limit", "count"]
+        );
+    }
+
+    #[test]
+    fn members_and_contextual_names_are_left_for_analysis() {
+        let source = "enum Direction {
+	.Up,
+	.Down,
+}
+
+fn flip(direction Direction) {
+	const next Direction = .Down
+	direction.flip()
+}
+";
+        let index = Index::new(&SyntaxTree::parse(source).unwrap());
+        let kinds: Vec<_> = index
+            .references
+            .iter()
+            .map(|reference| (&source[reference.range.clone()], reference.kind.clone()))
+            .collect();
+        let direction = index.item("Direction").unwrap();
+        let parameter = index
+            .symbols
+            .iter()
+            .position(|symbol| symbol.kind == SymbolKind::Parameter)
+            .unwrap();
+        assert_eq!(
+            kinds,
+            [
+                ("Direction", ReferenceKind::Name(direction)),
+                // Initializers are visited before what they're assigned to.
+                ("Down", ReferenceKind::Contextual("Down".into())),
+                ("Direction", ReferenceKind::Name(direction)),
+                ("direction", ReferenceKind::Name(parameter)),
+                (
+                    "flip",
+                    ReferenceKind::Member {
+                        base: 3,
+                        name: "flip".into()
+                    }
+                ),
+            ]
+        );
+        assert_eq!(
+            index
+                .members("Direction")
+                .map(|symbol| index.symbols[symbol].name.as_str())
+                .collect::<Vec<_>>(),
+            ["Up", "Down"]
+        );
+    }
+}
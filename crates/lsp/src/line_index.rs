@@ -0,0 +1,84 @@
+// LSP positions are lines and UTF-16 code units, everything else in elp works in byte offsets.
+// This converts between the two for one version of a document.
+
+use std::ops::Range;
+
+use lsp_types::Position;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct LineIndex {
+    // The byte offset each line starts at.
+    starts: Vec<usize>,
+    len: usize,
+}
+
+impl LineIndex {
+    pub fn new(source: &str) -> Self {
+        let starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(offset, _)| offset + 1))
+            .collect();
+        Self {
+            starts,
+            len: source.len(),
+        }
+    }
+
+    pub fn position(&self, source: &str, offset: usize) -> Position {
+        let offset = offset.min(self.len);
+        let line = self.starts.partition_point(|&start| start <= offset) - 1;
+        let character = source[self.starts[line]..offset]
+            .chars()
+            .map(char::len_utf16)
+            .sum::<usize>();
+        Position::new(line as u32, character as u32)
+    }
+
+    pub fn range(&self, source: &str, range: Range<usize>) -> lsp_types::Range {
+        lsp_types::Range::new(
+            self.position(source, range.start),
+            self.position(source, range.end),
+        )
+    }
+
+    // Positions past the end of a line are clamped to it, clients do send those.
+    pub fn offset(&self, source: &str, position: Position) -> usize {
+        let Some(&start) = self.starts.get(position.line as usize) else {
+            return self.len;
+        };
+        let end = self
+            .starts
+            .get(position.line as usize + 1)
+            .map_or(self.len, |next| next - 1);
+
+        let mut units = 0;
+        for (offset, c) in source[start..end].char_indices() {
+            if units >= position.character as usize {
+                return start + offset;
+            }
+            units += c.len_utf16();
+        }
+        end
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn positions_count_utf16() {
+        let source = "const a = \"ß😀\"\nfn main {\n}";
+        let index = LineIndex::new(source);
+
+        let after_emoji = source.find('😀').unwrap() + '😀'.len_utf8();
+        assert_eq!(index.position(source, after_emoji), Position::new(0, 14));
+        assert_eq!(index.offset(source, Position::new(0, 14)), after_emoji);
+        assert_eq!(
+            index.position(source, source.find("main").unwrap()),
+            Position::new(1, 3)
+        );
+        assert_eq!(index.offset(source, Position::new(1, 99)), source.len() - 2);
+        assert_eq!(index.offset(source, Position::new(9, 0)), source.len());
+    }
+}
@@ -0,0 +1,27 @@
+mod analysis;
+mod index;
+mod line_index;
+mod server;
+mod workspace;
+
+use std::process::ExitCode;
+
+use lsp_server::Connection;
+
+// Speaks the language server protocol over stdin and stdout, editors start it for elp files.
+fn main() -> ExitCode {
+    let (connection, io_threads) = Connection::stdio();
+    let result = server::run(connection);
+    let joined = io_threads.join();
+
+    match result
+        .map_err(|err| err.to_string())
+        .and(joined.map_err(|err| err.to_string()))
+    {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("elp-lsp: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,682 @@
+// The protocol side of the server, what it advertises and how requests and notifications turn
+// into questions for `Analysis`. Documents are synced whole, they're small enough that working
+// out incremental edits isn't worth it yet.
+
+use std::{collections::HashMap, error::Error, ops::Range, path::PathBuf};
+
+use elp_parser::formatter::{format_range, format_source};
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, RequestId, Response};
+use lsp_types::{
+    notification::{
+        DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, Notification as _,
+        PublishDiagnostics,
+    },
+    request::{
+        Completion, DocumentSymbolRequest, Formatting, GotoDefinition, HoverRequest,
+        RangeFormatting, References, Rename, Request as _,
+    },
+    CompletionItem, CompletionItemKind, CompletionOptions, CompletionResponse, Diagnostic,
+    DiagnosticSeverity, DocumentSymbol, DocumentSymbolResponse, GotoDefinitionResponse, Hover,
+    HoverContents, HoverProviderCapability, InitializeParams, Location, MarkupContent, MarkupKind,
+    OneOf, Position, PublishDiagnosticsParams, ServerCapabilities, ServerInfo,
+    TextDocumentSyncCapability, TextDocumentSyncKind, TextEdit, Url, WorkspaceEdit,
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    analysis::Analysis,
+    index::{Index, SymbolKind},
+    line_index::LineIndex,
+    workspace::Workspace,
+};
+
+pub fn run(connection: Connection) -> Result<()> {
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        references_provider: Some(OneOf::Left(true)),
+        document_symbol_provider: Some(OneOf::Left(true)),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(vec![".".into()]),
+            ..Default::default()
+        }),
+        rename_provider: Some(OneOf::Left(true)),
+        document_formatting_provider: Some(OneOf::Left(true)),
+        document_range_formatting_provider: Some(OneOf::Left(true)),
+        ..Default::default()
+    };
+    let (id, params) = connection.initialize_start()?;
+    let _: InitializeParams = serde_json::from_value(params)?;
+    connection.initialize_finish(
+        id,
+        serde_json::json!({
+            "capabilities": capabilities,
+            "serverInfo": ServerInfo {
+                name: "elp-lsp".into(),
+                version: Some(env!("CARGO_PKG_VERSION").into()),
+            },
+        }),
+    )?;
+
+    let mut server = Server {
+        connection: &connection,
+        workspace: Workspace::default(),
+        published: vec![],
+    };
+    for message in &connection.receiver {
+        match message {
+            Message::Request(request) => {
+                if connection.handle_shutdown(&request)? {
+                    return Ok(());
+                }
+                server.request(request)?;
+            }
+            Message::Notification(notification) => server.notification(notification)?,
+            Message::Response(_) => {}
+        }
+    }
+    Ok(())
+}
+
+type Result<T> = std::result::Result<T, Box<dyn Error + Sync + Send>>;
+
+struct Server<'c> {
+    connection: &'c Connection,
+    workspace: Workspace,
+    // Documents that were last sent diagnostics, so closing one clears them.
+    published: Vec<PathBuf>,
+}
+
+impl Server<'_> {
+    fn request(&mut self, request: Request) -> Result<()> {
+        let response = match request.method.as_str() {
+            HoverRequest::METHOD => {
+                self.handle(request, |server, params: lsp_types::HoverParams| {
+                    let position = params.text_document_position_params;
+                    server.hover(&position.text_document.uri, position.position)
+                })
+            }
+            GotoDefinition::METHOD => self.handle(
+                request,
+                |server, params: lsp_types::GotoDefinitionParams| {
+                    let position = params.text_document_position_params;
+                    server.definition(&position.text_document.uri, position.position)
+                },
+            ),
+            References::METHOD => {
+                self.handle(request, |server, params: lsp_types::ReferenceParams| {
+                    let position = params.text_document_position;
+                    server.references(
+                        &position.text_document.uri,
+                        position.position,
+                        params.context.include_declaration,
+                    )
+                })
+            }
+            DocumentSymbolRequest::METHOD => self.handle(
+                request,
+                |server, params: lsp_types::DocumentSymbolParams| {
+                    server.symbols(&params.text_document.uri)
+                },
+            ),
+            Completion::METHOD => {
+                self.handle(request, |server, params: lsp_types::CompletionParams| {
+                    let position = params.text_document_position;
+                    server.completion(&position.text_document.uri, position.position)
+                })
+            }
+            Rename::METHOD => self.handle(request, |server, params: lsp_types::RenameParams| {
+                let position = params.text_document_position;
+                server.rename(
+                    &position.text_document.uri,
+                    position.position,
+                    &params.new_name,
+                )
+            }),
+            Formatting::METHOD => self.handle(
+                request,
+                |server, params: lsp_types::DocumentFormattingParams| {
+                    server.format(&params.text_document.uri, None)
+                },
+            ),
+            RangeFormatting::METHOD => self.handle(
+                request,
+                |server, params: lsp_types::DocumentRangeFormattingParams| {
+                    server.format(&params.text_document.uri, Some(params.range))
+                },
+            ),
+            _ => Response::new_err(
+                request.id,
+                ErrorCode::MethodNotFound as i32,
+                format!("elp-lsp doesn't handle {}", request.method),
+            ),
+        };
+        self.connection.sender.send(Message::Response(response))?;
+        Ok(())
+    }
+
+    fn handle<P: DeserializeOwned, R: Serialize>(
+        &mut self,
+        request: Request,
+        handler: impl FnOnce(&mut Self, P) -> std::result::Result<R, String>,
+    ) -> Response {
+        let id: RequestId = request.id;
+        match serde_json::from_value(request.params) {
+            Ok(params) => match handler(self, params) {
+                Ok(result) => Response::new_ok(id, result),
+                Err(message) => Response::new_err(id, ErrorCode::RequestFailed as i32, message),
+            },
+            Err(err) => Response::new_err(id, ErrorCode::InvalidParams as i32, err.to_string()),
+        }
+    }
+
+    fn notification(&mut self, notification: Notification) -> Result<()> {
+        match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params: lsp_types::DidOpenTextDocumentParams =
+                    serde_json::from_value(notification.params)?;
+                if let Some(path) = path(&params.text_document.uri) {
+                    self.workspace.open(path, params.text_document.text);
+                }
+            }
+            DidChangeTextDocument::METHOD => {
+                let params: lsp_types::DidChangeTextDocumentParams =
+                    serde_json::from_value(notification.params)?;
+                // With full sync the last change is the whole document.
+                if let (Some(path), Some(change)) = (
+                    path(&params.text_document.uri),
+                    params.content_changes.into_iter().last(),
+                ) {
+                    self.workspace.open(path, change.text);
+                }
+            }
+            DidCloseTextDocument::METHOD => {
+                let params: lsp_types::DidCloseTextDocumentParams =
+                    serde_json::from_value(notification.params)?;
+                if let Some(path) = path(&params.text_document.uri) {
+                    self.workspace.close(&path);
+                }
+            }
+            _ => return Ok(()),
+        }
+        self.publish()
+    }
+
+    // Any change can fix or break the files importing it, so every open document is checked again.
+    fn publish(&mut self) -> Result<()> {
+        let analysis = Analysis::new(&self.workspace);
+        let mut published = vec![];
+        for path in self.workspace.documents() {
+            let Some(file) = analysis.file(path) else {
+                continue;
+            };
+            let diagnostics = analysis
+                .diagnostics(path)
+                .into_iter()
+                .map(|problem| Diagnostic {
+                    range: file
+                        .lines
+                        .range(&file.source, problem.range.unwrap_or(0..0)),
+                    severity: Some(DiagnosticSeverity::ERROR),
+                    source: Some("elp".into()),
+                    message: problem.message,
+                    ..Default::default()
+                })
+                .collect();
+            self.send_diagnostics(path, diagnostics)?;
+            published.push(path.clone());
+        }
+        for path in &self.published {
+            if !published.contains(path) {
+                self.send_diagnostics(path, vec![])?;
+            }
+        }
+        self.published = published;
+        Ok(())
+    }
+
+    fn send_diagnostics(&self, path: &PathBuf, diagnostics: Vec<Diagnostic>) -> Result<()> {
+        let Ok(uri) = Url::from_file_path(path) else {
+            return Ok(());
+        };
+        let params = PublishDiagnosticsParams::new(uri, diagnostics, None);
+        self.connection
+            .sender
+            .send(Message::Notification(Notification::new(
+                PublishDiagnostics::METHOD.into(),
+                params,
+            )))?;
+        Ok(())
+    }
+
+    // The file a request is about and the byte offset of `position` in it.
+    fn locate(
+        &self,
+        analysis: &Analysis,
+        uri: &Url,
+        position: Position,
+    ) -> std::result::Result<(PathBuf, usize), String> {
+        let path = path(uri).ok_or_else(|| format!("{} isn't a file", uri))?;
+        let file = analysis
+            .file(&path)
+            .ok_or_else(|| format!("can't read {}", path.display()))?;
+        let offset = file.lines.offset(&file.source, position);
+        Ok((path, offset))
+    }
+
+    fn location(
+        &self,
+        analysis: &Analysis,
+        path: PathBuf,
+        range: Range<usize>,
+    ) -> Option<Location> {
+        let file = analysis.file(&path)?;
+        Some(Location::new(
+            Url::from_file_path(&path).ok()?,
+            file.lines.range(&file.source, range),
+        ))
+    }
+
+    fn hover(&self, uri: &Url, position: Position) -> std::result::Result<Option<Hover>, String> {
+        let analysis = Analysis::new(&self.workspace);
+        let (path, offset) = self.locate(&analysis, uri, position)?;
+        let file = analysis.file(&path).expect("located");
+        Ok(analysis
+            .hover(&path, offset)
+            .map(|(markdown, range)| Hover {
+                contents: HoverContents::Markup(MarkupContent {
+                    kind: MarkupKind::Markdown,
+                    value: markdown,
+                }),
+                range: Some(file.lines.range(&file.source, range)),
+            }))
+    }
+
+    fn definition(
+        &self,
+        uri: &Url,
+        position: Position,
+    ) -> std::result::Result<Option<GotoDefinitionResponse>, String> {
+        let analysis = Analysis::new(&self.workspace);
+        let (path, offset) = self.locate(&analysis, uri, position)?;
+        Ok(analysis
+            .definition(&path, offset)
+            .and_then(|(path, range)| self.location(&analysis, path, range))
+            .map(GotoDefinitionResponse::Scalar))
+    }
+
+    fn references(
+        &self,
+        uri: &Url,
+        position: Position,
+        include_declaration: bool,
+    ) -> std::result::Result<Option<Vec<Location>>, String> {
+        let analysis = Analysis::new(&self.workspace);
+        let (path, offset) = self.locate(&analysis, uri, position)?;
+        let locations: Vec<Location> = analysis
+            .references(&path, offset, include_declaration)
+            .into_iter()
+            .filter_map(|(path, range)| self.location(&analysis, path, range))
+            .collect();
+        Ok((!locations.is_empty()).then_some(locations))
+    }
+
+    fn symbols(&self, uri: &Url) -> std::result::Result<Option<DocumentSymbolResponse>, String> {
+        let analysis = Analysis::new(&self.workspace);
+        let (path, _) = self.locate(&analysis, uri, Position::default())?;
+        let file = analysis.file(&path).expect("located");
+        let Ok(index) = &file.index else {
+            return Ok(None);
+        };
+        let symbols = index
+            .top_level()
+            .iter()
+            .map(|&symbol| {
+                let children: Vec<DocumentSymbol> = match index.symbols[symbol].kind.is_type() {
+                    true => index
+                        .members(&index.symbols[symbol].name)
+                        .map(|member| {
+                            document_symbol(index, &file.lines, &file.source, member, None)
+                        })
+                        .collect(),
+                    false => vec![],
+                };
+                document_symbol(
+                    index,
+                    &file.lines,
+                    &file.source,
+                    symbol,
+                    (!children.is_empty()).then_some(children),
+                )
+            })
+            .collect();
+        Ok(Some(DocumentSymbolResponse::Nested(symbols)))
+    }
+
+    fn completion(
+        &self,
+        uri: &Url,
+        position: Position,
+    ) -> std::result::Result<Option<CompletionResponse>, String> {
+        let analysis = Analysis::new(&self.workspace);
+        let (path, offset) = self.locate(&analysis, uri, position)?;
+        let items = analysis
+            .completions(&path, offset)
+            .into_iter()
+            .map(|completion| CompletionItem {
+                label: completion.label,
+                kind: Some(match completion.kind {
+                    Some(kind) => completion_kind(kind),
+                    None => CompletionItemKind::KEYWORD,
+                }),
+                detail: completion.detail,
+                ..Default::default()
+            })
+            .collect();
+        Ok(Some(CompletionResponse::Array(items)))
+    }
+
+    fn rename(
+        &self,
+        uri: &Url,
+        position: Position,
+        new_name: &str,
+    ) -> std::result::Result<Option<WorkspaceEdit>, String> {
+        let analysis = Analysis::new(&self.workspace);
+        let (path, offset) = self.locate(&analysis, uri, position)?;
+        let mut changes = HashMap::new();
+        for (path, ranges) in analysis.rename(&path, offset, new_name)? {
+            let (Some(file), Ok(uri)) = (analysis.file(&path), Url::from_file_path(&path)) else {
+                continue;
+            };
+            let edits = ranges
+                .into_iter()
+                .map(|range| TextEdit::new(file.lines.range(&file.source, range), new_name.into()))
+                .collect();
+            changes.insert(uri, edits);
+        }
+        Ok(Some(WorkspaceEdit::new(changes)))
+    }
+
+    // Formatting replaces the whole document, the formatter doesn't keep track of what it moved.
+    fn format(
+        &self,
+        uri: &Url,
+        range: Option<lsp_types::Range>,
+    ) -> std::result::Result<Option<Vec<TextEdit>>, String> {
+        let analysis = Analysis::new(&self.workspace);
+        let (path, _) = self.locate(&analysis, uri, Position::default())?;
+        let file = analysis.file(&path).expect("located");
+        let formatted = match range {
+            Some(range) => {
+                let range = file.lines.offset(&file.source, range.start)
+                    ..file.lines.offset(&file.source, range.end);
+                format_range(&file.source, range)
+            }
+            None => format_source(&file.source),
+        }
+        .map_err(|err| err.to_string())?;
+
+        if formatted == file.source {
+            return Ok(Some(vec![]));
+        }
+        Ok(Some(vec![TextEdit::new(
+            file.lines.range(&file.source, 0..file.source.len()),
+            formatted,
+        )]))
+    }
+}
+
+fn path(uri: &Url) -> Option<PathBuf> {
+    match uri.scheme() {
+        "file" => uri.to_file_path().ok(),
+        _ => None,
+    }
+}
+
+#[allow(deprecated)]
+fn document_symbol(
+    index: &Index,
+    lines: &LineIndex,
+    source: &str,
+    symbol: usize,
+    children: Option<Vec<DocumentSymbol>>,
+) -> DocumentSymbol {
+    let symbol = &index.symbols[symbol];
+    DocumentSymbol {
+        name: symbol.name.clone(),
+        detail: Some(symbol.signature.clone()),
+        kind: symbol_kind(symbol.kind),
+        tags: None,
+        // Deprecated in favour of `tags`, but it still has to be there.
+        deprecated: None,
+        range: lines.range(source, symbol.range.clone()),
+        selection_range: lines.range(source, symbol.name_range.clone()),
+        children,
+    }
+}
+
+fn symbol_kind(kind: SymbolKind) -> lsp_types::SymbolKind {
+    match kind {
+        SymbolKind::Object => lsp_types::SymbolKind::STRUCT,
+        SymbolKind::Enum => lsp_types::SymbolKind::ENUM,
+        SymbolKind::Interface => lsp_types::SymbolKind::INTERFACE,
+        SymbolKind::Component => lsp_types::SymbolKind::CLASS,
+        SymbolKind::Function => lsp_types::SymbolKind::FUNCTION,
+        SymbolKind::Method => lsp_types::SymbolKind::METHOD,
+        SymbolKind::Field => lsp_types::SymbolKind::FIELD,
+        SymbolKind::Variant => lsp_types::SymbolKind::ENUM_MEMBER,
+        SymbolKind::Global => lsp_types::SymbolKind::CONSTANT,
+        SymbolKind::Local | SymbolKind::Parameter => lsp_types::SymbolKind::VARIABLE,
+        SymbolKind::Import => lsp_types::SymbolKind::MODULE,
+    }
+}
+
+fn completion_kind(kind: SymbolKind) -> CompletionItemKind {
+    match kind {
+        SymbolKind::Object => CompletionItemKind::STRUCT,
+        SymbolKind::Enum => CompletionItemKind::ENUM,
+        SymbolKind::Interface => CompletionItemKind::INTERFACE,
+        SymbolKind::Component => CompletionItemKind::CLASS,
+        SymbolKind::Function => CompletionItemKind::FUNCTION,
+        SymbolKind::Method => CompletionItemKind::METHOD,
+        SymbolKind::Field => CompletionItemKind::FIELD,
+        SymbolKind::Variant => CompletionItemKind::ENUM_MEMBER,
+        SymbolKind::Global => CompletionItemKind::CONSTANT,
+        SymbolKind::Local | SymbolKind::Parameter => CompletionItemKind::VARIABLE,
+        SymbolKind::Import => CompletionItemKind::MODULE,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, thread};
+
+    use super::*;
+    use lsp_server::Connection;
+    use pretty_assertions::assert_eq;
+    use serde_json::{json, Value};
+
+    // An editor talking to the server over an in memory connection.
+    struct Client {
+        connection: Connection,
+        server: Option<thread::JoinHandle<()>>,
+        next_id: i32,
+    }
+
+    impl Client {
+        fn start() -> Self {
+            let (server, connection) = Connection::memory();
+            let server = thread::spawn(move || run(server).unwrap());
+            let mut client = Self {
+                connection,
+                server: Some(server),
+                next_id: 0,
+            };
+            client.request("initialize", json!({ "capabilities": {} }));
+            client.notify("initialized", json!({}));
+            client
+        }
+
+        fn request(&mut self, method: &str, params: Value) -> Value {
+            self.next_id += 1;
+            let request = Request::new(self.next_id.into(), method.into(), params);
+            self.connection.sender.send(request.into()).unwrap();
+            loop {
+                match self.connection.receiver.recv().unwrap() {
+                    Message::Response(response) => {
+                        return match response.error {
+                            Some(error) => json!({ "error": error.message }),
+                            None => response.result.unwrap_or(Value::Null),
+                        }
+                    }
+                    _ => continue,
+                }
+            }
+        }
+
+        fn notify(&self, method: &str, params: Value) {
+            let notification = Notification::new(method.into(), params);
+            self.connection.sender.send(notification.into()).unwrap();
+        }
+
+        // The next diagnostics published, as `line: message`.
+        fn diagnostics(&self) -> (Url, Vec<String>) {
+            loop {
+                let Message::Notification(notification) = self.connection.receiver.recv().unwrap()
+                else {
+                    continue;
+                };
+                if notification.method != PublishDiagnostics::METHOD {
+                    continue;
+                }
+                let params: PublishDiagnosticsParams =
+                    serde_json::from_value(notification.params).unwrap();
+                let messages = params
+                    .diagnostics
+                    .into_iter()
+                    .map(|d| format!("{}: {}", d.range.start.line, d.message))
+                    .collect();
+                return (params.uri, messages);
+            }
+        }
+
+        fn shutdown(mut self) {
+            self.request("shutdown", Value::Null);
+            self.notify("exit", Value::Null);
+            self.server.take().unwrap().join().unwrap();
+        }
+    }
+
+    fn position(uri: &Url, line: u32, character: u32) -> Value {
+        json!({
+            "textDocument": { "uri": uri },
+            "position": { "line": line, "character": character },
+        })
+    }
+
+    #[test]
+    fn answers_an_editing_session() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("Package.elp"), "").unwrap();
+        fs::write(
+            dir.path().join("math.elp"),
+            "/// Twice `n`.\nexport fn double(n int) -> int {\n\treturn n\n}\n",
+        )
+        .unwrap();
+        let main = Url::from_file_path(dir.path().join("main.elp")).unwrap();
+        let math = Url::from_file_path(dir.path().join("math.elp")).unwrap();
+        let source = "import { double } from \"$math\"\n\nfn main {\n\tconst x = double(2)\n\tmissing(x)\n}\n";
+
+        let mut client = Client::start();
+        client.notify(
+            "textDocument/didOpen",
+            json!({
+                "textDocument": {
+                    "uri": main,
+                    "languageId": "elp",
+                    "version": 1,
+                    "text": source,
+                },
+            }),
+        );
+        assert_eq!(
+            client.diagnostics(),
+            (main.clone(), vec!["4: `missing` isn't defined".to_string()])
+        );
+
+        let hover = client.request("textDocument/hover", position(&main, 3, 12));
+        assert_eq!(
+            hover["contents"]["value"],
+            "```elp\nfn double(n int) -> int\n```\n\nTwice `n`."
+        );
+        let definition = client.request("textDocument/definition", position(&main, 3, 12));
+        assert_eq!(
+            definition,
+            json!({
+                "uri": math,
+                "range": {
+                    "start": { "line": 1, "character": 10 },
+                    "end": { "line": 1, "character": 16 },
+                },
+            })
+        );
+
+        let mut params = position(&main, 3, 12);
+        params["context"] = json!({ "includeDeclaration": false });
+        let references = client.request("textDocument/references", params);
+        let lines: Vec<(String, u64)> = references
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|location| {
+                let uri = location["uri"].as_str().unwrap();
+                let file = uri.rsplit('/').next().unwrap().to_string();
+                (file, location["range"]["start"]["line"].as_u64().unwrap())
+            })
+            .collect();
+        assert_eq!(
+            lines,
+            [("main.elp".to_string(), 0), ("main.elp".to_string(), 3)]
+        );
+
+        let mut params = position(&main, 3, 8);
+        params["newName"] = json!("y");
+        let rename = client.request("textDocument/rename", params);
+        assert_eq!(
+            rename["changes"][main.as_str()].as_array().unwrap().len(),
+            2
+        );
+
+        let symbols = client.request(
+            "textDocument/documentSymbol",
+            json!({ "textDocument": { "uri": math } }),
+        );
+        assert_eq!(symbols[0]["name"], "double");
+
+        client.notify(
+            "textDocument/didChange",
+            json!({
+                "textDocument": { "uri": main, "version": 2 },
+                "contentChanges": [{ "text": "fn main {\n  const x = 1\n}\n" }],
+            }),
+        );
+        assert_eq!(client.diagnostics(), (main.clone(), vec![]));
+        let formatting = client.request(
+            "textDocument/formatting",
+            json!({
+                "textDocument": { "uri": main },
+                "options": { "tabSize": 4, "insertSpaces": false },
+            }),
+        );
+        assert_eq!(formatting[0]["newText"], "fn main {\n\tconst x = 1\n}\n");
+
+        let unknown = client.request("workspace/symbol", json!({ "query": "" }));
+        assert_eq!(
+            unknown,
+            json!({ "error": "elp-lsp doesn't handle workspace/symbol" })
+        );
+        client.shutdown();
+    }
+}
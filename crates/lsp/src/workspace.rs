@@ -0,0 +1,91 @@
+// What the server knows about the files it works on. Open documents are kept in the database with
+// whatever the editor has sent, everything else is read from disk when it's needed so imports of
+// files that aren't open still resolve. The database lives as long as the server does, so only
+// what an edit affects is worked out again.
+
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use elp_db::{
+    database::Database,
+    queries::{package_root, EXTENSIONS},
+};
+
+#[derive(Default, Clone)]
+pub struct Workspace {
+    documents: HashSet<PathBuf>,
+    db: Database,
+}
+
+impl Workspace {
+    pub fn open(&mut self, path: PathBuf, source: String) {
+        self.db.set_source(&path, source);
+        self.documents.insert(path);
+    }
+
+    pub fn close(&mut self, path: &Path) {
+        self.documents.remove(path);
+        self.db.reload(path);
+    }
+
+    pub fn documents(&self) -> impl Iterator<Item = &PathBuf> {
+        self.documents.iter()
+    }
+
+    pub fn db(&self) -> &Database {
+        &self.db
+    }
+
+    pub fn source(&self, path: &Path) -> Option<String> {
+        self.db.source(path).as_ref().clone()
+    }
+
+    pub fn root(&self, path: &Path) -> PathBuf {
+        package_root(path)
+    }
+
+    pub fn resolve(&self, from: &Path, module_path: &str) -> Option<PathBuf> {
+        self.db.resolve_path(from, module_path)
+    }
+
+    // Every source file in the package `path` is part of, open or not.
+    pub fn package_files(&self, path: &Path) -> Vec<PathBuf> {
+        let root = self.root(path);
+        let mut files = source_files(&root);
+        for document in &self.documents {
+            if document.starts_with(&root) && !files.contains(document) {
+                files.push(document.clone());
+            }
+        }
+        files.sort();
+        files
+    }
+}
+
+// The same files `elp` itself looks at, skipping build output and hidden directories.
+fn source_files(root: &Path) -> Vec<PathBuf> {
+    let mut files = vec![];
+    let Ok(entries) = fs::read_dir(root) else {
+        return files;
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if path.is_dir() {
+            if !name.starts_with('.') && name != "build" && name != "target" {
+                files.extend(source_files(&path));
+            }
+        } else if path
+            .extension()
+            .is_some_and(|extension| EXTENSIONS.iter().any(|e| extension == *e))
+        {
+            files.push(path);
+        }
+    }
+    files
+}
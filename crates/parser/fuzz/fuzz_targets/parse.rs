@@ -0,0 +1,11 @@
+// Any text at all through the parser, the CST and the AST. None of them should panic, other than
+// in the ways listed in `elp_parser::fuzzing::KNOWN_BUGS`.
+
+#![no_main]
+
+use elp_parser::fuzzing::lower;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|source: &str| {
+    lower(source);
+});
@@ -0,0 +1,19 @@
+// Programs made up from the grammar, with the fuzzer's input making the choices, formatted and
+// parsed again. Formatting has to keep what they mean and give the same text a second time.
+
+#![no_main]
+
+use std::sync::OnceLock;
+
+use elp_parser::fuzzing::{
+    check_round_trip,
+    generator::{Bytes, Generator},
+};
+use libfuzzer_sys::fuzz_target;
+
+static GENERATOR: OnceLock<Generator> = OnceLock::new();
+
+fuzz_target!(|choices: &[u8]| {
+    let generator = GENERATOR.get_or_init(Generator::new);
+    check_round_trip(&generator.generate("module", &mut Bytes(choices)));
+});
@@ -0,0 +1,129 @@
+use crate::cst::{
+    component::{CSTComponentDef, CSTFunctionComponentCall},
+    trivia::CSTNode,
+};
+
+use super::{
+    block::ASTBlock,
+    elp_type::ASTElpType,
+    function::{
+        ASTFunctionArguments, ASTFunctionCall, ASTFunctionHeaderDef, ASTFunctionReturnType,
+    },
+    traits::FromCST,
+    variable_access::ASTVariableAccess,
+};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ASTComponentDef<'a> {
+    pub span: &'a pest::Span<'a>,
+    pub name: ASTVariableAccess<'a>,
+    pub generics: Vec<ASTElpType<'a>>,
+    pub arguments: Option<ASTFunctionArguments<'a>>,
+    pub return_type: Option<ASTFunctionReturnType<'a>>,
+    pub block: Box<ASTBlock<'a>>,
+    // From the `///` comments above it.
+    pub docs: Option<String>,
+}
+
+impl<'a> ASTComponentDef<'a> {
+    // A component is called like a function, this is that function's signature.
+    pub fn header(&self) -> ASTFunctionHeaderDef<'a> {
+        ASTFunctionHeaderDef {
+            span: self.span,
+            pointer_semantics: None,
+            name: self.name.clone(),
+            generics: self.generics.clone(),
+            arguments: self.arguments.clone().unwrap_or(ASTFunctionArguments {
+                span: self.span,
+                arguments: vec![],
+            }),
+            return_type: self.return_type.clone().unwrap_or(ASTFunctionReturnType {
+                span: self.span,
+                type_annotations: vec![],
+            }),
+            docs: self.docs.clone(),
+        }
+    }
+}
+
+impl<'a> FromCST<'a, CSTComponentDef<'a>> for ASTComponentDef<'a> {
+    fn from_cst(cst: &'a CSTComponentDef<'a>) -> Self {
+        Self {
+            span: &cst.span,
+            name: ASTVariableAccess::from_cst(&cst.name),
+            generics: match &cst.generics {
+                Some(generic) => generic.params.iter().map(ASTElpType::from_cst).collect(),
+                None => vec![],
+            },
+            arguments: cst.arguments.as_ref().map(ASTFunctionArguments::from_cst),
+            return_type: cst
+                .return_type
+                .as_ref()
+                .map(ASTFunctionReturnType::from_cst),
+            block: Box::new(ASTBlock::from_cst(&cst.block)),
+            docs: cst.doc_comment(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ASTFunctionComponentCall<'a> {
+    pub span: &'a pest::Span<'a>,
+    pub call: ASTFunctionCall<'a>,
+    pub children: ASTBlock<'a>,
+}
+
+impl<'a> FromCST<'a, CSTFunctionComponentCall<'a>> for ASTFunctionComponentCall<'a> {
+    fn from_cst(cst: &'a CSTFunctionComponentCall<'a>) -> Self {
+        Self {
+            span: &cst.span,
+            call: ASTFunctionCall::from_cst(&cst.call),
+            children: ASTBlock::from_cst(&cst.block),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ast::{expression::ASTExpression, function::ASTFunctionCallName},
+        cst::expression::CSTExpression,
+        parser::{ElpParser, Rule},
+    };
+    use from_pest::FromPest;
+    use pest::Parser;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn component_def_from_cst() {
+        let expression_str = "component Greeting(name string) { return Text(name) }";
+        let mut pairs = ElpParser::parse(Rule::component_def, expression_str).unwrap();
+        let cst = CSTComponentDef::from_pest(&mut pairs).unwrap();
+        let ast = ASTComponentDef::from_cst(&cst);
+
+        assert_eq!(ast.name.names, vec!["Greeting".to_string()]);
+        assert_eq!(ast.arguments.unwrap().arguments[0].name, "name");
+        assert!(matches!(
+            ast.block.expressions.as_slice(),
+            [ASTExpression::FunctionReturnValue(_)]
+        ));
+    }
+
+    #[test]
+    fn component_call_from_cst() {
+        let expression_str = "Row(columns = 2) { Text(\"a\") Text(\"b\") }";
+        let mut pairs = ElpParser::parse(Rule::expression, expression_str).unwrap();
+        let cst = CSTExpression::from_pest(&mut pairs).unwrap();
+        let ASTExpression::FunctionComponentCall(ast) = ASTExpression::from_cst(&cst) else {
+            panic!("expected a component call");
+        };
+
+        let ASTFunctionCallName::VariableAccess(name) = &ast.call.name else {
+            panic!("expected a named call");
+        };
+        assert_eq!(name.names, vec!["Row".to_string()]);
+        assert_eq!(ast.call.arguments.len(), 1);
+        assert_eq!(ast.children.expressions.len(), 2);
+    }
+}
@@ -1,6 +1,9 @@
 use pest::Span;
 
-use crate::cst::r#enum::{CSTEnum, CSTEnumMember};
+use crate::cst::{
+    r#enum::{CSTEnum, CSTEnumMember},
+    trivia::CSTNode,
+};
 
 use super::{elp_type::ASTElpType, traits::FromCST};
 
@@ -10,6 +13,8 @@ pub struct ASTEnum<'a> {
     pub name: String,
     pub members: Vec<ASTEnumMember<'a>>,
     pub implements: Vec<ASTElpType<'a>>,
+    // From the `///` comments above it.
+    pub docs: Option<String>,
 }
 
 impl<'a> FromCST<'a, CSTEnum<'a>> for ASTEnum<'a> {
@@ -22,6 +27,7 @@ impl<'a> FromCST<'a, CSTEnum<'a>> for ASTEnum<'a> {
                 Some(implements) => implements.types.iter().map(ASTElpType::from_cst).collect(),
                 None => vec![],
             },
+            docs: cst.doc_comment(),
         }
     }
 }
@@ -82,6 +88,7 @@ mod tests {
                     parameters: vec![]
                 }],
                 implements: vec![],
+                docs: None,
             }
         )
     }
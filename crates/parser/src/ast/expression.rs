@@ -4,14 +4,20 @@ use crate::cst::expression::CSTExpression;
 
 use super::{
     block::ASTBlock,
+    component::{ASTComponentDef, ASTFunctionComponentCall},
     elp_type::{ASTElpType, ASTPointerSemantics},
     export::ASTExport,
+    external::ASTExternal,
     function::{ASTFunctionCall, ASTFunctionDef, ASTFunctionHeaderDef, ASTFunctionReturnValue},
     import::ASTImport,
+    interface::ASTInterface,
     number::ASTNumber,
     object::ASTObject,
+    precomp::ASTPrecomp,
     r#enum::ASTEnum,
+    r#macro::ASTMacro,
     string::ASTString,
+    style::ASTStyleSheet,
     traits::FromCST,
     value_assignment::ASTValueAssignment,
     variable_access::ASTVariableAccess,
@@ -22,21 +28,27 @@ use super::{
 #[derive(Debug, PartialEq, Clone)]
 pub enum ASTExpression<'a> {
     Block(Box<ASTBlock<'a>>),
+    Component(Box<ASTComponentDef<'a>>),
     ElpType(Box<ASTElpType<'a>>),
     Enum(Box<ASTEnum<'a>>),
     Number(Box<ASTNumber<'a>>),
     Export(Box<ASTExport<'a>>),
+    External(Box<ASTExternal<'a>>),
     FunctionDef(Box<ASTFunctionDef<'a>>),
     FunctionCall(Box<ASTFunctionCall<'a>>),
+    FunctionComponentCall(Box<ASTFunctionComponentCall<'a>>),
     FunctionHeaderDef(Box<ASTFunctionHeaderDef<'a>>),
     FunctionReturnValue(Box<ASTFunctionReturnValue<'a>>),
     Import(Box<ASTImport<'a>>),
-    //    Interface(Box<Interface>),
+    Interface(Box<ASTInterface<'a>>),
+    Macro(Box<ASTMacro<'a>>),
     //    Match(Box<MatchTree>),
     //    Number(Box<Number>),
     Object(Box<ASTObject<'a>>),
     PointerSemantics(Box<ASTPointerSemantics>),
+    Precomp(Box<ASTPrecomp<'a>>),
     String(Box<ASTString<'a>>),
+    StyleSheet(Box<ASTStyleSheet<'a>>),
     ValueAssignment(Box<ASTValueAssignment<'a>>),
     VariableAccess(Box<ASTVariableAccess<'a>>),
     VariableAssignment(Box<ASTVariableAssignment<'a>>),
@@ -60,10 +72,23 @@ impl<'a> FromCST<'a, CSTExpression<'a>> for ASTExpression<'a> {
             CSTExpression::Import(import) => {
                 ASTExpression::Import(Box::new(ASTImport::from_cst(import)))
             }
+            CSTExpression::Interface(interface) => {
+                ASTExpression::Interface(Box::new(ASTInterface::from_cst(interface)))
+            }
+            CSTExpression::Macro(cst) => ASTExpression::Macro(Box::new(ASTMacro::from_cst(cst))),
+            CSTExpression::Precomp(cst) => {
+                ASTExpression::Precomp(Box::new(ASTPrecomp::from_cst(cst)))
+            }
             CSTExpression::PointerSemantics(cst) => {
                 ASTExpression::PointerSemantics(Box::new(ASTPointerSemantics::from_cst(cst)))
             }
             CSTExpression::Export(cst) => ASTExpression::Export(Box::new(ASTExport::from_cst(cst))),
+            CSTExpression::ExternalSymbol(cst) => {
+                ASTExpression::External(Box::new(ASTExternal::from_cst(&**cst)))
+            }
+            CSTExpression::ExternalBlock(cst) => {
+                ASTExpression::External(Box::new(ASTExternal::from_cst(&**cst)))
+            }
             CSTExpression::ValueAssignment(cst) => {
                 ASTExpression::ValueAssignment(Box::new(ASTValueAssignment::from_cst(cst)))
             }
@@ -77,12 +102,21 @@ impl<'a> FromCST<'a, CSTExpression<'a>> for ASTExpression<'a> {
                 ASTExpression::VariableDeclaration(Box::new(ASTVariableDeclaration::from_cst(cst)))
             }
             CSTExpression::String(cst) => ASTExpression::String(Box::new(ASTString::from_cst(cst))),
+            CSTExpression::StyleSheet(cst) => {
+                ASTExpression::StyleSheet(Box::new(ASTStyleSheet::from_cst(&**cst)))
+            }
             CSTExpression::FunctionDef(func) => {
                 ASTExpression::FunctionDef(Box::new(ASTFunctionDef::from_cst(func)))
             }
             CSTExpression::FunctionCall(call) => {
                 ASTExpression::FunctionCall(Box::new(ASTFunctionCall::from_cst(call)))
             }
+            CSTExpression::Component(component) => {
+                ASTExpression::Component(Box::new(ASTComponentDef::from_cst(component)))
+            }
+            CSTExpression::FunctionComponentCall(call) => ASTExpression::FunctionComponentCall(
+                Box::new(ASTFunctionComponentCall::from_cst(call)),
+            ),
             CSTExpression::FunctionHeaderDef(header) => {
                 ASTExpression::FunctionHeaderDef(Box::new(ASTFunctionHeaderDef::from_cst(header)))
             }
@@ -163,7 +197,8 @@ mod tests {
                 span: &pest::Span::new("", 0, 0).unwrap(),
                 name: "test".into(),
                 members: vec![],
-                implements: vec![]
+                implements: vec![],
+                docs: None,
             }))
         );
     }
@@ -0,0 +1,125 @@
+use crate::cst::external::{CSTExternalAbi, CSTExternalBlock, CSTExternalItem, CSTExternalSymbol};
+
+use super::{function::ASTFunctionHeaderDef, object::ASTObject, traits::FromCST};
+
+// What the ABI is called is all that's known here, lowering checks it's one there is and
+// works out how calls through it are made.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ASTExternalAbi<'a> {
+    pub span: &'a pest::Span<'a>,
+    pub name: String,
+}
+
+impl<'a> FromCST<'a, CSTExternalAbi<'a>> for ASTExternalAbi<'a> {
+    fn from_cst(cst: &'a CSTExternalAbi<'a>) -> Self {
+        Self {
+            span: &cst.span,
+            name: cst.name.value.clone(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ASTExternalItem<'a> {
+    Function(ASTFunctionHeaderDef<'a>),
+    Object(ASTObject<'a>),
+}
+
+impl<'a> FromCST<'a, CSTExternalItem<'a>> for ASTExternalItem<'a> {
+    fn from_cst(cst: &'a CSTExternalItem<'a>) -> Self {
+        match cst {
+            CSTExternalItem::Function(function) => {
+                ASTExternalItem::Function(ASTFunctionHeaderDef::from_cst(function))
+            }
+            CSTExternalItem::Object(object) => ASTExternalItem::Object(ASTObject::from_cst(object)),
+        }
+    }
+}
+
+// `external fn ..` and `external { .. }` are the same thing to everything after the parser, items
+// defined outside elp that are called the way `abi` says, C's when there isn't one.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ASTExternal<'a> {
+    pub span: &'a pest::Span<'a>,
+    pub abi: Option<ASTExternalAbi<'a>>,
+    pub items: Vec<ASTExternalItem<'a>>,
+}
+
+impl<'a> FromCST<'a, CSTExternalSymbol<'a>> for ASTExternal<'a> {
+    fn from_cst(cst: &'a CSTExternalSymbol<'a>) -> Self {
+        Self {
+            span: &cst.span,
+            abi: cst.abi.as_ref().map(ASTExternalAbi::from_cst),
+            items: vec![ASTExternalItem::from_cst(&cst.item)],
+        }
+    }
+}
+
+impl<'a> FromCST<'a, CSTExternalBlock<'a>> for ASTExternal<'a> {
+    fn from_cst(cst: &'a CSTExternalBlock<'a>) -> Self {
+        Self {
+            span: &cst.span,
+            abi: cst.abi.as_ref().map(ASTExternalAbi::from_cst),
+            items: cst.items.iter().map(ASTExternalItem::from_cst).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ast::{expression::ASTExpression, module::ASTModule},
+        cst::CSTModule,
+        parser::{ElpParser, Rule},
+    };
+    use from_pest::FromPest;
+    use pest::Parser;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn external_cst_to_ast() {
+        let source = "external fn puts(text string) -> int32
+
+external \"ObjC\" {
+    fn OBJC_msgSend<T: OBJC_Obj + Send>(T, OBJC_Selector) -> T
+    object OBJC_Selector {
+        .name string,
+    }
+}
+";
+        let mut pairs = ElpParser::parse(Rule::module, source).unwrap();
+        let cst = CSTModule::from_pest(&mut pairs).unwrap();
+        let ast = ASTModule::from_cst(&cst);
+
+        let externals = ast
+            .expressions
+            .iter()
+            .map(|expression| match expression {
+                ASTExpression::External(external) => (
+                    external.abi.as_ref().map(|abi| abi.name.as_str()),
+                    external
+                        .items
+                        .iter()
+                        .map(|item| match item {
+                            ASTExternalItem::Function(function) => function.name.names.join("."),
+                            ASTExternalItem::Object(object) => object.name.clone(),
+                        })
+                        .collect::<Vec<_>>(),
+                ),
+                other => panic!("expected an external, got {:?}", other),
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            externals,
+            vec![
+                (None, vec!["puts".to_string()]),
+                (
+                    Some("ObjC"),
+                    vec!["OBJC_msgSend".to_string(), "OBJC_Selector".to_string()]
+                ),
+            ]
+        );
+    }
+}
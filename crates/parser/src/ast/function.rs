@@ -1,6 +1,9 @@
-use crate::cst::function::{
-    CSTFunctionArgument, CSTFunctionArguments, CSTFunctionCall, CSTFunctionCallName,
-    CSTFunctionDef, CSTFunctionHeaderDef, CSTFunctionReturnType, CSTFunctionReturnValue,
+use crate::cst::{
+    function::{
+        CSTFunctionArgument, CSTFunctionArguments, CSTFunctionCall, CSTFunctionCallName,
+        CSTFunctionDef, CSTFunctionHeaderDef, CSTFunctionReturnType, CSTFunctionReturnValue,
+    },
+    trivia::CSTNode,
 };
 
 use super::{
@@ -94,6 +97,29 @@ pub struct ASTFunctionDef<'a> {
     pub arguments: Option<ASTFunctionArguments<'a>>,
     pub return_type: Option<ASTFunctionReturnType<'a>>,
     pub block: Box<ASTBlock<'a>>,
+    // From the `///` comments above it.
+    pub docs: Option<String>,
+}
+
+impl<'a> ASTFunctionDef<'a> {
+    // The signature on its own, missing arguments and return types are empty lists.
+    pub fn header(&self) -> ASTFunctionHeaderDef<'a> {
+        ASTFunctionHeaderDef {
+            span: self.span,
+            pointer_semantics: None,
+            name: self.name.clone(),
+            generics: self.generics.clone(),
+            arguments: self.arguments.clone().unwrap_or(ASTFunctionArguments {
+                span: self.span,
+                arguments: vec![],
+            }),
+            return_type: self.return_type.clone().unwrap_or(ASTFunctionReturnType {
+                span: self.span,
+                type_annotations: vec![],
+            }),
+            docs: self.docs.clone(),
+        }
+    }
 }
 
 impl<'a> FromCST<'a, CSTFunctionDef<'a>> for ASTFunctionDef<'a> {
@@ -111,6 +137,7 @@ impl<'a> FromCST<'a, CSTFunctionDef<'a>> for ASTFunctionDef<'a> {
                 .as_ref()
                 .map(ASTFunctionReturnType::from_cst),
             block: Box::new(ASTBlock::from_cst(&cst.block)),
+            docs: cst.doc_comment(),
         }
     }
 }
@@ -123,6 +150,8 @@ pub struct ASTFunctionHeaderDef<'a> {
     pub generics: Vec<ASTElpType<'a>>,
     pub arguments: ASTFunctionArguments<'a>,
     pub return_type: ASTFunctionReturnType<'a>,
+    // From the `///` comments above it.
+    pub docs: Option<String>,
 }
 
 impl<'a> FromCST<'a, CSTFunctionHeaderDef<'a>> for ASTFunctionHeaderDef<'a> {
@@ -140,6 +169,7 @@ impl<'a> FromCST<'a, CSTFunctionHeaderDef<'a>> for ASTFunctionHeaderDef<'a> {
             },
             arguments: ASTFunctionArguments::from_cst(&cst.arguments),
             return_type: ASTFunctionReturnType::from_cst(&cst.return_type),
+            docs: cst.doc_comment(),
         }
     }
 }
@@ -331,7 +361,8 @@ mod tests {
                             })))
                         }
                     ))]
-                })
+                }),
+                docs: None,
             }
         )
     }
@@ -436,6 +467,7 @@ mod tests {
                         type_constraints: vec![]
                     }],
                 },
+                docs: None,
             }
         )
     }
@@ -0,0 +1,122 @@
+use pest::Span;
+
+use crate::cst::{
+    interface::{CSTInterface, CSTInterfaceMember, CSTInterfaceMemberKeyValue},
+    trivia::CSTNode,
+};
+
+use super::{elp_type::ASTElpType, function::ASTFunctionHeaderDef, traits::FromCST};
+
+// Interfaces only exist to be checked against, nothing of them is left at runtime.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ASTInterface<'a> {
+    pub span: &'a Span<'a>,
+    pub name: String,
+    pub generics: Vec<ASTElpType<'a>>,
+    pub members: Vec<ASTInterfaceMember<'a>>,
+    // From the `///` comments above it.
+    pub docs: Option<String>,
+}
+
+impl<'a> FromCST<'a, CSTInterface<'a>> for ASTInterface<'a> {
+    fn from_cst(cst: &'a CSTInterface) -> Self {
+        Self {
+            span: &cst.span,
+            name: cst.name.value.clone(),
+            generics: match &cst.generics {
+                Some(generic) => generic.params.iter().map(ASTElpType::from_cst).collect(),
+                None => vec![],
+            },
+            members: cst
+                .members
+                .iter()
+                .map(ASTInterfaceMember::from_cst)
+                .collect(),
+            docs: cst.doc_comment(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ASTInterfaceMember<'a> {
+    Field(ASTInterfaceField<'a>),
+    Method(ASTFunctionHeaderDef<'a>),
+}
+
+impl<'a> FromCST<'a, CSTInterfaceMember<'a>> for ASTInterfaceMember<'a> {
+    fn from_cst(cst: &'a CSTInterfaceMember) -> Self {
+        match cst {
+            CSTInterfaceMember::Field(field) => {
+                ASTInterfaceMember::Field(ASTInterfaceField::from_cst(&**field))
+            }
+            CSTInterfaceMember::Method(method) => {
+                ASTInterfaceMember::Method(ASTFunctionHeaderDef::from_cst(&**method))
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ASTInterfaceField<'a> {
+    pub span: &'a Span<'a>,
+    pub name: String,
+    pub type_annotation: Option<ASTElpType<'a>>,
+}
+
+impl<'a> FromCST<'a, CSTInterfaceMemberKeyValue<'a>> for ASTInterfaceField<'a> {
+    fn from_cst(cst: &'a CSTInterfaceMemberKeyValue) -> Self {
+        Self {
+            span: &cst.span,
+            name: cst.name.value.clone(),
+            type_annotation: cst.type_annotation.as_ref().map(ASTElpType::from_cst),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use from_pest::FromPest;
+    use pest::Parser;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::{
+        cst::{expression::CSTExpression, CSTModule},
+        parser::{ElpParser, Rule},
+    };
+
+    #[test]
+    fn interface_from_cst() {
+        let expression_str =
+            "/// Something with a name.\ninterface Named<T> {\n\t.name T,\n\tfn rename(name T) -> T\n}";
+        let mut pairs = ElpParser::parse(Rule::module, expression_str).unwrap();
+        let cst = CSTModule::from_pest(&mut pairs).unwrap();
+        let CSTExpression::Interface(interface) = &cst.expressions[0] else {
+            panic!("expected an interface, got {:?}", cst.expressions[0]);
+        };
+        let ast = ASTInterface::from_cst(interface);
+
+        assert_eq!(ast.name, "Named");
+        assert_eq!(ast.docs.as_deref(), Some("Something with a name."));
+        assert_eq!(
+            ast.generics
+                .iter()
+                .map(|generic| &generic.name)
+                .collect::<Vec<_>>(),
+            vec!["T"]
+        );
+
+        let ASTInterfaceMember::Field(field) = &ast.members[0] else {
+            panic!("expected a field, got {:?}", ast.members[0]);
+        };
+        assert_eq!(field.name, "name");
+        assert_eq!(field.type_annotation.as_ref().unwrap().name, "T");
+
+        let ASTInterfaceMember::Method(method) = &ast.members[1] else {
+            panic!("expected a method, got {:?}", ast.members[1]);
+        };
+        assert_eq!(method.name.names, vec!["rename"]);
+        assert_eq!(method.arguments.arguments[0].name, "name");
+        assert_eq!(method.return_type.type_annotations[0].name, "T");
+    }
+}
@@ -0,0 +1,21 @@
+use crate::cst::r#macro::CSTMacro;
+
+use super::{elp_type::ASTElpType, traits::FromCST};
+
+// An annotation, expanded against the item after it once the module is lowered.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ASTMacro<'a> {
+    pub span: &'a pest::Span<'a>,
+    pub name: String,
+    pub argument: Option<ASTElpType<'a>>,
+}
+
+impl<'a> FromCST<'a, CSTMacro<'a>> for ASTMacro<'a> {
+    fn from_cst(cst: &'a CSTMacro<'a>) -> Self {
+        Self {
+            span: &cst.span,
+            name: cst.name.value.clone(),
+            argument: cst.argument.as_ref().map(ASTElpType::from_cst),
+        }
+    }
+}
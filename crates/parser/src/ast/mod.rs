@@ -1,17 +1,23 @@
-pub(crate) mod block;
-pub(crate) mod elp_type;
-pub(crate) mod r#enum;
-pub(crate) mod export;
-pub(crate) mod expression;
-pub(crate) mod for_loop;
-pub(crate) mod function;
-pub(crate) mod import;
-pub(crate) mod module;
-pub(crate) mod number;
-pub(crate) mod object;
-pub(crate) mod string;
-pub(crate) mod traits;
-pub(crate) mod value_assignment;
-pub(crate) mod variable_access;
-pub(crate) mod variable_assignment;
-pub(crate) mod variable_declaration;
+pub mod block;
+pub mod component;
+pub mod elp_type;
+pub mod r#enum;
+pub mod export;
+pub mod expression;
+pub mod external;
+pub mod for_loop;
+pub mod function;
+pub mod import;
+pub mod interface;
+pub mod r#macro;
+pub mod module;
+pub mod number;
+pub mod object;
+pub mod precomp;
+pub mod string;
+pub mod style;
+pub mod traits;
+pub mod value_assignment;
+pub mod variable_access;
+pub mod variable_assignment;
+pub mod variable_declaration;
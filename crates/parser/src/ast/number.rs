@@ -29,6 +29,9 @@ impl<'a> FromCST<'a, CSTNumber<'a>> for ASTNumber<'a> {
 }
 
 impl ParsedNumber {
+    // The grammar's `number` is decimal, with an optional fraction and exponent, so of the panics
+    // here only an integer too big for an i64 can be reached from source. The prefixed bases are
+    // for numbers made some other way, and a fraction always has digits on both sides of its point.
     pub fn from_string(input: &str) -> Self {
         // Check for prefixes first to determine the base
         if input.starts_with("0x") {
@@ -55,8 +58,8 @@ impl ParsedNumber {
             } else {
                 panic!("Invalid octal number: {}", input)
             }
-        } else if input.contains('.') {
-            // Float (must contain a decimal point)
+        } else if input.contains(['.', 'e', 'E']) {
+            // Float (must contain a decimal point or an exponent)
             let clean_input = input.replace("_", "");
             if let Ok(value) = clean_input.parse::<f64>() {
                 ParsedNumber::Float(value)
@@ -106,4 +109,24 @@ mod tests {
         let parsed_number = ParsedNumber::from_string(number_str);
         assert_eq!(parsed_number, ParsedNumber::Float(1.23));
     }
+
+    #[test]
+    fn an_exponent_makes_a_float() {
+        assert_eq!(ParsedNumber::from_string("1e5"), ParsedNumber::Float(1e5));
+        assert_eq!(
+            ParsedNumber::from_string("-2E-1"),
+            ParsedNumber::Float(-0.2)
+        );
+    }
+
+    #[test]
+    fn source_numbers_are_decimal() {
+        use crate::parser::{ElpParser, Rule};
+        use pest::Parser;
+
+        for source in ["0x1F", "0b101", "0o17", "1_000"] {
+            let number = ElpParser::parse(Rule::number, source).unwrap();
+            assert_ne!(number.as_str(), source);
+        }
+    }
 }
@@ -1,7 +1,8 @@
 use pest::Span;
 
 use crate::cst::{
-    object::{CSTObject, CSTObjectImplements, CSTObjectMember, CSTObjectMemberTags},
+    object::{CSTObject, CSTObjectImplements, CSTObjectMember, CSTObjectMemberTag},
+    trivia::CSTNode,
     CSTVisibilitySelector,
 };
 
@@ -28,6 +29,8 @@ pub struct ASTObject<'a> {
     pub name: String,
     pub members: Vec<ASTObjectMember<'a>>,
     pub implements: Vec<ASTObjectImplements<'a>>,
+    // From the `///` comments above it.
+    pub docs: Option<String>,
 }
 
 impl<'a> FromCST<'a, CSTObject<'a>> for ASTObject<'a> {
@@ -41,6 +44,7 @@ impl<'a> FromCST<'a, CSTObject<'a>> for ASTObject<'a> {
                 .iter()
                 .map(ASTObjectImplements::from_cst)
                 .collect(),
+            docs: cst.doc_comment(),
         }
     }
 }
@@ -68,6 +72,7 @@ pub struct ASTObjectMember<'a> {
     pub default_value: Option<ASTExpression<'a>>,
     pub visibility: Option<ASTVisibility>,
     pub tags: Vec<ASTObjectMemberTags<'a>>,
+    pub docs: Option<String>,
 }
 
 impl<'a> FromCST<'a, CSTObjectMember<'a>> for ASTObjectMember<'a> {
@@ -81,7 +86,13 @@ impl<'a> FromCST<'a, CSTObjectMember<'a>> for ASTObjectMember<'a> {
                 .default_value
                 .as_ref()
                 .map(|default_value| ASTExpression::from_cst(&default_value.value)),
-            tags: cst.tags.iter().map(ASTObjectMemberTags::from_cst).collect(),
+            tags: cst
+                .tags
+                .iter()
+                .flat_map(|tags| &tags.tags)
+                .map(ASTObjectMemberTags::from_cst)
+                .collect(),
+            docs: cst.doc_comment(),
         }
     }
 }
@@ -93,8 +104,8 @@ pub struct ASTObjectMemberTags<'a> {
     pub value: String,
 }
 
-impl<'a> FromCST<'a, CSTObjectMemberTags<'a>> for ASTObjectMemberTags<'a> {
-    fn from_cst(cst: &'a CSTObjectMemberTags) -> Self {
+impl<'a> FromCST<'a, CSTObjectMemberTag<'a>> for ASTObjectMemberTags<'a> {
+    fn from_cst(cst: &'a CSTObjectMemberTag) -> Self {
         ASTObjectMemberTags {
             span: &cst.span,
             name: cst.name.value.clone(),
@@ -118,7 +129,7 @@ mod tests {
             expression::CSTExpression,
             ident::CSTIdent,
             number_value::CSTNumber,
-            object::CSTObjectMemberDefaultValue,
+            object::{CSTObjectMemberDefaultValue, CSTObjectMemberTags},
             string::CSTString,
             CSTPrivateVisibility, CSTPublicVisibility,
         },
@@ -159,7 +170,7 @@ mod tests {
                     }),
                 }),
                 default_value: None,
-                tags: vec![],
+                tags: None,
             }],
         };
 
@@ -184,8 +195,10 @@ mod tests {
                     }),
                     default_value: None,
                     tags: vec![],
+                    docs: None,
                 }],
                 implements: vec![],
+                docs: None,
             }
         )
     }
@@ -263,17 +276,20 @@ mod tests {
                         }),
                     }),
                     default_value: None,
-                    tags: vec![CSTObjectMemberTags {
+                    tags: Some(CSTObjectMemberTags {
                         span: pest::Span::new(expression_str, 72, 85).unwrap(),
-                        name: CSTIdent {
-                            span: pest::Span::new(expression_str, 73, 77).unwrap(),
-                            value: "json".into(),
-                        },
-                        contents: CSTString {
-                            span: pest::Span::new(expression_str, 78, 84).unwrap(),
-                            value: "name".into(),
-                        },
-                    }],
+                        tags: vec![CSTObjectMemberTag {
+                            span: pest::Span::new(expression_str, 73, 84).unwrap(),
+                            name: CSTIdent {
+                                span: pest::Span::new(expression_str, 73, 77).unwrap(),
+                                value: "json".into(),
+                            },
+                            contents: CSTString {
+                                span: pest::Span::new(expression_str, 78, 84).unwrap(),
+                                value: "name".into(),
+                            },
+                        }],
+                    }),
                 },
                 CSTObjectMember {
                     span: pest::Span::new(expression_str, 99, 135).unwrap(),
@@ -298,17 +314,20 @@ mod tests {
                         }),
                     }),
                     default_value: None,
-                    tags: vec![CSTObjectMemberTags {
+                    tags: Some(CSTObjectMemberTags {
                         span: pest::Span::new(expression_str, 123, 135).unwrap(),
-                        name: CSTIdent {
-                            span: pest::Span::new(expression_str, 124, 128).unwrap(),
-                            value: "json".into(),
-                        },
-                        contents: CSTString {
-                            span: pest::Span::new(expression_str, 129, 134).unwrap(),
-                            value: "age".into(),
-                        },
-                    }],
+                        tags: vec![CSTObjectMemberTag {
+                            span: pest::Span::new(expression_str, 124, 134).unwrap(),
+                            name: CSTIdent {
+                                span: pest::Span::new(expression_str, 124, 128).unwrap(),
+                                value: "json".into(),
+                            },
+                            contents: CSTString {
+                                span: pest::Span::new(expression_str, 129, 134).unwrap(),
+                                value: "age".into(),
+                            },
+                        }],
+                    }),
                 },
                 CSTObjectMember {
                     span: pest::Span::new(expression_str, 149, 189).unwrap(),
@@ -352,17 +371,20 @@ mod tests {
                         }),
                     }),
                     default_value: None,
-                    tags: vec![CSTObjectMemberTags {
+                    tags: Some(CSTObjectMemberTags {
                         span: pest::Span::new(expression_str, 173, 189).unwrap(),
-                        name: CSTIdent {
-                            span: pest::Span::new(expression_str, 174, 178).unwrap(),
-                            value: "json".into(),
-                        },
-                        contents: CSTString {
-                            span: pest::Span::new(expression_str, 179, 188).unwrap(),
-                            value: "friends".into(),
-                        },
-                    }],
+                        tags: vec![CSTObjectMemberTag {
+                            span: pest::Span::new(expression_str, 174, 188).unwrap(),
+                            name: CSTIdent {
+                                span: pest::Span::new(expression_str, 174, 178).unwrap(),
+                                value: "json".into(),
+                            },
+                            contents: CSTString {
+                                span: pest::Span::new(expression_str, 179, 188).unwrap(),
+                                value: "friends".into(),
+                            },
+                        }],
+                    }),
                 },
                 CSTObjectMember {
                     span: pest::Span::new(expression_str, 203, 245).unwrap(),
@@ -379,17 +401,20 @@ mod tests {
                             value: "123".into(),
                         })),
                     }),
-                    tags: vec![CSTObjectMemberTags {
+                    tags: Some(CSTObjectMemberTags {
                         span: pest::Span::new(expression_str, 227, 245).unwrap(),
-                        name: CSTIdent {
-                            span: pest::Span::new(expression_str, 228, 232).unwrap(),
-                            value: "json".into(),
-                        },
-                        contents: CSTString {
-                            span: pest::Span::new(expression_str, 233, 244).unwrap(),
-                            value: "studentId".into(),
-                        },
-                    }],
+                        tags: vec![CSTObjectMemberTag {
+                            span: pest::Span::new(expression_str, 228, 244).unwrap(),
+                            name: CSTIdent {
+                                span: pest::Span::new(expression_str, 228, 232).unwrap(),
+                                value: "json".into(),
+                            },
+                            contents: CSTString {
+                                span: pest::Span::new(expression_str, 233, 244).unwrap(),
+                                value: "studentId".into(),
+                            },
+                        }],
+                    }),
                 },
             ],
         };
@@ -434,10 +459,11 @@ mod tests {
                         }),
                         default_value: None,
                         tags: vec![ASTObjectMemberTags {
-                            span: &pest::Span::new(expression_str, 72, 85).unwrap(),
+                            span: &pest::Span::new(expression_str, 73, 84).unwrap(),
                             name: "json".into(),
                             value: "name".into(),
                         }],
+                        docs: None,
                     },
                     ASTObjectMember {
                         span: &pest::Span::new(expression_str, 99, 135).unwrap(),
@@ -453,10 +479,11 @@ mod tests {
                         }),
                         default_value: None,
                         tags: vec![ASTObjectMemberTags {
-                            span: &pest::Span::new(expression_str, 123, 135).unwrap(),
+                            span: &pest::Span::new(expression_str, 124, 134).unwrap(),
                             name: "json".into(),
                             value: "age".into(),
-                        }]
+                        }],
+                        docs: None,
                     },
                     ASTObjectMember {
                         span: &pest::Span::new(expression_str, 149, 189).unwrap(),
@@ -479,10 +506,11 @@ mod tests {
                         }),
                         default_value: None,
                         tags: vec![ASTObjectMemberTags {
-                            span: &pest::Span::new(expression_str, 173, 189).unwrap(),
+                            span: &pest::Span::new(expression_str, 174, 188).unwrap(),
                             name: "json".into(),
                             value: "friends".into(),
-                        }]
+                        }],
+                        docs: None,
                     },
                     ASTObjectMember {
                         span: &pest::Span::new(expression_str, 203, 245).unwrap(),
@@ -494,12 +522,14 @@ mod tests {
                             value: ParsedNumber::Integer(123),
                         }))),
                         tags: vec![ASTObjectMemberTags {
-                            span: &pest::Span::new(expression_str, 227, 245).unwrap(),
+                            span: &pest::Span::new(expression_str, 228, 244).unwrap(),
                             name: "json".into(),
                             value: "studentId".into(),
-                        }]
+                        }],
+                        docs: None,
                     }
                 ],
+                docs: None,
             }
         )
     }
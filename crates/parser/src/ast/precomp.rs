@@ -0,0 +1,21 @@
+use crate::cst::precomp::CSTPrecomp;
+
+use super::{elp_type::ASTElpType, traits::FromCST};
+
+// Resolved by `precomp::preprocess` before the module is lowered.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ASTPrecomp<'a> {
+    pub span: &'a pest::Span<'a>,
+    pub name: String,
+    pub argument: Option<ASTElpType<'a>>,
+}
+
+impl<'a> FromCST<'a, CSTPrecomp<'a>> for ASTPrecomp<'a> {
+    fn from_cst(cst: &'a CSTPrecomp<'a>) -> Self {
+        Self {
+            span: &cst.span,
+            name: cst.name.value.clone(),
+            argument: cst.argument.as_ref().map(ASTElpType::from_cst),
+        }
+    }
+}
@@ -0,0 +1,187 @@
+use pest::Span;
+
+use crate::cst::style::{
+    CSTStyleProperty, CSTStyleRule, CSTStyleSelector, CSTStyleSheet, CSTStyleSheetFile,
+    CSTStyleValue,
+};
+
+use super::{
+    number::ASTNumber, string::ASTString, traits::FromCST, variable_access::ASTVariableAccess,
+};
+
+// A `Stylesheet { .. }` block or a whole `.selp` file.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ASTStyleSheet<'a> {
+    pub span: &'a Span<'a>,
+    pub rules: Vec<ASTStyleRule<'a>>,
+}
+
+impl<'a> FromCST<'a, CSTStyleSheet<'a>> for ASTStyleSheet<'a> {
+    fn from_cst(cst: &'a CSTStyleSheet) -> Self {
+        Self {
+            span: &cst.span,
+            rules: cst.rules.iter().map(ASTStyleRule::from_cst).collect(),
+        }
+    }
+}
+
+impl<'a> FromCST<'a, CSTStyleSheetFile<'a>> for ASTStyleSheet<'a> {
+    fn from_cst(cst: &'a CSTStyleSheetFile) -> Self {
+        Self {
+            span: &cst.span,
+            rules: cst.rules.iter().map(ASTStyleRule::from_cst).collect(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ASTStyleRule<'a> {
+    pub span: &'a Span<'a>,
+    pub selectors: Vec<ASTStyleSelector<'a>>,
+    pub properties: Vec<ASTStyleProperty<'a>>,
+}
+
+impl<'a> FromCST<'a, CSTStyleRule<'a>> for ASTStyleRule<'a> {
+    fn from_cst(cst: &'a CSTStyleRule) -> Self {
+        Self {
+            span: &cst.span,
+            selectors: cst
+                .selectors
+                .iter()
+                .map(ASTStyleSelector::from_cst)
+                .collect(),
+            properties: cst
+                .properties
+                .iter()
+                .map(ASTStyleProperty::from_cst)
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ASTStyleSelector<'a> {
+    pub span: &'a Span<'a>,
+    pub component: Option<String>,
+    pub classes: Vec<String>,
+}
+
+impl<'a> FromCST<'a, CSTStyleSelector<'a>> for ASTStyleSelector<'a> {
+    fn from_cst(cst: &'a CSTStyleSelector) -> Self {
+        Self {
+            span: &cst.span,
+            component: cst.component.as_ref().map(|name| name.value.clone()),
+            classes: cst
+                .classes
+                .iter()
+                .map(|class| class.name.value.clone())
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ASTStyleProperty<'a> {
+    pub span: &'a Span<'a>,
+    pub name: String,
+    pub value: ASTStyleValue<'a>,
+}
+
+impl<'a> FromCST<'a, CSTStyleProperty<'a>> for ASTStyleProperty<'a> {
+    fn from_cst(cst: &'a CSTStyleProperty) -> Self {
+        Self {
+            span: &cst.span,
+            name: cst.name.value.clone(),
+            value: ASTStyleValue::from_cst(&cst.value),
+        }
+    }
+}
+
+// Values as they're written, what they mean depends on the property they're given to.
+#[derive(Debug, PartialEq, Clone)]
+pub enum ASTStyleValue<'a> {
+    // The hex digits, without the `#`.
+    Colour(&'a Span<'a>, String),
+    Length(ASTNumber<'a>, String),
+    Number(ASTNumber<'a>),
+    String(ASTString<'a>),
+    EnumCase(&'a Span<'a>, String),
+    VariableAccess(ASTVariableAccess<'a>),
+}
+
+impl<'a> FromCST<'a, CSTStyleValue<'a>> for ASTStyleValue<'a> {
+    fn from_cst(cst: &'a CSTStyleValue) -> Self {
+        match cst {
+            CSTStyleValue::Colour(colour) => {
+                ASTStyleValue::Colour(&colour.span, colour.value[1..].into())
+            }
+            CSTStyleValue::Length(length) => ASTStyleValue::Length(
+                ASTNumber::from_cst(&length.number),
+                length.unit.value.clone(),
+            ),
+            CSTStyleValue::Number(number) => ASTStyleValue::Number(ASTNumber::from_cst(number)),
+            CSTStyleValue::String(string) => ASTStyleValue::String(ASTString::from_cst(string)),
+            CSTStyleValue::EnumCase(case) => {
+                ASTStyleValue::EnumCase(&case.span, case.name.value.clone())
+            }
+            CSTStyleValue::VariableAccess(access) => {
+                ASTStyleValue::VariableAccess(ASTVariableAccess::from_cst(access))
+            }
+        }
+    }
+}
+
+impl<'a> ASTStyleValue<'a> {
+    pub fn span(&self) -> &'a Span<'a> {
+        match self {
+            ASTStyleValue::Colour(span, _) | ASTStyleValue::EnumCase(span, _) => span,
+            ASTStyleValue::Length(number, _) | ASTStyleValue::Number(number) => number.span,
+            ASTStyleValue::String(string) => string.span,
+            ASTStyleValue::VariableAccess(access) => access.span,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ast::number::ParsedNumber,
+        parser::{ElpParser, Rule},
+    };
+    use from_pest::FromPest;
+    use pest::Parser;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn stylesheet_from_cst() {
+        let source = "Stylesheet { Text, Date.muted { .padding = 16, .color = #333 } }";
+        let mut pairs = ElpParser::parse(Rule::stylesheet, source).unwrap();
+        let cst = CSTStyleSheet::from_pest(&mut pairs).unwrap();
+        let ast = ASTStyleSheet::from_cst(&cst);
+
+        let rule = &ast.rules[0];
+        assert_eq!(
+            rule.selectors
+                .iter()
+                .map(|selector| (selector.component.as_deref(), selector.classes.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                (Some("Text"), vec![]),
+                (Some("Date"), vec!["muted".to_string()])
+            ]
+        );
+        assert_eq!(rule.properties[0].name, "padding");
+        assert!(matches!(
+            &rule.properties[0].value,
+            ASTStyleValue::Number(ASTNumber {
+                value: ParsedNumber::Integer(16),
+                ..
+            })
+        ));
+        assert!(matches!(
+            &rule.properties[1].value,
+            ASTStyleValue::Colour(span, digits) if digits == "333" && span.as_str() == "#333"
+        ));
+    }
+}
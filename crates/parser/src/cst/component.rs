@@ -5,7 +5,7 @@ use crate::parser::Rule;
 use super::{
     block::CSTBlock,
     elp_type::CSTElpTypeGeneric,
-    function::{CSTFunctionArguments, CSTFunctionReturnType},
+    function::{CSTFunctionArguments, CSTFunctionCall, CSTFunctionReturnType},
     variable_access::CSTVariableAccess,
 };
 
@@ -21,6 +21,17 @@ pub struct CSTComponentDef<'a> {
     pub block: Box<CSTBlock<'a>>,
 }
 
+// A call with a block of children, `Column(class = "row") { Text("hi") }`. Only names starting
+// with a capital letter count so `for x in items() { .. }` still loops.
+#[derive(Debug, FromPest, PartialEq, Eq, Clone)]
+#[pest_ast(rule(Rule::function_component_call))]
+pub struct CSTFunctionComponentCall<'a> {
+    #[pest_ast(outer())]
+    pub span: pest::Span<'a>,
+    pub call: CSTFunctionCall<'a>,
+    pub block: CSTBlock<'a>,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -123,4 +134,31 @@ mod tests {
             }
         )
     }
+
+    #[test]
+    fn component_call_with_children() {
+        let expression_str = "Column(class = \"row\") { Text(\"hi\") }";
+        let mut pairs = ElpParser::parse(Rule::expression, expression_str).unwrap();
+        let expression = CSTExpression::from_pest(&mut pairs).unwrap();
+
+        let CSTExpression::FunctionComponentCall(call) = expression else {
+            panic!("expected a component call, got {:?}", expression);
+        };
+        assert_eq!(call.call.span.as_str(), "Column(class = \"row\")");
+        assert_eq!(call.call.arguments.len(), 1);
+        assert_eq!(call.block.span.as_str(), "{ Text(\"hi\") }");
+        assert!(matches!(
+            call.block.expressions.as_slice(),
+            [CSTExpression::FunctionCall(_)]
+        ));
+    }
+
+    #[test]
+    fn lowercase_calls_keep_their_blocks_separate() {
+        let expression_str = "items() { }";
+        let mut pairs = ElpParser::parse(Rule::expression, expression_str).unwrap();
+        let expression = CSTExpression::from_pest(&mut pairs).unwrap();
+
+        assert!(matches!(expression, CSTExpression::FunctionCall(_)));
+    }
 }
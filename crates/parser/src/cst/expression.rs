@@ -1,8 +1,9 @@
 use super::{
     block::CSTBlock,
-    component::CSTComponentDef,
+    component::{CSTComponentDef, CSTFunctionComponentCall},
     elp_type::CSTElpType,
     export::CSTExport,
+    external::{CSTExternalBlock, CSTExternalSymbol},
     function::{CSTFunctionCall, CSTFunctionDef, CSTFunctionHeaderDef, CSTFunctionReturnValue},
     ident::CSTIdent,
     if_tree::CSTIfTree,
@@ -10,9 +11,12 @@ use super::{
     interface::CSTInterface,
     number_value::CSTNumber,
     object::CSTObject,
+    precomp::CSTPrecomp,
     r#enum::CSTEnum,
+    r#macro::CSTMacro,
     r#match::CSTMatchTree,
     string::CSTString,
+    style::CSTStyleSheet,
     unary::CSTUnaryOperator,
     value_assignment::{CSTOperand, CSTValueAssignment},
     variable_access::{CSTPointerSemantics, CSTVariableAccess},
@@ -36,7 +40,10 @@ pub enum CSTExpression<'a> {
     ElpType(Box<CSTElpType<'a>>),
     Enum(Box<CSTEnum<'a>>),
     Export(Box<CSTExport<'a>>),
+    ExternalBlock(Box<CSTExternalBlock<'a>>),
+    ExternalSymbol(Box<CSTExternalSymbol<'a>>),
     FunctionCall(Box<CSTFunctionCall<'a>>),
+    FunctionComponentCall(Box<CSTFunctionComponentCall<'a>>),
     FunctionDef(Box<CSTFunctionDef<'a>>),
     FunctionHeaderDef(Box<CSTFunctionHeaderDef<'a>>),
     FunctionReturnValue(Box<CSTFunctionReturnValue<'a>>),
@@ -44,12 +51,15 @@ pub enum CSTExpression<'a> {
     Import(Box<CSTImport<'a>>),
     IfTree(Box<CSTIfTree<'a>>),
     Interface(Box<CSTInterface<'a>>),
+    Macro(Box<CSTMacro<'a>>),
     Match(Box<CSTMatchTree<'a>>),
     Number(Box<CSTNumber<'a>>),
     Object(Box<CSTObject<'a>>),
     Operand(Box<CSTOperand<'a>>),
     PointerSemantics(Box<CSTPointerSemantics<'a>>),
+    Precomp(Box<CSTPrecomp<'a>>),
     String(Box<CSTString<'a>>),
+    StyleSheet(Box<CSTStyleSheet<'a>>),
     Unary(Box<CSTUnaryOperator<'a>>),
     ValueAssignment(Box<CSTValueAssignment<'a>>),
     VariableAccess(Box<CSTVariableAccess<'a>>),
@@ -0,0 +1,94 @@
+use pest::Span;
+use pest_ast::FromPest;
+
+use super::{function::CSTFunctionHeaderDef, object::CSTObject, string::CSTString};
+use crate::parser::Rule;
+
+// The `"C"` in `external "C" fn puts(text string) -> int32`, how what's declared is called.
+#[derive(Debug, FromPest, PartialEq, Eq, Clone)]
+#[pest_ast(rule(Rule::external_abi))]
+pub struct CSTExternalAbi<'a> {
+    #[pest_ast(outer())]
+    pub span: Span<'a>,
+    pub name: CSTString<'a>,
+}
+
+#[derive(Debug, FromPest, PartialEq, Eq, Clone)]
+#[pest_ast(rule(Rule::external_item))]
+pub enum CSTExternalItem<'a> {
+    Function(CSTFunctionHeaderDef<'a>),
+    Object(CSTObject<'a>),
+}
+
+// `external fn puts(text string) -> int32`, one item defined outside elp.
+#[derive(Debug, FromPest, PartialEq, Eq, Clone)]
+#[pest_ast(rule(Rule::external_symbol))]
+pub struct CSTExternalSymbol<'a> {
+    #[pest_ast(outer())]
+    pub span: Span<'a>,
+    pub abi: Option<CSTExternalAbi<'a>>,
+    pub item: CSTExternalItem<'a>,
+}
+
+// `external "C" { .. }`, any number of items sharing an ABI.
+#[derive(Debug, FromPest, PartialEq, Eq, Clone)]
+#[pest_ast(rule(Rule::external_block))]
+pub struct CSTExternalBlock<'a> {
+    #[pest_ast(outer())]
+    pub span: Span<'a>,
+    pub abi: Option<CSTExternalAbi<'a>>,
+    pub items: Vec<CSTExternalItem<'a>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::ElpParser;
+    use from_pest::FromPest;
+    use pest::Parser;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn external_symbol() {
+        let expression_str = "external fn OBJC_msgSend<T: OBJC_Obj + Send>(T, OBJC_Selector) -> T";
+        let mut pairs = ElpParser::parse(Rule::external_symbol, expression_str).unwrap();
+        let cst = CSTExternalSymbol::from_pest(&mut pairs).unwrap();
+
+        assert_eq!(cst.abi, None);
+        let CSTExternalItem::Function(function) = cst.item else {
+            panic!("expected a function, got {:?}", cst.item);
+        };
+        assert_eq!(function.span.as_str(), &expression_str[9..]);
+    }
+
+    #[test]
+    fn external_block_with_an_abi() {
+        let expression_str = "external \"stdcall\" {
+    fn MessageBeep(kind uint32) -> bool
+    object Point {
+        .x int32,
+        .y int32,
+    }
+}";
+        let mut pairs = ElpParser::parse(Rule::external_block, expression_str).unwrap();
+        let cst = CSTExternalBlock::from_pest(&mut pairs).unwrap();
+
+        assert_eq!(
+            cst.abi.map(|abi| abi.name.span.as_str()),
+            Some("\"stdcall\"")
+        );
+        assert_eq!(
+            cst.items
+                .iter()
+                .map(|item| match item {
+                    CSTExternalItem::Function(function) => function.span.as_str(),
+                    CSTExternalItem::Object(object) => object.span.as_str(),
+                })
+                .collect::<Vec<_>>(),
+            vec![
+                "fn MessageBeep(kind uint32) -> bool\n    ",
+                "object Point {\n        .x int32,\n        .y int32,\n    }"
+            ]
+        );
+    }
+}
@@ -454,4 +454,13 @@ mod tests {
             }
         )
     }
+
+    #[test]
+    fn function_def_with_return_type_is_not_a_header() {
+        let expression_str = "fn double(value int) -> int { return value }";
+        let mut pairs = ElpParser::parse(Rule::expression, expression_str).unwrap();
+        let ast = CSTExpression::from_pest(&mut pairs).unwrap();
+
+        assert!(matches!(ast, CSTExpression::FunctionDef(_)));
+    }
 }
@@ -0,0 +1,63 @@
+use pest::Span;
+use pest_ast::FromPest;
+
+use super::{elp_type::CSTElpType, ident::CSTIdent};
+use crate::parser::Rule;
+
+// `@toString` or `@derive(Error)`, an annotation on the item that follows it.
+#[derive(Debug, FromPest, PartialEq, Eq, Clone)]
+#[pest_ast(rule(Rule::r#macro))]
+pub struct CSTMacro<'a> {
+    #[pest_ast(outer())]
+    pub span: Span<'a>,
+    pub name: CSTIdent<'a>,
+    pub argument: Option<CSTElpType<'a>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cst::{expression::CSTExpression, CSTModule},
+        parser::ElpParser,
+    };
+    use from_pest::FromPest;
+    use pest::Parser;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn parse_macro() {
+        let expression_str = "@toString";
+        let mut pairs = ElpParser::parse(Rule::r#macro, expression_str).unwrap();
+        let ast = CSTMacro::from_pest(&mut pairs).unwrap();
+
+        assert_eq!(
+            ast,
+            CSTMacro {
+                span: Span::new(expression_str, 0, 9).unwrap(),
+                name: CSTIdent {
+                    span: Span::new(expression_str, 1, 9).unwrap(),
+                    value: "toString".into(),
+                },
+                argument: None,
+            }
+        );
+    }
+
+    #[test]
+    fn macros_come_before_the_item() {
+        let source = "@error(Error)\nexport enum LoginError {\n\t.NO_HANDLER,\n}\n";
+        let mut pairs = ElpParser::parse(Rule::module, source).unwrap();
+        let module = CSTModule::from_pest(&mut pairs).unwrap();
+
+        let CSTExpression::Macro(annotation) = &module.expressions[0] else {
+            panic!("expected a macro, got {:?}", module.expressions[0]);
+        };
+        assert_eq!(annotation.name.value, "error");
+        assert_eq!(
+            annotation.argument.as_ref().map(|ty| ty.span.as_str()),
+            Some("Error")
+        );
+        assert!(matches!(module.expressions[1], CSTExpression::Export(_)));
+    }
+}
@@ -1,30 +1,42 @@
-pub(crate) mod block;
-pub(crate) mod component;
-pub(crate) mod elp_type;
-pub(crate) mod r#enum;
-pub(crate) mod export;
-pub(crate) mod expression;
-pub(crate) mod for_loop;
-pub(crate) mod function;
-pub(crate) mod ident;
-pub(crate) mod if_tree;
-pub(crate) mod import;
-pub(crate) mod interface;
-pub(crate) mod r#match;
-pub(crate) mod number_value;
-pub(crate) mod object;
-pub(crate) mod string;
-pub(crate) mod unary;
-pub(crate) mod value_assignment;
-pub(crate) mod variable_access;
-pub(crate) mod variable_assignment;
-pub(crate) mod variable_declaration;
+pub mod block;
+pub mod component;
+pub mod elp_type;
+pub mod r#enum;
+pub mod export;
+pub mod expression;
+pub mod external;
+pub mod for_loop;
+pub mod function;
+pub mod ident;
+pub mod if_tree;
+pub mod import;
+pub mod interface;
+pub mod r#macro;
+pub mod r#match;
+pub mod number_value;
+pub mod object;
+pub mod precomp;
+pub mod string;
+pub mod style;
+pub mod trivia;
+pub mod unary;
+pub mod value_assignment;
+pub mod variable_access;
+pub mod variable_assignment;
+pub mod variable_declaration;
+
+use std::{cmp::Ordering, ops::Range};
 
 use expression::CSTExpression;
-use pest::Span;
+use from_pest::{ConversionError, Void};
+use pest::{
+    iterators::{Pair, Pairs},
+    Parser, Span,
+};
 use pest_ast::FromPest;
+use trivia::{CSTNode, CSTNodeTrivia, CSTTriviaPiece};
 
-use crate::parser::Rule;
+use crate::parser::{ElpParser, Rule};
 
 #[derive(Debug, FromPest, PartialEq, Eq, Clone)]
 #[pest_ast(rule(Rule::VAR))]
@@ -72,17 +84,139 @@ fn span_into_string(span: Span) -> String {
     span.as_str().into()
 }
 
-#[derive(Debug, FromPest, PartialEq, Eq, Clone)]
-#[pest_ast(rule(Rule::module))]
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub struct CSTModule<'a> {
+    pub span: Span<'a>,
     pub expressions: Vec<CSTExpression<'a>>,
+    // The trivia around each expression, in the same order.
+    pub trivia: Vec<CSTNodeTrivia<'a>>,
+    // What's after the line the last expression ends on, or all of a module without any.
+    pub end: Vec<CSTTriviaPiece<'a>>,
+}
+
+// Where the pieces of a module don't fit back together into its source.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum CSTSourceError {
+    // Bytes more than one piece has.
+    Overlap(Range<usize>),
+    // Bytes none of them have.
+    Gap(Range<usize>),
+}
+
+impl CSTModule<'_> {
+    // The source the module was parsed from, put back together from its expressions and the
+    // trivia stored with them. Every byte belongs to exactly one of those, trivia that's been
+    // attached wrong is an error rather than something to paper over.
+    pub fn to_source(&self) -> Result<String, CSTSourceError> {
+        let pieces = self
+            .expressions
+            .iter()
+            .zip(&self.trivia)
+            .flat_map(|(expression, trivia)| {
+                let leading = trivia.leading.iter().map(CSTTriviaPiece::span);
+                let trailing = trivia.trailing.iter().map(CSTTriviaPiece::span);
+                leading
+                    .chain(std::iter::once(expression.code_span()))
+                    .chain(trailing)
+            })
+            .chain(self.end.iter().map(CSTTriviaPiece::span));
+
+        let mut source = String::with_capacity(self.span.as_str().len());
+        let mut cursor = self.span.start();
+        for piece in pieces {
+            match piece.start().cmp(&cursor) {
+                Ordering::Less => return Err(CSTSourceError::Overlap(piece.start()..cursor)),
+                Ordering::Greater => return Err(CSTSourceError::Gap(cursor..piece.start())),
+                Ordering::Equal => {}
+            }
+            source.push_str(piece.as_str());
+            cursor = piece.end();
+        }
+        match cursor.cmp(&self.span.end()) {
+            Ordering::Less => Err(CSTSourceError::Gap(cursor..self.span.end())),
+            _ => Ok(source),
+        }
+    }
+}
+
+// What pest gives for a module, before the trivia is shared out between its expressions.
+#[derive(Debug, FromPest)]
+#[pest_ast(rule(Rule::module))]
+struct ParsedModule<'a> {
+    #[pest_ast(outer())]
+    span: Span<'a>,
+    expressions: Vec<CSTExpression<'a>>,
     _eoi: Eoi,
 }
 
+impl<'a> from_pest::FromPest<'a> for CSTModule<'a> {
+    type Rule = Rule;
+    type FatalError = Void;
+
+    fn from_pest(pest: &mut Pairs<'a, Rule>) -> Result<Self, ConversionError<Void>> {
+        let ParsedModule {
+            span, expressions, ..
+        } = ParsedModule::from_pest(pest)?;
+        let (trivia, end) =
+            CSTNodeTrivia::attach(span.get_input(), span.start()..span.end(), &expressions);
+        Ok(Self {
+            span,
+            expressions,
+            trivia,
+            end,
+        })
+    }
+}
+
 #[derive(Debug, FromPest, PartialEq, Eq, Clone)]
 #[pest_ast(rule(Rule::EOI))]
 struct Eoi;
 
+// The grammar accepts more than the CST can represent so far. When a module parses but doesn't
+// convert this finds the innermost expression that doesn't convert on its own, what it is
+// (`object instantiation`) and where it starts.
+pub fn unsupported(source: &str) -> Option<(String, usize)> {
+    let module = ElpParser::parse(Rule::module, source).ok()?.next()?;
+
+    let mut failing: Option<Pair<Rule>> = None;
+    let mut candidates = child_expressions(module);
+    while let Some(expression) = candidates.into_iter().find(|pair| !converts(pair)) {
+        candidates = child_expressions(expression.clone());
+        failing = Some(expression);
+    }
+
+    let expression = failing?;
+    let construct = expression
+        .clone()
+        .into_inner()
+        .next()
+        .map_or("this expression".into(), |inner| {
+            format!("{:?}", inner.as_rule()).replace('_', " ")
+        });
+    Some((construct, expression.as_span().start()))
+}
+
+fn child_expressions(pair: Pair<Rule>) -> Vec<Pair<Rule>> {
+    let mut expressions = vec![];
+    for child in pair.into_inner() {
+        match child.as_rule() {
+            Rule::expression => expressions.push(child),
+            _ => expressions.extend(child_expressions(child)),
+        }
+    }
+    expressions
+}
+
+fn converts(expression: &Pair<Rule>) -> bool {
+    use from_pest::FromPest;
+
+    match ElpParser::parse(Rule::expression, expression.as_str()) {
+        Ok(mut pairs) => CSTExpression::from_pest(&mut pairs).is_ok(),
+        // Can't tell on its own, blame something else.
+        Err(_) => true,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -107,6 +241,7 @@ mod tests {
         assert_eq!(
             ast,
             CSTModule {
+                span: pest::Span::new(expression_str, 0, 40).unwrap(),
                 expressions: vec![CSTExpression::Import(Box::new(CSTImport {
                     span: pest::Span::new(expression_str, 0, 40).unwrap(),
                     names: vec![
@@ -141,7 +276,8 @@ mod tests {
                         }
                     }
                 })),],
-                _eoi: Eoi {}
+                trivia: vec![CSTNodeTrivia::default()],
+                end: vec![],
             }
         )
     }
@@ -155,6 +291,7 @@ mod tests {
         assert_eq!(
             ast,
             CSTModule {
+                span: pest::Span::new(expression_str, 0, 11).unwrap(),
                 expressions: vec![CSTExpression::VariableAssignment(Box::new(
                     CSTVariableAssignment {
                         span: pest::Span::new(expression_str, 0, 11).unwrap(),
@@ -186,7 +323,8 @@ mod tests {
                             )
                     }
                 ))],
-                _eoi: Eoi {}
+                trivia: vec![CSTNodeTrivia::default()],
+                end: vec![],
             }
         )
     }
@@ -200,6 +338,7 @@ mod tests {
         assert_eq!(
             ast,
             CSTModule {
+                span: pest::Span::new(expression_str, 0, 9).unwrap(),
                 expressions: vec![CSTExpression::VariableAssignment(Box::new(
                     CSTVariableAssignment {
                         span: pest::Span::new(expression_str, 0, 9).unwrap(),
@@ -231,8 +370,106 @@ mod tests {
                             )
                     }
                 ))],
-                _eoi: Eoi {}
+                trivia: vec![CSTNodeTrivia::default()],
+                end: vec![],
             }
         )
     }
+
+    #[test]
+    fn to_source_reproduces_the_input() {
+        let sources = [
+            "",
+            "// Nothing but a comment",
+            "\n\n// Comments\n\n// and blank lines\n\n",
+            "fn main {\r\n\t// Windows line endings\r\n\tprintln(\"hi\") // and a trailing comment\r\n}\r\n",
+            "var a int\n// Between.\nconst b = \"// not \\\" a comment\" // but this is",
+            "object Point {\n\t.x int, // Across.\n\t// Between.\n\t.y int,\n}\n\n\n// The end.",
+            "  const a = 1 const b = 2  \n\t",
+            "// Ünïcode — in comments\nvar s = \"ü\"\n",
+        ];
+        for source in sources {
+            let mut pairs = ElpParser::parse(Rule::module, source).unwrap();
+            let module = CSTModule::from_pest(&mut pairs).unwrap();
+
+            assert_eq!(module.to_source(), Ok(source.to_string()));
+        }
+
+        let mut examples = 0;
+        let mut directories = vec![std::path::PathBuf::from("../../examples")];
+        while let Some(directory) = directories.pop() {
+            for entry in std::fs::read_dir(directory).unwrap().flatten() {
+                let path = entry.path();
+                if path.is_dir() {
+                    directories.push(path);
+                    continue;
+                }
+                let source = std::fs::read_to_string(&path).unwrap();
+                // Plenty of the examples use syntax the grammar or the CST doesn't have yet.
+                let Ok(mut pairs) = ElpParser::parse(Rule::module, &source) else {
+                    continue;
+                };
+                let Ok(module) = CSTModule::from_pest(&mut pairs) else {
+                    continue;
+                };
+
+                assert_eq!(module.to_source(), Ok(source), "{}", path.display());
+                examples += 1;
+            }
+        }
+        assert!(examples > 0);
+    }
+
+    fn texts<'a>(pieces: &[CSTTriviaPiece<'a>]) -> Vec<&'a str> {
+        pieces.iter().map(|piece| piece.span().as_str()).collect()
+    }
+
+    #[test]
+    fn stores_the_trivia_around_each_expression() {
+        let source = "// The greeting.
+const greeting = \"hi\" // Short.
+
+// Loud.
+var volume = 11
+// The end.
+";
+        let mut pairs = ElpParser::parse(Rule::module, source).unwrap();
+        let module = CSTModule::from_pest(&mut pairs).unwrap();
+
+        assert_eq!(
+            module
+                .trivia
+                .iter()
+                .map(|trivia| (texts(&trivia.leading), texts(&trivia.trailing)))
+                .collect::<Vec<_>>(),
+            vec![
+                (vec!["// The greeting.", "\n"], vec![" ", "// Short."]),
+                (vec!["\n\n", "// Loud.", "\n"], vec![]),
+            ]
+        );
+        assert_eq!(texts(&module.end), vec!["\n", "// The end.", "\n"]);
+    }
+
+    #[test]
+    fn pieces_that_dont_fit_are_an_error() {
+        let source = "const a = 1 // One.\nconst b = 2\n";
+        let mut pairs = ElpParser::parse(Rule::module, source).unwrap();
+        let module = CSTModule::from_pest(&mut pairs).unwrap();
+
+        let mut overlapping = module.clone();
+        let comment = overlapping.trivia[0].trailing[1].clone();
+        overlapping.trivia[1].leading.insert(0, comment);
+        assert_eq!(
+            overlapping.to_source(),
+            Err(CSTSourceError::Overlap(12..19))
+        );
+
+        let mut missing = module.clone();
+        missing.trivia[0].trailing.pop();
+        assert_eq!(missing.to_source(), Err(CSTSourceError::Gap(12..19)));
+
+        let mut short = module;
+        short.end.clear();
+        assert_eq!(short.to_source(), Err(CSTSourceError::Gap(31..32)));
+    }
 }
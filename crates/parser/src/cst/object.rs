@@ -25,6 +25,14 @@ pub struct CSTObjectMemberDefaultValue<'a> {
 #[derive(Debug, FromPest, PartialEq, Eq, Clone)]
 #[pest_ast(rule(Rule::object_key_tags))]
 pub struct CSTObjectMemberTags<'a> {
+    #[pest_ast(outer())]
+    pub span: Span<'a>,
+    pub tags: Vec<CSTObjectMemberTag<'a>>,
+}
+
+#[derive(Debug, FromPest, PartialEq, Eq, Clone)]
+#[pest_ast(rule(Rule::object_key_tag))]
+pub struct CSTObjectMemberTag<'a> {
     #[pest_ast(outer())]
     pub span: Span<'a>,
     pub name: CSTIdent<'a>,
@@ -40,7 +48,7 @@ pub struct CSTObjectMember<'a> {
     pub name: CSTIdent<'a>,
     pub type_annotation: Option<CSTElpType<'a>>,
     pub default_value: Option<CSTObjectMemberDefaultValue<'a>>,
-    pub tags: Vec<CSTObjectMemberTags<'a>>,
+    pub tags: Option<CSTObjectMemberTags<'a>>,
 }
 
 #[derive(Debug, FromPest, PartialEq, Eq, Clone)]
@@ -214,18 +222,42 @@ mod tests {
             ast,
             CSTObjectMemberTags {
                 span: pest::Span::new(expression_str, 0, 17).unwrap(),
-                name: CSTIdent {
-                    span: pest::Span::new(expression_str, 1, 5).unwrap(),
-                    value: "name".into()
-                },
-                contents: CSTString {
-                    span: pest::Span::new(expression_str, 7, 16).unwrap(),
-                    value: "example".into()
-                }
+                tags: vec![CSTObjectMemberTag {
+                    span: pest::Span::new(expression_str, 1, 16).unwrap(),
+                    name: CSTIdent {
+                        span: pest::Span::new(expression_str, 1, 5).unwrap(),
+                        value: "name".into()
+                    },
+                    contents: CSTString {
+                        span: pest::Span::new(expression_str, 7, 16).unwrap(),
+                        value: "example".into()
+                    }
+                }]
             }
         );
     }
 
+    #[test]
+    fn object_member_with_many_tags() {
+        let expression_str =
+            "`json: \"id\", db: \"post_id\", validate: \"required\", a11y: \"Post\"`";
+        let mut pairs = ElpParser::parse(Rule::object_key_tags, expression_str).unwrap();
+        let ast = CSTObjectMemberTags::from_pest(&mut pairs).unwrap();
+
+        assert_eq!(
+            ast.tags
+                .iter()
+                .map(|tag| (tag.name.value.as_str(), tag.contents.value.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("json", "id"),
+                ("db", "post_id"),
+                ("validate", "required"),
+                ("a11y", "Post")
+            ]
+        );
+    }
+
     #[test]
     fn basic_object_member() {
         let expression_str = ".name String";
@@ -255,7 +287,7 @@ mod tests {
                     })
                 }),
                 default_value: None,
-                tags: vec![]
+                tags: None
             }
         );
     }
@@ -291,7 +323,7 @@ mod tests {
                     })
                 }),
                 default_value: None,
-                tags: vec![]
+                tags: None
             }
         );
     }
@@ -327,7 +359,7 @@ mod tests {
                     })
                 }),
                 default_value: None,
-                tags: vec![]
+                tags: None
             }
         );
     }
@@ -361,17 +393,20 @@ mod tests {
                     })
                 }),
                 default_value: None,
-                tags: vec![CSTObjectMemberTags {
+                tags: Some(CSTObjectMemberTags {
                     span: pest::Span::new(expression_str, 13, 30).unwrap(),
-                    name: CSTIdent {
-                        span: pest::Span::new(expression_str, 14, 18).unwrap(),
-                        value: "name".into()
-                    },
-                    contents: CSTString {
-                        span: pest::Span::new(expression_str, 20, 29).unwrap(),
-                        value: "example".into()
-                    }
-                }]
+                    tags: vec![CSTObjectMemberTag {
+                        span: pest::Span::new(expression_str, 14, 29).unwrap(),
+                        name: CSTIdent {
+                            span: pest::Span::new(expression_str, 14, 18).unwrap(),
+                            value: "name".into()
+                        },
+                        contents: CSTString {
+                            span: pest::Span::new(expression_str, 20, 29).unwrap(),
+                            value: "example".into()
+                        }
+                    }],
+                })
             }
         );
     }
@@ -411,7 +446,7 @@ mod tests {
                         value: "example".into()
                     }))
                 }),
-                tags: vec![]
+                tags: None
             }
         );
     }
@@ -451,17 +486,20 @@ mod tests {
                         value: "example_default".into()
                     }))
                 }),
-                tags: vec![CSTObjectMemberTags {
+                tags: Some(CSTObjectMemberTags {
                     span: pest::Span::new(expression_str, 33, 50).unwrap(),
-                    name: CSTIdent {
-                        span: pest::Span::new(expression_str, 34, 38).unwrap(),
-                        value: "name".into()
-                    },
-                    contents: CSTString {
-                        span: pest::Span::new(expression_str, 40, 49).unwrap(),
-                        value: "example".into()
-                    }
-                }]
+                    tags: vec![CSTObjectMemberTag {
+                        span: pest::Span::new(expression_str, 34, 49).unwrap(),
+                        name: CSTIdent {
+                            span: pest::Span::new(expression_str, 34, 38).unwrap(),
+                            value: "name".into()
+                        },
+                        contents: CSTString {
+                            span: pest::Span::new(expression_str, 40, 49).unwrap(),
+                            value: "example".into()
+                        }
+                    }],
+                })
             }
         );
     }
@@ -502,7 +540,7 @@ mod tests {
                         })
                     }),
                     default_value: None,
-                    tags: vec![]
+                    tags: None
                 }],
             }
         );
@@ -559,7 +597,7 @@ mod tests {
                         })
                     }),
                     default_value: None,
-                    tags: vec![]
+                    tags: None
                 }],
             }
         );
@@ -631,7 +669,7 @@ mod tests {
                         })
                     }),
                     default_value: None,
-                    tags: vec![]
+                    tags: None
                 }],
             }
         );
@@ -716,17 +754,20 @@ mod tests {
                             })
                         }),
                         default_value: None,
-                        tags: vec![CSTObjectMemberTags {
+                        tags: Some(CSTObjectMemberTags {
                             span: pest::Span::new(expression_str, 72, 85).unwrap(),
-                            name: CSTIdent {
-                                span: pest::Span::new(expression_str, 73, 77).unwrap(),
-                                value: "json".into()
-                            },
-                            contents: CSTString {
-                                span: pest::Span::new(expression_str, 78, 84).unwrap(),
-                                value: "name".into()
-                            }
-                        }]
+                            tags: vec![CSTObjectMemberTag {
+                                span: pest::Span::new(expression_str, 73, 84).unwrap(),
+                                name: CSTIdent {
+                                    span: pest::Span::new(expression_str, 73, 77).unwrap(),
+                                    value: "json".into()
+                                },
+                                contents: CSTString {
+                                    span: pest::Span::new(expression_str, 78, 84).unwrap(),
+                                    value: "name".into()
+                                }
+                            }],
+                        })
                     },
                     CSTObjectMember {
                         span: pest::Span::new(expression_str, 99, 135).unwrap(),
@@ -751,17 +792,20 @@ mod tests {
                             })
                         }),
                         default_value: None,
-                        tags: vec![CSTObjectMemberTags {
+                        tags: Some(CSTObjectMemberTags {
                             span: pest::Span::new(expression_str, 123, 135).unwrap(),
-                            name: CSTIdent {
-                                span: pest::Span::new(expression_str, 124, 128).unwrap(),
-                                value: "json".into()
-                            },
-                            contents: CSTString {
-                                span: pest::Span::new(expression_str, 129, 134).unwrap(),
-                                value: "age".into()
-                            }
-                        }],
+                            tags: vec![CSTObjectMemberTag {
+                                span: pest::Span::new(expression_str, 124, 134).unwrap(),
+                                name: CSTIdent {
+                                    span: pest::Span::new(expression_str, 124, 128).unwrap(),
+                                    value: "json".into()
+                                },
+                                contents: CSTString {
+                                    span: pest::Span::new(expression_str, 129, 134).unwrap(),
+                                    value: "age".into()
+                                }
+                            }],
+                        }),
                     },
                     CSTObjectMember {
                         span: pest::Span::new(expression_str, 149, 189).unwrap(),
@@ -812,17 +856,20 @@ mod tests {
                             })
                         }),
                         default_value: None,
-                        tags: vec![CSTObjectMemberTags {
+                        tags: Some(CSTObjectMemberTags {
                             span: pest::Span::new(expression_str, 173, 189).unwrap(),
-                            name: CSTIdent {
-                                span: pest::Span::new(expression_str, 174, 178).unwrap(),
-                                value: "json".into()
-                            },
-                            contents: CSTString {
-                                span: pest::Span::new(expression_str, 179, 188).unwrap(),
-                                value: "friends".into()
-                            }
-                        }]
+                            tags: vec![CSTObjectMemberTag {
+                                span: pest::Span::new(expression_str, 174, 188).unwrap(),
+                                name: CSTIdent {
+                                    span: pest::Span::new(expression_str, 174, 178).unwrap(),
+                                    value: "json".into()
+                                },
+                                contents: CSTString {
+                                    span: pest::Span::new(expression_str, 179, 188).unwrap(),
+                                    value: "friends".into()
+                                }
+                            }],
+                        })
                     },
                     CSTObjectMember {
                         span: pest::Span::new(expression_str, 203, 245).unwrap(),
@@ -839,17 +886,20 @@ mod tests {
                                 value: "123".into()
                             }))
                         }),
-                        tags: vec![CSTObjectMemberTags {
+                        tags: Some(CSTObjectMemberTags {
                             span: pest::Span::new(expression_str, 227, 245).unwrap(),
-                            name: CSTIdent {
-                                span: pest::Span::new(expression_str, 228, 232).unwrap(),
-                                value: "json".into()
-                            },
-                            contents: CSTString {
-                                span: pest::Span::new(expression_str, 233, 244).unwrap(),
-                                value: "studentId".into()
-                            }
-                        }]
+                            tags: vec![CSTObjectMemberTag {
+                                span: pest::Span::new(expression_str, 228, 244).unwrap(),
+                                name: CSTIdent {
+                                    span: pest::Span::new(expression_str, 228, 232).unwrap(),
+                                    value: "json".into()
+                                },
+                                contents: CSTString {
+                                    span: pest::Span::new(expression_str, 233, 244).unwrap(),
+                                    value: "studentId".into()
+                                }
+                            }],
+                        })
                     }
                 ]
             }
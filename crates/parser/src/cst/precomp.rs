@@ -0,0 +1,43 @@
+use pest::Span;
+use pest_ast::FromPest;
+
+use super::{elp_type::CSTElpType, ident::CSTIdent};
+use crate::parser::Rule;
+
+// `#if(iOS)` or `#else`, a directive about whether the expression after it is compiled at all.
+#[derive(Debug, FromPest, PartialEq, Eq, Clone)]
+#[pest_ast(rule(Rule::precomp))]
+pub struct CSTPrecomp<'a> {
+    #[pest_ast(outer())]
+    pub span: Span<'a>,
+    pub name: CSTIdent<'a>,
+    pub argument: Option<CSTElpType<'a>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::ElpParser;
+    use from_pest::FromPest;
+    use pest::Parser;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn parse_precomp() {
+        let expression_str = "#if(iOS)";
+        let mut pairs = ElpParser::parse(Rule::precomp, expression_str).unwrap();
+        let ast = CSTPrecomp::from_pest(&mut pairs).unwrap();
+
+        assert_eq!(
+            ast.name,
+            CSTIdent {
+                span: Span::new(expression_str, 1, 3).unwrap(),
+                value: "if".into(),
+            }
+        );
+        assert_eq!(
+            ast.argument.map(|argument| argument.span.as_str()),
+            Some("iOS")
+        );
+    }
+}
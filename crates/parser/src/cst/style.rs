@@ -0,0 +1,258 @@
+use pest::Span;
+use pest_ast::FromPest;
+
+use crate::parser::Rule;
+
+use super::{
+    ident::CSTIdent, number_value::CSTNumber, span_into_string, string::CSTString,
+    variable_access::CSTVariableAccess, Eoi,
+};
+
+// `.selp` files, which are only style rules.
+#[derive(Debug, FromPest, PartialEq, Eq, Clone)]
+#[pest_ast(rule(Rule::stylesheet_file))]
+pub struct CSTStyleSheetFile<'a> {
+    #[pest_ast(outer())]
+    pub span: Span<'a>,
+    pub rules: Vec<CSTStyleRule<'a>>,
+    _eoi: Eoi,
+}
+
+// `Stylesheet { .. }` in a module.
+#[derive(Debug, FromPest, PartialEq, Eq, Clone)]
+#[pest_ast(rule(Rule::stylesheet))]
+pub struct CSTStyleSheet<'a> {
+    #[pest_ast(outer())]
+    pub span: Span<'a>,
+    pub rules: Vec<CSTStyleRule<'a>>,
+}
+
+#[derive(Debug, FromPest, PartialEq, Eq, Clone)]
+#[pest_ast(rule(Rule::style_rule))]
+pub struct CSTStyleRule<'a> {
+    #[pest_ast(outer())]
+    pub span: Span<'a>,
+    pub selectors: Vec<CSTStyleSelector<'a>>,
+    pub properties: Vec<CSTStyleProperty<'a>>,
+}
+
+// `Text`, `.title` or both at once, `Text.title`.
+#[derive(Debug, FromPest, PartialEq, Eq, Clone)]
+#[pest_ast(rule(Rule::style_selector))]
+pub struct CSTStyleSelector<'a> {
+    #[pest_ast(outer())]
+    pub span: Span<'a>,
+    pub component: Option<CSTIdent<'a>>,
+    pub classes: Vec<CSTStyleClass<'a>>,
+}
+
+#[derive(Debug, FromPest, PartialEq, Eq, Clone)]
+#[pest_ast(rule(Rule::style_class))]
+pub struct CSTStyleClass<'a> {
+    #[pest_ast(outer())]
+    pub span: Span<'a>,
+    pub name: CSTIdent<'a>,
+}
+
+#[derive(Debug, FromPest, PartialEq, Eq, Clone)]
+#[pest_ast(rule(Rule::style_property))]
+pub struct CSTStyleProperty<'a> {
+    #[pest_ast(outer())]
+    pub span: Span<'a>,
+    pub name: CSTIdent<'a>,
+    pub value: CSTStyleValue<'a>,
+}
+
+#[derive(Debug, FromPest, PartialEq, Eq, Clone)]
+#[pest_ast(rule(Rule::style_value))]
+pub enum CSTStyleValue<'a> {
+    Colour(CSTStyleColour<'a>),
+    Length(CSTStyleLength<'a>),
+    Number(CSTNumber<'a>),
+    String(CSTString<'a>),
+    EnumCase(CSTStyleEnumCase<'a>),
+    VariableAccess(CSTVariableAccess<'a>),
+}
+
+// `#ff8800`, the `#` included.
+#[derive(Debug, FromPest, PartialEq, Eq, Clone)]
+#[pest_ast(rule(Rule::style_colour))]
+pub struct CSTStyleColour<'a> {
+    #[pest_ast(outer())]
+    pub span: Span<'a>,
+    #[pest_ast(outer(with(span_into_string)))]
+    pub value: String,
+}
+
+// `16px`, `1.5rem`, `50%`.
+#[derive(Debug, FromPest, PartialEq, Eq, Clone)]
+#[pest_ast(rule(Rule::style_length))]
+pub struct CSTStyleLength<'a> {
+    #[pest_ast(outer())]
+    pub span: Span<'a>,
+    pub number: CSTNumber<'a>,
+    pub unit: CSTStyleUnit<'a>,
+}
+
+#[derive(Debug, FromPest, PartialEq, Eq, Clone)]
+#[pest_ast(rule(Rule::style_unit))]
+pub struct CSTStyleUnit<'a> {
+    #[pest_ast(outer())]
+    pub span: Span<'a>,
+    #[pest_ast(outer(with(span_into_string)))]
+    pub value: String,
+}
+
+// `.SEMIBOLD`, a case of whichever enum the property takes.
+#[derive(Debug, FromPest, PartialEq, Eq, Clone)]
+#[pest_ast(rule(Rule::style_enum_case))]
+pub struct CSTStyleEnumCase<'a> {
+    #[pest_ast(outer())]
+    pub span: Span<'a>,
+    pub name: CSTIdent<'a>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::ElpParser;
+    use from_pest::FromPest;
+    use pest::Parser;
+    use pretty_assertions::assert_eq;
+
+    fn ident(input: &str, start: usize, end: usize) -> CSTIdent<'_> {
+        CSTIdent {
+            span: Span::new(input, start, end).unwrap(),
+            value: input[start..end].into(),
+        }
+    }
+
+    #[test]
+    fn compound_selectors() {
+        let expression_str = "Text.title.large";
+        let mut pairs = ElpParser::parse(Rule::style_selector, expression_str).unwrap();
+        let ast = CSTStyleSelector::from_pest(&mut pairs).unwrap();
+
+        assert_eq!(
+            ast,
+            CSTStyleSelector {
+                span: Span::new(expression_str, 0, 16).unwrap(),
+                component: Some(ident(expression_str, 0, 4)),
+                classes: vec![
+                    CSTStyleClass {
+                        span: Span::new(expression_str, 4, 10).unwrap(),
+                        name: ident(expression_str, 5, 10),
+                    },
+                    CSTStyleClass {
+                        span: Span::new(expression_str, 10, 16).unwrap(),
+                        name: ident(expression_str, 11, 16),
+                    },
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn rule_with_a_selector_list() {
+        let expression_str = "Text, .note { .padding = 16px, .fontWeight = .SEMIBOLD }";
+        let mut pairs = ElpParser::parse(Rule::style_rule, expression_str).unwrap();
+        let ast = CSTStyleRule::from_pest(&mut pairs).unwrap();
+
+        assert_eq!(
+            ast,
+            CSTStyleRule {
+                span: Span::new(expression_str, 0, 56).unwrap(),
+                selectors: vec![
+                    CSTStyleSelector {
+                        span: Span::new(expression_str, 0, 4).unwrap(),
+                        component: Some(ident(expression_str, 0, 4)),
+                        classes: vec![],
+                    },
+                    CSTStyleSelector {
+                        span: Span::new(expression_str, 6, 11).unwrap(),
+                        component: None,
+                        classes: vec![CSTStyleClass {
+                            span: Span::new(expression_str, 6, 11).unwrap(),
+                            name: ident(expression_str, 7, 11),
+                        }],
+                    },
+                ],
+                properties: vec![
+                    CSTStyleProperty {
+                        span: Span::new(expression_str, 14, 29).unwrap(),
+                        name: ident(expression_str, 15, 22),
+                        value: CSTStyleValue::Length(CSTStyleLength {
+                            span: Span::new(expression_str, 25, 29).unwrap(),
+                            number: CSTNumber {
+                                span: Span::new(expression_str, 25, 27).unwrap(),
+                                value: "16".into(),
+                            },
+                            unit: CSTStyleUnit {
+                                span: Span::new(expression_str, 27, 29).unwrap(),
+                                value: "px".into(),
+                            },
+                        }),
+                    },
+                    CSTStyleProperty {
+                        span: Span::new(expression_str, 31, 54).unwrap(),
+                        name: ident(expression_str, 32, 42),
+                        value: CSTStyleValue::EnumCase(CSTStyleEnumCase {
+                            span: Span::new(expression_str, 45, 54).unwrap(),
+                            name: ident(expression_str, 46, 54),
+                        }),
+                    },
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn colours_and_units() {
+        let value = |input: &'static str| {
+            let mut pairs = ElpParser::parse(Rule::style_value, input).unwrap();
+            CSTStyleValue::from_pest(&mut pairs).unwrap()
+        };
+
+        assert_eq!(
+            value("#ff8800"),
+            CSTStyleValue::Colour(CSTStyleColour {
+                span: Span::new("#ff8800", 0, 7).unwrap(),
+                value: "#ff8800".into(),
+            })
+        );
+        assert!(matches!(
+            value("1.5rem"),
+            CSTStyleValue::Length(CSTStyleLength { unit, .. }) if unit.value == "rem"
+        ));
+        assert!(matches!(
+            value("50%"),
+            CSTStyleValue::Length(CSTStyleLength { unit, .. }) if unit.value == "%"
+        ));
+        assert!(matches!(value("16"), CSTStyleValue::Number(_)));
+        assert!(matches!(
+            value("FontWeight.BOLD"),
+            CSTStyleValue::VariableAccess(_)
+        ));
+    }
+
+    #[test]
+    fn stylesheet_files() {
+        let source = include_str!("../../../../examples/BlogStyle/styles.selp");
+        let mut pairs = ElpParser::parse(Rule::stylesheet_file, source).unwrap();
+        let ast = CSTStyleSheetFile::from_pest(&mut pairs).unwrap();
+
+        let selectors: Vec<&str> = ast
+            .rules
+            .iter()
+            .flat_map(|rule| rule.selectors.iter().map(|selector| selector.span.as_str()))
+            .collect();
+        assert_eq!(selectors, vec!["App", "Text"]);
+    }
+
+    #[test]
+    fn properties_need_commas_between_them() {
+        let source = "Stylesheet { Text { .fontWeight = FontWeight.BOLD .padding = 4 } }";
+
+        assert!(ElpParser::parse(Rule::stylesheet, source).is_err());
+    }
+}
@@ -0,0 +1,702 @@
+// Comments and blank lines aren't part of the grammar (`COMMENT` and `WHITESPACE` are silent) so
+// they never turn up as pairs. They can only sit in the gaps between tokens though, and a gap
+// never holds a string, so every `//` in one starts a comment that runs to the end of its line.
+//
+// There are two views of them here. `CSTTriviaPiece`s are the exact bytes around a node. The
+// module's expressions each have theirs stored in a `CSTNodeTrivia` when the CST is built, sharing
+// out the gaps between them, and those are what `CSTModule::to_source` puts back together. Nodes
+// inside others find theirs from their span through `CSTNode`. `CSTGap` is the formatter's view,
+// which only cares which comments there are and where the blank lines were.
+
+use std::ops::Range;
+
+use pest::Span;
+
+use super::{
+    block::CSTBlock,
+    component::{CSTComponentDef, CSTFunctionComponentCall},
+    elp_type::{
+        CSTElpType, CSTElpTypeArray, CSTElpTypeGeneric, CSTElpTypeGenericConstraint,
+        CSTElpTypeGenericParam, CSTElpTypeParameter, CSTElpTypeValue,
+    },
+    export::CSTExport,
+    expression::CSTExpression,
+    external::{CSTExternalAbi, CSTExternalBlock, CSTExternalItem, CSTExternalSymbol},
+    for_loop::CSTForLoop,
+    function::{
+        CSTFunctionArgument, CSTFunctionArguments, CSTFunctionCall, CSTFunctionCallName,
+        CSTFunctionDef, CSTFunctionHeaderDef, CSTFunctionReturnType, CSTFunctionReturnValue,
+    },
+    ident::CSTIdent,
+    if_tree::{
+        CSTAndLogicConditional, CSTElseIfBranch, CSTIfTree, CSTIsLogicConditional,
+        CSTLogicConditional, CSTNotLogicConditional, CSTOrLogicConditional,
+    },
+    import::{CSTImport, CSTImportModulePath, CSTImportName, CSTImportNameAlias},
+    interface::{CSTInterface, CSTInterfaceMember, CSTInterfaceMemberKeyValue},
+    number_value::CSTNumber,
+    object::{
+        CSTObject, CSTObjectImplements, CSTObjectMember, CSTObjectMemberDefaultValue,
+        CSTObjectMemberTag, CSTObjectMemberTags,
+    },
+    precomp::CSTPrecomp,
+    r#enum::{CSTEnum, CSTEnumMember},
+    r#macro::CSTMacro,
+    r#match::{CSTMatchArmSubject, CSTMatchBody, CSTMatchRange, CSTMatchTree, CSTMatchTreeArm},
+    string::CSTString,
+    style::{
+        CSTStyleClass, CSTStyleColour, CSTStyleEnumCase, CSTStyleLength, CSTStyleProperty,
+        CSTStyleRule, CSTStyleSelector, CSTStyleSheet, CSTStyleSheetFile, CSTStyleUnit,
+        CSTStyleValue,
+    },
+    unary::{
+        CSTUnaryDivide, CSTUnaryMinus, CSTUnaryModulo, CSTUnaryMultiply, CSTUnaryOperator,
+        CSTUnaryPlus, CSTUnaryPower,
+    },
+    value_assignment::{
+        CSTBitAnd, CSTBitwiseOperand, CSTDivide, CSTEqualityBitNot, CSTEqualityEqual,
+        CSTEqualityNot, CSTEquals, CSTLeftShift, CSTMinus, CSTModulo, CSTMultiply, CSTOperand,
+        CSTOr, CSTPlus, CSTPower, CSTRightShift, CSTTilde, CSTValueAssignment,
+    },
+    variable_access::{
+        CSTContextualVariableAccess, CSTPointer, CSTPointerSemantics, CSTReference,
+        CSTVariableAccess, CSTVariableAccessNames,
+    },
+    variable_assignment::{CSTVariableAssignment, CSTVariableAssignmentTarget},
+    variable_declaration::CSTVariableDeclaration,
+    CSTMutabilitySelector, CSTPrivateVisibility, CSTPublicVisibility, CSTVisibilitySelector, Const,
+    Var,
+};
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum CSTTriviaPiece<'a> {
+    // A run of spaces, tabs and line breaks.
+    Whitespace(Span<'a>),
+    // From the slashes up to, but not including, the line break.
+    Comment(Span<'a>),
+    // Anything else the CST drops between nodes, like the parentheses around an expression.
+    Punctuation(Span<'a>),
+}
+
+impl<'a> CSTTriviaPiece<'a> {
+    pub fn span(&self) -> Span<'a> {
+        match self {
+            CSTTriviaPiece::Whitespace(span)
+            | CSTTriviaPiece::Comment(span)
+            | CSTTriviaPiece::Punctuation(span) => *span,
+        }
+    }
+
+    // Splits `start..end` of `input` into pieces, which between them cover every byte of it.
+    pub fn lex(input: &'a str, start: usize, end: usize) -> Vec<Self> {
+        let mut pieces = vec![];
+        let mut offset = start;
+        while offset < end {
+            let rest = &input[offset..end];
+            let (length, piece): (usize, fn(Span<'a>) -> Self) = if rest.starts_with("//") {
+                (
+                    rest.find('\n').unwrap_or(rest.len()),
+                    CSTTriviaPiece::Comment,
+                )
+            } else if rest.starts_with(|c: char| c.is_ascii_whitespace()) {
+                (
+                    rest.find(|c: char| !c.is_ascii_whitespace())
+                        .unwrap_or(rest.len()),
+                    CSTTriviaPiece::Whitespace,
+                )
+            } else {
+                let punctuation = rest
+                    .char_indices()
+                    .find(|(offset, c)| {
+                        c.is_ascii_whitespace() || rest[*offset..].starts_with("//")
+                    })
+                    .map_or(rest.len(), |(offset, _)| offset);
+                (punctuation, CSTTriviaPiece::Punctuation)
+            };
+
+            let span = Span::new(input, offset, offset + length).expect("lexed inside the input");
+            pieces.push(piece(span));
+            offset += length;
+        }
+
+        pieces
+    }
+}
+
+// The trivia belonging to one of a run of nodes. Its trailing trivia is the rest of the line the
+// node ends on, comment and all, and its leading trivia everything back from there to the node
+// before.
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct CSTNodeTrivia<'a> {
+    pub leading: Vec<CSTTriviaPiece<'a>>,
+    pub trailing: Vec<CSTTriviaPiece<'a>>,
+}
+
+impl<'a> CSTNodeTrivia<'a> {
+    // Shares out `range` of `input` between `nodes`, which come in order. What's after the line
+    // the last one ends on is left over, for whatever holds them.
+    pub fn attach(
+        input: &'a str,
+        range: Range<usize>,
+        nodes: &[impl CSTNode<'a>],
+    ) -> (Vec<Self>, Vec<CSTTriviaPiece<'a>>) {
+        let mut cursor = range.start;
+        let mut attached = vec![];
+        for (index, node) in nodes.iter().enumerate() {
+            let code = node.code_span();
+            let leading = CSTTriviaPiece::lex(input, cursor, code.start());
+
+            let next = nodes
+                .get(index + 1)
+                .map_or(range.end, |next| next.code_span().start())
+                .max(code.end());
+            cursor = input[code.end()..next]
+                .find('\n')
+                .map_or(next, |newline| code.end() + newline);
+            let trailing = CSTTriviaPiece::lex(input, code.end(), cursor);
+            attached.push(Self { leading, trailing });
+        }
+
+        (attached, CSTTriviaPiece::lex(input, cursor, range.end))
+    }
+}
+
+// Anything in the CST, which all know where they came from and so what's around them.
+pub trait CSTNode<'a> {
+    fn span(&self) -> Span<'a>;
+
+    // Pest skips whitespace and comments before trying an optional part of a rule and keeps them
+    // even when the part isn't there, so a node ending in one has the trivia after it in its span.
+    // This is the span without it, up to the end of the last token.
+    fn code_span(&self) -> Span<'a> {
+        let span = self.span();
+        let mut end = span.start();
+        let mut chars = span.as_str().char_indices().peekable();
+        while let Some((offset, c)) = chars.next() {
+            match c {
+                '"' => {
+                    while let Some((offset, c)) = chars.next() {
+                        end = span.start() + offset + c.len_utf8();
+                        match c {
+                            '\\' => {
+                                chars.next();
+                            }
+                            '"' => break,
+                            _ => {}
+                        }
+                    }
+                }
+                '/' if chars.peek().is_some_and(|(_, c)| *c == '/') => {
+                    for (_, c) in chars.by_ref() {
+                        if c == '\n' {
+                            break;
+                        }
+                    }
+                }
+                c if c.is_ascii_whitespace() => {}
+                c => end = span.start() + offset + c.len_utf8(),
+            }
+        }
+
+        Span::new(span.get_input(), span.start(), end).expect("inside the node's span")
+    }
+
+    // The comments and blank lines above the node, up to the end of the line the code before it
+    // finishes on, along with its indentation. A comment on the end of that line trails the code
+    // there instead.
+    //
+    // Unlike everything else this is found walking backwards, and a string can hold a line that
+    // looks like a comment, so the closing line of a multiline string that starts with `//` would
+    // be taken as one.
+    fn leading_trivia(&self) -> Vec<CSTTriviaPiece<'a>> {
+        let span = self.span();
+        let input = span.get_input();
+        let is_blank = |c: char| c == ' ' || c == '\t' || c == '\r';
+
+        let mut start = input[..span.start()].trim_end_matches(is_blank).len();
+        while let Some(above) = input[..start].strip_suffix('\n') {
+            let line_start = above.rfind('\n').map_or(0, |newline| newline + 1);
+            let line = above[line_start..].trim();
+            if !line.is_empty() && !line.starts_with("//") {
+                start = above.trim_end_matches('\r').len();
+                break;
+            }
+            start = line_start;
+        }
+
+        CSTTriviaPiece::lex(input, start, span.start())
+    }
+
+    // A comment on the same line after the node, looking past the comma that separates it from
+    // whatever comes next.
+    fn trailing_trivia(&self) -> Vec<CSTTriviaPiece<'a>> {
+        let span = self.code_span();
+        let input = span.get_input();
+        let is_blank = |c: char| c == ' ' || c == '\t';
+
+        let mut start = span.end();
+        let rest = input[start..].trim_start_matches(is_blank);
+        let rest = match rest.strip_prefix(',') {
+            Some(rest) => {
+                start = input.len() - rest.len();
+                rest
+            }
+            None => rest,
+        };
+        match rest.trim_start_matches(is_blank).starts_with("//") {
+            true => {
+                let end = start + input[start..].find('\n').unwrap_or(input.len() - start);
+                CSTTriviaPiece::lex(input, start, end)
+            }
+            false => vec![],
+        }
+    }
+
+    // The `///` lines directly above the node with the slashes, and the space after them, taken
+    // off. They go above `export` and any `@` annotations in front of the node. A blank line or
+    // an ordinary comment in between means they're about something else.
+    fn doc_comment(&self) -> Option<String> {
+        let span = self.span();
+        let input = span.get_input();
+        let is_blank = |c: char| c == ' ' || c == '\t' || c == '\r';
+
+        let mut start = input[..span.start()].trim_end_matches(is_blank).len();
+        if let Some(before) = input[..start].strip_suffix("export") {
+            if !before.ends_with(|c: char| c.is_ascii_alphanumeric() || c == '_') {
+                start = before.trim_end_matches(is_blank).len();
+            }
+        }
+
+        let mut lines = vec![];
+        while let Some(above) = input[..start].strip_suffix('\n') {
+            let line_start = above.rfind('\n').map_or(0, |newline| newline + 1);
+            let line = above[line_start..].trim();
+            match line.strip_prefix("///") {
+                Some(doc) if !doc.starts_with('/') => {
+                    lines.push(doc.strip_prefix(' ').unwrap_or(doc))
+                }
+                None if line.starts_with('@') => {}
+                _ => break,
+            }
+            start = line_start;
+        }
+
+        match lines.is_empty() {
+            true => None,
+            false => Some(lines.into_iter().rev().collect::<Vec<_>>().join("\n")),
+        }
+    }
+
+    fn comments(&self) -> Vec<&'a str> {
+        self.leading_trivia()
+            .into_iter()
+            .chain(self.trailing_trivia())
+            .filter_map(|piece| match piece {
+                CSTTriviaPiece::Comment(span) => Some(span.as_str()),
+                CSTTriviaPiece::Whitespace(_) | CSTTriviaPiece::Punctuation(_) => None,
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum CSTTrivia {
+    // A comment on its own line, slashes included and trailing whitespace trimmed.
+    Comment(String),
+    // One or more empty lines, they're only ever worth keeping one of.
+    BlankLine,
+}
+
+// Everything between two nodes other than punctuation.
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct CSTGap {
+    // A comment on the same line as the node before the gap.
+    pub trailing: Option<String>,
+    // What comes before the node after the gap, in order.
+    pub leading: Vec<CSTTrivia>,
+    pub has_newline: bool,
+}
+
+impl CSTGap {
+    // `after_node` is false at the start of a file or just inside a delimiter, where a comment on
+    // the first line has nothing to trail.
+    pub fn scan(gap: &str, after_node: bool) -> Self {
+        let lines: Vec<&str> = gap.split('\n').collect();
+        let mut scanned = CSTGap {
+            has_newline: lines.len() > 1,
+            ..Default::default()
+        };
+
+        for (index, line) in lines.iter().enumerate() {
+            let comment = line
+                .find("//")
+                .map(|start| line[start..].trim_end().to_string());
+            match comment {
+                Some(comment) if index == 0 && after_node => scanned.trailing = Some(comment),
+                Some(comment) => scanned.leading.push(CSTTrivia::Comment(comment)),
+                // Only whole lines can be blank, the first and last are shared with tokens.
+                None if index > 0
+                    && index < lines.len() - 1
+                    && line.trim().is_empty()
+                    && scanned.leading.last() != Some(&CSTTrivia::BlankLine) =>
+                {
+                    scanned.leading.push(CSTTrivia::BlankLine)
+                }
+                None => {}
+            }
+        }
+
+        scanned
+    }
+
+    pub fn comments(&self) -> impl Iterator<Item = &str> {
+        self.trailing
+            .iter()
+            .map(String::as_str)
+            .chain(self.leading.iter().filter_map(|trivia| match trivia {
+                CSTTrivia::Comment(comment) => Some(comment.as_str()),
+                CSTTrivia::BlankLine => None,
+            }))
+    }
+}
+
+macro_rules! cst_nodes {
+    ($($node:ident),* $(,)?) => {
+        $(
+            impl<'a> CSTNode<'a> for $node<'a> {
+                fn span(&self) -> Span<'a> {
+                    self.span
+                }
+            }
+        )*
+    };
+}
+
+macro_rules! cst_node_enums {
+    ($($node:ident { $($variant:ident),* $(,)? })*) => {
+        $(
+            impl<'a> CSTNode<'a> for $node<'a> {
+                fn span(&self) -> Span<'a> {
+                    match self {
+                        $($node::$variant(node) => node.span(),)*
+                    }
+                }
+            }
+        )*
+    };
+}
+
+cst_nodes!(
+    CSTAndLogicConditional,
+    CSTBitAnd,
+    CSTBlock,
+    CSTComponentDef,
+    CSTContextualVariableAccess,
+    CSTDivide,
+    CSTElpType,
+    CSTElpTypeArray,
+    CSTElpTypeGeneric,
+    CSTElpTypeGenericConstraint,
+    CSTElpTypeGenericParam,
+    CSTElpTypeParameter,
+    CSTElseIfBranch,
+    CSTEnum,
+    CSTEnumMember,
+    CSTEqualityBitNot,
+    CSTEqualityEqual,
+    CSTEqualityNot,
+    CSTEquals,
+    CSTExport,
+    CSTExternalAbi,
+    CSTExternalBlock,
+    CSTExternalSymbol,
+    CSTForLoop,
+    CSTFunctionArgument,
+    CSTFunctionArguments,
+    CSTFunctionCall,
+    CSTFunctionComponentCall,
+    CSTFunctionDef,
+    CSTFunctionHeaderDef,
+    CSTFunctionReturnType,
+    CSTFunctionReturnValue,
+    CSTIdent,
+    CSTIfTree,
+    CSTImport,
+    CSTImportModulePath,
+    CSTImportName,
+    CSTImportNameAlias,
+    CSTInterface,
+    CSTInterfaceMemberKeyValue,
+    CSTIsLogicConditional,
+    CSTLeftShift,
+    CSTMacro,
+    CSTMatchRange,
+    CSTMatchTree,
+    CSTMatchTreeArm,
+    CSTMinus,
+    CSTModulo,
+    CSTMultiply,
+    CSTNotLogicConditional,
+    CSTNumber,
+    CSTObject,
+    CSTObjectImplements,
+    CSTObjectMember,
+    CSTObjectMemberDefaultValue,
+    CSTObjectMemberTag,
+    CSTObjectMemberTags,
+    CSTOr,
+    CSTOrLogicConditional,
+    CSTPlus,
+    CSTPointer,
+    CSTPower,
+    CSTPrecomp,
+    CSTPrivateVisibility,
+    CSTPublicVisibility,
+    CSTReference,
+    CSTRightShift,
+    CSTString,
+    CSTStyleClass,
+    CSTStyleColour,
+    CSTStyleEnumCase,
+    CSTStyleLength,
+    CSTStyleProperty,
+    CSTStyleRule,
+    CSTStyleSelector,
+    CSTStyleSheet,
+    CSTStyleSheetFile,
+    CSTStyleUnit,
+    CSTTilde,
+    CSTUnaryDivide,
+    CSTUnaryMinus,
+    CSTUnaryModulo,
+    CSTUnaryMultiply,
+    CSTUnaryPlus,
+    CSTUnaryPower,
+    CSTValueAssignment,
+    CSTVariableAccess,
+    CSTVariableAccessNames,
+    CSTVariableAssignment,
+    CSTVariableDeclaration,
+    Const,
+    Var,
+);
+
+cst_node_enums! {
+    CSTBitwiseOperand { Tilde, LeftShift, RightShift, BitOr, BitAnd }
+    CSTElpTypeValue { Array, Parameter }
+    CSTExternalItem { Function, Object }
+    CSTExpression {
+        Block, Component, ElpType, Enum, Export, ExternalBlock, ExternalSymbol, FunctionCall, FunctionComponentCall, FunctionDef,
+        FunctionHeaderDef, FunctionReturnValue, Ident, Import, IfTree, Interface, Macro, Match, Number,
+        Object, Operand, PointerSemantics, Precomp, String, StyleSheet, Unary, ValueAssignment, VariableAccess,
+        VariableAssignment, VariableDeclaration,
+    }
+    CSTFunctionCallName { VariableAccess, ContextualVariableAccess }
+    CSTInterfaceMember { Field, Method }
+    CSTLogicConditional { Or, Not, Is, And }
+    CSTMatchArmSubject { Expression, MatchRange }
+    CSTMatchBody { Expression, Block }
+    CSTMutabilitySelector { Mutable, Immutable }
+    CSTOperand {
+        BitAnd, Divide, EqualityBitNot, EqualityEqual, EqualityNot, Equals, Minus, Modulo,
+        Multiply, Plus, Power,
+    }
+    CSTPointerSemantics { Pointer, Reference }
+    CSTStyleValue { Colour, Length, Number, String, EnumCase, VariableAccess }
+    CSTUnaryOperator { Plus, Minus, Multiply, Divide, Power, Modulo }
+    CSTVariableAssignmentTarget { VariableDeclaration, VariableAccess }
+    CSTVisibilitySelector { Public, Private }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cst::CSTModule,
+        parser::{ElpParser, Rule},
+    };
+    use from_pest::FromPest;
+    use pest::Parser;
+    use pretty_assertions::assert_eq;
+
+    fn texts<'a>(pieces: &[CSTTriviaPiece<'a>]) -> Vec<&'a str> {
+        pieces.iter().map(|piece| piece.span().as_str()).collect()
+    }
+
+    #[test]
+    fn trailing_and_leading_comments() {
+        let gap = CSTGap::scan(", // first\n\n\n\t// second\n\t,  // third\n\t", true);
+
+        assert_eq!(
+            gap,
+            CSTGap {
+                trailing: Some("// first".into()),
+                leading: vec![
+                    CSTTrivia::BlankLine,
+                    CSTTrivia::Comment("// second".into()),
+                    CSTTrivia::Comment("// third".into()),
+                ],
+                has_newline: true,
+            }
+        );
+        assert_eq!(
+            gap.comments().collect::<Vec<_>>(),
+            vec!["// first", "// second", "// third"]
+        );
+    }
+
+    #[test]
+    fn nothing_to_trail_at_the_start() {
+        let gap = CSTGap::scan(" // opening\n", false);
+
+        assert_eq!(gap.trailing, None);
+        assert_eq!(gap.leading, vec![CSTTrivia::Comment("// opening".into())]);
+        assert_eq!(CSTGap::scan(" ", true), CSTGap::default());
+    }
+
+    #[test]
+    fn lexes_whitespace_and_comments() {
+        let input = "( \t// one\r\n\n// two)\n)";
+        let pieces = CSTTriviaPiece::lex(input, 1, input.len() - 1);
+
+        assert_eq!(
+            texts(&pieces),
+            vec![" \t", "// one\r", "\n\n", "// two)", "\n"]
+        );
+        assert!(matches!(pieces[1], CSTTriviaPiece::Comment(_)));
+        assert!(matches!(pieces[2], CSTTriviaPiece::Whitespace(_)));
+
+        // Nothing is left out, the CST's punctuation included.
+        let pieces = CSTTriviaPiece::lex(input, 0, input.len());
+        assert_eq!(
+            pieces.first(),
+            Some(&CSTTriviaPiece::Punctuation(
+                Span::new(input, 0, 1).unwrap()
+            ))
+        );
+        assert_eq!(texts(&pieces).concat(), input);
+    }
+
+    #[test]
+    fn comments_around_nodes() {
+        let source = "import { Text } from \"elp/components\" // Only what's used.
+
+// Components are a visual element that can be presented to the user.
+// Behind the scenes though this is just an object.
+export component LoginForm {
+\t// Exports in a component are considered the props of the component.
+\texport var email Optional<string> // Optional, so not required.
+
+\t// They also have their own private variables.
+
+\t// Note that a variable is just that, it's variable in nature so it is mutable.
+\tvar attempts = 0
+}
+";
+        let mut pairs = ElpParser::parse(Rule::module, source).unwrap();
+        let module = CSTModule::from_pest(&mut pairs).unwrap();
+
+        assert_eq!(
+            texts(&module.expressions[0].leading_trivia()),
+            Vec::<&str>::new()
+        );
+        assert_eq!(
+            module.expressions[0].comments(),
+            vec!["// Only what's used."]
+        );
+        assert_eq!(
+            texts(&module.expressions[1].leading_trivia()),
+            vec![
+                "\n\n",
+                "// Components are a visual element that can be presented to the user.",
+                "\n",
+                "// Behind the scenes though this is just an object.",
+                "\n"
+            ]
+        );
+
+        let CSTExpression::Export(export) = &module.expressions[1] else {
+            panic!("expected an export, got {:?}", module.expressions[1]);
+        };
+        let CSTExpression::Component(component) = &export.expression else {
+            panic!("expected a component, got {:?}", export.expression);
+        };
+        // The component starts after `export` so there's nothing in front of it.
+        assert_eq!(component.comments(), Vec::<&str>::new());
+
+        let members = &component.block.expressions;
+        assert_eq!(
+            members[0].comments(),
+            vec![
+                "// Exports in a component are considered the props of the component.",
+                "// Optional, so not required."
+            ]
+        );
+        assert_eq!(
+            texts(&members[1].leading_trivia()),
+            vec![
+                "\n\n\t",
+                "// They also have their own private variables.",
+                "\n\n\t",
+                "// Note that a variable is just that, it's variable in nature so it is mutable.",
+                "\n\t"
+            ]
+        );
+        assert_eq!(members[1].trailing_trivia(), vec![]);
+    }
+
+    #[test]
+    fn trailing_comments_after_separators() {
+        let source = "object Point {\n\t.x int, // Across.\n\t.y int // Down.\n}";
+        let mut pairs = ElpParser::parse(Rule::module, source).unwrap();
+        let module = CSTModule::from_pest(&mut pairs).unwrap();
+
+        let CSTExpression::Object(object) = &module.expressions[0] else {
+            panic!("expected an object, got {:?}", module.expressions[0]);
+        };
+        assert_eq!(
+            texts(&object.members[0].trailing_trivia()),
+            vec![" ", "// Across."]
+        );
+        assert_eq!(texts(&object.members[1].leading_trivia()), vec!["\n\t"]);
+        // Pest took the comment into the member while looking for a default value.
+        assert_eq!(object.members[1].span().as_str(), ".y int // Down.\n");
+        assert_eq!(object.members[1].code_span().as_str(), ".y int");
+        assert_eq!(object.members[1].comments(), vec!["// Down."]);
+    }
+
+    #[test]
+    fn doc_comments() {
+        let source = "/// Not about anything.
+
+/// A point on the screen.
+///
+///     Point { .x = 1, .y = 2 }
+export object Point {
+\t/// Across.
+\t.x int,
+\t// Not a doc comment.
+\t.y int,
+\t//// Nor is this.
+\t.z int,
+}
+";
+        let mut pairs = ElpParser::parse(Rule::module, source).unwrap();
+        let module = CSTModule::from_pest(&mut pairs).unwrap();
+
+        let CSTExpression::Export(export) = &module.expressions[0] else {
+            panic!("expected an export, got {:?}", module.expressions[0]);
+        };
+        let CSTExpression::Object(object) = &export.expression else {
+            panic!("expected an object, got {:?}", export.expression);
+        };
+        assert_eq!(
+            object.doc_comment().as_deref(),
+            Some("A point on the screen.\n\n    Point { .x = 1, .y = 2 }")
+        );
+        assert_eq!(object.members[0].doc_comment().as_deref(), Some("Across."));
+        assert_eq!(object.members[1].doc_comment(), None);
+        assert_eq!(object.members[2].doc_comment(), None);
+        assert_eq!(export.doc_comment(), object.doc_comment());
+    }
+}
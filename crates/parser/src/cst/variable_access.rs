@@ -130,6 +130,18 @@ mod tests {
         )
     }
 
+    #[test]
+    fn names_starting_with_a_keyword() {
+        for expression_str in ["assert_equal", "island", "order", "constant", "iffy"] {
+            let mut pairs = ElpParser::parse(Rule::variable_access, expression_str).unwrap();
+            let ast = CSTVariableAccess::from_pest(&mut pairs).unwrap();
+
+            assert_eq!(ast.names.names[0].value, expression_str);
+        }
+
+        assert!(ElpParser::parse(Rule::variable_access, "as").is_err());
+    }
+
     #[test]
     fn variable_access_with_pointer_semantics() {
         let expression_str_reference = "&hello.world.my.name.is.dave";
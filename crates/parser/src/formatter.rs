@@ -0,0 +1,1477 @@
+// The canonical layout for elp source, used by `elp fmt`. It works on the pest parse tree rather
+// than the typed CST so that everything the grammar accepts can be printed, and picks comments back
+// up from the gaps between nodes (see `cst::trivia`) so none of them are lost.
+//
+// The rules, roughly: tabs for indentation, statements on the lines they were written on with at
+// most one blank line between them, enum, object, interface and match members one per line with
+// object and interface columns aligned, a trailing comma wherever the grammar allows one and a list
+// is split over lines, imports sorted, and other lists kept on one line when they were written that
+// way and still fit in `MAX_WIDTH` columns. Formatting formatted source changes nothing.
+
+use std::ops::Range;
+
+use pest::{error::Error, iterators::Pair, Parser};
+
+use crate::{
+    cst::trivia::{CSTGap, CSTTrivia},
+    parser::{ElpParser, Rule},
+};
+
+pub const MAX_WIDTH: usize = 100;
+const TAB_WIDTH: usize = 4;
+// Stands in for newlines inside string literals until the very end so indenting never touches
+// what's inside them.
+const STRING_NEWLINE: char = '\u{0}';
+
+pub type FormatResult = Result<String, Box<Error<Rule>>>;
+
+pub fn format_source(source: &str) -> FormatResult {
+    let mut formatter = Formatter::new(source);
+    let mut entries = formatter.module(&parse(source)?);
+    sort_imports(&mut entries);
+
+    let text = join_statements(&entries, &formatter.dangling);
+    Ok(match text.is_empty() {
+        true => String::new(),
+        false => finish(&format!("{}\n", text)),
+    })
+}
+
+// Formats the top level items that overlap `range`, a range of bytes, and leaves everything else as
+// it was. Imports aren't sorted as that would move code outside of the range.
+pub fn format_range(source: &str, range: Range<usize>) -> FormatResult {
+    let mut formatter = Formatter::new(source);
+    let entries = formatter.module(&parse(source)?);
+
+    let mut out = String::with_capacity(source.len());
+    let mut copied = 0;
+    for entry in entries {
+        let span = entry.pair.as_span();
+        let touched = match range.is_empty() {
+            true => span.start() <= range.start && range.start <= span.end(),
+            false => span.start() < range.end && range.start < span.end(),
+        };
+        if !touched {
+            continue;
+        }
+
+        out.push_str(&source[copied..span.start()]);
+        let mut lines = vec![];
+        push_trivia(&mut lines, &entry.moved);
+        lines.push(entry.text);
+        out.push_str(&finish(&lines.join("\n")));
+        copied = span.end();
+    }
+    out.push_str(&source[copied..]);
+
+    Ok(out)
+}
+
+fn parse(source: &str) -> Result<Pair<'_, Rule>, Box<Error<Rule>>> {
+    let mut pairs = ElpParser::parse(Rule::module, source).map_err(Box::new)?;
+    Ok(pairs.next().expect("a module always has a pair"))
+}
+
+fn finish(text: &str) -> String {
+    text.replace(STRING_NEWLINE, "\n")
+}
+
+// Where the code in `pair` ends. When an optional part at the end of a rule doesn't match, pest has
+// already skipped the whitespace and comments in front of where it would have been and leaves them
+// in the span, but they belong to whatever comes next.
+fn code_end(pair: &Pair<Rule>) -> usize {
+    let text = pair.as_str();
+    let mut end = 0;
+    let mut in_string = false;
+    let mut chars = text.char_indices();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '\\' if in_string => {
+                chars.next();
+            }
+            '"' => in_string = !in_string,
+            '/' if !in_string && text[index..].starts_with("//") => {
+                chars.by_ref().find(|&(_, c)| c == '\n');
+                continue;
+            }
+            c if c.is_whitespace() && !in_string => continue,
+            _ => {}
+        }
+        end = index + c.len_utf8();
+    }
+    pair.as_span().start() + end
+}
+
+// A statement or a member of a list, with the comments around it.
+struct Entry<'a> {
+    pair: Pair<'a, Rule>,
+    leading: Vec<CSTTrivia>,
+    // Comments from inside the entry that the layout had no place for.
+    moved: Vec<CSTTrivia>,
+    text: String,
+    trailing: Option<String>,
+    // The source started the entry on a new line.
+    newline: bool,
+}
+
+impl Entry<'_> {
+    fn has_comments(&self) -> bool {
+        self.trailing.is_some() || self.has_comments_before()
+    }
+
+    // Comments that go on lines of their own in front of the entry, so it has to start a new line.
+    fn has_comments_before(&self) -> bool {
+        self.leading
+            .iter()
+            .chain(&self.moved)
+            .any(|trivia| matches!(trivia, CSTTrivia::Comment(_)))
+    }
+
+    // Match arms don't need a comma between them, and one after an arm that ends in return types,
+    // `fn f() -> a, b`, would be read as the start of another one.
+    fn takes_comma(&self) -> bool {
+        if self.pair.as_rule() != Rule::match_arm {
+            return true;
+        }
+        let mut pair = self.pair.clone();
+        while let Some(last) = pair.clone().into_inner().last() {
+            if last.as_rule() == Rule::function_return_type {
+                return false;
+            }
+            pair = last;
+        }
+        true
+    }
+
+    // Whether a statement after `previous` starts a new line.
+    fn own_line(&self, previous: Option<&Entry>) -> bool {
+        self.newline
+            || self.has_comments_before()
+            || previous.is_none_or(|previous| previous.trailing.is_some())
+    }
+}
+
+struct List<'a> {
+    entries: Vec<Entry<'a>>,
+    // Whatever is left between the last entry and the closing delimiter.
+    dangling: Vec<CSTTrivia>,
+    // The source had a line break between the delimiters, other than in a string.
+    multiline: bool,
+}
+
+impl List<'_> {
+    fn has_comments(&self) -> bool {
+        self.entries.iter().any(Entry::has_comments)
+            || self
+                .dangling
+                .iter()
+                .any(|trivia| matches!(trivia, CSTTrivia::Comment(_)))
+    }
+}
+
+struct Delimiters {
+    open: &'static str,
+    close: &'static str,
+    // `{ a, b }` rather than `{a, b}`.
+    spaced: bool,
+    trailing_comma: bool,
+}
+
+const PARENS: Delimiters = Delimiters {
+    open: "(",
+    close: ")",
+    spaced: false,
+    trailing_comma: true,
+};
+
+const BRACKETS: Delimiters = Delimiters {
+    open: "[",
+    close: "]",
+    spaced: false,
+    trailing_comma: true,
+};
+
+const BRACES: Delimiters = Delimiters {
+    open: "{",
+    close: "}",
+    spaced: true,
+    trailing_comma: true,
+};
+
+struct Formatter<'a> {
+    source: &'a str,
+    // Everything before this has been printed or had its comments picked up.
+    cursor: usize,
+    // Comments waiting for the closest statement or list entry to be put in front of.
+    pending: Vec<CSTTrivia>,
+    // Comments after the last item in the module.
+    dangling: Vec<CSTTrivia>,
+    depth: usize,
+}
+
+impl<'a> Formatter<'a> {
+    fn new(source: &'a str) -> Self {
+        Self {
+            source,
+            cursor: 0,
+            pending: vec![],
+            dangling: vec![],
+            depth: 0,
+        }
+    }
+
+    fn module(&mut self, module: &Pair<'a, Rule>) -> Vec<Entry<'a>> {
+        let items = module
+            .clone()
+            .into_inner()
+            .filter(|pair| pair.as_rule() != Rule::EOI)
+            .collect();
+        let list = self.list(items, 0, self.source.len(), Self::print);
+        self.dangling = list.dangling;
+        list.entries
+    }
+
+    fn gap(&mut self, end: usize, after_node: bool) -> CSTGap {
+        let end = end.max(self.cursor);
+        let gap = CSTGap::scan(&self.source[self.cursor..end], after_node);
+        self.cursor = end;
+        gap
+    }
+
+    // Picks up comments somewhere the layout has no place for.
+    fn skip_to(&mut self, end: usize) {
+        let gap = self.gap(end, false);
+        self.pending.extend(
+            gap.comments()
+                .map(|comment| CSTTrivia::Comment(comment.to_string())),
+        );
+    }
+
+    // The position just after the next `token`, skipping whitespace, comments and keywords.
+    fn after_token(&self, from: usize, token: char) -> usize {
+        let mut position = from;
+        while position < self.source.len() {
+            let rest = &self.source[position..];
+            if rest.starts_with("//") {
+                position += rest.find('\n').unwrap_or(rest.len());
+            } else if rest.starts_with(token) {
+                return position + token.len_utf8();
+            } else {
+                position += rest.chars().next().map_or(1, char::len_utf8);
+            }
+        }
+        position
+    }
+
+    fn fits(&self, text: &str) -> bool {
+        !text.contains('\n') && self.depth * TAB_WIDTH + text.chars().count() <= MAX_WIDTH
+    }
+
+    // `open` is just after the opening delimiter, `close` is the closing delimiter.
+    fn list<F>(
+        &mut self,
+        items: Vec<Pair<'a, Rule>>,
+        open: usize,
+        close: usize,
+        mut render: F,
+    ) -> List<'a>
+    where
+        F: FnMut(&mut Self, Pair<'a, Rule>) -> String,
+    {
+        self.skip_to(open);
+        self.depth += 1;
+
+        let mut entries: Vec<Entry> = vec![];
+        let mut multiline = false;
+        for item in items {
+            let gap = self.gap(item.as_span().start(), !entries.is_empty());
+            multiline |= gap.has_newline;
+            if let (Some(trailing), Some(previous)) = (gap.trailing, entries.last_mut()) {
+                previous.trailing = Some(trailing);
+            }
+
+            let mark = self.pending.len();
+            let text = render(self, item.clone());
+            self.skip_to(code_end(&item));
+            entries.push(Entry {
+                leading: gap.leading,
+                moved: self.pending.split_off(mark),
+                text,
+                trailing: None,
+                newline: gap.has_newline,
+                pair: item,
+            });
+        }
+
+        let gap = self.gap(close, !entries.is_empty());
+        multiline |= gap.has_newline;
+        let mut dangling = gap.leading;
+        match (gap.trailing, entries.last_mut()) {
+            (Some(trailing), Some(last)) => last.trailing = Some(trailing),
+            (Some(trailing), None) => dangling.insert(0, CSTTrivia::Comment(trailing)),
+            (None, _) => {}
+        }
+
+        self.depth -= 1;
+        List {
+            entries,
+            dangling,
+            multiline,
+        }
+    }
+
+    fn layout(&self, list: &List, delimiters: &Delimiters, multiline: bool) -> String {
+        match multiline {
+            true => None,
+            false => self.single_line(list, delimiters),
+        }
+        .unwrap_or_else(|| multi_line(list, delimiters))
+    }
+
+    // The list on one line, if it was written on one, has no comments and fits.
+    fn single_line(&self, list: &List, delimiters: &Delimiters) -> Option<String> {
+        if list.entries.is_empty() && !list.has_comments() {
+            return Some(format!("{}{}", delimiters.open, delimiters.close));
+        }
+
+        let padding = if delimiters.spaced { " " } else { "" };
+        let single = format!(
+            "{}{}{}{}{}",
+            delimiters.open,
+            padding,
+            list.entries
+                .iter()
+                .map(|entry| entry.text.as_str())
+                .collect::<Vec<_>>()
+                .join(", "),
+            padding,
+            delimiters.close
+        );
+        match !list.multiline && !list.has_comments() && self.fits(&single) {
+            true => Some(single),
+            false => None,
+        }
+    }
+
+    fn children(pair: &Pair<'a, Rule>) -> Vec<Pair<'a, Rule>> {
+        pair.clone().into_inner().collect()
+    }
+
+    fn print(&mut self, pair: Pair<'a, Rule>) -> String {
+        self.skip_to(pair.as_span().start());
+        let end = pair.as_span().end();
+        let code_end = code_end(&pair);
+
+        let text = match pair.as_rule() {
+            Rule::expression
+            | Rule::elp_type_value
+            | Rule::external_abi
+            | Rule::external_item
+            | Rule::function_call_name
+            | Rule::import_module_path
+            | Rule::interface_member
+            | Rule::match_arm_body
+            | Rule::match_arm_subject
+            | Rule::variable_assignment_target => self.all(&pair, ""),
+            Rule::parenthesised_expression => format!("({})", self.all(&pair, " ")),
+            Rule::import => self.import(&pair),
+            Rule::export => format!("export {}", self.all(&pair, " ")),
+            Rule::external_symbol => format!("external {}", self.all(&pair, " ")),
+            Rule::external_block => self.external_block(&pair),
+            Rule::block => self.block(&pair),
+            Rule::string => pair.as_str().replace('\n', &STRING_NEWLINE.to_string()),
+            Rule::r#enum => self.enum_def(&pair),
+            Rule::enum_member => self.enum_member(&pair),
+            Rule::object_def => self.object_def(&pair),
+            Rule::object_member => self.object_member(pair).join(" "),
+            Rule::object_implements => format!("implements {}", self.all(&pair, ", ")),
+            Rule::object_key_default_value => format!("= {}", self.all(&pair, "")),
+            Rule::object_key_tags => self.tags(&pair),
+            Rule::object_instantiation => self.object_instantiation(&pair),
+            Rule::object_instantiation_member => self.object_instantiation_member(pair).join(" "),
+            Rule::object_value_spread => format!("...{}", self.all(&pair, "")),
+            Rule::interface_def => self.interface_def(&pair),
+            Rule::interface_member_key_value => {
+                let cells = self.interface_member(pair);
+                cells
+                    .into_iter()
+                    .filter(|cell| !cell.is_empty())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            }
+            Rule::function_def => self.callable("fn", &pair),
+            Rule::component_def => self.callable("component", &pair),
+            Rule::fn_header_def => self.fn_header(&pair),
+            Rule::function_arguments => {
+                let list = self.list(
+                    Self::children(&pair),
+                    pair.as_span().start() + 1,
+                    end - 1,
+                    Self::print,
+                );
+                self.layout(&list, &PARENS, false)
+            }
+            Rule::function_argument => self.function_argument(&pair),
+            Rule::function_return_type => format!("-> {}", self.all(&pair, ", ")),
+            Rule::function_return_value => format!("return {}", self.all(&pair, " ")),
+            Rule::function_call => self.call(&pair),
+            Rule::function_component_call => self.all(&pair, " "),
+            Rule::if_tree => format!("if {}", self.conditional(&pair)),
+            Rule::elseif_tree => format!("elseif {}", self.conditional(&pair)),
+            Rule::else_block => format!("else {}", self.all(&pair, "")),
+            Rule::for_loop => {
+                let parts: Vec<String> = Self::children(&pair)
+                    .into_iter()
+                    .map(|child| self.print(child))
+                    .collect();
+                format!("for {} in {} {}", parts[0], parts[1], parts[2])
+            }
+            Rule::match_tree => self.match_tree(&pair),
+            Rule::match_arm => self.all(&pair, " -> "),
+            Rule::match_range => self.match_range(&pair),
+            Rule::array => {
+                let list = self.list(
+                    Self::children(&pair),
+                    pair.as_span().start() + 1,
+                    end - 1,
+                    Self::print,
+                );
+                self.layout(&list, &BRACKETS, false)
+            }
+            Rule::stylesheet => self.stylesheet(&pair),
+            Rule::style_rule => self.style_rule(&pair),
+            Rule::style_property => format!(".{}", self.all(&pair, " = ")),
+            Rule::style_value => self.all(&pair, ""),
+            Rule::r#macro => format!("@{}", self.meta(&pair)),
+            Rule::precomp => format!("#{}", self.meta(&pair)),
+            Rule::import_name => self.all(&pair, " "),
+            Rule::import_name_alias => format!("as {}", self.all(&pair, "")),
+            Rule::variable_declaration => self.all(&pair, " "),
+            Rule::variable_assignment => self.all(&pair, " "),
+            Rule::value_assignment => self.all(&pair, " "),
+            Rule::variable_access => self.all(&pair, ""),
+            Rule::variable_access_names => self.all(&pair, "."),
+            Rule::contextual_variable_access => format!(".{}", self.all(&pair, "")),
+            Rule::bitwise_operation => self.all(&pair, " "),
+            Rule::elp_type => {
+                let mut text = String::new();
+                for child in Self::children(&pair) {
+                    let rule = child.as_rule();
+                    text.push_str(&self.print(child));
+                    if rule == Rule::mutability_selector {
+                        text.push(' ');
+                    }
+                }
+                text
+            }
+            Rule::elp_type_parameter => self.all(&pair, ""),
+            Rule::elp_type_array => format!("[{}]", self.all(&pair, "")),
+            Rule::elp_type_generic => format!("<{}>", self.all(&pair, ", ")),
+            Rule::elp_type_generic_param => self.all(&pair, ""),
+            Rule::elp_type_generic_constraint => format!(": {}", self.all(&pair, " + ")),
+            _ => pair.as_str().trim().to_string(),
+        };
+
+        self.skip_to(code_end);
+        text
+    }
+
+    // Every child of `pair` printed and joined with `separator`.
+    fn all(&mut self, pair: &Pair<'a, Rule>, separator: &str) -> String {
+        Self::children(pair)
+            .into_iter()
+            .map(|child| self.print(child))
+            .collect::<Vec<_>>()
+            .join(separator)
+    }
+
+    fn statements(&mut self, items: Vec<Pair<'a, Rule>>, open: usize, close: usize) -> String {
+        let list = self.list(items, open, close, Self::print);
+        let body = join_statements(&list.entries, &list.dangling);
+        match body.is_empty() {
+            true => "{}".into(),
+            false => format!("{{\n{}\n}}", indent(&body)),
+        }
+    }
+
+    fn block(&mut self, pair: &Pair<'a, Rule>) -> String {
+        let span = pair.as_span();
+        self.statements(Self::children(pair), span.start() + 1, span.end() - 1)
+    }
+
+    fn external_block(&mut self, pair: &Pair<'a, Rule>) -> String {
+        let span = pair.as_span();
+        let mut items = Self::children(pair);
+        // `external "C" { .. }`, the ABI comes before the brace.
+        let abi = match items.first() {
+            Some(first) if first.as_rule() == Rule::external_abi => Some(items.remove(0)),
+            _ => None,
+        };
+        let open = self.after_token(
+            abi.as_ref().map_or(span.start(), |abi| abi.as_span().end()),
+            '{',
+        );
+        let abi = abi.map_or(String::new(), |abi| format!("{} ", self.print(abi)));
+        format!(
+            "external {}{}",
+            abi,
+            self.statements(items, open, span.end() - 1)
+        )
+    }
+
+    fn import(&mut self, pair: &Pair<'a, Rule>) -> String {
+        let children = Self::children(pair);
+        let (names, path): (Vec<_>, Vec<_>) = children
+            .into_iter()
+            .partition(|child| child.as_rule() == Rule::import_name);
+
+        let open = self.after_token(pair.as_span().start(), '{');
+        let close =
+            self.after_token(names.last().map_or(open, |name| name.as_span().end()), '}') - 1;
+        let mut list = self.list(names, open, close, Self::print);
+        list.entries
+            .sort_by_key(|entry| (entry.text.to_lowercase(), entry.text.clone()));
+        let names = self.layout(
+            &list,
+            &Delimiters {
+                trailing_comma: false,
+                ..BRACES
+            },
+            false,
+        );
+
+        let path = path
+            .into_iter()
+            .map(|path| self.print(path))
+            .collect::<String>();
+        format!("import {} from {}", names, path)
+    }
+
+    // Everything up to the opening brace of an enum, object, interface or match.
+    fn header(&mut self, children: &mut Vec<Pair<'a, Rule>>, member: &[Rule]) -> Vec<String> {
+        let at = children
+            .iter()
+            .position(|child| member.contains(&child.as_rule()))
+            .unwrap_or(children.len());
+        children
+            .drain(..at)
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|child| self.print(child))
+            .collect()
+    }
+
+    fn enum_def(&mut self, pair: &Pair<'a, Rule>) -> String {
+        let mut members = Self::children(pair);
+        let header = self.header(&mut members, &[Rule::enum_member]);
+        let open = self.after_token(self.cursor, '{');
+        let list = self.list(members, open, pair.as_span().end() - 1, Self::print);
+
+        format!(
+            "enum {} {}",
+            header.join(" "),
+            self.layout(&list, &BRACES, true)
+        )
+    }
+
+    fn enum_member(&mut self, pair: &Pair<'a, Rule>) -> String {
+        let mut children = Self::children(pair).into_iter();
+        let name = self.print(children.next().expect("enum members have a name"));
+        let types: Vec<String> = children.map(|child| self.print(child)).collect();
+        match types.is_empty() {
+            true => format!(".{}", name),
+            false => format!(".{}({})", name, types.join(", ")),
+        }
+    }
+
+    fn object_def(&mut self, pair: &Pair<'a, Rule>) -> String {
+        let mut members = Self::children(pair);
+        let mut header = self.header(&mut members, &[Rule::object_member]);
+        let open = self.after_token(self.cursor, '{');
+
+        let mut rows = vec![];
+        let mut list = self.list(
+            members,
+            open,
+            pair.as_span().end() - 1,
+            |formatter, member| {
+                rows.push(Some(formatter.object_member(member)));
+                String::new()
+            },
+        );
+        align(&mut list.entries, rows);
+
+        // The generics belong to the name.
+        if header.len() > 1 && header[1].starts_with('<') {
+            let generics = header.remove(1);
+            header[0].push_str(&generics);
+        }
+        format!(
+            "object {} {}",
+            header.join(" "),
+            self.layout(&list, &BRACES, true)
+        )
+    }
+
+    // The columns an object member is aligned by.
+    fn object_member(&mut self, pair: Pair<'a, Rule>) -> Vec<String> {
+        let mut cells = vec![String::new(); 6];
+        for child in Self::children(&pair) {
+            let column = match child.as_rule() {
+                Rule::visibility_selector => 0,
+                Rule::mutability_selector => 1,
+                Rule::IDENT => 2,
+                Rule::elp_type => 3,
+                Rule::object_key_default_value => 4,
+                _ => 5,
+            };
+            let text = self.print(child);
+            cells[column] = match column {
+                2 => format!(".{}", text),
+                _ => text,
+            };
+        }
+        cells
+    }
+
+    fn tags(&mut self, pair: &Pair<'a, Rule>) -> String {
+        let tags: Vec<String> = Self::children(pair)
+            .into_iter()
+            .map(|tag| {
+                let parts: Vec<String> = Self::children(&tag)
+                    .into_iter()
+                    .map(|child| self.print(child))
+                    .collect();
+                format!("{}: {}", parts[0], parts.get(1).map_or("", String::as_str))
+            })
+            .collect();
+        format!("`{}`", tags.join(", "))
+    }
+
+    fn interface_def(&mut self, pair: &Pair<'a, Rule>) -> String {
+        let mut members = Self::children(pair);
+        let header = self.header(&mut members, &[Rule::interface_member]);
+        let open = self.after_token(self.cursor, '{');
+
+        let mut rows = vec![];
+        let mut list = self.list(
+            members,
+            open,
+            pair.as_span().end() - 1,
+            |formatter, member| {
+                let inner = member
+                    .clone()
+                    .into_inner()
+                    .next()
+                    .expect("members have a kind");
+                match inner.as_rule() {
+                    Rule::interface_member_key_value => {
+                        formatter.skip_to(member.as_span().start());
+                        rows.push(Some(formatter.interface_member(inner)));
+                        String::new()
+                    }
+                    _ => {
+                        rows.push(None);
+                        formatter.print(member)
+                    }
+                }
+            },
+        );
+        align(&mut list.entries, rows);
+
+        format!(
+            "interface {} {}",
+            header.concat(),
+            self.layout(
+                &list,
+                &Delimiters {
+                    trailing_comma: false,
+                    ..BRACES
+                },
+                true
+            )
+        )
+    }
+
+    fn interface_member(&mut self, pair: Pair<'a, Rule>) -> Vec<String> {
+        let mut cells = vec![String::new(); 3];
+        for child in Self::children(&pair) {
+            let column = match child.as_rule() {
+                Rule::visibility_selector => 0,
+                Rule::IDENT => 1,
+                _ => 2,
+            };
+            let text = self.print(child);
+            cells[column] = match column {
+                1 => format!(".{}", text),
+                _ => text,
+            };
+        }
+        cells
+    }
+
+    // `fn` and `component` definitions.
+    fn callable(&mut self, keyword: &str, pair: &Pair<'a, Rule>) -> String {
+        let mut text = format!("{} ", keyword);
+        for child in Self::children(pair) {
+            let rule = child.as_rule();
+            if matches!(rule, Rule::function_return_type | Rule::block) {
+                text.push(' ');
+            }
+            text.push_str(&self.print(child));
+        }
+        text
+    }
+
+    fn fn_header(&mut self, pair: &Pair<'a, Rule>) -> String {
+        let mut text = String::new();
+        for child in Self::children(pair) {
+            match child.as_rule() {
+                Rule::pointer_semantics => {
+                    text.push_str(&self.print(child));
+                    continue;
+                }
+                Rule::variable_access => text.push_str("fn "),
+                Rule::function_return_type => text.push(' '),
+                _ => {}
+            }
+            text.push_str(&self.print(child));
+        }
+        text
+    }
+
+    fn function_argument(&mut self, pair: &Pair<'a, Rule>) -> String {
+        let mut text = String::new();
+        for child in Self::children(pair) {
+            if child.as_rule() == Rule::elp_type {
+                text.push(' ');
+            }
+            text.push_str(&self.print(child));
+        }
+        text
+    }
+
+    fn call(&mut self, pair: &Pair<'a, Rule>) -> String {
+        let mut arguments = Self::children(pair);
+        let callee = self.header(
+            &mut arguments,
+            &[Rule::expression, Rule::parenthesised_expression],
+        );
+        let open = self.after_token(self.cursor, '(');
+        let list = self.list(arguments, open, pair.as_span().end() - 1, Self::print);
+
+        format!("{}{}", callee.concat(), self.layout(&list, &PARENS, false))
+    }
+
+    // Members on their own lines have their `=` lined up like the defaults of an object
+    // definition.
+    fn object_instantiation(&mut self, pair: &Pair<'a, Rule>) -> String {
+        let mut members = Self::children(pair);
+        let name = self.print(members.remove(0));
+        let open = self.after_token(self.cursor, '{');
+
+        let mut rows = vec![];
+        let mut list = self.list(
+            members,
+            open,
+            pair.as_span().end() - 1,
+            |formatter, member| {
+                let row = formatter.object_instantiation_member(member);
+                let text = row.join(" ");
+                rows.push(Some(row).filter(|row| row.len() > 1));
+                text
+            },
+        );
+
+        let layout = match self.single_line(&list, &BRACES) {
+            Some(single) => single,
+            None => {
+                align(&mut list.entries, rows);
+                multi_line(&list, &BRACES)
+            }
+        };
+        format!("{} {}", name, layout)
+    }
+
+    // `.name` and `= value` as the columns they're aligned by, or the one spread.
+    fn object_instantiation_member(&mut self, pair: Pair<'a, Rule>) -> Vec<String> {
+        let mut children = Self::children(&pair).into_iter();
+        let first = self.print(children.next().expect("members aren't empty"));
+        match children.next() {
+            Some(value) => vec![format!(".{}", first), format!("= {}", self.print(value))],
+            None => vec![first],
+        }
+    }
+
+    // The conditions and block of `if` and `elseif`.
+    fn conditional(&mut self, pair: &Pair<'a, Rule>) -> String {
+        let mut children = Self::children(pair);
+        let block = children.pop().expect("conditionals have a block");
+        let conditions: Vec<String> = children
+            .into_iter()
+            .map(|child| self.print(child))
+            .collect();
+        format!("{} then {}", conditions.join(" "), self.print(block))
+    }
+
+    fn match_tree(&mut self, pair: &Pair<'a, Rule>) -> String {
+        let mut arms = Self::children(pair);
+        let subject = self.header(&mut arms, &[Rule::match_arm]);
+        let open = self.after_token(self.cursor, '{');
+        let list = self.list(arms, open, pair.as_span().end() - 1, Self::print);
+
+        format!(
+            "match {} {}",
+            subject.concat(),
+            self.layout(&list, &BRACES, true)
+        )
+    }
+
+    fn stylesheet(&mut self, pair: &Pair<'a, Rule>) -> String {
+        let span = pair.as_span();
+        let open = self.after_token(span.start(), '{');
+        format!(
+            "Stylesheet {}",
+            self.statements(Self::children(pair), open, span.end() - 1)
+        )
+    }
+
+    fn style_rule(&mut self, pair: &Pair<'a, Rule>) -> String {
+        let mut properties = Self::children(pair);
+        let selectors = self.header(&mut properties, &[Rule::style_property]);
+        let open = self.after_token(self.cursor, '{');
+        let list = self.list(properties, open, pair.as_span().end() - 1, Self::print);
+
+        format!(
+            "{} {}",
+            selectors.join(", "),
+            self.layout(&list, &BRACES, true)
+        )
+    }
+
+    fn match_range(&mut self, pair: &Pair<'a, Rule>) -> String {
+        let start = pair.as_span().start();
+        let mut text = String::new();
+        for child in Self::children(pair) {
+            let is_start = child.as_span().start() == start;
+            let printed = self.print(child);
+            match is_start {
+                true => text = format!("{}..", printed),
+                false => {
+                    if text.is_empty() {
+                        text.push_str("..");
+                    }
+                    text.push_str(&printed);
+                }
+            }
+        }
+        match text.is_empty() {
+            true => "..".into(),
+            false => text,
+        }
+    }
+
+    // The name and optional type of a macro or precomp.
+    fn meta(&mut self, pair: &Pair<'a, Rule>) -> String {
+        let mut children = Self::children(pair).into_iter();
+        let name = self.print(children.next().expect("macros have a name"));
+        match children.next() {
+            Some(argument) => format!("{}({})", name, self.print(argument)),
+            None => name,
+        }
+    }
+}
+
+fn multi_line(list: &List, delimiters: &Delimiters) -> String {
+    if list.entries.is_empty() && !list.has_comments() {
+        return format!("{}{}", delimiters.open, delimiters.close);
+    }
+
+    let mut lines = vec![];
+    for (index, entry) in list.entries.iter().enumerate() {
+        push_trivia(&mut lines, &entry.leading);
+        push_trivia(&mut lines, &entry.moved);
+        let last = index + 1 == list.entries.len();
+        let comma = match (!last || delimiters.trailing_comma) && entry.takes_comma() {
+            true => ",",
+            false => "",
+        };
+        let mut line = format!("{}{}", entry.text, comma);
+        if let Some(trailing) = &entry.trailing {
+            line = format!("{} {}", line, trailing);
+        }
+        lines.push(line);
+    }
+    push_trivia(&mut lines, &list.dangling);
+    trim_blank_lines(&mut lines);
+
+    format!(
+        "{}\n{}\n{}",
+        delimiters.open,
+        indent(&lines.join("\n")),
+        delimiters.close
+    )
+}
+
+// Statements go on their own line when they were written on one, the rest share the line before.
+fn join_statements(entries: &[Entry], dangling: &[CSTTrivia]) -> String {
+    let mut lines: Vec<String> = vec![];
+    let mut previous = None;
+    for entry in entries {
+        let own_line = lines.is_empty() || entry.own_line(previous);
+        push_trivia(&mut lines, &entry.leading);
+        push_trivia(&mut lines, &entry.moved);
+
+        match (own_line, lines.last_mut()) {
+            (false, Some(line)) => {
+                line.push(' ');
+                line.push_str(&entry.text);
+            }
+            _ => lines.push(entry.text.clone()),
+        }
+        if let (Some(trailing), Some(line)) = (&entry.trailing, lines.last_mut()) {
+            line.push(' ');
+            line.push_str(trailing);
+        }
+        previous = Some(entry);
+    }
+    push_trivia(&mut lines, dangling);
+    trim_blank_lines(&mut lines);
+
+    lines.join("\n")
+}
+
+fn push_trivia(lines: &mut Vec<String>, trivia: &[CSTTrivia]) {
+    for trivia in trivia {
+        match trivia {
+            CSTTrivia::Comment(comment) => lines.push(comment.clone()),
+            CSTTrivia::BlankLine => {
+                if lines.last().is_some_and(|line| !line.is_empty()) {
+                    lines.push(String::new());
+                }
+            }
+        }
+    }
+}
+
+fn trim_blank_lines(lines: &mut Vec<String>) {
+    while lines.last().is_some_and(String::is_empty) {
+        lines.pop();
+    }
+}
+
+fn indent(text: &str) -> String {
+    text.split('\n')
+        .map(|line| match line.is_empty() {
+            true => String::new(),
+            false => format!("\t{}", line),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Lines up the columns of rows of cells, rows separated by a blank line are aligned on their own.
+// Entries without a row, and rows with a cell over more than one line, are left as they are.
+fn align(entries: &mut [Entry], rows: Vec<Option<Vec<String>>>) {
+    let mut start = 0;
+    while start < entries.len() {
+        let end = (start + 1..entries.len())
+            .find(|&index| entries[index].leading.contains(&CSTTrivia::BlankLine))
+            .unwrap_or(entries.len());
+        let group: Vec<&Vec<String>> = rows[start..end]
+            .iter()
+            .flatten()
+            .filter(|row| row.iter().all(|cell| !cell.contains('\n')))
+            .collect();
+        let columns = group.first().map_or(0, |row| row.len());
+        let widths: Vec<usize> = (0..columns)
+            .map(|column| {
+                group
+                    .iter()
+                    .map(|row| row[column].chars().count())
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+
+        for (entry, row) in entries[start..end].iter_mut().zip(&rows[start..end]) {
+            let Some(row) = row else {
+                continue;
+            };
+            let cells: Vec<String> = match row.iter().any(|cell| cell.contains('\n')) {
+                true => row
+                    .iter()
+                    .filter(|cell| !cell.is_empty())
+                    .cloned()
+                    .collect(),
+                false => row
+                    .iter()
+                    .zip(&widths)
+                    .filter(|(_, width)| **width > 0)
+                    .map(|(cell, width)| format!("{:width$}", cell, width = width))
+                    .collect(),
+            };
+            entry.text = cells.join(" ").trim_end().to_string();
+        }
+        start = end;
+    }
+}
+
+// Runs of imports, not split by a blank line, are sorted by path with the standard library first
+// and the package's own `$` imports last. Comments move with the import they're above.
+fn sort_imports(entries: &mut [Entry]) {
+    let is_import = |entry: &Entry| {
+        entry
+            .pair
+            .clone()
+            .into_inner()
+            .next()
+            .is_some_and(|inner| inner.as_rule() == Rule::import)
+    };
+
+    let mut start = 0;
+    while start < entries.len() {
+        if !is_import(&entries[start]) {
+            start += 1;
+            continue;
+        }
+        let end = (start + 1..entries.len())
+            .find(|&index| {
+                let entry = &entries[index];
+                !is_import(entry) || !entry.newline || entry.leading.contains(&CSTTrivia::BlankLine)
+            })
+            .unwrap_or(entries.len());
+
+        let first_newline = entries[start].newline;
+        entries[start..end].sort_by_key(|entry| {
+            let path = entry.text.rsplit(" from ").next().unwrap_or_default();
+            let path = path.trim_matches('"');
+            let group = match path {
+                _ if path == "elp" || path.starts_with("elp/") => 0,
+                _ if path.starts_with('$') => 2,
+                _ => 1,
+            };
+            (group, path.to_string())
+        });
+        // Whether the run starts on a new line doesn't move with the import.
+        for (index, entry) in entries[start..end].iter_mut().enumerate() {
+            entry.newline = index > 0 || first_newline;
+        }
+
+        let own_line = entries
+            .get(end)
+            .is_some_and(|next| next.own_line(Some(&entries[end - 1])));
+        if let Some(next) = entries.get_mut(end) {
+            if own_line && next.leading.first() != Some(&CSTTrivia::BlankLine) {
+                next.leading.insert(0, CSTTrivia::BlankLine);
+            }
+        }
+        start = end;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn format(source: &str) -> String {
+        let formatted = format_source(source).unwrap();
+        assert_eq!(
+            format_source(&formatted).unwrap(),
+            formatted,
+            "formatting isn't idempotent"
+        );
+        formatted
+    }
+
+    #[test]
+    fn reindents_blocks() {
+        assert_eq!(
+            format("fn   main(){\n  var   a=1\n\n\n\n    a +=  2\n  println( a )}"),
+            "fn main() {\n\tvar a = 1\n\n\ta += 2\n\tprintln(a)\n}\n"
+        );
+        assert_eq!(format("fn main {   }"), "fn main {}\n");
+        assert_eq!(format(""), "");
+    }
+
+    #[test]
+    fn keeps_statements_on_the_lines_they_were_written() {
+        assert_eq!(
+            format(
+                "fn main {\n\tif a==b then {\n\t\tc()\n\t} else {\n\t\td()\n\t}\n\treturn a+b\n}\n"
+            ),
+            "fn main {\n\tif a == b then {\n\t\tc()\n\t} else {\n\t\td()\n\t}\n\treturn a + b\n}\n"
+        );
+    }
+
+    #[test]
+    fn aligns_object_members() {
+        let source = "export object Graph<N,E> {
+	private const .direction *Direction = .Directed,
+	public var .nodes [N],
+	.edges [E] `json: \"edges\"`,
+
+	.id uuid `json:\"id\",db:  \"id\"`
+}
+";
+        assert_eq!(
+            format(source),
+            "export object Graph<N, E> {
+	private const .direction *Direction = .Directed,
+	public  var   .nodes     [N],
+	              .edges     [E]                    `json: \"edges\"`,
+
+	.id uuid `json: \"id\", db: \"id\"`,
+}
+"
+        );
+    }
+
+    #[test]
+    fn formats_externals() {
+        assert_eq!(
+            format("external   \"stdcall\"   fn MessageBeep(kind uint32) -> bool"),
+            "external \"stdcall\" fn MessageBeep(kind uint32) -> bool\n"
+        );
+        assert_eq!(
+            format("external \"ObjC\"{fn OBJC_msgSend<T>(T, OBJC_Selector) -> T\n// Selectors\nobject OBJC_Selector {}}"),
+            "external \"ObjC\" {
+	fn OBJC_msgSend<T>(T, OBJC_Selector) -> T
+	// Selectors
+	object OBJC_Selector {}
+}
+"
+        );
+    }
+
+    #[test]
+    fn lays_out_enums_and_interfaces() {
+        assert_eq!(
+            format("enum Direction { .Directed, .Undirected(int, string) }"),
+            "enum Direction {\n\t.Directed,\n\t.Undirected(int, string),\n}\n"
+        );
+        assert_eq!(
+            format("interface Props<T> { .email Optional<string>, public .password string, fn submit(a int) -> bool }"),
+            "interface Props<T> {
+	       .email    Optional<string>,
+	public .password string,
+	fn submit(a int) -> bool
+}
+"
+        );
+    }
+
+    #[test]
+    fn sorts_imports() {
+        let source = "import { b, A, c as d } from \"$types/chat\"
+// the layout helpers
+import { Column } from \"elp/layout\"
+import { parse } from \"json\"
+
+import { z } from \"elp\"
+fn main {}
+";
+        assert_eq!(
+            format(source),
+            "// the layout helpers
+import { Column } from \"elp/layout\"
+import { parse } from \"json\"
+import { A, b, c as d } from \"$types/chat\"
+
+import { z } from \"elp\"
+
+fn main {}
+"
+        );
+    }
+
+    #[test]
+    fn splits_long_lists_with_trailing_commas() {
+        let source = format!(
+            "fn main {{\n\tcall({}, {})\n\tx = [1,\n2]\n}}\n",
+            "a".repeat(50),
+            "b".repeat(50)
+        );
+        assert_eq!(
+            format(&source),
+            format!(
+                "fn main {{\n\tcall(\n\t\t{},\n\t\t{},\n\t)\n\tx = [\n\t\t1,\n\t\t2,\n\t]\n}}\n",
+                "a".repeat(50),
+                "b".repeat(50)
+            )
+        );
+        assert_eq!(
+            format("import {\n\tForm,\n\tButton\n} from \"elp/forms\""),
+            "import {\n\tButton,\n\tForm\n} from \"elp/forms\"\n"
+        );
+    }
+
+    #[test]
+    fn keeps_the_spaces_around_named_arguments() {
+        // From `examples/BlogStyle/main.velp`.
+        let source = "export fn HelloWorld -> App {
+	return App(globalStyles = Styles()) {
+		Window {
+			Home()
+		}
+	}
+}
+";
+        assert_eq!(format(source), source);
+        assert_eq!(
+            format("fn main {\n\tColumn(class=\"to-me\")\n}\n"),
+            "fn main {\n\tColumn(class = \"to-me\")\n}\n"
+        );
+    }
+
+    #[test]
+    fn aligns_object_instantiation_members() {
+        // From `examples/Graph/lib/dag.test.elp`.
+        let source = "fn main {
+	assert_equal(
+		graph.edges,
+		[
+			NodeIndex {
+				.from = 0,
+				.to   = 1,
+			},
+		],
+	)
+}
+";
+        assert_eq!(format(source), source);
+        assert_eq!(
+            format("fn main {\n\tNodeIndex {\n\t\t.from = 0,\n\t\t.to = 1,\n\t\t...rest\n\t}\n}\n"),
+            "fn main {\n\tNodeIndex {\n\t\t.from = 0,\n\t\t.to   = 1,\n\t\t...rest,\n\t}\n}\n"
+        );
+        // Unless they fit on one line.
+        assert_eq!(
+            format("fn main {\n\tNodeIndex { .from = 0, .to = 1 }\n}\n"),
+            "fn main {\n\tNodeIndex { .from = 0, .to = 1 }\n}\n"
+        );
+    }
+
+    #[test]
+    fn component_blocks() {
+        let source = "export component LoginForm {
+	var attempts = 0
+	return Form(onSubmit = handleSubmit) {
+		EmailInput(&email, placeholder=\"Enter your email\")
+		Button(type=.FORM_SUBMIT) { Text(\"Log in\") }
+	}
+}
+";
+        assert_eq!(
+            format(source),
+            "export component LoginForm {
+	var attempts = 0
+	return Form(onSubmit = handleSubmit) {
+		EmailInput(&email, placeholder = \"Enter your email\")
+		Button(type = .FORM_SUBMIT) {
+			Text(\"Log in\")
+		}
+	}
+}
+"
+        );
+    }
+
+    #[test]
+    fn keeps_every_comment() {
+        let source = "// leading
+fn main { // opening
+	// above
+	a() // trailing
+
+	b(1, // inside a list
+		2)
+	// closing
+}
+// the end
+";
+        assert_eq!(
+            format(source),
+            "// leading
+fn main {
+	// opening
+	// above
+	a() // trailing
+
+	b(
+		1, // inside a list
+		2,
+	)
+	// closing
+}
+// the end
+"
+        );
+    }
+
+    #[test]
+    fn moves_comments_it_has_no_place_for() {
+        assert_eq!(
+            format("fn main {\n\tconst a = // why\n\t\t1\n}\n"),
+            "fn main {\n\t// why\n\tconst a = 1\n}\n"
+        );
+        // A moved comment can't share a line with the code after it, even when that code has a
+        // comment of its own at the end.
+        assert_eq!(
+            format("(a) (b // why\n) // what\n"),
+            "(a)\n// why\n(b) // what\n"
+        );
+        // pest puts the comment in the span of `const a`, but it's in front of `(b)`.
+        assert_eq!(format("const a\n// why\n(b)\n"), "const a\n// why\n(b)\n");
+    }
+
+    #[test]
+    fn leaves_out_commas_match_arms_would_swallow() {
+        assert_eq!(
+            format("match a {\n\t1 -> fn f() -> b, c\n\td -> 3\n}\n"),
+            "match a {\n\t1 -> fn f() -> b, c\n\td -> 3,\n}\n"
+        );
+    }
+
+    #[test]
+    fn formats_stylesheets() {
+        let source = "Stylesheet {
+    Text,Date.muted { .padding = 16 }
+
+
+    // Headings.
+    .title{.fontWeight=.SEMIBOLD,.color=#333,}
+}
+";
+        assert_eq!(
+            format(source),
+            "Stylesheet {
+\tText, Date.muted {
+\t\t.padding = 16,
+\t}
+
+\t// Headings.
+\t.title {
+\t\t.fontWeight = .SEMIBOLD,
+\t\t.color = #333,
+\t}
+}
+"
+        );
+    }
+
+    #[test]
+    fn leaves_strings_alone() {
+        let source = "fn main {\n\t\tprintln(\"a  \n  b\")\n}\n";
+        assert_eq!(format(source), "fn main {\n\tprintln(\"a  \n  b\")\n}\n");
+    }
+
+    #[test]
+    fn formats_matches_and_objects() {
+        let source = "fn from(err HttpError) -> ApiError {
+	return match err.responseCode {
+		400..499 -> .new(.USER(err.toString())),
+		500..599 -> { return Self { .type = type, ...rest } }
+	}
+}
+";
+        assert_eq!(
+            format(source),
+            "fn from(err HttpError) -> ApiError {
+	return match err.responseCode {
+		400..499 -> .new(.USER(err.toString())),
+		500..599 -> {
+			return Self { .type = type, ...rest }
+		},
+	}
+}
+"
+        );
+    }
+
+    #[test]
+    fn formats_ranges() {
+        let source = "fn  a( ) {   }\n\nfn  b( ) {   }\n\nfn  c( ) {   }\n";
+        let b = source.find("fn  b").unwrap();
+
+        assert_eq!(
+            format_range(source, b + 3..b + 4).unwrap(),
+            "fn  a( ) {   }\n\nfn b() {}\n\nfn  c( ) {   }\n"
+        );
+        assert_eq!(
+            format_range(source, 0..0).unwrap(),
+            "fn a() {}\n\nfn  b( ) {   }\n\nfn  c( ) {   }\n"
+        );
+    }
+
+    // Every `//` outside a string literal.
+    fn comments(source: &str) -> Vec<String> {
+        let mut comments = vec![];
+        let (mut in_string, mut escaped) = (false, false);
+        for (index, c) in source.char_indices() {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' if in_string => escaped = true,
+                '"' => in_string = !in_string,
+                '/' if !in_string && source[index..].starts_with("//") => {
+                    let comment = source[index..].lines().next().unwrap_or_default();
+                    if !comments
+                        .last()
+                        .is_some_and(|last: &String| last.ends_with(comment))
+                    {
+                        comments.push(comment.trim_end().to_string());
+                    }
+                }
+                _ => {}
+            }
+        }
+        comments.sort();
+        comments
+    }
+
+    #[test]
+    fn formats_the_examples() {
+        let mut formatted_files = 0;
+        let mut directories = vec![std::path::PathBuf::from("../../examples")];
+        while let Some(directory) = directories.pop() {
+            for entry in std::fs::read_dir(directory).unwrap().flatten() {
+                let path = entry.path();
+                if path.is_dir() {
+                    directories.push(path);
+                    continue;
+                }
+                let source = std::fs::read_to_string(&path).unwrap();
+                // Some of the examples use syntax the grammar doesn't have yet.
+                let Ok(formatted) = format_source(&source) else {
+                    continue;
+                };
+
+                assert!(
+                    ElpParser::parse(Rule::module, &formatted).is_ok(),
+                    "{} doesn't parse once formatted:\n{}",
+                    path.display(),
+                    formatted
+                );
+                assert_eq!(
+                    format_source(&formatted).unwrap(),
+                    formatted,
+                    "{}",
+                    path.display()
+                );
+                assert_eq!(
+                    comments(&formatted),
+                    comments(&source),
+                    "{}",
+                    path.display()
+                );
+                formatted_files += 1;
+            }
+        }
+        assert!(formatted_files > 5);
+    }
+
+    #[test]
+    fn reports_syntax_errors() {
+        assert!(format_source("fn main {").is_err());
+    }
+}
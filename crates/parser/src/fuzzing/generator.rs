@@ -0,0 +1,288 @@
+// Makes up elp programs by walking the productions in `elp.pest`, so fuzzing and property tests
+// start from something close to real code rather than random bytes that hardly ever get past the
+// first token.
+//
+// Every decision, which alternative, how many repetitions, what goes between tokens, comes from a
+// `Choices`. A seed makes the same program every time and a fuzzer's input steers the walk. Once
+// the walk is `MAX_DEPTH` rules deep it takes the way out that makes the least text, so it always
+// finishes. Lookaheads aren't followed, which means not everything it makes parses, `program` only
+// hands back what does.
+
+use std::collections::HashMap;
+
+use pest::Parser;
+use pest_meta::{ast::RuleType, optimizer::OptimizedExpr};
+
+use crate::parser::{ElpParser, Rule};
+
+const GRAMMAR: &str = include_str!("../elp.pest");
+const MAX_DEPTH: usize = 12;
+// The most a `*` or `+` repeats.
+const MAX_REPEAT: usize = 3;
+// Identifiers and string contents, kept to a few so programs refer back to the same names. The
+// capitalised ones let component calls like `Row(..) { .. }` come up.
+const NAMES: [&str; 10] = [
+    "value", "name", "count", "items", "x", "y", "Point", "Shape", "Row", "Text",
+];
+const WORDS: [&str; 4] = ["", "hello", "elp", "a \\\"quoted\\\" word"];
+// What goes between tokens wherever the grammar skips whitespace and comments.
+const SEPARATORS: [&str; 4] = [" ", " ", "\n", " // note\n"];
+
+pub trait Choices {
+    // A number below `count`, which is never 0.
+    fn choose(&mut self, count: usize) -> usize;
+}
+
+// Choices from a seed, the same seed always makes the same program.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Seeded(u64);
+
+impl Seeded {
+    pub fn new(seed: u64) -> Self {
+        // xorshift never leaves 0.
+        Seeded(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1)
+    }
+}
+
+impl Choices for Seeded {
+    fn choose(&mut self, count: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % count as u64) as usize
+    }
+}
+
+// Choices read from a fuzzer's input. Once it runs out every choice is the first one, which is
+// no repetitions and the first alternative, so short inputs make short programs.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Bytes<'a>(pub &'a [u8]);
+
+impl Choices for Bytes<'_> {
+    fn choose(&mut self, count: usize) -> usize {
+        match self.0.split_first() {
+            Some((&byte, rest)) => {
+                self.0 = rest;
+                byte as usize % count
+            }
+            None => 0,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Generator {
+    rules: HashMap<String, (RuleType, OptimizedExpr)>,
+    // The least text each rule can make, what the walk heads for when it's too deep.
+    sizes: HashMap<String, usize>,
+}
+
+impl Default for Generator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Generator {
+    pub fn new() -> Self {
+        let (_, rules) = pest_meta::parse_and_optimize(GRAMMAR).expect("elp.pest is valid");
+        let rules: HashMap<String, (RuleType, OptimizedExpr)> = rules
+            .into_iter()
+            .map(|rule| (rule.name, (rule.ty, rule.expr)))
+            .collect();
+
+        // Every rule starts as unreachably big and shrinks until nothing changes.
+        let mut sizes: HashMap<String, usize> = rules
+            .keys()
+            .map(|name| (name.clone(), usize::MAX / 4))
+            .collect();
+        loop {
+            let mut changed = false;
+            for (name, (_, expr)) in &rules {
+                let size = smallest(expr, &sizes);
+                if size < sizes[name] {
+                    sizes.insert(name.clone(), size);
+                    changed = true;
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+
+        Generator { rules, sizes }
+    }
+
+    // Text for `rule`, which may or may not parse.
+    pub fn generate(&self, rule: &str, choices: &mut impl Choices) -> String {
+        let mut out = String::new();
+        self.rule(rule, false, 0, choices, &mut out);
+        out
+    }
+
+    // A module that parses, if `attempts` tries at making one find one.
+    pub fn program(&self, choices: &mut impl Choices, attempts: usize) -> Option<String> {
+        (0..attempts)
+            .map(|_| self.generate("module", choices))
+            .find(|program| ElpParser::parse(Rule::module, program).is_ok())
+    }
+
+    fn rule(
+        &self,
+        name: &str,
+        atomic: bool,
+        depth: usize,
+        choices: &mut impl Choices,
+        out: &mut String,
+    ) {
+        let vocabulary: &[&str] = match name {
+            "IDENT" => &NAMES,
+            "inner" => &WORDS,
+            _ => &[],
+        };
+        if !vocabulary.is_empty() {
+            separate(atomic, choices, out);
+            out.push_str(vocabulary[choices.choose(vocabulary.len())]);
+            return;
+        }
+
+        let Some((ty, expr)) = self.rules.get(name) else {
+            separate(atomic, choices, out);
+            out.push_str(builtin(name, choices));
+            return;
+        };
+        let atomic = match ty {
+            RuleType::Atomic | RuleType::CompoundAtomic => {
+                separate(atomic, choices, out);
+                true
+            }
+            RuleType::NonAtomic => false,
+            RuleType::Normal | RuleType::Silent => atomic,
+        };
+        self.expr(expr, atomic, depth + 1, choices, out);
+    }
+
+    fn expr(
+        &self,
+        expr: &OptimizedExpr,
+        atomic: bool,
+        depth: usize,
+        choices: &mut impl Choices,
+        out: &mut String,
+    ) {
+        let deep = depth > MAX_DEPTH;
+        match expr {
+            OptimizedExpr::Str(text) | OptimizedExpr::Insens(text) => {
+                separate(atomic, choices, out);
+                out.push_str(text);
+            }
+            OptimizedExpr::Range(start, _) => {
+                separate(atomic, choices, out);
+                out.push_str(start);
+            }
+            OptimizedExpr::Ident(name) => self.rule(name, atomic, depth, choices, out),
+            OptimizedExpr::Seq(first, second) => {
+                self.expr(first, atomic, depth, choices, out);
+                self.expr(second, atomic, depth, choices, out);
+            }
+            OptimizedExpr::Choice(..) => {
+                let alternatives = alternatives(expr);
+                let chosen = match deep {
+                    true => alternatives
+                        .iter()
+                        .min_by_key(|alternative| smallest(alternative, &self.sizes))
+                        .expect("a choice has alternatives"),
+                    false => alternatives[choices.choose(alternatives.len())],
+                };
+                self.expr(chosen, atomic, depth, choices, out);
+            }
+            OptimizedExpr::Opt(inner) if !deep && choices.choose(2) == 1 => {
+                self.expr(inner, atomic, depth, choices, out)
+            }
+            OptimizedExpr::Opt(_) => {}
+            OptimizedExpr::Rep(inner) => {
+                let times = match deep {
+                    true => 0,
+                    false => choices.choose(MAX_REPEAT + 1),
+                };
+                for _ in 0..times {
+                    self.expr(inner, atomic, depth, choices, out);
+                }
+            }
+            OptimizedExpr::Push(inner) | OptimizedExpr::RestoreOnErr(inner) => {
+                self.expr(inner, atomic, depth, choices, out)
+            }
+            // Lookaheads, and the stack operations elp.pest doesn't use.
+            _ => {}
+        }
+    }
+}
+
+// `a | b | c` is `Choice(a, Choice(b, c))`.
+fn alternatives(expr: &OptimizedExpr) -> Vec<&OptimizedExpr> {
+    match expr {
+        OptimizedExpr::Choice(first, rest) => {
+            let mut all = alternatives(first);
+            all.extend(alternatives(rest));
+            all
+        }
+        expr => vec![expr],
+    }
+}
+
+fn smallest(expr: &OptimizedExpr, sizes: &HashMap<String, usize>) -> usize {
+    match expr {
+        OptimizedExpr::Str(text) | OptimizedExpr::Insens(text) => text.len(),
+        OptimizedExpr::Range(..) => 1,
+        OptimizedExpr::Ident(name) => sizes.get(name).copied().unwrap_or(1),
+        OptimizedExpr::Seq(first, second) => {
+            smallest(first, sizes).saturating_add(smallest(second, sizes))
+        }
+        OptimizedExpr::Choice(first, second) => smallest(first, sizes).min(smallest(second, sizes)),
+        OptimizedExpr::Push(inner) | OptimizedExpr::RestoreOnErr(inner) => smallest(inner, sizes),
+        _ => 0,
+    }
+}
+
+fn separate(atomic: bool, choices: &mut impl Choices, out: &mut String) {
+    if !atomic && !out.is_empty() {
+        out.push_str(SEPARATORS[choices.choose(SEPARATORS.len())]);
+    }
+}
+
+fn builtin(name: &str, choices: &mut impl Choices) -> &'static str {
+    const DIGITS: [&str; 10] = ["0", "1", "2", "3", "4", "5", "6", "7", "8", "9"];
+    match name {
+        "SOI" | "EOI" => "",
+        "ASCII_DIGIT" | "ASCII_HEX_DIGIT" => DIGITS[choices.choose(DIGITS.len())],
+        "ASCII_NONZERO_DIGIT" => DIGITS[1 + choices.choose(DIGITS.len() - 1)],
+        "ASCII_ALPHA_UPPER" => "A",
+        "ASCII_ALPHA" | "ASCII_ALPHANUMERIC" | "ANY" => "a",
+        "NEWLINE" => "\n",
+        _ => panic!("elp.pest uses {}, which the generator doesn't know", name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn makes_the_same_program_from_a_seed() {
+        let generator = Generator::new();
+        let program = |seed| generator.program(&mut Seeded::new(seed), 50);
+
+        let first = program(7).expect("a program parses in 50 tries");
+        assert_eq!(program(7), Some(first.clone()));
+        assert!((0..20).any(|seed| program(seed) != Some(first.clone())));
+    }
+
+    #[test]
+    fn stops_when_the_input_runs_out() {
+        let generator = Generator::new();
+
+        assert_eq!(generator.generate("module", &mut Bytes(&[])), "");
+        assert_eq!(generator.generate("import_name", &mut Bytes(&[])), "value");
+    }
+}
@@ -0,0 +1,163 @@
+// What the fuzz targets in `crates/parser/fuzz` and the round trip property test share: running
+// source all the way through to the AST, and checking that formatting it doesn't change what it
+// means.
+//
+// Nothing source can contain should make the parser panic. The panics that are known about and
+// not fixed yet are listed in `KNOWN_BUGS` so they don't stop fuzzers finding new ones, anything
+// else carries on unwinding and is reported as a crash.
+
+pub mod generator;
+
+use std::{
+    any::Any,
+    panic::{self, AssertUnwindSafe},
+};
+
+use from_pest::FromPest;
+use pest::Parser;
+
+use crate::{
+    ast::{expression::ASTExpression, module::ASTModule, traits::FromCST},
+    cst::CSTModule,
+    formatter::format_source,
+    parser::{ElpParser, Rule},
+};
+
+// The other panics converting to the AST can't be reached from source. `ParsedNumber::from_string`
+// says why for its own, and the ones in `ast/component.rs` are only in its tests.
+//
+// A panic that's been found and is being tracked. `reproducer` panics with a message starting with
+// `panic`, a test keeps that true so the entry goes once the bug is fixed.
+#[derive(Debug, PartialEq)]
+pub struct KnownBug {
+    pub panic: &'static str,
+    pub description: &'static str,
+    pub reproducer: &'static str,
+}
+
+pub const KNOWN_BUGS: [KnownBug; 2] = [
+    KnownBug {
+        panic: "Invalid CST expression",
+        description: "the AST has no node for some expressions the CST does, matches, arrays, \
+                      blocks, bitwise operations and unary operators among them",
+        reproducer: "match value { 1 -> 2 }",
+    },
+    KnownBug {
+        panic: "Invalid integer",
+        description: "an integer literal that doesn't fit in an i64 panics rather than being \
+                      reported",
+        reproducer: "const big = 99999999999999999999",
+    },
+];
+
+#[derive(Debug, PartialEq)]
+pub enum Lowered {
+    // The AST, each top level expression as its pretty printed `Debug` with spans left out so
+    // the same code anywhere in a file looks the same.
+    Module(Vec<String>),
+    ParseError,
+    // It parses but the CST can't represent it yet.
+    Unsupported,
+    KnownBug(&'static KnownBug),
+}
+
+pub fn lower(source: &str) -> Lowered {
+    let lowered = panic::catch_unwind(AssertUnwindSafe(|| {
+        let Ok(mut pairs) = ElpParser::parse(Rule::module, source) else {
+            return Lowered::ParseError;
+        };
+        let Ok(cst) = CSTModule::from_pest(&mut pairs) else {
+            return Lowered::Unsupported;
+        };
+        let mut ast = ASTModule::from_cst(&cst);
+        ast.expressions.iter_mut().for_each(sort_import_names);
+        Lowered::Module(ast.expressions.iter().map(shape).collect())
+    }));
+    lowered.unwrap_or_else(|payload| match known_bug(&*payload) {
+        Some(bug) => Lowered::KnownBug(bug),
+        None => panic::resume_unwind(payload),
+    })
+}
+
+// Parses, formats and parses again, panicking if the formatted source doesn't mean the same as
+// `source` or formatting it again changes it.
+pub fn check_round_trip(source: &str) {
+    let before = lower(source);
+    if before == Lowered::ParseError {
+        return;
+    }
+    let formatted = format_source(source)
+        .unwrap_or_else(|err| panic!("formatting failed on source that parses: {}", err));
+    let after = lower(&formatted);
+
+    let equivalent = match (&before, &after) {
+        (Lowered::Module(before), Lowered::Module(after)) => {
+            canonical(before.clone()) == canonical(after.clone())
+        }
+        (before, after) => before == after,
+    };
+    if !equivalent {
+        panic!(
+            "formatting changed what this means\n--- source\n{}\n--- formatted\n{}\n--- before\n\
+             {:#?}\n--- after\n{:#?}",
+            source, formatted, before, after
+        );
+    }
+
+    let again = format_source(&formatted).expect("formatted source parses");
+    if again != formatted {
+        panic!(
+            "formatting isn't stable\n--- source\n{}\n--- formatted\n{}\n--- formatted again\n{}",
+            source, formatted, again
+        );
+    }
+}
+
+fn known_bug(payload: &(dyn Any + Send)) -> Option<&'static KnownBug> {
+    let message = payload
+        .downcast_ref::<String>()
+        .map(String::as_str)
+        .or_else(|| payload.downcast_ref::<&str>().copied())?;
+    KNOWN_BUGS.iter().find(|bug| message.starts_with(bug.panic))
+}
+
+fn shape(expression: &ASTExpression) -> String {
+    let debug = format!("{:#?}", expression);
+    let lines: Vec<&str> = debug.lines().collect();
+    let mut kept = vec![];
+    let mut index = 0;
+    while index < lines.len() {
+        // Pretty printed spans are `Span {`, `str: ..,`, `range: ..,` and `}` on lines of their
+        // own.
+        if lines[index].ends_with("Span {") {
+            index += 4;
+            continue;
+        }
+        kept.push(lines[index]);
+        index += 1;
+    }
+    kept.join("\n")
+}
+
+// The formatter sorts the names an import brings in, and imports that are next to each other,
+// neither of which changes what they mean.
+fn sort_import_names(expression: &mut ASTExpression) {
+    match expression {
+        ASTExpression::Import(import) => import.names.sort(),
+        ASTExpression::Export(export) => sort_import_names(&mut export.value),
+        _ => {}
+    }
+}
+
+// Runs of imports in order, as the formatter leaves them.
+fn canonical(mut expressions: Vec<String>) -> Vec<String> {
+    let mut start = 0;
+    while start < expressions.len() {
+        let end = (start..expressions.len())
+            .find(|&index| !expressions[index].starts_with("Import("))
+            .unwrap_or(expressions.len());
+        expressions[start..end].sort();
+        start = end + 1;
+    }
+    expressions
+}
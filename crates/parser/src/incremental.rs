@@ -0,0 +1,389 @@
+// Reparsing after an edit without starting over. Editors send a change on every keystroke and
+// parsing the whole of a big `.velp` screen each time doesn't keep up, but a keystroke hardly ever
+// changes more than the item it's in.
+//
+// A module is a run of top level items with only whitespace and comments between them, and pest
+// parses each one from where it starts without looking back. So the items before an edit parse the
+// same as they did, and once reparsing reaches an item that starts where an old one did, past the
+// edit, everything from there on does too. Those items are shared with the old tree rather than
+// copied, their ranges are relative to where they start so moving them costs nothing.
+//
+// The tree is owned rather than borrowing the source like the CST does, that's what lets it outlive
+// the text it was parsed from. `pairs` turns it back into what pest would have given, so the CST
+// and everything after it are built from a stored tree without parsing again.
+
+use std::{ops::Range, sync::Arc};
+
+use pest::{
+    error::Error,
+    iterators::{Pair, Pairs, PairsBuilder},
+    Parser,
+};
+
+use crate::parser::{ElpParser, Rule};
+
+pub type ParseResult = Result<SyntaxTree, Box<Error<Rule>>>;
+
+// One rule of the parse tree, with its range relative to the start of the item it's part of.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SyntaxNode {
+    pub rule: Rule,
+    pub range: Range<usize>,
+    pub children: Vec<SyntaxNode>,
+}
+
+impl SyntaxNode {
+    fn from_pair(pair: Pair<Rule>, start: usize) -> Self {
+        let span = pair.as_span();
+        Self {
+            rule: pair.as_rule(),
+            range: span.start() - start..span.end() - start,
+            children: pair
+                .into_inner()
+                .map(|child| SyntaxNode::from_pair(child, start))
+                .collect(),
+        }
+    }
+}
+
+// A top level item and where it starts in the source.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SyntaxItem {
+    pub start: usize,
+    pub node: Arc<SyntaxNode>,
+}
+
+impl SyntaxItem {
+    pub fn range(&self) -> Range<usize> {
+        self.start + self.node.range.start..self.start + self.node.range.end
+    }
+}
+
+// `range` of the old source, in bytes, replaced with `text`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TextEdit {
+    pub range: Range<usize>,
+    pub text: String,
+}
+
+impl TextEdit {
+    // The smallest edit turning `old` into `new`, for clients that only ever send whole documents.
+    pub fn between(old: &str, new: &str) -> Self {
+        let prefix = common_len(old.chars(), new.chars());
+        let suffix = common_len(old[prefix..].chars().rev(), new[prefix..].chars().rev());
+        Self {
+            range: prefix..old.len() - suffix,
+            text: new[prefix..new.len() - suffix].into(),
+        }
+    }
+}
+
+// How many bytes the two runs of characters have in common at their start.
+fn common_len(old: impl Iterator<Item = char>, new: impl Iterator<Item = char>) -> usize {
+    old.zip(new)
+        .take_while(|(a, b)| a == b)
+        .map(|(c, _)| c.len_utf8())
+        .sum()
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SyntaxTree {
+    source: String,
+    items: Vec<SyntaxItem>,
+}
+
+impl SyntaxTree {
+    pub fn parse(source: impl Into<String>) -> ParseResult {
+        let source = source.into();
+        let module = ElpParser::parse(Rule::module, &source)?
+            .next()
+            .expect("a module always has a pair");
+        let items = module
+            .into_inner()
+            .filter(|pair| pair.as_rule() != Rule::EOI)
+            .map(|pair| {
+                let start = pair.as_span().start();
+                SyntaxItem {
+                    start,
+                    node: Arc::new(SyntaxNode::from_pair(pair, start)),
+                }
+            })
+            .collect();
+
+        Ok(Self { source, items })
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn items(&self) -> &[SyntaxItem] {
+        &self.items
+    }
+
+    // The `module` pair parsing the whole source would give, items and all.
+    pub fn pairs(&self) -> Pairs<'_, Rule> {
+        let len = self.source.len();
+        PairsBuilder::new(&self.source)
+            .rule_with(Rule::module, 0, len, |inner| {
+                self.items
+                    .iter()
+                    .fold(inner, |builder, item| {
+                        push_node(builder, &item.node, item.start)
+                    })
+                    .rule(Rule::EOI, len, len)
+            })
+            .build()
+    }
+
+    // The tree for the source with `edit` made to it, the same as parsing that from scratch.
+    // Trees are only made from source that parses, so after an edit that breaks it the caller
+    // has to parse the whole of the next version again.
+    pub fn edit(&self, edit: &TextEdit) -> ParseResult {
+        let mut source = self.source.clone();
+        source.replace_range(edit.range.clone(), &edit.text);
+        let edit_end = edit.range.start + edit.text.len();
+
+        // An item can look past its own end while it's parsed, whether something more follows
+        // it, so the one before the edit might parse differently too and is done again as well.
+        let first = self
+            .items
+            .iter()
+            .rposition(|item| item.start < edit.range.start)
+            .map_or(0, |item| item.saturating_sub(1));
+        let mut items = self.items[..first].to_vec();
+        let mut offset = match self.items.get(first) {
+            Some(item) if first > 0 => item.start,
+            _ => skip_trivia(&source, 0),
+        };
+
+        loop {
+            if offset >= edit_end {
+                let old = offset + edit.range.end - edit_end;
+                if let Ok(reused) = self.items.binary_search_by_key(&old, |item| item.start) {
+                    items.extend(self.items[reused..].iter().map(|item| SyntaxItem {
+                        start: item.start + edit_end - edit.range.end,
+                        node: item.node.clone(),
+                    }));
+                    return Ok(Self { source, items });
+                }
+            }
+            if offset == source.len() {
+                return Ok(Self { source, items });
+            }
+
+            // Whatever went wrong, the error has to be the one parsing everything would give.
+            let Some(node) = parse_item(&source[offset..]).filter(|node| !node.range.is_empty())
+            else {
+                return Self::parse(source);
+            };
+            let end = offset + node.range.end;
+            items.push(SyntaxItem {
+                start: offset,
+                node: Arc::new(node),
+            });
+            offset = skip_trivia(&source, end);
+        }
+    }
+}
+
+// `node` and everything in it, its ranges moved from being relative to `start`.
+fn push_node<'i>(
+    builder: PairsBuilder<'i, Rule>,
+    node: &SyntaxNode,
+    start: usize,
+) -> PairsBuilder<'i, Rule> {
+    let range = start + node.range.start..start + node.range.end;
+    builder.rule_with(node.rule, range.start, range.end, |inner| {
+        node.children
+            .iter()
+            .fold(inner, |builder, child| push_node(builder, child, start))
+    })
+}
+
+// A top level item from the start of `source`, tried the same way `expressions` in the grammar
+// does.
+fn parse_item(source: &str) -> Option<SyntaxNode> {
+    [Rule::expression, Rule::parenthesised_expression]
+        .into_iter()
+        .find_map(|rule| ElpParser::parse(rule, source).ok())
+        .and_then(|mut pairs| pairs.next())
+        .map(|pair| SyntaxNode::from_pair(pair, 0))
+}
+
+// Past the whitespace and comments from `offset`, as pest skips them between items.
+fn skip_trivia(source: &str, mut offset: usize) -> usize {
+    loop {
+        let rest = &source[offset..];
+        if rest.starts_with("//") {
+            offset += rest.find('\n').unwrap_or(rest.len());
+        } else if rest.starts_with([' ', '\t', '\r', '\n']) {
+            offset += 1;
+        } else {
+            return offset;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const SOURCE: &str = "import { println } from \"elp/stdio\"
+
+// Which way edges go.
+export enum Direction {
+\t.Directed,
+\t.Undirected,
+}
+
+export object Point {
+\t.x int,
+\t.y int,
+}
+
+fn Point.flip(self) -> Point {
+\treturn self
+}
+
+const origin = 0
+
+fn main {
+\tconst name = \"elp\"
+\tprintln(name)
+}
+";
+
+    fn edit(source: &str, range: Range<usize>, text: &str) -> TextEdit {
+        assert!(source.is_char_boundary(range.start) && source.is_char_boundary(range.end));
+        TextEdit {
+            range,
+            text: text.into(),
+        }
+    }
+
+    #[test]
+    fn only_reparses_the_items_around_an_edit() {
+        let tree = SyntaxTree::parse(SOURCE).unwrap();
+        let at = SOURCE.find("\"elp\"").unwrap();
+        let edited = tree
+            .edit(&edit(SOURCE, at..at + 5, "\"elp, again\""))
+            .unwrap();
+
+        assert_eq!(
+            edited,
+            SyntaxTree::parse(edited.source().to_string()).unwrap()
+        );
+        let reused: Vec<bool> = tree
+            .items()
+            .iter()
+            .zip(edited.items())
+            .map(|(old, new)| Arc::ptr_eq(&old.node, &new.node))
+            .collect();
+        assert_eq!(reused, [true, true, true, true, false, false]);
+
+        // The items after an edit are shared, only moved.
+        let at = SOURCE.find(".Directed").unwrap();
+        let edited = tree.edit(&edit(SOURCE, at..at + 9, ".Both")).unwrap();
+        assert!(Arc::ptr_eq(&tree.items()[5].node, &edited.items()[5].node));
+        assert_eq!(edited.items()[5].start + 4, tree.items()[5].start);
+    }
+
+    #[test]
+    fn matches_a_full_parse_after_any_edit() {
+        let inserts = [
+            "x",
+            ".",
+            "(",
+            ")",
+            "{",
+            "}",
+            "\n",
+            "// c\n",
+            "\"",
+            "fn",
+            "é",
+            "const a = 1\n",
+        ];
+        let tree = SyntaxTree::parse(SOURCE).unwrap();
+        let boundaries: Vec<usize> = (0..=SOURCE.len())
+            .filter(|&offset| SOURCE.is_char_boundary(offset))
+            .collect();
+
+        // Every insertion at every position, and deleting or retyping a few lengths from there.
+        for (position, &start) in boundaries.iter().enumerate() {
+            let end = |len: usize| boundaries[(position + len).min(boundaries.len() - 1)];
+            let edits = inserts.iter().map(|&text| (start, text)).chain(
+                [1, 3, 40]
+                    .into_iter()
+                    .flat_map(|len| [(end(len), ""), (end(len), "x")]),
+            );
+            for (end, text) in edits {
+                let edit = edit(SOURCE, start..end, text);
+                let mut expected = SOURCE.to_string();
+                expected.replace_range(start..end, text);
+
+                assert_eq!(
+                    tree.edit(&edit),
+                    SyntaxTree::parse(expected),
+                    "replacing {:?} with {:?}",
+                    &SOURCE[start..end],
+                    text
+                );
+            }
+        }
+    }
+
+    // Every pair in order, with its range and how many pairs are directly inside it.
+    fn shape(pairs: Pairs<Rule>) -> Vec<(Rule, Range<usize>, usize)> {
+        pairs
+            .flatten()
+            .map(|pair| {
+                let span = pair.as_span();
+                (
+                    pair.as_rule(),
+                    span.start()..span.end(),
+                    pair.into_inner().count(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn gives_the_pairs_pest_would() {
+        for source in [SOURCE, "", "// nothing\n"] {
+            assert_eq!(
+                shape(SyntaxTree::parse(source).unwrap().pairs()),
+                shape(ElpParser::parse(Rule::module, source).unwrap()),
+                "{:?}",
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn edits_between_documents() {
+        assert_eq!(
+            TextEdit::between("const a = 1", "const abc = 1"),
+            TextEdit {
+                range: 7..7,
+                text: "bc".into()
+            }
+        );
+        assert_eq!(
+            TextEdit::between("aéb", "ab"),
+            TextEdit {
+                range: 1..3,
+                text: "".into()
+            }
+        );
+        assert_eq!(
+            TextEdit::between("aaa", "aa"),
+            TextEdit {
+                range: 2..3,
+                text: "".into()
+            }
+        );
+    }
+}
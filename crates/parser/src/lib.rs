@@ -1,3 +1,8 @@
-pub(crate) mod ast;
-pub(crate) mod cst;
-pub(crate) mod parser;
+pub mod ast;
+pub mod cst;
+pub mod formatter;
+#[cfg(feature = "fuzzing")]
+pub mod fuzzing;
+pub mod incremental;
+pub mod parser;
+pub mod precomp;
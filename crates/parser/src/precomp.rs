@@ -0,0 +1,289 @@
+// Precompilation directives decide whether the expression after them is compiled at all.
+// `#if(iOS)` keeps it when building for iOS and `#else` keeps the one after it when the `#if`
+// before didn't. This runs over the AST before it's lowered, so whatever's left out is never
+// checked or seen by a backend. Without a target to build for every branch is kept, that's how
+// `elp check` and the language server see the whole file.
+
+use crate::ast::{expression::ASTExpression, precomp::ASTPrecomp};
+
+// What `.targets` in `Package.elp` can name.
+pub const TARGETS: [&str; 7] = [
+    "iOS",
+    "Android",
+    "Web",
+    "Windows",
+    "MacOS",
+    "Linux",
+    "SharedLibrary",
+];
+
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Targets {
+    // `.targets` from `Package.elp`, empty for files that aren't in a package.
+    pub declared: Vec<String>,
+    // The one being built, None keeps every branch.
+    pub active: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PrecompDiagnostic {
+    pub severity: Severity,
+    // Byte offsets of the directive.
+    pub start: usize,
+    pub end: usize,
+    pub message: String,
+}
+
+// Takes every directive out of `expressions`, and the expressions they leave out with them,
+// including inside function and component bodies.
+pub fn preprocess(
+    expressions: &mut Vec<ASTExpression>,
+    targets: &Targets,
+) -> Vec<PrecompDiagnostic> {
+    let mut preprocessor = Preprocessor {
+        targets,
+        diagnostics: vec![],
+    };
+    preprocessor.expressions(expressions);
+    preprocessor.diagnostics
+}
+
+struct Preprocessor<'t> {
+    targets: &'t Targets,
+    diagnostics: Vec<PrecompDiagnostic>,
+}
+
+impl Preprocessor<'_> {
+    fn report(&mut self, severity: Severity, directive: &ASTPrecomp, message: String) {
+        self.diagnostics.push(PrecompDiagnostic {
+            severity,
+            start: directive.span.start(),
+            end: directive.span.end(),
+            message,
+        });
+    }
+
+    fn expressions(&mut self, expressions: &mut Vec<ASTExpression>) {
+        let mut kept = vec![];
+        // The directive waiting for its expression and whether that expression is kept.
+        let mut pending: Option<(Box<ASTPrecomp>, bool)> = None;
+        // Whether the expression after the last `#if` was kept, for an `#else` after it.
+        let mut last_if = None;
+
+        for mut expression in std::mem::take(expressions) {
+            let ASTExpression::Precomp(directive) = expression else {
+                let keep = match pending.take() {
+                    Some((directive, keep)) => {
+                        last_if = (directive.name == "if").then_some(keep);
+                        keep
+                    }
+                    None => {
+                        last_if = None;
+                        true
+                    }
+                };
+                if keep {
+                    self.nested(&mut expression);
+                    kept.push(expression);
+                }
+                continue;
+            };
+
+            if let Some((previous, _)) = pending.take() {
+                self.unfollowed(&previous);
+            }
+            let keep = match directive.name.as_str() {
+                "if" => self.condition(&directive),
+                "else" => match last_if.take() {
+                    Some(kept) => self.targets.active.is_none() || !kept,
+                    None => {
+                        self.report(
+                            Severity::Error,
+                            &directive,
+                            "`#else` has to come after an `#if` and what it's for".into(),
+                        );
+                        true
+                    }
+                },
+                other => {
+                    let message = format!("there's no precompilation directive `#{}`", other);
+                    self.report(Severity::Error, &directive, message);
+                    true
+                }
+            };
+            pending = Some((directive, keep));
+        }
+
+        if let Some((directive, _)) = pending {
+            self.unfollowed(&directive);
+        }
+        *expressions = kept;
+    }
+
+    fn unfollowed(&mut self, directive: &ASTPrecomp) {
+        let message = format!("`#{}` has to come before what it's for", directive.name);
+        self.report(Severity::Error, directive, message);
+    }
+
+    // Whether an `#if` keeps its expression. Branches for a target the package isn't built for
+    // are never compiled, they're reported and left out even when every branch is kept.
+    fn condition(&mut self, directive: &ASTPrecomp) -> bool {
+        let Some(target) = &directive.argument else {
+            let message = "`#if` needs a target, `#if(Web)`".into();
+            self.report(Severity::Error, directive, message);
+            return true;
+        };
+        let target = target.name.as_str();
+        if !TARGETS.contains(&target) {
+            let message = format!(
+                "there's no target `{}`, it can be one of {}",
+                target,
+                TARGETS.join(", ")
+            );
+            self.report(Severity::Error, directive, message);
+            return self.targets.active.is_none();
+        }
+        if !self.targets.declared.is_empty()
+            && !self
+                .targets
+                .declared
+                .iter()
+                .any(|declared| declared == target)
+        {
+            let message = format!(
+                "`#if({})` is never true, `{}` isn't one of the package's targets",
+                target, target
+            );
+            self.report(Severity::Warning, directive, message);
+            return false;
+        }
+
+        self.targets
+            .active
+            .as_ref()
+            .is_none_or(|active| active == target)
+    }
+
+    fn nested(&mut self, expression: &mut ASTExpression) {
+        match expression {
+            ASTExpression::Export(export) => self.nested(&mut export.value),
+            ASTExpression::FunctionDef(function) => {
+                self.expressions(&mut function.block.expressions)
+            }
+            ASTExpression::Component(component) => {
+                self.expressions(&mut component.block.expressions)
+            }
+            ASTExpression::Block(block) => self.expressions(&mut block.expressions),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ast::{module::ASTModule, traits::FromCST},
+        cst::CSTModule,
+        parser::{ElpParser, Rule},
+    };
+    use from_pest::FromPest;
+    use pest::Parser;
+    use pretty_assertions::assert_eq;
+
+    const SOURCE: &str = "#if(iOS)
+fn haptics() {
+	vibrate()
+}
+#else
+fn haptics() {}
+
+fn main {
+	#if(Web)
+	log()
+	#if(Windows)
+	beep()
+}
+";
+
+    fn targets(declared: &[&str], active: Option<&str>) -> Targets {
+        Targets {
+            declared: declared.iter().map(|target| target.to_string()).collect(),
+            active: active.map(String::from),
+        }
+    }
+
+    // The statement count of each function left, and what was reported.
+    fn preprocessed(source: &str, targets: &Targets) -> (Vec<usize>, Vec<(Severity, String)>) {
+        let mut pairs = ElpParser::parse(Rule::module, source).unwrap();
+        let cst = CSTModule::from_pest(&mut pairs).unwrap();
+        let mut ast = ASTModule::from_cst(&cst);
+        let diagnostics = preprocess(&mut ast.expressions, targets);
+
+        let functions = ast
+            .expressions
+            .iter()
+            .map(|expression| match expression {
+                ASTExpression::FunctionDef(function) => function.block.expressions.len(),
+                other => panic!("expected a function, got {:?}", other),
+            })
+            .collect();
+        let diagnostics = diagnostics
+            .into_iter()
+            .map(|diagnostic| (diagnostic.severity, diagnostic.message))
+            .collect();
+        (functions, diagnostics)
+    }
+
+    #[test]
+    fn keeps_the_branches_for_the_active_target() {
+        let declared = ["iOS", "Web"];
+
+        assert_eq!(
+            preprocessed(SOURCE, &targets(&declared, Some("iOS"))).0,
+            vec![1, 0]
+        );
+        assert_eq!(
+            preprocessed(SOURCE, &targets(&declared, Some("Web"))).0,
+            vec![0, 1]
+        );
+        // Everything is kept to be checked, apart from what could never be built.
+        let (functions, diagnostics) = preprocessed(SOURCE, &targets(&declared, None));
+        assert_eq!(functions, vec![1, 0, 1]);
+        assert_eq!(
+            diagnostics,
+            vec![(
+                Severity::Warning,
+                "`#if(Windows)` is never true, `Windows` isn't one of the package's targets".into()
+            )]
+        );
+    }
+
+    #[test]
+    fn misplaced_and_unknown_directives() {
+        let (_, diagnostics) = preprocessed(
+            "#else\nfn a() {}\n#ifdef(Web)\nfn b() {}\n#if(Playstation)\nfn c() {}\n#if\n",
+            &Targets::default(),
+        );
+
+        assert_eq!(
+            diagnostics
+                .into_iter()
+                .map(|(_, message)| message)
+                .collect::<Vec<_>>(),
+            vec![
+                "`#else` has to come after an `#if` and what it's for",
+                "there's no precompilation directive `#ifdef`",
+                "there's no target `Playstation`, it can be one of iOS, Android, Web, Windows, MacOS, Linux, SharedLibrary",
+                "`#if` needs a target, `#if(Web)`",
+                "`#if` has to come before what it's for",
+            ]
+        );
+    }
+}
@@ -0,0 +1,77 @@
+// Property tests over programs made up from the grammar: whatever parses lowers without a panic
+// that isn't already known about, and formatting it and parsing it again means the same thing.
+//
+// A failure prints the seed, `ELP_SEED=<seed>` runs just that program. `ELP_PROGRAMS` runs more
+// programs than the default when looking for bugs.
+
+use std::{env, panic};
+
+use elp_parser::fuzzing::{
+    check_round_trip,
+    generator::{Generator, Seeded},
+    lower, Lowered, KNOWN_BUGS,
+};
+
+const PROGRAMS: u64 = 300;
+
+fn seeds() -> Vec<u64> {
+    let number = |name: &str| env::var(name).ok().and_then(|value| value.parse().ok());
+    match number("ELP_SEED") {
+        Some(seed) => vec![seed],
+        None => (0..number("ELP_PROGRAMS").unwrap_or(PROGRAMS)).collect(),
+    }
+}
+
+#[test]
+fn formatting_generated_programs_keeps_their_meaning() {
+    let generator = Generator::new();
+    let mut lowered = 0;
+    let mut failures = vec![];
+
+    // Known bugs panic on the way to being caught, which would bury the report.
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    for seed in seeds() {
+        let Some(program) = generator.program(&mut Seeded::new(seed), 100) else {
+            continue;
+        };
+        if matches!(lower(&program), Lowered::Module(_)) {
+            lowered += 1;
+        }
+        if let Err(err) = panic::catch_unwind(|| check_round_trip(&program)) {
+            let message = err
+                .downcast_ref::<String>()
+                .cloned()
+                .or_else(|| {
+                    err.downcast_ref::<&str>()
+                        .map(|message| message.to_string())
+                })
+                .unwrap_or_default();
+            failures.push(format!("seed {}: {}", seed, message));
+        }
+    }
+    panic::set_hook(hook);
+
+    assert!(failures.is_empty(), "{}", failures.join("\n\n"));
+    assert!(
+        lowered > 0 || env::var_os("ELP_SEED").is_some(),
+        "none of the generated programs made it to the AST"
+    );
+}
+
+#[test]
+fn known_bugs_still_reproduce() {
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let lowered: Vec<Lowered> = KNOWN_BUGS.iter().map(|bug| lower(bug.reproducer)).collect();
+    panic::set_hook(hook);
+
+    for (bug, lowered) in KNOWN_BUGS.iter().zip(lowered) {
+        assert_eq!(
+            lowered,
+            Lowered::KnownBug(bug),
+            "{} is fixed, take it out of KNOWN_BUGS",
+            bug.description
+        );
+    }
+}
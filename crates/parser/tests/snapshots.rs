@@ -0,0 +1,269 @@
+// Golden file tests for the parser. Every `.elp`, `.velp` and `.selp` file under `examples/` and
+// `tests/fixtures/` is parsed, and what the CST and AST make of it along with any diagnostics is
+// compared with a snapshot checked in under `tests/snapshots/`. Spans are shown as `start..end`
+// rather than repeating the text they cover, which keeps the dumps readable.
+//
+// When the grammar changes on purpose, look at what changed and update the snapshots with
+//
+//     ELP_BLESS=1 cargo test -p elp_parser --test snapshots
+//
+// which also writes snapshots for new files and deletes those for files that are gone.
+
+use std::{
+    fs, panic,
+    path::{Path, PathBuf},
+};
+
+use elp_parser::{
+    ast::{module::ASTModule, style::ASTStyleSheet, traits::FromCST},
+    cst::{self, style::CSTStyleSheetFile, CSTModule},
+    parser::{ElpParser, Rule},
+};
+use from_pest::FromPest;
+use pest::{error::InputLocation, Parser};
+
+const EXTENSIONS: [&str; 3] = ["elp", "velp", "selp"];
+
+fn root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("../..")
+}
+
+fn files(directory: &Path, extensions: &[&str], found: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(directory) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            files(&path, extensions, found);
+        } else if path
+            .extension()
+            .is_some_and(|extension| extensions.iter().any(|e| extension == *e))
+        {
+            found.push(path);
+        }
+    }
+}
+
+// `examples/Graph/lib/dag.elp` is `tests/snapshots/examples/Graph/lib/dag.elp.snap`, fixtures
+// drop the `tests/` they start with.
+fn snapshot_path(relative: &Path) -> PathBuf {
+    let relative = relative.strip_prefix("tests").unwrap_or(relative);
+    let mut name = relative.as_os_str().to_owned();
+    name.push(".snap");
+    root().join("tests/snapshots").join(name)
+}
+
+fn snapshot(relative: &Path, source: &str) -> String {
+    let mut out = format!("// {}\n", relative.display());
+    let mut diagnostics = vec![];
+
+    // Stylesheets have a grammar of their own.
+    if relative
+        .extension()
+        .is_some_and(|extension| extension == "selp")
+    {
+        let sheet = ElpParser::parse(Rule::stylesheet_file, source)
+            .map_err(|err| diagnostics.push(parse_error(err)))
+            .ok()
+            .and_then(|mut pairs| CSTStyleSheetFile::from_pest(&mut pairs).ok());
+        out.push_str("\n== cst ==\n");
+        match &sheet {
+            Some(sheet) => {
+                out.push_str(&readable(&format!("{:#?}", sheet)));
+                out.push_str("\n\n== ast ==\n");
+                out.push_str(&readable(&format!("{:#?}", ASTStyleSheet::from_cst(sheet))));
+            }
+            None => out.push_str("none\n\n== ast ==\nnone"),
+        }
+        return finish(out, source, diagnostics);
+    }
+
+    let cst = match ElpParser::parse(Rule::module, source) {
+        Err(err) => {
+            diagnostics.push(parse_error(err));
+            None
+        }
+        Ok(mut pairs) => match CSTModule::from_pest(&mut pairs) {
+            Ok(cst) => Some(cst),
+            Err(_) => {
+                let (construct, offset) =
+                    cst::unsupported(source).unwrap_or_else(|| ("this file's syntax".into(), 0));
+                diagnostics.push((offset, format!("{} isn't supported yet", construct)));
+                None
+            }
+        },
+    };
+
+    out.push_str("\n== cst ==\n");
+    match &cst {
+        Some(cst) => out.push_str(&readable(&format!("{:#?}", cst))),
+        None => out.push_str("none"),
+    }
+
+    out.push_str("\n\n== ast ==\n");
+    let ast = cst.as_ref().and_then(|cst| {
+        panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            readable(&format!("{:#?}", ASTModule::from_cst(cst)))
+        }))
+        .map_err(|err| {
+            let message = err
+                .downcast_ref::<String>()
+                .cloned()
+                .or_else(|| {
+                    err.downcast_ref::<&str>()
+                        .map(|message| message.to_string())
+                })
+                .unwrap_or_default();
+            diagnostics.push((0, format!("the AST can't represent this yet: {}", message)));
+        })
+        .ok()
+    });
+    out.push_str(ast.as_deref().unwrap_or("none"));
+    finish(out, source, diagnostics)
+}
+
+fn parse_error(err: pest::error::Error<Rule>) -> (usize, String) {
+    let offset = match err.location {
+        InputLocation::Pos(offset) => offset,
+        InputLocation::Span((start, _)) => start,
+    };
+    (
+        offset,
+        format!("couldn't parse this, {}", err.variant.message()),
+    )
+}
+
+// Adds the diagnostics, each at a line and column.
+fn finish(mut out: String, source: &str, diagnostics: Vec<(usize, String)>) -> String {
+    out.push_str("\n\n== diagnostics ==\n");
+    if diagnostics.is_empty() {
+        out.push_str("none\n");
+    }
+    for (offset, message) in diagnostics {
+        let line = source[..offset].matches('\n').count() + 1;
+        let column = source[..offset]
+            .rfind('\n')
+            .map_or(offset, |newline| offset - newline - 1)
+            + 1;
+        out.push_str(&format!("{}:{}: {}\n", line, column, message));
+    }
+    out
+}
+
+// Pretty printed spans repeat all of the text they cover, which for a module is the whole file.
+// Each one is folded down to `start..end`.
+fn readable(debug: &str) -> String {
+    let lines: Vec<&str> = debug.lines().collect();
+    let mut out = vec![];
+    let mut index = 0;
+    while index < lines.len() {
+        let line = lines[index];
+        let folded = line.strip_suffix("Span {").and_then(|prefix| {
+            let text = lines.get(index + 1)?.trim_start().strip_prefix("str: ");
+            let range = lines
+                .get(index + 2)?
+                .trim()
+                .strip_prefix("range: ")?
+                .strip_suffix(',')?;
+            let close = lines.get(index + 3)?.trim();
+            (text.is_some() && close.starts_with('}'))
+                .then(|| format!("{}{}{}", prefix, range, &close[1..]))
+        });
+        match folded {
+            Some(folded) => {
+                out.push(folded);
+                index += 4;
+            }
+            None => {
+                out.push(line.to_string());
+                index += 1;
+            }
+        }
+    }
+    out.join("\n")
+}
+
+#[test]
+fn parser_output_matches_the_snapshots() {
+    let bless = std::env::var_os("ELP_BLESS").is_some();
+    let root = root();
+    let mut sources = vec![];
+    files(&root.join("examples"), &EXTENSIONS, &mut sources);
+    files(&root.join("tests/fixtures"), &EXTENSIONS, &mut sources);
+    sources.sort();
+    assert!(!sources.is_empty(), "there's nothing to snapshot");
+
+    // The AST panics on some of what it doesn't handle yet, that's recorded rather than printed.
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let snapshots: Vec<(PathBuf, String)> = sources
+        .iter()
+        .map(|path| {
+            let relative = path.strip_prefix(&root).unwrap();
+            let source = fs::read_to_string(path).unwrap();
+            (snapshot_path(relative), snapshot(relative, &source))
+        })
+        .collect();
+    panic::set_hook(hook);
+
+    let mut failures = vec![];
+    for (path, actual) in &snapshots {
+        let expected = fs::read_to_string(path).ok();
+        if expected.as_deref() == Some(actual.as_str()) {
+            continue;
+        }
+        if bless {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, actual).unwrap();
+            continue;
+        }
+        failures.push(match expected {
+            None => format!("{} is missing", path.display()),
+            Some(expected) => difference(path, &expected, actual),
+        });
+    }
+
+    let mut stale = vec![];
+    files(&root.join("tests/snapshots"), &["snap"], &mut stale);
+    for path in stale {
+        if snapshots.iter().any(|(snapshot, _)| *snapshot == path) {
+            continue;
+        }
+        match bless {
+            true => fs::remove_file(&path).unwrap(),
+            false => failures.push(format!("{} has no source any more", path.display())),
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "{}\n\n{} snapshots don't match, run with ELP_BLESS=1 to update them",
+        failures.join("\n\n"),
+        failures.len()
+    );
+}
+
+// Where the snapshot first differs, with a few lines either side.
+fn difference(path: &Path, expected: &str, actual: &str) -> String {
+    let expected: Vec<&str> = expected.lines().collect();
+    let actual: Vec<&str> = actual.lines().collect();
+    let first = expected
+        .iter()
+        .zip(&actual)
+        .position(|(expected, actual)| expected != actual)
+        .unwrap_or(expected.len().min(actual.len()));
+    let context = first.saturating_sub(3);
+
+    let mut out = format!("{} differs from line {}:", path.display(), first + 1);
+    for line in &expected[context..first] {
+        out.push_str(&format!("\n  {}", line));
+    }
+    for line in expected.iter().skip(first).take(4) {
+        out.push_str(&format!("\n- {}", line));
+    }
+    for line in actual.iter().skip(first).take(4) {
+        out.push_str(&format!("\n+ {}", line));
+    }
+    out
+}